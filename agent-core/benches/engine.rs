@@ -0,0 +1,47 @@
+use agent_core::config::Config;
+use agent_core::rules_engine::bench::{self, Workload};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use std::time::Duration;
+
+// Engine throughput over synthetic workloads (see `rules_engine::bench`). Each iteration feeds the
+// whole workload to a fresh engine in agent-sized batches. `--bench-engine` covers the larger
+// sizes and reports memory; these are for comparing changes quickly.
+
+const BATCH: usize = 500;
+
+fn process(c: &mut Criterion) {
+  let cfg = Config::default();
+  let mut group = c.benchmark_group("engine_process");
+  group.measurement_time(Duration::from_secs(10));
+
+  for events in [10_000, 100_000] {
+    if events > 10_000 {
+      group.sample_size(10);
+    }
+    for pids in [16, 1_024, 65_536] {
+      let workload = Workload::new(events, pids);
+      let batches: Vec<_> = workload.batches(&cfg, BATCH).collect();
+      group.throughput(Throughput::Elements(events as u64));
+      group.bench_with_input(
+        BenchmarkId::new(format!("{events}_events"), format!("{pids}_pids")),
+        &batches,
+        |b, batches| {
+          b.iter_batched(
+            bench::engine,
+            |mut engine| {
+              for batch in batches {
+                engine.process(&cfg, batch).unwrap();
+                engine.drain_observations();
+              }
+            },
+            BatchSize::LargeInput,
+          )
+        },
+      );
+    }
+  }
+  group.finish();
+}
+
+criterion_group!(benches, process);
+criterion_main!(benches);
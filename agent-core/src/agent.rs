@@ -1,20 +1,43 @@
+use crate::baseline;
+use crate::browser_profiles;
 use crate::config::Config;
 use crate::event_collector;
+use crate::heartbeat;
+use crate::ipc;
 use crate::kill_switch;
+use crate::metrics;
 use crate::paths;
+use crate::recording;
 use crate::response_engine;
 use crate::rules_engine;
+use crate::runtime;
+use crate::scan_ipc;
+use crate::scan_schedule;
+use crate::self_protection;
+use crate::status_api;
 use crate::threat_feed;
+use crate::watch;
+use crate::watchdog::Liveness;
 use std::sync::mpsc;
 use std::time::Duration;
 
 pub struct Agent {
   cfg: Config,
+  liveness: Liveness,
 }
 
 impl Agent {
   pub fn new(cfg: Config) -> Self {
-    Self { cfg }
+    Self {
+      cfg,
+      liveness: Liveness::default(),
+    }
+  }
+
+  /// Stamped once per loop tick, for the `watchdog`.
+  pub fn with_liveness(mut self, liveness: Liveness) -> Self {
+    self.liveness = liveness;
+    self
   }
 
   pub fn run(&self, stop_rx: mpsc::Receiver<()>, tick: Duration) -> anyhow::Result<()> {
@@ -33,29 +56,95 @@ impl Agent {
     );
 
     let base = paths::base_dir()?;
+    browser_profiles::refresh();
     let mut refresh_scheduler = threat_feed::AutoRefreshScheduler::new(&cfg, &base);
 
+    let mut heartbeat = heartbeat::HeartbeatWriter::new(&base, cfg.mode);
+    let mut metrics_file = metrics::MetricsFile::new(&cfg.metrics, &base);
     let mut engine = rules_engine::Engine::new();
+    let feed = threat_feed::load_current_at(&base);
+    heartbeat.set_feed(feed.as_ref());
+    engine.set_threat_feed(feed);
+    let mut engine_state = rules_engine::state::StateStore::new(&base);
+    engine_state.restore(&cfg, &mut engine);
+    let mut breaker = rules_engine::CircuitBreaker::new(&base);
+    let mut dedup = response_engine::dedup::Deduplicator::new();
+    let mut baseline = baseline::Baseline::load(&base);
+    let mut mailbox = ipc::Mailbox::new(&base);
+    let mut scan_monitor = scan_ipc::ScanMonitor::new(&base);
+    let mut targeted_scans = response_engine::targeted_scan::TargetedScans::new();
+    let mut recorder = recording::Recorder::new(&base);
+    let mut tracer = rules_engine::trace::Tracer::new(&base);
+    let mut watcher = watch::Watcher::new(&base);
+    let mut scan_scheduler = scan_schedule::ScanScheduler::new(&cfg);
+    // Tamper checks belong to the privileged process, like the failsafe below.
+    let mut self_protection = (!runtime::is_worker()).then(|| self_protection::Monitor::new(&base));
+    status_api::start(&cfg, &base);
 
     loop {
+      self.liveness.beat();
       if stop_rx.recv_timeout(tick).is_ok() {
         break;
       }
 
-      let _ = kill_switch::poll_failsafe();
-      refresh_scheduler.tick(&cfg, &base);
+      // In the split service the core owns the firewall, failsafe included.
+      if !runtime::is_worker() {
+        let _ = kill_switch::poll_failsafe();
+      }
+      if let Some(monitor) = &mut self_protection {
+        for mut incident in monitor.tick(&cfg) {
+          response_engine::handle_incident(&cfg, &mut incident)?;
+        }
+      }
+      if refresh_scheduler.tick(&cfg, &base) {
+        let feed = threat_feed::load_current_at(&base);
+        heartbeat.set_feed(feed.as_ref());
+        engine.set_threat_feed(feed);
+      }
+      mailbox.tick(&cfg);
+      scan_monitor.tick();
+      scan_scheduler.tick(&cfg);
 
-      let events = event_collector::collect_once()?;
+      let events = event_collector::collect_once(&cfg.collector)?;
+      heartbeat.tick(
+        events.len(),
+        event_collector::last_errors(),
+        event_collector::lag(),
+      );
+      metrics::events_processed(events.len());
+      metrics_file.tick(&cfg.metrics);
       if events.is_empty() {
         continue;
       }
+      recorder.record(&cfg.recording, &events);
+      watcher.observe(&cfg.watch, &events);
 
+      tracer.sync(&mut engine);
       let incidents = engine.process(&cfg, &events)?;
+      engine_state.save(&cfg, &engine, false);
+      let incidents = breaker.filter(&cfg.circuit_breaker, incidents);
+      let incidents = dedup.filter(&cfg.response.dedup, incidents);
+      tracer.flush(&mut engine, &incidents);
+      baseline.record(&cfg.baseline, &engine.drain_observations());
+      if let Err(e) = baseline.flush(&cfg.baseline, false) {
+        tracing::warn!(error = ?e, "failed to write learning baseline (continuing)");
+      }
       for mut incident in incidents {
         response_engine::handle_incident(&cfg, &mut incident)?;
+        targeted_scans.on_incident(&cfg, &incident);
       }
     }
 
+    // Shutdown: answer prompts replied to since the last tick, pick up a scheduled scan that just
+    // finished, and write out what's still in memory.
+    tracing::info!("agent stopping; flushing state");
+    mailbox.tick(&cfg);
+    scan_scheduler.shutdown(&cfg);
+    engine_state.save(&cfg, &engine, true);
+    baseline.record(&cfg.baseline, &engine.drain_observations());
+    if let Err(e) = baseline.flush(&cfg.baseline, true) {
+      tracing::warn!(error = ?e, "failed to write learning baseline");
+    }
     tracing::info!("agent main loop exiting");
     Ok(())
   }
@@ -0,0 +1,325 @@
+use crate::config::BaselineConfig;
+use crate::paths;
+use crate::runtime;
+use defender_common::fs::write_atomic;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// Learning-mode baseline: counts which (publisher, image, protected target) tuples keep showing up
+// during the learning period and turns the recurring ones into allowlist suggestions. Nothing is
+// allowlisted automatically; suggestions are accepted or rejected with `--baseline review`.
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A non-browser process touching a protected browser target, as seen by the rules engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+  pub publisher: Option<String>,
+  pub image_path: String,
+  pub target: String,
+  pub timestamp_unix_ms: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct BaselineState {
+  started_at_unix_ms: u64,
+  #[serde(default)]
+  tuples: Vec<TupleStats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TupleStats {
+  #[serde(default)]
+  publisher: String,
+  image_path: String,
+  target: String,
+  count: u64,
+  first_seen_unix_ms: u64,
+  last_seen_unix_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestionStatus {
+  Pending,
+  Accepted,
+  Rejected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+  /// Pin the image binary by SHA-256 (`allowlist.hashes`).
+  Hash,
+  /// Allowlist the signer publisher (`allowlist.publishers`).
+  Publisher,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Suggestion {
+  pub id: String,
+  #[serde(default)]
+  pub publisher: String,
+  pub image_path: String,
+  pub target: String,
+  pub count: u64,
+  pub first_seen_unix_ms: u64,
+  pub last_seen_unix_ms: u64,
+  pub kind: EntryKind,
+  pub value: String,
+  pub status: SuggestionStatus,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SuggestionsFile {
+  #[serde(default)]
+  suggestions: Vec<Suggestion>,
+}
+
+pub struct Baseline {
+  base: PathBuf,
+  state: BaselineState,
+  dirty: bool,
+  last_flush: Instant,
+}
+
+impl Baseline {
+  pub fn load(base: &Path) -> Self {
+    let state = fs::read_to_string(paths::baseline_state_path(base))
+      .ok()
+      .and_then(|raw| toml::from_str::<BaselineState>(&raw).ok())
+      .unwrap_or_default();
+    Self {
+      base: base.to_path_buf(),
+      state,
+      dirty: false,
+      last_flush: Instant::now(),
+    }
+  }
+
+  pub fn started_at_unix_ms(&self) -> u64 {
+    self.state.started_at_unix_ms
+  }
+
+  pub fn tuples_tracked(&self) -> usize {
+    self.state.tuples.len()
+  }
+
+  pub fn learning_ends_at_unix_ms(&self, cfg: &BaselineConfig) -> Option<u64> {
+    (self.state.started_at_unix_ms > 0).then(|| {
+      self
+        .state
+        .started_at_unix_ms
+        .saturating_add(cfg.learning_period_days.saturating_mul(86_400_000))
+    })
+  }
+
+  pub fn record(&mut self, cfg: &BaselineConfig, observations: &[Observation]) {
+    if !cfg.enabled || observations.is_empty() {
+      return;
+    }
+    if self.state.started_at_unix_ms == 0 {
+      self.state.started_at_unix_ms = observations[0].timestamp_unix_ms;
+    }
+    let ends_at = self.learning_ends_at_unix_ms(cfg).unwrap_or(u64::MAX);
+
+    for obs in observations {
+      if obs.timestamp_unix_ms > ends_at {
+        continue;
+      }
+      let publisher = obs.publisher.as_deref().unwrap_or("").trim().to_string();
+      let existing = self.state.tuples.iter_mut().find(|t| {
+        t.publisher.eq_ignore_ascii_case(&publisher)
+          && t.image_path.eq_ignore_ascii_case(&obs.image_path)
+          && t.target == obs.target
+      });
+      match existing {
+        Some(t) => {
+          t.count += 1;
+          t.last_seen_unix_ms = t.last_seen_unix_ms.max(obs.timestamp_unix_ms);
+        }
+        None => self.state.tuples.push(TupleStats {
+          publisher,
+          image_path: obs.image_path.clone(),
+          target: obs.target.clone(),
+          count: 1,
+          first_seen_unix_ms: obs.timestamp_unix_ms,
+          last_seen_unix_ms: obs.timestamp_unix_ms,
+        }),
+      }
+      self.dirty = true;
+    }
+  }
+
+  /// Persists state and refreshes the suggestion file, at most once per minute unless `force`.
+  pub fn flush(&mut self, cfg: &BaselineConfig, force: bool) -> anyhow::Result<()> {
+    if !self.dirty || (!force && self.last_flush.elapsed() < FLUSH_INTERVAL) {
+      return Ok(());
+    }
+    self.last_flush = Instant::now();
+    self.dirty = false;
+
+    if runtime::is_dry_run() {
+      tracing::warn!(
+        tuples = self.state.tuples.len(),
+        "DRY-RUN: would write learning baseline and suggestions"
+      );
+      return Ok(());
+    }
+
+    write_atomic(
+      &paths::baseline_state_path(&self.base),
+      &toml::to_string_pretty(&self.state)?,
+    )?;
+
+    let mut file = load_suggestions_file(&self.base);
+    let before = file.suggestions.len();
+    for t in self
+      .state
+      .tuples
+      .iter()
+      .filter(|t| t.count >= cfg.min_occurrences)
+    {
+      let id = tuple_id(&t.publisher, &t.image_path, &t.target);
+      if let Some(s) = file.suggestions.iter_mut().find(|s| s.id == id) {
+        s.count = t.count;
+        s.last_seen_unix_ms = t.last_seen_unix_ms;
+        continue;
+      }
+      let Some((kind, value)) = suggested_entry(t) else {
+        continue;
+      };
+      file.suggestions.push(Suggestion {
+        id,
+        publisher: t.publisher.clone(),
+        image_path: t.image_path.clone(),
+        target: t.target.clone(),
+        count: t.count,
+        first_seen_unix_ms: t.first_seen_unix_ms,
+        last_seen_unix_ms: t.last_seen_unix_ms,
+        kind,
+        value,
+        status: SuggestionStatus::Pending,
+      });
+    }
+
+    if file.suggestions.len() > before {
+      tracing::info!(
+        new = file.suggestions.len() - before,
+        "baseline: new allowlist suggestions (review with `--baseline review`)"
+      );
+    }
+    save_suggestions_file(&self.base, &file)
+  }
+}
+
+pub fn load_suggestions(base: &Path) -> Vec<Suggestion> {
+  load_suggestions_file(base).suggestions
+}
+
+pub fn set_status(base: &Path, id: &str, status: SuggestionStatus) -> anyhow::Result<()> {
+  let mut file = load_suggestions_file(base);
+  let Some(s) = file.suggestions.iter_mut().find(|s| s.id == id) else {
+    anyhow::bail!("no baseline suggestion with id {id}");
+  };
+  s.status = status;
+
+  if runtime::is_dry_run() {
+    tracing::warn!(id = %id, status = ?status, "DRY-RUN: would update baseline suggestion");
+    return Ok(());
+  }
+  save_suggestions_file(base, &file)
+}
+
+// Prefer pinning the exact binary; fall back to the publisher only when the image can't be hashed.
+fn suggested_entry(t: &TupleStats) -> Option<(EntryKind, String)> {
+  if let Ok(hash) = defender_common::hash::sha256_hex(Path::new(&t.image_path)) {
+    return Some((EntryKind::Hash, hash));
+  }
+  (!t.publisher.is_empty()).then(|| (EntryKind::Publisher, t.publisher.clone()))
+}
+
+fn tuple_id(publisher: &str, image_path: &str, target: &str) -> String {
+  let mut h = Sha256::new();
+  h.update(publisher.to_ascii_lowercase().as_bytes());
+  h.update(b"|");
+  h.update(image_path.to_ascii_lowercase().as_bytes());
+  h.update(b"|");
+  h.update(target.as_bytes());
+  format!("{:x}", h.finalize())[..12].to_string()
+}
+
+fn load_suggestions_file(base: &Path) -> SuggestionsFile {
+  fs::read_to_string(paths::baseline_suggestions_path(base))
+    .ok()
+    .and_then(|raw| toml::from_str::<SuggestionsFile>(&raw).ok())
+    .unwrap_or_default()
+}
+
+fn save_suggestions_file(base: &Path, file: &SuggestionsFile) -> anyhow::Result<()> {
+  write_atomic(
+    &paths::baseline_suggestions_path(base),
+    &toml::to_string_pretty(file)?,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn obs(image: &str, ts: u64) -> Observation {
+    Observation {
+      publisher: Some("Contoso Ltd".to_string()),
+      image_path: image.to_string(),
+      target: "chrome_cookies".to_string(),
+      timestamp_unix_ms: ts,
+    }
+  }
+
+  #[test]
+  fn aggregates_tuples_and_suggests_recurring_ones() {
+    let base = std::env::temp_dir().join(format!("aid-baseline-{}", uuid::Uuid::new_v4()));
+    let cfg = BaselineConfig {
+      enabled: true,
+      learning_period_days: 1,
+      min_occurrences: 3,
+    };
+    let t0 = 1_700_000_000_000;
+
+    let mut b = Baseline::load(&base);
+    b.record(
+      &cfg,
+      &[
+        obs("C:\\Tools\\backup.exe", t0),
+        obs("c:\\tools\\BACKUP.exe", t0 + 1),
+        obs("C:\\Tools\\backup.exe", t0 + 2),
+        obs("C:\\Tools\\rare.exe", t0 + 3),
+        // After the learning period: ignored.
+        obs("C:\\Tools\\rare.exe", t0 + 2 * 86_400_000),
+      ],
+    );
+    assert_eq!(b.tuples_tracked(), 2);
+    b.flush(&cfg, true).unwrap();
+
+    // Images don't exist here, so suggestions fall back to the publisher.
+    let suggestions = load_suggestions(&base);
+    assert_eq!(suggestions.len(), 1);
+    assert_eq!(suggestions[0].count, 3);
+    assert_eq!(suggestions[0].kind, EntryKind::Publisher);
+    assert_eq!(suggestions[0].status, SuggestionStatus::Pending);
+
+    set_status(&base, &suggestions[0].id, SuggestionStatus::Rejected).unwrap();
+    let mut b = Baseline::load(&base);
+    b.record(&cfg, &[obs("C:\\Tools\\backup.exe", t0 + 10)]);
+    b.flush(&cfg, true).unwrap();
+    let suggestions = load_suggestions(&base);
+    let _ = fs::remove_dir_all(&base);
+
+    assert_eq!(suggestions.len(), 1);
+    assert_eq!(suggestions[0].count, 4);
+    assert_eq!(suggestions[0].status, SuggestionStatus::Rejected);
+  }
+}
@@ -0,0 +1,248 @@
+// Browser extension sideloading indicators shared by the rules engine and the scanner:
+// - Chromium `ExtensionInstallForcelist` policy values (silently force-install an extension),
+// - Chromium browsers started with `--load-extension` (loads an unpacked extension from disk).
+// Both are common stealer/adware persistence paths that never touch the protected profile files.
+
+use crate::browsers;
+#[cfg(windows)]
+use std::process::Command;
+
+/// Policy keys (relative to HKLM/HKCU) that force-install Chromium extensions.
+pub const FORCELIST_POLICY_KEYS: &[&str] = &[
+  "SOFTWARE\\Policies\\Google\\Chrome\\ExtensionInstallForcelist",
+  "SOFTWARE\\Policies\\Microsoft\\Edge\\ExtensionInstallForcelist",
+  "SOFTWARE\\Policies\\BraveSoftware\\Brave\\ExtensionInstallForcelist",
+  "SOFTWARE\\Policies\\Chromium\\ExtensionInstallForcelist",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForcelistEntry {
+  pub key: String,
+  pub value_name: String,
+  /// `<extension-id>[;<update-url>]`
+  pub data: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrowserProcess {
+  pub pid: u32,
+  pub image_path: String,
+  pub command_line: String,
+}
+
+/// True for registry paths (as reported by Sysmon, e.g. `HKLM\SOFTWARE\Policies\...` or
+/// `HKU\<sid>\SOFTWARE\Policies\...`) under any Chromium forcelist policy key.
+pub fn is_forcelist_key(key_path: &str) -> bool {
+  let k = key_path.to_ascii_lowercase().replace("\\wow6432node", "");
+  FORCELIST_POLICY_KEYS
+    .iter()
+    .any(|p| k.contains(&p.to_ascii_lowercase()))
+}
+
+/// Executables of every Chromium browser AI Defender knows (see `browsers`), installed or not.
+pub fn is_chromium_image(image_path: &str) -> bool {
+  browsers::candidates()
+    .iter()
+    .any(|b| b.has_image(image_path))
+}
+
+/// Extension directories passed via `--load-extension` (comma-separated, optionally quoted).
+pub fn load_extension_paths(command_line: &str) -> Vec<String> {
+  const FLAG: &str = "--load-extension";
+
+  let tokens = split_command_line(command_line);
+  let mut out = Vec::new();
+  let mut i = 0;
+  while i < tokens.len() {
+    let t = &tokens[i];
+    if t.len() >= FLAG.len() && t[..FLAG.len()].eq_ignore_ascii_case(FLAG) {
+      let value = match t[FLAG.len()..].strip_prefix('=') {
+        Some(v) => Some(v.to_string()),
+        None if t.len() == FLAG.len() => {
+          i += 1;
+          tokens.get(i).cloned()
+        }
+        None => None,
+      };
+      if let Some(v) = value {
+        out.extend(
+          v.split(',')
+            .map(|p| p.trim().trim_matches('"').to_string())
+            .filter(|p| !p.is_empty()),
+        );
+      }
+    }
+    i += 1;
+  }
+  out
+}
+
+// Whitespace-separated tokens; double quotes group and are removed.
+fn split_command_line(command_line: &str) -> Vec<String> {
+  let mut tokens = Vec::new();
+  let mut cur = String::new();
+  let mut in_quotes = false;
+  for c in command_line.chars() {
+    match c {
+      '"' => in_quotes = !in_quotes,
+      c if c.is_whitespace() && !in_quotes => {
+        if !cur.is_empty() {
+          tokens.push(std::mem::take(&mut cur));
+        }
+      }
+      c => cur.push(c),
+    }
+  }
+  if !cur.is_empty() {
+    tokens.push(cur);
+  }
+  tokens
+}
+
+/// Current forcelist policy values under HKLM and HKCU.
+#[cfg(windows)]
+pub fn read_forcelist_entries() -> Vec<ForcelistEntry> {
+  let mut out = Vec::new();
+  for hive in ["HKLM", "HKCU"] {
+    for key in FORCELIST_POLICY_KEYS {
+      let full = format!("{hive}\\{key}");
+      let Ok(output) = Command::new("reg").args(["query", &full]).output() else {
+        continue;
+      };
+      if !output.status.success() {
+        continue;
+      }
+      out.extend(parse_reg_query(
+        &full,
+        &String::from_utf8_lossy(&output.stdout),
+      ));
+    }
+  }
+  out
+}
+
+#[cfg(not(windows))]
+pub fn read_forcelist_entries() -> Vec<ForcelistEntry> {
+  Vec::new()
+}
+
+/// Running Chromium-family browser processes with their command lines.
+#[cfg(windows)]
+pub fn running_browser_processes() -> Vec<BrowserProcess> {
+  // Only image names made of `[a-z0-9._-]` (configured ones included) are interpolated.
+  let mut images: Vec<String> = browsers::candidates()
+    .into_iter()
+    .flat_map(|b| b.images)
+    .filter(|i| {
+      i.chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    })
+    .collect();
+  images.sort();
+  images.dedup();
+  if images.is_empty() {
+    return Vec::new();
+  }
+  let filter = images
+    .iter()
+    .map(|i| format!("Name='{i}'"))
+    .collect::<Vec<_>>()
+    .join(" or ");
+  let script = format!(
+    "Get-CimInstance Win32_Process -Filter \"{filter}\" | ForEach-Object {{ \"$($_.ProcessId)`t$($_.ExecutablePath)`t$($_.CommandLine)\" }}"
+  );
+  let Ok(output) = Command::new("powershell")
+    .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+    .output()
+  else {
+    return Vec::new();
+  };
+  if !output.status.success() {
+    return Vec::new();
+  }
+  String::from_utf8_lossy(&output.stdout)
+    .lines()
+    .filter_map(|l| {
+      let mut parts = l.splitn(3, '\t');
+      let pid = parts.next()?.trim().parse::<u32>().ok()?;
+      let image_path = parts.next()?.trim().to_string();
+      let command_line = parts.next().unwrap_or("").trim().to_string();
+      Some(BrowserProcess {
+        pid,
+        image_path,
+        command_line,
+      })
+    })
+    .collect()
+}
+
+#[cfg(not(windows))]
+pub fn running_browser_processes() -> Vec<BrowserProcess> {
+  Vec::new()
+}
+
+// `reg query` prints one `    <name>    REG_SZ    <data>` line per value.
+#[cfg_attr(not(any(windows, test)), allow(dead_code))]
+fn parse_reg_query(key: &str, stdout: &str) -> Vec<ForcelistEntry> {
+  stdout
+    .lines()
+    .filter_map(|l| {
+      let (name, rest) = l.trim().split_once("REG_SZ")?;
+      Some(ForcelistEntry {
+        key: key.to_string(),
+        value_name: name.trim().to_string(),
+        data: rest.trim().to_string(),
+      })
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detects_forcelist_keys_in_any_hive() {
+    assert!(is_forcelist_key(
+      "HKLM\\SOFTWARE\\Policies\\Google\\Chrome\\ExtensionInstallForcelist\\1"
+    ));
+    assert!(is_forcelist_key(
+      "HKU\\S-1-5-21-1\\Software\\Policies\\Microsoft\\Edge\\ExtensionInstallForcelist\\3"
+    ));
+    assert!(is_forcelist_key(
+      "HKLM\\SOFTWARE\\WOW6432Node\\Policies\\Google\\Chrome\\ExtensionInstallForcelist\\1"
+    ));
+    assert!(!is_forcelist_key(
+      "HKLM\\SOFTWARE\\Policies\\Google\\Chrome\\ExtensionInstallAllowlist\\1"
+    ));
+  }
+
+  #[test]
+  fn extracts_load_extension_paths() {
+    assert_eq!(
+      load_extension_paths(
+        "\"C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe\" --load-extension=C:\\Users\\u\\AppData\\Local\\Temp\\ext1,C:\\ext2 --no-first-run"
+      ),
+      vec!["C:\\Users\\u\\AppData\\Local\\Temp\\ext1", "C:\\ext2"]
+    );
+    assert_eq!(
+      load_extension_paths("chrome.exe \"--load-extension=C:\\My Ext\""),
+      vec!["C:\\My Ext"]
+    );
+    assert_eq!(
+      load_extension_paths("msedge.exe --LOAD-EXTENSION \"C:\\x y\""),
+      vec!["C:\\x y"]
+    );
+    assert!(load_extension_paths("chrome.exe --load-extensions-list").is_empty());
+    assert!(load_extension_paths("chrome.exe --profile-directory=Default").is_empty());
+  }
+
+  #[test]
+  fn parses_reg_query_output() {
+    let out = "\r\nHKEY_LOCAL_MACHINE\\SOFTWARE\\Policies\\Google\\Chrome\\ExtensionInstallForcelist\r\n    1    REG_SZ    abcdefghijklmnopabcdefghijklmnop;https://clients2.google.com/service/update2/crx\r\n\r\n";
+    let entries = parse_reg_query("HKLM\\X", out);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].value_name, "1");
+    assert!(entries[0].data.starts_with("abcdefghijklmnop"));
+  }
+}
@@ -0,0 +1,432 @@
+use crate::browsers::{self, ChromiumBrowser};
+use crate::types::now_unix_ms;
+use crate::user_profiles::{self, UserProfile};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+// Browser profile discovery shared by protected-path classification, coverage and the scanner.
+// Chromium browsers (see `browsers`) keep one directory per profile under `User Data` (`Default`,
+// `Profile 1`, ..., listed in `Local State`; Opera uses `User Data` itself); Firefox lists its
+// profiles in `profiles.ini`, and a profile can live outside `Profiles\` when created with an
+// absolute path. Every profile holds its own Login
+// Data/Cookies (logins.json/key4.db) and extensions, so all of them are covered, not just the first.
+//
+// Profiles are discovered for every user on the machine (see `user_profiles`), not just the
+// account the agent runs as.
+//
+// The agent keeps the last discovery cached and re-runs it when a `User Data` directory, a
+// `Local State` or `profiles.ini` changes (checked at most once a minute), so profiles added and
+// browsers installed while it runs are picked up.
+
+const RECHECK_INTERVAL_MS: u64 = 60_000;
+
+/// Firefox's directory relative to `%APPDATA%`.
+pub const FIREFOX_DIR: &str = "Mozilla\\Firefox";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+  pub browser: String,
+  /// The user profile folder name the browser profile belongs to.
+  pub user: String,
+  pub dir: PathBuf,
+}
+
+impl Profile {
+  pub fn is_firefox(&self) -> bool {
+    self.browser == "Firefox"
+  }
+
+  /// Where the profile's extensions are installed.
+  pub fn extensions_dir(&self) -> PathBuf {
+    if self.is_firefox() {
+      self.dir.join("extensions")
+    } else {
+      self.dir.join("Extensions")
+    }
+  }
+}
+
+struct Cache {
+  stamp: Vec<Option<SystemTime>>,
+  browsers: Vec<ChromiumBrowser>,
+  profiles: Vec<Profile>,
+  /// Lowercased directories with a trailing separator, for prefix matching.
+  dirs_lower: Vec<(String, String)>,
+  /// Lowercased Chromium `User Data` directories of every user, with a trailing backslash.
+  user_data_lower: Vec<(String, String)>,
+  checked_at_unix_ms: u64,
+}
+
+static CACHE: RwLock<Option<Cache>> = RwLock::new(None);
+
+/// Browser profiles of every user, freshly enumerated.
+pub fn discover() -> Vec<Profile> {
+  let users = user_profiles::all();
+  let browsers = browsers::detect(&users);
+  users
+    .iter()
+    .flat_map(|u| discover_in(u, &browsers))
+    .collect()
+}
+
+pub fn discover_in(user: &UserProfile, browsers: &[ChromiumBrowser]) -> Vec<Profile> {
+  let mut out = Vec::new();
+  for browser in browsers {
+    for user_data in browser.user_data_dirs(user) {
+      out.extend(
+        chromium_profiles(&user_data)
+          .into_iter()
+          .map(|dir| Profile {
+            browser: browser.name.clone(),
+            user: user.name.clone(),
+            dir,
+          }),
+      );
+    }
+  }
+  out.extend(
+    firefox_profiles(&user.appdata.join(FIREFOX_DIR))
+      .into_iter()
+      .map(|dir| Profile {
+        browser: "Firefox".to_string(),
+        user: user.name.clone(),
+        dir,
+      }),
+  );
+  out
+}
+
+/// Discovers profiles now and logs them; the agent calls this at startup.
+pub fn refresh() -> Vec<Profile> {
+  refresh_cache(true);
+  let profiles = current();
+  for p in &profiles {
+    tracing::debug!(browser = p.browser, user = %p.user, dir = %p.dir.display(), "browser profile");
+  }
+  tracing::info!(count = profiles.len(), "browser profiles discovered");
+  profiles
+}
+
+/// Cached profiles, re-discovered if browser profile lists changed.
+pub fn current() -> Vec<Profile> {
+  refresh_cache(false);
+  let cache = CACHE.read().unwrap_or_else(|e| e.into_inner());
+  cache
+    .as_ref()
+    .map(|c| c.profiles.clone())
+    .unwrap_or_default()
+}
+
+/// The browser whose profile directory contains `path_lower` (a lowercased path), if any.
+pub fn profile_browser(path_lower: &str) -> Option<String> {
+  refresh_cache(false);
+  let cache = CACHE.read().unwrap_or_else(|e| e.into_inner());
+  cache
+    .as_ref()?
+    .dirs_lower
+    .iter()
+    .find(|(_, dir)| path_lower.starts_with(dir.as_str()))
+    .map(|(browser, _)| browser.clone())
+}
+
+/// The Chromium browser whose `User Data` directory (of any user) contains `path_lower`, if any.
+pub fn user_data_browser(path_lower: &str) -> Option<String> {
+  refresh_cache(false);
+  let p = path_lower.replace('/', "\\");
+  let cache = CACHE.read().unwrap_or_else(|e| e.into_inner());
+  cache
+    .as_ref()?
+    .user_data_lower
+    .iter()
+    .find(|(_, dir)| p.starts_with(dir.as_str()))
+    .map(|(browser, _)| browser.clone())
+}
+
+/// The Chromium browsers covered on this machine (see `browsers::detect`).
+pub fn chromium_browsers() -> Vec<ChromiumBrowser> {
+  refresh_cache(false);
+  let cache = CACHE.read().unwrap_or_else(|e| e.into_inner());
+  cache
+    .as_ref()
+    .map(|c| c.browsers.clone())
+    .unwrap_or_default()
+}
+
+/// Whether `image_path` is the executable of a covered Chromium browser.
+pub fn is_chromium_image(image_path: &str) -> bool {
+  refresh_cache(false);
+  let cache = CACHE.read().unwrap_or_else(|e| e.into_inner());
+  cache
+    .as_ref()
+    .is_some_and(|c| c.browsers.iter().any(|b| b.has_image(image_path)))
+}
+
+fn refresh_cache(force: bool) {
+  let now = now_unix_ms();
+  {
+    let cache = CACHE.read().unwrap_or_else(|e| e.into_inner());
+    if let Some(c) = cache.as_ref() {
+      if !force && now.saturating_sub(c.checked_at_unix_ms) < RECHECK_INTERVAL_MS {
+        return;
+      }
+    }
+  }
+
+  let mut cache = CACHE.write().unwrap_or_else(|e| e.into_inner());
+  let stamp = change_stamp();
+  if let Some(c) = cache.as_mut() {
+    if !force && c.stamp == stamp {
+      c.checked_at_unix_ms = now;
+      return;
+    }
+  }
+
+  let users = user_profiles::all();
+  let browsers = browsers::detect(&users);
+  let profiles: Vec<Profile> = users
+    .iter()
+    .flat_map(|u| discover_in(u, &browsers))
+    .collect();
+  if cache.is_some() {
+    tracing::info!(
+      count = profiles.len(),
+      "browser profiles changed; re-discovered"
+    );
+  }
+  let dirs_lower = profiles
+    .iter()
+    .map(|p| {
+      let mut dir = p.dir.to_string_lossy().to_ascii_lowercase();
+      if !dir.ends_with(['\\', '/']) {
+        dir.push('\\');
+      }
+      (p.browser.clone(), dir)
+    })
+    .collect();
+  // Event paths use backslashes; `join` may not (tests on other platforms).
+  let user_data_lower = users
+    .iter()
+    .flat_map(|u| {
+      browsers.iter().flat_map(move |b| {
+        b.user_data_dirs(u).into_iter().map(|d| {
+          let dir = d.to_string_lossy().to_ascii_lowercase().replace('/', "\\");
+          (b.name.clone(), format!("{}\\", dir.trim_end_matches('\\')))
+        })
+      })
+    })
+    .collect();
+  *cache = Some(Cache {
+    stamp,
+    browsers,
+    profiles,
+    dirs_lower,
+    user_data_lower,
+    checked_at_unix_ms: now,
+  });
+}
+
+/// Modification times of everything a new or removed profile touches.
+fn change_stamp() -> Vec<Option<SystemTime>> {
+  let mut paths = Vec::new();
+  let candidates = browsers::candidates();
+  for user in user_profiles::all().iter() {
+    for browser in &candidates {
+      for user_data in browser.user_data_dirs(user) {
+        paths.push(user_data.join("Local State"));
+      }
+      paths.push(browser.install_marker(user));
+    }
+    let firefox = user.appdata.join(FIREFOX_DIR);
+    paths.push(firefox.join("profiles.ini"));
+    paths.push(firefox.join("Profiles"));
+  }
+  paths
+    .iter()
+    .map(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+    .collect()
+}
+
+/// Profiles listed in `Local State`, plus `Default`/`Profile N` directories it doesn't list yet.
+fn chromium_profiles(user_data: &Path) -> Vec<PathBuf> {
+  let mut names: Vec<String> = fs::read(user_data.join("Local State"))
+    .ok()
+    .and_then(|bytes| serde_json::from_slice::<serde_json::Value>(&bytes).ok())
+    .and_then(|v| {
+      v.get("profile")?
+        .get("info_cache")?
+        .as_object()
+        .map(|cache| cache.keys().cloned().collect())
+    })
+    .unwrap_or_default();
+
+  if let Ok(entries) = fs::read_dir(user_data) {
+    for entry in entries.flatten() {
+      let name = entry.file_name().to_string_lossy().to_string();
+      let is_profile_dir = name == "Default" || name.starts_with("Profile ");
+      if is_profile_dir && entry.path().is_dir() && !names.contains(&name) {
+        names.push(name);
+      }
+    }
+  }
+
+  names.sort();
+  let dirs: Vec<PathBuf> = names
+    .into_iter()
+    .map(|n| user_data.join(n))
+    .filter(|d| d.is_dir())
+    .collect();
+  // Opera keeps its single profile directly in its `User Data` equivalent.
+  if dirs.is_empty() && user_data.join("Preferences").is_file() {
+    return vec![user_data.to_path_buf()];
+  }
+  dirs
+}
+
+/// Profiles listed in `profiles.ini` (relative or absolute), plus directories under `Profiles\`.
+fn firefox_profiles(firefox_dir: &Path) -> Vec<PathBuf> {
+  let mut out: Vec<PathBuf> = fs::read_to_string(firefox_dir.join("profiles.ini"))
+    .map(|ini| parse_profiles_ini(&ini))
+    .unwrap_or_default()
+    .into_iter()
+    .map(|(path, relative)| {
+      if relative {
+        firefox_dir.join(path.replace('/', "\\"))
+      } else {
+        // Profile dirs are prefix-matched against event paths, which use the native separator.
+        PathBuf::from(path.replace('/', std::path::MAIN_SEPARATOR_STR))
+      }
+    })
+    .collect();
+
+  if let Ok(entries) = fs::read_dir(firefox_dir.join("Profiles")) {
+    out.extend(entries.flatten().map(|e| e.path()));
+  }
+
+  let mut seen: Vec<String> = Vec::new();
+  out.retain(|d| {
+    let key = d.to_string_lossy().to_ascii_lowercase();
+    let new = d.is_dir() && !seen.contains(&key);
+    seen.push(key);
+    new
+  });
+  out
+}
+
+/// `(Path, IsRelative)` of every `[Profile*]` section.
+fn parse_profiles_ini(ini: &str) -> Vec<(String, bool)> {
+  let mut out = Vec::new();
+  let mut in_profile = false;
+  let mut path: Option<String> = None;
+  let mut relative = true;
+  let mut flush = |path: &mut Option<String>, relative: bool| {
+    if let Some(p) = path.take() {
+      out.push((p, relative));
+    }
+  };
+
+  // Files edited with Notepad may start with a BOM.
+  let ini = ini.trim_start_matches('\u{feff}');
+  for line in ini.lines().map(str::trim) {
+    if line.starts_with('[') {
+      flush(&mut path, relative);
+      in_profile = line.to_ascii_lowercase().starts_with("[profile");
+      relative = true;
+      continue;
+    }
+    if !in_profile {
+      continue;
+    }
+    let Some((key, value)) = line.split_once('=').map(|(k, v)| (k.trim(), v.trim())) else {
+      continue;
+    };
+    if key.eq_ignore_ascii_case("Path") && !value.is_empty() {
+      path = Some(value.to_string());
+    } else if key.eq_ignore_ascii_case("IsRelative") {
+      relative = value != "0";
+    }
+  }
+  flush(&mut path, relative);
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_every_chromium_and_firefox_profile() {
+    let base = std::env::temp_dir().join(format!("aid-profiles-{}", uuid::Uuid::new_v4()));
+    let local = base.join("Local");
+    let roam = base.join("Roaming");
+    let chrome = local.join("Google\\Chrome\\User Data");
+    for dir in ["Default", "Profile 1", "Work", "Crashpad"] {
+      fs::create_dir_all(chrome.join(dir)).unwrap();
+    }
+    // `Work` is only known from Local State.
+    fs::write(
+      chrome.join("Local State"),
+      r#"{"profile":{"info_cache":{"Default":{},"Work":{},"Gone":{}}}}"#,
+    )
+    .unwrap();
+
+    let firefox = roam.join(FIREFOX_DIR);
+    let elsewhere = base.join("ff-elsewhere");
+    fs::create_dir_all(firefox.join("Profiles\\abcd.default-release")).unwrap();
+    fs::create_dir_all(&elsewhere).unwrap();
+    fs::write(
+      firefox.join("profiles.ini"),
+      format!(
+        "[General]\nStartWithLastProfile=1\n\n[Profile0]\nName=default-release\nIsRelative=1\n\
+         Path=Profiles/abcd.default-release\n\n[Profile1]\nName=work\nIsRelative=0\nPath={}\n",
+        elsewhere.display()
+      ),
+    )
+    .unwrap();
+
+    let user = UserProfile {
+      name: "alice".to_string(),
+      home: base.clone(),
+      local_appdata: local,
+      appdata: roam,
+    };
+    let profiles = discover_in(&user, &browsers::candidates());
+    let names: Vec<(&str, String)> = profiles
+      .iter()
+      .map(|p| {
+        let dir = p.dir.to_string_lossy();
+        (
+          p.browser.as_str(),
+          dir.rsplit(['\\', '/']).next().unwrap().to_string(),
+        )
+      })
+      .collect();
+    assert_eq!(
+      names,
+      vec![
+        ("Chrome", "Default".to_string()),
+        ("Chrome", "Profile 1".to_string()),
+        ("Chrome", "Work".to_string()),
+        ("Firefox", "abcd.default-release".to_string()),
+        ("Firefox", "ff-elsewhere".to_string()),
+      ]
+    );
+
+    let _ = fs::remove_dir_all(&base);
+  }
+
+  #[test]
+  fn parses_relocated_profiles_from_profiles_ini() {
+    let ini = "\u{feff}[Profile1]\r\nName=work\r\nisrelative=0\r\npath=D:/Firefox/work\r\n\r\n\
+               [Install308046B0AF4A39CB]\r\nDefault=Profiles/abcd.default-release\r\n\r\n\
+               [Profile0]\r\nPath=Profiles/abcd.default-release\r\nIsRelative=1\r\n";
+    assert_eq!(
+      parse_profiles_ini(ini),
+      vec![
+        ("D:/Firefox/work".to_string(), false),
+        ("Profiles/abcd.default-release".to_string(), true),
+      ]
+    );
+  }
+}
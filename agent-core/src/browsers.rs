@@ -0,0 +1,349 @@
+use crate::config::{BrowsersConfig, ChromiumBrowserConfig};
+use crate::user_profiles::UserProfile;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+#[cfg(windows)]
+use std::process::Command;
+
+// Chromium-based browsers whose profiles are protected (`[browsers]`). Chrome, Edge and Brave are
+// always covered. Other forks in the catalog below are covered once installed: their `User Data`
+// directory exists for some user, or their executable is registered under
+// `HKLM\SOFTWARE\Clients\StartMenuInternet`. Browsers from `[browsers] chromium` are always
+// covered. `browser_profiles` runs the detection with its profile discovery and caches the result;
+// the covered browsers' executables are the ones treated as browsers for owner access.
+
+/// Which per-user folder a `User Data` path is relative to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppDataDir {
+  Local,
+  Roaming,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChromiumBrowser {
+  pub name: String,
+  pub base: AppDataDir,
+  /// Relative to `base`; a `*` segment matches any directory name.
+  pub user_data: String,
+  /// Lowercase executable file names.
+  pub images: Vec<String>,
+  /// Covered whether or not it is installed.
+  pub always: bool,
+}
+
+impl ChromiumBrowser {
+  /// `User Data` directories of `user`. Paths without wildcards are returned whether or not they
+  /// exist; wildcard paths only when a match exists.
+  pub fn user_data_dirs(&self, user: &UserProfile) -> Vec<PathBuf> {
+    let base = match self.base {
+      AppDataDir::Local => &user.local_appdata,
+      AppDataDir::Roaming => &user.appdata,
+    };
+    if !self.user_data.contains('*') {
+      return vec![base.join(&self.user_data)];
+    }
+    let mut dirs = vec![base.clone()];
+    for segment in self.user_data.split('\\') {
+      dirs = dirs
+        .iter()
+        .flat_map(|d| expand_segment(d, segment))
+        .collect();
+    }
+    dirs
+  }
+
+  /// The directory whose modification time changes when the browser is first installed for
+  /// `user`: the `User Data` directory, or the directory above its first wildcard.
+  pub fn install_marker(&self, user: &UserProfile) -> PathBuf {
+    let base = match self.base {
+      AppDataDir::Local => &user.local_appdata,
+      AppDataDir::Roaming => &user.appdata,
+    };
+    match self.user_data.split_once("\\*") {
+      Some((fixed, _)) => base.join(fixed),
+      None => base.join(&self.user_data),
+    }
+  }
+
+  pub fn has_image(&self, image_path: &str) -> bool {
+    let p = image_path.to_ascii_lowercase();
+    let name = p.rsplit(['\\', '/']).next().unwrap_or(&p);
+    self.images.iter().any(|i| i == name)
+  }
+}
+
+fn expand_segment(dir: &Path, segment: &str) -> Vec<PathBuf> {
+  if !segment.contains('*') {
+    return vec![dir.join(segment)];
+  }
+  let (prefix, suffix) = segment.split_once('*').unwrap_or((segment, ""));
+  let (prefix, suffix) = (prefix.to_ascii_lowercase(), suffix.to_ascii_lowercase());
+  let Ok(entries) = fs::read_dir(dir) else {
+    return Vec::new();
+  };
+  let mut out: Vec<PathBuf> = entries
+    .flatten()
+    .filter(|e| {
+      let name = e.file_name().to_string_lossy().to_ascii_lowercase();
+      name.starts_with(&prefix) && name.ends_with(&suffix) && e.path().is_dir()
+    })
+    .map(|e| e.path())
+    .collect();
+  out.sort();
+  out
+}
+
+/// `(name, base, User Data path, images, always)` of the browsers AI Defender knows.
+const CATALOG: &[(&str, AppDataDir, &str, &[&str], bool)] = &[
+  (
+    "Chrome",
+    AppDataDir::Local,
+    "Google\\Chrome\\User Data",
+    &["chrome.exe"],
+    true,
+  ),
+  (
+    "Edge",
+    AppDataDir::Local,
+    "Microsoft\\Edge\\User Data",
+    &["msedge.exe"],
+    true,
+  ),
+  (
+    "Brave",
+    AppDataDir::Local,
+    "BraveSoftware\\Brave-Browser\\User Data",
+    &["brave.exe"],
+    true,
+  ),
+  (
+    "Chromium",
+    AppDataDir::Local,
+    "Chromium\\User Data",
+    &["chromium.exe"],
+    false,
+  ),
+  (
+    "Vivaldi",
+    AppDataDir::Local,
+    "Vivaldi\\User Data",
+    &["vivaldi.exe"],
+    false,
+  ),
+  (
+    "Opera",
+    AppDataDir::Roaming,
+    "Opera Software\\Opera Stable",
+    &["opera.exe"],
+    false,
+  ),
+  (
+    "Opera GX",
+    AppDataDir::Roaming,
+    "Opera Software\\Opera GX Stable",
+    &["opera.exe"],
+    false,
+  ),
+  // Arc is a packaged app; the package folder name ends in a publisher hash.
+  (
+    "Arc",
+    AppDataDir::Local,
+    "Packages\\TheBrowserCompany.Arc_*\\LocalCache\\Local\\Arc\\User Data",
+    &["arc.exe"],
+    false,
+  ),
+];
+
+static CONFIG: RwLock<Option<BrowsersConfig>> = RwLock::new(None);
+
+/// Applies `[browsers]`; until called, the defaults apply.
+pub fn configure(cfg: &BrowsersConfig) {
+  *CONFIG.write().unwrap_or_else(|e| e.into_inner()) = Some(cfg.clone());
+}
+
+fn config() -> BrowsersConfig {
+  CONFIG
+    .read()
+    .unwrap_or_else(|e| e.into_inner())
+    .clone()
+    .unwrap_or_default()
+}
+
+pub fn is_builtin(name: &str) -> bool {
+  CATALOG
+    .iter()
+    .any(|b| b.0.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Every browser that could be covered: the catalog minus `disabled`, plus configured ones.
+pub fn candidates() -> Vec<ChromiumBrowser> {
+  candidates_for(&config())
+}
+
+fn candidates_for(cfg: &BrowsersConfig) -> Vec<ChromiumBrowser> {
+  let builtin = CATALOG
+    .iter()
+    .filter(|b| !cfg.disabled.iter().any(|d| d.eq_ignore_ascii_case(b.0)))
+    .map(|(name, base, user_data, images, always)| ChromiumBrowser {
+      name: name.to_string(),
+      base: *base,
+      user_data: user_data.to_string(),
+      images: images.iter().map(|i| i.to_string()).collect(),
+      always: *always,
+    });
+  builtin
+    .chain(cfg.chromium.iter().map(from_config))
+    .collect()
+}
+
+fn from_config(c: &ChromiumBrowserConfig) -> ChromiumBrowser {
+  let rel = c.user_data.trim().replace('/', "\\");
+  let (base, rel) = match strip_prefix_ignore_case(&rel, "%APPDATA%\\") {
+    Some(r) => (AppDataDir::Roaming, r.to_string()),
+    None => (
+      AppDataDir::Local,
+      strip_prefix_ignore_case(&rel, "%LOCALAPPDATA%\\")
+        .unwrap_or(&rel)
+        .to_string(),
+    ),
+  };
+  ChromiumBrowser {
+    name: c.name.trim().to_string(),
+    base,
+    user_data: rel.trim_matches('\\').to_string(),
+    images: c
+      .images
+      .iter()
+      .map(|i| i.trim().to_ascii_lowercase())
+      .filter(|i| !i.is_empty())
+      .collect(),
+    always: true,
+  }
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+  let head = s.get(..prefix.len())?;
+  head
+    .eq_ignore_ascii_case(prefix)
+    .then(|| &s[prefix.len()..])
+}
+
+/// The browsers to cover on this machine.
+pub fn detect(users: &[UserProfile]) -> Vec<ChromiumBrowser> {
+  let cfg = config();
+  let registered = if cfg.auto_discover {
+    registered_browser_images()
+  } else {
+    Vec::new()
+  };
+  detect_with(&cfg, users, &registered)
+}
+
+fn detect_with(
+  cfg: &BrowsersConfig,
+  users: &[UserProfile],
+  registered: &[String],
+) -> Vec<ChromiumBrowser> {
+  candidates_for(cfg)
+    .into_iter()
+    .filter(|b| {
+      b.always
+        || (cfg.auto_discover
+          && (b.images.iter().any(|i| registered.contains(i))
+            || users
+              .iter()
+              .any(|u| b.user_data_dirs(u).iter().any(|d| d.is_dir()))))
+    })
+    .collect()
+}
+
+/// Executable file names (lowercase) of browsers registered with Windows.
+#[cfg(windows)]
+fn registered_browser_images() -> Vec<String> {
+  let Ok(output) = Command::new("reg")
+    .args(["query", "HKLM\\SOFTWARE\\Clients\\StartMenuInternet", "/s"])
+    .output()
+  else {
+    return Vec::new();
+  };
+  if !output.status.success() {
+    return Vec::new();
+  }
+  parse_registered_images(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(not(windows))]
+fn registered_browser_images() -> Vec<String> {
+  Vec::new()
+}
+
+// Every `...\shell\open\command` default value is a quoted executable path, optionally followed
+// by arguments.
+#[cfg_attr(not(any(windows, test)), allow(dead_code))]
+fn parse_registered_images(stdout: &str) -> Vec<String> {
+  let mut out: Vec<String> = stdout
+    .lines()
+    .filter_map(|l| {
+      let (_, data) = l.split_once("REG_SZ")?;
+      let data = data.trim().to_ascii_lowercase();
+      let exe_end = data.find(".exe")? + ".exe".len();
+      let exe = data[..exe_end].trim_start_matches('"');
+      Some(exe.rsplit(['\\', '/']).next()?.to_string())
+    })
+    .collect();
+  out.sort();
+  out.dedup();
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detects_installed_forks_and_applies_overrides() {
+    let base = std::env::temp_dir().join(format!("aid-browsers-{}", uuid::Uuid::new_v4()));
+    let user = UserProfile {
+      name: "alice".to_string(),
+      home: base.clone(),
+      local_appdata: base.join("Local"),
+      appdata: base.join("Roaming"),
+    };
+    fs::create_dir_all(user.local_appdata.join("Vivaldi\\User Data")).unwrap();
+    fs::create_dir_all(user.local_appdata.join("Packages")).unwrap();
+
+    let names = |cfg: &BrowsersConfig, registered: &[&str]| -> Vec<String> {
+      let registered: Vec<String> = registered.iter().map(|r| r.to_string()).collect();
+      detect_with(cfg, std::slice::from_ref(&user), &registered)
+        .into_iter()
+        .map(|b| b.name)
+        .collect()
+    };
+    assert_eq!(
+      names(&BrowsersConfig::default(), &["opera.exe"]),
+      ["Chrome", "Edge", "Brave", "Vivaldi", "Opera", "Opera GX"]
+    );
+
+    let cfg = BrowsersConfig {
+      auto_discover: false,
+      disabled: vec!["brave".to_string()],
+      chromium: vec![ChromiumBrowserConfig {
+        name: "Thorium".to_string(),
+        user_data: "Thorium/User Data".to_string(),
+        images: vec!["Thorium.exe".to_string()],
+      }],
+    };
+    assert_eq!(names(&cfg, &["opera.exe"]), ["Chrome", "Edge", "Thorium"]);
+    let thorium = candidates_for(&cfg).pop().unwrap();
+    assert_eq!(thorium.user_data, "Thorium\\User Data");
+    assert!(thorium.has_image("C:\\Apps\\Thorium\\THORIUM.EXE"));
+
+    let reg = "HKEY_LOCAL_MACHINE\\SOFTWARE\\Clients\\StartMenuInternet\\Opera\\shell\\open\\command\r\n    \
+               (Default)    REG_SZ    \"C:\\Program Files\\Opera\\opera.exe\" --single-argument\r\n";
+    assert_eq!(parse_registered_images(reg), ["opera.exe"]);
+
+    let _ = fs::remove_dir_all(&base);
+  }
+}
@@ -1,4 +1,8 @@
+use crate::types::{Incident, Severity};
+use defender_common::fs::write_atomic;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::path::Path;
 
@@ -11,6 +15,27 @@ pub struct Config {
   pub allowlist: AllowlistConfig,
   pub protected: ProtectedConfig,
   pub threat_feed: ThreatFeedConfig,
+  pub collector: CollectorConfig,
+  pub circuit_breaker: CircuitBreakerConfig,
+  pub baseline: BaselineConfig,
+  pub engine_state: EngineStateConfig,
+  pub rules: RulesConfig,
+  pub response: ResponseConfig,
+  pub recording: RecordingConfig,
+  pub notifications: NotificationsConfig,
+  pub machine: MachineConfig,
+  pub status_api: StatusApiConfig,
+  pub scanner: ScannerConfig,
+  pub watch: WatchConfig,
+  pub service: ServiceConfig,
+  pub self_protection: SelfProtectionConfig,
+  pub ransomware: RansomwareConfig,
+  pub metrics: MetricsConfig,
+  pub browsers: BrowsersConfig,
+  pub scoring: ScoringConfig,
+  pub license: LicenseConfig,
+  /// Set when a signed config profile is installed; its sections override local config.
+  pub managed: Option<ManagedProfile>,
 }
 
 impl Default for Config {
@@ -23,10 +48,97 @@ impl Default for Config {
       allowlist: AllowlistConfig::default(),
       protected: ProtectedConfig::default(),
       threat_feed: ThreatFeedConfig::default(),
+      collector: CollectorConfig::default(),
+      circuit_breaker: CircuitBreakerConfig::default(),
+      baseline: BaselineConfig::default(),
+      engine_state: EngineStateConfig::default(),
+      rules: RulesConfig::default(),
+      response: ResponseConfig::default(),
+      recording: RecordingConfig::default(),
+      notifications: NotificationsConfig::default(),
+      machine: MachineConfig::default(),
+      status_api: StatusApiConfig::default(),
+      scanner: ScannerConfig::default(),
+      watch: WatchConfig::default(),
+      service: ServiceConfig::default(),
+      self_protection: SelfProtectionConfig::default(),
+      ransomware: RansomwareConfig::default(),
+      metrics: MetricsConfig::default(),
+      browsers: BrowsersConfig::default(),
+      scoring: ScoringConfig::default(),
+      license: LicenseConfig::default(),
+      managed: None,
     }
   }
 }
 
+impl Config {
+  /// This config with the tag-scoped `[[allowlist.scoped]]` and `[[rules.scoped]]` entries that
+  /// match `machine.tags` merged into the plain lists. Borrowed when nothing scoped applies.
+  pub fn for_machine(&self) -> Cow<'_, Config> {
+    let tags = &self.machine.tags;
+    let allow: Vec<&ScopedAllowlist> = self
+      .allowlist
+      .scoped
+      .iter()
+      .filter(|s| tags_match(&s.tags, &s.exclude_tags, tags))
+      .collect();
+    let rules: Vec<&ScopedRules> = self
+      .rules
+      .scoped
+      .iter()
+      .filter(|s| tags_match(&s.tags, &s.exclude_tags, tags))
+      .collect();
+    if allow.is_empty() && rules.is_empty() {
+      return Cow::Borrowed(self);
+    }
+
+    let mut out = self.clone();
+    for s in allow {
+      let a = &mut out.allowlist;
+      a.publishers.extend(s.publishers.iter().cloned());
+      a.paths_allowlist.extend(s.paths_allowlist.iter().cloned());
+      a.hashes.extend(s.hashes.iter().cloned());
+      a.destinations.extend(s.destinations.iter().cloned());
+    }
+    for s in rules {
+      out.rules.disabled.extend(s.disabled.iter().cloned());
+      for (id, sev) in &s.severity {
+        out
+          .rules
+          .severity
+          .retain(|r, _| !r.trim().eq_ignore_ascii_case(id));
+        out.rules.severity.insert(id.clone(), *sev);
+      }
+    }
+    Cow::Owned(out)
+  }
+}
+
+/// Whether an entry scoped by `tags` / `exclude_tags` applies to a machine with `machine` tags:
+/// no include tags means every machine, otherwise at least one must match; any exclude tag wins.
+pub fn tags_match(tags: &[String], exclude_tags: &[String], machine: &[String]) -> bool {
+  let has = |t: &String| machine.iter().any(|m| m.eq_ignore_ascii_case(t.trim()));
+  (tags.is_empty() || tags.iter().any(has)) && !exclude_tags.iter().any(has)
+}
+
+/// Local facts about this machine that shared policy can key on.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MachineConfig {
+  /// Free-form tags (e.g. `"build-server"`, `"kiosk"`) matched by `tags` / `exclude_tags` on
+  /// scoped allowlist entries, scoped rule settings and threat feed rule overrides.
+  #[serde(default)]
+  pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ManagedProfile {
+  pub profile_id: String,
+  pub organization: String,
+  pub created_at: u64,
+  pub sections: Vec<String>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Mode {
@@ -41,6 +153,18 @@ pub struct LoggingConfig {
 
   #[serde(default = "default_retention_days")]
   pub retention_days: u64,
+
+  /// A log file is rotated when it reaches this size, as well as daily. 0 = daily only.
+  #[serde(default = "default_log_max_file_mb")]
+  pub max_file_mb: u64,
+
+  /// Gzip rotated log files.
+  #[serde(default = "default_true")]
+  pub compress: bool,
+
+  /// The oldest rotated log files are deleted while the logs directory is larger. 0 = no cap.
+  #[serde(default = "default_log_max_total_mb")]
+  pub max_total_mb: u64,
 }
 
 fn default_log_level() -> String {
@@ -51,11 +175,22 @@ fn default_retention_days() -> u64 {
   14
 }
 
+fn default_log_max_file_mb() -> u64 {
+  50
+}
+
+fn default_log_max_total_mb() -> u64 {
+  500
+}
+
 impl Default for LoggingConfig {
   fn default() -> Self {
     Self {
       level: default_log_level(),
       retention_days: default_retention_days(),
+      max_file_mb: default_log_max_file_mb(),
+      compress: true,
+      max_total_mb: default_log_max_total_mb(),
     }
   }
 }
@@ -70,6 +205,11 @@ pub struct KillSwitchConfig {
 
   #[serde(default = "default_failsafe_minutes")]
   pub failsafe_minutes: u64,
+
+  /// Remote addresses (IPv4/IPv6 or CIDR ranges) the kill switch never blocks, e.g. DHCP and DNS
+  /// servers or domain controllers.
+  #[serde(default)]
+  pub always_allow: Vec<String>,
 }
 
 impl Default for KillSwitchConfig {
@@ -78,6 +218,7 @@ impl Default for KillSwitchConfig {
       enabled: true,
       auto_trigger: true,
       failsafe_minutes: default_failsafe_minutes(),
+      always_allow: Vec::new(),
     }
   }
 }
@@ -89,6 +230,44 @@ pub struct AllowlistConfig {
 
   #[serde(default)]
   pub paths_allowlist: Vec<String>,
+
+  /// SHA-256 hashes (hex) of specific binaries whose activity is never reported.
+  #[serde(default)]
+  pub hashes: Vec<String>,
+
+  /// Network destinations (IPv4/IPv6 addresses or CIDR ranges) never used for correlation.
+  #[serde(default)]
+  pub destinations: Vec<String>,
+
+  /// Skip correlation findings for loopback destinations (e.g. a local helper process).
+  #[serde(default = "default_true")]
+  pub exclude_loopback_destinations: bool,
+
+  /// Skip correlation findings for RFC1918 / link-local / IPv6 unique-local destinations.
+  #[serde(default = "default_true")]
+  pub exclude_private_destinations: bool,
+
+  /// Entries that only apply on machines with matching tags (see `[machine]`).
+  #[serde(default)]
+  pub scoped: Vec<ScopedAllowlist>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScopedAllowlist {
+  /// Applies only on machines with at least one of these tags (empty: every machine).
+  #[serde(default)]
+  pub tags: Vec<String>,
+  /// Never applies on machines with any of these tags.
+  #[serde(default)]
+  pub exclude_tags: Vec<String>,
+  #[serde(default)]
+  pub publishers: Vec<String>,
+  #[serde(default)]
+  pub paths_allowlist: Vec<String>,
+  #[serde(default)]
+  pub hashes: Vec<String>,
+  #[serde(default)]
+  pub destinations: Vec<String>,
 }
 
 impl Default for AllowlistConfig {
@@ -96,6 +275,11 @@ impl Default for AllowlistConfig {
     Self {
       publishers: default_allowlist_publishers(),
       paths_allowlist: Vec::new(),
+      hashes: Vec::new(),
+      destinations: Vec::new(),
+      exclude_loopback_destinations: true,
+      exclude_private_destinations: true,
+      scoped: Vec::new(),
     }
   }
 }
@@ -107,6 +291,11 @@ pub struct ProtectedConfig {
 
   #[serde(default = "default_firefox_targets")]
   pub firefox_targets: Vec<String>,
+
+  /// Non-browser credential stores to protect: `ssh`, `crypto_wallets`, `credential_manager`,
+  /// `cloud_cli`, `vpn` (rules R018-R022).
+  #[serde(default = "default_protected_groups")]
+  pub groups: Vec<String>,
 }
 
 impl Default for ProtectedConfig {
@@ -114,6 +303,7 @@ impl Default for ProtectedConfig {
     Self {
       chrome_targets: default_chrome_targets(),
       firefox_targets: default_firefox_targets(),
+      groups: default_protected_groups(),
     }
   }
 }
@@ -134,6 +324,33 @@ pub struct ThreatFeedConfig {
 
   #[serde(default = "default_threat_feed_timeout_seconds")]
   pub timeout_seconds: u64,
+
+  /// Skip scheduled refreshes while the active connection is metered (cellular, metered Wi-Fi).
+  /// Manual `--feed refresh-now` still runs.
+  #[serde(default)]
+  pub defer_on_metered: bool,
+
+  /// Additional Ed25519 public key (base64url) trusted for bundle verification, for organizations
+  /// signing their own private feed. Vendor-signed bundles are still accepted.
+  #[serde(default)]
+  pub org_public_key: Option<String>,
+
+  /// Same as `org_public_key`, read from a file. Ignored when `org_public_key` is set.
+  #[serde(default)]
+  pub org_public_key_file: Option<String>,
+
+  /// HTTP(S) proxy for feed downloads, e.g. `"http://proxy.corp.example:8080"`. Overrides the
+  /// system proxy.
+  #[serde(default)]
+  pub proxy_url: Option<String>,
+
+  /// Name of an environment variable holding proxy credentials as `user:password`.
+  #[serde(default)]
+  pub proxy_auth_env: Option<String>,
+
+  /// Without `proxy_url`, use the system proxy (environment variables, WinHTTP, Internet Options).
+  #[serde(default = "default_true")]
+  pub use_system_proxy: bool,
 }
 
 impl Default for ThreatFeedConfig {
@@ -144,70 +361,1169 @@ impl Default for ThreatFeedConfig {
       endpoints: default_threat_feed_endpoints(),
       allowlist_domains: default_threat_feed_allowlist_domains(),
       timeout_seconds: default_threat_feed_timeout_seconds(),
+      defer_on_metered: false,
+      org_public_key: None,
+      org_public_key_file: None,
+      proxy_url: None,
+      proxy_auth_env: None,
+      use_system_proxy: true,
     }
   }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct LegacySafetyConfig {
+/// Noise filters applied by the event collector before events reach the rules engine.
+/// Empty lists (the default) keep every event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectorConfig {
+  /// File events whose path ends with one of these extensions are dropped (e.g. `".log"`).
   #[serde(default)]
-  pub strict_mode: bool,
+  pub ignore_file_extensions: Vec<String>,
+
+  /// Network events from these images are dropped. Entries match a full image path or a bare
+  /// file name (e.g. `"OneDrive.exe"`), case-insensitively.
+  #[serde(default)]
+  pub ignore_net_images: Vec<String>,
+
+  /// Sysmon or PowerShell event IDs left out of the event log queries, so their records are never
+  /// fetched or parsed (e.g. `[11]` where file creates are too many to be worth it). Leaving out
+  /// 1 or 5 breaks process trees.
+  #[serde(default)]
+  pub exclude_event_ids: Vec<u32>,
+
+  /// Events from processes whose image path starts with one of these (case-insensitive, e.g.
+  /// `"C:\\Program Files\\Backup\\"`) are dropped. Process starts and exits are always kept, so
+  /// process trees stay whole.
+  #[serde(default)]
+  pub exclude_image_prefixes: Vec<String>,
+
+  /// Exceptions to `exclude_image_prefixes`: events from images starting with one of these are
+  /// kept.
+  #[serde(default)]
+  pub include_image_prefixes: Vec<String>,
+
+  /// File events for paths in one of these directories or below are dropped.
+  #[serde(default)]
+  pub exclude_file_dirs: Vec<String>,
+
+  /// Exceptions to `exclude_file_dirs`: file events in one of these directories or below are
+  /// kept.
+  #[serde(default)]
+  pub include_file_dirs: Vec<String>,
+
+  /// Samples clipboard changes for clipboard hijack detection (R024). Only crypto addresses are
+  /// ever read from the clipboard; needs the agent to run in a user session.
+  #[serde(default = "default_true")]
+  pub clipboard: bool,
+
+  /// Reads PowerShell script block logging (Event ID 4104) for R027-R030.
+  #[serde(default = "default_true")]
+  pub powershell: bool,
+
+  /// Reads file writes, renames and deletes from the NTFS change journal of the system volume
+  /// when Sysmon is unavailable. The journal does not record which process made a change.
+  #[serde(default = "default_true")]
+  pub usn_journal: bool,
+
+  /// Watches browser profiles, document folders and the hosts file's folder for changes
+  /// (`ReadDirectoryChangesW`) when Sysmon is unavailable, instead of reading the USN journal.
+  /// Lighter and near-real-time, but also without the process behind a change.
+  #[serde(default)]
+  pub directory_watch: bool,
+
+  /// Polls the system TCP tables for new outbound connections when Sysmon is unavailable.
+  #[serde(default = "default_true")]
+  pub tcp_table: bool,
+
+  /// Names network connections without a destination host from the DNS answers seen in DNS
+  /// query events.
+  #[serde(default = "default_true")]
+  pub resolve_hosts: bool,
+
+  /// Also looks up the reverse DNS name of addresses no DNS answer named (needs
+  /// `resolve_hosts`). A few lookups per poll; each can block the collector briefly.
+  #[serde(default)]
+  pub reverse_dns: bool,
+
+  /// Replaces the publisher of new processes (Sysmon's `Company`, taken from the version resource
+  /// and free to forge) with the signer of the image's verified Authenticode signature. Unsigned
+  /// images get no publisher.
+  #[serde(default = "default_true")]
+  pub verify_publishers: bool,
+
+  /// Most event log records read, and events handed to the rules engine, per poll. A burst
+  /// larger than this is worked off over the following polls.
+  #[serde(default = "default_collector_max_records_per_tick")]
+  pub max_records_per_tick: usize,
+
+  /// Events the collector holds between reading and handing them on. Event log reads stop when
+  /// it is full (the records stay in the log); sources that can't wait (directory watch, USN
+  /// journal, TCP table, clipboard) lose what doesn't fit.
+  #[serde(default = "default_collector_queue_capacity")]
+  pub queue_capacity: usize,
 }
 
-fn default_true() -> bool {
-  true
+impl Default for CollectorConfig {
+  fn default() -> Self {
+    Self {
+      ignore_file_extensions: Vec::new(),
+      ignore_net_images: Vec::new(),
+      exclude_event_ids: Vec::new(),
+      exclude_image_prefixes: Vec::new(),
+      include_image_prefixes: Vec::new(),
+      exclude_file_dirs: Vec::new(),
+      include_file_dirs: Vec::new(),
+      clipboard: true,
+      powershell: true,
+      usn_journal: true,
+      directory_watch: false,
+      tcp_table: true,
+      resolve_hosts: true,
+      reverse_dns: false,
+      verify_publishers: true,
+      max_records_per_tick: default_collector_max_records_per_tick(),
+      queue_capacity: default_collector_queue_capacity(),
+    }
+  }
 }
 
-fn default_failsafe_minutes() -> u64 {
-  10
+/// Per-rule incident rate limit. A rule producing more than `max_incidents` incidents within
+/// `window_minutes` is suppressed (log-only) until the agent restarts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+  #[serde(default = "default_true")]
+  pub enabled: bool,
+
+  #[serde(default = "default_circuit_breaker_max_incidents")]
+  pub max_incidents: usize,
+
+  #[serde(default = "default_circuit_breaker_window_minutes")]
+  pub window_minutes: u64,
 }
 
-fn default_correlation_window_seconds() -> u64 {
-  120
+impl Default for CircuitBreakerConfig {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      max_incidents: default_circuit_breaker_max_incidents(),
+      window_minutes: default_circuit_breaker_window_minutes(),
+    }
+  }
 }
 
-fn default_refresh_interval_minutes() -> u64 {
-  60
+/// Learning-mode baseline: recurring (publisher, image, protected target) tuples seen during the
+/// learning period become allowlist suggestions for `--baseline review`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaselineConfig {
+  #[serde(default = "default_true")]
+  pub enabled: bool,
+
+  #[serde(default = "default_baseline_learning_period_days")]
+  pub learning_period_days: u64,
+
+  #[serde(default = "default_baseline_min_occurrences")]
+  pub min_occurrences: u64,
 }
 
-fn default_threat_feed_endpoints() -> Vec<String> {
-  vec!["https://updates.aidefender.shop/feed/".to_string()]
+impl Default for BaselineConfig {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      learning_period_days: default_baseline_learning_period_days(),
+      min_occurrences: default_baseline_min_occurrences(),
+    }
+  }
 }
 
-fn default_threat_feed_allowlist_domains() -> Vec<String> {
-  vec!["updates.aidefender.shop".to_string()]
+/// Correlation state (process lineage, sensitive accesses) saved while the agent runs and taken
+/// back after a restart, so a read before the restart still correlates with a connection after it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineStateConfig {
+  #[serde(default = "default_true")]
+  pub enabled: bool,
+
+  /// A snapshot older than this at startup is ignored.
+  #[serde(default = "default_engine_state_max_age_seconds")]
+  pub max_age_seconds: u64,
+
+  /// How often the snapshot is rewritten while events come in.
+  #[serde(default = "default_engine_state_save_interval_seconds")]
+  pub save_interval_seconds: u64,
 }
 
-fn default_threat_feed_timeout_seconds() -> u64 {
-  10
+impl Default for EngineStateConfig {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      max_age_seconds: default_engine_state_max_age_seconds(),
+      save_interval_seconds: default_engine_state_save_interval_seconds(),
+    }
+  }
 }
 
-fn default_allowlist_publishers() -> Vec<String> {
-  vec![
-    "Microsoft Windows".to_string(),
-    "Google LLC".to_string(),
-    "Mozilla Corporation".to_string(),
-  ]
+/// Automatic follow-up actions that don't contain anything (containment lives under `[killswitch]`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseConfig {
+  /// On YELLOW incidents, scan the folders of implicated binaries with the scanner and merge the
+  /// results into the incident.
+  #[serde(default = "default_true")]
+  pub targeted_scan: bool,
+
+  /// Minimum time before the same folder is scanned again.
+  #[serde(default = "default_targeted_scan_cooldown_minutes")]
+  pub targeted_scan_cooldown_minutes: u64,
+
+  /// What the agent does for an incident, by severity (`[response.severity]`).
+  #[serde(default)]
+  pub severity: SeverityActions,
+
+  /// Per-rule replacement for the severity's actions (`[response.rules]`, e.g. `R009 = ["log"]`).
+  #[serde(default)]
+  pub rules: BTreeMap<String, Vec<ResponseAction>>,
+
+  #[serde(default)]
+  pub dedup: DedupConfig,
 }
 
-fn default_chrome_targets() -> Vec<String> {
-  vec![
-    "Login Data".to_string(),
-    "Cookies".to_string(),
-    "Local State".to_string(),
-  ]
+/// Flood control for noisy processes (`[response.dedup]`): a finding repeating one from the last
+/// `window_seconds` (same PID, rule and target) is counted on the earlier incident instead of
+/// raising a new one, and each PID gets at most `max_incidents_per_pid` new incidents per minute.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupConfig {
+  #[serde(default = "default_true")]
+  pub enabled: bool,
+
+  #[serde(default = "default_dedup_window_seconds")]
+  pub window_seconds: u64,
+
+  #[serde(default = "default_dedup_max_incidents_per_pid")]
+  pub max_incidents_per_pid: usize,
+}
+
+impl Default for DedupConfig {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      window_seconds: default_dedup_window_seconds(),
+      max_incidents_per_pid: default_dedup_max_incidents_per_pid(),
+    }
+  }
+}
+
+impl Default for ResponseConfig {
+  fn default() -> Self {
+    Self {
+      targeted_scan: true,
+      targeted_scan_cooldown_minutes: default_targeted_scan_cooldown_minutes(),
+      severity: SeverityActions::default(),
+      rules: BTreeMap::new(),
+      dedup: DedupConfig::default(),
+    }
+  }
+}
+
+impl ResponseConfig {
+  /// Actions for `incident`: those of each finding's rule in `rules`, plus the incident
+  /// severity's actions if any finding's rule has none configured.
+  pub fn actions_for(&self, incident: &Incident) -> BTreeSet<ResponseAction> {
+    let mut out = BTreeSet::new();
+    let mut by_severity = incident.findings.is_empty();
+    for f in &incident.findings {
+      match self.rule_actions(&f.rule_id) {
+        Some(actions) => out.extend(actions.iter().copied()),
+        None => by_severity = true,
+      }
+    }
+    if by_severity {
+      out.extend(self.severity.get(incident.severity).iter().copied());
+    }
+    out
+  }
+
+  fn rule_actions(&self, rule_id: &str) -> Option<&[ResponseAction]> {
+    self
+      .rules
+      .iter()
+      .find(|(r, _)| r.trim().eq_ignore_ascii_case(rule_id))
+      .map(|(_, a)| a.as_slice())
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseAction {
+  /// Record the incident (agent log and incident store). Always included.
+  Log,
+  /// Tray notification for the logged-on user and admin alerts (`[notifications]`).
+  Notify,
+  /// Terminate the implicated process.
+  ContainProcess,
+  /// Enable the network kill switch, subject to `mode` and `[killswitch]`.
+  Killswitch,
+  /// Move the implicated executable to quarantine.
+  Quarantine,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeverityActions {
+  #[serde(default = "default_log_actions")]
+  pub green: Vec<ResponseAction>,
+
+  #[serde(default = "default_log_actions")]
+  pub yellow: Vec<ResponseAction>,
+
+  #[serde(default = "default_red_actions")]
+  pub red: Vec<ResponseAction>,
+}
+
+impl Default for SeverityActions {
+  fn default() -> Self {
+    Self {
+      green: default_log_actions(),
+      yellow: default_log_actions(),
+      red: default_red_actions(),
+    }
+  }
+}
+
+impl SeverityActions {
+  pub fn get(&self, severity: Severity) -> &[ResponseAction] {
+    match severity {
+      Severity::Green => &self.green,
+      Severity::Yellow => &self.yellow,
+      Severity::Red => &self.red,
+    }
+  }
+}
+
+/// How `scanner.exe` scans, and periodic scans run by the agent service. No schedule (the
+/// default) means scans only run when started by hand or as a targeted follow-up.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScannerConfig {
+  /// `every <N>m|h|d`, `daily HH:MM` or `weekly <mon..sun> HH:MM` (local time).
+  #[serde(default)]
+  pub schedule: Option<String>,
+
+  #[serde(default)]
+  pub mode: ScheduledScanMode,
+
+  #[serde(default)]
+  pub archives: ArchiveScanConfig,
+
+  #[serde(default)]
+  pub full: FullScanConfig,
+
+  /// Paths every scan skips: case-insensitive prefixes, or globs (`C:\Dev\**\target\**`).
+  #[serde(default)]
+  pub exclude: Vec<String>,
+
+  /// SHA-256 digests of executables every scan skips.
+  #[serde(default)]
+  pub exclude_hashes: Vec<String>,
+
+  /// A file of more digests to skip, one per line (`#` starts a comment).
+  #[serde(default)]
+  pub exclude_hashes_file: Option<String>,
+
+  /// Files larger than this are not hashed or signature-checked, in every scan type; 0 means no
+  /// limit. `[scanner.full] max_hash_mb`, when set, takes precedence for full scans.
+  #[serde(default)]
+  pub max_file_mb: u64,
+
+  /// Files hashed and signature-checked in parallel; 0 picks half the logical processors (1-4).
+  #[serde(default)]
+  pub threads: usize,
+
+  /// Walk into junctions, symlinks and other reparse points. Off by default: they can pull a
+  /// second copy of a folder into the walk, and cloud-only placeholders are downloaded when read.
+  #[serde(default)]
+  pub follow_reparse_points: bool,
+}
+
+pub const MAX_SCANNER_THREADS: usize = 16;
+
+/// Archive inspection (`[scanner.archives]`): zip and 7z archives in Downloads, Desktop, Temp and
+/// AppData are listed and flagged when they contain executable content. Off by default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveScanConfig {
+  #[serde(default)]
+  pub enabled: bool,
+
+  /// Archives larger than this are skipped.
+  #[serde(default = "default_archive_max_mb")]
+  pub max_archive_mb: u64,
+
+  /// Entries listed per archive; the rest are ignored.
+  #[serde(default = "default_archive_max_entries")]
+  pub max_entries: usize,
+
+  /// Extract archives to a temporary sandbox directory to hash and signature-check executables,
+  /// and to look inside nested archives.
+  #[serde(default)]
+  pub deep_extract: bool,
+
+  /// Nesting levels inspected with `deep_extract` (1 = only the archive itself).
+  #[serde(default = "default_archive_max_depth")]
+  pub max_depth: usize,
+
+  /// Total uncompressed size extracted per archive.
+  #[serde(default = "default_archive_max_extract_mb")]
+  pub max_extract_mb: u64,
+}
+
+impl Default for ArchiveScanConfig {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      max_archive_mb: default_archive_max_mb(),
+      max_entries: default_archive_max_entries(),
+      deep_extract: false,
+      max_depth: default_archive_max_depth(),
+      max_extract_mb: default_archive_max_extract_mb(),
+    }
+  }
+}
+
+/// What full scans walk (`[scanner.full]`). Fixed drives are always scanned; removable and
+/// network drives only when enabled, since a disconnected mapping can hang the walk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FullScanConfig {
+  #[serde(default)]
+  pub removable: bool,
+
+  #[serde(default)]
+  pub network: bool,
+
+  /// Files walked per drive before moving on to the next one; 0 means no limit.
+  #[serde(default)]
+  pub max_files_per_root: u64,
+
+  /// Files larger than this are not hashed or signature-checked; 0 means no limit.
+  #[serde(default)]
+  pub max_hash_mb: u64,
+
+  /// Skip junctions, symlinks and other reparse points, including cloud-only placeholders that
+  /// would be downloaded when read.
+  #[serde(default = "default_true")]
+  pub skip_reparse_points: bool,
+}
+
+impl Default for FullScanConfig {
+  fn default() -> Self {
+    Self {
+      removable: false,
+      network: false,
+      max_files_per_root: 0,
+      max_hash_mb: 0,
+      skip_reparse_points: true,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduledScanMode {
+  #[default]
+  Quick,
+  Full,
+}
+
+/// Captures collected events to `recordings\events.ndjson` (user names redacted) so a session can
+/// be shared and replayed with `--replay`. Off by default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingConfig {
+  #[serde(default)]
+  pub enabled: bool,
+
+  /// Disk budget for the current file plus the one rotated copy.
+  #[serde(default = "default_recording_max_mb")]
+  pub max_mb: u64,
+}
+
+impl Default for RecordingConfig {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      max_mb: default_recording_max_mb(),
+    }
+  }
+}
+
+/// Enhanced monitoring for targets added with `--watch add`: every event touching one is logged
+/// in full to `watch\events.ndjson`, whatever the rules decide.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchConfig {
+  /// How long a target stays watched when `--watch add` gets no `--minutes`.
+  #[serde(default = "default_watch_minutes")]
+  pub default_minutes: u64,
+
+  /// Disk budget for the current watch log plus the one rotated copy.
+  #[serde(default = "default_watch_max_mb")]
+  pub max_mb: u64,
+}
+
+impl Default for WatchConfig {
+  fn default() -> Self {
+    Self {
+      default_minutes: default_watch_minutes(),
+      max_mb: default_watch_max_mb(),
+    }
+  }
+}
+
+/// How the Windows service is laid out. With `privilege_split` the service process keeps only the
+/// privileged duties (firewall, failsafe, containment requests) and runs event parsing, the rules
+/// engine and feed downloads in a worker process with its token privileges removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceConfig {
+  #[serde(default = "default_true")]
+  pub privilege_split: bool,
+
+  /// Restart the agent loop in-process when it fails, and exit for the service manager to restart
+  /// the agent when it stops ticking.
+  #[serde(default = "default_true")]
+  pub watchdog: bool,
+
+  /// A loop that hasn't completed a tick for this long counts as hung.
+  #[serde(default = "default_watchdog_hang_seconds")]
+  pub watchdog_hang_seconds: u64,
+}
+
+impl Default for ServiceConfig {
+  fn default() -> Self {
+    Self {
+      privilege_split: true,
+      watchdog: true,
+      watchdog_hang_seconds: default_watchdog_hang_seconds(),
+    }
+  }
+}
+
+fn default_watchdog_hang_seconds() -> u64 {
+  180
+}
+
+/// Tamper detection for AI Defender's own files and process: restrictive ACLs on the data
+/// directory, a seal over `config.toml`, and a check for agent runs that ended without a clean
+/// stop. Tampering raises a RED incident.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfProtectionConfig {
+  #[serde(default = "default_true")]
+  pub enabled: bool,
+
+  /// Re-apply the data directory ACLs when they were changed behind the agent's back.
+  #[serde(default = "default_true")]
+  pub repair_acls: bool,
+
+  /// Record every incident write in an HMAC-chained log (see `incident_chain`), checked by
+  /// `--incidents verify`.
+  #[serde(default = "default_true")]
+  pub chain_incidents: bool,
+}
+
+impl Default for SelfProtectionConfig {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      repair_acls: true,
+      chain_incidents: true,
+    }
+  }
+}
+
+/// Ransomware behavior rule (R025): a burst of file writes/deletes by one process under users'
+/// document folders (Documents, Desktop, Pictures, ...). A burst alone is YELLOW; it escalates to
+/// RED when most new files carry a non-document extension (an appended ransom extension or random
+/// per-file extensions).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RansomwareConfig {
+  #[serde(default = "default_true")]
+  pub enabled: bool,
+
+  /// Writes/deletes by one process within `window_seconds` that make a burst.
+  #[serde(default = "default_ransomware_min_files")]
+  pub min_files: usize,
+
+  #[serde(default = "default_ransomware_window_seconds")]
+  pub window_seconds: u64,
+
+  /// Share (0.0-1.0) of written files in a burst with a non-document extension needed for RED.
+  #[serde(default = "default_ransomware_min_new_extension_ratio")]
+  pub min_new_extension_ratio: f64,
+}
+
+impl Default for RansomwareConfig {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      min_files: default_ransomware_min_files(),
+      window_seconds: default_ransomware_window_seconds(),
+      min_new_extension_ratio: default_ransomware_min_new_extension_ratio(),
+    }
+  }
+}
+
+fn default_ransomware_min_files() -> usize {
+  40
+}
+
+fn default_ransomware_window_seconds() -> u64 {
+  20
+}
+
+fn default_ransomware_min_new_extension_ratio() -> f64 {
+  0.5
+}
+
+/// Prometheus text exposition of the agent's counters. The status API serves it at `/metrics`
+/// when enabled; `write_file` also writes it to a file every `interval_seconds` for a textfile
+/// collector.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsConfig {
+  #[serde(default)]
+  pub write_file: bool,
+
+  /// Defaults to `metrics.prom` in the ProgramData folder.
+  #[serde(default)]
+  pub file: Option<String>,
+
+  #[serde(default = "default_metrics_interval_seconds")]
+  pub interval_seconds: u64,
+}
+
+impl Default for MetricsConfig {
+  fn default() -> Self {
+    Self {
+      write_file: false,
+      file: None,
+      interval_seconds: default_metrics_interval_seconds(),
+    }
+  }
+}
+
+fn default_metrics_interval_seconds() -> u64 {
+  15
+}
+
+/// Which Chromium-based browsers get protected profile roots and count as browsers for owner
+/// access (see `browsers`). Chrome, Edge and Brave are always included; other known forks
+/// (Vivaldi, Opera, Chromium, Arc, ...) are added when found on disk or in the registry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BrowsersConfig {
+  #[serde(default = "default_true")]
+  pub auto_discover: bool,
+
+  /// Built-in browsers to leave out, by name.
+  #[serde(default)]
+  pub disabled: Vec<String>,
+
+  /// Additional Chromium-based browsers, always included.
+  #[serde(default)]
+  pub chromium: Vec<ChromiumBrowserConfig>,
+}
+
+impl Default for BrowsersConfig {
+  fn default() -> Self {
+    Self {
+      auto_discover: true,
+      disabled: Vec::new(),
+      chromium: Vec::new(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChromiumBrowserConfig {
+  pub name: String,
+  /// The `User Data` directory, relative to `%LOCALAPPDATA%`, or to `%APPDATA%` when prefixed with
+  /// `%APPDATA%\`. A `*` path segment matches any directory name.
+  pub user_data: String,
+  /// Executable file names, e.g. `thorium.exe`.
+  #[serde(default)]
+  pub images: Vec<String>,
+}
+
+/// Risk scores (0-100) for findings and incidents (see `scoring`). Scores are always recorded;
+/// with `enabled` the incident severity comes from the thresholds instead of the highest finding
+/// severity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoringConfig {
+  #[serde(default)]
+  pub enabled: bool,
+
+  #[serde(default = "default_scoring_yellow")]
+  pub yellow: u32,
+
+  #[serde(default = "default_scoring_red")]
+  pub red: u32,
+
+  /// Base score per rule ID, replacing the default for the finding's severity.
+  #[serde(default)]
+  pub rules: BTreeMap<String, u32>,
+
+  #[serde(default)]
+  pub weights: ScoringWeights,
+}
+
+impl Default for ScoringConfig {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      yellow: default_scoring_yellow(),
+      red: default_scoring_red(),
+      rules: BTreeMap::new(),
+      weights: ScoringWeights::default(),
+    }
+  }
+}
+
+fn default_scoring_yellow() -> u32 {
+  40
+}
+
+fn default_scoring_red() -> u32 {
+  70
+}
+
+/// Points added to a finding's base score (negative values lower it).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScoringWeights {
+  /// Base score of a finding without a `[scoring.rules]` entry, by its severity.
+  #[serde(default = "default_weight_green")]
+  pub green: u32,
+  #[serde(default = "default_weight_yellow")]
+  pub yellow: u32,
+  #[serde(default = "default_weight_red")]
+  pub red: u32,
+  /// The process has no verified signer.
+  #[serde(default = "default_weight_unsigned")]
+  pub unsigned: i32,
+  /// The process image is in a user-writable folder (AppData, Temp, Downloads, Public).
+  #[serde(default = "default_weight_user_writable")]
+  pub user_writable: i32,
+  /// The finding comes from a threat-feed list (blocklisted or newly registered domain, drainer
+  /// pattern).
+  #[serde(default = "default_weight_reputation")]
+  pub reputation: i32,
+  /// The finding links a sensitive access to later network activity.
+  #[serde(default = "default_weight_correlation")]
+  pub correlation: i32,
+  /// Added to the incident score for every other rule in the incident.
+  #[serde(default = "default_weight_additional_rule")]
+  pub additional_rule: i32,
+}
+
+impl Default for ScoringWeights {
+  fn default() -> Self {
+    Self {
+      green: default_weight_green(),
+      yellow: default_weight_yellow(),
+      red: default_weight_red(),
+      unsigned: default_weight_unsigned(),
+      user_writable: default_weight_user_writable(),
+      reputation: default_weight_reputation(),
+      correlation: default_weight_correlation(),
+      additional_rule: default_weight_additional_rule(),
+    }
+  }
+}
+
+fn default_weight_green() -> u32 {
+  10
+}
+
+fn default_weight_yellow() -> u32 {
+  40
+}
+
+fn default_weight_red() -> u32 {
+  70
+}
+
+fn default_weight_unsigned() -> i32 {
+  10
+}
+
+fn default_weight_user_writable() -> i32 {
+  10
+}
+
+fn default_weight_reputation() -> i32 {
+  15
+}
+
+fn default_weight_correlation() -> i32 {
+  10
+}
+
+fn default_weight_additional_rule() -> i32 {
+  5
+}
+
+/// Read-only JSON status endpoint on 127.0.0.1 for local UIs and monitoring agents. Off by
+/// default; requests must carry the bearer token from `status-api.token`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusApiConfig {
+  #[serde(default)]
+  pub enabled: bool,
+
+  #[serde(default = "default_status_api_port")]
+  pub port: u16,
+}
+
+impl Default for StatusApiConfig {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      port: default_status_api_port(),
+    }
+  }
+}
+
+/// Where `--license activate` registers this device (see `license::activation`). Without an
+/// `activation_endpoint` activation is local only, so seats aren't counted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LicenseConfig {
+  /// HTTPS URL of the vendor's activation server, e.g. `"https://license.example.com/v1/"`.
+  #[serde(default)]
+  pub activation_endpoint: Option<String>,
+
+  /// Hosts `activation_endpoint` may point to.
+  #[serde(default)]
+  pub allowlist_domains: Vec<String>,
+
+  #[serde(default = "default_license_timeout_seconds")]
+  pub timeout_seconds: u64,
+}
+
+impl Default for LicenseConfig {
+  fn default() -> Self {
+    Self {
+      activation_endpoint: None,
+      allowlist_domains: Vec::new(),
+      timeout_seconds: default_license_timeout_seconds(),
+    }
+  }
+}
+
+/// Admin alerts for RED incidents and kill switch activation. Off by default; when enabled these
+/// are the only outbound calls besides the threat feed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+  #[serde(default)]
+  pub enabled: bool,
+
+  /// Alerts beyond this many per hour are dropped (logged) so an incident storm can't flood chat
+  /// channels or mailboxes.
+  #[serde(default = "default_notifications_max_per_hour")]
+  pub max_per_hour: u32,
+
+  /// Extra delivery attempts per channel after a failure.
+  #[serde(default = "default_notifications_retries")]
+  pub retries: u32,
+
+  #[serde(default = "default_notifications_timeout_seconds")]
+  pub timeout_seconds: u64,
+
+  #[serde(default)]
+  pub webhooks: Vec<WebhookConfig>,
+
+  #[serde(default)]
+  pub smtp: Option<SmtpConfig>,
+}
+
+impl Default for NotificationsConfig {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      max_per_hour: default_notifications_max_per_hour(),
+      retries: default_notifications_retries(),
+      timeout_seconds: default_notifications_timeout_seconds(),
+      webhooks: Vec::new(),
+      smtp: None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+  /// HTTPS URL the alert is POSTed to.
+  pub url: String,
+
+  #[serde(default)]
+  pub format: WebhookFormat,
+
+  /// Name of a secret (see `--secrets`) used to sign the body; sent as
+  /// `X-AI-Defender-Signature: sha256=<hex HMAC-SHA256>`.
+  #[serde(default)]
+  pub hmac_secret: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+  /// The full alert as JSON.
+  #[default]
+  Json,
+  /// Slack incoming webhook (`{"text": ...}`).
+  Slack,
+  /// Microsoft Teams incoming webhook (`{"text": ...}`).
+  Teams,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpConfig {
+  pub host: String,
+
+  #[serde(default = "default_smtp_port")]
+  pub port: u16,
+
+  /// `starttls` (usually port 587) or `tls` (implicit TLS, usually 465). Plaintext is not offered.
+  #[serde(default)]
+  pub security: SmtpSecurity,
+
+  #[serde(default)]
+  pub username: Option<String>,
+
+  /// Name of the secret holding the SMTP password.
+  #[serde(default)]
+  pub password_secret: Option<String>,
+
+  pub from: String,
+
+  pub to: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+  #[default]
+  Starttls,
+  Tls,
+}
+
+/// Per-rule overrides, keyed by rule ID. They take precedence over threat-feed rule overrides.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RulesConfig {
+  /// Rules that never produce findings.
+  #[serde(default)]
+  pub disabled: Vec<String>,
+
+  /// Replacement severity per rule (`green`, `yellow`, `red`).
+  #[serde(default)]
+  pub severity: BTreeMap<String, Severity>,
+
+  /// Settings that only apply on machines with matching tags; added to `disabled`, and their
+  /// severities replace the plain ones.
+  #[serde(default)]
+  pub scoped: Vec<ScopedRules>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScopedRules {
+  #[serde(default)]
+  pub tags: Vec<String>,
+  #[serde(default)]
+  pub exclude_tags: Vec<String>,
+  #[serde(default)]
+  pub disabled: Vec<String>,
+  #[serde(default)]
+  pub severity: BTreeMap<String, Severity>,
+}
+
+impl RulesConfig {
+  pub fn is_disabled(&self, rule_id: &str) -> bool {
+    self
+      .disabled
+      .iter()
+      .any(|r| r.trim().eq_ignore_ascii_case(rule_id))
+  }
+
+  pub fn severity_for(&self, rule_id: &str) -> Option<Severity> {
+    self
+      .severity
+      .iter()
+      .find(|(r, _)| r.trim().eq_ignore_ascii_case(rule_id))
+      .map(|(_, s)| *s)
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LegacySafetyConfig {
+  #[serde(default)]
+  pub strict_mode: bool,
+}
+
+fn default_true() -> bool {
+  true
+}
+
+fn default_failsafe_minutes() -> u64 {
+  10
+}
+
+fn default_collector_max_records_per_tick() -> usize {
+  1024
+}
+
+fn default_collector_queue_capacity() -> usize {
+  8192
+}
+
+fn default_correlation_window_seconds() -> u64 {
+  120
+}
+
+fn default_circuit_breaker_max_incidents() -> usize {
+  20
+}
+
+fn default_circuit_breaker_window_minutes() -> u64 {
+  10
+}
+
+fn default_baseline_learning_period_days() -> u64 {
+  14
+}
+
+fn default_baseline_min_occurrences() -> u64 {
+  5
+}
+
+fn default_targeted_scan_cooldown_minutes() -> u64 {
+  60
+}
+
+fn default_dedup_window_seconds() -> u64 {
+  600
+}
+
+fn default_dedup_max_incidents_per_pid() -> usize {
+  5
+}
+
+fn default_log_actions() -> Vec<ResponseAction> {
+  vec![ResponseAction::Log]
+}
+
+fn default_red_actions() -> Vec<ResponseAction> {
+  vec![
+    ResponseAction::Log,
+    ResponseAction::Notify,
+    ResponseAction::ContainProcess,
+    ResponseAction::Killswitch,
+    ResponseAction::Quarantine,
+  ]
+}
+
+fn default_engine_state_max_age_seconds() -> u64 {
+  900
+}
+
+fn default_engine_state_save_interval_seconds() -> u64 {
+  15
+}
+
+fn default_recording_max_mb() -> u64 {
+  50
+}
+
+fn default_watch_minutes() -> u64 {
+  24 * 60
+}
+
+fn default_watch_max_mb() -> u64 {
+  100
+}
+
+fn default_status_api_port() -> u16 {
+  47651
+}
+
+fn default_notifications_max_per_hour() -> u32 {
+  20
+}
+
+fn default_notifications_retries() -> u32 {
+  3
+}
+
+fn default_notifications_timeout_seconds() -> u64 {
+  10
+}
+
+fn default_smtp_port() -> u16 {
+  587
+}
+
+fn default_refresh_interval_minutes() -> u64 {
+  60
+}
+
+fn default_threat_feed_endpoints() -> Vec<String> {
+  vec!["https://updates.aidefender.shop/feed/".to_string()]
+}
+
+fn default_threat_feed_allowlist_domains() -> Vec<String> {
+  vec!["updates.aidefender.shop".to_string()]
+}
+
+fn default_threat_feed_timeout_seconds() -> u64 {
+  10
+}
+
+fn default_license_timeout_seconds() -> u64 {
+  15
+}
+
+fn default_allowlist_publishers() -> Vec<String> {
+  vec![
+    "Microsoft Windows".to_string(),
+    "Google LLC".to_string(),
+    "Mozilla Corporation".to_string(),
+  ]
+}
+
+fn default_chrome_targets() -> Vec<String> {
+  vec![
+    "Login Data".to_string(),
+    "Cookies".to_string(),
+    "Local State".to_string(),
+  ]
+}
+
+fn default_firefox_targets() -> Vec<String> {
+  vec![
+    "logins.json".to_string(),
+    "key4.db".to_string(),
+    "cookies.sqlite".to_string(),
+  ]
+}
+
+fn default_archive_max_mb() -> u64 {
+  200
+}
+
+fn default_archive_max_entries() -> usize {
+  10_000
+}
+
+fn default_archive_max_depth() -> usize {
+  2
 }
 
-fn default_firefox_targets() -> Vec<String> {
-  vec![
-    "logins.json".to_string(),
-    "key4.db".to_string(),
-    "cookies.sqlite".to_string(),
-  ]
+fn default_archive_max_extract_mb() -> u64 {
+  500
+}
+
+fn default_protected_groups() -> Vec<String> {
+  crate::rules_engine::protected_paths::TARGET_GROUPS
+    .iter()
+    .map(|g| g.name.to_string())
+    .collect()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct ConfigFile {
+pub struct ConfigFile {
   #[serde(default)]
   pub mode: Option<Mode>,
 
@@ -229,6 +1545,50 @@ struct ConfigFile {
   #[serde(default)]
   pub threat_feed: Option<ThreatFeedConfig>,
 
+  #[serde(default)]
+  pub collector: Option<CollectorConfig>,
+
+  #[serde(default)]
+  pub circuit_breaker: Option<CircuitBreakerConfig>,
+
+  #[serde(default)]
+  pub baseline: Option<BaselineConfig>,
+
+  #[serde(default)]
+  pub engine_state: Option<EngineStateConfig>,
+
+  #[serde(default)]
+  pub rules: Option<RulesConfig>,
+
+  #[serde(default)]
+  pub response: Option<ResponseConfig>,
+  #[serde(default)]
+  pub recording: Option<RecordingConfig>,
+  #[serde(default)]
+  pub notifications: Option<NotificationsConfig>,
+  #[serde(default)]
+  pub machine: Option<MachineConfig>,
+  #[serde(default)]
+  pub status_api: Option<StatusApiConfig>,
+  #[serde(default)]
+  pub scanner: Option<ScannerConfig>,
+  #[serde(default)]
+  pub watch: Option<WatchConfig>,
+  #[serde(default)]
+  pub service: Option<ServiceConfig>,
+  #[serde(default)]
+  pub self_protection: Option<SelfProtectionConfig>,
+  #[serde(default)]
+  pub ransomware: Option<RansomwareConfig>,
+  #[serde(default)]
+  pub metrics: Option<MetricsConfig>,
+  #[serde(default)]
+  pub browsers: Option<BrowsersConfig>,
+  #[serde(default)]
+  pub scoring: Option<ScoringConfig>,
+  #[serde(default)]
+  pub license: Option<LicenseConfig>,
+
   // Back-compat: old configs had `[safety] strict_mode = true|false`.
   #[serde(default)]
   pub safety: Option<LegacySafetyConfig>,
@@ -240,32 +1600,47 @@ struct ConfigFile {
 
 impl ConfigFile {
   fn normalize(self) -> Config {
+    self.normalize_checked().0
+  }
+
+  /// `normalize`, also returning what validation corrected.
+  fn normalize_checked(self) -> (Config, Vec<ConfigIssue>) {
     let mut cfg = Config::default();
-    if let Some(mode) = self.mode {
-      cfg.mode = mode;
-    } else if self.safety.as_ref().map(|s| s.strict_mode).unwrap_or(false) {
+    if self.mode.is_none() && self.safety.as_ref().map(|s| s.strict_mode).unwrap_or(false) {
       cfg.mode = Mode::Strict;
     }
 
+    let legacy_failsafe = self.failsafe_minutes;
+    let can_apply_legacy_failsafe = self
+      .killswitch
+      .as_ref()
+      .map(|ks| ks.failsafe_minutes == default_failsafe_minutes())
+      .unwrap_or(true);
+
+    let issues = self.overlay(&mut cfg);
+
+    if let Some(legacy) = legacy_failsafe {
+      if can_apply_legacy_failsafe {
+        cfg.killswitch.failsafe_minutes = legacy;
+      }
+    }
+    (cfg, issues)
+  }
+
+  /// Replaces the sections of `cfg` that are present in this file, then re-validates.
+  pub(crate) fn overlay(self, cfg: &mut Config) -> Vec<ConfigIssue> {
+    if let Some(mode) = self.mode {
+      cfg.mode = mode;
+    }
     if let Some(w) = self.correlation_window_seconds {
       cfg.correlation_window_seconds = w;
     }
     if let Some(l) = self.logging {
       cfg.logging = l;
     }
-    let killswitch_opt = self.killswitch;
-    if let Some(k) = killswitch_opt.clone() {
+    if let Some(k) = self.killswitch {
       cfg.killswitch = k;
     }
-    if let Some(legacy) = self.failsafe_minutes {
-      let can_apply = killswitch_opt
-        .as_ref()
-        .map(|ks| ks.failsafe_minutes == default_failsafe_minutes())
-        .unwrap_or(true);
-      if can_apply {
-        cfg.killswitch.failsafe_minutes = legacy;
-      }
-    }
     if let Some(a) = self.allowlist {
       cfg.allowlist = a;
     }
@@ -275,16 +1650,104 @@ impl ConfigFile {
     if let Some(tf) = self.threat_feed {
       cfg.threat_feed = tf;
     }
-
-    if let Some(reason) = validate_threat_feed_config(&cfg.threat_feed) {
-      cfg.threat_feed.auto_refresh = false;
-      tracing::warn!(
-        reason = %reason,
-        "threat_feed config invalid; auto refresh disabled"
-      );
+    if let Some(c) = self.collector {
+      cfg.collector = c;
+    }
+    if let Some(cb) = self.circuit_breaker {
+      cfg.circuit_breaker = cb;
+    }
+    if let Some(b) = self.baseline {
+      cfg.baseline = b;
+    }
+    if let Some(e) = self.engine_state {
+      cfg.engine_state = e;
+    }
+    if let Some(r) = self.rules {
+      cfg.rules = r;
+    }
+    if let Some(r) = self.response {
+      cfg.response = r;
+    }
+    if let Some(r) = self.recording {
+      cfg.recording = r;
+    }
+    if let Some(n) = self.notifications {
+      cfg.notifications = n;
+    }
+    if let Some(m) = self.machine {
+      cfg.machine = m;
+    }
+    if let Some(s) = self.status_api {
+      cfg.status_api = s;
+    }
+    if let Some(s) = self.scanner {
+      cfg.scanner = s;
+    }
+    if let Some(s) = self.watch {
+      cfg.watch = s;
     }
+    if let Some(s) = self.service {
+      cfg.service = s;
+    }
+    if let Some(s) = self.self_protection {
+      cfg.self_protection = s;
+    }
+    if let Some(s) = self.ransomware {
+      cfg.ransomware = s;
+    }
+    if let Some(m) = self.metrics {
+      cfg.metrics = m;
+    }
+    if let Some(b) = self.browsers {
+      cfg.browsers = b;
+    }
+    if let Some(s) = self.scoring {
+      cfg.scoring = s;
+    }
+    if let Some(l) = self.license {
+      cfg.license = l;
+    }
+    validate(cfg)
+  }
 
-    cfg
+  /// Names of the sections (and top-level keys) set in this file.
+  pub fn present_sections(&self) -> Vec<String> {
+    let sections = [
+      ("mode", self.mode.is_some()),
+      (
+        "correlation_window_seconds",
+        self.correlation_window_seconds.is_some(),
+      ),
+      ("logging", self.logging.is_some()),
+      ("killswitch", self.killswitch.is_some()),
+      ("allowlist", self.allowlist.is_some()),
+      ("protected", self.protected.is_some()),
+      ("threat_feed", self.threat_feed.is_some()),
+      ("collector", self.collector.is_some()),
+      ("circuit_breaker", self.circuit_breaker.is_some()),
+      ("baseline", self.baseline.is_some()),
+      ("engine_state", self.engine_state.is_some()),
+      ("rules", self.rules.is_some()),
+      ("response", self.response.is_some()),
+      ("recording", self.recording.is_some()),
+      ("notifications", self.notifications.is_some()),
+      ("machine", self.machine.is_some()),
+      ("status_api", self.status_api.is_some()),
+      ("scanner", self.scanner.is_some()),
+      ("watch", self.watch.is_some()),
+      ("service", self.service.is_some()),
+      ("self_protection", self.self_protection.is_some()),
+      ("ransomware", self.ransomware.is_some()),
+      ("metrics", self.metrics.is_some()),
+      ("browsers", self.browsers.is_some()),
+      ("scoring", self.scoring.is_some()),
+      ("license", self.license.is_some()),
+    ];
+    sections
+      .into_iter()
+      .filter(|(_, present)| *present)
+      .map(|(name, _)| name.to_string())
+      .collect()
   }
 
   fn needs_upgrade(&self) -> bool {
@@ -295,15 +1758,739 @@ impl ConfigFile {
       || self.allowlist.is_none()
       || self.protected.is_none()
       || self.threat_feed.is_none()
+      || self.collector.is_none()
+      || self.circuit_breaker.is_none()
+      || self.baseline.is_none()
+      || self.engine_state.is_none()
+      || self.rules.is_none()
+      || self.response.is_none()
+      || self.recording.is_none()
+      || self.notifications.is_none()
+      || self.machine.is_none()
+      || self.status_api.is_none()
+      || self.scanner.is_none()
+      || self.watch.is_none()
+      || self.service.is_none()
+      || self.self_protection.is_none()
+      || self.ransomware.is_none()
+      || self.metrics.is_none()
+      || self.browsers.is_none()
+      || self.scoring.is_none()
+      || self.license.is_none()
+  }
+}
+
+/// How serious a config problem is. Errors are settings that are ignored or replaced by a
+/// default, so the file doesn't do what it says; warnings are allowed but probably unintended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueLevel {
+  Error,
+  Warning,
+}
+
+/// A problem found in `config.toml` by [`check`] or while loading it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigIssue {
+  pub level: IssueLevel,
+  /// Dotted setting name, e.g. `response.severity.red`; empty for syntax errors.
+  pub setting: String,
+  pub message: String,
+  /// The offending entry of a list or table, used to find its line.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub value: Option<String>,
+  /// 1-based line in the file, if it could be located.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub line: Option<usize>,
+}
+
+/// Collects validation issues. Each one is logged as well: the service has no other way to
+/// report a config it had to correct.
+#[derive(Debug, Default)]
+struct Issues(Vec<ConfigIssue>);
+
+impl Issues {
+  fn error(&mut self, setting: &str, value: Option<&str>, message: impl Into<String>) {
+    self.push(IssueLevel::Error, setting, value, message.into());
+  }
+
+  fn warning(&mut self, setting: &str, value: Option<&str>, message: impl Into<String>) {
+    self.push(IssueLevel::Warning, setting, value, message.into());
+  }
+
+  fn push(&mut self, level: IssueLevel, setting: &str, value: Option<&str>, message: String) {
+    match value {
+      Some(v) => tracing::warn!(setting, value = %v, "config: {message}"),
+      None => tracing::warn!(setting, "config: {message}"),
+    }
+    self.0.push(ConfigIssue {
+      level,
+      setting: setting.to_string(),
+      message,
+      value: value.map(str::to_string),
+      line: None,
+    });
+  }
+}
+
+fn validate(cfg: &mut Config) -> Vec<ConfigIssue> {
+  let mut issues = Issues::default();
+  let known = |id: &str| {
+    crate::rules_engine::rules::ALL_RULES
+      .iter()
+      .any(|r| r.id.eq_ignore_ascii_case(id.trim()))
+      || crate::rules_engine::rules::is_feed_rule_id(id)
+  };
+  let unknown_rule = |issues: &mut Issues, setting: &str, id: &str| {
+    let ok = known(id);
+    if !ok {
+      issues.error(setting, Some(id), format!("unknown rule ID {id}; ignored"));
+    }
+    ok
+  };
+  cfg
+    .rules
+    .disabled
+    .retain(|id| unknown_rule(&mut issues, "rules.disabled", id));
+  cfg
+    .rules
+    .severity
+    .retain(|id, _| unknown_rule(&mut issues, "rules.severity", id));
+  for scoped in &mut cfg.rules.scoped {
+    scoped
+      .disabled
+      .retain(|id| unknown_rule(&mut issues, "rules.scoped.disabled", id));
+    scoped
+      .severity
+      .retain(|id, _| unknown_rule(&mut issues, "rules.scoped.severity", id));
+  }
+
+  cfg
+    .response
+    .rules
+    .retain(|id, _| unknown_rule(&mut issues, "response.rules", id));
+  let severity = &mut cfg.response.severity;
+  let lists = [
+    ("response.severity.green".to_string(), &mut severity.green),
+    ("response.severity.yellow".to_string(), &mut severity.yellow),
+    ("response.severity.red".to_string(), &mut severity.red),
+  ];
+  let rule_lists = cfg
+    .response
+    .rules
+    .iter_mut()
+    .map(|(id, actions)| (format!("response.rules.{id}"), actions));
+  for (name, actions) in lists.into_iter().chain(rule_lists) {
+    if !actions.contains(&ResponseAction::Log) {
+      issues.warning(&name, None, "incidents are always logged; adding \"log\"");
+      actions.insert(0, ResponseAction::Log);
+    }
+    actions.sort();
+    actions.dedup();
+  }
+  for action in [ResponseAction::Killswitch, ResponseAction::ContainProcess] {
+    for (name, actions) in [
+      ("response.severity.green", &cfg.response.severity.green),
+      ("response.severity.yellow", &cfg.response.severity.yellow),
+    ] {
+      if actions.contains(&action) {
+        issues.warning(
+          name,
+          None,
+          format!("response policy enforces ({action:?}) on non-RED incidents"),
+        );
+      }
+    }
+  }
+
+  if cfg.response.dedup.window_seconds == 0 || cfg.response.dedup.max_incidents_per_pid == 0 {
+    issues.error(
+      "response.dedup",
+      None,
+      "window_seconds and max_incidents_per_pid must be > 0; using defaults",
+    );
+    cfg.response.dedup = DedupConfig {
+      enabled: cfg.response.dedup.enabled,
+      ..DedupConfig::default()
+    };
+  }
+
+  cfg.protected.groups.retain(|name| {
+    let ok = crate::rules_engine::protected_paths::known_group(name);
+    if !ok {
+      issues.error(
+        "protected.groups",
+        Some(name),
+        format!("unknown group {name}; ignored"),
+      );
+    }
+    ok
+  });
+
+  cfg.machine.tags = cfg
+    .machine
+    .tags
+    .iter()
+    .map(|t| t.trim().to_ascii_lowercase())
+    .filter(|t| !t.is_empty())
+    .collect();
+  cfg.machine.tags.sort();
+  cfg.machine.tags.dedup();
+
+  if cfg.circuit_breaker.max_incidents == 0 || cfg.circuit_breaker.window_minutes == 0 {
+    issues.error(
+      "circuit_breaker",
+      None,
+      "max_incidents and window_minutes must be > 0; using defaults",
+    );
+    cfg.circuit_breaker = CircuitBreakerConfig {
+      enabled: cfg.circuit_breaker.enabled,
+      ..CircuitBreakerConfig::default()
+    };
+  }
+
+  for id in [1, 5] {
+    if cfg.collector.exclude_event_ids.contains(&id) {
+      issues.warning(
+        "collector.exclude_event_ids",
+        Some(&id.to_string()),
+        "process starts and exits build the process trees rules correlate on; detections will miss parents and PID reuse",
+      );
+    }
+  }
+
+  if cfg.collector.max_records_per_tick == 0 || cfg.collector.queue_capacity == 0 {
+    issues.error(
+      "collector",
+      None,
+      "max_records_per_tick and queue_capacity must be > 0; using defaults",
+    );
+    cfg.collector.max_records_per_tick = default_collector_max_records_per_tick();
+    cfg.collector.queue_capacity = default_collector_queue_capacity();
+  }
+
+  if cfg.engine_state.save_interval_seconds == 0 {
+    issues.error(
+      "engine_state.save_interval_seconds",
+      None,
+      "must be > 0; using default",
+    );
+    cfg.engine_state.save_interval_seconds = default_engine_state_save_interval_seconds();
+  }
+
+  if cfg.recording.max_mb == 0 {
+    issues.error("recording.max_mb", None, "must be > 0; using default");
+    cfg.recording.max_mb = default_recording_max_mb();
+  }
+
+  cfg.notifications.webhooks.retain(|w| {
+    let ok = reqwest::Url::parse(&w.url).is_ok_and(|u| u.scheme() == "https");
+    if !ok {
+      issues.error(
+        "notifications.webhooks",
+        Some(&w.url),
+        "URL must be https; ignored",
+      );
+    }
+    ok
+  });
+  if let Some(schedule) = cfg.scanner.schedule.as_deref() {
+    if let Err(e) = crate::scan_schedule::Schedule::parse(schedule) {
+      issues.error(
+        "scanner.schedule",
+        Some(schedule),
+        format!("invalid ({e}); scheduled scans disabled"),
+      );
+      cfg.scanner.schedule = None;
+    }
+  }
+  if cfg.scanner.threads > MAX_SCANNER_THREADS {
+    issues.error(
+      "scanner.threads",
+      Some(&cfg.scanner.threads.to_string()),
+      format!("at most {MAX_SCANNER_THREADS}; using {MAX_SCANNER_THREADS}"),
+    );
+    cfg.scanner.threads = MAX_SCANNER_THREADS;
+  }
+
+  if cfg.watch.default_minutes == 0 {
+    issues.error("watch.default_minutes", None, "must be > 0; using default");
+    cfg.watch.default_minutes = default_watch_minutes();
+  }
+  if cfg.watch.max_mb == 0 {
+    issues.error("watch.max_mb", None, "must be > 0; using default");
+    cfg.watch.max_mb = default_watch_max_mb();
+  }
+
+  if cfg.ransomware.min_files == 0 || cfg.ransomware.window_seconds == 0 {
+    issues.error(
+      "ransomware",
+      None,
+      "min_files and window_seconds must be > 0; using defaults",
+    );
+    cfg.ransomware.min_files = default_ransomware_min_files();
+    cfg.ransomware.window_seconds = default_ransomware_window_seconds();
+  }
+  if !(0.0..=1.0).contains(&cfg.ransomware.min_new_extension_ratio) {
+    issues.error(
+      "ransomware.min_new_extension_ratio",
+      None,
+      "must be between 0.0 and 1.0; using default",
+    );
+    cfg.ransomware.min_new_extension_ratio = default_ransomware_min_new_extension_ratio();
+  }
+
+  // Shorter than a slow tick (firewall calls, a large batch) would restart a healthy agent.
+  if cfg.service.watchdog_hang_seconds < 30 {
+    issues.error(
+      "service.watchdog_hang_seconds",
+      None,
+      "must be at least 30; using default",
+    );
+    cfg.service.watchdog_hang_seconds = default_watchdog_hang_seconds();
+  }
+
+  if cfg.metrics.interval_seconds == 0 {
+    issues.error(
+      "metrics.interval_seconds",
+      None,
+      "must be > 0; using default",
+    );
+    cfg.metrics.interval_seconds = default_metrics_interval_seconds();
+  }
+
+  cfg.browsers.disabled.retain(|name| {
+    let ok = crate::browsers::is_builtin(name);
+    if !ok {
+      issues.error(
+        "browsers.disabled",
+        Some(name),
+        format!("unknown browser {name}; ignored"),
+      );
+    }
+    ok
+  });
+  cfg.browsers.chromium.retain(|b| {
+    let rel = b.user_data.trim();
+    let ok = !b.name.trim().is_empty()
+      && !rel.is_empty()
+      && !rel.contains(':')
+      && !rel.starts_with(['\\', '/'])
+      && !rel.split(['\\', '/']).any(|s| s == "..");
+    if !ok {
+      issues.error(
+        "browsers.chromium",
+        Some(&b.user_data),
+        format!(
+          "{:?} needs a name and a user_data path relative to %LOCALAPPDATA% or %APPDATA%; ignored",
+          b.name
+        ),
+      );
+    }
+    ok
+  });
+
+  if cfg.scoring.red > 100 || cfg.scoring.yellow == 0 || cfg.scoring.yellow >= cfg.scoring.red {
+    issues.error(
+      "scoring",
+      None,
+      format!(
+        "thresholds must satisfy 0 < yellow < red <= 100 (yellow = {}, red = {}); using defaults",
+        cfg.scoring.yellow, cfg.scoring.red
+      ),
+    );
+    cfg.scoring.yellow = default_scoring_yellow();
+    cfg.scoring.red = default_scoring_red();
+  }
+  cfg.scoring.rules.retain(|rule, score| {
+    let ok = *score <= 100;
+    if !ok {
+      issues.error(
+        "scoring.rules",
+        Some(rule),
+        format!("{rule} = {score}: scores range from 0 to 100; ignored"),
+      );
+    }
+    ok
+  });
+
+  if cfg.status_api.port == 0 {
+    issues.error("status_api.port", None, "must be > 0; using default");
+    cfg.status_api.port = default_status_api_port();
+  }
+
+  if let Some(endpoint) = cfg.license.activation_endpoint.clone() {
+    if let Err(e) = crate::license::activation::validate_endpoint(&endpoint, &cfg.license) {
+      issues.error(
+        "license.activation_endpoint",
+        Some(&endpoint),
+        format!("{e}; using local activation"),
+      );
+      cfg.license.activation_endpoint = None;
+    }
+  }
+  if cfg.license.timeout_seconds == 0 {
+    issues.error(
+      "license.timeout_seconds",
+      None,
+      "must be > 0; using default",
+    );
+    cfg.license.timeout_seconds = default_license_timeout_seconds();
+  }
+
+  if cfg.logging.max_total_mb > 0 && cfg.logging.max_file_mb > cfg.logging.max_total_mb {
+    issues.warning(
+      "logging.max_file_mb",
+      Some(&cfg.logging.max_file_mb.to_string()),
+      "larger than logging.max_total_mb; rotated logs will be deleted as soon as they rotate",
+    );
+  }
+
+  if cfg.notifications.timeout_seconds == 0 {
+    issues.error(
+      "notifications.timeout_seconds",
+      None,
+      "must be > 0; using default",
+    );
+    cfg.notifications.timeout_seconds = default_notifications_timeout_seconds();
+  }
+
+  for entry in &cfg.allowlist.hashes {
+    if !is_sha256_hex(entry) {
+      issues.error(
+        "allowlist.hashes",
+        Some(entry),
+        "not a SHA-256 hex digest; ignored",
+      );
+    }
+  }
+
+  for entry in &cfg.scanner.exclude_hashes {
+    if !is_sha256_hex(entry) {
+      issues.error(
+        "scanner.exclude_hashes",
+        Some(entry),
+        "not a SHA-256 hex digest; ignored",
+      );
+    }
+  }
+
+  for entry in &cfg.killswitch.always_allow {
+    if entry.parse::<crate::net::Cidr>().is_err() {
+      issues.error(
+        "killswitch.always_allow",
+        Some(entry),
+        "not a valid IP/CIDR; ignored",
+      );
+    }
+  }
+
+  for entry in &cfg.allowlist.destinations {
+    if entry.parse::<crate::net::Cidr>().is_err() {
+      issues.error(
+        "allowlist.destinations",
+        Some(entry),
+        "not a valid IP/CIDR; ignored",
+      );
+    }
+  }
+
+  if cfg.threat_feed.org_public_key.is_some() && cfg.threat_feed.org_public_key_file.is_some() {
+    issues.warning(
+      "threat_feed.org_public_key",
+      None,
+      "org_public_key and org_public_key_file both set; using org_public_key",
+    );
+  }
+
+  if let Some((setting, value, reason)) = validate_threat_feed_config(&cfg.threat_feed) {
+    cfg.threat_feed.auto_refresh = false;
+    issues.error(
+      &format!("threat_feed.{setting}"),
+      value.as_deref(),
+      format!("{reason}; auto refresh disabled"),
+    );
+  }
+
+  issues.0
+}
+
+pub fn is_sha256_hex(s: &str) -> bool {
+  let s = s.trim();
+  s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Writes `cfg` back to `path` (used by console commands that edit config).
+///
+/// Sections owned by a managed profile keep their local values, so the profile's settings are
+/// never copied into `config.toml`.
+pub fn save(path: &Path, cfg: &Config) -> anyhow::Result<()> {
+  let Some(managed) = &cfg.managed else {
+    return write_atomic(path, &toml::to_string_pretty(&to_config_file(cfg))?);
+  };
+  let local = load_impl(path, false)?;
+  let mut out = cfg.clone();
+  for section in &managed.sections {
+    match section.as_str() {
+      "mode" => out.mode = local.mode,
+      "correlation_window_seconds" => {
+        out.correlation_window_seconds = local.correlation_window_seconds
+      }
+      "logging" => out.logging = local.logging.clone(),
+      "killswitch" => out.killswitch = local.killswitch.clone(),
+      "allowlist" => out.allowlist = local.allowlist.clone(),
+      "protected" => out.protected = local.protected.clone(),
+      "threat_feed" => out.threat_feed = local.threat_feed.clone(),
+      "collector" => out.collector = local.collector.clone(),
+      "circuit_breaker" => out.circuit_breaker = local.circuit_breaker.clone(),
+      "baseline" => out.baseline = local.baseline.clone(),
+      "engine_state" => out.engine_state = local.engine_state.clone(),
+      "rules" => out.rules = local.rules.clone(),
+      "response" => out.response = local.response.clone(),
+      "recording" => out.recording = local.recording.clone(),
+      "notifications" => out.notifications = local.notifications.clone(),
+      "machine" => out.machine = local.machine.clone(),
+      "status_api" => out.status_api = local.status_api.clone(),
+      "scanner" => out.scanner = local.scanner.clone(),
+      "watch" => out.watch = local.watch.clone(),
+      "service" => out.service = local.service.clone(),
+      "self_protection" => out.self_protection = local.self_protection.clone(),
+      "ransomware" => out.ransomware = local.ransomware.clone(),
+      "metrics" => out.metrics = local.metrics.clone(),
+      "browsers" => out.browsers = local.browsers.clone(),
+      "scoring" => out.scoring = local.scoring.clone(),
+      "license" => out.license = local.license.clone(),
+      _ => {}
+    }
+  }
+  write_atomic(path, &toml::to_string_pretty(&to_config_file(&out))?)
+}
+
+/// The value at dotted `key` (e.g. `killswitch.failsafe_minutes`) as it would be written to
+/// config.toml. Unset optional settings are an error, like unknown keys.
+pub fn get_key(cfg: &Config, key: &str) -> anyhow::Result<toml::Value> {
+  let root = toml::Value::try_from(to_config_file(cfg))?;
+  key
+    .split('.')
+    .try_fold(&root, |v, part| v.get(part))
+    .cloned()
+    .ok_or_else(|| anyhow::anyhow!("unknown or unset config key: {key}"))
+}
+
+/// Sets dotted `key` to `raw`, a TOML value (`30`, `true`, `["a", "b"]`); a bare word is taken as
+/// a string. The new value must have the type of the current one and pass validation; `cfg` is
+/// left unchanged otherwise. Only existing keys and new entries in existing tables can be set.
+pub fn set_key(cfg: &mut Config, key: &str, raw: &str) -> anyhow::Result<()> {
+  let parts: Vec<&str> = key.split('.').collect();
+  if parts.iter().any(|p| p.trim().is_empty()) {
+    anyhow::bail!("invalid config key: {key}");
+  }
+  let mut root = toml::Value::try_from(to_config_file(cfg))?;
+  let (last, parents) = parts.split_last().unwrap_or((&"", &[]));
+  let table = parents
+    .iter()
+    .try_fold(&mut root, |v, part| v.get_mut(*part))
+    .and_then(toml::Value::as_table_mut)
+    .ok_or_else(|| anyhow::anyhow!("unknown config key: {key}"))?;
+
+  let current = table.get(*last);
+  let parsed = toml::from_str::<toml::Table>(&format!("v = {raw}")).map(|mut t| t.remove("v"));
+  let value = match parsed {
+    Ok(Some(v)) => v,
+    _ if current.is_none_or(toml::Value::is_str) => toml::Value::String(raw.to_string()),
+    Ok(None) => anyhow::bail!("invalid value for {key}"),
+    Err(e) => anyhow::bail!("invalid value for {key}: {}", e.message().trim()),
+  };
+  let value = match (current, value) {
+    (Some(toml::Value::Float(_)), toml::Value::Integer(i)) => toml::Value::Float(i as f64),
+    (
+      Some(toml::Value::String(_)),
+      toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_),
+    ) => toml::Value::String(raw.trim().to_string()),
+    (_, v) => v,
+  };
+  if let Some(current) = current {
+    if current.type_str() != value.type_str() {
+      anyhow::bail!(
+        "{key} expects {}, got {}",
+        current.type_str(),
+        value.type_str()
+      );
+    }
+  }
+  table.insert(last.to_string(), value);
+
+  let file: ConfigFile = root.try_into().map_err(|e: toml::de::Error| {
+    anyhow::anyhow!("invalid value for {key}: {}", e.message().trim())
+  })?;
+  let (updated, issues) = file.normalize_checked();
+  let related = |setting: &str| {
+    setting == key
+      || key.starts_with(&format!("{setting}."))
+      || setting.starts_with(&format!("{key}."))
+  };
+  if let Some(issue) = issues
+    .iter()
+    .find(|i| i.level == IssueLevel::Error && related(&i.setting))
+  {
+    anyhow::bail!("invalid value for {key}: {}", issue.message);
+  }
+  *cfg = Config {
+    managed: cfg.managed.clone(),
+    ..updated
+  };
+  Ok(())
+}
+
+/// Validates the config file at `path` without writing anything: syntax and value types first,
+/// then every setting validator. Issues point at the line they refer to where it can be found.
+/// The managed profile is not applied; this checks the local file only.
+pub fn check(path: &Path) -> anyhow::Result<Vec<ConfigIssue>> {
+  let raw =
+    fs::read_to_string(path).map_err(|e| anyhow::anyhow!("read {}: {e}", path.display()))?;
+  Ok(check_str(&raw))
+}
+
+fn check_str(raw: &str) -> Vec<ConfigIssue> {
+  let issue = |level, setting: &str, message: String, line: Option<usize>| ConfigIssue {
+    level,
+    setting: setting.to_string(),
+    message,
+    value: None,
+    line,
+  };
+  let file = match toml::from_str::<ConfigFile>(raw) {
+    Ok(file) => file,
+    Err(e) => {
+      let line = e
+        .span()
+        .map(|s| raw[..s.start.min(raw.len())].matches('\n').count() + 1);
+      return vec![issue(
+        IssueLevel::Error,
+        "",
+        e.message().trim().to_string(),
+        line,
+      )];
+    }
+  };
+
+  let mut issues = Vec::new();
+  if file.safety.is_some() {
+    issues.push(issue(
+      IssueLevel::Warning,
+      "safety",
+      "deprecated; use `mode = \"strict\"` or `mode = \"learning\"`".to_string(),
+      None,
+    ));
+  }
+  if file.failsafe_minutes.is_some() {
+    issues.push(issue(
+      IssueLevel::Warning,
+      "failsafe_minutes",
+      "deprecated; use killswitch.failsafe_minutes".to_string(),
+      None,
+    ));
+  }
+  issues.extend(file.normalize_checked().1);
+  for issue in &mut issues {
+    if issue.line.is_none() {
+      issue.line = locate(raw, &issue.setting, issue.value.as_deref());
+    }
+  }
+  issues
+}
+
+/// 1-based line of `setting` in `raw`, or of `value` (a list entry or table key) within it. Falls
+/// back to the setting's table header, so a line is found even for settings left at a default.
+fn locate(raw: &str, setting: &str, value: Option<&str>) -> Option<usize> {
+  fn header(line: &str) -> Option<&str> {
+    let t = line.trim();
+    let t = t.split('#').next().unwrap_or(t).trim();
+    let inner = t.strip_prefix("[[").and_then(|t| t.strip_suffix("]]"));
+    inner
+      .or_else(|| t.strip_prefix('[').and_then(|t| t.strip_suffix(']')))
+      .map(str::trim)
+  }
+  fn key(line: &str) -> Option<&str> {
+    let (k, _) = line.split_once('=')?;
+    Some(k.trim().trim_matches('"'))
+  }
+
+  let lines: Vec<&str> = raw.lines().collect();
+  let parts: Vec<&str> = setting.split('.').filter(|p| !p.is_empty()).collect();
+  if parts.is_empty() {
+    return None;
+  }
+  // The longest prefix of `setting` with a table header is the table; the rest is the key.
+  let (from, rest) = (1..=parts.len())
+    .rev()
+    .find_map(|n| {
+      let table = parts[..n].join(".");
+      let at = lines
+        .iter()
+        .position(|l| header(l) == Some(table.as_str()))?;
+      Some((at + 1, &parts[n..]))
+    })
+    .unwrap_or((0, &parts[..]));
+  let to = lines[from..]
+    .iter()
+    .position(|l| header(l).is_some())
+    .map_or(lines.len(), |i| from + i);
+  let section = &lines[from..to];
+
+  let key_at = rest.first().and_then(|k| {
+    section
+      .iter()
+      .position(|l| key(l).is_some_and(|lk| lk.eq_ignore_ascii_case(k)))
+  });
+  let value_at = value.filter(|v| !v.is_empty()).and_then(|v| {
+    let after = key_at.unwrap_or(0);
+    section[after..]
+      .iter()
+      .position(|l| l.contains(v))
+      .map(|i| after + i)
+  });
+  match value_at.or(key_at) {
+    Some(i) => Some(from + i + 1),
+    None => (from > 0).then_some(from),
   }
 }
 
 pub fn load_or_create_default(path: &Path) -> anyhow::Result<Config> {
-  load_impl(path, true)
+  let mut cfg = load_impl(path, true)?;
+  apply_managed_profile(path, &mut cfg);
+  Ok(cfg)
 }
 
 pub fn load_or_default_readonly(path: &Path) -> anyhow::Result<Config> {
-  load_impl(path, false)
+  let mut cfg = load_impl(path, false)?;
+  apply_managed_profile(path, &mut cfg);
+  Ok(cfg)
+}
+
+fn apply_managed_profile(config_path: &Path, cfg: &mut Config) {
+  if let Some(base) = config_path.parent() {
+    crate::config_profile::apply_managed(base, cfg);
+  }
+}
+
+/// Fails if `section` is controlled by the installed managed profile; local edits to it would be
+/// overridden anyway and must not be written as if they took effect.
+pub fn ensure_unmanaged(cfg: &Config, section: &str) -> anyhow::Result<()> {
+  if let Some(m) = &cfg.managed {
+    if m.sections.iter().any(|s| s == section) {
+      anyhow::bail!(
+        "`{section}` is managed by config profile {} ({}); change it in the profile instead",
+        m.profile_id,
+        if m.organization.is_empty() {
+          "unknown organization"
+        } else {
+          &m.organization
+        }
+      );
+    }
+  }
+  Ok(())
 }
 
 fn load_impl(path: &Path, allow_writes: bool) -> anyhow::Result<Config> {
@@ -388,53 +2575,196 @@ fn to_config_file(cfg: &Config) -> ConfigFile {
     allowlist: Some(cfg.allowlist.clone()),
     protected: Some(cfg.protected.clone()),
     threat_feed: Some(cfg.threat_feed.clone()),
+    collector: Some(cfg.collector.clone()),
+    circuit_breaker: Some(cfg.circuit_breaker.clone()),
+    baseline: Some(cfg.baseline.clone()),
+    engine_state: Some(cfg.engine_state.clone()),
+    rules: Some(cfg.rules.clone()),
+    response: Some(cfg.response.clone()),
+    recording: Some(cfg.recording.clone()),
+    notifications: Some(cfg.notifications.clone()),
+    machine: Some(cfg.machine.clone()),
+    status_api: Some(cfg.status_api.clone()),
+    scanner: Some(cfg.scanner.clone()),
+    watch: Some(cfg.watch.clone()),
+    service: Some(cfg.service.clone()),
+    self_protection: Some(cfg.self_protection.clone()),
+    ransomware: Some(cfg.ransomware.clone()),
+    metrics: Some(cfg.metrics.clone()),
+    browsers: Some(cfg.browsers.clone()),
+    scoring: Some(cfg.scoring.clone()),
+    license: Some(cfg.license.clone()),
     safety: None,
     failsafe_minutes: None,
   }
 }
 
-fn write_atomic(path: &Path, contents: &str) -> anyhow::Result<()> {
-  let parent = path
-    .parent()
-    .ok_or_else(|| anyhow::anyhow!("file path has no parent: {}", path.display()))?;
-  let tmp = parent.join(format!(
-    ".{}.tmp",
-    path.file_name().unwrap_or_default().to_string_lossy()
-  ));
-
-  fs::write(&tmp, contents)?;
-  fs::rename(&tmp, path)?;
-  Ok(())
-}
-
-fn validate_threat_feed_config(cfg: &ThreatFeedConfig) -> Option<String> {
+/// The first problem with the threat feed settings: setting name, offending value, reason.
+fn validate_threat_feed_config(
+  cfg: &ThreatFeedConfig,
+) -> Option<(&'static str, Option<String>, String)> {
+  let problem = |setting, value: Option<&str>, reason: String| {
+    Some((setting, value.map(str::to_string), reason))
+  };
   if cfg.refresh_interval_minutes == 0 {
-    return Some("refresh_interval_minutes must be > 0".to_string());
+    return problem("refresh_interval_minutes", None, "must be > 0".to_string());
   }
   if cfg.timeout_seconds == 0 {
-    return Some("timeout_seconds must be > 0".to_string());
+    return problem("timeout_seconds", None, "must be > 0".to_string());
   }
   if cfg.endpoints.is_empty() {
-    return Some("endpoints must not be empty".to_string());
+    return problem("endpoints", None, "must not be empty".to_string());
   }
   if cfg.allowlist_domains.is_empty() {
-    return Some("allowlist_domains must not be empty".to_string());
+    return problem("allowlist_domains", None, "must not be empty".to_string());
   }
 
   for endpoint in &cfg.endpoints {
+    let value = Some(endpoint.as_str());
     let Ok(url) = reqwest::Url::parse(endpoint) else {
-      return Some(format!("invalid endpoint URL: {endpoint}"));
+      return problem(
+        "endpoints",
+        value,
+        format!("invalid endpoint URL: {endpoint}"),
+      );
     };
     if url.scheme() != "https" {
-      return Some(format!("endpoint must use HTTPS: {endpoint}"));
+      return problem(
+        "endpoints",
+        value,
+        format!("endpoint must use HTTPS: {endpoint}"),
+      );
     }
     let Some(host) = url.host_str() else {
-      return Some(format!("endpoint has no host: {endpoint}"));
+      return problem(
+        "endpoints",
+        value,
+        format!("endpoint has no host: {endpoint}"),
+      );
     };
     if !cfg.allowlist_domains.iter().any(|d| d == host) {
-      return Some(format!("endpoint host not allowlisted: {host}"));
+      return problem(
+        "endpoints",
+        value,
+        format!("endpoint host not allowlisted: {host}"),
+      );
+    }
+  }
+
+  if let Some(proxy) = cfg.proxy_url.as_deref().filter(|p| !p.trim().is_empty()) {
+    let ok = reqwest::Url::parse(proxy.trim())
+      .is_ok_and(|u| matches!(u.scheme(), "http" | "https") && u.host_str().is_some());
+    if !ok {
+      return problem(
+        "proxy_url",
+        None,
+        "must be an http:// or https:// URL with a host".to_string(),
+      );
     }
   }
 
   None
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn check_reports_issues_with_their_lines() {
+    let raw = r#"mode = "learning"
+
+[rules]
+disabled = [
+  "R001",
+  "R999",
+]
+
+[threat_feed]
+endpoints = ["http://feed.example.com/bundle.json"]
+"#;
+    let issues = check_str(raw);
+    let find = |setting: &str| issues.iter().find(|i| i.setting == setting).unwrap();
+
+    let rule = find("rules.disabled");
+    assert_eq!(rule.level, IssueLevel::Error);
+    assert_eq!(rule.value.as_deref(), Some("R999"));
+    assert_eq!(rule.line, Some(6));
+
+    let feed = find("threat_feed.endpoints");
+    assert_eq!(feed.level, IssueLevel::Error);
+    assert!(feed.message.contains("HTTPS"), "{}", feed.message);
+    assert_eq!(feed.line, Some(10));
+
+    let broken = check_str("mode = \"learning\"\n[rules]\ndisabled = [\"R001\"\n");
+    assert_eq!(broken.len(), 1);
+    assert_eq!(broken[0].level, IssueLevel::Error);
+    assert!(broken[0].line.is_some());
+
+    assert!(check_str("mode = \"learning\"\n").is_empty());
+  }
+
+  #[test]
+  fn set_key_checks_types_and_validation() {
+    let mut cfg = Config::default();
+    set_key(&mut cfg, "killswitch.failsafe_minutes", "30").unwrap();
+    assert_eq!(cfg.killswitch.failsafe_minutes, 30);
+    assert_eq!(
+      get_key(&cfg, "killswitch.failsafe_minutes").unwrap(),
+      toml::Value::Integer(30)
+    );
+
+    set_key(&mut cfg, "mode", "strict").unwrap();
+    assert_eq!(cfg.mode, Mode::Strict);
+    set_key(&mut cfg, "allowlist.publishers", r#"["Contoso Ltd"]"#).unwrap();
+    assert_eq!(cfg.allowlist.publishers, ["Contoso Ltd"]);
+
+    let before = cfg.clone();
+    assert!(set_key(&mut cfg, "killswitch.failsafe_minutes", "soon").is_err());
+    assert!(set_key(&mut cfg, "mode", "paranoid").is_err());
+    assert!(set_key(&mut cfg, "killswitch.no_such_table.x", "1").is_err());
+    assert!(set_key(&mut cfg, "rules.disabled", r#"["R999"]"#).is_err());
+    assert_eq!(format!("{cfg:?}"), format!("{before:?}"));
+    assert!(get_key(&cfg, "killswitch.nope").is_err());
+  }
+
+  #[test]
+  fn scanner_settings_default_clamp_and_reject_bad_types() {
+    let load = |raw: &str| {
+      toml::from_str::<ConfigFile>(raw)
+        .unwrap()
+        .normalize_checked()
+    };
+
+    // Missing keys, and a partial `[scanner.full]`, take the defaults.
+    let (cfg, issues) = load("[scanner]\n[scanner.full]\nmax_files_per_root = 1000\n");
+    assert!(issues.is_empty(), "{issues:?}");
+    let scanner = &cfg.scanner;
+    assert_eq!((scanner.max_file_mb, scanner.threads), (0, 0));
+    assert!(!scanner.follow_reparse_points && scanner.exclude.is_empty());
+    assert_eq!(scanner.full.max_files_per_root, 1000);
+    assert!(scanner.full.skip_reparse_points && !scanner.full.network);
+    assert!(!scanner.archives.enabled);
+
+    let (cfg, issues) = load("[scanner]\nthreads = 64\nmax_file_mb = 200\n");
+    assert_eq!(cfg.scanner.threads, MAX_SCANNER_THREADS);
+    assert_eq!(cfg.scanner.max_file_mb, 200);
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].setting, "scanner.threads");
+    let (cfg, issues) = load(&format!("[scanner]\nthreads = {MAX_SCANNER_THREADS}\n"));
+    assert_eq!(cfg.scanner.threads, MAX_SCANNER_THREADS);
+    assert!(issues.is_empty());
+
+    for raw in [
+      "[scanner]\nthreads = \"four\"\n",
+      "[scanner]\nthreads = -1\n",
+      "[scanner]\nmax_file_mb = 1.5\n",
+      "[scanner]\nfollow_reparse_points = \"yes\"\n",
+    ] {
+      assert!(toml::from_str::<ConfigFile>(raw).is_err(), "{raw}");
+      let issues = check_str(raw);
+      assert_eq!(issues.len(), 1, "{raw}");
+      assert_eq!(issues[0].line, Some(2), "{raw}");
+    }
+  }
+}
@@ -0,0 +1,163 @@
+use crate::config::{Config, ConfigFile, ManagedProfile};
+use crate::paths;
+use crate::runtime;
+use anyhow::Context;
+use defender_common::fs::write_atomic;
+use defender_common::signature::{self, decode_signature, parse_public_key};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+// Public-key only. Replace with the production public key for enterprise config profiles.
+const PROFILE_PUBKEY_B64URL: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+
+const MAX_PROFILE_JSON_BYTES: usize = 256 * 1024;
+
+/// Ed25519-signed config profile for enterprise deployment. Sections present in `config` replace
+/// the corresponding local config sections and are reported as managed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigProfile {
+  pub version: u32,
+  pub profile_id: String,
+  pub created_at: u64,
+  #[serde(default)]
+  pub organization: String,
+  pub config: ConfigFile,
+}
+
+/// Verifies `profile.json` + `profile.sig` and installs them as the managed profile.
+///
+/// A profile older than the installed one is rejected, so a stale (weaker) profile can't be
+/// replayed over a newer one.
+pub fn import(base: &Path, src_json: &Path, src_sig: &Path) -> anyhow::Result<ManagedProfile> {
+  let json = fs::read(src_json).with_context(|| format!("read {}", src_json.display()))?;
+  let sig = fs::read(src_sig).with_context(|| format!("read {}", src_sig.display()))?;
+  let profile = verify_profile_bytes(&json, &sig)?;
+
+  if let Some(current) = load_verified(base) {
+    if profile.created_at < current.created_at {
+      anyhow::bail!(
+        "profile {} is older than the installed profile {} (created_at {} < {})",
+        profile.profile_id,
+        current.profile_id,
+        profile.created_at,
+        current.created_at
+      );
+    }
+  }
+
+  let managed = managed_info(&profile);
+  if runtime::is_dry_run() {
+    tracing::warn!(
+      profile_id = %profile.profile_id,
+      sections = ?managed.sections,
+      "DRY-RUN: would install managed config profile"
+    );
+    return Ok(managed);
+  }
+
+  write_atomic(&paths::managed_profile_json_path(base), &json)?;
+  write_atomic(&paths::managed_profile_sig_path(base), &sig)?;
+  tracing::info!(
+    profile_id = %profile.profile_id,
+    organization = %profile.organization,
+    sections = ?managed.sections,
+    "managed config profile installed"
+  );
+  Ok(managed)
+}
+
+/// Applies the installed managed profile (if any, and only if its signature still verifies).
+pub fn apply_managed(base: &Path, cfg: &mut Config) {
+  let json_path = paths::managed_profile_json_path(base);
+  if !json_path.exists() {
+    return;
+  }
+  let Some(profile) = load_verified(base) else {
+    tracing::error!(
+      path = %json_path.display(),
+      "managed config profile present but failed verification; ignoring it"
+    );
+    return;
+  };
+
+  let managed = managed_info(&profile);
+  profile.config.overlay(cfg);
+  cfg.managed = Some(managed);
+}
+
+fn load_verified(base: &Path) -> Option<ConfigProfile> {
+  let json = fs::read(paths::managed_profile_json_path(base)).ok()?;
+  let sig = fs::read(paths::managed_profile_sig_path(base)).ok()?;
+  verify_profile_bytes(&json, &sig).ok()
+}
+
+fn managed_info(profile: &ConfigProfile) -> ManagedProfile {
+  ManagedProfile {
+    profile_id: profile.profile_id.clone(),
+    organization: profile.organization.clone(),
+    created_at: profile.created_at,
+    sections: profile.config.present_sections(),
+  }
+}
+
+fn verify_profile_bytes(json: &[u8], sig_raw: &[u8]) -> anyhow::Result<ConfigProfile> {
+  if json.len() > MAX_PROFILE_JSON_BYTES {
+    anyhow::bail!("profile too large (max {MAX_PROFILE_JSON_BYTES} bytes)");
+  }
+  let key = parse_public_key(PROFILE_PUBKEY_B64URL).context("load embedded public key")?;
+  signature::verify_strict(&key, json, &decode_signature(sig_raw)?)?;
+
+  let profile: ConfigProfile = serde_json::from_slice(json).context("parse profile JSON")?;
+  if profile.version != 1 {
+    anyhow::bail!(
+      "unsupported profile version {}; expected 1",
+      profile.version
+    );
+  }
+  if uuid::Uuid::parse_str(profile.profile_id.trim()).is_err() {
+    anyhow::bail!("profile_id must be a UUID");
+  }
+  if profile.created_at == 0 {
+    anyhow::bail!("created_at must be > 0");
+  }
+  if profile.config.present_sections().is_empty() {
+    anyhow::bail!("profile config has no sections");
+  }
+  Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PROFILE: &str = r#"{
+    "version": 1,
+    "profile_id": "4f6c2a1e-8c1d-4d7e-9a57-3b1f0c2d9e10",
+    "created_at": 1700000000,
+    "organization": "Contoso",
+    "config": {
+      "mode": "strict",
+      "allowlist": { "publishers": ["Contoso Ltd"] }
+    }
+  }"#;
+
+  #[test]
+  fn profile_sections_override_local_config() {
+    let profile: ConfigProfile = serde_json::from_str(PROFILE).unwrap();
+    assert_eq!(profile.config.present_sections(), vec!["mode", "allowlist"]);
+
+    let mut cfg = Config::default();
+    let logging_before = cfg.logging.level.clone();
+    profile.config.overlay(&mut cfg);
+    assert_eq!(cfg.mode, crate::config::Mode::Strict);
+    assert_eq!(cfg.allowlist.publishers, vec!["Contoso Ltd"]);
+    assert_eq!(cfg.logging.level, logging_before);
+  }
+
+  #[test]
+  fn rejects_bad_signature() {
+    let err = verify_profile_bytes(PROFILE.as_bytes(), &[0u8; 64]).unwrap_err();
+    assert!(err.to_string().contains("signature"), "{err:#}");
+  }
+}
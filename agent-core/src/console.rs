@@ -1,9 +1,30 @@
-use crate::config::Config;
+use crate::baseline::{self, SuggestionStatus};
+use crate::config::{self, Config};
+use crate::config_profile;
+use crate::coverage;
+use crate::heartbeat;
+use crate::incident_chain;
 use crate::incident_store;
 use crate::kill_switch;
+use crate::net_usage;
+use crate::rules_engine::policy;
 use crate::runtime;
-use crate::types::{now_unix_ms, Event, FileAccessType};
+use crate::scan_history;
+use crate::scan_ipc;
+use crate::scenario;
+use crate::secrets;
+use crate::self_protection;
+use crate::service_install;
+use crate::support_bundle;
+use crate::timeline;
+use crate::trust;
+use crate::types::{
+  now_unix_ms, utc_timestamp, Event, FileAccessType, Incident, Severity, Verdict,
+};
+use crate::watch;
 use crate::{license, paths, threat_feed};
+use anyhow::Context;
+use std::net::IpAddr;
 use std::sync::mpsc;
 use std::time::Duration;
 
@@ -24,6 +45,10 @@ pub fn run_console_command(cfg: &Config, args: &[String]) -> anyhow::Result<Cons
     return run_killswitch(cfg, &args[i + 1..]);
   }
 
+  if let Some(i) = args.iter().position(|a| a == "--containment") {
+    return run_containment(&args[i + 1..]);
+  }
+
   if let Some(i) = args.iter().position(|a| a == "--incidents") {
     return run_incidents(&args[i + 1..]);
   }
@@ -33,17 +58,86 @@ pub fn run_console_command(cfg: &Config, args: &[String]) -> anyhow::Result<Cons
   }
 
   if let Some(i) = args.iter().position(|a| a == "--license") {
-    return run_license(&args[i + 1..]);
+    return run_license(cfg, &args[i + 1..]);
+  }
+
+  if let Some(i) = args.iter().position(|a| a == "--trust") {
+    return run_trust(&args[i + 1..]);
   }
 
   if let Some(i) = args.iter().position(|a| a == "--feed") {
     return run_feed(cfg, &args[i + 1..]);
   }
 
+  if let Some(i) = args.iter().position(|a| a == "--scanner") {
+    return run_scanner(&args[i + 1..]);
+  }
+
+  if let Some(i) = args.iter().position(|a| a == "--allowlist") {
+    return run_allowlist(cfg, &args[i + 1..]);
+  }
+
+  if let Some(i) = args.iter().position(|a| a == "--baseline") {
+    return run_baseline(cfg, &args[i + 1..]);
+  }
+
+  if let Some(i) = args.iter().position(|a| a == "--rules") {
+    return run_rules(cfg, &args[i + 1..]);
+  }
+
+  if let Some(i) = args.iter().position(|a| a == "--config") {
+    return run_config(cfg, &args[i + 1..]);
+  }
+
+  if let Some(i) = args.iter().position(|a| a == "--secrets") {
+    return run_secrets(&args[i + 1..]);
+  }
+
+  if let Some(i) = args.iter().position(|a| a == "--service") {
+    return run_service(&args[i + 1..]);
+  }
+  if let Some(i) = args.iter().position(|a| a == "--watch") {
+    return run_watch(cfg, &args[i + 1..]);
+  }
+
+  if let Some(i) = args.iter().position(|a| a == "--debug") {
+    return run_debug(cfg, &args[i + 1..]);
+  }
+
+  if let Some(i) = args.iter().position(|a| a == "--replay") {
+    return run_replay(cfg, &args[i + 1..]);
+  }
+
+  // Not in --help: a developer tool for measuring engine changes.
+  if let Some(i) = args.iter().position(|a| a == "--bench-engine") {
+    return run_bench_engine(&args[i + 1..]);
+  }
+
+  if args.iter().any(|a| a == "--coverage") {
+    return run_coverage(cfg);
+  }
+
+  if args.iter().any(|a| a == "--status") {
+    return run_status();
+  }
+
   Ok(ConsoleAction::RunAgent)
 }
 
 fn run_killswitch(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
+  // The scheduled-task restorer must run even if containment was disabled in config meanwhile.
+  if tail.first().map(String::as_str) == Some("failsafe-restore") {
+    let restored = kill_switch::run_failsafe_restore()?;
+    println!(
+      "{}",
+      if restored {
+        "Kill switch is off; networking is restored."
+      } else {
+        "Kill switch is still on (failsafe not expired or keep_locked set)."
+      }
+    );
+    return Ok(ConsoleAction::ExitOk);
+  }
   if !cfg.killswitch.enabled {
     println!("Kill switch is disabled in config (`killswitch.enabled = false`).");
     return Ok(ConsoleAction::ExitOk);
@@ -52,7 +146,16 @@ fn run_killswitch(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction
   let sub = tail.first().map(|s| s.as_str()).unwrap_or("");
   match sub {
     "on" => {
-      if let Err(e) = kill_switch::enable_manual() {
+      let duration_minutes = match tail.iter().position(|a| a == "--duration") {
+        Some(i) => {
+          let raw = tail.get(i + 1).map(String::as_str).unwrap_or("");
+          Some(parse_duration_minutes(raw).ok_or_else(|| {
+            anyhow::anyhow!("expected a duration like `30m` or `2h` for `--duration`, got `{raw}`")
+          })?)
+        }
+        None => None,
+      };
+      if let Err(e) = kill_switch::enable_manual(duration_minutes) {
         print_admin_hint(&e);
         return Err(e);
       }
@@ -62,9 +165,13 @@ fn run_killswitch(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction
       }
       tracing::warn!(
         group = kill_switch::FIREWALL_RULE_GROUP,
+        duration_minutes,
         "manual kill switch enabled"
       );
       println!("Kill switch enabled: ALL inbound + outbound traffic is now blocked.");
+      if let Some(m) = duration_minutes {
+        println!("Networking is restored automatically in {m} minute(s).");
+      }
       Ok(ConsoleAction::ExitOk)
     }
     "off" => {
@@ -110,20 +217,52 @@ fn run_killswitch(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction
       Ok(ConsoleAction::ExitOk)
     }
     _ => {
-      eprintln!("Unknown `--killswitch` subcommand. Expected: on|off|status|keep-locked");
+      eprintln!(
+        "Unknown `--killswitch` subcommand. Expected: on|off|status|keep-locked|failsafe-restore"
+      );
       print_help();
       Ok(ConsoleAction::ExitOk)
     }
   }
 }
 
-fn run_license(tail: &[String]) -> anyhow::Result<ConsoleAction> {
+fn run_license(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
   let base = paths::base_dir()?;
 
   let sub = tail.first().map(|s| s.as_str()).unwrap_or("");
   match sub {
     "status" => {
       let st = license::status(&base);
+      let act = license::read_activation_state(&base)
+        .filter(|a| st.license_id.as_ref() == Some(&a.license_id));
+      if tail.iter().any(|a| a == "--json") {
+        let licensed: Vec<&str> = license::FEATURES
+          .iter()
+          .copied()
+          .filter(|f| st.has_feature(f))
+          .collect();
+        let report = serde_json::json!({
+          "state": st.state.as_str(),
+          "license_id": st.license_id,
+          "plan": st.plan,
+          "seats": st.seats,
+          "seats_used": act.as_ref().and_then(|a| a.seats_used),
+          "features": licensed,
+          "expires_at_unix_seconds": st.expires_at_unix_seconds,
+          "last_verified_at_unix_seconds": st.last_verified_at_unix_seconds,
+          "checked_at_unix_seconds": st.checked_at_unix_seconds,
+          "reason": st.reason,
+          "device": {
+            "device_id": act.as_ref().map(|a| a.device_id.clone()).or_else(|| license::device_id(&base)),
+            "label": act.as_ref().and_then(|a| a.device_label.clone()),
+            "activated": act.is_some(),
+            "activated_at_unix_seconds": act.as_ref().map(|a| a.activated_at),
+            "activation": act.as_ref().map(|a| a.backend.as_deref().unwrap_or("local")),
+          },
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(ConsoleAction::ExitOk);
+      }
       println!(
         "License: {}",
         match st.state {
@@ -141,8 +280,32 @@ fn run_license(tail: &[String]) -> anyhow::Result<ConsoleAction> {
         println!("Plan: {plan}");
       }
       if let Some(seats) = st.seats {
-        println!("Seats: {seats} (server activation planned; offline mode is local-only)");
+        match act.as_ref().and_then(|a| a.seats_used) {
+          Some(used) => println!("Seats: {seats} ({used} in use)"),
+          None => println!("Seats: {seats} (not counted with local activation)"),
+        }
+      }
+      if let Some(act) = &act {
+        println!("Activation: {}", act.backend.as_deref().unwrap_or("local"));
+        if let Some(label) = act.device_label.as_deref() {
+          println!("Device: {label} ({})", act.device_id);
+        } else {
+          println!("Device: {}", act.device_id);
+        }
       }
+      let licensed: Vec<&str> = license::FEATURES
+        .iter()
+        .copied()
+        .filter(|f| st.has_feature(f))
+        .collect();
+      println!(
+        "Features: {}",
+        if licensed.is_empty() {
+          "none".to_string()
+        } else {
+          licensed.join(", ")
+        }
+      );
       match st.expires_at_unix_seconds {
         Some(exp) => println!("Expires (unix seconds): {exp}"),
         None => println!("Expires: none"),
@@ -180,7 +343,16 @@ fn run_license(tail: &[String]) -> anyhow::Result<ConsoleAction> {
       Ok(ConsoleAction::ExitOk)
     }
     "activate" => {
-      let st = license::activate(&base)?;
+      let name = match tail.iter().position(|a| a == "--name") {
+        Some(i) => Some(
+          tail
+            .get(i + 1)
+            .map(String::as_str)
+            .ok_or_else(|| anyhow::anyhow!("expected: --license activate [--name <label>]"))?,
+        ),
+        None => None,
+      };
+      let st = license::activate(&base, &*license::activation::backend(cfg)?, name)?;
       if runtime::is_dry_run() {
         println!("DRY-RUN: would activate license on this device.");
         return Ok(ConsoleAction::ExitOk);
@@ -197,7 +369,7 @@ fn run_license(tail: &[String]) -> anyhow::Result<ConsoleAction> {
       Ok(ConsoleAction::ExitOk)
     }
     "deactivate" => {
-      license::deactivate(&base)?;
+      license::deactivate(&base, &*license::activation::backend(cfg)?)?;
       if runtime::is_dry_run() {
         println!("DRY-RUN: would deactivate this device.");
         return Ok(ConsoleAction::ExitOk);
@@ -207,7 +379,7 @@ fn run_license(tail: &[String]) -> anyhow::Result<ConsoleAction> {
     }
     _ => {
       eprintln!(
-        "Unknown `--license` subcommand. Expected: status|install <json> <sig>|activate|deactivate"
+        "Unknown `--license` subcommand. Expected: status [--json]|install <json> <sig>|activate [--name <label>]|deactivate"
       );
       print_help();
       Ok(ConsoleAction::ExitOk)
@@ -240,9 +412,21 @@ fn run_feed(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
       if let Some(ts) = st.last_refresh_attempt_at {
         println!("Last refresh attempt (unix seconds): {ts}");
       }
+      if let Some(signer) = st.signer {
+        println!("Signed by: {signer}");
+      }
       if let Some(result) = st.last_refresh_result {
         println!("Last refresh result: {result}");
       }
+      match threat_feed::proxy::select(&cfg.threat_feed) {
+        Some(p) => println!(
+          "Proxy: {} (from {})",
+          threat_feed::proxy::label(&p.url),
+          p.source
+        ),
+        None => println!("Proxy: none (direct)"),
+      }
+      print_network_usage(&base);
       Ok(ConsoleAction::ExitOk)
     }
     "import" => {
@@ -268,8 +452,10 @@ fn run_feed(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
       if b.is_empty() || s.is_empty() {
         anyhow::bail!("expected: --feed verify <path-to-bundle.json> <path-to-bundle.sig>");
       }
-      let bundle = threat_feed::verify_files(std::path::Path::new(b), std::path::Path::new(s))?;
+      let (bundle, signer) =
+        threat_feed::verify_files_signed(std::path::Path::new(b), std::path::Path::new(s))?;
       println!("Threat feed bundle verified.");
+      println!("Signed by: {}", signer.label());
       println!("Bundle schema version: {}", bundle.version);
       println!("Rules version: {}", bundle.rules_version);
       println!("Created at (unix seconds): {}", bundle.created_at);
@@ -313,6 +499,17 @@ fn run_feed(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
         Some(v) => println!("Last result: {v}"),
         None => println!("Last result: none"),
       }
+      if let Some(bytes) = st.last_bytes {
+        println!("Last download: {}", net_usage::format_bytes(bytes));
+      }
+      let connection = match st.metered {
+        Some(true) if st.defer_on_metered => "metered (scheduled refreshes deferred)",
+        Some(true) => "metered",
+        Some(false) => "not metered",
+        None => "unknown",
+      };
+      println!("Connection: {connection}");
+      print_network_usage(&base);
       Ok(ConsoleAction::ExitOk)
     }
     _ => {
@@ -325,45 +522,248 @@ fn run_feed(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
   }
 }
 
-fn run_simulate(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
+fn print_network_usage(base: &std::path::Path) {
+  let months = net_usage::monthly(base);
+  if months.is_empty() {
+    println!("Network usage: none recorded");
+    return;
+  }
+  println!("Network usage (threat feed downloads, UTC months):");
+  for (month, usage) in months.iter().take(3) {
+    println!(
+      "- {month}: {} in {} request(s)",
+      net_usage::format_bytes(usage.threat_feed_bytes),
+      usage.threat_feed_requests
+    );
+  }
+}
+
+fn run_coverage(cfg: &Config) -> anyhow::Result<ConsoleAction> {
+  let base = paths::base_dir()?;
+  let report = coverage::assess(cfg, &base);
+
+  println!("Event sources:");
+  for s in &report.sources {
+    println!(
+      "- {}: {}",
+      s.source.label(),
+      if s.active { "active" } else { "MISSING" }
+    );
+  }
+  println!(
+    "Threat feed: {}",
+    if report.threat_feed_installed {
+      "installed"
+    } else {
+      "not installed"
+    }
+  );
+
+  println!("Rules:");
+  for r in &report.rules {
+    if r.can_fire {
+      println!("- {} can fire: {}", r.rule_id, r.title);
+    } else {
+      println!(
+        "- {} BLIND (needs {}): {}",
+        r.rule_id,
+        r.missing.join(", "),
+        r.title
+      );
+    }
+  }
+
+  println!("Protected targets:");
+  for p in &report.protected {
+    if !p.root_present {
+      println!(
+        "- {} [{}]: not present ({})",
+        p.browser,
+        p.user,
+        p.root.display()
+      );
+      continue;
+    }
+    if p.targets_found.is_empty() {
+      println!(
+        "- {} [{}]: profile root present, no protected targets found",
+        p.browser, p.user
+      );
+    } else {
+      println!(
+        "- {} [{}] ({} profile(s)): {}",
+        p.browser,
+        p.user,
+        p.profiles,
+        p.targets_found.join(", ")
+      );
+    }
+  }
+
+  let blind = report.blind_spots();
+  if blind == 0 {
+    println!("Coverage: no blind spots detected.");
+  } else {
+    println!("Coverage: {blind} blind spot(s); see MISSING/BLIND entries above.");
+  }
+  Ok(ConsoleAction::ExitOk)
+}
+
+fn run_status() -> anyhow::Result<ConsoleAction> {
+  let base = paths::base_dir()?;
+  let Some(beat) = heartbeat::load(&base) else {
+    println!(
+      "Agent: no heartbeat recorded (the agent has not run yet, or cannot write to ProgramData)."
+    );
+    return Ok(ConsoleAction::ExitOk);
+  };
+
+  let now = now_unix_ms();
+  let age_seconds = now.saturating_sub(beat.updated_at_unix_ms) / 1000;
+  println!(
+    "Agent: {} (pid {}, v{}, {:?} mode{}), heartbeat {}s ago",
+    if beat.is_stale(now) {
+      "NOT RESPONDING"
+    } else {
+      "running"
+    },
+    beat.pid,
+    beat.agent_version,
+    beat.mode,
+    if beat.worker { ", worker" } else { "" },
+    age_seconds
+  );
+  let uptime = beat.uptime_seconds();
+  println!(
+    "Uptime: {}h {}m (since {})",
+    uptime / 3_600,
+    uptime % 3_600 / 60,
+    utc_timestamp(beat.started_at_unix_ms)
+  );
+  match beat.last_batch_at_unix_ms {
+    Some(at) => println!(
+      "Last event batch: {} event(s) at {} ({} total)",
+      beat.last_batch_events,
+      utc_timestamp(at),
+      beat.events_total
+    ),
+    None => println!("Last event batch: none yet"),
+  }
+  if beat.collector_errors.is_empty() {
+    println!("Collector: ok");
+  } else {
+    for e in &beat.collector_errors {
+      println!("Collector: ERROR {e}");
+    }
+  }
+  if beat.records_behind > 0 || beat.queued_events > 0 {
+    println!(
+      "Collector lag: {} event log record(s) not yet read, {} event(s) queued",
+      beat.records_behind, beat.queued_events
+    );
+  }
+  if beat.dropped_batches > 0 {
+    println!(
+      "Collector: WARNING {} batch(es) dropped with the queue full ({} event(s) lost since start); raise [collector] queue_capacity or max_records_per_tick",
+      beat.dropped_batches, beat.dropped_events
+    );
+  }
+  match beat.feed_version {
+    Some(v) => println!("Threat feed: rules_version {v}"),
+    None => println!("Threat feed: not installed"),
+  }
+  println!("License: {}", beat.license);
+
+  if beat.is_stale(now) {
+    println!(
+      "WARNING: the heartbeat is {age_seconds}s old (expected every few seconds); the agent is stopped or hung. Check the service and the agent log."
+    );
+  }
+  Ok(ConsoleAction::ExitOk)
+}
+
+fn run_containment(tail: &[String]) -> anyhow::Result<ConsoleAction> {
   let sub = tail.first().map(|s| s.as_str()).unwrap_or("");
   match sub {
-    "red" => {
-      // Alias for `--simulate chain-red` to ensure simulations go through the same pipeline.
-      let pid = 4242;
-      let base = now_unix_ms();
-      let image = "C:\\Temp\\evil.exe".to_string();
-      let file_path = format!(
-        "{}\\Google\\Chrome\\User Data\\Default\\Login Data",
-        localappdata()
+    "snapshot" => {
+      let Some(out) = tail.get(1) else {
+        anyhow::bail!("expected: --containment snapshot <file>");
+      };
+      if runtime::is_dry_run() {
+        println!("DRY-RUN: would write signed containment snapshot to {out}");
+        return Ok(ConsoleAction::ExitOk);
+      }
+      let base = paths::base_dir()?;
+      let doc = kill_switch::snapshot::create(&base)?;
+      std::fs::write(out, serde_json::to_string_pretty(&doc)?)
+        .with_context(|| format!("write {out}"))?;
+
+      let rules = doc.snapshot["firewall_rules"]
+        .as_array()
+        .map(|a| a.len())
+        .unwrap_or(0);
+      println!("Containment snapshot written: {out}");
+      println!(
+        "Kill switch state: {}  Firewall rules in group: {rules}",
+        if doc.snapshot["killswitch"]["state_enabled"] == true {
+          "ON"
+        } else {
+          "OFF"
+        }
       );
+      if let Some(err) = doc.snapshot["firewall_error"].as_str() {
+        println!("Firewall query failed: {err}");
+      }
+      println!("Signing public key: {}", doc.public_key);
+      Ok(ConsoleAction::ExitOk)
+    }
+    "verify" => {
+      let Some(path) = tail.get(1) else {
+        anyhow::bail!("expected: --containment verify <file>");
+      };
+      let raw = std::fs::read_to_string(path).with_context(|| format!("read {path}"))?;
+      let doc: kill_switch::snapshot::SignedSnapshot =
+        serde_json::from_str(&raw).with_context(|| format!("parse {path}"))?;
+      let base = paths::base_dir()?;
+      let v = kill_switch::snapshot::verify(&base, &doc)?;
 
-      let events = vec![
-        Event::ProcessStart {
-          pid,
-          ppid: 0,
-          image_path: image.clone(),
-          signer_publisher: None,
-          timestamp_unix_ms: base,
-        },
-        Event::FileAccess {
-          pid,
-          image_path: Some(image.clone()),
-          file_path: file_path.clone(),
-          access: FileAccessType::Read,
-          timestamp_unix_ms: base + 1_000,
-        },
-        Event::NetConnect {
-          pid,
-          image_path: Some(image),
-          dest_ip: "1.2.3.4".to_string(),
-          dest_port: 443,
-          dest_host: None,
-          protocol: "tcp".to_string(),
-          timestamp_unix_ms: base + 2_000,
-        },
-      ];
+      println!(
+        "Signature: {}",
+        if v.signature_valid {
+          "VALID"
+        } else {
+          "INVALID (document was modified)"
+        }
+      );
+      match v.signed_by_this_machine {
+        Some(true) => println!("Signed by this machine: yes"),
+        Some(false) => println!("Signed by this machine: no (public key {})", doc.public_key),
+        None => println!("Signed by this machine: unknown (no local signing key)"),
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "key" => {
+      let base = paths::base_dir()?;
+      match kill_switch::snapshot::machine_public_key(&base) {
+        Some(k) => println!("Snapshot signing public key: {k}"),
+        None => println!("No snapshot signing key yet (created by the first snapshot)."),
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    _ => {
+      eprintln!("Unknown `--containment` subcommand. Expected: snapshot <file>|verify <file>|key");
+      print_help();
+      Ok(ConsoleAction::ExitOk)
+    }
+  }
+}
 
+fn run_simulate(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
+  let sub = tail.first().map(|s| s.as_str()).unwrap_or("");
+  match sub {
+    "red" => {
+      // Alias for `--simulate chain-red` to ensure simulations go through the same pipeline.
+      let events = red_chain_events(now_unix_ms());
       let incident_ids = run_events_through_pipeline(cfg, events)?;
       let incident_id = incident_ids.first().cloned().unwrap_or_default();
 
@@ -420,6 +820,10 @@ fn run_simulate(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction>
           ppid: 0,
           image_path: image.clone(),
           signer_publisher: None,
+          command_line: None,
+          download_origin: None,
+          process_guid: None,
+          parent_process_guid: None,
           timestamp_unix_ms: base,
         },
         Event::FileAccess {
@@ -427,6 +831,7 @@ fn run_simulate(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction>
           image_path: Some(image),
           file_path,
           access: FileAccessType::Read,
+          process_guid: None,
           timestamp_unix_ms: base + 1_000,
         },
       ];
@@ -450,15 +855,20 @@ fn run_simulate(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction>
           ppid: 0,
           image_path: image.clone(),
           signer_publisher: None,
+          command_line: None,
+          download_origin: None,
+          process_guid: None,
+          parent_process_guid: None,
           timestamp_unix_ms: base,
         },
         Event::NetConnect {
           pid,
           image_path: Some(image),
-          dest_ip: "1.2.3.4".to_string(),
+          dest_ip: IpAddr::from([1, 2, 3, 4]),
           dest_port: 443,
           dest_host: None,
           protocol: "tcp".to_string(),
+          process_guid: None,
           timestamp_unix_ms: base + 1_000,
         },
       ];
@@ -472,36 +882,112 @@ fn run_simulate(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction>
       Ok(ConsoleAction::ExitOk)
     }
     "chain-red" => {
-      let pid = 4242;
+      let events = red_chain_events(now_unix_ms());
+      let incident_ids = run_events_through_pipeline(cfg, events)?;
+      if incident_ids.is_empty() {
+        println!("No incidents generated.");
+      } else {
+        println!("Generated incidents: {}", incident_ids.join(", "));
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "extension-sideload" => {
+      let chrome = "C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe".to_string();
       let base = now_unix_ms();
-      let image = "C:\\Temp\\evil.exe".to_string();
-      let file_path = format!(
-        "{}\\Google\\Chrome\\User Data\\Default\\Login Data",
-        localappdata()
-      );
+      let events = vec![
+        Event::ProcessStart {
+          pid: 4343,
+          ppid: 0,
+          image_path: chrome.clone(),
+          signer_publisher: Some("Google LLC".to_string()),
+          command_line: Some(format!(
+            "\"{chrome}\" --load-extension={}\\Temp\\ext",
+            localappdata()
+          )),
+          download_origin: None,
+          process_guid: None,
+          parent_process_guid: None,
+          timestamp_unix_ms: base,
+        },
+        Event::RegistrySet {
+          pid: 4344,
+          image_path: Some("C:\\Temp\\evil.exe".to_string()),
+          key_path: "HKLM\\SOFTWARE\\Policies\\Google\\Chrome\\ExtensionInstallForcelist\\1"
+            .to_string(),
+          value: Some(
+            "aaaabbbbccccddddeeeeffffgggghhhh;https://example.invalid/update.xml".to_string(),
+          ),
+          timestamp_unix_ms: base + 1_000,
+        },
+      ];
+
+      let incident_ids = run_events_through_pipeline(cfg, events)?;
+      if incident_ids.is_empty() {
+        println!("No incidents generated.");
+      } else {
+        println!("Generated incidents: {}", incident_ids.join(", "));
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "network-hijack" => {
+      let base = now_unix_ms();
+      let evil = Some("C:\\Temp\\evil.exe".to_string());
+      let events = vec![
+        Event::RegistrySet {
+          pid: 4545,
+          image_path: evil.clone(),
+          key_path: "HKU\\S-1-5-21-0\\Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings\\AutoConfigURL".to_string(),
+          value: Some("http://203.0.113.9/proxy.pac".to_string()),
+          timestamp_unix_ms: base,
+        },
+        Event::RegistrySet {
+          pid: 4545,
+          image_path: evil,
+          key_path: "HKLM\\System\\CurrentControlSet\\Services\\Tcpip\\Parameters\\Interfaces\\{00000000-0000-0000-0000-000000000000}\\NameServer".to_string(),
+          value: Some("203.0.113.53".to_string()),
+          timestamp_unix_ms: base + 1_000,
+        },
+      ];
 
+      let incident_ids = run_events_through_pipeline(cfg, events)?;
+      if incident_ids.is_empty() {
+        println!("No incidents generated.");
+      } else {
+        println!("Generated incidents: {}", incident_ids.join(", "));
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "dns-exfil" => {
+      let base = now_unix_ms();
+      let image = "C:\\Temp\\evil.exe".to_string();
       let events = vec![
         Event::ProcessStart {
-          pid,
+          pid: 4646,
           ppid: 0,
           image_path: image.clone(),
           signer_publisher: None,
+          command_line: None,
+          download_origin: None,
+          process_guid: None,
+          parent_process_guid: None,
           timestamp_unix_ms: base,
         },
         Event::FileAccess {
-          pid,
+          pid: 4646,
           image_path: Some(image.clone()),
-          file_path: file_path.clone(),
+          file_path: format!(
+            "{}\\Google\\Chrome\\User Data\\Default\\Login Data",
+            localappdata()
+          ),
           access: FileAccessType::Read,
+          process_guid: None,
           timestamp_unix_ms: base + 1_000,
         },
-        Event::NetConnect {
-          pid,
+        Event::DnsQuery {
+          pid: 4646,
           image_path: Some(image),
-          dest_ip: "1.2.3.4".to_string(),
-          dest_port: 443,
-          dest_host: None,
-          protocol: "tcp".to_string(),
+          query_name: "xjw9kq2vbz7hd.top".to_string(),
+          query_results: Some("203.0.113.7;".to_string()),
           timestamp_unix_ms: base + 2_000,
         },
       ];
@@ -514,9 +1000,68 @@ fn run_simulate(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction>
       }
       Ok(ConsoleAction::ExitOk)
     }
+    "clipboard-hijack" => {
+      let base = now_unix_ms();
+      let events = vec![
+        Event::ClipboardChange {
+          pid: 4700,
+          image_path: Some("C:\\Program Files\\Wallet\\wallet.exe".to_string()),
+          address: Some("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_string()),
+          timestamp_unix_ms: base,
+        },
+        Event::ClipboardChange {
+          pid: 4747,
+          image_path: Some("C:\\Temp\\clipper.exe".to_string()),
+          address: Some("bc1qm34lsc65zpw79lxes69zkqmk6ee3ewf0j77s3h".to_string()),
+          timestamp_unix_ms: base + 120,
+        },
+      ];
+
+      let incident_ids = run_events_through_pipeline(cfg, events)?;
+      if incident_ids.is_empty() {
+        println!("No incidents generated.");
+      } else {
+        println!("Generated incidents: {}", incident_ids.join(", "));
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "ransomware" => {
+      // Always dry-run: shows the response without locking the network or storing incidents.
+      runtime::set_dry_run(true);
+      let home = crate::user_profiles::UserProfile::current_process().home;
+      let files = cfg.ransomware.min_files.max(1);
+      let incidents = run_pipeline(cfg, ransomware_events(now_unix_ms(), &home, files))?;
+
+      println!(
+        "Simulated ransomware: {files} documents replaced with .locked copies  Incidents: {}  (mode: {:?}, dry-run)",
+        incidents.len(),
+        cfg.mode
+      );
+      for inc in &incidents {
+        let mut rule_ids: Vec<&str> = inc.findings.iter().map(|f| f.rule_id.as_str()).collect();
+        rule_ids.sort_unstable();
+        rule_ids.dedup();
+        println!(
+          "Incident {} severity={:?} rules={} actions={}",
+          inc.incident_id,
+          inc.severity,
+          rule_ids.join(","),
+          inc.actions_taken.join(",")
+        );
+      }
+      if !cfg.ransomware.enabled {
+        println!("R025 is off ([ransomware] enabled = false).");
+      } else if cfg.mode != config::Mode::Strict || !cfg.killswitch.auto_trigger {
+        println!(
+          "The kill switch only triggers in strict mode with killswitch.auto_trigger = true."
+        );
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "from-file" => run_scenarios(cfg, tail.get(1)),
     _ => {
       eprintln!(
-        "Unknown `--simulate` subcommand. Expected: red|file-access-chrome|net-connect|chain-red"
+        "Unknown `--simulate` subcommand. Expected: red|file-access-chrome|net-connect|chain-red|extension-sideload|network-hijack|dns-exfil|clipboard-hijack|ransomware|from-file"
       );
       print_help();
       Ok(ConsoleAction::ExitOk)
@@ -538,26 +1083,1060 @@ fn run_incidents(tail: &[String]) -> anyhow::Result<ConsoleAction> {
       println!("Last {}/{} incidents:", items.len(), limit);
       for it in items {
         println!(
-          "- {} severity={:?} created_at_unix_ms={} rules={}",
+          "- {} severity={:?} score={} created_at_unix_ms={} rules={}",
           it.incident_id,
           it.severity,
+          it.score
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "-".to_string()),
           it.created_at_unix_ms,
           it.rule_ids.join(",")
         );
       }
       Ok(ConsoleAction::ExitOk)
     }
-    _ => {
-      eprintln!("Unknown `--incidents` subcommand. Expected: list [--limit N]");
-      print_help();
-      Ok(ConsoleAction::ExitOk)
-    }
-  }
-}
-
-fn parse_limit(args: &[String]) -> Option<usize> {
-  let mut i = 0;
-  while i < args.len() {
+    "show" => {
+      let id = incident_id_arg(tail.get(1), "--incidents show <incident-id>")?;
+      let inc = incident_store::load(id).with_context(|| format!("load incident {id}"))?;
+      println!("Incident {}", inc.incident_id);
+      println!("Severity: {:?}", inc.severity);
+      if let Some(score) = &inc.score {
+        println!("Risk score: {}/100", score.total);
+      }
+      println!("Created at (unix ms): {}", inc.created_at_unix_ms);
+      match &inc.resolution {
+        Some(r) => println!(
+          "Resolution: {:?} at {} (unix ms){}",
+          r.verdict,
+          r.resolved_at_unix_ms,
+          r.note
+            .as_deref()
+            .map(|n| format!(" - {n}"))
+            .unwrap_or_default()
+        ),
+        None => println!("Resolution: unresolved"),
+      }
+      if let Some(flood) = &inc.flood {
+        println!(
+          "Flood control: {} duplicate finding(s), {} rate-limited incident(s) merged, last at {} (unix ms)",
+          flood.duplicate_findings, flood.rate_limited_incidents, flood.last_merged_at_unix_ms
+        );
+      }
+      println!("Findings:");
+      for (i, f) in inc.findings.iter().enumerate() {
+        println!("  - {} {:?}: {}", f.rule_id, f.severity, f.description);
+        if let Some(s) = inc.score.as_ref().and_then(|s| s.findings.get(i)) {
+          println!("      score {}: {}", s.score, s.factors.join(", "));
+        }
+        for e in &f.evidence {
+          println!("      {}", serde_json::to_string(e)?);
+        }
+      }
+      print_guidance(&inc);
+      println!("Actions: {}", inc.actions_taken.join(", "));
+      print_quarantine(&inc.actions_taken);
+      let scans: Vec<_> = scan_history::list_recent(100)?
+        .into_iter()
+        .filter(|r| r.incident_id.as_deref() == Some(id))
+        .collect();
+      for r in scans {
+        println!(
+          "Linked scan: {} mode={} outcome={:?} findings={}",
+          r.scan_id, r.mode, r.outcome, r.findings_count
+        );
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "resolve" => {
+      const USAGE: &str = "--incidents resolve <incident-id> false-positive|confirmed [note]";
+      let id = incident_id_arg(tail.get(1), USAGE)?;
+      let verdict = match tail.get(2).map(|s| s.as_str()) {
+        Some("false-positive") => Verdict::FalsePositive,
+        Some("confirmed") => Verdict::Confirmed,
+        _ => anyhow::bail!("expected: {USAGE}"),
+      };
+      let note = tail[3.min(tail.len())..].join(" ");
+      let note = Some(note.trim()).filter(|n| !n.is_empty());
+
+      let inc = incident_store::load(id).with_context(|| format!("load incident {id}"))?;
+      incident_store::resolve(id, verdict, note)?;
+      if runtime::is_dry_run() {
+        println!("DRY-RUN: would mark incident {id} as {verdict:?}.");
+      } else {
+        println!("Incident {id} marked as {verdict:?}.");
+      }
+      if verdict == Verdict::FalsePositive {
+        print_quarantine(&inc.actions_taken);
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "timeline" => {
+      const USAGE: &str = "--incidents timeline <incident-id> | --pid <pid>";
+      // Older incidents than this are not searched for the PID.
+      const MAX_INCIDENTS: usize = 1000;
+      let pids = match tail.get(1).map(|s| s.as_str()) {
+        Some("--pid") => {
+          let pid = tail
+            .get(2)
+            .and_then(|p| p.parse::<u32>().ok())
+            .ok_or_else(|| anyhow::anyhow!("expected: {USAGE}"))?;
+          vec![pid]
+        }
+        _ => {
+          let id = incident_id_arg(tail.get(1), USAGE)?;
+          let inc = incident_store::load(id).with_context(|| format!("load incident {id}"))?;
+          timeline::incident_pids(&inc)
+        }
+      };
+      if pids.is_empty() {
+        println!("The incident names no process.");
+        return Ok(ConsoleAction::ExitOk);
+      }
+      let incidents = incident_store::load_recent(MAX_INCIDENTS)?;
+      let events = timeline::recorded_events(&paths::base_dir()?);
+      let entries = timeline::build(&pids, &incidents, &events);
+
+      let pid_list: Vec<String> = pids.iter().map(u32::to_string).collect();
+      println!("Timeline for PID {}:", pid_list.join(", "));
+      let first = entries.first().map_or(0, |e| e.timestamp_unix_ms);
+      for e in &entries {
+        println!(
+          "  {} (+{:.1}s) [{}] {}",
+          utc_timestamp(e.timestamp_unix_ms),
+          e.timestamp_unix_ms.saturating_sub(first) as f64 / 1000.0,
+          e.kind,
+          e.text
+        );
+      }
+      if events.is_empty() {
+        println!("No event recording found; enable [recording] to include the process's events.");
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "export" => {
+      let id = incident_id_arg(
+        tail.get(1),
+        "--incidents export <incident-id> [--out <file.zip>] [--yes]",
+      )?;
+      let out = match tail.iter().position(|a| a == "--out") {
+        Some(i) => std::path::PathBuf::from(
+          tail
+            .get(i + 1)
+            .ok_or_else(|| anyhow::anyhow!("--out expects a file path"))?,
+        ),
+        None => std::path::PathBuf::from(format!("ai-defender-incident-{id}.zip")),
+      };
+      let inc = incident_store::load(id).with_context(|| format!("load incident {id}"))?;
+      let base = paths::base_dir()?;
+      let files = support_bundle::collect(&base, &inc)?;
+
+      println!("Support bundle for incident {id} will contain:");
+      for f in &files {
+        println!(
+          "  - {} ({} bytes): {}",
+          f.name,
+          f.contents.len(),
+          f.description
+        );
+      }
+      println!(
+        "Review before sharing: log lines and the incident keep file paths, process names and command lines."
+      );
+
+      if runtime::is_dry_run() {
+        println!("DRY-RUN: would write {}", out.display());
+        return Ok(ConsoleAction::ExitOk);
+      }
+      if !tail.iter().any(|a| a == "--yes") {
+        eprint!("Write {}? [y/N] ", out.display());
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+          println!("Not written.");
+          return Ok(ConsoleAction::ExitOk);
+        }
+      }
+      support_bundle::write_zip(&out, &files)
+        .with_context(|| format!("write {}", out.display()))?;
+      println!("Wrote {}", out.display());
+      Ok(ConsoleAction::ExitOk)
+    }
+    "verify" => {
+      let report = incident_chain::verify(&paths::base_dir()?);
+      if tail.iter().any(|a| a == "--json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+      } else {
+        println!(
+          "Checked {} chain record(s) and {} incident file(s).",
+          report.records, report.incidents_checked
+        );
+        for p in &report.problems {
+          let at = match (p.seq, p.incident_id.as_deref()) {
+            (Some(seq), Some(id)) => format!(" (record {seq}, incident {id})"),
+            (Some(seq), None) => format!(" (record {seq})"),
+            (None, Some(id)) => format!(" (incident {id})"),
+            (None, None) => String::new(),
+          };
+          println!("- {}{at}: {}", p.kind, p.detail);
+        }
+        if !report.unrecorded.is_empty() {
+          println!(
+            "{} incident file(s) are not in the chain (written before chaining was enabled, or by a scan run as the user): {}",
+            report.unrecorded.len(),
+            report.unrecorded.join(", ")
+          );
+        }
+      }
+      if !report.problems.is_empty() {
+        anyhow::bail!(
+          "incident records failed verification ({} problem(s))",
+          report.problems.len()
+        );
+      }
+      if !tail.iter().any(|a| a == "--json") {
+        println!("No tampering detected.");
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    _ => {
+      eprintln!(
+        "Unknown `--incidents` subcommand. Expected: list [--limit N] | show <id> | timeline <id> | timeline --pid <pid> | resolve <id> false-positive|confirmed [note] | export <id> [--out <file.zip>] [--yes] | verify [--json]"
+      );
+      print_help();
+      Ok(ConsoleAction::ExitOk)
+    }
+  }
+}
+
+fn incident_id_arg<'a>(arg: Option<&'a String>, usage: &str) -> anyhow::Result<&'a str> {
+  let Some(id) = arg.map(|s| s.trim()) else {
+    anyhow::bail!("expected: {usage}");
+  };
+  if uuid::Uuid::parse_str(id).is_err() {
+    anyhow::bail!("incident ID must be a UUID: {id}");
+  }
+  Ok(id)
+}
+
+/// Quarantine isn't implemented yet, so no files are ever held for an incident; say so instead of
+/// implying there is something to restore.
+fn print_quarantine(actions: &[String]) {
+  if actions.iter().any(|a| a == "quarantine_skipped_no_path") {
+    println!("Quarantined files: none (quarantine skipped: no executable path)");
+  } else {
+    println!("Quarantined files: none");
+  }
+}
+
+fn run_scanner(tail: &[String]) -> anyhow::Result<ConsoleAction> {
+  let sub = tail.first().map(|s| s.as_str()).unwrap_or("");
+  match sub {
+    "history" => {
+      match scan_history::last_completed("full")? {
+        Some(r) => println!(
+          "Last completed full scan: ended_at_unix_ms={} scan_id={}",
+          r.ended_at_unix_ms, r.scan_id
+        ),
+        None => println!("Last completed full scan: never"),
+      }
+
+      let limit = parse_limit(tail).unwrap_or(10);
+      let items = scan_history::list_recent(limit)?;
+      if items.is_empty() {
+        println!("No scans recorded.");
+        return Ok(ConsoleAction::ExitOk);
+      }
+
+      println!("Last {}/{} scans:", items.len(), limit);
+      for r in items {
+        println!(
+          "- {} mode={} outcome={:?} started_at_unix_ms={} ended_at_unix_ms={} files={} findings={} incident={} roots={}",
+          r.scan_id,
+          r.mode,
+          r.outcome,
+          r.started_at_unix_ms,
+          r.ended_at_unix_ms,
+          r.files_scanned,
+          r.findings_count,
+          r.incident_id.as_deref().unwrap_or("none"),
+          r.roots.len()
+        );
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "running" => {
+      let scans = scan_ipc::running(&paths::base_dir()?);
+      if scans.is_empty() {
+        println!("No scans registered with the agent.");
+        return Ok(ConsoleAction::ExitOk);
+      }
+      for s in scans {
+        let total = s
+          .total_files
+          .map_or_else(|| "?".to_string(), |t| t.to_string());
+        println!(
+          "- {} mode={} pid={} started_at_unix_ms={} scanned={}/{} findings={}",
+          s.scan_id, s.mode, s.pid, s.started_at_unix_ms, s.scanned, total, s.findings
+        );
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "cancel" => {
+      let id = tail
+        .get(1)
+        .ok_or_else(|| anyhow::anyhow!("expected: --scanner cancel <scan-id>"))?;
+      scan_ipc::request_cancel(&paths::base_dir()?, id)?;
+      println!("Cancel requested for scan_id={}", id.trim());
+      Ok(ConsoleAction::ExitOk)
+    }
+    _ => {
+      eprintln!(
+        "Unknown `--scanner` subcommand. Expected: history [--limit N] | running | cancel <scan-id>"
+      );
+      print_help();
+      Ok(ConsoleAction::ExitOk)
+    }
+  }
+}
+
+fn run_allowlist(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
+  let sub = tail.first().map(|s| s.as_str()).unwrap_or("");
+  let value = tail[1.min(tail.len())..].join(" ").trim().to_string();
+
+  if sub == "list" {
+    let a = &cfg.allowlist;
+    println!("Publishers:");
+    for p in &a.publishers {
+      println!("- {p}");
+    }
+    println!("Paths (prefix):");
+    for p in &a.paths_allowlist {
+      println!("- {p}");
+    }
+    println!("Hashes (SHA-256):");
+    for h in &a.hashes {
+      println!("- {h}");
+    }
+    return Ok(ConsoleAction::ExitOk);
+  }
+
+  if value.is_empty() {
+    eprintln!(
+      "Unknown `--allowlist` subcommand. Expected: add-publisher <name> | add-path <prefix> | add-hash <sha256> | list | remove <value>"
+    );
+    print_help();
+    return Ok(ConsoleAction::ExitOk);
+  }
+
+  config::ensure_unmanaged(cfg, "allowlist")?;
+  let mut updated = cfg.clone();
+  let a = &mut updated.allowlist;
+  let changed = match sub {
+    "add-publisher" => add_unique(&mut a.publishers, &value),
+    "add-path" => add_unique(&mut a.paths_allowlist, &value),
+    "add-hash" => {
+      if !config::is_sha256_hex(&value) {
+        anyhow::bail!("not a SHA-256 hex digest: {value}");
+      }
+      add_unique(&mut a.hashes, &value.to_ascii_lowercase())
+    }
+    "remove" => {
+      let before = a.publishers.len() + a.paths_allowlist.len() + a.hashes.len();
+      for list in [&mut a.publishers, &mut a.paths_allowlist, &mut a.hashes] {
+        list.retain(|e| !e.trim().eq_ignore_ascii_case(&value));
+      }
+      before != a.publishers.len() + a.paths_allowlist.len() + a.hashes.len()
+    }
+    _ => {
+      eprintln!(
+        "Unknown `--allowlist` subcommand. Expected: add-publisher <name> | add-path <prefix> | add-hash <sha256> | list | remove <value>"
+      );
+      print_help();
+      return Ok(ConsoleAction::ExitOk);
+    }
+  };
+
+  if !changed {
+    let what = if sub == "remove" {
+      "not found"
+    } else {
+      "already present"
+    };
+    println!("Allowlist unchanged ({what}): {value}");
+    return Ok(ConsoleAction::ExitOk);
+  }
+
+  if runtime::is_dry_run() {
+    println!("DRY-RUN: would update allowlist ({sub}): {value}");
+    return Ok(ConsoleAction::ExitOk);
+  }
+
+  let base = paths::base_dir()?;
+  config::save(&paths::config_path(&base), &updated)?;
+  self_protection::seal_config(&base);
+  println!("Allowlist updated ({sub}): {value}");
+  println!("Restart the agent service to apply.");
+  Ok(ConsoleAction::ExitOk)
+}
+
+fn run_baseline(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
+  let base = paths::base_dir()?;
+  let sub = tail.first().map(|s| s.as_str()).unwrap_or("");
+  match sub {
+    "status" => {
+      let b = baseline::Baseline::load(&base);
+      println!(
+        "Baseline: {}",
+        if cfg.baseline.enabled {
+          "enabled"
+        } else {
+          "disabled"
+        }
+      );
+      match b.learning_ends_at_unix_ms(&cfg.baseline) {
+        Some(ends) => println!(
+          "Learning started_at_unix_ms={} ends_at_unix_ms={ends}{}",
+          b.started_at_unix_ms(),
+          if ends <= now_unix_ms() {
+            " (complete)"
+          } else {
+            ""
+          }
+        ),
+        None => println!("Learning not started (no observations yet)."),
+      }
+      println!("Tuples tracked: {}", b.tuples_tracked());
+
+      let suggestions = baseline::load_suggestions(&base);
+      let count = |st| suggestions.iter().filter(|s| s.status == st).count();
+      println!(
+        "Suggestions: pending={} accepted={} rejected={}",
+        count(SuggestionStatus::Pending),
+        count(SuggestionStatus::Accepted),
+        count(SuggestionStatus::Rejected)
+      );
+      Ok(ConsoleAction::ExitOk)
+    }
+    "review" => {
+      let pending: Vec<_> = baseline::load_suggestions(&base)
+        .into_iter()
+        .filter(|s| s.status == SuggestionStatus::Pending)
+        .collect();
+      if pending.is_empty() {
+        println!("No pending baseline suggestions.");
+        return Ok(ConsoleAction::ExitOk);
+      }
+      config::ensure_unmanaged(cfg, "allowlist")?;
+
+      let mut updated = cfg.clone();
+      let mut accepted = 0;
+      for s in pending {
+        println!();
+        println!(
+          "[{}] {} touched {} {} time(s)",
+          s.id, s.image_path, s.target, s.count
+        );
+        println!(
+          "  publisher={} first_seen_unix_ms={} last_seen_unix_ms={}",
+          if s.publisher.is_empty() {
+            "<unknown>"
+          } else {
+            &s.publisher
+          },
+          s.first_seen_unix_ms,
+          s.last_seen_unix_ms
+        );
+        println!("  suggested allowlist entry: {:?} {}", s.kind, s.value);
+        eprint!("  [a]ccept / [r]eject / [s]kip? ");
+
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+          break;
+        }
+        let status = match answer.trim().to_ascii_lowercase().as_str() {
+          "a" | "accept" => SuggestionStatus::Accepted,
+          "r" | "reject" => SuggestionStatus::Rejected,
+          _ => continue,
+        };
+
+        if status == SuggestionStatus::Accepted {
+          let list = match s.kind {
+            baseline::EntryKind::Hash => &mut updated.allowlist.hashes,
+            baseline::EntryKind::Publisher => &mut updated.allowlist.publishers,
+          };
+          add_unique(list, &s.value);
+          accepted += 1;
+        }
+        baseline::set_status(&base, &s.id, status)?;
+      }
+
+      if accepted > 0 {
+        if runtime::is_dry_run() {
+          println!("DRY-RUN: would add accepted allowlist entries to config ({accepted})");
+        } else {
+          config::save(&paths::config_path(&base), &updated)?;
+          self_protection::seal_config(&base);
+          println!("Allowlist entries added: {accepted}. Restart the agent service to apply.");
+        }
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    _ => {
+      eprintln!("Unknown `--baseline` subcommand. Expected: status | review");
+      print_help();
+      Ok(ConsoleAction::ExitOk)
+    }
+  }
+}
+
+fn run_rules(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
+  let sub = tail.first().map(|s| s.as_str()).unwrap_or("");
+  match sub {
+    "list" => {
+      let (overrides, detections) = threat_feed::load_current()
+        .map(|b| (b.rules, b.detections))
+        .unwrap_or_default();
+      let source_label = |s: policy::PolicySource| match s {
+        policy::PolicySource::Default => "default",
+        policy::PolicySource::ThreatFeed => "threat-feed",
+        policy::PolicySource::Config => "config",
+      };
+      println!("Mode: {:?}", cfg.mode);
+      for r in policy::effective_rules(cfg, &overrides) {
+        println!(
+          "- {} {} severity={:?} default={:?} source={} {}",
+          r.meta.id,
+          if r.enabled { "enabled " } else { "DISABLED" },
+          r.severity,
+          r.meta.default_severity,
+          source_label(r.source),
+          r.meta.title
+        );
+      }
+      if !detections.is_empty() {
+        println!("Threat feed detections:");
+      }
+      for (d, enabled, severity, source) in
+        policy::effective_detections(cfg, &overrides, &detections)
+      {
+        println!(
+          "- {} {} severity={:?} default={:?} source={} {}",
+          d.rule_id.trim(),
+          if enabled { "enabled " } else { "DISABLED" },
+          severity,
+          Severity::from(d.severity),
+          source_label(source),
+          d.title
+        );
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "test" => {
+      let Some(path) = tail.get(1) else {
+        anyhow::bail!("expected: --rules test <events.json>");
+      };
+      let raw = std::fs::read_to_string(path).with_context(|| format!("read {path}"))?;
+      let events: Vec<Event> =
+        serde_json::from_str(&raw).with_context(|| format!("parse events from {path}"))?;
+
+      // Rules engine only: nothing is stored and no response runs.
+      let mut engine = crate::rules_engine::Engine::new();
+      engine.set_threat_feed(threat_feed::load_current());
+      let incidents = engine.process(cfg, &events)?;
+
+      println!(
+        "Events: {}  Incidents: {}  (mode: {:?})",
+        events.len(),
+        incidents.len(),
+        cfg.mode
+      );
+      for inc in &incidents {
+        println!("Incident {} severity={:?}", inc.incident_id, inc.severity);
+        for f in &inc.findings {
+          println!("  - {} {:?}: {}", f.rule_id, f.severity, f.description);
+          for e in &f.evidence {
+            println!("      {}", serde_json::to_string(e)?);
+          }
+        }
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    _ => {
+      eprintln!("Unknown `--rules` subcommand. Expected: list|test <events.json>");
+      print_help();
+      Ok(ConsoleAction::ExitOk)
+    }
+  }
+}
+
+/// Runs recorded events through the rules and response engines the way the agent loop does, with
+/// dry-run forced on so nothing is stored, contained, or posted.
+fn run_replay(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
+  // Same batch size as a busy collector tick; the engine keeps its state across batches.
+  const BATCH: usize = 500;
+
+  let Some(path) = tail.first() else {
+    anyhow::bail!("expected: --replay <events.ndjson>");
+  };
+  let raw = std::fs::read_to_string(path).with_context(|| format!("read {path}"))?;
+  let events = parse_event_file(&raw).with_context(|| format!("parse events from {path}"))?;
+
+  runtime::set_dry_run(true);
+  let mut engine = crate::rules_engine::Engine::new();
+  engine.set_threat_feed(threat_feed::load_current());
+  let mut targeted_scans = crate::response_engine::targeted_scan::TargetedScans::new();
+
+  let mut incidents = Vec::new();
+  for batch in events.chunks(BATCH) {
+    for mut inc in engine.process(cfg, batch)? {
+      crate::response_engine::handle_incident(cfg, &mut inc)?;
+      targeted_scans.on_incident(cfg, &inc);
+      incidents.push(inc);
+    }
+  }
+
+  println!(
+    "Replayed {} events from {path}  Incidents: {}  (mode: {:?}, dry-run)",
+    events.len(),
+    incidents.len(),
+    cfg.mode
+  );
+  for inc in &incidents {
+    let mut rule_ids: Vec<&str> = inc.findings.iter().map(|f| f.rule_id.as_str()).collect();
+    rule_ids.sort_unstable();
+    rule_ids.dedup();
+    println!(
+      "Incident {} severity={:?} rules={} actions={}",
+      inc.incident_id,
+      inc.severity,
+      rule_ids.join(","),
+      inc.actions_taken.join(",")
+    );
+    for f in &inc.findings {
+      println!("  - {} {:?}: {}", f.rule_id, f.severity, f.description);
+    }
+  }
+  Ok(ConsoleAction::ExitOk)
+}
+
+/// One `Event` JSON object per line (blank lines skipped), or a single JSON array of events.
+fn parse_event_file(raw: &str) -> anyhow::Result<Vec<Event>> {
+  if raw.trim_start().starts_with('[') {
+    return Ok(serde_json::from_str(raw)?);
+  }
+  raw
+    .lines()
+    .enumerate()
+    .filter(|(_, line)| !line.trim().is_empty())
+    .map(|(i, line)| serde_json::from_str(line.trim()).with_context(|| format!("line {}", i + 1)))
+    .collect()
+}
+
+fn run_config(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
+  let sub = tail.first().map(|s| s.as_str()).unwrap_or("");
+  match sub {
+    "status" => {
+      match &cfg.managed {
+        Some(m) => {
+          println!("Config: MANAGED");
+          println!("Profile id: {}", m.profile_id);
+          if !m.organization.is_empty() {
+            println!("Organization: {}", m.organization);
+          }
+          println!("Profile created_at: {}", m.created_at);
+          println!("Managed sections: {}", m.sections.join(", "));
+        }
+        None => println!("Config: local (no managed profile installed)"),
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "import" => {
+      let (Some(json), Some(sig)) = (tail.get(1), tail.get(2)) else {
+        eprintln!("Usage: --config import <path-to-profile.json> <path-to-profile.sig>");
+        return Ok(ConsoleAction::ExitOk);
+      };
+      let base = paths::base_dir()?;
+      let managed =
+        config_profile::import(&base, std::path::Path::new(json), std::path::Path::new(sig))?;
+      if runtime::is_dry_run() {
+        println!(
+          "DRY-RUN: profile verified; would install managed profile {}",
+          managed.profile_id
+        );
+      } else {
+        println!("Managed profile installed: {}", managed.profile_id);
+        println!("Managed sections: {}", managed.sections.join(", "));
+        println!("Restart the agent service to apply.");
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "check" => {
+      run_config_check(&tail[1..])?;
+      Ok(ConsoleAction::ExitOk)
+    }
+    "get" => {
+      let Some(key) = tail.get(1) else {
+        eprintln!("Usage: --config get <key>   (e.g. killswitch.failsafe_minutes)");
+        return Ok(ConsoleAction::ExitOk);
+      };
+      match config::get_key(cfg, key)? {
+        toml::Value::String(s) => println!("{s}"),
+        toml::Value::Table(t) => print!("{}", toml::to_string_pretty(&t)?),
+        v => println!("{v}"),
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "set" => {
+      let (Some(key), Some(value)) = (tail.get(1), tail.get(2)) else {
+        eprintln!("Usage: --config set <key> <value>   (e.g. killswitch.failsafe_minutes 30)");
+        return Ok(ConsoleAction::ExitOk);
+      };
+      let section = key.split('.').next().unwrap_or_default();
+      config::ensure_unmanaged(cfg, section)?;
+      let mut updated = cfg.clone();
+      config::set_key(&mut updated, key, value)?;
+      let shown = config::get_key(&updated, key)?;
+
+      if runtime::is_dry_run() {
+        println!("DRY-RUN: would set {key} = {shown}");
+        return Ok(ConsoleAction::ExitOk);
+      }
+
+      let base = paths::base_dir()?;
+      config::save(&paths::config_path(&base), &updated)?;
+      self_protection::seal_config(&base);
+      println!("Config updated: {key} = {shown}");
+      println!("Restart the agent service to apply.");
+      Ok(ConsoleAction::ExitOk)
+    }
+    _ => {
+      eprintln!(
+        "Unknown `--config` subcommand. Expected: status | check [path] [--json] | get <key> | set <key> <value> | import <json> <sig>"
+      );
+      print_help();
+      Ok(ConsoleAction::ExitOk)
+    }
+  }
+}
+
+/// `--config check [path] [--json]`: validates config.toml (or `path`) and prints every problem
+/// with its line. Called before the agent loads its config, since loading replaces an unreadable
+/// file with defaults; never writes anything. Fails if there are errors.
+pub fn run_config_check(tail: &[String]) -> anyhow::Result<()> {
+  let json = tail.iter().any(|a| a == "--json");
+  let path = match tail.iter().find(|a| !a.starts_with("--")) {
+    Some(p) => std::path::PathBuf::from(p),
+    None => paths::config_path(&paths::base_dir()?),
+  };
+  let issues = config::check(&path)?;
+  let errors = issues
+    .iter()
+    .filter(|i| i.level == config::IssueLevel::Error)
+    .count();
+  let warnings = issues.len() - errors;
+
+  if json {
+    let report = serde_json::json!({
+      "path": path,
+      "errors": errors,
+      "warnings": warnings,
+      "issues": issues,
+    });
+    println!("{}", serde_json::to_string_pretty(&report)?);
+  } else {
+    let raw = std::fs::read_to_string(&path).unwrap_or_default();
+    let lines: Vec<&str> = raw.lines().collect();
+    println!("Config: {}", path.display());
+    for issue in &issues {
+      let level = match issue.level {
+        config::IssueLevel::Error => "error",
+        config::IssueLevel::Warning => "warning",
+      };
+      let setting = if issue.setting.is_empty() {
+        "syntax"
+      } else {
+        &issue.setting
+      };
+      match issue.line {
+        Some(n) => println!("{level}: {setting} (line {n}): {}", issue.message),
+        None => println!("{level}: {setting}: {}", issue.message),
+      }
+      if let Some((n, text)) = issue
+        .line
+        .and_then(|n| Some((n, lines.get(n.checked_sub(1)?)?)))
+      {
+        println!("  {n:>4} | {}", text.trim_end());
+      }
+    }
+    println!("{errors} error(s), {warnings} warning(s)");
+  }
+
+  if errors > 0 {
+    anyhow::bail!("config check failed with {errors} error(s)");
+  }
+  Ok(())
+}
+
+fn add_unique(list: &mut Vec<String>, value: &str) -> bool {
+  if list.iter().any(|e| e.trim().eq_ignore_ascii_case(value)) {
+    return false;
+  }
+  list.push(value.to_string());
+  true
+}
+
+fn run_secrets(tail: &[String]) -> anyhow::Result<ConsoleAction> {
+  let base = paths::base_dir()?;
+  let sub = tail.first().map(|s| s.as_str()).unwrap_or("");
+  match (sub, tail.get(1)) {
+    ("set", Some(name)) => {
+      // Read the value from stdin so it never appears in shell history or process listings.
+      eprintln!("Enter value for secret `{name}` (end with newline):");
+      let mut value = String::new();
+      std::io::stdin().read_line(&mut value)?;
+      let value = value.trim_end_matches(['\r', '\n']);
+      secrets::set(&base, name, value)?;
+      println!("Secret `{name}` stored.");
+      Ok(ConsoleAction::ExitOk)
+    }
+    ("list", _) => {
+      let names = secrets::list(&base)?;
+      if names.is_empty() {
+        println!("No secrets stored.");
+        return Ok(ConsoleAction::ExitOk);
+      }
+      println!("Stored secrets:");
+      for n in names {
+        println!("- {n}");
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    ("remove", Some(name)) => {
+      if secrets::remove(&base, name)? {
+        println!("Secret `{name}` removed.");
+      } else {
+        println!("Secret `{name}` not found.");
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    _ => {
+      eprintln!("Unknown `--secrets` subcommand. Expected: set <name> | list | remove <name>");
+      print_help();
+      Ok(ConsoleAction::ExitOk)
+    }
+  }
+}
+
+fn run_service(tail: &[String]) -> anyhow::Result<ConsoleAction> {
+  let sub = tail.first().map(String::as_str).unwrap_or("");
+  let res = match sub {
+    "install" => {
+      let exe = std::env::current_exe()?;
+      service_install::install(&exe).map(|()| {
+        format!(
+          "Service {} installed ({}); start it with `--service start`.",
+          service_install::SERVICE_NAME,
+          exe.display()
+        )
+      })
+    }
+    "uninstall" => service_install::uninstall()
+      .map(|()| format!("Service {} removed.", service_install::SERVICE_NAME)),
+    "start" => service_install::start().map(|()| "Service start requested.".to_string()),
+    "stop" => service_install::stop().map(|()| "Service stopped.".to_string()),
+    "restart" => service_install::restart().map(|()| "Service restart requested.".to_string()),
+    _ => {
+      eprintln!("Unknown `--service` subcommand. Expected: install|uninstall|start|stop|restart");
+      print_help();
+      return Ok(ConsoleAction::ExitOk);
+    }
+  };
+  match res {
+    Ok(_) if runtime::is_dry_run() => {}
+    Ok(msg) => println!("{msg}"),
+    Err(e) => {
+      if e.to_string().contains("exit code 5)") {
+        eprintln!("Hint: installing and controlling services requires Administrator privileges.");
+      }
+      return Err(e);
+    }
+  }
+  Ok(ConsoleAction::ExitOk)
+}
+
+fn run_watch(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
+  let base = paths::base_dir()?;
+  let sub = tail.first().map(|s| s.as_str()).unwrap_or("");
+  match (sub, tail.get(1)) {
+    ("add", Some(target)) => {
+      let minutes = match tail.iter().position(|a| a == "--minutes") {
+        Some(i) => tail
+          .get(i + 1)
+          .and_then(|s| s.parse::<u64>().ok())
+          .ok_or_else(|| anyhow::anyhow!("--minutes expects a number"))?,
+        None => cfg.watch.default_minutes,
+      };
+      let entry = watch::add(&base, target, minutes)?;
+      println!(
+        "Watching {:?} target `{}` for {minutes} minute(s) (until unix_ms={}).",
+        entry.kind, entry.target, entry.expires_at_unix_ms
+      );
+      println!(
+        "Events are logged to {} by the running agent.",
+        paths::watch_log_path(&base).display()
+      );
+      Ok(ConsoleAction::ExitOk)
+    }
+    ("list", _) => {
+      let entries = watch::list(&base);
+      if entries.is_empty() {
+        println!("No watched targets.");
+        return Ok(ConsoleAction::ExitOk);
+      }
+      let now = now_unix_ms();
+      println!("Watched targets:");
+      for e in entries {
+        println!(
+          "- {} kind={:?} remaining_minutes={}",
+          e.target,
+          e.kind,
+          e.expires_at_unix_ms.saturating_sub(now).div_ceil(60_000)
+        );
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    ("remove", Some(target)) => {
+      if watch::remove(&base, target)? {
+        println!("Stopped watching `{target}`.");
+      } else {
+        println!("`{target}` is not watched.");
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    _ => {
+      eprintln!(
+        "Unknown `--watch` subcommand. Expected: add <path|process> [--minutes N] | list | remove <path|process>"
+      );
+      print_help();
+      Ok(ConsoleAction::ExitOk)
+    }
+  }
+}
+
+fn run_debug(cfg: &Config, tail: &[String]) -> anyhow::Result<ConsoleAction> {
+  use crate::rules_engine::trace;
+
+  let base = paths::base_dir()?;
+  let args: Vec<&str> = tail.iter().map(String::as_str).collect();
+  let pid_arg = |s: &str| {
+    s.parse::<u32>()
+      .map_err(|_| anyhow::anyhow!("not a process id: {s}"))
+  };
+  match args.as_slice() {
+    ["trace", "add", pid, ..] => {
+      let pid = pid_arg(pid)?;
+      trace::add_pid(&base, pid)?;
+      println!("Tracing correlation decisions for pid {pid} (for 24 hours).");
+      println!(
+        "The running agent writes steps to {}.",
+        paths::trace_log_path(&base).display()
+      );
+    }
+    ["trace", "remove", pid, ..] => {
+      let pid = pid_arg(pid)?;
+      if trace::remove_pid(&base, pid)? {
+        println!("Stopped tracing pid {pid}.");
+      } else {
+        println!("pid {pid} is not traced.");
+      }
+    }
+    ["trace", "list", ..] => {
+      let pids = trace::list_pids(&base);
+      if pids.is_empty() {
+        println!("No traced pids.");
+      }
+      let now = now_unix_ms();
+      for p in pids {
+        println!(
+          "- pid {} remaining_minutes={}",
+          p.pid,
+          p.expires_at_unix_ms().saturating_sub(now).div_ceil(60_000)
+        );
+      }
+    }
+    ["explain-incident", ..] => {
+      let id = incident_id_arg(tail.get(1), "--debug explain-incident <incident-id>")?;
+      let incident = incident_store::load(id)?;
+      match trace::explain(&base, &incident, cfg.correlation_window_seconds) {
+        Some(lines) => lines.iter().for_each(|l| println!("{l}")),
+        None => println!(
+          "No trace recorded for incident {id}. Tracing only covers pids added with `--debug trace add <pid>` before the activity."
+        ),
+      }
+    }
+    _ => {
+      eprintln!(
+        "Unknown `--debug` subcommand. Expected: trace add <pid> | trace remove <pid> | trace list | explain-incident <incident-id>"
+      );
+      print_help();
+    }
+  }
+  Ok(ConsoleAction::ExitOk)
+}
+
+fn run_bench_engine(tail: &[String]) -> anyhow::Result<ConsoleAction> {
+  use crate::rules_engine::bench::{self, Workload};
+
+  let flag = |name: &str| -> anyhow::Result<Option<usize>> {
+    match tail.iter().position(|a| a == name) {
+      Some(i) => tail
+        .get(i + 1)
+        .and_then(|s| s.replace('_', "").parse::<usize>().ok())
+        .map(Some)
+        .ok_or_else(|| anyhow::anyhow!("{name} expects a number")),
+      None => Ok(None),
+    }
+  };
+  let events = match flag("--events")? {
+    Some(n) => vec![n],
+    None => vec![10_000, 100_000, 1_000_000],
+  };
+  let pids = match flag("--pids")? {
+    Some(n) => vec![n as u32],
+    None => vec![16, 1_024, 65_536],
+  };
+  let batch = flag("--batch")?.unwrap_or(500);
+
+  // Defaults rather than this machine's config, so numbers compare across machines.
+  let cfg = Config::default();
+  println!("Engine benchmark (default config, batch size {batch}):");
+  for &n in &events {
+    for &p in &pids {
+      let r = bench::run(&cfg, &Workload::new(n, p), batch)?;
+      let memory = match (r.working_set_before, r.working_set_after) {
+        (Some(before), Some(after)) => format!(
+          "working_set={} MiB (+{} MiB)",
+          after / (1024 * 1024),
+          after.saturating_sub(before) / (1024 * 1024)
+        ),
+        _ => "working_set=n/a".to_string(),
+      };
+      println!(
+        "- events={} pids={} elapsed_ms={} events_per_sec={:.0} incidents={} {memory}",
+        r.events,
+        r.pids,
+        r.elapsed.as_millis(),
+        r.events_per_second(),
+        r.incidents
+      );
+    }
+  }
+  Ok(ConsoleAction::ExitOk)
+}
+
+fn parse_limit(args: &[String]) -> Option<usize> {
+  let mut i = 0;
+  while i < args.len() {
     if args[i] == "--limit" {
       return args.get(i + 1).and_then(|s| s.parse::<usize>().ok());
     }
@@ -566,15 +2145,194 @@ fn parse_limit(args: &[String]) -> Option<usize> {
   None
 }
 
-fn run_events_through_pipeline(cfg: &Config, events: Vec<Event>) -> anyhow::Result<Vec<String>> {
+/// The `--simulate red` chain starting at `base`: an unsigned process reads Chrome's Login Data,
+/// then connects out.
+pub fn red_chain_events(base: u64) -> Vec<Event> {
+  let pid = 4242;
+  let image = "C:\\Temp\\evil.exe".to_string();
+  let file_path = format!(
+    "{}\\Google\\Chrome\\User Data\\Default\\Login Data",
+    localappdata()
+  );
+
+  vec![
+    Event::ProcessStart {
+      pid,
+      ppid: 0,
+      image_path: image.clone(),
+      signer_publisher: None,
+      command_line: None,
+      download_origin: None,
+      process_guid: None,
+      parent_process_guid: None,
+      timestamp_unix_ms: base,
+    },
+    Event::FileAccess {
+      pid,
+      image_path: Some(image.clone()),
+      file_path,
+      access: FileAccessType::Read,
+      process_guid: None,
+      timestamp_unix_ms: base + 1_000,
+    },
+    Event::NetConnect {
+      pid,
+      image_path: Some(image),
+      dest_ip: IpAddr::from([1, 2, 3, 4]),
+      dest_port: 443,
+      dest_host: None,
+      protocol: "tcp".to_string(),
+      process_guid: None,
+      timestamp_unix_ms: base + 2_000,
+    },
+  ]
+}
+
+/// The `--simulate ransomware` burst starting at `base`: an unsigned process replaces `files`
+/// documents under `home`'s Documents folder with `.locked` copies, deleting each original.
+pub fn ransomware_events(base: u64, home: &std::path::Path, files: usize) -> Vec<Event> {
+  let pid = 4848;
+  let image = "C:\\Temp\\crypt.exe".to_string();
+  let dir = format!(
+    "{}\\Documents\\AI Defender simulation",
+    home.to_string_lossy()
+  );
+
+  let mut events = vec![Event::ProcessStart {
+    pid,
+    ppid: 0,
+    image_path: image.clone(),
+    signer_publisher: None,
+    command_line: None,
+    download_origin: None,
+    process_guid: None,
+    parent_process_guid: None,
+    timestamp_unix_ms: base,
+  }];
+  for i in 0..files as u64 {
+    let original = format!("{dir}\\report-{i}.docx");
+    let at = base + 1_000 + i * 50;
+    events.push(Event::FileAccess {
+      pid,
+      image_path: Some(image.clone()),
+      file_path: format!("{original}.locked"),
+      access: FileAccessType::Write,
+      process_guid: None,
+      timestamp_unix_ms: at,
+    });
+    events.push(Event::FileAccess {
+      pid,
+      image_path: Some(image.clone()),
+      file_path: original,
+      access: FileAccessType::Delete,
+      process_guid: None,
+      timestamp_unix_ms: at + 10,
+    });
+  }
+  events
+}
+
+/// Runs `events` through the rules engine and the response engine, as the agent loop does, and
+/// returns the IDs of the incidents raised.
+pub fn run_events_through_pipeline(
+  cfg: &Config,
+  events: Vec<Event>,
+) -> anyhow::Result<Vec<String>> {
+  Ok(
+    run_pipeline(cfg, events)?
+      .into_iter()
+      .map(|inc| inc.incident_id)
+      .collect(),
+  )
+}
+
+/// The incident's remediation guidance per rule; incidents stored before guidance was recorded get
+/// the current text.
+fn print_guidance(inc: &Incident) {
+  let mut rule_ids: Vec<&str> = inc.findings.iter().map(|f| f.rule_id.as_str()).collect();
+  rule_ids.sort_unstable();
+  rule_ids.dedup();
+  let feed = threat_feed::load_current();
+  let guidance: Vec<_> = rule_ids
+    .into_iter()
+    .filter_map(|id| {
+      let g = inc
+        .guidance
+        .get(id)
+        .cloned()
+        .or_else(|| crate::guidance::for_rule(id, feed.as_ref()))?;
+      Some((id, g))
+    })
+    .collect();
+  if guidance.is_empty() {
+    return;
+  }
+  println!("Guidance:");
+  for (id, g) in guidance {
+    println!("  {id}: {}", g.meaning);
+    println!("      Often benign: {}", g.false_positives);
+    println!("      What to do: {}", g.action);
+  }
+}
+
+/// Runs `events` through a fresh rules engine and the response engine; returns the incidents as
+/// handled.
+pub fn run_pipeline(cfg: &Config, events: Vec<Event>) -> anyhow::Result<Vec<Incident>> {
   let mut engine = crate::rules_engine::Engine::new();
-  let incidents = engine.process(cfg, &events)?;
-  let mut ids = Vec::new();
-  for mut inc in incidents {
-    ids.push(inc.incident_id.clone());
-    crate::response_engine::handle_incident(cfg, &mut inc)?;
+  engine.set_threat_feed(threat_feed::load_current());
+  let mut incidents = engine.process(cfg, &events)?;
+  for inc in &mut incidents {
+    crate::response_engine::handle_incident(cfg, inc)?;
   }
-  Ok(ids)
+  Ok(incidents)
+}
+
+/// `--simulate from-file`: runs each scenario through the pipeline and checks its expectations.
+fn run_scenarios(cfg: &Config, path: Option<&String>) -> anyhow::Result<ConsoleAction> {
+  let Some(path) = path else {
+    anyhow::bail!("expected: --simulate from-file <scenario.json>");
+  };
+  let raw = std::fs::read_to_string(path).with_context(|| format!("read {path}"))?;
+  let scenarios = scenario::parse(&raw).with_context(|| format!("parse scenarios from {path}"))?;
+
+  let mut failed = 0;
+  for s in &scenarios {
+    let incidents = run_pipeline(cfg, scenario::events_at(s, now_unix_ms())?)?;
+    let failures = scenario::check(&s.expect, &incidents);
+    let mut rule_ids: Vec<&str> = incidents
+      .iter()
+      .flat_map(|i| &i.findings)
+      .map(|f| f.rule_id.as_str())
+      .collect();
+    rule_ids.sort_unstable();
+    rule_ids.dedup();
+    let fired = if rule_ids.is_empty() {
+      "none".to_string()
+    } else {
+      rule_ids.join(",")
+    };
+    let verdict = if failures.is_empty() { "PASS" } else { "FAIL" };
+    println!(
+      "{verdict} {}  (incidents: {}, rules: {fired})",
+      s.name,
+      incidents.len()
+    );
+    for f in &failures {
+      println!("  - {f}");
+    }
+    if !failures.is_empty() {
+      failed += 1;
+    }
+  }
+
+  println!(
+    "{} passed, {failed} failed ({path})",
+    scenarios.len() - failed
+  );
+  if failed > 0 {
+    anyhow::bail!("{failed} of {} scenario(s) failed", scenarios.len());
+  }
+  Ok(ConsoleAction::ExitOk)
 }
 
 fn localappdata() -> String {
@@ -589,6 +2347,23 @@ fn parse_bool(s: &str) -> Option<bool> {
   }
 }
 
+/// `30m`, `2h`, or bare minutes (`45`). Zero is rejected.
+fn parse_duration_minutes(s: &str) -> Option<u64> {
+  let s = s.trim();
+  let (num, per_unit) = if let Some(n) = s.strip_suffix('h') {
+    (n, 60)
+  } else if let Some(n) = s.strip_suffix('m') {
+    (n, 1)
+  } else {
+    (s, 1)
+  };
+  num
+    .parse::<u64>()
+    .ok()
+    .filter(|n| *n > 0)
+    .and_then(|n| n.checked_mul(per_unit))
+}
+
 fn strip_console_flag(args: &[String]) -> Vec<String> {
   args
     .iter()
@@ -597,18 +2372,85 @@ fn strip_console_flag(args: &[String]) -> Vec<String> {
     .collect()
 }
 
+fn run_trust(tail: &[String]) -> anyhow::Result<ConsoleAction> {
+  let base = paths::base_dir()?;
+
+  let sub = tail.first().map(|s| s.as_str()).unwrap_or("");
+  match sub {
+    "status" => {
+      match trust::installed_sequence(&base) {
+        0 => println!("Key manifest: none installed (embedded keys only)"),
+        seq => println!("Key manifest: sequence {seq}"),
+      }
+      for (label, purpose) in [
+        ("Threat feed", trust::Purpose::ThreatFeed),
+        ("License", trust::Purpose::License),
+      ] {
+        println!("{label} keys:");
+        for (key, state) in trust::TrustSet::load(purpose).entries() {
+          println!(
+            "  {} {} ({state})",
+            key.key_id,
+            trust::fingerprint(&key.key)
+          );
+        }
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    "install" => {
+      let json = tail.get(1).map(|s| s.as_str()).unwrap_or("");
+      let sig = tail.get(2).map(|s| s.as_str()).unwrap_or("");
+      if json.is_empty() || sig.is_empty() {
+        anyhow::bail!("expected: --trust install <path-to-keys.json> <path-to-keys.sig>");
+      }
+      let json = std::fs::read(json).with_context(|| format!("read {json}"))?;
+      let sig = std::fs::read(sig).with_context(|| format!("read {sig}"))?;
+      match trust::install_manifest(&base, &json, &sig)? {
+        None => println!(
+          "Key manifest is not newer than the installed one (sequence {}); nothing changed.",
+          trust::installed_sequence(&base)
+        ),
+        Some(m) if runtime::is_dry_run() => {
+          println!(
+            "DRY-RUN: would install key manifest sequence {}.",
+            m.sequence
+          )
+        }
+        Some(m) => println!(
+          "Installed key manifest sequence {} ({} keys, {} revoked).",
+          m.sequence,
+          m.keys.len(),
+          m.revoked.len()
+        ),
+      }
+      Ok(ConsoleAction::ExitOk)
+    }
+    _ => {
+      eprintln!("Unknown `--trust` subcommand. Expected: status|install <json> <sig>");
+      print_help();
+      Ok(ConsoleAction::ExitOk)
+    }
+  }
+}
+
 fn print_help() {
   println!("AI Defender v{} (console mode)", env!("CARGO_PKG_VERSION"));
   println!("Commands:");
   println!("  --dry-run (global; logs actions without side effects)");
-  println!("  --killswitch on");
+  println!("  --killswitch on [--duration 30m|2h]");
   println!("  --killswitch off");
   println!("  --killswitch status");
   println!("  --killswitch keep-locked true|false");
-  println!("  --license status");
+  println!("  --killswitch failsafe-restore (run by the failsafe scheduled task)");
+  println!("  --containment snapshot <file>");
+  println!("  --containment verify <file>");
+  println!("  --containment key");
+  println!("  --license status [--json]");
   println!("  --license install <path-to-license.json> <path-to-license.sig>");
-  println!("  --license activate");
+  println!("  --license activate [--name <label>]");
   println!("  --license deactivate");
+  println!("  --trust status");
+  println!("  --trust install <path-to-keys.json> <path-to-keys.sig>");
   println!("  --feed status");
   println!("  --feed import <path-to-bundle.json> <path-to-bundle.sig>");
   println!("  --feed verify <path-to-bundle.json> <path-to-bundle.sig>");
@@ -618,7 +2460,49 @@ fn print_help() {
   println!("  --simulate file-access-chrome");
   println!("  --simulate net-connect");
   println!("  --simulate chain-red");
+  println!("  --simulate extension-sideload");
+  println!("  --simulate network-hijack");
+  println!("  --simulate dns-exfil");
+  println!("  --simulate clipboard-hijack");
+  println!("  --simulate ransomware");
+  println!("  --simulate from-file <scenario.json>");
   println!("  --incidents list [--limit N]");
+  println!("  --incidents show <incident-id>");
+  println!("  --incidents timeline <incident-id> | --pid <pid>");
+  println!("  --incidents resolve <incident-id> false-positive|confirmed [note]");
+  println!("  --incidents export <incident-id> [--out <file.zip>] [--yes]");
+  println!("  --incidents verify [--json]");
+  println!("  --scanner history [--limit N]");
+  println!("  --scanner running");
+  println!("  --scanner cancel <scan-id>");
+  println!("  --allowlist add-publisher <name>");
+  println!("  --allowlist add-path <prefix>");
+  println!("  --allowlist add-hash <sha256>");
+  println!("  --allowlist list");
+  println!("  --allowlist remove <value>");
+  println!("  --baseline status");
+  println!("  --baseline review");
+  println!("  --rules list");
+  println!("  --rules test <events.json>");
+  println!("  --service install|uninstall|start|stop|restart");
+  println!("  --watch add <path|process> [--minutes N]");
+  println!("  --watch list");
+  println!("  --watch remove <path|process>");
+  println!("  --debug trace add <pid>");
+  println!("  --debug trace remove <pid>");
+  println!("  --debug trace list");
+  println!("  --debug explain-incident <incident-id>");
+  println!("  --replay <events.ndjson> (full pipeline, always dry-run)");
+  println!("  --config status");
+  println!("  --config check [path] [--json]");
+  println!("  --config get <key>");
+  println!("  --config set <key> <value>");
+  println!("  --config import <path-to-profile.json> <path-to-profile.sig>");
+  println!("  --secrets set <name> (value read from stdin)");
+  println!("  --secrets list");
+  println!("  --secrets remove <name>");
+  println!("  --coverage");
+  println!("  --status (agent heartbeat: uptime, last event batch, collector, feed, license)");
 }
 
 fn print_status(st: &kill_switch::KillSwitchStatus) {
@@ -641,7 +2525,7 @@ fn print_status(st: &kill_switch::KillSwitchStatus) {
     if deadline > now {
       let remaining_ms = deadline - now;
       let remaining_min = remaining_ms.div_ceil(60_000);
-      println!("Failsafe: auto-restore in ~{remaining_min} minute(s) (AUTO mode only).");
+      println!("Failsafe: auto-restore in ~{remaining_min} minute(s).");
     } else {
       println!("Failsafe: deadline passed (reconcile will restore on startup if still locked).");
     }
@@ -653,7 +2537,10 @@ fn print_status(st: &kill_switch::KillSwitchStatus) {
     println!("Last incident_id: {id}");
   }
 
-  if st.enabled && st.enabled_mode == Some(kill_switch::KillSwitchMode::Manual) {
+  if st.enabled
+    && st.enabled_mode == Some(kill_switch::KillSwitchMode::Manual)
+    && st.failsafe_deadline_unix_ms.is_none()
+  {
     println!("Note: manual lock never auto-restores.");
   }
 }
@@ -0,0 +1,126 @@
+use crate::config::Config;
+use crate::event_collector::{self, EventSource};
+use crate::rules_engine::protected_paths;
+use crate::rules_engine::rules;
+use crate::threat_feed;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct CoverageReport {
+  pub sources: Vec<SourceCoverage>,
+  pub threat_feed_installed: bool,
+  pub rules: Vec<RuleCoverage>,
+  pub protected: Vec<ProtectedCoverage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceCoverage {
+  pub source: EventSource,
+  pub active: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct RuleCoverage {
+  pub rule_id: &'static str,
+  pub title: &'static str,
+  pub can_fire: bool,
+  pub missing: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProtectedCoverage {
+  pub browser: String,
+  pub user: String,
+  pub root: PathBuf,
+  pub root_present: bool,
+  /// Profile directories found for the browser.
+  pub profiles: usize,
+  pub targets_found: Vec<String>,
+}
+
+impl CoverageReport {
+  pub fn blind_spots(&self) -> usize {
+    self.sources.iter().filter(|s| !s.active).count()
+      + self.rules.iter().filter(|r| !r.can_fire).count()
+  }
+}
+
+pub fn assess(cfg: &Config, base: &Path) -> CoverageReport {
+  let active = event_collector::active_sources(&cfg.collector);
+  let threat_feed_installed = threat_feed::bundle_status_at(base).present;
+
+  let sources = EventSource::ALL
+    .iter()
+    .map(|s| SourceCoverage {
+      source: *s,
+      active: active.contains(s),
+    })
+    .collect();
+
+  let rules = rules::ALL_RULES
+    .iter()
+    .map(|meta| {
+      let mut missing = Vec::new();
+      if let Some(req) = rules::requirements_for(meta.id) {
+        for s in req.sources {
+          if !active.contains(s) {
+            missing.push(s.label().to_string());
+          }
+        }
+        if req.needs_threat_feed && !threat_feed_installed {
+          missing.push("threat feed bundle".to_string());
+        }
+      }
+      RuleCoverage {
+        rule_id: meta.id,
+        title: meta.title,
+        can_fire: missing.is_empty(),
+        missing,
+      }
+    })
+    .collect();
+
+  let protected = protected_paths::protected_roots(cfg)
+    .into_iter()
+    .map(|r| {
+      let root_present = r.root.is_dir() || !r.profiles.is_empty();
+      let targets_found = if root_present {
+        find_targets(&r.root, &r.profiles, &r.targets)
+      } else {
+        Vec::new()
+      };
+      ProtectedCoverage {
+        browser: r.browser,
+        user: r.user,
+        root: r.root,
+        root_present,
+        profiles: r.profiles.len(),
+        targets_found,
+      }
+    })
+    .collect();
+
+  CoverageReport {
+    sources,
+    threat_feed_installed,
+    rules,
+    protected,
+  }
+}
+
+// Targets live either at the user-data root (e.g. Chromium `Local State`) or in a profile
+// directory, which is usually one level down. We only check existence; contents are never read.
+fn find_targets(root: &Path, profiles: &[PathBuf], targets: &[String]) -> Vec<String> {
+  let mut dirs = vec![root.to_path_buf()];
+  if let Ok(entries) = fs::read_dir(root) {
+    dirs.extend(entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()));
+  }
+  dirs.extend(profiles.iter().cloned());
+
+  targets
+    .iter()
+    .filter(|t| dirs.iter().any(|d| d.join(t.as_str()).exists()))
+    .cloned()
+    .collect()
+}
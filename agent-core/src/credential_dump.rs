@@ -0,0 +1,87 @@
+// Credential dumping from LSASS (R026). Tools like Mimikatz, procdump and comsvcs.dll MiniDump open
+// `lsass.exe` with rights to read (or write into) its memory; Sysmon reports the opened handle as
+// ProcessAccess (Event ID 10) with the granted access mask.
+//
+// Plenty of Windows components open LSASS with query-only rights all day, so only masks carrying a
+// memory, thread or handle-duplication right count, and the system processes that legitimately
+// hold such handles are excluded by full path.
+
+/// Access rights that let the opener read credentials out of LSASS or run code in it.
+const SUSPICIOUS_RIGHTS: &[(u32, &str)] = &[
+  (0x0002, "PROCESS_CREATE_THREAD"),
+  (0x0008, "PROCESS_VM_OPERATION"),
+  (0x0010, "PROCESS_VM_READ"),
+  (0x0020, "PROCESS_VM_WRITE"),
+  (0x0040, "PROCESS_DUP_HANDLE"),
+];
+
+/// System images that open LSASS with memory rights as part of normal operation.
+const SYSTEM_ACCESSORS: &[&str] = &[
+  "c:\\windows\\system32\\wininit.exe",
+  "c:\\windows\\system32\\csrss.exe",
+  "c:\\windows\\system32\\services.exe",
+  "c:\\windows\\system32\\lsm.exe",
+  "c:\\windows\\system32\\svchost.exe",
+  "c:\\windows\\system32\\wbem\\wmiprvse.exe",
+  "c:\\windows\\system32\\taskmgr.exe",
+];
+
+/// Microsoft Defender's engine lives under a versioned platform directory.
+const DEFENDER_PLATFORM_DIR: &str = "c:\\programdata\\microsoft\\windows defender\\platform\\";
+
+pub fn is_lsass(image_path: &str) -> bool {
+  let p = image_path.to_ascii_lowercase();
+  p == "lsass.exe" || p.ends_with("\\lsass.exe")
+}
+
+/// Sysmon's `GrantedAccess` (`0x1410`); decimal is accepted too.
+pub fn parse_access_mask(s: &str) -> Option<u32> {
+  let s = s.trim();
+  match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+    None => s.parse().ok(),
+  }
+}
+
+/// Names of the suspicious rights in `mask`; empty when the mask is harmless.
+pub fn suspicious_rights(mask: u32) -> Vec<&'static str> {
+  SUSPICIOUS_RIGHTS
+    .iter()
+    .filter(|(bit, _)| mask & bit != 0)
+    .map(|(_, name)| *name)
+    .collect()
+}
+
+/// Windows components expected to open LSASS, matched by full path so a copy elsewhere isn't.
+pub fn is_system_accessor(image_path: &str) -> bool {
+  let p = image_path.replace('/', "\\").to_ascii_lowercase();
+  SYSTEM_ACCESSORS.contains(&p.as_str())
+    || (p.starts_with(DEFENDER_PLATFORM_DIR) && p.ends_with("\\msmpeng.exe"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn flags_memory_access_masks_from_unexpected_images() {
+    assert!(is_lsass("C:\\Windows\\System32\\lsass.exe"));
+    assert!(!is_lsass("C:\\Temp\\notlsass.exe"));
+
+    assert_eq!(parse_access_mask("0x1410"), Some(0x1410));
+    assert_eq!(parse_access_mask("4096"), Some(0x1000));
+    assert_eq!(
+      suspicious_rights(parse_access_mask("0x1410").unwrap()),
+      vec!["PROCESS_VM_READ"]
+    );
+    assert!(suspicious_rights(0x1000).is_empty());
+    assert!(suspicious_rights(0x1400).is_empty());
+    assert_eq!(suspicious_rights(0x1fffff).len(), 5);
+
+    assert!(is_system_accessor("C:\\Windows\\System32\\svchost.exe"));
+    assert!(is_system_accessor(
+      "C:\\ProgramData\\Microsoft\\Windows Defender\\Platform\\4.18.24010.12-0\\MsMpEng.exe"
+    ));
+    assert!(!is_system_accessor("C:\\Users\\Public\\svchost.exe"));
+  }
+}
@@ -0,0 +1,109 @@
+// Recognizing crypto wallet addresses in clipboard text, for clipboard hijack detection (R024).
+// Only the shape is checked (prefix, length, alphabet), not checksums: a clipper swaps in an
+// address of the same shape, and a false match only matters when another process replaces it
+// within seconds.
+
+const BASE58: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BECH32: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Characters of an address kept in findings and logs.
+const REDACTED_PREFIX_CHARS: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressKind {
+  Bitcoin,
+  Ethereum,
+}
+
+impl AddressKind {
+  pub fn label(self) -> &'static str {
+    match self {
+      AddressKind::Bitcoin => "BTC",
+      AddressKind::Ethereum => "ETH",
+    }
+  }
+}
+
+/// The kind of address `text` is, if it is exactly one address (surrounding whitespace allowed).
+pub fn classify(text: &str) -> Option<AddressKind> {
+  let s = text.trim();
+  if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+    return (hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+      .then_some(AddressKind::Ethereum);
+  }
+
+  let lower = s.to_ascii_lowercase();
+  if let Some(data) = lower.strip_prefix("bc1") {
+    // Bech32 is case-insensitive but never mixed-case.
+    let mixed =
+      s.chars().any(|c| c.is_ascii_lowercase()) && s.chars().any(|c| c.is_ascii_uppercase());
+    return (!mixed && (39..=59).contains(&data.len()) && data.chars().all(|c| BECH32.contains(c)))
+      .then_some(AddressKind::Bitcoin);
+  }
+
+  let legacy = s.starts_with(['1', '3'])
+    && (26..=35).contains(&s.len())
+    && s.chars().all(|c| BASE58.contains(c));
+  legacy.then_some(AddressKind::Bitcoin)
+}
+
+/// Whether two addresses are the same destination. Ethereum and bech32 addresses only differ in
+/// letter case when a checksum or display style is applied.
+pub fn same(a: &str, b: &str) -> bool {
+  let (a, b) = (a.trim(), b.trim());
+  match classify(a) {
+    Some(AddressKind::Ethereum) => a.eq_ignore_ascii_case(b),
+    Some(AddressKind::Bitcoin) if a.to_ascii_lowercase().starts_with("bc1") => {
+      a.eq_ignore_ascii_case(b)
+    }
+    _ => a == b,
+  }
+}
+
+/// `address` cut down to its first characters, e.g. `0x71C7...`.
+pub fn redact(address: &str) -> String {
+  let prefix: String = address.trim().chars().take(REDACTED_PREFIX_CHARS).collect();
+  format!("{prefix}...")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn recognizes_btc_and_eth_addresses_only() {
+    let eth = "0x71C7656EC7ab88b098defB751B7401B5f6d8976F";
+    assert_eq!(classify(eth), Some(AddressKind::Ethereum));
+    assert_eq!(
+      classify(&format!("  {eth}\r\n")),
+      Some(AddressKind::Ethereum)
+    );
+    assert_eq!(
+      classify("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"),
+      Some(AddressKind::Bitcoin)
+    );
+    assert_eq!(
+      classify("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy"),
+      Some(AddressKind::Bitcoin)
+    );
+    assert_eq!(
+      classify("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"),
+      Some(AddressKind::Bitcoin)
+    );
+
+    assert_eq!(classify("0x71C7656EC7ab88b098defB751B7401B5f6d8976"), None);
+    assert_eq!(classify("bc1qAr0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"), None);
+    assert_eq!(classify("10000000000000000000000000"), None);
+    assert_eq!(
+      classify("send 0.1 BTC to 1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"),
+      None
+    );
+
+    assert!(same(eth, &eth.to_ascii_lowercase()));
+    assert!(!same(
+      "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2",
+      "1bvbmseystwetqtfn5au4m4gfg7xjanvn2"
+    ));
+    assert_eq!(redact(eth), "0x71C7...");
+  }
+}
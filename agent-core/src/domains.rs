@@ -0,0 +1,116 @@
+// Domain-name helpers for DNS-based rules: normalization, threat-feed blocklist matching, and a
+// conservative "looks machine-generated" (DGA) heuristic on the registrable label.
+
+// Second-level labels that are part of a public suffix (`example.co.uk`, `example.com.br`).
+const SUFFIX_SECOND_LEVELS: &[&str] = &["co", "com", "net", "org", "gov", "ac", "edu"];
+
+const MIN_DGA_LABEL_LEN: usize = 10;
+const MIN_DGA_ENTROPY: f64 = 3.3;
+
+/// Lowercased, trailing dot removed. `None` for single-label/local names and reverse lookups,
+/// which never carry a registrable domain.
+pub fn normalize(query_name: &str) -> Option<String> {
+  let d = query_name.trim().trim_end_matches('.').to_ascii_lowercase();
+  if !d.contains('.') || d.ends_with(".arpa") || d.ends_with(".local") {
+    return None;
+  }
+  Some(d)
+}
+
+/// The blocklist entry matching `domain` exactly or as a parent domain.
+pub fn blocklist_match<'a>(domain: &str, blocklist: &'a [String]) -> Option<&'a str> {
+  blocklist
+    .iter()
+    .map(|e| e.trim().trim_start_matches("*.").trim_end_matches('.'))
+    .filter(|e| !e.is_empty())
+    .find(|e| {
+      domain.eq_ignore_ascii_case(e)
+        || (domain.len() > e.len()
+          && domain.as_bytes()[domain.len() - e.len() - 1] == b'.'
+          && domain[domain.len() - e.len()..].eq_ignore_ascii_case(e))
+    })
+}
+
+/// Label left of the public suffix: `login` for `login.example.co.uk` is not it, `example` is.
+pub fn registrable_label(domain: &str) -> Option<&str> {
+  let labels: Vec<&str> = domain.split('.').filter(|l| !l.is_empty()).collect();
+  if labels.len() < 2 {
+    return None;
+  }
+  let sld = labels[labels.len() - 2];
+  if labels.len() >= 3 && SUFFIX_SECOND_LEVELS.contains(&sld) {
+    return Some(labels[labels.len() - 3]);
+  }
+  Some(sld)
+}
+
+/// Long, high-entropy registrable labels with few vowels or many digits. Tuned to keep common
+/// brand and CDN names (`googleusercontent`, `microsoftonline`) out; only used together with a
+/// preceding sensitive file access, never on its own.
+pub fn looks_generated(domain: &str) -> bool {
+  let Some(label) = registrable_label(domain) else {
+    return false;
+  };
+  let chars: Vec<char> = label.chars().filter(|c| *c != '-').collect();
+  if chars.len() < MIN_DGA_LABEL_LEN {
+    return false;
+  }
+  let len = chars.len() as f64;
+  let vowels = chars.iter().filter(|c| "aeiou".contains(**c)).count() as f64;
+  let digits = chars.iter().filter(|c| c.is_ascii_digit()).count() as f64;
+
+  entropy(&chars) >= MIN_DGA_ENTROPY && (vowels / len < 0.3 || digits / len >= 0.25)
+}
+
+fn entropy(chars: &[char]) -> f64 {
+  let mut counts = std::collections::HashMap::new();
+  for c in chars {
+    *counts.entry(*c).or_insert(0usize) += 1;
+  }
+  let len = chars.len() as f64;
+  counts
+    .values()
+    .map(|n| {
+      let p = *n as f64 / len;
+      -p * p.log2()
+    })
+    .sum()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches_blocklist_entries_and_subdomains() {
+    let list = vec!["drainer.example".to_string(), "*.evil.test".to_string()];
+    assert_eq!(
+      blocklist_match("drainer.example", &list),
+      Some("drainer.example")
+    );
+    assert_eq!(
+      blocklist_match("cdn.DRAINER.example", &list),
+      Some("drainer.example")
+    );
+    assert_eq!(blocklist_match("a.evil.test", &list), Some("evil.test"));
+    assert_eq!(blocklist_match("notdrainer.example", &list), None);
+  }
+
+  #[test]
+  fn flags_generated_looking_domains_only() {
+    assert!(looks_generated("xjw9kq2vbz7hd.top"));
+    assert!(looks_generated("cdn.qx7rtz4kwp9m.co.uk"));
+    for benign in [
+      "googleusercontent.com",
+      "login.microsoftonline.com",
+      "stackoverflow.com",
+      "a1b2.example",
+      "sub.example.co.uk",
+    ] {
+      assert!(!looks_generated(benign), "{benign}");
+    }
+    assert_eq!(normalize("Example.COM."), Some("example.com".to_string()));
+    assert_eq!(normalize("wpad"), None);
+    assert_eq!(normalize("4.3.2.1.in-addr.arpa"), None);
+  }
+}
@@ -0,0 +1,173 @@
+#[cfg(windows)]
+use crate::types::Event;
+#[cfg(windows)]
+use std::sync::Mutex;
+
+// Clipboard change sampling for clipboard hijack detection (R024). A background thread watches
+// the clipboard sequence number and, on every change, records which process owns the new content.
+// Clipboard text is only kept when it is a crypto address (`crypto_address::classify`); anything
+// else is reported as a change without content, so copied passwords or documents never reach the
+// engine, recordings or logs.
+//
+// Clipboards belong to a window station. The service runs in session 0, which cannot see the
+// signed-in user's clipboard, so the source is only active when the agent runs in a user session
+// (e.g. console runs).
+
+#[cfg(windows)]
+const POLL_INTERVAL_MS: u64 = 100;
+/// Changes kept between two collector polls; older ones are dropped first.
+#[cfg(windows)]
+const MAX_QUEUED: usize = 256;
+
+#[cfg(windows)]
+static QUEUE: Mutex<Vec<Event>> = Mutex::new(Vec::new());
+
+/// Whether this process can see an interactive user's clipboard.
+pub fn available() -> bool {
+  #[cfg(windows)]
+  {
+    use windows::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+    use windows::Win32::System::Threading::GetCurrentProcessId;
+
+    let mut session = 0u32;
+    // SAFETY: `session` is a valid out pointer for the duration of the call.
+    let ok = unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session) }.is_ok();
+    ok && session != 0
+  }
+
+  #[cfg(not(windows))]
+  {
+    false
+  }
+}
+
+/// Clipboard changes seen since the last call. Starts the sampling thread on first use.
+#[cfg(windows)]
+pub fn drain() -> Vec<Event> {
+  static STARTED: std::sync::Once = std::sync::Once::new();
+  STARTED.call_once(|| {
+    if !available() {
+      tracing::info!("clipboard monitor inactive: no interactive session (session 0)");
+      return;
+    }
+    if let Err(e) = std::thread::Builder::new()
+      .name("clipboard-monitor".to_string())
+      .spawn(sample_loop)
+    {
+      tracing::warn!(error = ?e, "failed to start clipboard monitor");
+    }
+  });
+
+  let mut queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+  std::mem::take(&mut *queue)
+}
+
+#[cfg(windows)]
+fn push(ev: Event) {
+  let mut queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+  if queue.len() >= MAX_QUEUED {
+    queue.remove(0);
+  }
+  queue.push(ev);
+}
+
+#[cfg(windows)]
+fn sample_loop() {
+  use crate::crypto_address;
+  use crate::types::now_unix_ms;
+  use windows::Win32::System::DataExchange::GetClipboardSequenceNumber;
+
+  // SAFETY: no arguments; only reads the window station's counter.
+  let mut last = unsafe { GetClipboardSequenceNumber() };
+  loop {
+    std::thread::sleep(std::time::Duration::from_millis(POLL_INTERVAL_MS));
+    // SAFETY: as above.
+    let seq = unsafe { GetClipboardSequenceNumber() };
+    if seq == last {
+      continue;
+    }
+    last = seq;
+
+    let timestamp_unix_ms = now_unix_ms();
+    let pid = owner_pid().unwrap_or(0);
+    let address = read_text()
+      .filter(|t| crypto_address::classify(t).is_some())
+      .map(|t| t.trim().to_string());
+    push(Event::ClipboardChange {
+      pid,
+      image_path: (pid != 0).then(|| super::image_path(pid)).flatten(),
+      address,
+      timestamp_unix_ms,
+    });
+  }
+}
+
+/// Process owning the clipboard's current content (the one that last emptied and set it).
+#[cfg(windows)]
+fn owner_pid() -> Option<u32> {
+  use windows::Win32::System::DataExchange::GetClipboardOwner;
+  use windows::Win32::UI::WindowsAndMessaging::GetWindowThreadProcessId;
+
+  // SAFETY: both calls only take handles/pointers valid for the duration of the call.
+  unsafe {
+    let hwnd = GetClipboardOwner();
+    if hwnd.0 == 0 {
+      return None;
+    }
+    let mut pid = 0u32;
+    GetWindowThreadProcessId(hwnd, Some(&mut pid as *mut u32));
+    (pid != 0).then_some(pid)
+  }
+}
+
+/// The clipboard's Unicode text, if any. Longer text can't be an address and is skipped unread.
+#[cfg(windows)]
+fn read_text() -> Option<String> {
+  use windows::Win32::Foundation::{HANDLE, HGLOBAL, HWND};
+  use windows::Win32::System::DataExchange::{
+    CloseClipboard, GetClipboardData, IsClipboardFormatAvailable, OpenClipboard,
+  };
+  use windows::Win32::System::Memory::{GlobalLock, GlobalSize, GlobalUnlock};
+  use windows::Win32::System::Ole::CF_UNICODETEXT;
+
+  const MAX_TEXT_BYTES: usize = 512;
+  let format = u32::from(CF_UNICODETEXT.0);
+
+  // SAFETY: the clipboard is opened and closed around every access; the locked buffer is read
+  // within `GlobalSize` bounds and unlocked before the clipboard is closed.
+  unsafe {
+    IsClipboardFormatAvailable(format).ok()?;
+    // Another process may hold the clipboard open for a moment.
+    let mut opened = false;
+    for _ in 0..5 {
+      if OpenClipboard(HWND(0)).is_ok() {
+        opened = true;
+        break;
+      }
+      std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    if !opened {
+      return None;
+    }
+
+    let text = (|| {
+      let HANDLE(handle) = GetClipboardData(format).ok()?;
+      let mem = HGLOBAL(handle as _);
+      let size = GlobalSize(mem);
+      if size == 0 || size > MAX_TEXT_BYTES {
+        return None;
+      }
+      let ptr = GlobalLock(mem) as *const u16;
+      if ptr.is_null() {
+        return None;
+      }
+      let units = std::slice::from_raw_parts(ptr, size / 2);
+      let len = units.iter().position(|&u| u == 0).unwrap_or(units.len());
+      let text = String::from_utf16_lossy(&units[..len]);
+      let _ = GlobalUnlock(mem);
+      Some(text)
+    })();
+    let _ = CloseClipboard();
+    text
+  }
+}
@@ -0,0 +1,359 @@
+#![cfg_attr(not(windows), allow(dead_code))]
+
+use crate::types::{Event, FileAccessType};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Directory watchers (`ReadDirectoryChangesW`), a lighter file event source than the USN journal
+// for machines without Sysmon. Each directory of `protected_paths::watched_dirs` that exists gets
+// a thread blocked on its changes, so events arrive as they happen instead of once per poll. Like
+// the journal, a watcher doesn't see which process made a change: events carry `UNKNOWN_PID` and
+// no image.
+//
+// A watch handle is invalidated when its directory is deleted, renamed or its volume goes away;
+// the thread then re-opens the directory after a delay until it succeeds or the directory is no
+// longer wanted. The wanted set is recomputed every `RESYNC_INTERVAL_MS` (new users, new browser
+// profiles).
+
+/// Events kept between two collector polls; older ones are dropped first.
+#[cfg(windows)]
+const MAX_QUEUED: usize = 4096;
+
+static QUEUE: Mutex<Vec<Event>> = Mutex::new(Vec::new());
+
+/// `FILE_ACTION_*` values of a `FILE_NOTIFY_INFORMATION` record.
+const FILE_ACTION_ADDED: u32 = 1;
+const FILE_ACTION_REMOVED: u32 = 2;
+const FILE_ACTION_MODIFIED: u32 = 3;
+const FILE_ACTION_RENAMED_NEW_NAME: u32 = 5;
+
+fn access_for(action: u32) -> Option<FileAccessType> {
+  match action {
+    FILE_ACTION_ADDED | FILE_ACTION_MODIFIED | FILE_ACTION_RENAMED_NEW_NAME => {
+      Some(FileAccessType::Write)
+    }
+    FILE_ACTION_REMOVED => Some(FileAccessType::Delete),
+    // The old name of a rename; the new name follows as its own record.
+    _ => None,
+  }
+}
+
+/// Parses a `ReadDirectoryChangesW` buffer into `(action, path relative to the directory)`.
+fn parse_notifications(buf: &[u8]) -> Vec<(u32, String)> {
+  let u32_at = |o: usize| {
+    buf
+      .get(o..o + 4)
+      .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+  };
+  let mut out = Vec::new();
+  let mut off = 0;
+  while let (Some(next), Some(action), Some(name_len)) =
+    (u32_at(off), u32_at(off + 4), u32_at(off + 8))
+  {
+    let name_start = off + 12;
+    let Some(name) = buf.get(name_start..name_start + name_len as usize) else {
+      break;
+    };
+    let name: Vec<u16> = name
+      .chunks_exact(2)
+      .map(|c| u16::from_le_bytes([c[0], c[1]]))
+      .collect();
+    out.push((action, String::from_utf16_lossy(&name)));
+    if next == 0 {
+      break;
+    }
+    off += next as usize;
+  }
+  out
+}
+
+/// `dirs` without duplicates and without directories beneath another entry, which a subtree
+/// watch on the outer one already covers.
+fn outermost(mut dirs: Vec<PathBuf>) -> Vec<PathBuf> {
+  let lower = |d: &PathBuf| {
+    d.to_string_lossy()
+      .replace('/', "\\")
+      .trim_end_matches('\\')
+      .to_ascii_lowercase()
+  };
+  dirs.sort_by_key(lower);
+  let mut out: Vec<PathBuf> = Vec::new();
+  let mut kept: Vec<String> = Vec::new();
+  for d in dirs {
+    let l = lower(&d);
+    if kept
+      .iter()
+      .any(|k| l == *k || l.starts_with(&format!("{k}\\")))
+    {
+      continue;
+    }
+    kept.push(l);
+    out.push(d);
+  }
+  out
+}
+
+pub fn available() -> bool {
+  cfg!(windows)
+}
+
+/// File changes seen since the last call. Starts, and periodically updates, the watchers.
+pub fn drain() -> Vec<Event> {
+  #[cfg(windows)]
+  imp::sync();
+
+  let mut queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+  std::mem::take(&mut *queue)
+}
+
+#[cfg(windows)]
+fn push(events: impl IntoIterator<Item = Event>) {
+  let mut queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+  queue.extend(events);
+  if queue.len() > MAX_QUEUED {
+    let excess = queue.len() - MAX_QUEUED;
+    queue.drain(..excess);
+  }
+}
+
+#[cfg(windows)]
+mod imp {
+  use super::*;
+  use crate::rules_engine::protected_paths;
+  use crate::types::{now_unix_ms, UNKNOWN_PID};
+  use std::collections::HashMap;
+  use std::path::Path;
+  use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+  use std::sync::Arc;
+  use std::time::Duration;
+  use windows::core::HSTRING;
+  use windows::Win32::Foundation::{CloseHandle, HANDLE};
+  use windows::Win32::Storage::FileSystem::{
+    CreateFileW, ReadDirectoryChangesW, FILE_FLAG_BACKUP_SEMANTICS, FILE_LIST_DIRECTORY,
+    FILE_NOTIFY_CHANGE_FILE_NAME, FILE_NOTIFY_CHANGE_LAST_WRITE, FILE_NOTIFY_CHANGE_SIZE,
+    FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+  };
+  use windows::Win32::System::IO::CancelIoEx;
+
+  const RESYNC_INTERVAL_MS: u64 = 60_000;
+  const REARM_DELAY: Duration = Duration::from_secs(5);
+  /// Each watcher is a thread; directories past this many are not watched.
+  const MAX_WATCHES: usize = 64;
+  const BUFFER_SIZE: usize = 64 * 1024;
+
+  static WATCHES: Mutex<Option<Watches>> = Mutex::new(None);
+
+  struct Watches {
+    dirs: HashMap<PathBuf, Arc<Watch>>,
+    synced_at_unix_ms: u64,
+  }
+
+  #[derive(Default)]
+  struct Watch {
+    stop: AtomicBool,
+    /// The open directory handle, 0 while none is open.
+    handle: AtomicIsize,
+  }
+
+  impl Watch {
+    fn stopped(&self) -> bool {
+      self.stop.load(Ordering::Relaxed)
+    }
+
+    fn stop(&self) {
+      self.stop.store(true, Ordering::Relaxed);
+      let handle = self.handle.load(Ordering::Relaxed);
+      if handle != 0 {
+        // SAFETY: cancels the blocked read on the handle; fails harmlessly if it was closed.
+        let _ = unsafe { CancelIoEx(HANDLE(handle), None) };
+      }
+    }
+  }
+
+  pub(super) fn sync() {
+    let mut watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    let now = now_unix_ms();
+    let w = watches.get_or_insert_with(|| Watches {
+      dirs: HashMap::new(),
+      synced_at_unix_ms: 0,
+    });
+    if w.synced_at_unix_ms != 0 && now.saturating_sub(w.synced_at_unix_ms) < RESYNC_INTERVAL_MS {
+      return;
+    }
+    w.synced_at_unix_ms = now;
+
+    let mut wanted: Vec<PathBuf> = outermost(protected_paths::watched_dirs())
+      .into_iter()
+      .filter(|d| d.is_dir())
+      .collect();
+    if wanted.len() > MAX_WATCHES {
+      tracing::warn!(
+        dirs = wanted.len(),
+        max = MAX_WATCHES,
+        "too many directories to watch; watching the first ones only"
+      );
+      wanted.truncate(MAX_WATCHES);
+    }
+
+    w.dirs.retain(|dir, watch| {
+      let keep = wanted.contains(dir);
+      if !keep {
+        tracing::info!(dir = %dir.display(), "directory watch removed");
+        watch.stop();
+      }
+      keep
+    });
+    for dir in wanted {
+      if w.dirs.contains_key(&dir) {
+        continue;
+      }
+      let watch = Arc::new(Watch::default());
+      let thread_watch = Arc::clone(&watch);
+      let thread_dir = dir.clone();
+      match std::thread::Builder::new()
+        .name("dir-watch".to_string())
+        .spawn(move || watch_loop(&thread_dir, &thread_watch))
+      {
+        Ok(_) => {
+          tracing::info!(dir = %dir.display(), "directory watch added");
+          w.dirs.insert(dir, watch);
+        }
+        Err(e) => {
+          tracing::warn!(dir = %dir.display(), error = ?e, "failed to start directory watch")
+        }
+      }
+    }
+  }
+
+  fn watch_loop(dir: &Path, watch: &Watch) {
+    while !watch.stopped() {
+      if let Err(e) = watch_once(dir, watch) {
+        if !watch.stopped() {
+          tracing::info!(dir = %dir.display(), error = %e, "directory watch lost; re-arming");
+        }
+      }
+      std::thread::sleep(REARM_DELAY);
+    }
+  }
+
+  /// Reads changes of `dir` until its handle fails or the watch is stopped.
+  fn watch_once(dir: &Path, watch: &Watch) -> anyhow::Result<()> {
+    // SAFETY: plain open of a directory; the handle is closed below.
+    let handle = unsafe {
+      CreateFileW(
+        &HSTRING::from(dir.as_os_str()),
+        FILE_LIST_DIRECTORY.0,
+        FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+        None,
+        OPEN_EXISTING,
+        FILE_FLAG_BACKUP_SEMANTICS,
+        None,
+      )
+    }?;
+    watch.handle.store(handle.0, Ordering::Relaxed);
+
+    // `FILE_NOTIFY_INFORMATION` records must be DWORD-aligned.
+    let mut buf = vec![0u32; BUFFER_SIZE / 4];
+    let res = loop {
+      if watch.stopped() {
+        break Ok(());
+      }
+      let mut returned = 0u32;
+      // SAFETY: `buf` is valid for `BUFFER_SIZE` bytes; the call blocks until changes arrive or
+      // the read is cancelled.
+      let res = unsafe {
+        ReadDirectoryChangesW(
+          handle,
+          buf.as_mut_ptr() as *mut _,
+          BUFFER_SIZE as u32,
+          true,
+          FILE_NOTIFY_CHANGE_FILE_NAME | FILE_NOTIFY_CHANGE_LAST_WRITE | FILE_NOTIFY_CHANGE_SIZE,
+          Some(&mut returned),
+          None,
+          None,
+        )
+      };
+      if let Err(e) = res {
+        break Err(e.into());
+      }
+      if returned == 0 {
+        tracing::warn!(dir = %dir.display(), "directory watch buffer overflowed; changes were lost");
+        continue;
+      }
+      // SAFETY: the first `returned` bytes of `buf` were written by the call.
+      let bytes =
+        unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const u8, returned as usize) };
+      let now = now_unix_ms();
+      push(
+        parse_notifications(bytes)
+          .into_iter()
+          .filter_map(|(action, name)| {
+            let access = access_for(action)?;
+            let file_path = dir.join(name).to_string_lossy().to_string();
+            protected_paths::is_watched_location(&file_path).then_some(Event::FileAccess {
+              pid: UNKNOWN_PID,
+              image_path: None,
+              file_path,
+              access,
+              process_guid: None,
+              timestamp_unix_ms: now,
+            })
+          }),
+      );
+    };
+
+    watch.handle.store(0, Ordering::Relaxed);
+    // SAFETY: the handle was opened above and is closed once.
+    let _ = unsafe { CloseHandle(handle) };
+    res
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn record(next: u32, action: u32, name: &str) -> Vec<u8> {
+    let name: Vec<u8> = name.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    let mut r = Vec::new();
+    r.extend(next.to_le_bytes());
+    r.extend(action.to_le_bytes());
+    r.extend((name.len() as u32).to_le_bytes());
+    r.extend(name);
+    r.resize(next.max(r.len() as u32) as usize, 0);
+    r
+  }
+
+  #[test]
+  fn parses_notifications_and_drops_nested_dirs() {
+    let mut buf = record(44, FILE_ACTION_RENAMED_NEW_NAME, "Default\\Cookies");
+    buf.extend(record(0, FILE_ACTION_REMOVED, "a.txt"));
+    let parsed = parse_notifications(&buf);
+    assert_eq!(
+      parsed,
+      [
+        (FILE_ACTION_RENAMED_NEW_NAME, "Default\\Cookies".to_string()),
+        (FILE_ACTION_REMOVED, "a.txt".to_string())
+      ]
+    );
+    assert_eq!(access_for(parsed[0].0), Some(FileAccessType::Write));
+    assert_eq!(access_for(parsed[1].0), Some(FileAccessType::Delete));
+    assert_eq!(access_for(4), None);
+
+    let dirs = outermost(vec![
+      PathBuf::from("C:\\Users\\a\\AppData\\Roaming\\Mozilla\\Firefox\\Profiles\\x.default"),
+      PathBuf::from("C:\\Users\\a\\Documents"),
+      PathBuf::from("C:\\Users\\a\\AppData\\Roaming\\Mozilla\\Firefox\\Profiles"),
+      PathBuf::from("C:\\Users\\a\\documents"),
+      PathBuf::from("C:\\Users\\a\\Documents2"),
+    ]);
+    assert_eq!(
+      dirs,
+      [
+        PathBuf::from("C:\\Users\\a\\AppData\\Roaming\\Mozilla\\Firefox\\Profiles"),
+        PathBuf::from("C:\\Users\\a\\Documents"),
+        PathBuf::from("C:\\Users\\a\\Documents2"),
+      ]
+    );
+  }
+}
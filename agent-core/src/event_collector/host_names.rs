@@ -0,0 +1,302 @@
+#![cfg_attr(not(windows), allow(dead_code))]
+
+use crate::config::CollectorConfig;
+use crate::domains;
+use crate::net;
+use crate::types::Event;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+// Host names for NetConnect events that arrive without one (`dest_host`). Sysmon only fills
+// `DestinationHostname` when it resolves the address itself, and the TCP table source never does,
+// so R010 ("connection to a direct IP") would fire on many ordinary connections. Two sources, in
+// order:
+//
+// - the DNS event stream: every `DnsQuery` answer maps its addresses to the queried name. This is
+//   what the connecting process (or another one) actually looked up.
+// - reverse DNS (`GetNameInfoW`, `[collector] reverse_dns`): the PTR name of the address. Weaker,
+//   since anyone can publish a PTR record for their own address, and it blocks the collector for
+//   the lookup, so it is off by default and limited per poll.
+//
+// Both results are cached per address for `TTL_MS`, failed reverse lookups included.
+
+const TTL_MS: u64 = 60 * 60_000;
+const MAX_ENTRIES: usize = 8192;
+/// Reverse lookups per poll; the rest of the addresses stay without a name until the next one.
+#[cfg(windows)]
+const MAX_REVERSE_PER_POLL: usize = 8;
+
+#[derive(Debug, Clone)]
+struct Entry {
+  host: Option<String>,
+  expires_unix_ms: u64,
+}
+
+#[derive(Debug, Default)]
+struct HostNames {
+  by_ip: HashMap<IpAddr, Entry>,
+}
+
+static CACHE: Mutex<Option<HostNames>> = Mutex::new(None);
+
+impl HostNames {
+  fn insert(&mut self, ip: IpAddr, host: Option<String>, now: u64) {
+    if self.by_ip.len() >= MAX_ENTRIES {
+      self.by_ip.retain(|_, e| e.expires_unix_ms > now);
+      if self.by_ip.len() >= MAX_ENTRIES {
+        self.by_ip.clear();
+      }
+    }
+    self.by_ip.insert(
+      ip,
+      Entry {
+        host,
+        expires_unix_ms: now + TTL_MS,
+      },
+    );
+  }
+
+  /// The cached name of `ip`: `Some(None)` for a failed reverse lookup, `None` if not cached.
+  fn get(&self, ip: &IpAddr, now: u64) -> Option<Option<String>> {
+    self
+      .by_ip
+      .get(ip)
+      .filter(|e| e.expires_unix_ms > now)
+      .map(|e| e.host.clone())
+  }
+
+  /// Records the addresses of a DNS answer under the queried name.
+  fn observe_dns(&mut self, query_name: &str, query_results: &str, now: u64) {
+    let Some(name) = domains::normalize(query_name) else {
+      return;
+    };
+    for ip in answer_addresses(query_results) {
+      self.insert(ip, Some(name.clone()), now);
+    }
+  }
+
+  /// Fills `dest_host` of NetConnect events from the cache, looking up uncached addresses with
+  /// `reverse` (at most `max_reverse` of them).
+  fn enrich(
+    &mut self,
+    events: &mut [Event],
+    now: u64,
+    max_reverse: usize,
+    reverse: &mut dyn FnMut(&IpAddr) -> Option<String>,
+  ) {
+    for ev in events.iter() {
+      if let Event::DnsQuery {
+        query_name,
+        query_results: Some(results),
+        ..
+      } = ev
+      {
+        self.observe_dns(query_name, results, now);
+      }
+    }
+
+    let mut lookups = 0;
+    for ev in events.iter_mut() {
+      let Event::NetConnect {
+        dest_ip, dest_host, ..
+      } = ev
+      else {
+        continue;
+      };
+      if dest_host.as_deref().is_some_and(|h| !h.trim().is_empty())
+        || net::is_loopback(dest_ip)
+        || net::is_private(dest_ip)
+      {
+        continue;
+      }
+      let host = match self.get(dest_ip, now) {
+        Some(host) => host,
+        None if lookups < max_reverse => {
+          lookups += 1;
+          let host = reverse(dest_ip);
+          self.insert(*dest_ip, host.clone(), now);
+          host
+        }
+        None => None,
+      };
+      if host.is_some() {
+        *dest_host = host;
+      }
+    }
+  }
+}
+
+/// Addresses in a Sysmon `QueryResults` value, e.g.
+/// `type:  5 edge.example.net;::ffff:13.107.21.200;2620:1ec:c11::200;`. CNAME entries
+/// (`type: 5 ...`) are skipped; IPv4-mapped addresses are returned as IPv4.
+fn answer_addresses(query_results: &str) -> Vec<IpAddr> {
+  query_results
+    .split(';')
+    .filter_map(|r| r.trim().parse::<IpAddr>().ok())
+    .map(|ip| match ip {
+      IpAddr::V6(v6) => v6
+        .to_ipv4_mapped()
+        .map(IpAddr::V4)
+        .unwrap_or(IpAddr::V6(v6)),
+      v4 => v4,
+    })
+    .collect()
+}
+
+/// Attaches host names to NetConnect events without one (see the module comment).
+pub fn enrich(cfg: &CollectorConfig, events: &mut [Event]) {
+  if !cfg.resolve_hosts {
+    return;
+  }
+  let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+  let cache = cache.get_or_insert_with(HostNames::default);
+
+  #[cfg(windows)]
+  let max_reverse = if cfg.reverse_dns {
+    MAX_REVERSE_PER_POLL
+  } else {
+    0
+  };
+  #[cfg(not(windows))]
+  let max_reverse = 0;
+
+  cache.enrich(
+    events,
+    crate::types::now_unix_ms(),
+    max_reverse,
+    &mut reverse_lookup,
+  );
+}
+
+#[cfg(windows)]
+fn reverse_lookup(ip: &IpAddr) -> Option<String> {
+  use std::sync::Once;
+  use windows::Win32::Networking::WinSock::{
+    socklen_t, GetNameInfoW, WSAStartup, AF_INET, AF_INET6, IN6_ADDR, IN6_ADDR_0, IN_ADDR,
+    IN_ADDR_0, NI_NAMEREQD, SOCKADDR, SOCKADDR_IN, SOCKADDR_IN6, WSADATA,
+  };
+
+  static WINSOCK: Once = Once::new();
+  WINSOCK.call_once(|| {
+    let mut data = WSADATA::default();
+    // SAFETY: `data` is a valid out pointer; Winsock stays initialized for the process lifetime.
+    let _ = unsafe { WSAStartup(0x0202, &mut data) };
+  });
+
+  let mut host = vec![0u16; 1025];
+  // SAFETY: the socket address outlives the call and its length matches its type; `host` is a
+  // valid output buffer.
+  let res = unsafe {
+    match ip {
+      IpAddr::V4(v4) => {
+        let addr = SOCKADDR_IN {
+          sin_family: AF_INET,
+          sin_addr: IN_ADDR {
+            S_un: IN_ADDR_0 {
+              S_addr: u32::from_ne_bytes(v4.octets()),
+            },
+          },
+          ..Default::default()
+        };
+        GetNameInfoW(
+          &addr as *const _ as *const SOCKADDR,
+          socklen_t(std::mem::size_of::<SOCKADDR_IN>() as i32),
+          Some(&mut host),
+          None,
+          NI_NAMEREQD as i32,
+        )
+      }
+      IpAddr::V6(v6) => {
+        let addr = SOCKADDR_IN6 {
+          sin6_family: AF_INET6,
+          sin6_addr: IN6_ADDR {
+            u: IN6_ADDR_0 { Byte: v6.octets() },
+          },
+          ..Default::default()
+        };
+        GetNameInfoW(
+          &addr as *const _ as *const SOCKADDR,
+          socklen_t(std::mem::size_of::<SOCKADDR_IN6>() as i32),
+          Some(&mut host),
+          None,
+          NI_NAMEREQD as i32,
+        )
+      }
+    }
+  };
+  if res != 0 {
+    return None;
+  }
+  let len = host.iter().position(|&c| c == 0).unwrap_or(host.len());
+  domains::normalize(&String::from_utf16_lossy(&host[..len]))
+}
+
+#[cfg(not(windows))]
+fn reverse_lookup(_ip: &IpAddr) -> Option<String> {
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn connect(ip: &str, host: Option<&str>) -> Event {
+    Event::NetConnect {
+      pid: 1,
+      image_path: None,
+      dest_ip: ip.parse().unwrap(),
+      dest_port: 443,
+      dest_host: host.map(str::to_string),
+      protocol: "tcp".to_string(),
+      process_guid: None,
+      timestamp_unix_ms: 0,
+    }
+  }
+
+  fn host(ev: &Event) -> Option<&str> {
+    match ev {
+      Event::NetConnect { dest_host, .. } => dest_host.as_deref(),
+      _ => None,
+    }
+  }
+
+  #[test]
+  fn names_connections_from_dns_answers_then_reverse_lookups() {
+    let mut cache = HostNames::default();
+    let mut events = vec![
+      Event::DnsQuery {
+        pid: 2,
+        image_path: None,
+        query_name: "Cdn.Example.net.".to_string(),
+        query_results: Some("type:  5 edge.example.net;::ffff:93.184.216.34;".to_string()),
+        timestamp_unix_ms: 0,
+      },
+      connect("93.184.216.34", None),
+      connect("93.184.216.34", Some("sysmon.example.org")),
+      connect("203.0.113.7", None),
+      connect("198.51.100.1", None),
+      connect("192.168.1.10", None),
+    ];
+    let mut looked_up = Vec::new();
+    let mut reverse = |ip: &IpAddr| {
+      looked_up.push(*ip);
+      (ip.to_string() == "203.0.113.7").then(|| "host-7.example.com".to_string())
+    };
+    cache.enrich(&mut events, 1_000, 1, &mut reverse);
+
+    assert_eq!(host(&events[1]), Some("cdn.example.net"));
+    assert_eq!(host(&events[2]), Some("sysmon.example.org"));
+    assert_eq!(host(&events[3]), Some("host-7.example.com"));
+    // Over the per-poll lookup limit; private addresses are never looked up.
+    assert_eq!(host(&events[4]), None);
+    assert_eq!(host(&events[5]), None);
+    assert_eq!(looked_up.len(), 1);
+
+    // Failed lookups are cached too; entries expire.
+    let ip: IpAddr = "198.51.100.1".parse().unwrap();
+    cache.insert(ip, None, 1_000);
+    assert_eq!(cache.get(&ip, 2_000), Some(None));
+    assert_eq!(cache.get(&ip, 1_000 + TTL_MS), None);
+  }
+}
@@ -1,104 +1,782 @@
+use crate::config::CollectorConfig;
+#[cfg(windows)]
 use crate::paths;
+use crate::rules_engine::feed_rules::event_image;
+#[cfg(windows)]
 use crate::runtime;
-use crate::types::{now_unix_ms, Event, FileAccessType};
+use crate::types::Event;
+#[cfg(windows)]
+use crate::types::{now_unix_ms, FileAccessType};
+#[cfg(windows)]
+use defender_common::fs::write_atomic;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+#[cfg(windows)]
 use std::fs;
+#[cfg(windows)]
 use std::process::Command;
-use std::sync::{Mutex, OnceLock};
+#[cfg(windows)]
+use std::sync::OnceLock;
+use std::sync::{Mutex, PoisonError};
+
+mod clipboard;
+mod dir_watch;
+mod host_names;
+mod tcp_table;
+mod usn;
+
+#[cfg(windows)]
+static SYSMON: OnceLock<Mutex<LogCursor>> = OnceLock::new();
+#[cfg(windows)]
+static POWERSHELL: OnceLock<Mutex<LogCursor>> = OnceLock::new();
+/// Sources that could not be read on the last `collect_once`, for the heartbeat.
+static LAST_ERRORS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static BACKLOG: Mutex<Backlog> = Mutex::new(Backlog::new());
+
+const SYSMON_LOG: &str = "Microsoft-Windows-Sysmon/Operational";
+const POWERSHELL_LOG: &str = "Microsoft-Windows-PowerShell/Operational";
+
+/// Records per `wevtutil qe` call; a poll makes as many calls as its budget allows.
+#[cfg(windows)]
+const LOG_BATCH: usize = 64;
+
+const SYSMON_EVENT_IDS: &[u32] = &[1, 3, 5, 10, 11, 13, 22, 23, 26];
+/// Script block logging.
+const POWERSHELL_EVENT_IDS: &[u32] = &[4104];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventSource {
+  ProcessStart,
+  ProcessExit,
+  FileAccess,
+  NetConnect,
+  RegistrySet,
+  DnsQuery,
+  ProcessAccess,
+  ScriptBlock,
+  ClipboardChange,
+}
+
+impl EventSource {
+  pub const ALL: &'static [EventSource] = &[
+    EventSource::ProcessStart,
+    EventSource::ProcessExit,
+    EventSource::FileAccess,
+    EventSource::NetConnect,
+    EventSource::RegistrySet,
+    EventSource::DnsQuery,
+    EventSource::ProcessAccess,
+    EventSource::ScriptBlock,
+    EventSource::ClipboardChange,
+  ];
 
-static COLLECTOR: OnceLock<Mutex<SysmonCollector>> = OnceLock::new();
+  pub fn label(self) -> &'static str {
+    match self {
+      EventSource::ProcessStart => "process start (Sysmon EID 1)",
+      EventSource::ProcessExit => "process exit (Sysmon EID 5)",
+      EventSource::FileAccess => {
+        "file create/delete (Sysmon EID 11/23/26, USN journal or directory watch)"
+      }
+      EventSource::NetConnect => "network connect (Sysmon EID 3 or TCP table)",
+      EventSource::RegistrySet => "registry value set (Sysmon EID 13)",
+      EventSource::DnsQuery => "DNS query (Sysmon EID 22)",
+      EventSource::ProcessAccess => "process access (Sysmon EID 10)",
+      EventSource::ScriptBlock => "PowerShell script block (EID 4104)",
+      EventSource::ClipboardChange => "clipboard change (user session)",
+    }
+  }
 
+  /// Event log IDs the source is read from (none for the clipboard).
+  fn event_ids(self) -> &'static [u32] {
+    match self {
+      EventSource::ProcessStart => &[1],
+      EventSource::ProcessExit => &[5],
+      EventSource::FileAccess => &[11, 23, 26],
+      EventSource::NetConnect => &[3],
+      EventSource::RegistrySet => &[13],
+      EventSource::DnsQuery => &[22],
+      EventSource::ProcessAccess => &[10],
+      EventSource::ScriptBlock => &[4104],
+      EventSource::ClipboardChange => &[],
+    }
+  }
+}
+
+#[cfg(windows)]
 #[derive(Debug)]
-struct SysmonCollector {
+struct LogCursor {
   last_record_id: u64,
+  /// Records past `last_record_id` left in the log by the last poll (0 when it caught up).
+  behind: u64,
+  /// Set while the log can't be read, so an outage is warned about once.
   warned_missing: bool,
   initialized: bool,
 }
 
+/// How far the collector is behind its sources, for the heartbeat and `--status`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectorLag {
+  /// Event log records not yet read after the last poll (0 when caught up).
+  pub records_behind: u64,
+  /// Events read but not yet handed to the rules engine.
+  pub queued_events: u64,
+  /// Batches since the agent started that did not fit in the queue, and the events lost with
+  /// them.
+  pub dropped_batches: u64,
+  pub dropped_events: u64,
+}
+
+/// Events read from the sources but not yet handed to the rules engine, bounded by
+/// `collector.queue_capacity`. Event log reads are sized to the free room and leave the rest in
+/// the log; the directory watch, USN journal, TCP table and clipboard can't wait, so what of their
+/// batches doesn't fit is dropped and counted.
+struct Backlog {
+  queue: VecDeque<Event>,
+  records_behind: u64,
+  dropped_batches: u64,
+  dropped_events: u64,
+}
+
+impl Backlog {
+  const fn new() -> Self {
+    Self {
+      queue: VecDeque::new(),
+      records_behind: 0,
+      dropped_batches: 0,
+      dropped_events: 0,
+    }
+  }
+
+  #[cfg(any(windows, test))]
+  fn room(&self, capacity: usize) -> usize {
+    capacity.saturating_sub(self.queue.len())
+  }
+
+  #[cfg(any(windows, test))]
+  fn push(&mut self, capacity: usize, events: Vec<Event>) {
+    let room = self.room(capacity);
+    if events.len() > room {
+      let dropped = events.len() - room;
+      self.dropped_batches += 1;
+      self.dropped_events += dropped as u64;
+      tracing::warn!(
+        dropped,
+        queued = self.queue.len(),
+        "collector queue full; events dropped"
+      );
+    }
+    self.queue.extend(events.into_iter().take(room));
+  }
+
+  fn take(&mut self, max: usize) -> Vec<Event> {
+    let n = max.min(self.queue.len());
+    self.queue.drain(..n).collect()
+  }
+
+  fn lag(&self) -> CollectorLag {
+    CollectorLag {
+      records_behind: self.records_behind,
+      queued_events: self.queue.len() as u64,
+      dropped_batches: self.dropped_batches,
+      dropped_events: self.dropped_events,
+    }
+  }
+}
+
+#[cfg(windows)]
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-struct SysmonBookmark {
+struct Bookmark {
   #[serde(default)]
   last_record_id: u64,
 }
 
-pub fn collect_once() -> anyhow::Result<Vec<Event>> {
-  #[cfg(not(windows))]
-  {
-    return Ok(Vec::new());
-  }
-
+/// Reads new events from all sources into the collector queue and returns the oldest queued
+/// ones, at most `max_records_per_tick`.
+pub fn collect_once(cfg: &CollectorConfig) -> anyhow::Result<Vec<Event>> {
   #[cfg(windows)]
-  {
+  let events = {
     let base = paths::base_dir()?;
     if !runtime::is_dry_run() {
       fs::create_dir_all(&base)?;
     }
-    let bookmark_path = paths::sysmon_bookmark_path(&base);
+    collect_from(cfg, &mut SystemSources { cfg, base })
+  };
+  #[cfg(not(windows))]
+  let events = backlog().take(cfg.max_records_per_tick);
+  Ok(events)
+}
 
-    let m = COLLECTOR.get_or_init(|| {
-      Mutex::new(SysmonCollector {
-        last_record_id: 0,
-        warned_missing: false,
-        initialized: false,
-      })
-    });
+/// An event log that could not be read on this poll. `first` is set on the first failed poll of
+/// an outage, so its warning is logged once; the error is reported on every poll.
+#[cfg(any(windows, test))]
+struct LogUnavailable {
+  error: anyhow::Error,
+  first: bool,
+}
+
+/// The readers behind one collection, so which sources stand in for a missing Sysmon can be
+/// tested off Windows.
+#[cfg(any(windows, test))]
+trait Sources {
+  /// Sysmon's operational log; reading it takes records from `budget`.
+  fn sysmon(&mut self, budget: &mut usize) -> Result<Vec<Event>, LogUnavailable>;
+  /// The PowerShell script block log; reading it takes records from `budget`.
+  fn powershell(&mut self, budget: &mut usize) -> Result<Vec<Event>, LogUnavailable>;
+  fn dir_watch(&mut self) -> Vec<Event>;
+  fn usn_journal(&mut self) -> anyhow::Result<Vec<Event>>;
+  fn tcp_table(&mut self) -> Vec<Event>;
+  fn clipboard(&mut self) -> Vec<Event>;
+  /// Event log records left unread by the last poll.
+  fn records_behind(&self) -> u64;
+}
+
+#[cfg(windows)]
+struct SystemSources<'a> {
+  cfg: &'a CollectorConfig,
+  base: std::path::PathBuf,
+}
+
+#[cfg(windows)]
+impl Sources for SystemSources<'_> {
+  fn sysmon(&mut self, budget: &mut usize) -> Result<Vec<Event>, LogUnavailable> {
+    read_log(
+      &SYSMON,
+      SYSMON_LOG,
+      &queried_event_ids(self.cfg, SYSMON_EVENT_IDS),
+      &paths::sysmon_bookmark_path(&self.base),
+      normalize_sysmon,
+      budget,
+    )
+  }
+
+  fn powershell(&mut self, budget: &mut usize) -> Result<Vec<Event>, LogUnavailable> {
+    read_log(
+      &POWERSHELL,
+      POWERSHELL_LOG,
+      &queried_event_ids(self.cfg, POWERSHELL_EVENT_IDS),
+      &paths::powershell_bookmark_path(&self.base),
+      normalize_powershell,
+      budget,
+    )
+  }
+
+  fn dir_watch(&mut self) -> Vec<Event> {
+    dir_watch::drain()
+  }
+
+  fn usn_journal(&mut self) -> anyhow::Result<Vec<Event>> {
+    usn::read()
+  }
+
+  fn tcp_table(&mut self) -> Vec<Event> {
+    tcp_table::poll()
+  }
 
-    let mut c = m.lock().unwrap();
-    if !c.initialized {
-      c.last_record_id = load_bookmark(&bookmark_path)
-        .unwrap_or_default()
-        .last_record_id;
-      c.initialized = true;
+  fn clipboard(&mut self) -> Vec<Event> {
+    clipboard::drain()
+  }
+
+  fn records_behind(&self) -> u64 {
+    [&SYSMON, &POWERSHELL]
+      .iter()
+      .filter_map(|c| c.get())
+      .map(|m| m.lock().map(|c| c.behind).unwrap_or_default())
+      .sum()
+  }
+}
+
+/// One collection: reads what fits in the queue, records failed sources for the heartbeat and
+/// hands on the oldest queued events.
+#[cfg(any(windows, test))]
+fn collect_from(cfg: &CollectorConfig, sources: &mut impl Sources) -> Vec<Event> {
+  let room = backlog().room(cfg.queue_capacity);
+  let (mut events, errors) = read_sources(cfg, sources, cfg.max_records_per_tick.min(room));
+  if let Ok(mut last) = LAST_ERRORS.lock() {
+    *last = errors;
+  }
+
+  #[cfg(windows)]
+  {
+    if cfg.verify_publishers {
+      verify_publishers(&mut events);
     }
+    download_origins(&mut events);
+  }
+  host_names::enrich(cfg, &mut events);
+  let events = filter_events(cfg, events);
 
-    let events = match poll_sysmon(&mut c) {
-      Ok(evs) => evs,
-      Err(e) => {
-        if !c.warned_missing {
+  let mut backlog = backlog();
+  backlog.records_behind = sources.records_behind();
+  backlog.push(cfg.queue_capacity, events);
+  backlog.take(cfg.max_records_per_tick)
+}
+
+/// New events from the event logs (at most `budget` records between them) and from the sources
+/// that stand in for Sysmon or add to it, with the sources that could not be read. The stand-ins
+/// are read on every poll Sysmon is unavailable.
+#[cfg(any(windows, test))]
+fn read_sources(
+  cfg: &CollectorConfig,
+  sources: &mut impl Sources,
+  mut budget: usize,
+) -> (Vec<Event>, Vec<String>) {
+  let mut errors = Vec::new();
+  // Events from sources that can't be paused, kept apart so they go after the event log ones.
+  let (mut events, mut unpausable) = match sources.sysmon(&mut budget) {
+    Ok(events) => (events, Vec::new()),
+    Err(LogUnavailable { error, first }) => {
+      errors.push(format!("Sysmon: {error:#}"));
+      // Without Sysmon there are no file events; directory watchers or the change journal
+      // stand in for them.
+      let mut events = if cfg.directory_watch {
+        if first {
           tracing::warn!(
-            error = ?e,
-            "Sysmon collector unavailable (Sysmon not installed or access denied). Detection will run only on simulated events."
+            error = ?error,
+            "Sysmon collector unavailable (Sysmon not installed or access denied); watching protected directories for file changes"
+          );
+        }
+        sources.dir_watch()
+      } else if cfg.usn_journal {
+        if first {
+          tracing::warn!(
+            error = ?error,
+            "Sysmon collector unavailable (Sysmon not installed or access denied); reading file changes from the USN journal"
+          );
+        }
+        sources.usn_journal().unwrap_or_else(|e| {
+          if first {
+            tracing::warn!(error = ?e, "USN journal unavailable");
+          }
+          errors.push(format!("USN journal: {e:#}"));
+          Vec::new()
+        })
+      } else {
+        if first {
+          tracing::warn!(
+            error = ?error,
+            "Sysmon collector unavailable (Sysmon not installed or access denied); no file events without it."
           );
-          c.warned_missing = true;
         }
         Vec::new()
+      };
+      // ... and the TCP tables for network connections.
+      if cfg.tcp_table {
+        events.extend(sources.tcp_table());
       }
-    };
+      (Vec::new(), events)
+    }
+  };
+  if cfg.powershell {
+    events.extend(sources.powershell(&mut budget).unwrap_or_else(
+      |LogUnavailable { error, first }| {
+        if first {
+          tracing::warn!(error = ?error, "PowerShell script block log unavailable");
+        }
+        errors.push(format!("PowerShell: {error:#}"));
+        Vec::new()
+      },
+    ));
+  }
 
-    if !events.is_empty() {
-      let bm = SysmonBookmark {
-        last_record_id: c.last_record_id,
-      };
-      if runtime::is_dry_run() {
-        tracing::warn!(
-          record_id = bm.last_record_id,
-          "DRY-RUN: would update Sysmon bookmark"
+  if cfg.clipboard {
+    unpausable.extend(sources.clipboard());
+  }
+  // Event log reads were sized to fit the queue; the rest takes what room is left.
+  events.append(&mut unpausable);
+  (events, errors)
+}
+
+fn backlog() -> std::sync::MutexGuard<'static, Backlog> {
+  BACKLOG.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Sets the publisher of new processes from the image's verified signature. Sysmon reports the
+/// version resource's `Company`, which any binary can claim; a process whose image is unsigned or
+/// already gone gets no publisher.
+#[cfg(windows)]
+fn verify_publishers(events: &mut [Event]) {
+  for ev in events.iter_mut() {
+    if let Event::ProcessStart {
+      image_path,
+      signer_publisher,
+      ..
+    } = ev
+    {
+      let verified =
+        defender_common::authenticode::verified_publisher(std::path::Path::new(image_path));
+      if verified != *signer_publisher {
+        tracing::debug!(
+          image = %image_path,
+          claimed = ?signer_publisher,
+          verified = ?verified,
+          "process publisher replaced by verified signer"
         );
-      } else {
-        let _ = save_bookmark(&bookmark_path, &bm);
       }
+      *signer_publisher = verified;
+    }
+  }
+}
+
+/// Records where unsigned images were downloaded from, from their Mark of the Web (see `motw`).
+/// Signed images are skipped: their publisher already says more than the zone would.
+#[cfg(windows)]
+fn download_origins(events: &mut [Event]) {
+  for ev in events.iter_mut() {
+    if let Event::ProcessStart {
+      image_path,
+      signer_publisher: None,
+      download_origin,
+      ..
+    } = ev
+    {
+      *download_origin = crate::motw::read(std::path::Path::new(image_path));
+    }
+  }
+}
+
+/// Records behind, queue depth and drops, as of the last `collect_once`.
+pub fn lag() -> CollectorLag {
+  backlog().lag()
+}
+
+/// Event log sources that failed on the last collection, e.g. `Sysmon: access denied`.
+pub fn last_errors() -> Vec<String> {
+  LAST_ERRORS.lock().map(|e| e.clone()).unwrap_or_default()
+}
+
+/// Drops events the config marks as irrelevant. Runs after the bookmark is advanced so filtered
+/// events are never re-read.
+pub fn filter_events(cfg: &CollectorConfig, events: Vec<Event>) -> Vec<Event> {
+  let filters = Filters::new(cfg);
+  if filters.is_empty() {
+    return events;
+  }
+
+  let before = events.len();
+  let kept: Vec<Event> = events.into_iter().filter(|ev| filters.keep(ev)).collect();
+
+  if kept.len() != before {
+    tracing::debug!(
+      dropped = before - kept.len(),
+      kept = kept.len(),
+      "collector filters dropped events"
+    );
+  }
+  kept
+}
+
+/// The `[collector]` filter lists, trimmed and lowercased once per collection.
+struct Filters {
+  extensions: Vec<String>,
+  net_images: Vec<String>,
+  exclude_images: Vec<String>,
+  include_images: Vec<String>,
+  exclude_dirs: Vec<String>,
+  include_dirs: Vec<String>,
+}
+
+impl Filters {
+  fn new(cfg: &CollectorConfig) -> Self {
+    let extensions = lowercase(&cfg.ignore_file_extensions)
+      .into_iter()
+      .map(|e| {
+        if e.starts_with('.') {
+          e
+        } else {
+          format!(".{e}")
+        }
+      })
+      .collect();
+    // Directories end in a separator so `C:\Temp` doesn't cover `C:\Temporary`.
+    let dirs = |list: &[String]| -> Vec<String> {
+      lowercase(list)
+        .into_iter()
+        .map(|d| format!("{}\\", d.replace('/', "\\").trim_end_matches('\\')))
+        .collect()
+    };
+    Self {
+      extensions,
+      net_images: lowercase(&cfg.ignore_net_images),
+      exclude_images: lowercase(&cfg.exclude_image_prefixes),
+      include_images: lowercase(&cfg.include_image_prefixes),
+      exclude_dirs: dirs(&cfg.exclude_file_dirs),
+      include_dirs: dirs(&cfg.include_file_dirs),
+    }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.extensions.is_empty()
+      && self.net_images.is_empty()
+      && self.exclude_images.is_empty()
+      && self.exclude_dirs.is_empty()
+  }
+
+  fn keep(&self, ev: &Event) -> bool {
+    // Process trees need every start and exit, whatever the image.
+    if !matches!(ev, Event::ProcessStart { .. } | Event::ProcessExit { .. }) {
+      if let Some(image) = event_image(ev) {
+        if excluded(&self.exclude_images, &self.include_images, image) {
+          return false;
+        }
+      }
+    }
+    match ev {
+      Event::FileAccess { file_path, .. } => {
+        let p = file_path.to_ascii_lowercase();
+        !self.extensions.iter().any(|e| p.ends_with(e.as_str()))
+          && !excluded(&self.exclude_dirs, &self.include_dirs, file_path)
+      }
+      Event::NetConnect {
+        image_path: Some(image),
+        ..
+      }
+      | Event::DnsQuery {
+        image_path: Some(image),
+        ..
+      } => !image_matches(&self.net_images, image),
+      _ => true,
+    }
+  }
+}
+
+fn lowercase(list: &[String]) -> Vec<String> {
+  list
+    .iter()
+    .map(|i| i.trim().to_ascii_lowercase())
+    .filter(|i| !i.is_empty())
+    .collect()
+}
+
+/// Whether `path` starts with one of `exclude` and none of `include`, ignoring case.
+fn excluded(exclude: &[String], include: &[String], path: &str) -> bool {
+  if exclude.is_empty() {
+    return false;
+  }
+  let p = path.to_ascii_lowercase();
+  exclude.iter().any(|e| p.starts_with(e.as_str()))
+    && !include.iter().any(|i| p.starts_with(i.as_str()))
+}
+
+fn image_matches(images: &[String], image_path: &str) -> bool {
+  let p = image_path.to_ascii_lowercase();
+  let name = p.rsplit(['\\', '/']).next().unwrap_or(&p);
+  images.iter().any(|i| *i == p || *i == name)
+}
+
+/// Event IDs of `ids` that `exclude_event_ids` leaves in the event log queries.
+fn queried_event_ids(cfg: &CollectorConfig, ids: &[u32]) -> Vec<u32> {
+  ids
+    .iter()
+    .copied()
+    .filter(|id| !cfg.exclude_event_ids.contains(id))
+    .collect()
+}
+
+/// Event sources the collector can currently read from on this machine.
+///
+/// All Sysmon sources come from its operational log, so they are either all active or all
+/// missing, less those whose event IDs are all in `exclude_event_ids`. Script blocks come from the
+/// PowerShell log; the clipboard needs an interactive session (see `clipboard`).
+pub fn active_sources(cfg: &CollectorConfig) -> Vec<EventSource> {
+  let mut out = Vec::new();
+  if log_enabled(SYSMON_LOG) {
+    let ids = queried_event_ids(cfg, SYSMON_EVENT_IDS);
+    out.extend(
+      EventSource::ALL
+        .iter()
+        .filter(|s| s.event_ids().iter().any(|id| ids.contains(id))),
+    );
+  } else {
+    if (cfg.directory_watch && dir_watch::available()) || (cfg.usn_journal && usn::available()) {
+      out.push(EventSource::FileAccess);
     }
+    if cfg.tcp_table && tcp_table::available() {
+      out.push(EventSource::NetConnect);
+    }
+  }
+  if cfg.powershell
+    && !queried_event_ids(cfg, POWERSHELL_EVENT_IDS).is_empty()
+    && log_enabled(POWERSHELL_LOG)
+  {
+    out.push(EventSource::ScriptBlock);
+  }
+  if cfg.clipboard && clipboard::available() {
+    out.push(EventSource::ClipboardChange);
+  }
+  out
+}
 
-    Ok(events)
+/// Full image path of a running process.
+#[cfg(windows)]
+fn image_path(pid: u32) -> Option<String> {
+  use windows::core::PWSTR;
+  use windows::Win32::Foundation::CloseHandle;
+  use windows::Win32::System::Threading::{
+    OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+  };
+
+  // SAFETY: the handle is closed before returning; `buf` outlives the query.
+  unsafe {
+    let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+    let mut buf = vec![0u16; 1024];
+    let mut len = buf.len() as u32;
+    let res = QueryFullProcessImageNameW(
+      handle,
+      PROCESS_NAME_WIN32,
+      PWSTR(buf.as_mut_ptr()),
+      &mut len,
+    );
+    let _ = CloseHandle(handle);
+    res.ok()?;
+    Some(String::from_utf16_lossy(&buf[..len as usize]))
   }
 }
 
 #[cfg(windows)]
-fn poll_sysmon(c: &mut SysmonCollector) -> anyhow::Result<Vec<Event>> {
-  // Use a constant, sanitized query. No user input is interpolated besides last_record_id (u64).
-  const LOG: &str = "Microsoft-Windows-Sysmon/Operational";
+fn log_enabled(log: &str) -> bool {
+  let Ok(output) = Command::new("wevtutil").args(["gl", log]).output() else {
+    return false;
+  };
+  if !output.status.success() {
+    return false;
+  }
+  String::from_utf8_lossy(&output.stdout)
+    .lines()
+    .any(|l| l.trim().eq_ignore_ascii_case("enabled: true"))
+}
+
+#[cfg(not(windows))]
+fn log_enabled(_log: &str) -> bool {
+  false
+}
+
+#[cfg(windows)]
+fn read_log(
+  cursor: &OnceLock<Mutex<LogCursor>>,
+  log: &str,
+  event_ids: &[u32],
+  bookmark_path: &std::path::Path,
+  normalize: fn(&RawEvent) -> Option<Event>,
+  budget: &mut usize,
+) -> Result<Vec<Event>, LogUnavailable> {
+  let m = cursor.get_or_init(|| {
+    Mutex::new(LogCursor {
+      last_record_id: 0,
+      behind: 0,
+      warned_missing: false,
+      initialized: false,
+    })
+  });
+
+  let mut c = m.lock().unwrap();
+  if !c.initialized {
+    c.last_record_id = load_bookmark(bookmark_path)
+      .unwrap_or_default()
+      .last_record_id;
+    c.initialized = true;
+  }
+
+  let events = match poll_log(&mut c, log, event_ids, normalize, budget) {
+    Ok(evs) => {
+      c.warned_missing = false;
+      evs
+    }
+    Err(error) => {
+      let first = !c.warned_missing;
+      c.warned_missing = true;
+      return Err(LogUnavailable { error, first });
+    }
+  };
+
+  if !events.is_empty() {
+    let bm = Bookmark {
+      last_record_id: c.last_record_id,
+    };
+    if runtime::is_dry_run() {
+      tracing::warn!(
+        log,
+        record_id = bm.last_record_id,
+        "DRY-RUN: would update event log bookmark"
+      );
+    } else {
+      let _ = save_bookmark(bookmark_path, &bm);
+    }
+  }
+  Ok(events)
+}
+
+/// Reads batches of new records until the log is drained or `budget` records have been read.
+/// What is left is worked off on later polls; `c.behind` says how much.
+#[cfg(windows)]
+fn poll_log(
+  c: &mut LogCursor,
+  log: &str,
+  event_ids: &[u32],
+  normalize: fn(&RawEvent) -> Option<Event>,
+  budget: &mut usize,
+) -> anyhow::Result<Vec<Event>> {
+  let mut events = Vec::new();
+  if event_ids.is_empty() {
+    // Every ID is in `exclude_event_ids`.
+    c.behind = 0;
+    return Ok(events);
+  }
+  let mut read = 0;
+  loop {
+    let count = LOG_BATCH.min(*budget);
+    if count == 0 {
+      // By record ID, so records of event IDs the collector doesn't read count too.
+      if let Some(newest) = newest_record_id(log) {
+        c.behind = newest.saturating_sub(c.last_record_id);
+      }
+      break;
+    }
+    let raw = match query_log(c, log, event_ids, count) {
+      Ok(raw) => raw,
+      Err(e) if read == 0 => return Err(e),
+      // The cursor is already past the records read so far; keep them.
+      Err(e) => {
+        tracing::warn!(error = ?e, log, "event log read failed mid-poll");
+        break;
+      }
+    };
+    *budget -= raw.len().min(*budget);
+    read += raw.len();
+    events.extend(raw.iter().filter_map(normalize));
+    if raw.len() < count {
+      c.behind = 0;
+      break;
+    }
+  }
+  Ok(events)
+}
+
+/// Up to `count` records after the cursor, oldest first; advances the cursor past them.
+#[cfg(windows)]
+fn query_log(
+  c: &mut LogCursor,
+  log: &str,
+  event_ids: &[u32],
+  count: usize,
+) -> anyhow::Result<Vec<RawEvent>> {
+  // Use a constant, sanitized query. Only numbers (event IDs, last_record_id) are interpolated.
+  let ids = event_ids
+    .iter()
+    .map(|id| format!("EventID={id}"))
+    .collect::<Vec<_>>()
+    .join(" or ");
   let query = format!(
-    "*[System[(EventID=1 or EventID=3 or EventID=11) and (EventRecordID > {})]]",
+    "*[System[({ids}) and (EventRecordID > {})]]",
     c.last_record_id
   );
 
   let output = Command::new("wevtutil")
     .args([
       "qe",
-      LOG,
+      log,
       "/f:xml",
       "/rd:false",
-      "/c:64",
+      &format!("/c:{count}"),
       &format!("/q:{query}"),
     ])
     .output()?;
@@ -110,11 +788,43 @@ fn poll_sysmon(c: &mut SysmonCollector) -> anyhow::Result<Vec<Event>> {
 
   let stdout = String::from_utf8_lossy(&output.stdout);
   let xml = format!("<Events>{}</Events>", stdout);
-  parse_sysmon_xml(&xml, c)
+  parse_event_xml(&xml, c)
 }
 
+/// Record ID of the newest record in `log`.
 #[cfg(windows)]
-fn parse_sysmon_xml(xml: &str, c: &mut SysmonCollector) -> anyhow::Result<Vec<Event>> {
+fn newest_record_id(log: &str) -> Option<u64> {
+  let output = Command::new("wevtutil")
+    .args(["qe", log, "/f:xml", "/rd:true", "/c:1"])
+    .output()
+    .ok()?;
+  if !output.status.success() {
+    return None;
+  }
+  let xml = format!(
+    "<Events>{}</Events>",
+    String::from_utf8_lossy(&output.stdout)
+  );
+  let mut newest = LogCursor {
+    last_record_id: 0,
+    behind: 0,
+    warned_missing: false,
+    initialized: true,
+  };
+  parse_event_xml(&xml, &mut newest).ok()?;
+  Some(newest.last_record_id)
+}
+
+/// One event from `wevtutil` XML: its ID, the process that logged it, and its named data fields.
+#[cfg(windows)]
+struct RawEvent {
+  event_id: u32,
+  process_id: Option<u32>,
+  data: std::collections::HashMap<String, String>,
+}
+
+#[cfg(windows)]
+fn parse_event_xml(xml: &str, c: &mut LogCursor) -> anyhow::Result<Vec<RawEvent>> {
   use quick_xml::events::Event as XEvent;
   use quick_xml::Reader;
 
@@ -131,6 +841,7 @@ fn parse_sysmon_xml(xml: &str, c: &mut SysmonCollector) -> anyhow::Result<Vec<Ev
   let mut current_tag: Option<String> = None;
   let mut event_id: Option<u32> = None;
   let mut record_id: Option<u64> = None;
+  let mut process_id: Option<u32> = None;
   let mut data_name: Option<String> = None;
   let mut data: std::collections::HashMap<String, String> = std::collections::HashMap::new();
 
@@ -147,6 +858,7 @@ fn parse_sysmon_xml(xml: &str, c: &mut SysmonCollector) -> anyhow::Result<Vec<Ev
           in_event = true;
           event_id = None;
           record_id = None;
+          process_id = None;
           data.clear();
           continue;
         }
@@ -165,6 +877,9 @@ fn parse_sysmon_xml(xml: &str, c: &mut SysmonCollector) -> anyhow::Result<Vec<Ev
         }
 
         if in_system {
+          if lname == "Execution" {
+            process_id = execution_process_id(&e);
+          }
           current_tag = Some(lname.to_string());
         } else if in_event_data && lname == "Data" {
           data_name = e
@@ -174,6 +889,10 @@ fn parse_sysmon_xml(xml: &str, c: &mut SysmonCollector) -> anyhow::Result<Vec<Ev
             .and_then(|a| String::from_utf8(a.value.to_vec()).ok());
         }
       }
+      // `<Execution ProcessID=".." ThreadID=".."/>` is usually self-closing.
+      Ok(XEvent::Empty(e)) if in_system && e.name().as_ref() == b"Execution" => {
+        process_id = execution_process_id(&e);
+      }
       Ok(XEvent::Text(t)) => {
         if !in_event {
           continue;
@@ -213,14 +932,16 @@ fn parse_sysmon_xml(xml: &str, c: &mut SysmonCollector) -> anyhow::Result<Vec<Ev
             if rid > c.last_record_id {
               c.last_record_id = rid;
             }
-            if let Some(ev) = normalize_sysmon(eid, &data) {
-              events.push(ev);
-            }
+            events.push(RawEvent {
+              event_id: eid,
+              process_id,
+              data: std::mem::take(&mut data),
+            });
           }
         }
       }
       Ok(XEvent::Eof) => break,
-      Err(e) => return Err(anyhow::anyhow!("sysmon xml parse error: {e}")),
+      Err(e) => return Err(anyhow::anyhow!("event xml parse error: {e}")),
       _ => {}
     }
     buf.clear();
@@ -230,10 +951,16 @@ fn parse_sysmon_xml(xml: &str, c: &mut SysmonCollector) -> anyhow::Result<Vec<Ev
 }
 
 #[cfg(windows)]
-fn normalize_sysmon(
-  event_id: u32,
-  data: &std::collections::HashMap<String, String>,
-) -> Option<Event> {
+fn execution_process_id(e: &quick_xml::events::BytesStart) -> Option<u32> {
+  e.attributes()
+    .flatten()
+    .find(|a| a.key.as_ref() == b"ProcessID")
+    .and_then(|a| std::str::from_utf8(&a.value).ok()?.parse().ok())
+}
+
+#[cfg(windows)]
+fn normalize_sysmon(raw: &RawEvent) -> Option<Event> {
+  let (event_id, data) = (raw.event_id, &raw.data);
   let ts = now_unix_ms();
   match event_id {
     1 => {
@@ -249,13 +976,23 @@ fn normalize_sysmon(
         ppid,
         image_path: image,
         signer_publisher: publisher,
+        command_line: data.get("CommandLine").cloned(),
+        download_origin: None,
+        process_guid: data.get("ProcessGuid").cloned(),
+        parent_process_guid: data.get("ParentProcessGuid").cloned(),
         timestamp_unix_ms: ts,
       })
     }
+    5 => Some(Event::ProcessExit {
+      pid: data.get("ProcessId")?.parse::<u32>().ok()?,
+      image_path: data.get("Image").cloned(),
+      process_guid: data.get("ProcessGuid").cloned(),
+      timestamp_unix_ms: ts,
+    }),
     3 => {
       let pid = data.get("ProcessId")?.parse::<u32>().ok()?;
       let image = data.get("Image").cloned();
-      let dest_ip = data.get("DestinationIp")?.to_string();
+      let dest_ip = crate::net::parse_ip(data.get("DestinationIp")?)?;
       let dest_port = data
         .get("DestinationPort")
         .and_then(|s| s.parse::<u16>().ok())
@@ -272,6 +1009,18 @@ fn normalize_sysmon(
         dest_port,
         dest_host: host,
         protocol,
+        process_guid: data.get("ProcessGuid").cloned(),
+        timestamp_unix_ms: ts,
+      })
+    }
+    10 => {
+      let pid = data.get("SourceProcessId")?.parse::<u32>().ok()?;
+      Some(Event::ProcessAccess {
+        pid,
+        image_path: data.get("SourceImage").cloned(),
+        target_pid: data.get("TargetProcessId")?.parse::<u32>().ok()?,
+        target_image: data.get("TargetImage")?.to_string(),
+        granted_access: crate::credential_dump::parse_access_mask(data.get("GrantedAccess")?)?,
         timestamp_unix_ms: ts,
       })
     }
@@ -284,6 +1033,39 @@ fn normalize_sysmon(
         image_path: image,
         file_path: file,
         access: FileAccessType::Write,
+        process_guid: data.get("ProcessGuid").cloned(),
+        timestamp_unix_ms: ts,
+      })
+    }
+    13 => {
+      let pid = data.get("ProcessId")?.parse::<u32>().ok()?;
+      Some(Event::RegistrySet {
+        pid,
+        image_path: data.get("Image").cloned(),
+        key_path: data.get("TargetObject")?.to_string(),
+        value: data.get("Details").cloned(),
+        timestamp_unix_ms: ts,
+      })
+    }
+    22 => {
+      let pid = data.get("ProcessId")?.parse::<u32>().ok()?;
+      Some(Event::DnsQuery {
+        pid,
+        image_path: data.get("Image").cloned(),
+        query_name: data.get("QueryName")?.to_string(),
+        query_results: data.get("QueryResults").cloned(),
+        timestamp_unix_ms: ts,
+      })
+    }
+    // FileDelete (archived) and FileDeleteDetected.
+    23 | 26 => {
+      let pid = data.get("ProcessId")?.parse::<u32>().ok()?;
+      Some(Event::FileAccess {
+        pid,
+        image_path: data.get("Image").cloned(),
+        file_path: data.get("TargetFilename")?.to_string(),
+        access: FileAccessType::Delete,
+        process_guid: data.get("ProcessGuid").cloned(),
         timestamp_unix_ms: ts,
       })
     }
@@ -291,30 +1073,245 @@ fn normalize_sysmon(
   }
 }
 
-fn load_bookmark(path: &std::path::Path) -> anyhow::Result<SysmonBookmark> {
+/// Script block text (4104). Long scripts are logged in several parts; each part is its own event.
+#[cfg(windows)]
+fn normalize_powershell(raw: &RawEvent) -> Option<Event> {
+  if raw.event_id != 4104 {
+    return None;
+  }
+  let part = |k: &str| raw.data.get(k).and_then(|v| v.trim().parse::<u32>().ok());
+  Some(Event::ScriptBlock {
+    pid: raw.process_id.unwrap_or(0),
+    script_block_id: raw.data.get("ScriptBlockId").cloned().unwrap_or_default(),
+    part: part("MessageNumber").unwrap_or(1),
+    parts: part("MessageTotal").unwrap_or(1),
+    text: raw.data.get("ScriptBlockText")?.to_string(),
+    path: raw.data.get("Path").filter(|p| !p.is_empty()).cloned(),
+    timestamp_unix_ms: now_unix_ms(),
+  })
+}
+
+#[cfg(windows)]
+fn load_bookmark(path: &std::path::Path) -> anyhow::Result<Bookmark> {
   if !path.exists() {
-    return Ok(SysmonBookmark::default());
+    return Ok(Bookmark::default());
   }
   let raw = fs::read_to_string(path)?;
   Ok(toml::from_str(&raw)?)
 }
 
-fn save_bookmark(path: &std::path::Path, bm: &SysmonBookmark) -> anyhow::Result<()> {
+#[cfg(windows)]
+fn save_bookmark(path: &std::path::Path, bm: &Bookmark) -> anyhow::Result<()> {
   let raw = toml::to_string_pretty(bm)?;
   write_atomic(path, &raw)
 }
 
-fn write_atomic(path: &std::path::Path, contents: &str) -> anyhow::Result<()> {
-  let parent = path
-    .parent()
-    .ok_or_else(|| anyhow::anyhow!("bookmark path has no parent: {}", path.display()))?;
-  fs::create_dir_all(parent)?;
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::FileAccessType;
+
+  fn file(path: &str) -> Event {
+    Event::FileAccess {
+      pid: 1,
+      image_path: None,
+      file_path: path.to_string(),
+      access: FileAccessType::Write,
+      process_guid: None,
+      timestamp_unix_ms: 0,
+    }
+  }
+
+  fn net(image: &str) -> Event {
+    Event::NetConnect {
+      pid: 1,
+      image_path: Some(image.to_string()),
+      dest_ip: std::net::IpAddr::from([1, 2, 3, 4]),
+      dest_port: 443,
+      dest_host: None,
+      protocol: "tcp".to_string(),
+      process_guid: None,
+      timestamp_unix_ms: 0,
+    }
+  }
+
+  #[test]
+  fn prefix_and_directory_filters_with_include_exceptions() {
+    let cfg = CollectorConfig {
+      exclude_image_prefixes: vec!["C:\\Program Files\\".to_string()],
+      include_image_prefixes: vec!["c:\\program files\\python".to_string()],
+      exclude_file_dirs: vec!["C:\\Temp\\".to_string(), "C:/Build".to_string()],
+      include_file_dirs: vec!["C:\\Temp\\keep".to_string()],
+      ..CollectorConfig::default()
+    };
+    let start = Event::ProcessStart {
+      pid: 2,
+      ppid: 1,
+      image_path: "C:\\Program Files\\Backup\\agent.exe".to_string(),
+      command_line: None,
+      signer_publisher: None,
+      download_origin: None,
+      process_guid: None,
+      parent_process_guid: None,
+      timestamp_unix_ms: 0,
+    };
+    let events = vec![
+      start,
+      net("C:\\Program Files\\Backup\\agent.exe"),
+      net("C:\\Program Files\\Python312\\python.exe"),
+      file("C:\\Temp\\a.txt"),
+      file("C:\\Temp\\Keep\\b.txt"),
+      file("C:\\Build\\out.obj"),
+      file("C:\\Temporary\\c.txt"),
+    ];
+
+    let kept = filter_events(&cfg, events);
+    assert_eq!(kept.len(), 4);
+    assert!(matches!(&kept[0], Event::ProcessStart { .. }));
+    assert!(
+      matches!(&kept[1], Event::NetConnect { image_path: Some(i), .. } if i.ends_with("python.exe"))
+    );
+    assert!(
+      matches!(&kept[2], Event::FileAccess { file_path, .. } if file_path.ends_with("b.txt"))
+    );
+    assert!(
+      matches!(&kept[3], Event::FileAccess { file_path, .. } if file_path.ends_with("c.txt"))
+    );
+
+    let cfg = CollectorConfig {
+      exclude_event_ids: vec![11, 4104],
+      ..CollectorConfig::default()
+    };
+    assert_eq!(
+      queried_event_ids(&cfg, SYSMON_EVENT_IDS),
+      [1, 3, 5, 10, 13, 22, 23, 26]
+    );
+    assert!(queried_event_ids(&cfg, POWERSHELL_EVENT_IDS).is_empty());
+  }
+
+  /// A machine without Sysmon: every poll fails Sysmon and each stand-in has one new event.
+  struct NoSysmon;
+
+  impl Sources for NoSysmon {
+    fn sysmon(&mut self, _budget: &mut usize) -> Result<Vec<Event>, LogUnavailable> {
+      Err(LogUnavailable {
+        error: anyhow::anyhow!("channel not found"),
+        first: false,
+      })
+    }
+    fn powershell(&mut self, _budget: &mut usize) -> Result<Vec<Event>, LogUnavailable> {
+      Ok(Vec::new())
+    }
+    fn dir_watch(&mut self) -> Vec<Event> {
+      vec![file("C:\\Users\\a\\Documents\\watched.txt")]
+    }
+    fn usn_journal(&mut self) -> anyhow::Result<Vec<Event>> {
+      Ok(vec![file("C:\\Users\\a\\Documents\\journal.txt")])
+    }
+    fn tcp_table(&mut self) -> Vec<Event> {
+      vec![net("C:\\Tools\\curl.exe")]
+    }
+    fn clipboard(&mut self) -> Vec<Event> {
+      Vec::new()
+    }
+    fn records_behind(&self) -> u64 {
+      0
+    }
+  }
 
-  let tmp = parent.join(format!(
-    ".{}.tmp",
-    path.file_name().unwrap_or_default().to_string_lossy()
-  ));
-  fs::write(&tmp, contents)?;
-  fs::rename(&tmp, path)?;
-  Ok(())
+  // One test, as `collect_from` shares the collector queue and error list.
+  #[test]
+  fn stand_ins_are_read_on_every_poll_while_sysmon_is_down() {
+    let cfg = CollectorConfig {
+      directory_watch: true,
+      tcp_table: false,
+      powershell: false,
+      clipboard: false,
+      ..CollectorConfig::default()
+    };
+    for _ in 0..2 {
+      let events = collect_from(&cfg, &mut NoSysmon);
+      assert!(
+        matches!(&events[..], [Event::FileAccess { file_path, .. }] if file_path.ends_with("watched.txt"))
+      );
+      // The heartbeat keeps reporting the missing source, not just after the first poll.
+      assert_eq!(last_errors(), ["Sysmon: channel not found"]);
+    }
+
+    // The USN journal stands in when the directory watch is off.
+    let cfg = CollectorConfig {
+      directory_watch: false,
+      ..cfg
+    };
+    for _ in 0..2 {
+      let events = collect_from(&cfg, &mut NoSysmon);
+      assert!(
+        matches!(&events[..], [Event::FileAccess { file_path, .. }] if file_path.ends_with("journal.txt"))
+      );
+    }
+
+    // New connections come from the TCP tables, also on every poll.
+    let cfg = CollectorConfig {
+      tcp_table: true,
+      ..cfg
+    };
+    for _ in 0..2 {
+      let events = collect_from(&cfg, &mut NoSysmon);
+      assert_eq!(events.len(), 2);
+      assert!(
+        matches!(&events[1], Event::NetConnect { image_path: Some(i), .. } if i.ends_with("curl.exe"))
+      );
+    }
+  }
+
+  #[test]
+  fn queue_is_bounded_and_counts_what_it_drops() {
+    let mut backlog = Backlog::new();
+    backlog.push(4, vec![file("C:\\a"), file("C:\\b"), file("C:\\c")]);
+    assert_eq!(backlog.room(4), 1);
+
+    // Only the first event of the next batch fits.
+    backlog.push(4, vec![file("C:\\d"), file("C:\\e"), file("C:\\f")]);
+    let lag = backlog.lag();
+    assert_eq!(lag.queued_events, 4);
+    assert_eq!((lag.dropped_batches, lag.dropped_events), (1, 2));
+
+    // Oldest first, at most `max` per take.
+    let taken = backlog.take(3);
+    assert!(matches!(&taken[0], Event::FileAccess { file_path, .. } if file_path == "C:\\a"));
+    assert_eq!(taken.len(), 3);
+    assert_eq!(backlog.take(3).len(), 1);
+    assert_eq!(backlog.lag().queued_events, 0);
+  }
+
+  #[test]
+  fn empty_filters_keep_everything() {
+    let events = vec![file("C:\\a.log"), net("C:\\x\\OneDrive.exe")];
+    assert_eq!(filter_events(&CollectorConfig::default(), events).len(), 2);
+  }
+
+  #[test]
+  fn drops_ignored_extensions_and_images() {
+    let cfg = CollectorConfig {
+      ignore_file_extensions: vec!["LOG".to_string(), ".tmp".to_string()],
+      ignore_net_images: vec!["onedrive.exe".to_string()],
+      ..CollectorConfig::default()
+    };
+    let events = vec![
+      file("C:\\Temp\\a.LOG"),
+      file("C:\\Temp\\b.tmp"),
+      file("C:\\Users\\User\\AppData\\Local\\Google\\Chrome\\User Data\\Default\\Login Data"),
+      net("C:\\Program Files\\Microsoft OneDrive\\OneDrive.exe"),
+      net("C:\\Temp\\evil.exe"),
+    ];
+
+    let kept = filter_events(&cfg, events);
+    assert_eq!(kept.len(), 2);
+    assert!(
+      matches!(&kept[0], Event::FileAccess { file_path, .. } if file_path.ends_with("Login Data"))
+    );
+    assert!(
+      matches!(&kept[1], Event::NetConnect { image_path: Some(i), .. } if i.ends_with("evil.exe"))
+    );
+  }
 }
@@ -0,0 +1,255 @@
+#![cfg_attr(not(windows), allow(dead_code))]
+
+use crate::types::Event;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+// Outbound TCP connections from the system TCP tables (`GetExtendedTcpTable`), the NetConnect
+// source when Sysmon is unavailable. Every poll reads the IPv4 and IPv6 tables with owning PIDs
+// and reports connections that weren't there on the previous poll. Connections opened and closed
+// between two polls are missed, and there is no DNS name: `dest_host` is always empty.
+//
+// The first poll only records what is already connected; it reports nothing.
+
+/// `MIB_TCP_STATE_SYN_SENT` and `MIB_TCP_STATE_ESTAB`.
+const STATE_SYN_SENT: u32 = 3;
+const STATE_ESTABLISHED: u32 = 5;
+
+/// One outbound connection: owning PID and remote endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Connection {
+  pid: u32,
+  ip: IpAddr,
+  port: u16,
+}
+
+/// Parses a `MIB_TCPTABLE_OWNER_PID` (`v6 = false`) or `MIB_TCP6TABLE_OWNER_PID` buffer into the
+/// connections being opened or established to a remote host.
+fn parse_table(buf: &[u8], v6: bool) -> Vec<Connection> {
+  let u32_at = |o: usize| {
+    buf
+      .get(o..o + 4)
+      .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+  };
+  // Ports are the first two bytes of their DWORD, in network byte order.
+  let port_at = |o: usize| buf.get(o..o + 2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+  let (row_len, remote_addr, remote_port, state, pid) = if v6 {
+    (56, 24, 44, 48, 52)
+  } else {
+    (24, 12, 16, 0, 20)
+  };
+
+  let count = u32_at(0).unwrap_or(0) as usize;
+  let mut out = Vec::new();
+  for i in 0..count {
+    let row = 4 + i * row_len;
+    let Some(bytes) = buf.get(row..row + row_len) else {
+      break;
+    };
+    let ip = if v6 {
+      let octets: [u8; 16] = bytes[remote_addr..remote_addr + 16]
+        .try_into()
+        .unwrap_or_default();
+      IpAddr::V6(Ipv6Addr::from(octets))
+    } else {
+      let o = &bytes[remote_addr..remote_addr + 4];
+      IpAddr::V4(Ipv4Addr::new(o[0], o[1], o[2], o[3]))
+    };
+    let (Some(state), Some(pid), Some(port)) = (
+      u32_at(row + state),
+      u32_at(row + pid),
+      port_at(row + remote_port),
+    ) else {
+      break;
+    };
+    if !matches!(state, STATE_SYN_SENT | STATE_ESTABLISHED)
+      || pid == 0
+      || ip.is_unspecified()
+      || ip.is_loopback()
+    {
+      continue;
+    }
+    out.push(Connection { pid, ip, port });
+  }
+  out
+}
+
+/// Connections in `current` that aren't in `previous`.
+fn new_connections(previous: &HashSet<Connection>, current: &[Connection]) -> Vec<Connection> {
+  let mut seen = HashSet::new();
+  current
+    .iter()
+    .filter(|c| !previous.contains(c) && seen.insert(**c))
+    .copied()
+    .collect()
+}
+
+/// Whether the TCP tables can be read.
+pub fn available() -> bool {
+  #[cfg(windows)]
+  {
+    imp::read_table(false).is_some()
+  }
+
+  #[cfg(not(windows))]
+  {
+    false
+  }
+}
+
+/// Connections opened since the last call.
+pub fn poll() -> Vec<Event> {
+  #[cfg(windows)]
+  {
+    imp::poll()
+  }
+
+  #[cfg(not(windows))]
+  {
+    Vec::new()
+  }
+}
+
+#[cfg(windows)]
+mod imp {
+  use super::*;
+  use crate::types::now_unix_ms;
+  use std::collections::HashMap;
+  use std::sync::Mutex;
+  use windows::Win32::Foundation::NO_ERROR;
+  use windows::Win32::NetworkManagement::IpHelper::{GetExtendedTcpTable, TCP_TABLE_OWNER_PID_ALL};
+
+  /// `AF_INET` and `AF_INET6`.
+  const AF_INET: u32 = 2;
+  const AF_INET6: u32 = 23;
+
+  struct State {
+    connections: HashSet<Connection>,
+    /// Image paths of PIDs with open connections.
+    images: HashMap<u32, Option<String>>,
+  }
+
+  static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+  pub(super) fn read_table(v6: bool) -> Option<Vec<Connection>> {
+    let family = if v6 { AF_INET6 } else { AF_INET };
+    let mut size = 0u32;
+    // The table can grow between the size query and the read; retry a few times.
+    for _ in 0..4 {
+      let mut buf = vec![0u8; size as usize];
+      // SAFETY: `buf` is valid for `size` bytes (no buffer on the first, size-only call).
+      let res = unsafe {
+        GetExtendedTcpTable(
+          (size > 0).then_some(buf.as_mut_ptr() as *mut _),
+          &mut size,
+          false,
+          family,
+          TCP_TABLE_OWNER_PID_ALL,
+          0,
+        )
+      };
+      if res == NO_ERROR.0 && !buf.is_empty() {
+        return Some(parse_table(&buf, v6));
+      }
+      if size == 0 {
+        return None;
+      }
+    }
+    None
+  }
+
+  pub(super) fn poll() -> Vec<Event> {
+    let mut current = read_table(false).unwrap_or_default();
+    current.extend(read_table(true).unwrap_or_default());
+
+    let mut guard = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(state) = guard.as_mut() else {
+      *guard = Some(State {
+        connections: current.into_iter().collect(),
+        images: HashMap::new(),
+      });
+      return Vec::new();
+    };
+
+    let now = now_unix_ms();
+    let events = new_connections(&state.connections, &current)
+      .into_iter()
+      .map(|c| Event::NetConnect {
+        pid: c.pid,
+        image_path: state
+          .images
+          .entry(c.pid)
+          .or_insert_with(|| super::super::image_path(c.pid))
+          .clone(),
+        dest_ip: c.ip,
+        dest_port: c.port,
+        dest_host: None,
+        protocol: "tcp".to_string(),
+        process_guid: None,
+        timestamp_unix_ms: now,
+      })
+      .collect();
+
+    state.connections = current.into_iter().collect();
+    let pids: HashSet<u32> = state.connections.iter().map(|c| c.pid).collect();
+    state.images.retain(|pid, _| pids.contains(pid));
+    events
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_outbound_rows_and_reports_new_connections() {
+    let v4_row = |state: u32, remote: [u8; 4], port: u16, pid: u32| {
+      let mut r = state.to_le_bytes().to_vec();
+      r.extend([10, 0, 0, 5]);
+      r.extend([0xc3, 0x50, 0, 0]); // local port 50000
+      r.extend(remote);
+      r.extend(port.to_be_bytes());
+      r.extend([0, 0]);
+      r.extend(pid.to_le_bytes());
+      r
+    };
+    let mut v4 = 4u32.to_le_bytes().to_vec();
+    v4.extend(v4_row(STATE_ESTABLISHED, [1, 2, 3, 4], 443, 100));
+    v4.extend(v4_row(2, [0, 0, 0, 0], 0, 200)); // listening
+    v4.extend(v4_row(STATE_ESTABLISHED, [127, 0, 0, 1], 8080, 300));
+    v4.extend(v4_row(STATE_SYN_SENT, [5, 6, 7, 8], 80, 100));
+    let parsed = parse_table(&v4, false);
+    let conn = |pid, ip: [u8; 4], port| Connection {
+      pid,
+      ip: IpAddr::from(ip),
+      port,
+    };
+    assert_eq!(
+      parsed,
+      [conn(100, [1, 2, 3, 4], 443), conn(100, [5, 6, 7, 8], 80)]
+    );
+
+    let mut v6 = 1u32.to_le_bytes().to_vec();
+    v6.extend([0u8; 24]);
+    let remote: Ipv6Addr = "2606:4700::1111".parse().unwrap();
+    v6.extend(remote.octets());
+    v6.extend(0u32.to_le_bytes());
+    v6.extend([0x01, 0xbb, 0, 0]);
+    v6.extend(STATE_ESTABLISHED.to_le_bytes());
+    v6.extend(7u32.to_le_bytes());
+    assert_eq!(
+      parse_table(&v6, true),
+      [Connection {
+        pid: 7,
+        ip: IpAddr::V6(remote),
+        port: 443
+      }]
+    );
+
+    let previous: HashSet<Connection> = [conn(100, [1, 2, 3, 4], 443)].into_iter().collect();
+    assert_eq!(
+      new_connections(&previous, &parsed),
+      [conn(100, [5, 6, 7, 8], 80)]
+    );
+  }
+}
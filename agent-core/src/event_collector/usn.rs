@@ -0,0 +1,367 @@
+#![cfg_attr(not(windows), allow(dead_code))]
+
+use crate::types::{Event, FileAccessType};
+
+// NTFS change journal (USN journal) of the system volume, the file event source when Sysmon isn't
+// available. The journal records every write, rename and delete on the volume with the file name
+// and its parent directory, but not the process that made the change, so events carry
+// `UNKNOWN_PID` and no image. Only records closing a file are read (one per open/close, with the
+// reasons accumulated), and only changes under a location a file rule looks at are kept (see
+// `protected_paths::is_watched_location`).
+//
+// The first read starts at the end of the journal; history from before the agent started is not
+// replayed. Reading needs an administrator or LocalSystem token to open the volume.
+
+/// Record reasons that count as a change of the file.
+const WRITE_REASONS: u32 = 0x0000_0001 // USN_REASON_DATA_OVERWRITE
+  | 0x0000_0002 // USN_REASON_DATA_EXTEND
+  | 0x0000_0004 // USN_REASON_DATA_TRUNCATION
+  | 0x0000_0100 // USN_REASON_FILE_CREATE
+  | 0x0000_2000; // USN_REASON_RENAME_NEW_NAME
+const DELETE_REASON: u32 = 0x0000_0200; // USN_REASON_FILE_DELETE
+const CLOSE_REASON: u32 = 0x8000_0000; // USN_REASON_CLOSE
+const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+
+/// One `USN_RECORD_V2` of a file (not a directory) change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UsnRecord {
+  parent_id: u64,
+  name: String,
+  reason: u32,
+  timestamp_unix_ms: u64,
+}
+
+impl UsnRecord {
+  fn access(&self) -> Option<FileAccessType> {
+    if self.reason & DELETE_REASON != 0 {
+      Some(FileAccessType::Delete)
+    } else if self.reason & WRITE_REASONS != 0 {
+      Some(FileAccessType::Write)
+    } else {
+      None
+    }
+  }
+}
+
+/// Parses a `FSCTL_READ_USN_JOURNAL` output buffer: the USN to continue from, then `USN_RECORD_V2`
+/// records. Other record versions and directory records are skipped.
+fn parse_records(buf: &[u8]) -> Option<(i64, Vec<UsnRecord>)> {
+  let u16_at = |o: usize| Some(u16::from_le_bytes(buf.get(o..o + 2)?.try_into().ok()?));
+  let u32_at = |o: usize| Some(u32::from_le_bytes(buf.get(o..o + 4)?.try_into().ok()?));
+  let u64_at = |o: usize| Some(u64::from_le_bytes(buf.get(o..o + 8)?.try_into().ok()?));
+
+  let next_usn = u64_at(0)? as i64;
+  let mut out = Vec::new();
+  let mut off = 8;
+  while off + 60 <= buf.len() {
+    let len = u32_at(off)? as usize;
+    if len < 60 || off + len > buf.len() {
+      break;
+    }
+    let attributes = u32_at(off + 52)?;
+    if u16_at(off + 4)? == 2 && attributes & FILE_ATTRIBUTE_DIRECTORY == 0 {
+      let name_len = u16_at(off + 56)? as usize;
+      let name_off = off + u16_at(off + 58)? as usize;
+      let name: Vec<u16> = buf
+        .get(name_off..name_off + name_len)?
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+      out.push(UsnRecord {
+        parent_id: u64_at(off + 16)?,
+        name: String::from_utf16_lossy(&name),
+        reason: u32_at(off + 40)?,
+        timestamp_unix_ms: filetime_to_unix_ms(u64_at(off + 32)?),
+      });
+    }
+    off += len;
+  }
+  Some((next_usn, out))
+}
+
+fn filetime_to_unix_ms(filetime: u64) -> u64 {
+  (filetime / 10_000).saturating_sub(11_644_473_600_000)
+}
+
+/// Whether the system volume's journal can be read.
+pub fn available() -> bool {
+  #[cfg(windows)]
+  {
+    imp::Volume::open().and_then(|v| v.query()).is_ok()
+  }
+
+  #[cfg(not(windows))]
+  {
+    false
+  }
+}
+
+/// File changes recorded since the last call.
+pub fn read() -> anyhow::Result<Vec<Event>> {
+  #[cfg(windows)]
+  {
+    imp::read()
+  }
+
+  #[cfg(not(windows))]
+  {
+    Ok(Vec::new())
+  }
+}
+
+#[cfg(windows)]
+mod imp {
+  use super::*;
+  use crate::rules_engine::protected_paths;
+  use crate::types::UNKNOWN_PID;
+  use std::collections::HashMap;
+  use std::sync::Mutex;
+  use windows::core::HSTRING;
+  use windows::Win32::Foundation::{CloseHandle, HANDLE};
+  use windows::Win32::Storage::FileSystem::{
+    CreateFileW, FileIdType, GetFinalPathNameByHandleW, OpenFileById, FILE_FLAGS_AND_ATTRIBUTES,
+    FILE_FLAG_BACKUP_SEMANTICS, FILE_ID_DESCRIPTOR, FILE_ID_DESCRIPTOR_0, FILE_NAME_NORMALIZED,
+    FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+  };
+  use windows::Win32::System::Ioctl::{
+    FSCTL_QUERY_USN_JOURNAL, FSCTL_READ_USN_JOURNAL, READ_USN_JOURNAL_DATA_V0, USN_JOURNAL_DATA_V0,
+  };
+  use windows::Win32::System::IO::DeviceIoControl;
+
+  const BUFFER_SIZE: usize = 64 * 1024;
+  /// Buffers read per poll; the rest is picked up by the next one.
+  const MAX_READS: usize = 32;
+  /// Directory paths kept by file reference number; the cache is dropped when full.
+  const MAX_CACHED_DIRS: usize = 4096;
+
+  static STATE: Mutex<Option<Journal>> = Mutex::new(None);
+
+  struct Journal {
+    volume: Volume,
+    journal_id: u64,
+    next_usn: i64,
+    dirs: HashMap<u64, Option<String>>,
+  }
+
+  pub(super) struct Volume(HANDLE);
+
+  impl Drop for Volume {
+    fn drop(&mut self) {
+      // SAFETY: the handle was opened by `Volume::open` and is closed once.
+      let _ = unsafe { CloseHandle(self.0) };
+    }
+  }
+
+  impl Volume {
+    pub(super) fn open() -> anyhow::Result<Self> {
+      let drive = std::env::var("SystemDrive").unwrap_or_else(|_| "C:".to_string());
+      // SAFETY: plain open of the volume device; the handle is owned by the returned `Volume`.
+      let handle = unsafe {
+        CreateFileW(
+          &HSTRING::from(format!("\\\\.\\{drive}")),
+          0x8000_0000, // GENERIC_READ
+          FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+          None,
+          OPEN_EXISTING,
+          FILE_FLAGS_AND_ATTRIBUTES(0),
+          None,
+        )
+      }
+      .map_err(|e| anyhow::anyhow!("failed to open volume {drive}: {e}"))?;
+      Ok(Self(handle))
+    }
+
+    pub(super) fn query(&self) -> anyhow::Result<USN_JOURNAL_DATA_V0> {
+      let mut data = USN_JOURNAL_DATA_V0::default();
+      let mut returned = 0u32;
+      // SAFETY: `data` is a valid output buffer of the given size for the duration of the call.
+      unsafe {
+        DeviceIoControl(
+          self.0,
+          FSCTL_QUERY_USN_JOURNAL,
+          None,
+          0,
+          Some(&mut data as *mut _ as *mut _),
+          std::mem::size_of::<USN_JOURNAL_DATA_V0>() as u32,
+          Some(&mut returned),
+          None,
+        )
+      }
+      .map_err(|e| anyhow::anyhow!("USN journal not available: {e}"))?;
+      Ok(data)
+    }
+
+    /// Directory path of a file reference number, if the directory still exists.
+    fn dir_path(&self, id: u64) -> Option<String> {
+      let desc = FILE_ID_DESCRIPTOR {
+        dwSize: std::mem::size_of::<FILE_ID_DESCRIPTOR>() as u32,
+        Type: FileIdType,
+        Anonymous: FILE_ID_DESCRIPTOR_0 { FileId: id as i64 },
+      };
+      // SAFETY: `desc` outlives the call; the returned handle is closed below.
+      let handle = unsafe {
+        OpenFileById(
+          self.0,
+          &desc,
+          0,
+          FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+          None,
+          FILE_FLAG_BACKUP_SEMANTICS,
+        )
+      }
+      .ok()?;
+      let mut buf = vec![0u16; 1024];
+      // SAFETY: `buf` is a valid output buffer; the handle is valid until closed.
+      let len = unsafe {
+        let len = GetFinalPathNameByHandleW(handle, &mut buf, FILE_NAME_NORMALIZED);
+        let _ = CloseHandle(handle);
+        len as usize
+      };
+      if len == 0 || len > buf.len() {
+        return None;
+      }
+      let path = String::from_utf16_lossy(&buf[..len]);
+      Some(path.strip_prefix("\\\\?\\").unwrap_or(&path).to_string())
+    }
+  }
+
+  pub(super) fn read() -> anyhow::Result<Vec<Event>> {
+    let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    if state.is_none() {
+      let volume = Volume::open()?;
+      let data = volume.query()?;
+      tracing::info!("reading file changes from the USN journal (Sysmon unavailable)");
+      *state = Some(Journal {
+        volume,
+        journal_id: data.UsnJournalID,
+        next_usn: data.NextUsn,
+        dirs: HashMap::new(),
+      });
+    }
+    let Some(journal) = state.as_mut() else {
+      return Ok(Vec::new());
+    };
+
+    let mut events = Vec::new();
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    for _ in 0..MAX_READS {
+      let request = READ_USN_JOURNAL_DATA_V0 {
+        StartUsn: journal.next_usn,
+        ReasonMask: WRITE_REASONS | DELETE_REASON | CLOSE_REASON,
+        ReturnOnlyOnClose: 1,
+        Timeout: 0,
+        BytesToWaitFor: 0,
+        UsnJournalID: journal.journal_id,
+      };
+      let mut returned = 0u32;
+      // SAFETY: `request` and `buf` are valid for their sizes for the duration of the call.
+      let res = unsafe {
+        DeviceIoControl(
+          journal.volume.0,
+          FSCTL_READ_USN_JOURNAL,
+          Some(&request as *const _ as *const _),
+          std::mem::size_of::<READ_USN_JOURNAL_DATA_V0>() as u32,
+          Some(buf.as_mut_ptr() as *mut _),
+          buf.len() as u32,
+          Some(&mut returned),
+          None,
+        )
+      };
+      if let Err(e) = res {
+        // The journal wrapped past our position or was recreated: continue from its end.
+        let data = journal.volume.query()?;
+        tracing::warn!(error = %e, "USN journal position lost; skipping to its end");
+        journal.journal_id = data.UsnJournalID;
+        journal.next_usn = data.NextUsn;
+        break;
+      }
+      let Some((next_usn, records)) = parse_records(&buf[..returned as usize]) else {
+        break;
+      };
+      journal.next_usn = next_usn;
+      for r in records {
+        let Some(access) = r.access() else {
+          continue;
+        };
+        if journal.dirs.len() >= MAX_CACHED_DIRS {
+          journal.dirs.clear();
+        }
+        let volume = &journal.volume;
+        let Some(dir) = journal
+          .dirs
+          .entry(r.parent_id)
+          .or_insert_with(|| volume.dir_path(r.parent_id))
+          .clone()
+        else {
+          continue;
+        };
+        let file_path = format!("{dir}\\{}", r.name);
+        if !protected_paths::is_watched_location(&file_path) {
+          continue;
+        }
+        events.push(Event::FileAccess {
+          pid: UNKNOWN_PID,
+          image_path: None,
+          file_path,
+          access,
+          process_guid: None,
+          timestamp_unix_ms: r.timestamp_unix_ms,
+        });
+      }
+      // Only the next USN came back: caught up.
+      if (returned as usize) < 8 + 60 {
+        break;
+      }
+    }
+    Ok(events)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn record(parent: u64, name: &str, reason: u32, attributes: u32) -> Vec<u8> {
+    let name: Vec<u8> = name.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    let len = (60 + name.len() + 7) & !7;
+    let mut r = vec![0u8; len];
+    r[0..4].copy_from_slice(&(len as u32).to_le_bytes());
+    r[4..6].copy_from_slice(&2u16.to_le_bytes());
+    r[16..24].copy_from_slice(&parent.to_le_bytes());
+    // 2023-11-14T22:13:20Z
+    r[32..40].copy_from_slice(&133_444_736_000_000_000u64.to_le_bytes());
+    r[40..44].copy_from_slice(&reason.to_le_bytes());
+    r[52..56].copy_from_slice(&attributes.to_le_bytes());
+    r[56..58].copy_from_slice(&(name.len() as u16).to_le_bytes());
+    r[58..60].copy_from_slice(&60u16.to_le_bytes());
+    r[60..60 + name.len()].copy_from_slice(&name);
+    r
+  }
+
+  #[test]
+  fn parses_file_records_and_maps_reasons() {
+    let mut buf = 4096i64.to_le_bytes().to_vec();
+    buf.extend(record(7, "report.docx.locked", 0x8000_2000, 0x20));
+    buf.extend(record(
+      7,
+      "Documents",
+      0x8000_0100,
+      FILE_ATTRIBUTE_DIRECTORY,
+    ));
+    buf.extend(record(9, "Cookies", 0x8000_0202, 0x20));
+    buf.extend(record(9, "Cookies-journal", 0x8000_0000, 0x20));
+
+    let (next, records) = parse_records(&buf).unwrap();
+    assert_eq!(next, 4096);
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].name, "report.docx.locked");
+    assert_eq!(records[0].parent_id, 7);
+    assert_eq!(records[0].timestamp_unix_ms, 1_700_000_000_000);
+    assert_eq!(records[0].access(), Some(FileAccessType::Write));
+    assert_eq!(records[1].access(), Some(FileAccessType::Delete));
+    assert_eq!(records[2].access(), None);
+
+    assert_eq!(parse_records(&4096i64.to_le_bytes()).unwrap().1, []);
+    assert!(parse_records(&[0u8; 4]).is_none());
+  }
+}
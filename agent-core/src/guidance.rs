@@ -0,0 +1,140 @@
+use crate::threat_feed::{self, schema::ThreatFeedBundle};
+use crate::types::{Guidance, Incident};
+
+// Remediation guidance per rule: what a finding means, what usually causes it on a clean machine,
+// and what to do. Built in for the scanner rules (S001-S011); a threat feed bundle can add
+// guidance for any rule, or replace the built-in text, with `rules[].guidance` (see
+// `docs/THREAT_FEED.md`). Incidents record the guidance for their rules when they are stored, so
+// the record still explains itself after the feed changes.
+
+/// `(rule, meaning, false positives, action)`.
+const BUILTIN: &[(&str, &str, &str, &str)] = &[
+  (
+    "S001",
+    "An executable or script has no valid Authenticode signature, so nothing vouches for who made it.",
+    "In-house tools, installers of small vendors, developer builds and portable apps are often unsigned.",
+    "Check that you know where the file came from. If not, quarantine it and scan it with a second engine; if it is expected, allowlist its hash.",
+  ),
+  (
+    "S002",
+    "An executable sits in a folder any user can write to (Temp, AppData, Downloads), where droppers and stealers stage their payloads.",
+    "Per-user installs (browsers, chat and meeting clients, updaters) live in AppData; installers leave files in Temp.",
+    "Match the path against software you installed. Delete or quarantine unknown files, especially ones that were just downloaded or are unsigned (S001).",
+  ),
+  (
+    "S003",
+    "An executable or script in a Startup folder runs every time a user signs in, a common way for malware to persist.",
+    "Some vendor tools place a shortcut or helper there on purpose.",
+    "Remove it if you don't recognize it, then run a full scan: whatever put it there may still be present.",
+  ),
+  (
+    "S004",
+    "A browser extension is force-installed by policy (ExtensionInstallForcelist), so users can't remove it.",
+    "Organizations deploy password managers and security extensions this way through Group Policy or MDM.",
+    "Confirm the extension ID with whoever manages the machine. If nobody set it, delete the policy value and the extension, and change the passwords saved in the browser.",
+  ),
+  (
+    "S005",
+    "A running browser was started with --load-extension, loading unpacked extension code from disk outside the store.",
+    "Extension developers and some automation or testing tools start browsers this way.",
+    "Close the browser and check the loaded folder. If it isn't yours, delete it and look for the shortcut or task that starts the browser with it.",
+  ),
+  (
+    "S006",
+    "Safe Mode was changed to start an unexpected shell or service, which keeps malware running even when booting into Safe Mode to remove it.",
+    "Some backup, recovery and security products register Safe Mode services.",
+    "Verify the service or shell with its vendor. Remove unknown entries under SafeBoot and scan the image they point to.",
+  ),
+  (
+    "S007",
+    "The Winlogon Shell or Userinit value runs something besides Explorer or userinit.exe at every sign-in.",
+    "Kiosk and thin-client setups replace the shell on purpose.",
+    "Restore Shell to explorer.exe and Userinit to userinit.exe unless the machine is a managed kiosk, then scan the program that was added.",
+  ),
+  (
+    "S008",
+    "Image File Execution Options make Windows start another program (a Debugger or a silent-exit monitor) whenever a given program runs.",
+    "Developers and debugging tools (e.g. Process Explorer replacing Task Manager) set Debugger values.",
+    "Check the program it launches. If it isn't a debugger you installed, delete the IFEO value and quarantine the program.",
+  ),
+  (
+    "S009",
+    "An archive in a download or temp folder contains executable content, a common way to deliver malware past mail and browser filters.",
+    "Software downloads, driver packages and backups are archives full of executables.",
+    "Don't open the archive unless you expected it. Delete it if its source is unknown; with [scanner.archives] deep_extract = true, scans check the files inside.",
+  ),
+  (
+    "S010",
+    "A script in a browser profile or user folder matches a known wallet-drainer pattern from the threat feed.",
+    "Rare: legitimate wallet extensions can share code fragments with a pattern.",
+    "Remove the extension or file, move funds from wallets used in that browser to a new wallet, and revoke token approvals.",
+  ),
+  (
+    "S011",
+    "A file's SHA-256 is on the threat feed's list of known-bad files: this exact file has been seen in malware.",
+    "Very rare: a hash listed by mistake, or a dual-use tool the feed lists on purpose.",
+    "Quarantine or delete the file, find out how it got there (download, archive, another program), and run a full scan. If it ran, treat the machine as compromised and change the passwords used on it.",
+  ),
+];
+
+/// Whether `rule_id` is one of the scanner's rules, which feed bundles may give guidance for.
+pub fn is_scanner_rule(rule_id: &str) -> bool {
+  BUILTIN
+    .iter()
+    .any(|(id, ..)| id.eq_ignore_ascii_case(rule_id.trim()))
+}
+
+/// The guidance for `rule_id`: the feed's, else the built-in text.
+pub fn for_rule(rule_id: &str, feed: Option<&ThreatFeedBundle>) -> Option<Guidance> {
+  let from_feed = feed.and_then(|b| {
+    b.rules
+      .iter()
+      .filter(|r| r.rule_id.eq_ignore_ascii_case(rule_id))
+      .find_map(|r| r.guidance.clone())
+  });
+  from_feed.or_else(|| {
+    BUILTIN
+      .iter()
+      .find(|(id, ..)| id.eq_ignore_ascii_case(rule_id))
+      .map(|(_, meaning, false_positives, action)| Guidance {
+        meaning: meaning.to_string(),
+        false_positives: false_positives.to_string(),
+        action: action.to_string(),
+      })
+  })
+}
+
+/// Records the guidance for the incident's rules, keeping what it already has.
+pub fn attach(incident: &mut Incident) {
+  let feed = threat_feed::load_current();
+  for f in &incident.findings {
+    if incident.guidance.contains_key(&f.rule_id) {
+      continue;
+    }
+    if let Some(g) = for_rule(&f.rule_id, feed.as_ref()) {
+      incident.guidance.insert(f.rule_id.clone(), g);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn feed_guidance_replaces_the_builtin_text() {
+    assert!(for_rule("s001", None)
+      .unwrap()
+      .meaning
+      .contains("Authenticode"));
+    assert!(for_rule("R001", None).is_none());
+
+    let bundle: ThreatFeedBundle = serde_json::from_str(
+      r#"{"version":1,"bundle_id":"b","created_at":1,"rules_version":1,"reputation":{},
+        "rules":[{"rule_id":"R001","guidance":{"meaning":"m","false_positives":"f","action":"a"}}]}"#,
+    )
+    .unwrap();
+    assert_eq!(for_rule("R001", Some(&bundle)).unwrap().action, "a");
+    assert!(for_rule("S001", Some(&bundle)).is_some());
+  }
+}
@@ -0,0 +1,195 @@
+use crate::config::Mode;
+use crate::event_collector::CollectorLag;
+use crate::license;
+use crate::paths;
+use crate::runtime;
+use crate::threat_feed::schema::ThreatFeedBundle;
+use crate::types::now_unix_ms;
+use defender_common::fs::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Liveness record for `--status`: the agent loop updates it every tick and writes it out every few
+// seconds, so a heartbeat much older than that means the agent is stopped, hung, or stuck in a
+// slow tick. Only counters and status labels are kept, never event contents.
+
+/// Minimum time between heartbeat writes; ticks in between only update the in-memory copy.
+const WRITE_INTERVAL_MS: u64 = 5_000;
+
+/// A heartbeat older than this is reported as stale.
+pub const STALE_AFTER_MS: u64 = 60_000;
+
+/// How often the license is re-checked for the heartbeat.
+const LICENSE_REFRESH_MS: u64 = 10 * 60_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Heartbeat {
+  pub pid: u32,
+  pub agent_version: String,
+  pub mode: Mode,
+  pub worker: bool,
+  pub started_at_unix_ms: u64,
+  pub updated_at_unix_ms: u64,
+  pub ticks: u64,
+  /// Events in the most recent non-empty batch, and when it was collected.
+  pub last_batch_events: usize,
+  #[serde(default)]
+  pub last_batch_at_unix_ms: Option<u64>,
+  pub events_total: u64,
+  /// Event log sources that could not be read on the last tick (empty when collection is healthy).
+  #[serde(default)]
+  pub collector_errors: Vec<String>,
+  /// Event log records not yet read and events waiting in the collector queue, as of the last
+  /// tick; dropped batches and events count since the agent started.
+  #[serde(default)]
+  pub records_behind: u64,
+  #[serde(default)]
+  pub queued_events: u64,
+  #[serde(default)]
+  pub dropped_batches: u64,
+  #[serde(default)]
+  pub dropped_events: u64,
+  #[serde(default)]
+  pub feed_version: Option<u64>,
+  pub license: String,
+}
+
+impl Heartbeat {
+  pub fn uptime_seconds(&self) -> u64 {
+    self
+      .updated_at_unix_ms
+      .saturating_sub(self.started_at_unix_ms)
+      / 1000
+  }
+
+  pub fn is_stale(&self, now_unix_ms: u64) -> bool {
+    now_unix_ms.saturating_sub(self.updated_at_unix_ms) > STALE_AFTER_MS
+  }
+}
+
+pub struct HeartbeatWriter {
+  path: PathBuf,
+  base: PathBuf,
+  beat: Heartbeat,
+  last_write_unix_ms: u64,
+  license_checked_unix_ms: u64,
+}
+
+impl HeartbeatWriter {
+  pub fn new(base: &Path, mode: Mode) -> Self {
+    let now = now_unix_ms();
+    Self {
+      path: paths::heartbeat_path(base),
+      base: base.to_path_buf(),
+      beat: Heartbeat {
+        pid: std::process::id(),
+        agent_version: env!("CARGO_PKG_VERSION").to_string(),
+        mode,
+        worker: runtime::is_worker(),
+        started_at_unix_ms: now,
+        updated_at_unix_ms: now,
+        ticks: 0,
+        last_batch_events: 0,
+        last_batch_at_unix_ms: None,
+        events_total: 0,
+        collector_errors: Vec::new(),
+        records_behind: 0,
+        queued_events: 0,
+        dropped_batches: 0,
+        dropped_events: 0,
+        feed_version: None,
+        license: license::status(base).state.as_str().to_string(),
+      },
+      last_write_unix_ms: 0,
+      license_checked_unix_ms: now,
+    }
+  }
+
+  pub fn set_feed(&mut self, feed: Option<&ThreatFeedBundle>) {
+    self.beat.feed_version = feed.map(|b| b.rules_version);
+  }
+
+  /// Records one loop tick that collected `events` events.
+  pub fn tick(&mut self, events: usize, collector_errors: Vec<String>, lag: CollectorLag) {
+    let now = now_unix_ms();
+    let beat = &mut self.beat;
+    beat.updated_at_unix_ms = now;
+    beat.ticks += 1;
+    beat.events_total += events as u64;
+    beat.collector_errors = collector_errors;
+    beat.records_behind = lag.records_behind;
+    beat.queued_events = lag.queued_events;
+    beat.dropped_batches = lag.dropped_batches;
+    beat.dropped_events = lag.dropped_events;
+    if events > 0 {
+      beat.last_batch_events = events;
+      beat.last_batch_at_unix_ms = Some(now);
+    }
+    if now.saturating_sub(self.license_checked_unix_ms) >= LICENSE_REFRESH_MS {
+      beat.license = license::status(&self.base).state.as_str().to_string();
+      self.license_checked_unix_ms = now;
+    }
+    if now.saturating_sub(self.last_write_unix_ms) >= WRITE_INTERVAL_MS {
+      self.write(now);
+    }
+  }
+
+  fn write(&mut self, now: u64) {
+    self.last_write_unix_ms = now;
+    if runtime::is_dry_run() {
+      return;
+    }
+    let res = toml::to_string(&self.beat)
+      .map_err(anyhow::Error::from)
+      .and_then(|raw| write_atomic(&self.path, &raw));
+    if let Err(e) = res {
+      tracing::warn!(error = ?e, "failed to write heartbeat (continuing)");
+    }
+  }
+}
+
+pub fn load(base: &Path) -> Option<Heartbeat> {
+  let raw = fs::read_to_string(paths::heartbeat_path(base)).ok()?;
+  toml::from_str(&raw).ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn heartbeat_round_trips_and_goes_stale() {
+    let base = std::env::temp_dir().join(format!("aid-heartbeat-{}", uuid::Uuid::new_v4()));
+    let mut writer = HeartbeatWriter::new(&base, Mode::Learning);
+    writer.tick(0, Vec::new(), CollectorLag::default());
+    writer.tick(
+      12,
+      vec!["PowerShell: access denied".to_string()],
+      CollectorLag {
+        records_behind: 3_000,
+        queued_events: 40,
+        dropped_batches: 1,
+        dropped_events: 7,
+      },
+    );
+
+    // The second tick falls inside the write interval, so the file holds the first one.
+    let beat = load(&base).unwrap();
+    assert_eq!(beat.ticks, 1);
+    assert_eq!(beat.last_batch_at_unix_ms, None);
+
+    writer.write(now_unix_ms());
+    let beat = load(&base).unwrap();
+    assert_eq!(beat.ticks, 2);
+    assert_eq!(beat.last_batch_events, 12);
+    assert_eq!(beat.collector_errors.len(), 1);
+    assert_eq!((beat.records_behind, beat.queued_events), (3_000, 40));
+    assert_eq!((beat.dropped_batches, beat.dropped_events), (1, 7));
+    assert_eq!(beat.license, "community");
+    assert!(!beat.is_stale(beat.updated_at_unix_ms + STALE_AFTER_MS));
+    assert!(beat.is_stale(beat.updated_at_unix_ms + STALE_AFTER_MS + 1));
+
+    let _ = fs::remove_dir_all(&base);
+  }
+}
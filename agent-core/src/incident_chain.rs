@@ -0,0 +1,343 @@
+use crate::config::SelfProtectionConfig;
+use crate::paths;
+use crate::runtime;
+use crate::types::now_unix_ms;
+use anyhow::Context;
+use defender_common::fs::write_atomic;
+use defender_common::hash::{hex, hmac_sha256, sha256_bytes_hex};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+// Tamper evidence for incident records (`[self_protection] chain_incidents`). Incident files are
+// plain TOML that malware could edit or delete to cover its tracks, so every write of one appends
+// a record to `incident-chain.ndjson`: the file's SHA-256, the SHA-256 of the previous record
+// line, and an HMAC-SHA256 over both under a random machine-local key (`incident-chain.key`).
+// Both live in the data directory root, which only SYSTEM and Administrators can write, and the
+// key is readable by them alone. `--incidents verify` replays the chain and reports altered,
+// missing or reordered records and incident files that were modified, deleted or never recorded.
+//
+// Scans run as the signed-in user can't read the key, so their incidents stay unrecorded until
+// the agent updates them. This makes tampering evident, not impossible: code running as an
+// administrator can read the key and rewrite the whole chain, and removing the newest records is
+// only noticed through the incident files they covered.
+
+/// `prev` of the first record.
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+/// Serializes appends, so two writers can't both extend the same head.
+static APPEND: Mutex<()> = Mutex::new(());
+
+/// Applies `[self_protection]`; until called, chaining is on.
+pub fn configure(cfg: &SelfProtectionConfig) {
+  ENABLED.store(cfg.enabled && cfg.chain_incidents, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Record {
+  seq: u64,
+  incident_id: String,
+  file_sha256: String,
+  timestamp_unix_ms: u64,
+  /// SHA-256 of the previous record's line.
+  prev: String,
+  /// HMAC-SHA256 of the fields above (see `signed_text`).
+  mac: String,
+}
+
+impl Record {
+  fn signed_text(&self) -> String {
+    format!(
+      "{}|{}|{}|{}|{}",
+      self.seq, self.incident_id, self.file_sha256, self.timestamp_unix_ms, self.prev
+    )
+  }
+}
+
+/// Creates the chain key if chaining is on, before `self_protection::harden` restricts it.
+pub fn prepare(base: &Path) {
+  if !ENABLED.load(Ordering::Relaxed) || runtime::is_dry_run() {
+    return;
+  }
+  if let Err(e) = load_or_create_key(base) {
+    tracing::warn!(error = ?e, "failed to set up the incident chain key (continuing)");
+  }
+}
+
+/// Records that `incident_id` was written with `contents`. Best effort: failures are logged.
+pub fn record(base: &Path, incident_id: &str, contents: &[u8]) {
+  if !ENABLED.load(Ordering::Relaxed) {
+    return;
+  }
+  match append(base, incident_id, contents) {
+    Ok(()) => {}
+    Err(e)
+      if e
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == std::io::ErrorKind::PermissionDenied) =>
+    {
+      tracing::debug!(
+        incident_id,
+        "incident not chained: no access to the chain (not elevated)"
+      );
+    }
+    Err(e) => {
+      tracing::warn!(incident_id, error = ?e, "failed to chain incident record (continuing)")
+    }
+  }
+}
+
+fn append(base: &Path, incident_id: &str, contents: &[u8]) -> anyhow::Result<()> {
+  let _guard = APPEND.lock().unwrap_or_else(|e| e.into_inner());
+  let key = load_or_create_key(base)?;
+  let path = paths::incident_chain_path(base);
+  let existing = fs::read_to_string(&path).unwrap_or_default();
+  let (seq, prev) = match existing.lines().rev().find(|l| !l.trim().is_empty()) {
+    Some(last) => {
+      let last_seq = serde_json::from_str::<Record>(last)
+        .map(|r| r.seq)
+        .context("parse the last incident chain record")?;
+      (last_seq + 1, sha256_bytes_hex(last.as_bytes()))
+    }
+    None => (1, GENESIS.to_string()),
+  };
+
+  let mut rec = Record {
+    seq,
+    incident_id: incident_id.to_string(),
+    file_sha256: sha256_bytes_hex(contents),
+    timestamp_unix_ms: now_unix_ms(),
+    prev,
+    mac: String::new(),
+  };
+  rec.mac = hex(&hmac_sha256(&key, rec.signed_text().as_bytes()));
+
+  let mut file = fs::OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(&path)
+    .with_context(|| format!("open {}", path.display()))?;
+  writeln!(file, "{}", serde_json::to_string(&rec)?)
+    .with_context(|| format!("append to {}", path.display()))
+}
+
+fn load_or_create_key(base: &Path) -> anyhow::Result<Vec<u8>> {
+  let path = paths::incident_chain_key_path(base);
+  match fs::read_to_string(&path) {
+    Ok(text) => {
+      return parse_key(&text)
+        .ok_or_else(|| anyhow::anyhow!("{} is not a valid key; not replaced", path.display()))
+    }
+    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+    Err(_) => {}
+  }
+  let mut key = [0u8; 32];
+  getrandom::getrandom(&mut key).map_err(|e| anyhow::anyhow!("generate chain key: {e}"))?;
+  write_atomic(&path, hex(&key))?;
+  Ok(key.to_vec())
+}
+
+fn read_key(base: &Path) -> Option<Vec<u8>> {
+  parse_key(&fs::read_to_string(paths::incident_chain_key_path(base)).ok()?)
+}
+
+fn parse_key(text: &str) -> Option<Vec<u8>> {
+  let text = text.trim();
+  (text.len() == 64 && text.bytes().all(|b| b.is_ascii_hexdigit())).then(|| {
+    (0..32)
+      .map(|i| u8::from_str_radix(&text[2 * i..2 * i + 2], 16).unwrap_or_default())
+      .collect()
+  })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Problem {
+  /// `altered_record`, `missing_records`, `broken_link`, `unreadable_record`, `modified`,
+  /// `deleted` or `missing_key`.
+  pub kind: &'static str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub seq: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub incident_id: Option<String>,
+  pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+  pub records: u64,
+  pub incidents_checked: usize,
+  pub problems: Vec<Problem>,
+  /// Incident files with no chain record: written before chaining was on, or not by the agent.
+  pub unrecorded: Vec<String>,
+}
+
+/// Replays the chain and compares it with the incident files.
+pub fn verify(base: &Path) -> Report {
+  let mut report = Report::default();
+  let chain = fs::read_to_string(paths::incident_chain_path(base)).unwrap_or_default();
+  let key = read_key(base);
+  let problem = |kind, seq, incident_id: Option<&str>, detail: String| Problem {
+    kind,
+    seq,
+    incident_id: incident_id.map(str::to_string),
+    detail,
+  };
+
+  if key.is_none() && !chain.trim().is_empty() {
+    report.problems.push(problem(
+      "missing_key",
+      None,
+      None,
+      "the chain key is missing or invalid; record MACs can't be checked".to_string(),
+    ));
+  }
+
+  // Latest file hash per incident, as recorded.
+  let mut latest: BTreeMap<String, String> = BTreeMap::new();
+  let mut expected_seq = 1;
+  let mut prev = GENESIS.to_string();
+  for line in chain.lines().filter(|l| !l.trim().is_empty()) {
+    report.records += 1;
+    let Ok(rec) = serde_json::from_str::<Record>(line) else {
+      report.problems.push(problem(
+        "unreadable_record",
+        Some(expected_seq),
+        None,
+        "record can't be parsed".to_string(),
+      ));
+      prev = sha256_bytes_hex(line.as_bytes());
+      expected_seq += 1;
+      continue;
+    };
+    let id = Some(rec.incident_id.as_str());
+    if rec.seq != expected_seq {
+      report.problems.push(problem(
+        "missing_records",
+        Some(rec.seq),
+        id,
+        format!("expected record {expected_seq}, found {}", rec.seq),
+      ));
+    } else if rec.prev != prev {
+      report.problems.push(problem(
+        "broken_link",
+        Some(rec.seq),
+        id,
+        "does not follow the previous record".to_string(),
+      ));
+    }
+    if let Some(key) = &key {
+      if hex(&hmac_sha256(key, rec.signed_text().as_bytes())) != rec.mac {
+        report.problems.push(problem(
+          "altered_record",
+          Some(rec.seq),
+          id,
+          "record MAC doesn't match".to_string(),
+        ));
+      }
+    }
+    expected_seq = rec.seq + 1;
+    prev = sha256_bytes_hex(line.as_bytes());
+    latest.insert(rec.incident_id, rec.file_sha256);
+  }
+
+  let mut on_disk = BTreeSet::new();
+  if let Ok(entries) = fs::read_dir(paths::incidents_dir(base)) {
+    for path in entries.flatten().map(|e| e.path()) {
+      if path.extension().and_then(|e| e.to_str()) != Some("toml") {
+        continue;
+      }
+      let Some(id) = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .map(str::to_string)
+      else {
+        continue;
+      };
+      report.incidents_checked += 1;
+      match (latest.get(&id), fs::read(&path)) {
+        (None, _) => report.unrecorded.push(id.clone()),
+        (Some(hash), Ok(bytes)) if *hash != sha256_bytes_hex(&bytes) => {
+          report.problems.push(problem(
+            "modified",
+            None,
+            Some(&id),
+            "incident file changed since it was last recorded".to_string(),
+          ));
+        }
+        (Some(_), Err(e)) => report.problems.push(problem(
+          "modified",
+          None,
+          Some(&id),
+          format!("incident file can't be read: {e}"),
+        )),
+        _ => {}
+      }
+      on_disk.insert(id);
+    }
+  }
+  for id in latest.keys().filter(|id| !on_disk.contains(*id)) {
+    report.problems.push(problem(
+      "deleted",
+      None,
+      Some(id),
+      "recorded incident file is missing".to_string(),
+    ));
+  }
+  report.unrecorded.sort();
+  report
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detects_modified_deleted_and_removed_records() {
+    let base = std::env::temp_dir().join(format!("aid-chain-{}", uuid::Uuid::new_v4()));
+    let dir = paths::incidents_dir(&base);
+    fs::create_dir_all(&dir).unwrap();
+    let write = |id: &str, body: &str| {
+      fs::write(dir.join(format!("{id}.toml")), body).unwrap();
+      append(&base, id, body.as_bytes()).unwrap();
+    };
+    write("a", "severity = \"Red\"\n");
+    write("b", "severity = \"Yellow\"\n");
+    write("a", "severity = \"Red\"\nresolution = 1\n");
+    fs::write(dir.join("old.toml"), "x").unwrap();
+
+    let clean = verify(&base);
+    assert_eq!(clean.records, 3);
+    assert!(clean.problems.is_empty(), "{:?}", clean.problems);
+    assert_eq!(clean.unrecorded, ["old"]);
+
+    // Downgrading an incident and deleting another.
+    fs::write(dir.join("a.toml"), "severity = \"Green\"\n").unwrap();
+    fs::remove_file(dir.join("b.toml")).unwrap();
+    let kinds: Vec<_> = verify(&base).problems.iter().map(|p| p.kind).collect();
+    assert_eq!(kinds, ["modified", "deleted"]);
+
+    // Dropping the record of `b` from the chain.
+    let chain_path = paths::incident_chain_path(&base);
+    let chain = fs::read_to_string(&chain_path).unwrap();
+    let lines: Vec<&str> = chain.lines().collect();
+    fs::write(&chain_path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+    let report = verify(&base);
+    assert!(report
+      .problems
+      .iter()
+      .any(|p| p.kind == "missing_records" && p.seq == Some(3)));
+
+    // Editing a record breaks its MAC and the next record's link.
+    let tampered = chain.replacen("\"incident_id\":\"b\"", "\"incident_id\":\"c\"", 1);
+    fs::write(&chain_path, tampered).unwrap();
+    let kinds: Vec<_> = verify(&base).problems.iter().map(|p| p.kind).collect();
+    assert!(kinds.contains(&"altered_record") && kinds.contains(&"broken_link"));
+
+    let _ = fs::remove_dir_all(&base);
+  }
+}
@@ -1,8 +1,12 @@
+use crate::guidance;
+use crate::incident_chain;
 use crate::paths;
 use crate::runtime;
-use crate::types::{Incident, Severity};
+use crate::scoring;
+use crate::types::{now_unix_ms, Incident, Resolution, Severity, Verdict};
+use defender_common::fs::write_atomic;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 pub fn store_incident(incident: &Incident) -> anyhow::Result<PathBuf> {
   let base = paths::base_dir()?;
@@ -22,18 +26,91 @@ pub fn store_incident(incident: &Incident) -> anyhow::Result<PathBuf> {
 
   let raw = toml::to_string_pretty(incident)?;
   write_atomic(&file_path, &raw)?;
+  incident_chain::record(&base, &incident.incident_id, raw.as_bytes());
   Ok(file_path)
 }
 
+/// Appends a follow-up action (e.g. a user's answer to a containment prompt) to a stored incident.
+pub fn record_action(incident_id: &str, action: &str) -> anyhow::Result<()> {
+  update_incident(incident_id, |incident| {
+    incident.actions_taken.push(action.to_string())
+  })
+}
+
+/// Records a reviewer's verdict on a stored incident; resolving again replaces the verdict.
+pub fn resolve(incident_id: &str, verdict: Verdict, note: Option<&str>) -> anyhow::Result<()> {
+  update_incident(incident_id, |incident| {
+    incident.resolution = Some(Resolution {
+      verdict,
+      resolved_at_unix_ms: now_unix_ms(),
+      note: note.map(str::to_string),
+    });
+    incident.actions_taken.push(
+      match verdict {
+        Verdict::FalsePositive => "resolved_false_positive",
+        Verdict::Confirmed => "resolved_confirmed",
+      }
+      .to_string(),
+    );
+  })
+}
+
+pub fn load(incident_id: &str) -> anyhow::Result<Incident> {
+  let base = paths::base_dir()?;
+  let file_path = paths::incidents_dir(&base).join(format!("{incident_id}.toml"));
+  Ok(toml::from_str(&fs::read_to_string(&file_path)?)?)
+}
+
+/// Rewrites a stored incident in place (follow-up actions, findings merged from a later scan).
+pub fn update_incident(incident_id: &str, f: impl FnOnce(&mut Incident)) -> anyhow::Result<()> {
+  let base = paths::base_dir()?;
+  let file_path = paths::incidents_dir(&base).join(format!("{incident_id}.toml"));
+
+  if runtime::is_dry_run() {
+    tracing::warn!(
+      incident_id = %incident_id,
+      "DRY-RUN: would update incident record"
+    );
+    return Ok(());
+  }
+
+  let mut incident: Incident = toml::from_str(&fs::read_to_string(&file_path)?)?;
+  f(&mut incident);
+  scoring::apply(&scoring::configured(), &mut incident);
+  guidance::attach(&mut incident);
+  let raw = toml::to_string_pretty(&incident)?;
+  write_atomic(&file_path, &raw)?;
+  incident_chain::record(&base, incident_id, raw.as_bytes());
+  Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub struct IncidentSummary {
   pub incident_id: String,
   pub created_at_unix_ms: u64,
   pub severity: Severity,
+  /// Unset for incidents recorded before scores were.
+  pub score: Option<u32>,
   pub rule_ids: Vec<String>,
 }
 
 pub fn list_recent(limit: usize) -> anyhow::Result<Vec<IncidentSummary>> {
+  Ok(
+    load_recent(limit)?
+      .into_iter()
+      .map(|inc| IncidentSummary {
+        rule_ids: inc.findings.iter().map(|f| f.rule_id.clone()).collect(),
+        incident_id: inc.incident_id,
+        created_at_unix_ms: inc.created_at_unix_ms,
+        severity: inc.severity,
+        score: inc.score.map(|s| s.total),
+      })
+      .collect(),
+  )
+}
+
+/// The `limit` most recently written incidents, newest first; unreadable records are skipped.
+pub fn load_recent(limit: usize) -> anyhow::Result<Vec<Incident>> {
   let base = paths::base_dir()?;
   let dir = paths::incidents_dir(&base);
   if !dir.exists() {
@@ -54,33 +131,11 @@ pub fn list_recent(limit: usize) -> anyhow::Result<Vec<IncidentSummary>> {
       Ok(r) => r,
       Err(_) => continue,
     };
-    let inc: Incident = match toml::from_str(&raw) {
-      Ok(i) => i,
+    match toml::from_str::<Incident>(&raw) {
+      Ok(inc) => out.push(inc),
       Err(_) => continue,
-    };
-    let rule_ids = inc.findings.iter().map(|f| f.rule_id.clone()).collect();
-    out.push(IncidentSummary {
-      incident_id: inc.incident_id,
-      created_at_unix_ms: inc.created_at_unix_ms,
-      severity: inc.severity,
-      rule_ids,
-    });
+    }
   }
 
   Ok(out)
 }
-
-fn write_atomic(path: &Path, contents: &str) -> anyhow::Result<()> {
-  let parent = path
-    .parent()
-    .ok_or_else(|| anyhow::anyhow!("file path has no parent: {}", path.display()))?;
-  fs::create_dir_all(parent)?;
-
-  let tmp = parent.join(format!(
-    ".{}.tmp",
-    path.file_name().unwrap_or_default().to_string_lossy()
-  ));
-  fs::write(&tmp, contents)?;
-  fs::rename(&tmp, path)?;
-  Ok(())
-}
@@ -0,0 +1,398 @@
+use crate::config::{Config, Mode};
+use crate::incident_store;
+use crate::paths;
+use crate::privsep::{self, Containment, ContainmentSource};
+use crate::response_engine::notifications;
+use crate::runtime;
+use crate::types::{now_unix_ms, Incident, Severity};
+use defender_common::fs::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Mailbox between the service and the per-user tray helper. The service runs in session 0 and
+// cannot show UI, so it posts notifications as files and picks up the helper's replies:
+//
+//   ipc\status.toml                agent -> helper (heartbeat, effective mode, pending prompts)
+//   ipc\notifications\<id>.toml    agent -> helper (toasts, containment prompts)
+//   ipc\replies\<id>.toml          helper -> agent (prompt answers)
+//
+// `replies` is the only folder in the data directory that users can write (see
+// `self_protection::harden`); running scans register in `ipc\scans` (see `scan_ipc`).
+//
+// Files are flat TOML so the tray's minimal parser can read them. A reply can only ever enable
+// containment for a prompt the agent posted, never restore the network.
+
+const NOTIFICATION_TTL_MS: u64 = 15 * 60_000;
+const STATUS_INTERVAL_MS: u64 = 5_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+  Toast,
+  ContainmentPrompt,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+  pub id: String,
+  pub kind: NotificationKind,
+  pub incident_id: String,
+  pub severity: Severity,
+  pub title: String,
+  pub message: String,
+  pub created_at_unix_ms: u64,
+  pub expires_at_unix_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+  Contain,
+  Dismiss,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reply {
+  pub id: String,
+  pub decision: Decision,
+  #[serde(default)]
+  pub user: Option<String>,
+  pub replied_at_unix_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentStatus {
+  pub updated_at_unix_ms: u64,
+  pub mode: Mode,
+  pub pending_prompts: usize,
+}
+
+/// Posts the user-facing notification for an incident: a containment prompt when `prompt` is set,
+/// otherwise a toast describing what happened.
+pub fn notify_incident(incident: &Incident, prompt: bool) -> anyhow::Result<()> {
+  let base = paths::base_dir()?;
+  let n = incident_notification(incident, prompt, now_unix_ms());
+
+  if runtime::is_dry_run() {
+    tracing::warn!(
+      incident_id = %incident.incident_id,
+      kind = ?n.kind,
+      "DRY-RUN: would post user notification"
+    );
+    return Ok(());
+  }
+
+  post_at(&base, &n)
+}
+
+fn incident_notification(incident: &Incident, prompt: bool, now: u64) -> Notification {
+  let mut rule_ids: Vec<&str> = incident
+    .findings
+    .iter()
+    .map(|f| f.rule_id.as_str())
+    .collect();
+  rule_ids.sort_unstable();
+  rule_ids.dedup();
+  let rules = rule_ids.join(", ");
+
+  let contained = incident
+    .actions_taken
+    .iter()
+    .any(|a| a == "killswitch_enable_auto" || a == "killswitch_enable_requested");
+
+  let (kind, title, message) = if prompt {
+    (
+      NotificationKind::ContainmentPrompt,
+      "AI Defender - Block network?".to_string(),
+      format!(
+        "A high-confidence threat was detected ({rules}). Block all network traffic now to stop \
+         possible data theft? Networking is restored automatically after the failsafe period."
+      ),
+    )
+  } else if contained {
+    (
+      NotificationKind::Toast,
+      "AI Defender - Network locked".to_string(),
+      format!("A high-confidence threat was detected ({rules}). Network traffic is blocked."),
+    )
+  } else {
+    (
+      NotificationKind::Toast,
+      "AI Defender - Threat detected".to_string(),
+      format!("Suspicious activity was detected ({rules}). See the incident for details."),
+    )
+  };
+
+  Notification {
+    id: uuid::Uuid::new_v4().to_string(),
+    kind,
+    incident_id: incident.incident_id.clone(),
+    severity: incident.severity,
+    title,
+    message,
+    created_at_unix_ms: now,
+    expires_at_unix_ms: now.saturating_add(NOTIFICATION_TTL_MS),
+  }
+}
+
+pub fn post_at(base: &Path, n: &Notification) -> anyhow::Result<()> {
+  let path = notification_path(base, &n.id);
+  write_atomic(&path, &toml::to_string(n)?)?;
+  tracing::info!(
+    notification_id = %n.id,
+    incident_id = %n.incident_id,
+    kind = ?n.kind,
+    "user notification posted"
+  );
+  Ok(())
+}
+
+/// Services the mailbox from the agent loop: applies prompt replies, drops expired notifications,
+/// and refreshes the status file.
+pub struct Mailbox {
+  base: PathBuf,
+  last_status_unix_ms: u64,
+}
+
+impl Mailbox {
+  pub fn new(base: &Path) -> Self {
+    Self {
+      base: base.to_path_buf(),
+      last_status_unix_ms: 0,
+    }
+  }
+
+  pub fn tick(&mut self, cfg: &Config) {
+    if runtime::is_dry_run() {
+      return;
+    }
+    let now = now_unix_ms();
+
+    for (reply, prompt) in take_replies(&self.base, now) {
+      apply_reply(cfg, &reply, &prompt);
+    }
+    prune_expired(&self.base, now);
+
+    if now.saturating_sub(self.last_status_unix_ms) >= STATUS_INTERVAL_MS {
+      self.last_status_unix_ms = now;
+      let status = AgentStatus {
+        updated_at_unix_ms: now,
+        mode: cfg.mode,
+        pending_prompts: pending(&self.base)
+          .iter()
+          .filter(|n| n.kind == NotificationKind::ContainmentPrompt)
+          .count(),
+      };
+      if let Err(e) = toml::to_string(&status)
+        .map_err(anyhow::Error::from)
+        .and_then(|raw| write_atomic(&paths::ipc_status_path(&self.base), &raw))
+      {
+        tracing::warn!(error = ?e, "failed to write IPC status (continuing)");
+      }
+    }
+  }
+}
+
+fn apply_reply(cfg: &Config, reply: &Reply, prompt: &Notification) {
+  let user = reply.user.as_deref().unwrap_or("unknown");
+  match reply.decision {
+    Decision::Contain => {
+      tracing::warn!(
+        incident_id = %prompt.incident_id,
+        user = %user,
+        "user confirmed containment; enabling network kill switch"
+      );
+      let action = match privsep::enable_containment(
+        cfg,
+        &prompt.incident_id,
+        ContainmentSource::UserConfirmed,
+        reply.user.as_deref(),
+      ) {
+        Ok(Containment::Enabled) => {
+          if let Ok(incident) = incident_store::load(&prompt.incident_id) {
+            notifications::notify_killswitch_enabled(cfg, &incident, "user confirmed");
+          }
+          "killswitch_enable_user_confirmed"
+        }
+        Ok(Containment::Requested) => "killswitch_enable_user_confirmed_requested",
+        Err(e) => {
+          tracing::error!(
+            incident_id = %prompt.incident_id,
+            error = ?e,
+            "user-confirmed kill switch failed"
+          );
+          "killswitch_enable_user_confirmed_failed"
+        }
+      };
+      record_action(&prompt.incident_id, action);
+    }
+    Decision::Dismiss => {
+      tracing::info!(
+        incident_id = %prompt.incident_id,
+        user = %user,
+        "user dismissed containment prompt"
+      );
+      record_action(&prompt.incident_id, "containment_prompt_dismissed");
+    }
+  }
+}
+
+fn record_action(incident_id: &str, action: &str) {
+  if let Err(e) = incident_store::record_action(incident_id, action) {
+    tracing::warn!(
+      incident_id = %incident_id,
+      error = ?e,
+      "failed to update incident record (continuing)"
+    );
+  }
+}
+
+/// Valid replies paired with the prompt they answer. Every reply file is consumed; replies to
+/// unknown, expired, or non-prompt notifications are discarded.
+fn take_replies(base: &Path, now: u64) -> Vec<(Reply, Notification)> {
+  let Ok(entries) = fs::read_dir(paths::ipc_replies_dir(base)) else {
+    return Vec::new();
+  };
+
+  let mut out = Vec::new();
+  for path in entries.flatten().map(|e| e.path()) {
+    if path.extension().and_then(|s| s.to_str()) != Some("toml") {
+      continue;
+    }
+    let reply = fs::read_to_string(&path)
+      .ok()
+      .and_then(|raw| toml::from_str::<Reply>(&raw).ok());
+    let _ = fs::remove_file(&path);
+
+    let Some(reply) = reply else {
+      tracing::warn!(path = %path.display(), "discarding malformed notification reply");
+      continue;
+    };
+    let prompt = read_notification(base, &reply.id)
+      .filter(|n| n.kind == NotificationKind::ContainmentPrompt && n.expires_at_unix_ms > now);
+    let Some(prompt) = prompt else {
+      tracing::warn!(
+        notification_id = %reply.id,
+        "discarding reply to unknown or expired containment prompt"
+      );
+      continue;
+    };
+    let _ = fs::remove_file(notification_path(base, &prompt.id));
+    out.push((reply, prompt));
+  }
+  out
+}
+
+fn prune_expired(base: &Path, now: u64) {
+  for n in pending(base) {
+    if n.expires_at_unix_ms <= now {
+      let _ = fs::remove_file(notification_path(base, &n.id));
+    }
+  }
+}
+
+fn pending(base: &Path) -> Vec<Notification> {
+  let Ok(entries) = fs::read_dir(paths::ipc_notifications_dir(base)) else {
+    return Vec::new();
+  };
+  entries
+    .flatten()
+    .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("toml"))
+    .filter_map(|e| fs::read_to_string(e.path()).ok())
+    .filter_map(|raw| toml::from_str::<Notification>(&raw).ok())
+    .collect()
+}
+
+fn read_notification(base: &Path, id: &str) -> Option<Notification> {
+  // IDs come from a user-writable file; never let one escape the notifications dir.
+  if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+    return None;
+  }
+  let raw = fs::read_to_string(notification_path(base, id)).ok()?;
+  toml::from_str(&raw).ok()
+}
+
+fn notification_path(base: &Path, id: &str) -> PathBuf {
+  paths::ipc_notifications_dir(base).join(format!("{id}.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::Finding;
+
+  fn red_incident() -> Incident {
+    Incident::new(vec![Finding {
+      rule_id: "R009".to_string(),
+      severity: Severity::Red,
+      description: "test".to_string(),
+      evidence: vec![],
+      timestamp_unix_ms: 1_700_000_000_000,
+    }])
+  }
+
+  fn write_reply(base: &Path, id: &str, decision: Decision) {
+    let reply = Reply {
+      id: id.to_string(),
+      decision,
+      user: Some("DESKTOP\\alice".to_string()),
+      replied_at_unix_ms: 0,
+    };
+    write_atomic(
+      &paths::ipc_replies_dir(base).join(format!("{id}.toml")),
+      toml::to_string(&reply).unwrap(),
+    )
+    .unwrap();
+  }
+
+  #[test]
+  fn replies_are_matched_to_live_prompts_only() {
+    let base = std::env::temp_dir().join(format!("aid-ipc-{}", uuid::Uuid::new_v4()));
+    let now = 1_700_000_000_000;
+    let inc = red_incident();
+
+    let prompt = incident_notification(&inc, true, now);
+    let toast = incident_notification(&inc, false, now);
+    assert_eq!(prompt.kind, NotificationKind::ContainmentPrompt);
+    assert!(toast.message.contains("R009"));
+    post_at(&base, &prompt).unwrap();
+    post_at(&base, &toast).unwrap();
+
+    write_reply(&base, &prompt.id, Decision::Contain);
+    write_reply(&base, &toast.id, Decision::Contain);
+    write_reply(&base, "..\\..\\config", Decision::Contain);
+
+    let replies = take_replies(&base, now + 1);
+    assert_eq!(replies.len(), 1);
+    assert_eq!(replies[0].1.incident_id, inc.incident_id);
+    assert_eq!(replies[0].0.decision, Decision::Contain);
+
+    // The answered prompt is gone, all reply files are consumed, and the toast stays until expiry.
+    let left = pending(&base);
+    assert_eq!(left.len(), 1);
+    assert_eq!(left[0].kind, NotificationKind::Toast);
+    assert_eq!(
+      fs::read_dir(paths::ipc_replies_dir(&base)).unwrap().count(),
+      0
+    );
+
+    prune_expired(&base, now + NOTIFICATION_TTL_MS);
+    assert!(pending(&base).is_empty());
+
+    let _ = fs::remove_dir_all(&base);
+  }
+
+  #[test]
+  fn expired_prompts_cannot_be_answered() {
+    let base = std::env::temp_dir().join(format!("aid-ipc-{}", uuid::Uuid::new_v4()));
+    let now = 1_700_000_000_000;
+    let prompt = incident_notification(&red_incident(), true, now);
+    post_at(&base, &prompt).unwrap();
+    write_reply(&base, &prompt.id, Decision::Contain);
+
+    assert!(take_replies(&base, now + NOTIFICATION_TTL_MS).is_empty());
+
+    let _ = fs::remove_dir_all(&base);
+  }
+}
@@ -0,0 +1,148 @@
+use crate::paths;
+use crate::types::utc_timestamp;
+use std::fs;
+use std::path::Path;
+
+// While the agent runs, it restores networking itself once a lock's failsafe expires (AUTO
+// containment or a timed manual lock, see `poll_failsafe`). When it stops with such a lock still
+// active, a one-shot scheduled task
+// takes that duty over: it runs as SYSTEM at the failsafe deadline (or as soon as possible after,
+// if the machine was off) and calls `--killswitch failsafe-restore`. The agent deletes the task
+// again when it starts, since it is back in charge.
+
+pub const TASK_NAME: &str = "AI Defender Failsafe Restore";
+
+/// Registers the restore task for `deadline_unix_ms`, replacing any previous one.
+pub fn register(deadline_unix_ms: u64) -> anyhow::Result<()> {
+  let exe = std::env::current_exe()?;
+  let xml = task_xml(
+    &exe.to_string_lossy(),
+    paths::base_dir_override().as_deref(),
+    deadline_unix_ms,
+  );
+
+  // schtasks reads task XML as UTF-16.
+  let mut bytes = vec![0xFF, 0xFE];
+  bytes.extend(xml.encode_utf16().flat_map(u16::to_le_bytes));
+  let base = paths::base_dir()?;
+  fs::create_dir_all(&base)?;
+  let xml_path = base.join("failsafe-task.xml");
+  fs::write(&xml_path, bytes)?;
+
+  let res = schtasks(&[
+    "/Create",
+    "/TN",
+    TASK_NAME,
+    "/XML",
+    &xml_path.to_string_lossy(),
+    "/F",
+  ]);
+  let _ = fs::remove_file(&xml_path);
+  res
+}
+
+/// Deletes the restore task if it exists.
+pub fn remove() -> anyhow::Result<()> {
+  if !exists() {
+    return Ok(());
+  }
+  schtasks(&["/Delete", "/TN", TASK_NAME, "/F"])
+}
+
+pub fn exists() -> bool {
+  schtasks(&["/Query", "/TN", TASK_NAME]).is_ok()
+}
+
+fn task_xml(exe: &str, base_dir: Option<&Path>, deadline_unix_ms: u64) -> String {
+  let mut args = "--console --killswitch failsafe-restore".to_string();
+  if let Some(dir) = base_dir {
+    args.push_str(&format!(" --base-dir \"{}\"", dir.display()));
+  }
+  format!(
+    r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <Description>Restores networking when an AI Defender kill switch failsafe expires while the agent is stopped.</Description>
+  </RegistrationInfo>
+  <Triggers>
+    <TimeTrigger>
+      <StartBoundary>{}</StartBoundary>
+      <Enabled>true</Enabled>
+    </TimeTrigger>
+  </Triggers>
+  <Principals>
+    <Principal id="System">
+      <UserId>S-1-5-18</UserId>
+      <RunLevel>HighestAvailable</RunLevel>
+    </Principal>
+  </Principals>
+  <Settings>
+    <StartWhenAvailable>true</StartWhenAvailable>
+    <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>
+    <StopIfGoingOnBatteries>false</StopIfGoingOnBatteries>
+    <MultipleInstancesPolicy>IgnoreNew</MultipleInstancesPolicy>
+    <ExecutionTimeLimit>PT10M</ExecutionTimeLimit>
+  </Settings>
+  <Actions Context="System">
+    <Exec>
+      <Command>{}</Command>
+      <Arguments>{}</Arguments>
+    </Exec>
+  </Actions>
+</Task>
+"#,
+    utc_timestamp(deadline_unix_ms),
+    xml_escape(exe),
+    xml_escape(&args)
+  )
+}
+
+fn xml_escape(s: &str) -> String {
+  s.replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+#[cfg(windows)]
+fn schtasks(args: &[&str]) -> anyhow::Result<()> {
+  let output = std::process::Command::new("schtasks").args(args).output()?;
+  if !output.status.success() {
+    return Err(anyhow::anyhow!(
+      "schtasks failed ({}): {}",
+      output.status,
+      String::from_utf8_lossy(&output.stderr).trim()
+    ));
+  }
+  Ok(())
+}
+
+#[cfg(not(windows))]
+fn schtasks(_args: &[&str]) -> anyhow::Result<()> {
+  Err(anyhow::anyhow!(
+    "scheduled tasks are only supported on Windows"
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn task_fires_at_the_failsafe_deadline_in_utc() {
+    assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+    assert_eq!(utc_timestamp(1_709_210_096_000), "2024-02-29T12:34:56Z");
+
+    let xml = task_xml(
+      "C:\\Program Files\\AI & Co\\agent-core.exe",
+      None,
+      1_709_210_096_000,
+    );
+    assert!(xml.contains("<StartBoundary>2024-02-29T12:34:56Z</StartBoundary>"));
+    assert!(xml.contains("<Command>C:\\Program Files\\AI &amp; Co\\agent-core.exe</Command>"));
+    assert!(xml.contains("<Arguments>--console --killswitch failsafe-restore</Arguments>"));
+
+    let xml = task_xml("agent-core.exe", Some(Path::new("D:\\Tenants\\A")), 0);
+    assert!(xml.contains("failsafe-restore --base-dir &quot;D:\\Tenants\\A&quot;</Arguments>"));
+  }
+}
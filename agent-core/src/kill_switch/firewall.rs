@@ -1,9 +1,15 @@
 use super::{FIREWALL_RULE_GROUP, RULE_IN_NAME, RULE_OUT_NAME};
+use crate::net::Cidr;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::{Arc, Mutex, RwLock};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FirewallBackend {
   Com,
   NetshFallback,
+  /// `MockFirewall`, installed by tests.
+  Mock,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -13,21 +19,59 @@ pub struct FirewallRuleStatus {
   pub backend: FirewallBackend,
 }
 
-pub fn enable_rules() -> anyhow::Result<FirewallBackend> {
+/// A rule in our firewall group as the OS reports it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirewallRuleInfo {
+  pub name: String,
+  /// `in` or `out`.
+  pub direction: String,
+  /// `block` or `allow`.
+  pub action: String,
+  pub enabled: bool,
+  /// Program the rule applies to; `None` means all programs.
+  pub program: Option<String>,
+  pub remote_addresses: String,
+}
+
+/// How the kill switch rules are managed: `INetFwPolicy2` over COM, or `netsh` where COM is
+/// unavailable. Every implementation touches only the two rules in `AI_DEFENDER_KILLSWITCH`.
+pub trait FirewallOps: Send + Sync {
+  fn backend(&self) -> FirewallBackend;
+  /// Adds (or refreshes) the two block rules, blocking `remote` (`*` or address ranges).
+  fn enable_rules(&self, remote: &str) -> anyhow::Result<()>;
+  fn disable_rules(&self) -> anyhow::Result<()>;
+  fn rules_status(&self) -> anyhow::Result<FirewallRuleStatus>;
+  /// Every rule in the group, not just the two we expect.
+  fn group_rules(&self) -> anyhow::Result<Vec<FirewallRuleInfo>>;
+}
+
+static INSTALLED: RwLock<Option<Arc<dyn FirewallOps>>> = RwLock::new(None);
+
+/// Replaces the Windows Firewall for this process (`None` restores it). For tests and harnesses
+/// that drive the kill switch without admin rights, with `MockFirewall`.
+pub fn set_firewall(ops: Option<Arc<dyn FirewallOps>>) {
+  *INSTALLED.write().unwrap_or_else(|e| e.into_inner()) = ops;
+}
+
+/// Runs `f` against the installed firewall, or COM with a netsh fallback.
+fn with_firewall<T>(
+  f: impl Fn(&dyn FirewallOps) -> anyhow::Result<T>,
+) -> anyhow::Result<(T, FirewallBackend)> {
+  let installed = INSTALLED.read().unwrap_or_else(|e| e.into_inner()).clone();
+  if let Some(ops) = installed {
+    return Ok((f(ops.as_ref())?, ops.backend()));
+  }
+
   #[cfg(windows)]
   {
-    match com::enable_rules() {
-      Ok(()) => Ok(FirewallBackend::Com),
-      Err(e) => {
-        // Only fall back when COM initialization/activation is unavailable.
-        // For access-denied or other errors, fallback likely won't help and could reduce clarity.
-        let msg = e.to_string();
-        if msg.starts_with("COM unavailable:") {
-          netsh::enable_rules()?;
-          return Ok(FirewallBackend::NetshFallback);
-        }
-        Err(e)
+    match f(&ComFirewall) {
+      Ok(v) => Ok((v, FirewallBackend::Com)),
+      // Only fall back when COM initialization/activation is unavailable. For access-denied or
+      // other errors, fallback likely won't help and could reduce clarity.
+      Err(e) if e.to_string().starts_with("COM unavailable:") => {
+        Ok((f(&NetshFirewall)?, FirewallBackend::NetshFallback))
       }
+      Err(e) => Err(e),
     }
   }
   #[cfg(not(windows))]
@@ -36,60 +80,197 @@ pub fn enable_rules() -> anyhow::Result<FirewallBackend> {
   }
 }
 
-pub fn disable_rules() -> anyhow::Result<FirewallBackend> {
-  #[cfg(windows)]
-  {
-    match com::disable_rules() {
-      Ok(()) => Ok(FirewallBackend::Com),
-      Err(e) => {
-        let msg = e.to_string();
-        if msg.starts_with("COM unavailable:") {
-          netsh::disable_rules()?;
-          return Ok(FirewallBackend::NetshFallback);
-        }
-        Err(e)
+/// Adds (or refreshes) the two block-all rules, leaving out the `exempt` remote addresses.
+pub fn enable_rules(exempt: &[Cidr]) -> anyhow::Result<FirewallBackend> {
+  let remote = block_remote_addresses(exempt);
+  with_firewall(|fw| fw.enable_rules(&remote)).map(|((), backend)| backend)
+}
+
+/// `RemoteAddresses` for the block rules. Windows Firewall block rules always win over allow rules,
+/// so always-allowed destinations can't be opened with allow rules next to them; the block rules
+/// cover every other address instead.
+fn block_remote_addresses(exempt: &[Cidr]) -> String {
+  if exempt.is_empty() {
+    return "*".to_string();
+  }
+  let ranges = complement_ranges(exempt);
+  if ranges.is_empty() {
+    tracing::error!("killswitch.always_allow covers every address; blocking everything instead");
+    return "*".to_string();
+  }
+  ranges.join(",")
+}
+
+/// Address ranges (`a-b`, or `a` for a single address) not covered by `exempt`, IPv4 then IPv6.
+fn complement_ranges(exempt: &[Cidr]) -> Vec<String> {
+  let mut out = Vec::new();
+  for v4 in [true, false] {
+    let max = if v4 { u128::from(u32::MAX) } else { u128::MAX };
+    let mut spans: Vec<(u128, u128)> = exempt
+      .iter()
+      .map(Cidr::bounds)
+      .filter(|(first, _)| first.is_ipv4() == v4)
+      .map(|(first, last)| (ip_to_u128(first), ip_to_u128(last)))
+      .collect();
+    spans.sort_unstable();
+
+    let mut next = Some(0u128);
+    for (first, last) in spans {
+      let Some(start) = next else {
+        break;
+      };
+      if first > start {
+        out.push(format_range(v4, start, first - 1));
       }
+      if last >= start {
+        next = last.checked_add(1).filter(|n| *n <= max);
+      }
+    }
+    if let Some(start) = next {
+      out.push(format_range(v4, start, max));
     }
   }
-  #[cfg(not(windows))]
-  {
-    Err(anyhow::anyhow!("kill switch is only supported on Windows"))
+  out
+}
+
+fn ip_to_u128(ip: IpAddr) -> u128 {
+  match ip {
+    IpAddr::V4(v4) => u128::from(u32::from(v4)),
+    IpAddr::V6(v6) => u128::from(v6),
   }
 }
 
+fn format_range(v4: bool, first: u128, last: u128) -> String {
+  let ip = |n: u128| -> IpAddr {
+    if v4 {
+      IpAddr::V4(Ipv4Addr::from(n as u32))
+    } else {
+      IpAddr::V6(Ipv6Addr::from(n))
+    }
+  };
+  if first == last {
+    ip(first).to_string()
+  } else {
+    format!("{}-{}", ip(first), ip(last))
+  }
+}
+
+pub fn disable_rules() -> anyhow::Result<FirewallBackend> {
+  with_firewall(|fw| fw.disable_rules()).map(|((), backend)| backend)
+}
+
 pub fn rules_status() -> anyhow::Result<FirewallRuleStatus> {
-  #[cfg(windows)]
-  {
-    match com::rules_status() {
-      Ok(st) => Ok(FirewallRuleStatus {
-        outbound_ok: st.outbound_ok,
-        inbound_ok: st.inbound_ok,
-        backend: FirewallBackend::Com,
-      }),
-      Err(e) => {
-        let msg = e.to_string();
-        if msg.starts_with("COM unavailable:") {
-          let st = netsh::rules_status()?;
-          return Ok(FirewallRuleStatus {
-            outbound_ok: st.outbound_ok,
-            inbound_ok: st.inbound_ok,
-            backend: FirewallBackend::NetshFallback,
-          });
-        }
-        Err(e)
-      }
+  with_firewall(|fw| fw.rules_status()).map(|(status, _)| status)
+}
+
+/// Every rule in `AI_DEFENDER_KILLSWITCH`, not just the two we expect (for audit snapshots).
+pub fn group_rules() -> anyhow::Result<(Vec<FirewallRuleInfo>, FirewallBackend)> {
+  with_firewall(|fw| fw.group_rules())
+}
+
+#[cfg(windows)]
+struct ComFirewall;
+
+#[cfg(windows)]
+impl FirewallOps for ComFirewall {
+  fn backend(&self) -> FirewallBackend {
+    FirewallBackend::Com
+  }
+  fn enable_rules(&self, remote: &str) -> anyhow::Result<()> {
+    com::enable_rules(remote)
+  }
+  fn disable_rules(&self) -> anyhow::Result<()> {
+    com::disable_rules()
+  }
+  fn rules_status(&self) -> anyhow::Result<FirewallRuleStatus> {
+    com::rules_status()
+  }
+  fn group_rules(&self) -> anyhow::Result<Vec<FirewallRuleInfo>> {
+    com::group_rules()
+  }
+}
+
+#[cfg(windows)]
+struct NetshFirewall;
+
+#[cfg(windows)]
+impl FirewallOps for NetshFirewall {
+  fn backend(&self) -> FirewallBackend {
+    FirewallBackend::NetshFallback
+  }
+  fn enable_rules(&self, remote: &str) -> anyhow::Result<()> {
+    netsh::enable_rules(remote)
+  }
+  fn disable_rules(&self) -> anyhow::Result<()> {
+    netsh::disable_rules()
+  }
+  fn rules_status(&self) -> anyhow::Result<FirewallRuleStatus> {
+    netsh::rules_status()
+  }
+  fn group_rules(&self) -> anyhow::Result<Vec<FirewallRuleInfo>> {
+    netsh::group_rules()
+  }
+}
+
+/// In-memory firewall: keeps the rules it is asked for, needs no rights, never touches the OS.
+#[derive(Debug, Default)]
+pub struct MockFirewall {
+  rules: Mutex<Vec<FirewallRuleInfo>>,
+}
+
+impl MockFirewall {
+  /// The rules currently "installed".
+  pub fn rules(&self) -> Vec<FirewallRuleInfo> {
+    self.rules.lock().unwrap_or_else(|e| e.into_inner()).clone()
+  }
+}
+
+impl FirewallOps for MockFirewall {
+  fn backend(&self) -> FirewallBackend {
+    FirewallBackend::Mock
+  }
+
+  fn enable_rules(&self, remote: &str) -> anyhow::Result<()> {
+    let mut rules = self.rules.lock().unwrap_or_else(|e| e.into_inner());
+    for (name, direction) in [(RULE_OUT_NAME, "out"), (RULE_IN_NAME, "in")] {
+      rules.retain(|r| r.name != name);
+      rules.push(FirewallRuleInfo {
+        name: name.to_string(),
+        direction: direction.to_string(),
+        action: "block".to_string(),
+        enabled: true,
+        program: None,
+        remote_addresses: remote.to_string(),
+      });
     }
+    Ok(())
   }
-  #[cfg(not(windows))]
-  {
-    Err(anyhow::anyhow!("kill switch is only supported on Windows"))
+
+  fn disable_rules(&self) -> anyhow::Result<()> {
+    let mut rules = self.rules.lock().unwrap_or_else(|e| e.into_inner());
+    rules.retain(|r| r.name != RULE_OUT_NAME && r.name != RULE_IN_NAME);
+    Ok(())
+  }
+
+  fn rules_status(&self) -> anyhow::Result<FirewallRuleStatus> {
+    let rules = self.rules.lock().unwrap_or_else(|e| e.into_inner());
+    let present = |name: &str| rules.iter().any(|r| r.name == name);
+    Ok(FirewallRuleStatus {
+      outbound_ok: present(RULE_OUT_NAME),
+      inbound_ok: present(RULE_IN_NAME),
+      backend: FirewallBackend::Mock,
+    })
+  }
+
+  fn group_rules(&self) -> anyhow::Result<Vec<FirewallRuleInfo>> {
+    Ok(self.rules())
   }
 }
 
 #[cfg(windows)]
 mod com {
   use super::*;
-  use windows::core::{Result as WinResult, BSTR, HRESULT};
+  use windows::core::{IUnknown, Interface, Result as WinResult, BSTR, HRESULT, VARIANT};
   use windows::Win32::Foundation::{ERROR_FILE_NOT_FOUND, E_ACCESSDENIED, VARIANT_TRUE};
   use windows::Win32::NetworkManagement::WindowsFirewall::{
     INetFwPolicy2, INetFwRule, NetFwPolicy2, NetFwRule, NET_FW_ACTION_BLOCK,
@@ -98,15 +279,16 @@ mod com {
   use windows::Win32::System::Com::{
     CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED,
   };
+  use windows::Win32::System::Ole::IEnumVARIANT;
 
-  pub fn enable_rules() -> anyhow::Result<()> {
+  pub fn enable_rules(remote: &str) -> anyhow::Result<()> {
     with_com(|| {
       let policy: INetFwPolicy2 =
         unsafe { CoCreateInstance(&NetFwPolicy2, None, CLSCTX_INPROC_SERVER) }?;
       let rules = unsafe { policy.Rules()? };
 
-      ensure_rule(&rules, RULE_OUT_NAME, NET_FW_RULE_DIRECTION(2))?;
-      ensure_rule(&rules, RULE_IN_NAME, NET_FW_RULE_DIRECTION(1))?;
+      ensure_rule(&rules, RULE_OUT_NAME, NET_FW_RULE_DIRECTION(2), remote)?;
+      ensure_rule(&rules, RULE_IN_NAME, NET_FW_RULE_DIRECTION(1), remote)?;
 
       Ok(())
     })
@@ -142,6 +324,76 @@ mod com {
     })
   }
 
+  pub fn group_rules() -> anyhow::Result<Vec<FirewallRuleInfo>> {
+    with_com(|| {
+      let policy: INetFwPolicy2 =
+        unsafe { CoCreateInstance(&NetFwPolicy2, None, CLSCTX_INPROC_SERVER) }?;
+      let rules = unsafe { policy.Rules()? };
+      let items: IEnumVARIANT = unsafe { rules._NewEnum()? }.cast()?;
+
+      let mut out = Vec::new();
+      loop {
+        let mut item = [VARIANT::new()];
+        let mut fetched = 0u32;
+        // SAFETY: `Next` fills at most `item.len()` VARIANTs, which we own and drop.
+        unsafe { items.Next(&mut item, &mut fetched) }.ok()?;
+        if fetched == 0 {
+          break;
+        }
+        let Some(rule) = rule_from_variant(&item[0]) else {
+          continue;
+        };
+        // SAFETY: COM property getters generated by the `windows` crate.
+        unsafe {
+          if rule
+            .Grouping()
+            .map(|g| g != FIREWALL_RULE_GROUP)
+            .unwrap_or(true)
+          {
+            continue;
+          }
+          let program = rule
+            .ApplicationName()
+            .map(|p| p.to_string())
+            .unwrap_or_default();
+          out.push(FirewallRuleInfo {
+            name: rule.Name()?.to_string(),
+            direction: if rule.Direction()?.0 == 1 {
+              "in"
+            } else {
+              "out"
+            }
+            .to_string(),
+            action: if rule.Action()? == NET_FW_ACTION_BLOCK {
+              "block"
+            } else {
+              "allow"
+            }
+            .to_string(),
+            enabled: rule.Enabled()? == VARIANT_TRUE,
+            program: (!program.is_empty()).then_some(program),
+            remote_addresses: rule.RemoteAddresses()?.to_string(),
+          });
+        }
+      }
+      Ok(out)
+    })
+  }
+
+  fn rule_from_variant(v: &VARIANT) -> Option<INetFwRule> {
+    const VT_DISPATCH: u16 = 9;
+    let raw = v.as_raw();
+    // SAFETY: the rules enumerator yields VT_DISPATCH items; the interface pointer is borrowed
+    // from `v` (which keeps its reference) and cloned by `cast`.
+    unsafe {
+      if raw.Anonymous.Anonymous.vt != VT_DISPATCH {
+        return None;
+      }
+      let ptr = raw.Anonymous.Anonymous.Anonymous.pdispVal;
+      IUnknown::from_raw_borrowed(&ptr)?.cast().ok()
+    }
+  }
+
   fn with_com<T>(f: impl FnOnce() -> WinResult<T>) -> anyhow::Result<T> {
     // SAFETY: Windows Firewall management is exposed via COM APIs. `CoInitializeEx`,
     // `CoUninitialize`, and `CoCreateInstance` require `unsafe` calls in the Windows bindings.
@@ -168,6 +420,7 @@ mod com {
     rules: &windows::Win32::NetworkManagement::WindowsFirewall::INetFwRules,
     name: &str,
     direction: NET_FW_RULE_DIRECTION,
+    remote: &str,
   ) -> WinResult<()> {
     match unsafe { rules.Item(&BSTR::from(name)) } {
       Ok(rule) => {
@@ -179,7 +432,7 @@ mod com {
             "rule name collision (not in AI_DEFENDER_KILLSWITCH group)",
           ));
         }
-        apply_rule_properties(&rule, name, direction)?;
+        apply_rule_properties(&rule, name, direction, remote)?;
         Ok(())
       }
       Err(e) => {
@@ -188,7 +441,7 @@ mod com {
           return Err(e);
         }
         let rule: INetFwRule = unsafe { CoCreateInstance(&NetFwRule, None, CLSCTX_INPROC_SERVER) }?;
-        apply_rule_properties(&rule, name, direction)?;
+        apply_rule_properties(&rule, name, direction, remote)?;
         unsafe {
           rules.Add(&rule)?;
         }
@@ -201,6 +454,7 @@ mod com {
     rule: &INetFwRule,
     name: &str,
     direction: NET_FW_RULE_DIRECTION,
+    remote: &str,
   ) -> WinResult<()> {
     // SAFETY: these are COM property setters generated by the `windows` crate.
     // They are marked `unsafe` by the bindings; we keep the unsafe surface scoped here.
@@ -213,7 +467,7 @@ mod com {
       rule.SetProfiles(NET_FW_PROFILE2_ALL.0)?;
       rule.SetProtocol(NET_FW_IP_PROTOCOL_ANY.0)?;
       rule.SetLocalAddresses(&BSTR::from("*"))?;
-      rule.SetRemoteAddresses(&BSTR::from("*"))?;
+      rule.SetRemoteAddresses(&BSTR::from(remote))?;
       rule.SetDescription(&BSTR::from(
         "AI Defender: emergency network kill switch (blocks all inbound+outbound).",
       ))?;
@@ -282,7 +536,8 @@ mod netsh {
   use super::*;
   use std::process::Command;
 
-  pub fn enable_rules() -> anyhow::Result<()> {
+  pub fn enable_rules(remote: &str) -> anyhow::Result<()> {
+    let remoteip = if remote == "*" { "any" } else { remote };
     // Idempotent: remove only our two rules first, then add exactly two rules.
     let _ = disable_rules();
 
@@ -298,7 +553,7 @@ mod netsh {
       "protocol=any",
       "profile=any",
       "localip=any",
-      "remoteip=any",
+      &format!("remoteip={remoteip}"),
       &format!("group={FIREWALL_RULE_GROUP}"),
       "enable=yes",
     ])?;
@@ -315,7 +570,7 @@ mod netsh {
       "protocol=any",
       "profile=any",
       "localip=any",
-      "remoteip=any",
+      &format!("remoteip={remoteip}"),
       &format!("group={FIREWALL_RULE_GROUP}"),
       "enable=yes",
     ])?;
@@ -344,6 +599,24 @@ mod netsh {
     Ok(())
   }
 
+  /// netsh output is locale-dependent, so the fallback only reports our two known rules.
+  pub fn group_rules() -> anyhow::Result<Vec<FirewallRuleInfo>> {
+    let mut out = Vec::new();
+    for (name, direction) in [(RULE_OUT_NAME, "out"), (RULE_IN_NAME, "in")] {
+      if has_rule(name)? {
+        out.push(FirewallRuleInfo {
+          name: name.to_string(),
+          direction: direction.to_string(),
+          action: "block".to_string(),
+          enabled: true,
+          program: None,
+          remote_addresses: block_remote_addresses(&super::super::always_allow()),
+        });
+      }
+    }
+    Ok(out)
+  }
+
   pub fn rules_status() -> anyhow::Result<FirewallRuleStatus> {
     let out_ok = has_rule(RULE_OUT_NAME)?;
     let in_ok = has_rule(RULE_IN_NAME)?;
@@ -389,3 +662,27 @@ mod netsh {
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn block_rules_leave_out_always_allowed_ranges() {
+    let cidrs =
+      |entries: &[&str]| -> Vec<Cidr> { entries.iter().map(|e| e.parse().unwrap()).collect() };
+
+    assert_eq!(block_remote_addresses(&[]), "*");
+    assert_eq!(
+      block_remote_addresses(&cidrs(&["10.0.0.0/8", "10.1.0.0/16", "255.255.255.255"])),
+      "0.0.0.0-9.255.255.255,11.0.0.0-255.255.255.254,::-ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff"
+    );
+    assert_eq!(
+      block_remote_addresses(&cidrs(&["0.0.0.0/1", "192.168.1.1", "2001:db8::/32"])),
+      "128.0.0.0-192.168.1.0,192.168.1.2-255.255.255.255,::-2001:db7:ffff:ffff:ffff:ffff:ffff:ffff,\
+       2001:db9::-ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff"
+    );
+    // Exempting everything must not turn the rules into "block nothing".
+    assert_eq!(block_remote_addresses(&cidrs(&["0.0.0.0/0", "::/0"])), "*");
+  }
+}
@@ -1,9 +1,12 @@
 use crate::config::Config;
+use crate::net::Cidr;
 use crate::paths;
 use crate::runtime;
+use defender_common::fs::write_atomic;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub const FIREWALL_RULE_GROUP: &str = "AI_DEFENDER_KILLSWITCH";
@@ -11,8 +14,14 @@ pub const FIREWALL_RULE_GROUP: &str = "AI_DEFENDER_KILLSWITCH";
 pub(super) const RULE_OUT_NAME: &str = "AI Defender KillSwitch Outbound";
 pub(super) const RULE_IN_NAME: &str = "AI Defender KillSwitch Inbound";
 
+mod failsafe_task;
 mod firewall;
-pub use firewall::FirewallBackend;
+pub mod snapshot;
+pub use firewall::{
+  set_firewall, FirewallBackend, FirewallOps, FirewallRuleInfo, FirewallRuleStatus, MockFirewall,
+};
+
+static ALWAYS_ALLOW: RwLock<Vec<Cidr>> = RwLock::new(Vec::new());
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -54,6 +63,32 @@ pub struct KillSwitchStatus {
   pub last_incident_id: Option<String>,
 }
 
+/// Installs `[killswitch] always_allow` for rules this process applies. Call before
+/// `reconcile_on_startup`, which may re-apply the rules. Invalid entries are skipped; config
+/// validation already warned about them.
+pub fn configure(cfg: &Config) {
+  let allow: Vec<Cidr> = cfg
+    .killswitch
+    .always_allow
+    .iter()
+    .filter_map(|e| e.parse().ok())
+    .collect();
+  if !allow.is_empty() {
+    tracing::info!(
+      always_allow = %allow.iter().map(Cidr::to_string).collect::<Vec<_>>().join(","),
+      "kill switch exempts always-allowed destinations"
+    );
+  }
+  *ALWAYS_ALLOW.write().unwrap_or_else(|e| e.into_inner()) = allow;
+}
+
+fn always_allow() -> Vec<Cidr> {
+  ALWAYS_ALLOW
+    .read()
+    .unwrap_or_else(|e| e.into_inner())
+    .clone()
+}
+
 pub fn reconcile_on_startup(cfg: &Config) -> anyhow::Result<()> {
   if !cfg.killswitch.enabled {
     tracing::warn!(
@@ -106,7 +141,7 @@ pub fn reconcile_on_startup(cfg: &Config) -> anyhow::Result<()> {
         group = FIREWALL_RULE_GROUP,
         "startup reconcile: state says ON but rules missing; re-enabling"
       );
-      let backend = firewall::enable_rules()?;
+      let backend = firewall::enable_rules(&always_allow())?;
       tracing::warn!(
         group = FIREWALL_RULE_GROUP,
         backend = ?backend,
@@ -168,10 +203,13 @@ pub fn set_keep_locked(keep_locked: bool) -> anyhow::Result<()> {
   Ok(())
 }
 
-pub fn enable_manual() -> anyhow::Result<()> {
+/// Locks the network until it is turned off. With `duration_minutes`, the lock gets a failsafe
+/// deadline like AUTO containment and restores itself (unless `keep_locked` is set afterwards).
+pub fn enable_manual(duration_minutes: Option<u64>) -> anyhow::Result<()> {
   if runtime::is_dry_run() {
     tracing::warn!(
       group = FIREWALL_RULE_GROUP,
+      duration_minutes,
       "DRY-RUN: would enable firewall kill switch (group AI_DEFENDER_KILLSWITCH)"
     );
     return Ok(());
@@ -183,19 +221,27 @@ pub fn enable_manual() -> anyhow::Result<()> {
   let state_path = paths::killswitch_state_path(&base);
   let mut state = load_state_or_default(&state_path);
 
-  let backend = firewall::enable_rules()?;
+  let backend = firewall::enable_rules(&always_allow())?;
+  let enabled_at = now_unix_ms();
+  let deadline = duration_minutes.map(|m| enabled_at.saturating_add(m.saturating_mul(60_000)));
+  if deadline.is_some() {
+    // A bounded window was asked for explicitly; a leftover keep_locked must not void it.
+    state.keep_locked = false;
+  }
   state.enabled = true;
   state.enabled_mode = Some(KillSwitchMode::Manual);
-  state.enabled_at_unix_ms = Some(now_unix_ms());
-  state.failsafe_deadline_unix_ms = None;
+  state.enabled_at_unix_ms = Some(enabled_at);
+  state.failsafe_deadline_unix_ms = deadline;
   state.last_incident_id = None;
   save_state(&state_path, &state)?;
 
   tracing::warn!(
     group = FIREWALL_RULE_GROUP,
     backend = ?backend,
-    reason = "manual",
-    timestamp_unix_ms = state.enabled_at_unix_ms.unwrap_or_default(),
+    reason = if deadline.is_some() { "manual_timed" } else { "manual" },
+    duration_minutes,
+    failsafe_deadline_unix_ms = deadline,
+    timestamp_unix_ms = enabled_at,
     "kill switch enabled"
   );
 
@@ -219,7 +265,7 @@ pub fn enable_auto(incident_id: &str, failsafe_minutes: u64) -> anyhow::Result<(
   let state_path = paths::killswitch_state_path(&base);
   let mut state = load_state_or_default(&state_path);
 
-  let backend = firewall::enable_rules()?;
+  let backend = firewall::enable_rules(&always_allow())?;
 
   if state.keep_locked {
     tracing::info!(
@@ -300,9 +346,6 @@ fn should_auto_restore(state: &KillSwitchState) -> bool {
   if state.keep_locked {
     return false;
   }
-  if state.enabled_mode != Some(KillSwitchMode::AutoRedOnly) {
-    return false;
-  }
   let deadline = match state.failsafe_deadline_unix_ms {
     Some(d) => d,
     None => return false,
@@ -340,19 +383,6 @@ fn save_state(path: &Path, state: &KillSwitchState) -> anyhow::Result<()> {
   Ok(())
 }
 
-fn write_atomic(path: &Path, contents: &str) -> anyhow::Result<()> {
-  let parent = path
-    .parent()
-    .ok_or_else(|| anyhow::anyhow!("file path has no parent: {}", path.display()))?;
-  let tmp = parent.join(format!(
-    ".{}.tmp",
-    path.file_name().unwrap_or_default().to_string_lossy()
-  ));
-  fs::write(&tmp, contents)?;
-  fs::rename(&tmp, path)?;
-  Ok(())
-}
-
 fn now_unix_ms() -> u64 {
   SystemTime::now()
     .duration_since(UNIX_EPOCH)
@@ -365,6 +395,75 @@ pub fn state_file_path_for_docs() -> anyhow::Result<PathBuf> {
   Ok(paths::killswitch_state_path(&base))
 }
 
+/// Called when the agent stops. With a failsafe deadline pending, registers the scheduled-task
+/// restorer for the failsafe deadline so networking still comes back if the agent isn't running
+/// then. Returns the deadline that was handed off.
+pub fn hand_off_failsafe() -> anyhow::Result<Option<u64>> {
+  let base = paths::base_dir()?;
+  let state = load_state_or_default(&paths::killswitch_state_path(&base));
+  let Some(deadline) = pending_failsafe_deadline(&state) else {
+    return Ok(None);
+  };
+  if should_auto_restore(&state) {
+    poll_failsafe()?;
+    return Ok(None);
+  }
+  if runtime::is_dry_run() {
+    tracing::warn!(
+      group = FIREWALL_RULE_GROUP,
+      failsafe_deadline_unix_ms = deadline,
+      "DRY-RUN: would hand the kill switch failsafe to a scheduled task"
+    );
+    return Ok(None);
+  }
+
+  failsafe_task::register(deadline)?;
+  tracing::warn!(
+    group = FIREWALL_RULE_GROUP,
+    task = failsafe_task::TASK_NAME,
+    failsafe_deadline_unix_ms = deadline,
+    incident_id = state.last_incident_id.as_deref().unwrap_or(""),
+    "agent stopping with a failsafe deadline pending; failsafe handed to scheduled task"
+  );
+  Ok(Some(deadline))
+}
+
+/// Called when the agent starts: it enforces the failsafe itself again, so the scheduled-task
+/// restorer is removed.
+pub fn reclaim_failsafe() {
+  if runtime::is_dry_run() || !failsafe_task::exists() {
+    return;
+  }
+  match failsafe_task::remove() {
+    Ok(()) => tracing::info!(
+      task = failsafe_task::TASK_NAME,
+      "failsafe restore task removed; agent enforces the failsafe again"
+    ),
+    Err(e) => tracing::warn!(error = ?e, "failed to remove failsafe restore task (continuing)"),
+  }
+}
+
+/// Run by the scheduled-task restorer (`--killswitch failsafe-restore`). Restores networking if the
+/// AUTO failsafe has expired, then removes the task unless a deadline is still ahead. Returns
+/// whether the kill switch is off afterwards.
+pub fn run_failsafe_restore() -> anyhow::Result<bool> {
+  poll_failsafe()?;
+  let base = paths::base_dir()?;
+  let state = load_state_or_default(&paths::killswitch_state_path(&base));
+  if pending_failsafe_deadline(&state).is_none() {
+    failsafe_task::remove()?;
+  }
+  Ok(!state.enabled)
+}
+
+/// The deadline of an active lock (AUTO or timed manual) the failsafe may still lift.
+fn pending_failsafe_deadline(state: &KillSwitchState) -> Option<u64> {
+  if !state.enabled || state.keep_locked {
+    return None;
+  }
+  state.failsafe_deadline_unix_ms
+}
+
 pub fn poll_failsafe() -> anyhow::Result<()> {
   let base = paths::base_dir()?;
   let state_path = paths::killswitch_state_path(&base);
@@ -384,3 +483,31 @@ pub fn poll_failsafe() -> anyhow::Result<()> {
 
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn timed_manual_lock_restores_like_auto_but_untimed_never_does() {
+    let now = now_unix_ms();
+    let mut state = KillSwitchState {
+      enabled: true,
+      enabled_mode: Some(KillSwitchMode::Manual),
+      enabled_at_unix_ms: Some(now - 60_000),
+      ..KillSwitchState::default()
+    };
+    assert!(!should_auto_restore(&state));
+    assert_eq!(pending_failsafe_deadline(&state), None);
+
+    state.failsafe_deadline_unix_ms = Some(now - 1);
+    assert!(should_auto_restore(&state));
+
+    state.failsafe_deadline_unix_ms = Some(now + 60_000);
+    assert!(!should_auto_restore(&state));
+    assert_eq!(pending_failsafe_deadline(&state), Some(now + 60_000));
+
+    state.keep_locked = true;
+    assert_eq!(pending_failsafe_deadline(&state), None);
+  }
+}
@@ -0,0 +1,222 @@
+use super::firewall::{self, FirewallRuleInfo};
+use super::{load_state_or_default, now_unix_ms, KillSwitchMode};
+use crate::paths;
+use crate::secrets;
+use anyhow::Context;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Point-in-time export of everything containment-related, for audits and support handoffs. The
+// document is signed with a per-machine Ed25519 key kept in the secrets store, so a copy can be
+// checked for tampering later (`--containment verify`). The embedded public key only proves the
+// document wasn't changed after signing; compare it with the one reported on the machine itself.
+
+const FORMAT_VERSION: u32 = 1;
+const SIGNING_KEY_SECRET: &str = "containment-snapshot-key";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedSnapshot {
+  pub snapshot: serde_json::Value,
+  /// Base64 Ed25519 public key of the machine that produced the snapshot.
+  pub public_key: String,
+  /// Base64 Ed25519 signature over the compact JSON of `snapshot`.
+  pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Snapshot {
+  format: u32,
+  created_at_unix_ms: u64,
+  hostname: String,
+  agent_version: String,
+  killswitch: KillSwitchSection,
+  /// Rules in our firewall group as the OS reports them.
+  firewall_rules: Vec<FirewallRuleInfo>,
+  firewall_backend: Option<String>,
+  firewall_error: Option<String>,
+  /// Rules in our group scoped to a single program.
+  program_blocks: Vec<FirewallRuleInfo>,
+  /// Quarantine isn't implemented yet (RED incidents record `quarantine_skipped_no_path`), so this
+  /// is always empty; the field keeps the format stable for when it is.
+  quarantine: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct KillSwitchSection {
+  state_enabled: bool,
+  rules_present: Option<bool>,
+  keep_locked: bool,
+  enabled_mode: Option<KillSwitchMode>,
+  enabled_at_unix_ms: Option<u64>,
+  failsafe_deadline_unix_ms: Option<u64>,
+  last_incident_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Verification {
+  pub signature_valid: bool,
+  /// Whether the document was signed by this machine's key; `None` if this machine has none.
+  pub signed_by_this_machine: Option<bool>,
+}
+
+/// Collects the current state and signs it. Creates the machine's signing key on first use.
+pub fn create(base: &Path) -> anyhow::Result<SignedSnapshot> {
+  let snapshot = collect(base);
+  let key = load_or_create_key(base)?;
+  sign(&serde_json::to_value(&snapshot)?, &key)
+}
+
+pub fn verify(base: &Path, doc: &SignedSnapshot) -> anyhow::Result<Verification> {
+  let signature_valid = verify_signature(doc)?;
+  let signed_by_this_machine = load_key(base)
+    .ok()
+    .map(|k| STANDARD.encode(k.verifying_key().as_bytes()) == doc.public_key);
+  Ok(Verification {
+    signature_valid,
+    signed_by_this_machine,
+  })
+}
+
+/// Public key of this machine's snapshot signing key, if one was created.
+pub fn machine_public_key(base: &Path) -> Option<String> {
+  load_key(base)
+    .ok()
+    .map(|k| STANDARD.encode(k.verifying_key().as_bytes()))
+}
+
+fn collect(base: &Path) -> Snapshot {
+  let state = load_state_or_default(&paths::killswitch_state_path(base));
+  let (firewall_rules, firewall_backend, firewall_error) = match firewall::group_rules() {
+    Ok((rules, backend)) => (rules, Some(format!("{backend:?}")), None),
+    Err(e) => (Vec::new(), None, Some(format!("{e:#}"))),
+  };
+  let rules_present = firewall_error.is_none().then(|| {
+    [super::RULE_OUT_NAME, super::RULE_IN_NAME]
+      .iter()
+      .all(|name| firewall_rules.iter().any(|r| r.name == *name))
+  });
+  let program_blocks = firewall_rules
+    .iter()
+    .filter(|r| r.program.is_some())
+    .cloned()
+    .collect();
+
+  Snapshot {
+    format: FORMAT_VERSION,
+    created_at_unix_ms: now_unix_ms(),
+    hostname: std::env::var("COMPUTERNAME").unwrap_or_default(),
+    agent_version: env!("CARGO_PKG_VERSION").to_string(),
+    killswitch: KillSwitchSection {
+      state_enabled: state.enabled,
+      rules_present,
+      keep_locked: state.keep_locked,
+      enabled_mode: state.enabled_mode,
+      enabled_at_unix_ms: state.enabled_at_unix_ms,
+      failsafe_deadline_unix_ms: state.failsafe_deadline_unix_ms,
+      last_incident_id: state.last_incident_id,
+    },
+    firewall_rules,
+    firewall_backend,
+    firewall_error,
+    program_blocks,
+    quarantine: Vec::new(),
+  }
+}
+
+fn sign(snapshot: &serde_json::Value, key: &SigningKey) -> anyhow::Result<SignedSnapshot> {
+  let payload = serde_json::to_vec(snapshot)?;
+  Ok(SignedSnapshot {
+    snapshot: snapshot.clone(),
+    public_key: STANDARD.encode(key.verifying_key().as_bytes()),
+    signature: STANDARD.encode(key.sign(&payload).to_bytes()),
+  })
+}
+
+fn verify_signature(doc: &SignedSnapshot) -> anyhow::Result<bool> {
+  let pk: [u8; 32] = STANDARD
+    .decode(doc.public_key.trim())
+    .context("decode public_key")?
+    .try_into()
+    .map_err(|_| anyhow::anyhow!("public_key must be 32 bytes"))?;
+  let sig: [u8; 64] = STANDARD
+    .decode(doc.signature.trim())
+    .context("decode signature")?
+    .try_into()
+    .map_err(|_| anyhow::anyhow!("signature must be 64 bytes"))?;
+  let key = VerifyingKey::from_bytes(&pk).context("invalid public_key")?;
+  let payload = serde_json::to_vec(&doc.snapshot)?;
+  Ok(key.verify(&payload, &Signature::from_bytes(&sig)).is_ok())
+}
+
+fn load_key(base: &Path) -> anyhow::Result<SigningKey> {
+  let seed: [u8; 32] = STANDARD
+    .decode(secrets::get(base, SIGNING_KEY_SECRET)?.trim())
+    .context("decode snapshot signing key")?
+    .try_into()
+    .map_err(|_| anyhow::anyhow!("snapshot signing key must be 32 bytes"))?;
+  Ok(SigningKey::from_bytes(&seed))
+}
+
+fn load_or_create_key(base: &Path) -> anyhow::Result<SigningKey> {
+  if let Ok(key) = load_key(base) {
+    return Ok(key);
+  }
+  let seed = random_seed()?;
+  secrets::set(base, SIGNING_KEY_SECRET, &STANDARD.encode(seed))
+    .context("store snapshot signing key")?;
+  tracing::info!("created containment snapshot signing key");
+  Ok(SigningKey::from_bytes(&seed))
+}
+
+#[cfg(windows)]
+fn random_seed() -> anyhow::Result<[u8; 32]> {
+  use windows::Win32::Security::Cryptography::{
+    BCryptGenRandom, BCRYPT_ALG_HANDLE, BCRYPT_USE_SYSTEM_PREFERRED_RNG,
+  };
+
+  let mut seed = [0u8; 32];
+  // SAFETY: fills the provided buffer using the system-preferred RNG; no handle is needed.
+  unsafe {
+    BCryptGenRandom(
+      BCRYPT_ALG_HANDLE::default(),
+      &mut seed,
+      BCRYPT_USE_SYSTEM_PREFERRED_RNG,
+    )
+  }
+  .ok()
+  .context("BCryptGenRandom")?;
+  Ok(seed)
+}
+
+#[cfg(not(windows))]
+fn random_seed() -> anyhow::Result<[u8; 32]> {
+  Err(anyhow::anyhow!(
+    "containment snapshots are only supported on Windows"
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn signature_covers_the_snapshot() {
+    let base = std::env::temp_dir().join(format!("aid-snapshot-{}", uuid::Uuid::new_v4()));
+    let key = SigningKey::from_bytes(&[9u8; 32]);
+    let snapshot = serde_json::to_value(collect(&base)).unwrap();
+    assert_eq!(snapshot["format"], FORMAT_VERSION);
+    assert_eq!(snapshot["killswitch"]["state_enabled"], false);
+
+    let doc = sign(&snapshot, &key).unwrap();
+    let reparsed: SignedSnapshot =
+      serde_json::from_str(&serde_json::to_string_pretty(&doc).unwrap()).unwrap();
+    assert!(verify_signature(&reparsed).unwrap());
+
+    let mut tampered = reparsed.clone();
+    tampered.snapshot["killswitch"]["state_enabled"] = serde_json::Value::Bool(true);
+    assert!(!verify_signature(&tampered).unwrap());
+  }
+}
@@ -1,24 +1,69 @@
 pub mod agent;
+pub mod baseline;
+pub mod browser_extensions;
+pub mod browser_profiles;
+pub mod browsers;
 pub mod config;
+pub mod config_profile;
 pub mod console;
+pub mod coverage;
+pub mod credential_dump;
+pub mod crypto_address;
+pub mod domains;
 pub mod event_collector;
+pub mod guidance;
+pub mod heartbeat;
+pub mod incident_chain;
 pub mod incident_store;
+pub mod ipc;
 pub mod kill_switch;
 pub mod license;
+pub mod lifecycle;
 pub mod logging;
+pub mod metrics;
+pub mod motw;
+pub mod net;
+pub mod net_usage;
+pub mod network_hijack;
 pub mod paths;
+pub mod powershell;
+pub mod privsep;
+pub mod recording;
 pub mod response_engine;
 pub mod rules_engine;
 pub mod runtime;
+pub mod scan_history;
+pub mod scan_ipc;
+pub mod scan_schedule;
+pub mod scenario;
+pub mod scoring;
+pub mod secrets;
+pub mod self_protection;
 pub mod service;
+pub mod service_install;
+pub mod status_api;
+pub mod support_bundle;
 pub mod threat_feed;
+pub mod timeline;
+pub mod trust;
 pub mod types;
+pub mod user_profiles;
+pub mod wallet_drain;
+pub mod watch;
+pub mod watchdog;
 
 use std::sync::mpsc;
 use std::time::Duration;
 
 pub fn run_console(args: &[String]) -> anyhow::Result<()> {
   runtime::configure_from_args(args);
+  paths::configure_from_args(args)?;
+  // Before loading: loading would replace an invalid config.toml with defaults.
+  if let Some(i) = args.iter().position(|a| a == "--config") {
+    if args.get(i + 1).is_some_and(|a| a == "check") {
+      return console::run_config_check(&args[i + 2..]);
+    }
+  }
   let base = paths::base_dir()?;
   let config_path = paths::config_path(&base);
   let cfg = if runtime::is_dry_run() {
@@ -31,14 +76,15 @@ pub fn run_console(args: &[String]) -> anyhow::Result<()> {
     logging::init_stderr(&cfg.logging.level)?;
     tracing::warn!("DRY-RUN MODE ACTIVE");
   } else {
-    logging::init_file_and_stderr(
-      &paths::logs_dir(&base),
-      &cfg.logging.level,
-      cfg.logging.retention_days,
-    )?;
+    logging::init_file_and_stderr(&paths::logs_dir(&base), &cfg.logging)?;
   }
 
+  kill_switch::configure(&cfg);
   kill_switch::reconcile_on_startup(&cfg)?;
+  threat_feed::configure(&cfg);
+  browsers::configure(&cfg.browsers);
+  scoring::configure(&cfg.scoring);
+  incident_chain::configure(&cfg.self_protection);
 
   // Best-effort: refresh local status files for UI/CLI consumers.
   // This must not affect enforcement behavior.
@@ -58,7 +104,10 @@ pub fn run_console(args: &[String]) -> anyhow::Result<()> {
     let _ = ctrlc_tx.send(());
   })?;
 
+  let previous_run = lifecycle::on_start(&base);
+  self_protection::on_start(&cfg, &base, previous_run.as_ref());
   agent::Agent::new(cfg).run(stop_rx, Duration::from_millis(500))?;
+  lifecycle::on_stop(&base);
   tracing::info!("agent stopped");
   Ok(())
 }
@@ -0,0 +1,279 @@
+use crate::config::{Config, LicenseConfig, ThreatFeedConfig};
+use crate::threat_feed::proxy;
+use crate::trust::{decode_signature, Purpose, TrustSet};
+use anyhow::Context;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::blocking::Client;
+use reqwest::header::{CONTENT_TYPE, USER_AGENT};
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::time::Duration;
+
+// Device activation (`--license activate|deactivate`). Without `[license] activation_endpoint` it
+// is local only: the device is recorded in `activation.json` and seats aren't counted. With one,
+// the device is registered with the vendor's activation server, which counts seats:
+//
+//   POST <activation_endpoint>activate    {"version":1,"license_id":"…","device_id":"…",
+//                                          "device_label":"Front desk PC","nonce":"…"}
+//   POST <activation_endpoint>deactivate  (same body)
+//
+// The server answers `{"grant":"<base64 grant JSON>","signature":"<base64 Ed25519>"}`, the grant
+// signed with a license key (see `trust`):
+//
+//   {"version":1,"license_id":"…","device_id":"…","nonce":"…","status":"activated",
+//    "seats":2,"seats_used":1,"reason":null}
+//
+// `status` is `activated`, `deactivated` or `denied` (with a `reason`, e.g. all seats in use). The
+// grant must echo the license, device and nonce of the request, so a recorded answer can't be
+// replayed for another device or a later request. Only the license ID, the random device ID (no
+// hardware fingerprint) and the admin's label for the device, if any, are sent.
+
+const MAX_RESPONSE_BYTES: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActivationRequest {
+  pub version: u32,
+  pub license_id: String,
+  pub device_id: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub device_label: Option<String>,
+  pub nonce: String,
+}
+
+impl ActivationRequest {
+  pub fn new(license_id: &str, device_id: &str, device_label: Option<&str>) -> Self {
+    Self {
+      version: 1,
+      license_id: license_id.to_string(),
+      device_id: device_id.to_string(),
+      device_label: device_label.map(str::to_string),
+      nonce: uuid::Uuid::new_v4().to_string(),
+    }
+  }
+}
+
+/// A successful activation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Activation {
+  /// Devices activated on the license, this one included, when the backend counts them.
+  pub seats_used: Option<u32>,
+}
+
+pub trait ActivationBackend {
+  /// `local` or `server`; recorded in `activation.json`.
+  fn name(&self) -> &'static str;
+  /// Registers the device; fails if the license can't be activated on it.
+  fn activate(&self, req: &ActivationRequest) -> anyhow::Result<Activation>;
+  /// Releases the device's seat.
+  fn deactivate(&self, req: &ActivationRequest) -> anyhow::Result<()>;
+}
+
+/// The configured backend.
+pub fn backend(cfg: &Config) -> anyhow::Result<Box<dyn ActivationBackend>> {
+  Ok(match cfg.license.activation_endpoint.as_deref() {
+    Some(endpoint) => Box::new(HttpsActivation {
+      endpoint: validate_endpoint(endpoint, &cfg.license)?,
+      timeout: Duration::from_secs(cfg.license.timeout_seconds),
+      proxy: cfg.threat_feed.clone(),
+    }),
+    None => Box::new(LocalActivation),
+  })
+}
+
+/// Checks `[license] activation_endpoint`: HTTPS and an allowlisted host.
+pub fn validate_endpoint(raw: &str, cfg: &LicenseConfig) -> anyhow::Result<Url> {
+  let mut url = Url::parse(raw.trim()).context("invalid URL")?;
+  if url.scheme() != "https" {
+    anyhow::bail!("must use HTTPS");
+  }
+  let host = url.host_str().unwrap_or_default();
+  if !cfg.allowlist_domains.iter().any(|d| d.trim() == host) {
+    anyhow::bail!("host {host:?} is not in license.allowlist_domains");
+  }
+  // `activate` and `deactivate` are resolved relative to the endpoint.
+  if !url.path().ends_with('/') {
+    url.set_path(&format!("{}/", url.path()));
+  }
+  Ok(url)
+}
+
+/// Activation without a server: always succeeds, seats aren't counted.
+pub struct LocalActivation;
+
+impl ActivationBackend for LocalActivation {
+  fn name(&self) -> &'static str {
+    "local"
+  }
+
+  fn activate(&self, _req: &ActivationRequest) -> anyhow::Result<Activation> {
+    Ok(Activation { seats_used: None })
+  }
+
+  fn deactivate(&self, _req: &ActivationRequest) -> anyhow::Result<()> {
+    Ok(())
+  }
+}
+
+/// Activation through the vendor's activation server (see the module comment).
+pub struct HttpsActivation {
+  endpoint: Url,
+  timeout: Duration,
+  /// Proxy settings are shared with the threat feed.
+  proxy: ThreatFeedConfig,
+}
+
+impl HttpsActivation {
+  fn post(&self, action: &str, req: &ActivationRequest) -> anyhow::Result<Grant> {
+    let url = self
+      .endpoint
+      .join(action)
+      .with_context(|| format!("build {action} URL"))?;
+    let client = proxy::apply(Client::builder(), &self.proxy)?
+      .timeout(self.timeout)
+      .redirect(Policy::none())
+      .build()
+      .context("build HTTP client")?;
+    let response = client
+      .post(url)
+      .header(
+        USER_AGENT,
+        format!("AI-Defender/{}", env!("CARGO_PKG_VERSION")),
+      )
+      .header(CONTENT_TYPE, "application/json")
+      .body(serde_json::to_vec(req).context("serialize activation request")?)
+      .send()
+      .with_context(|| format!("POST {action} to the activation server"))?;
+    let status = response.status().as_u16();
+    if status != 200 {
+      anyhow::bail!("activation server answered HTTP {status}");
+    }
+
+    let mut body = Vec::new();
+    response
+      .take(MAX_RESPONSE_BYTES as u64 + 1)
+      .read_to_end(&mut body)
+      .context("read activation response")?;
+    if body.len() > MAX_RESPONSE_BYTES {
+      anyhow::bail!("activation response exceeds {MAX_RESPONSE_BYTES} bytes");
+    }
+    let signed: SignedGrant = serde_json::from_slice(&body).context("parse activation response")?;
+    let grant = STANDARD
+      .decode(signed.grant.trim())
+      .context("decode activation grant (base64)")?;
+    let sig = decode_signature(signed.signature.as_bytes())?;
+    TrustSet::load(Purpose::License)
+      .verify(&grant, &sig)
+      .context("activation grant signature")?;
+    check_grant(req, &grant)
+  }
+}
+
+impl ActivationBackend for HttpsActivation {
+  fn name(&self) -> &'static str {
+    "server"
+  }
+
+  fn activate(&self, req: &ActivationRequest) -> anyhow::Result<Activation> {
+    let grant = self.post("activate", req)?;
+    expect_status(&grant, "activated")?;
+    Ok(Activation {
+      seats_used: grant.seats_used,
+    })
+  }
+
+  fn deactivate(&self, req: &ActivationRequest) -> anyhow::Result<()> {
+    expect_status(&self.post("deactivate", req)?, "deactivated")
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct SignedGrant {
+  grant: String,
+  signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Grant {
+  version: u32,
+  license_id: String,
+  device_id: String,
+  nonce: String,
+  status: String,
+  #[serde(default)]
+  seats_used: Option<u32>,
+  #[serde(default)]
+  reason: Option<String>,
+}
+
+/// Parses a grant whose signature was verified and checks that it answers `req`.
+fn check_grant(req: &ActivationRequest, grant: &[u8]) -> anyhow::Result<Grant> {
+  let grant: Grant = serde_json::from_slice(grant).context("parse activation grant")?;
+  if grant.version != 1 {
+    anyhow::bail!("unsupported activation grant version {}", grant.version);
+  }
+  if grant.license_id != req.license_id
+    || grant.device_id != req.device_id
+    || grant.nonce != req.nonce
+  {
+    anyhow::bail!("activation grant does not answer this request");
+  }
+  Ok(grant)
+}
+
+fn expect_status(grant: &Grant, expected: &str) -> anyhow::Result<()> {
+  match grant.status.as_str() {
+    s if s == expected => Ok(()),
+    "denied" => anyhow::bail!(
+      "activation server denied the request: {}",
+      grant.reason.as_deref().unwrap_or("no reason given")
+    ),
+    other => anyhow::bail!("unexpected activation status {other:?}"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn grants_must_answer_the_request() {
+    let req = ActivationRequest::new("00000000-0000-0000-0000-000000000001", "device-1", None);
+    let grant = |device: &str, nonce: &str, status: &str| {
+      format!(
+        r#"{{"version":1,"license_id":"{}","device_id":"{device}","nonce":"{nonce}","status":"{status}","seats":2,"seats_used":2,"reason":"all 2 seats in use"}}"#,
+        req.license_id
+      )
+    };
+
+    let ok = check_grant(&req, grant("device-1", &req.nonce, "activated").as_bytes()).unwrap();
+    assert_eq!(ok.seats_used, Some(2));
+    assert!(expect_status(&ok, "activated").is_ok());
+    assert!(expect_status(&ok, "deactivated").is_err());
+
+    // Replayed for another device or request.
+    assert!(check_grant(&req, grant("device-2", &req.nonce, "activated").as_bytes()).is_err());
+    assert!(check_grant(&req, grant("device-1", "old-nonce", "activated").as_bytes()).is_err());
+
+    let denied = check_grant(&req, grant("device-1", &req.nonce, "denied").as_bytes()).unwrap();
+    let err = expect_status(&denied, "activated").unwrap_err().to_string();
+    assert!(err.contains("all 2 seats in use"), "{err}");
+  }
+
+  #[test]
+  fn endpoint_must_be_https_and_allowlisted() {
+    let cfg = LicenseConfig {
+      allowlist_domains: vec!["license.example.com".to_string()],
+      ..LicenseConfig::default()
+    };
+    let url = validate_endpoint("https://license.example.com/v1", &cfg).unwrap();
+    assert_eq!(
+      url.join("activate").unwrap().as_str(),
+      "https://license.example.com/v1/activate"
+    );
+    assert!(validate_endpoint("http://license.example.com/v1/", &cfg).is_err());
+    assert!(validate_endpoint("https://evil.example.net/v1/", &cfg).is_err());
+  }
+}
@@ -1,17 +1,17 @@
 use crate::paths;
 use crate::runtime;
+use crate::trust::{decode_signature, Purpose, TrustSet};
 use crate::types::now_unix_ms;
 use anyhow::Context;
-use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
-use base64::Engine;
-use ed25519_dalek::{Signature, VerifyingKey};
+use defender_common::fs::write_atomic;
+use defender_common::toml_fmt::{toml_string_or_null, toml_u32_or_null, toml_u64_or_null};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
-// Public-key only. Replace with the production public key for license verification.
-// Base64url (no padding) encoded 32-byte Ed25519 public key.
-const LICENSE_PUBKEY_B64URL: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+pub mod activation;
+
+use activation::{ActivationBackend, ActivationRequest};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LicenseState {
@@ -21,6 +21,17 @@ pub enum LicenseState {
   ProInvalid,
 }
 
+impl LicenseState {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      LicenseState::Community => "community",
+      LicenseState::ProActive => "pro_active",
+      LicenseState::ProExpired => "pro_expired",
+      LicenseState::ProInvalid => "pro_invalid",
+    }
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct LicenseStatus {
   pub state: LicenseState,
@@ -31,6 +42,38 @@ pub struct LicenseStatus {
   pub last_verified_at_unix_seconds: Option<u64>,
   pub checked_at_unix_seconds: u64,
   pub reason: Option<String>,
+  /// `features` of the license file, as issued (see `has_feature`).
+  pub features: Vec<String>,
+}
+
+/// Threat feed auto-refresh (`[threat_feed] auto_refresh`).
+pub const FEATURE_AUTO_REFRESH: &str = "auto_refresh";
+/// Scheduled scans (`[scanner] schedule`).
+pub const FEATURE_SCHEDULED_SCANS: &str = "scheduled_scans";
+/// Admin alerts by webhook and e-mail (`[notifications]`).
+pub const FEATURE_ADMIN_ALERTS: &str = "admin_alerts";
+
+/// Every feature a license can grant, by the name used in its `features` list.
+pub const FEATURES: &[&str] = &[
+  FEATURE_AUTO_REFRESH,
+  FEATURE_SCHEDULED_SCANS,
+  FEATURE_ADMIN_ALERTS,
+];
+
+impl LicenseStatus {
+  /// Whether `feature` (one of `FEATURES`) is licensed: the license is active and lists it.
+  /// Licenses issued before feature flags list none of `FEATURES` and grant them all; Community
+  /// grants none.
+  pub fn has_feature(&self, feature: &str) -> bool {
+    let listed = |name: &str| self.features.iter().any(|f| f.trim() == name);
+    self.state == LicenseState::ProActive
+      && (listed(feature) || !FEATURES.iter().any(|f| listed(f)))
+  }
+}
+
+/// `status(base).has_feature(feature)`.
+pub fn has_feature(base: &Path, feature: &str) -> bool {
+  status(base).has_feature(feature)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,12 +95,22 @@ pub struct LicensePayloadV1 {
   pub issuer: Option<String>,
 }
 
+/// `activation.json`: the license activated on this device.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct ActivationState {
-  device_id: String,
-  activated_at: u64,
-  license_id: String,
-  last_verified_at: u64,
+pub struct ActivationState {
+  pub device_id: String,
+  pub activated_at: u64,
+  pub license_id: String,
+  pub last_verified_at: u64,
+  /// Name given to the device by an admin (`--license activate --name`), to tell seats apart.
+  #[serde(default)]
+  pub device_label: Option<String>,
+  /// `local` or `server` (see `activation`); files without one are local.
+  #[serde(default)]
+  pub backend: Option<String>,
+  /// Devices activated on the license when this one was, as counted by the activation server.
+  #[serde(default)]
+  pub seats_used: Option<u32>,
 }
 
 pub fn install_license(
@@ -93,6 +146,7 @@ pub fn install_license(
       last_verified_at_unix_seconds: None,
       checked_at_unix_seconds: now_unix_s(),
       reason: Some("DRY-RUN: would install license".to_string()),
+      features: payload.features.clone(),
     });
   }
 
@@ -102,8 +156,8 @@ pub fn install_license(
   let dst_json = paths::license_json_path(base);
   let dst_sig = paths::license_sig_path(base);
 
-  atomic_write_file(&dst_json, &payload_bytes)?;
-  atomic_write_file(&dst_sig, &sig_bytes)?;
+  write_atomic(&dst_json, &payload_bytes)?;
+  write_atomic(&dst_sig, &sig_bytes)?;
 
   // New license installed: require explicit activation. Do not auto-activate.
   let _ = write_status(
@@ -117,13 +171,25 @@ pub fn install_license(
       last_verified_at_unix_seconds: None,
       checked_at_unix_seconds: now_unix_s(),
       reason: Some("license installed; activation required".to_string()),
+      features: payload.features.clone(),
     },
   );
 
   Ok(status(base))
 }
 
-pub fn activate(base: &Path) -> anyhow::Result<LicenseStatus> {
+/// Longest accepted device label, in characters.
+pub const MAX_DEVICE_LABEL_CHARS: usize = 64;
+
+/// Activates the installed license on this device through `backend`. Without `device_label`, a
+/// label given at an earlier activation of the same license is kept.
+pub fn activate(
+  base: &Path,
+  backend: &dyn ActivationBackend,
+  device_label: Option<&str>,
+) -> anyhow::Result<LicenseStatus> {
+  let device_label = device_label.map(check_device_label).transpose()?;
+
   let (payload_bytes, sig_bytes) = load_license_files(base).context("load installed license")?;
 
   let (payload, state, reason) = validate_payload_and_signature(&payload_bytes, &sig_bytes)?;
@@ -139,6 +205,7 @@ pub fn activate(base: &Path) -> anyhow::Result<LicenseStatus> {
         last_verified_at_unix_seconds: None,
         checked_at_unix_seconds: now_unix_s(),
         reason,
+        features: payload.features.clone(),
       };
       if !runtime::is_dry_run() {
         write_status(base, &st)?;
@@ -165,11 +232,25 @@ pub fn activate(base: &Path) -> anyhow::Result<LicenseStatus> {
       last_verified_at_unix_seconds: Some(now_unix_s()),
       checked_at_unix_seconds: now_unix_s(),
       reason: Some("DRY-RUN: would activate license".to_string()),
+      features: payload.features.clone(),
     });
   }
 
   // Ensure we have a stable local device id without fingerprinting.
   let dev_id = get_or_create_device_id(base)?;
+  let device_label = device_label.or_else(|| {
+    read_activation_state(base)
+      .filter(|a| a.license_id == payload.license_id)
+      .and_then(|a| a.device_label)
+  });
+
+  let granted = backend
+    .activate(&ActivationRequest::new(
+      &payload.license_id,
+      &dev_id,
+      device_label.as_deref(),
+    ))
+    .with_context(|| format!("activate license ({} activation)", backend.name()))?;
 
   let now = now_unix_s();
   let act = ActivationState {
@@ -177,6 +258,9 @@ pub fn activate(base: &Path) -> anyhow::Result<LicenseStatus> {
     activated_at: now,
     license_id: payload.license_id.clone(),
     last_verified_at: now,
+    device_label,
+    backend: Some(backend.name().to_string()),
+    seats_used: granted.seats_used,
   };
 
   let act_path = paths::license_activation_path(base);
@@ -184,18 +268,29 @@ pub fn activate(base: &Path) -> anyhow::Result<LicenseStatus> {
   fs::create_dir_all(&act_dir).with_context(|| format!("create {}", act_dir.display()))?;
 
   let json = serde_json::to_vec_pretty(&act).context("serialize activation.json")?;
-  atomic_write_file(&act_path, &json)?;
+  write_atomic(&act_path, &json)?;
 
   Ok(status(base))
 }
 
-pub fn deactivate(base: &Path) -> anyhow::Result<()> {
+/// Removes this device's activation, releasing its seat through `backend` first. If that fails the
+/// activation is kept, so it can be retried.
+pub fn deactivate(base: &Path, backend: &dyn ActivationBackend) -> anyhow::Result<()> {
   if runtime::is_dry_run() {
     tracing::warn!("DRY-RUN: would deactivate license on this device");
     return Ok(());
   }
 
   let act_path = paths::license_activation_path(base);
+  if let Some(act) = read_activation(&act_path) {
+    backend
+      .deactivate(&ActivationRequest::new(
+        &act.license_id,
+        &act.device_id,
+        act.device_label.as_deref(),
+      ))
+      .with_context(|| format!("release seat ({} activation)", backend.name()))?;
+  }
   if act_path.exists() {
     fs::remove_file(&act_path).with_context(|| format!("delete {}", act_path.display()))?;
   }
@@ -217,6 +312,7 @@ pub fn status(base: &Path) -> LicenseStatus {
       last_verified_at_unix_seconds: None,
       checked_at_unix_seconds: checked,
       reason: Some("no license installed".to_string()),
+      features: Vec::new(),
     };
     if !runtime::is_dry_run() {
       let _ = write_status(base, &st);
@@ -236,6 +332,7 @@ pub fn status(base: &Path) -> LicenseStatus {
         last_verified_at_unix_seconds: None,
         checked_at_unix_seconds: checked,
         reason: Some(format!("invalid license: {e:#}")),
+        features: Vec::new(),
       };
       if !runtime::is_dry_run() {
         let _ = write_status(base, &st);
@@ -254,6 +351,7 @@ pub fn status(base: &Path) -> LicenseStatus {
       last_verified_at_unix_seconds: None,
       checked_at_unix_seconds: checked,
       reason,
+      features: payload.features.clone(),
     };
     if !runtime::is_dry_run() {
       let _ = write_status(base, &st);
@@ -279,9 +377,9 @@ pub fn status(base: &Path) -> LicenseStatus {
       last_verified_at_unix_seconds: act.as_ref().map(|a| a.last_verified_at),
       checked_at_unix_seconds: checked,
       reason: Some(
-        "license is valid but not activated on this device (local activation only; server activation planned)"
-          .to_string(),
+        "license is valid but not activated on this device (`--license activate`)".to_string(),
       ),
+      features: payload.features.clone(),
     };
     if !runtime::is_dry_run() {
       let _ = write_status(base, &st);
@@ -294,7 +392,7 @@ pub fn status(base: &Path) -> LicenseStatus {
   act2.last_verified_at = checked;
   if !runtime::is_dry_run() {
     if let Ok(json) = serde_json::to_vec_pretty(&act2) {
-      let _ = atomic_write_file(&act_path, &json);
+      let _ = write_atomic(&act_path, &json);
     }
   }
 
@@ -307,6 +405,7 @@ pub fn status(base: &Path) -> LicenseStatus {
     last_verified_at_unix_seconds: Some(checked),
     checked_at_unix_seconds: checked,
     reason,
+    features: payload.features.clone(),
   };
   if !runtime::is_dry_run() {
     let _ = write_status(base, &st);
@@ -326,36 +425,13 @@ fn validate_payload_and_signature(
 }
 
 fn verify_signature(payload_bytes: &[u8], sig_bytes_raw: &[u8]) -> anyhow::Result<()> {
-  let sig_bytes = normalize_sig_bytes(sig_bytes_raw)?;
-  if sig_bytes.len() != 64 {
-    anyhow::bail!("invalid signature length (expected 64 bytes)");
-  }
-  let mut sig_arr = [0u8; 64];
-  sig_arr.copy_from_slice(&sig_bytes);
-  let sig = Signature::from_bytes(&sig_arr);
-
-  let key = verifying_key().context("load embedded public key")?;
-  key
-    .verify_strict(payload_bytes, &sig)
-    .context("signature verification failed")?;
-  Ok(())
-}
+  let sig = decode_signature(sig_bytes_raw)?;
 
-fn normalize_sig_bytes(raw: &[u8]) -> anyhow::Result<Vec<u8>> {
-  if raw.len() == 64 {
-    return Ok(raw.to_vec());
-  }
-  // Support base64/base64url text signature files for ease of distribution.
-  let text = std::str::from_utf8(raw).context("signature file must be raw bytes or UTF-8")?;
-  let t = text.trim();
-  if t.is_empty() {
-    anyhow::bail!("empty signature");
-  }
-
-  URL_SAFE_NO_PAD
-    .decode(t.as_bytes())
-    .or_else(|_| STANDARD.decode(t.as_bytes()))
-    .context("decode signature (base64 or base64url)")
+  // Embedded or key-manifest license keys; see `trust`.
+  let keys = TrustSet::load(Purpose::License);
+  let key = keys.verify(payload_bytes, &sig)?;
+  tracing::debug!(key_id = %key.key_id, "license signed by vendor key");
+  Ok(())
 }
 
 fn validate_fields(payload: &LicensePayloadV1) -> (LicenseState, Option<String>) {
@@ -409,18 +485,6 @@ fn validate_fields(payload: &LicensePayloadV1) -> (LicenseState, Option<String>)
   (LicenseState::ProActive, None)
 }
 
-fn verifying_key() -> anyhow::Result<VerifyingKey> {
-  let pk = URL_SAFE_NO_PAD
-    .decode(LICENSE_PUBKEY_B64URL.as_bytes())
-    .context("decode embedded public key base64url")?;
-  if pk.len() != 32 {
-    anyhow::bail!("embedded public key must be 32 bytes (ed25519)");
-  }
-  let mut arr = [0u8; 32];
-  arr.copy_from_slice(&pk);
-  Ok(VerifyingKey::from_bytes(&arr)?)
-}
-
 fn load_license_files(base: &Path) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
   let json_path = paths::license_json_path(base);
   let sig_path = paths::license_sig_path(base);
@@ -432,11 +496,38 @@ fn load_license_files(base: &Path) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
   Ok((payload_bytes, sig_bytes))
 }
 
+/// This device's activation, if any.
+pub fn read_activation_state(base: &Path) -> Option<ActivationState> {
+  read_activation(&paths::license_activation_path(base))
+}
+
 fn read_activation(path: &Path) -> Option<ActivationState> {
   let bytes = fs::read(path).ok()?;
   serde_json::from_slice(&bytes).ok()
 }
 
+/// This device's ID, if one was created.
+pub fn device_id(base: &Path) -> Option<String> {
+  let id = fs::read_to_string(paths::device_id_path(base)).ok()?;
+  let id = id.trim();
+  uuid::Uuid::parse_str(id).is_ok().then(|| id.to_string())
+}
+
+/// Trimmed label, 1 to `MAX_DEVICE_LABEL_CHARS` printable characters.
+fn check_device_label(raw: &str) -> anyhow::Result<String> {
+  let label = raw.trim();
+  if label.is_empty() {
+    anyhow::bail!("device label must not be empty");
+  }
+  if label.chars().count() > MAX_DEVICE_LABEL_CHARS {
+    anyhow::bail!("device label must be at most {MAX_DEVICE_LABEL_CHARS} characters");
+  }
+  if label.chars().any(char::is_control) {
+    anyhow::bail!("device label must not contain control characters");
+  }
+  Ok(label.to_string())
+}
+
 fn get_or_create_device_id(base: &Path) -> anyhow::Result<String> {
   let path = paths::device_id_path(base);
   if let Ok(s) = fs::read_to_string(&path) {
@@ -448,7 +539,7 @@ fn get_or_create_device_id(base: &Path) -> anyhow::Result<String> {
 
   fs::create_dir_all(base).with_context(|| format!("create {}", base.display()))?;
   let id = uuid::Uuid::new_v4().to_string();
-  atomic_write_file(&path, id.as_bytes())?;
+  write_atomic(&path, id.as_bytes())?;
   Ok(id)
 }
 
@@ -474,76 +565,10 @@ fn write_status(base: &Path, st: &LicenseStatus) -> anyhow::Result<()> {
     toml_string_or_null(st.reason.as_deref()),
   );
 
-  atomic_write_file(&status_path, content.as_bytes())?;
+  write_atomic(&status_path, content.as_bytes())?;
   Ok(())
 }
 
-fn atomic_write_file(dst: &Path, bytes: &[u8]) -> anyhow::Result<()> {
-  let dir = dst
-    .parent()
-    .context("destination has no parent directory")?;
-  fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
-
-  let tmp = tmp_path(dst);
-  fs::write(&tmp, bytes).with_context(|| format!("write {}", tmp.display()))?;
-  replace_file(&tmp, dst)?;
-  Ok(())
-}
-
-fn tmp_path(dst: &Path) -> PathBuf {
-  let name = dst.file_name().and_then(|s| s.to_str()).unwrap_or("tmp");
-  dst.with_file_name(format!("{name}.new"))
-}
-
-fn replace_file(staging: &Path, dst: &Path) -> anyhow::Result<()> {
-  let bak = dst.with_extension("bak");
-  if bak.exists() {
-    let _ = fs::remove_file(&bak);
-  }
-
-  if dst.exists() {
-    fs::rename(dst, &bak).with_context(|| format!("backup {}", dst.display()))?;
-  }
-
-  match fs::rename(staging, dst) {
-    Ok(()) => {
-      if bak.exists() {
-        let _ = fs::remove_file(&bak);
-      }
-      Ok(())
-    }
-    Err(e) => {
-      if bak.exists() {
-        let _ = fs::rename(&bak, dst);
-      }
-      Err(e).with_context(|| format!("replace {}", dst.display()))
-    }
-  }?;
-
-  Ok(())
-}
-
-fn toml_string_or_null(v: Option<&str>) -> String {
-  match v {
-    Some(s) => format!("\"{}\"", s.replace('"', "\\\"")),
-    None => "null".to_string(),
-  }
-}
-
-fn toml_u64_or_null(v: Option<u64>) -> String {
-  match v {
-    Some(x) => x.to_string(),
-    None => "null".to_string(),
-  }
-}
-
-fn toml_u32_or_null(v: Option<u32>) -> String {
-  match v {
-    Some(x) => x.to_string(),
-    None => "null".to_string(),
-  }
-}
-
 fn now_unix_s() -> u64 {
   now_unix_ms() / 1000
 }
@@ -551,7 +576,7 @@ fn now_unix_s() -> u64 {
 #[cfg(test)]
 mod tests {
   use super::*;
-  use ed25519_dalek::{Signer, SigningKey};
+  use ed25519_dalek::{Signature, Signer, SigningKey};
 
   fn sign(payload: &[u8], key: &SigningKey) -> [u8; 64] {
     let sig: Signature = key.sign(payload);
@@ -591,4 +616,41 @@ mod tests {
     let (st, _) = validate_fields(&p);
     assert_eq!(st, LicenseState::ProExpired);
   }
+
+  #[test]
+  fn features_need_an_active_license_that_lists_them() {
+    let st = |state, features: &[&str]| LicenseStatus {
+      state,
+      license_id: None,
+      plan: None,
+      seats: None,
+      expires_at_unix_seconds: None,
+      last_verified_at_unix_seconds: None,
+      checked_at_unix_seconds: 0,
+      reason: None,
+      features: features.iter().map(|f| f.to_string()).collect(),
+    };
+    let active = st(LicenseState::ProActive, &[FEATURE_AUTO_REFRESH]);
+    assert!(active.has_feature(FEATURE_AUTO_REFRESH));
+    assert!(!active.has_feature(FEATURE_SCHEDULED_SCANS));
+    // Issued before feature flags: everything.
+    assert!(st(LicenseState::ProActive, &[]).has_feature(FEATURE_ADMIN_ALERTS));
+    assert!(st(LicenseState::ProActive, &["threat_feed"]).has_feature(FEATURE_SCHEDULED_SCANS));
+    assert!(
+      !st(LicenseState::ProExpired, &[FEATURE_AUTO_REFRESH]).has_feature(FEATURE_AUTO_REFRESH)
+    );
+    assert!(!st(LicenseState::Community, &[]).has_feature(FEATURE_AUTO_REFRESH));
+  }
+
+  #[test]
+  fn device_labels_are_trimmed_and_bounded() {
+    assert_eq!(
+      check_device_label("  Front desk PC ").unwrap(),
+      "Front desk PC"
+    );
+    assert!(check_device_label("   ").is_err());
+    assert!(check_device_label("a\nb").is_err());
+    assert!(check_device_label(&"x".repeat(MAX_DEVICE_LABEL_CHARS)).is_ok());
+    assert!(check_device_label(&"x".repeat(MAX_DEVICE_LABEL_CHARS + 1)).is_err());
+  }
 }
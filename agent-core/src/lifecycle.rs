@@ -0,0 +1,127 @@
+use crate::kill_switch;
+use crate::paths;
+use crate::runtime;
+use crate::types::now_unix_ms;
+use defender_common::fs::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+// Start/stop bookkeeping around the agent loop, shared by the service and console agent mode. A
+// run marker notes when the agent started and whether it stopped cleanly, so the next start can
+// tell a crash or power loss from a normal stop. Stopping also hands a pending kill switch failsafe
+// (AUTO containment or a timed manual lock) to the scheduled-task restorer, and starting takes it
+// back.
+
+/// Boot times derived from uptime drift a little between runs.
+pub const BOOT_TIME_TOLERANCE_MS: u64 = 120_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunMarker {
+  pub pid: u32,
+  pub started_at_unix_ms: u64,
+  #[serde(default)]
+  pub clean_shutdown_at_unix_ms: Option<u64>,
+  /// When the machine booted, as seen by this run; tells a killed agent from a power loss.
+  #[serde(default)]
+  pub boot_unix_ms: Option<u64>,
+}
+
+/// Before the agent loop starts. Returns the previous run's marker, if any.
+pub fn on_start(base: &Path) -> Option<RunMarker> {
+  kill_switch::reclaim_failsafe();
+
+  let previous = load(base);
+  if let Some(p) = &previous {
+    if p.clean_shutdown_at_unix_ms.is_none() {
+      tracing::warn!(
+        pid = p.pid,
+        started_at_unix_ms = p.started_at_unix_ms,
+        "previous agent run did not shut down cleanly (crash, kill, or power loss)"
+      );
+    }
+  }
+  if runtime::is_dry_run() {
+    return previous;
+  }
+
+  let marker = RunMarker {
+    pid: std::process::id(),
+    started_at_unix_ms: now_unix_ms(),
+    clean_shutdown_at_unix_ms: None,
+    boot_unix_ms: boot_time_unix_ms(),
+  };
+  if let Err(e) = save(base, &marker) {
+    tracing::warn!(error = ?e, "failed to write run marker (continuing)");
+  }
+  previous
+}
+
+/// After the agent loop has stopped and flushed its state.
+pub fn on_stop(base: &Path) {
+  if let Err(e) = kill_switch::hand_off_failsafe() {
+    tracing::error!(
+      error = ?e,
+      "failed to hand the kill switch failsafe to a scheduled task; networking is only restored \
+       once the agent runs again"
+    );
+  }
+  if runtime::is_dry_run() {
+    return;
+  }
+
+  let mut marker = load(base).unwrap_or(RunMarker {
+    pid: std::process::id(),
+    started_at_unix_ms: 0,
+    clean_shutdown_at_unix_ms: None,
+    boot_unix_ms: boot_time_unix_ms(),
+  });
+  marker.clean_shutdown_at_unix_ms = Some(now_unix_ms());
+  match save(base, &marker) {
+    Ok(()) => tracing::info!("clean shutdown recorded"),
+    Err(e) => tracing::warn!(error = ?e, "failed to record clean shutdown"),
+  }
+}
+
+/// When the machine booted (now minus uptime), to the tick-count's precision.
+#[cfg(windows)]
+pub fn boot_time_unix_ms() -> Option<u64> {
+  // SAFETY: no arguments; reads the system uptime counter.
+  let uptime_ms = unsafe { windows::Win32::System::SystemInformation::GetTickCount64() };
+  now_unix_ms().checked_sub(uptime_ms)
+}
+
+#[cfg(not(windows))]
+pub fn boot_time_unix_ms() -> Option<u64> {
+  None
+}
+
+fn load(base: &Path) -> Option<RunMarker> {
+  let raw = fs::read_to_string(paths::run_marker_path(base)).ok()?;
+  toml::from_str(&raw).ok()
+}
+
+fn save(base: &Path, marker: &RunMarker) -> anyhow::Result<()> {
+  write_atomic(&paths::run_marker_path(base), &toml::to_string(marker)?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn marks_unclean_runs_until_a_clean_stop() {
+    let base = std::env::temp_dir().join(format!("aid-lifecycle-{}", uuid::Uuid::new_v4()));
+
+    assert!(on_start(&base).is_none());
+    // Stopped without `on_stop`: the next start sees an unclean marker.
+    let previous = on_start(&base).unwrap();
+    assert!(previous.clean_shutdown_at_unix_ms.is_none());
+
+    on_stop(&base);
+    let previous = on_start(&base).unwrap();
+    assert!(previous.clean_shutdown_at_unix_ms.is_some());
+
+    let _ = fs::remove_dir_all(&base);
+  }
+}
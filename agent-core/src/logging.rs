@@ -1,24 +1,42 @@
-use std::fs;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 use std::time::{Duration, SystemTime};
 
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 
+use crate::config::LoggingConfig;
+use crate::types::{now_unix_ms, utc_timestamp};
+
+// Log files are named `agent-core.log.YYYY-MM-DD` (UTC day) and rotate daily, and also when they
+// reach `max_file_mb`: the full file is renamed to `agent-core.log.YYYY-MM-DD.N`. Rotated files are
+// gzipped (`.gz`) and, besides `retention_days`, the oldest are deleted while the directory holds
+// more than `max_total_mb`, so a single noisy day can't fill the disk. The worker process logs to
+// `agent-worker.log.*` under the same limits.
+
 static FILE_GUARD: OnceLock<WorkerGuard> = OnceLock::new();
 
-pub fn init_file_only(log_dir: &Path, level: &str, retention_days: u64) -> anyhow::Result<()> {
-  init_impl(log_dir, level, retention_days, false)
+const AGENT_LOG: &str = "agent-core.log";
+const WORKER_LOG: &str = "agent-worker.log";
+const MB: u64 = 1024 * 1024;
+const DAY_MS: u64 = 86_400_000;
+
+pub fn init_file_only(log_dir: &Path, cfg: &LoggingConfig) -> anyhow::Result<()> {
+  init_impl(log_dir, AGENT_LOG, cfg, false)
 }
 
-pub fn init_file_and_stderr(
-  log_dir: &Path,
-  level: &str,
-  retention_days: u64,
-) -> anyhow::Result<()> {
-  init_impl(log_dir, level, retention_days, true)
+/// The split service's worker process logs to its own file next to the service's.
+pub fn init_worker_file_only(log_dir: &Path, cfg: &LoggingConfig) -> anyhow::Result<()> {
+  init_impl(log_dir, WORKER_LOG, cfg, false)
+}
+
+pub fn init_file_and_stderr(log_dir: &Path, cfg: &LoggingConfig) -> anyhow::Result<()> {
+  init_impl(log_dir, AGENT_LOG, cfg, true)
 }
 
 pub fn init_stderr(level: &str) -> anyhow::Result<()> {
@@ -37,15 +55,21 @@ pub fn init_stderr(level: &str) -> anyhow::Result<()> {
   Ok(())
 }
 
-fn init_impl(log_dir: &Path, level: &str, retention_days: u64, stderr: bool) -> anyhow::Result<()> {
+fn init_impl(
+  log_dir: &Path,
+  file_name: &'static str,
+  cfg: &LoggingConfig,
+  stderr: bool,
+) -> anyhow::Result<()> {
   fs::create_dir_all(log_dir)?;
-  cleanup_old_logs(log_dir, retention_days)?;
+  let file = RollingFile::new(log_dir, file_name, cfg);
+  file.maintain(now_unix_ms());
 
-  let file_appender = tracing_appender::rolling::daily(log_dir, "agent-core.log");
-  let (file_writer, guard) = tracing_appender::non_blocking(file_appender);
+  // Rotation and compression run on the non-blocking writer's thread.
+  let (file_writer, guard) = tracing_appender::non_blocking(file);
   let _ = FILE_GUARD.set(guard);
 
-  let filter = tracing_subscriber::EnvFilter::try_new(level)
+  let filter = tracing_subscriber::EnvFilter::try_new(&cfg.level)
     .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
 
   let file_layer = tracing_subscriber::fmt::layer()
@@ -74,6 +98,199 @@ fn init_impl(log_dir: &Path, level: &str, retention_days: u64, stderr: bool) ->
   Ok(())
 }
 
+/// A log file of `file_name` that rotates daily and by size (see the module comment).
+struct RollingFile {
+  dir: PathBuf,
+  file_name: &'static str,
+  max_file_bytes: u64,
+  max_total_bytes: u64,
+  compress: bool,
+  retention_days: u64,
+  file: Option<File>,
+  /// Days since 1970-01-01 of the open file.
+  day: u64,
+  size: u64,
+}
+
+impl RollingFile {
+  fn new(dir: &Path, file_name: &'static str, cfg: &LoggingConfig) -> Self {
+    Self {
+      dir: dir.to_path_buf(),
+      file_name,
+      max_file_bytes: cfg.max_file_mb.saturating_mul(MB),
+      max_total_bytes: cfg.max_total_mb.saturating_mul(MB),
+      compress: cfg.compress,
+      retention_days: cfg.retention_days,
+      file: None,
+      day: 0,
+      size: 0,
+    }
+  }
+
+  fn path(&self, day: u64) -> PathBuf {
+    let date = utc_timestamp(day * DAY_MS);
+    self.dir.join(format!("{}.{}", self.file_name, &date[..10]))
+  }
+
+  fn write_at(&mut self, buf: &[u8], now_ms: u64) -> io::Result<usize> {
+    let day = now_ms / DAY_MS;
+    if self.file.is_some() && day != self.day {
+      self.file = None;
+      self.maintain(now_ms);
+    } else if self.max_file_bytes > 0
+      && self.size > 0
+      && self.size + buf.len() as u64 > self.max_file_bytes
+    {
+      self.file = None;
+      self.rotate_by_size(day);
+      self.maintain(now_ms);
+    }
+
+    let file = match &mut self.file {
+      Some(f) => f,
+      None => {
+        let path = self.path(day);
+        let f = OpenOptions::new().create(true).append(true).open(&path)?;
+        self.size = f.metadata().map(|m| m.len()).unwrap_or(0);
+        self.day = day;
+        self.file.insert(f)
+      }
+    };
+    let n = file.write(buf)?;
+    self.size += n as u64;
+    Ok(n)
+  }
+
+  /// Renames the full file of `day` to the next free `.N`.
+  fn rotate_by_size(&self, day: u64) {
+    let current = self.path(day);
+    let name = current.file_name().unwrap_or_default().to_string_lossy();
+    let next = (1..)
+      .find(|n| {
+        !self.dir.join(format!("{name}.{n}")).exists()
+          && !self.dir.join(format!("{name}.{n}.gz")).exists()
+      })
+      .unwrap_or(1);
+    let _ = fs::rename(&current, self.dir.join(format!("{name}.{next}")));
+  }
+
+  /// Compresses this log's rotated files and applies the retention and size limits. Best effort.
+  fn maintain(&self, now_ms: u64) {
+    let active = self.path(now_ms / DAY_MS);
+    if self.compress {
+      for path in log_files(&self.dir) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path != active && name.starts_with(self.file_name) && !name.ends_with(".gz") {
+          if let Err(e) = compress(&path) {
+            tracing::warn!(path = %path.display(), error = ?e, "failed to compress log file");
+          }
+        }
+      }
+    }
+    let _ = cleanup_old_logs(&self.dir, self.retention_days);
+    enforce_total_size(&self.dir, self.max_total_bytes, now_ms);
+  }
+}
+
+impl Write for RollingFile {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.write_at(buf, now_unix_ms())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    match &mut self.file {
+      Some(f) => f.flush(),
+      None => Ok(()),
+    }
+  }
+}
+
+/// Replaces `path` with `path.gz`.
+fn compress(path: &Path) -> io::Result<()> {
+  let mut gz_name = path.as_os_str().to_owned();
+  gz_name.push(".gz");
+  let gz = PathBuf::from(gz_name);
+  let tmp = gz.with_extension("gz.tmp");
+
+  let mut input = File::open(path)?;
+  let mut encoder = GzEncoder::new(File::create(&tmp)?, Compression::default());
+  io::copy(&mut input, &mut encoder)?;
+  encoder.finish()?.sync_all()?;
+  drop(input);
+  fs::rename(&tmp, &gz)?;
+  fs::remove_file(path)
+}
+
+/// Deletes the oldest rotated log files while the logs take more than `max_bytes`. Today's files
+/// are kept: another process may be writing them.
+fn enforce_total_size(log_dir: &Path, max_bytes: u64, now_ms: u64) {
+  if max_bytes == 0 {
+    return;
+  }
+  let today = &utc_timestamp(now_ms)[..10];
+  let mut files: Vec<(PathBuf, u64)> = log_files(log_dir)
+    .into_iter()
+    .filter_map(|p| Some((fs::metadata(&p).ok()?.len(), p)))
+    .map(|(len, p)| (p, len))
+    .collect();
+  let mut total: u64 = files.iter().map(|(_, len)| len).sum();
+  files.sort_by_key(|(p, _)| log_file_order(&p.file_name().unwrap_or_default().to_string_lossy()));
+
+  for (path, len) in files {
+    if total <= max_bytes {
+      break;
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    if name.ends_with(today) {
+      continue;
+    }
+    if fs::remove_file(&path).is_ok() {
+      total = total.saturating_sub(len);
+    }
+  }
+}
+
+fn log_files(log_dir: &Path) -> Vec<PathBuf> {
+  fs::read_dir(log_dir)
+    .map(|entries| {
+      entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| is_agent_log_file(p))
+        .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Sort key putting log files in the order they were written: by day, then rotation number, with
+/// the file still named after the day last. `None` (first) for names without a day.
+pub(crate) fn log_file_order(name: &str) -> Option<(String, u32)> {
+  let (_, rest) = name.rsplit_once(".log.")?;
+  let day = rest.get(..10)?.to_string();
+  let rest = &rest[10..];
+  let rest = rest.strip_suffix(".gz").unwrap_or(rest);
+  let seq = match rest.strip_prefix('.') {
+    Some(n) => n.parse().ok()?,
+    None if rest.is_empty() => u32::MAX,
+    None => return None,
+  };
+  Some((day, seq))
+}
+
+/// The text of a log file, gzipped or not.
+pub(crate) fn read_log_file(path: &Path) -> io::Result<String> {
+  let mut text = String::new();
+  if path.extension().is_some_and(|e| e == "gz") {
+    io::Read::read_to_string(
+      &mut flate2::read::GzDecoder::new(File::open(path)?),
+      &mut text,
+    )?;
+  } else {
+    text = fs::read_to_string(path)?;
+  }
+  Ok(text)
+}
+
 fn cleanup_old_logs(log_dir: &Path, retention_days: u64) -> anyhow::Result<()> {
   if retention_days == 0 {
     return Ok(());
@@ -120,5 +337,64 @@ fn is_agent_log_file(path: &Path) -> bool {
     None => return false,
   };
 
-  name == "agent-core.log" || name.starts_with("agent-core.log.")
+  [AGENT_LOG, WORKER_LOG].iter().any(|log| {
+    name == *log
+      || name
+        .strip_prefix(log)
+        .is_some_and(|rest| rest.starts_with('.'))
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rotates_by_size_and_day_compresses_and_caps_the_total() {
+    let dir = std::env::temp_dir().join(format!("aid-logs-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let cfg = LoggingConfig {
+      retention_days: 0,
+      max_file_mb: 1,
+      compress: true,
+      max_total_mb: 0,
+      ..LoggingConfig::default()
+    };
+    let mut log = RollingFile::new(&dir, AGENT_LOG, &cfg);
+    let day1 = 1_709_164_800_000; // 2024-02-29T00:00:00Z
+    let line = vec![b'x'; 400 * 1024];
+    for _ in 0..3 {
+      log.write_at(&line, day1).unwrap();
+    }
+    log.write_at(b"next day\n", day1 + DAY_MS).unwrap();
+    log.flush().unwrap();
+
+    let mut names: Vec<String> = log_files(&dir)
+      .iter()
+      .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+      .collect();
+    names.sort_by_key(|n| log_file_order(n));
+    assert_eq!(
+      names,
+      [
+        "agent-core.log.2024-02-29.1.gz",
+        "agent-core.log.2024-02-29.gz",
+        "agent-core.log.2024-03-01",
+      ]
+    );
+    let rotated = read_log_file(&dir.join("agent-core.log.2024-02-29.1.gz")).unwrap();
+    assert_eq!(rotated.len(), 800 * 1024);
+
+    // Only the old day's files count against the cap; the oldest goes first.
+    fs::write(
+      dir.join("agent-core.log.2024-02-28"),
+      vec![b'y'; 2 * MB as usize],
+    )
+    .unwrap();
+    enforce_total_size(&dir, MB, day1 + DAY_MS);
+    assert!(!dir.join("agent-core.log.2024-02-28").exists());
+    assert!(dir.join("agent-core.log.2024-02-29.gz").exists());
+    assert!(dir.join("agent-core.log.2024-03-01").exists());
+    let _ = fs::remove_dir_all(&dir);
+  }
 }
@@ -3,6 +3,7 @@ use anyhow::Context;
 fn main() -> anyhow::Result<()> {
   let args: Vec<String> = std::env::args().collect();
   let dry_run = agent_core::runtime::configure_from_args(&args);
+  agent_core::paths::configure_from_args(&args)?;
 
   if args.iter().any(|a| a == "--version") {
     println!("{}", env!("CARGO_PKG_VERSION"));
@@ -13,6 +14,10 @@ fn main() -> anyhow::Result<()> {
     return agent_core::run_console(&args).context("run console mode");
   }
 
+  if args.iter().any(|a| a == "--worker") {
+    return agent_core::privsep::run_worker().context("run service worker");
+  }
+
   if dry_run {
     eprintln!("DRY-RUN MODE ACTIVE: service mode does not run with --dry-run; exiting cleanly.");
     return Ok(());
@@ -0,0 +1,262 @@
+use crate::config::MetricsConfig;
+use crate::paths;
+use crate::privsep::ContainmentSource;
+use crate::runtime;
+use crate::types::{now_unix_ms, Severity};
+use defender_common::fs::write_atomic;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+// Process-wide counters for fleet monitoring, rendered in the Prometheus text exposition format
+// (version 0.0.4). Counters start at zero when the process starts; Prometheus handles the reset.
+// In the split service the worker runs the detection loop and the status API, so its counters are
+// the ones exposed; containment the worker asks the core for is counted when requested.
+
+static EVENTS_PROCESSED: AtomicU64 = AtomicU64::new(0);
+static INCIDENTS_GREEN: AtomicU64 = AtomicU64::new(0);
+static INCIDENTS_YELLOW: AtomicU64 = AtomicU64::new(0);
+static INCIDENTS_RED: AtomicU64 = AtomicU64::new(0);
+static FEED_REFRESH_SUCCESS: AtomicU64 = AtomicU64::new(0);
+static FEED_REFRESH_FAILURE: AtomicU64 = AtomicU64::new(0);
+static KILLSWITCH_AUTO: AtomicU64 = AtomicU64::new(0);
+static KILLSWITCH_USER: AtomicU64 = AtomicU64::new(0);
+static SCANS: Mutex<BTreeMap<String, ScanStats>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ScanStats {
+  count: u64,
+  seconds_sum: f64,
+  last_seconds: f64,
+}
+
+pub fn events_processed(n: usize) {
+  EVENTS_PROCESSED.fetch_add(n as u64, Ordering::Relaxed);
+}
+
+pub fn incident(severity: Severity) {
+  let counter = match severity {
+    Severity::Green => &INCIDENTS_GREEN,
+    Severity::Yellow => &INCIDENTS_YELLOW,
+    Severity::Red => &INCIDENTS_RED,
+  };
+  counter.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn feed_refresh(success: bool) {
+  let counter = if success {
+    &FEED_REFRESH_SUCCESS
+  } else {
+    &FEED_REFRESH_FAILURE
+  };
+  counter.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn killswitch_activation(source: ContainmentSource) {
+  let counter = match source {
+    ContainmentSource::Auto => &KILLSWITCH_AUTO,
+    ContainmentSource::UserConfirmed => &KILLSWITCH_USER,
+  };
+  counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A scan of `mode` (`quick`, `full`, ...) took `duration_ms`.
+pub fn scan_finished(mode: &str, duration_ms: u64) {
+  let seconds = duration_ms as f64 / 1000.0;
+  if let Ok(mut scans) = SCANS.lock() {
+    let s = scans.entry(mode.to_string()).or_default();
+    s.count += 1;
+    s.seconds_sum += seconds;
+    s.last_seconds = seconds;
+  }
+}
+
+/// All metrics in the Prometheus text format.
+pub fn render() -> String {
+  let mut out = String::new();
+  let load = |c: &AtomicU64| c.load(Ordering::Relaxed);
+
+  header(
+    &mut out,
+    "ai_defender_build_info",
+    "gauge",
+    "Agent version (always 1).",
+  );
+  let _ = writeln!(
+    out,
+    "ai_defender_build_info{{version=\"{}\"}} 1",
+    env!("CARGO_PKG_VERSION")
+  );
+
+  header(
+    &mut out,
+    "ai_defender_events_processed_total",
+    "counter",
+    "Events collected and run through the rules engine.",
+  );
+  let _ = writeln!(
+    out,
+    "ai_defender_events_processed_total {}",
+    load(&EVENTS_PROCESSED)
+  );
+
+  header(
+    &mut out,
+    "ai_defender_incidents_total",
+    "counter",
+    "Incidents handled by the response engine, by severity.",
+  );
+  for (label, c) in [
+    ("green", &INCIDENTS_GREEN),
+    ("yellow", &INCIDENTS_YELLOW),
+    ("red", &INCIDENTS_RED),
+  ] {
+    let _ = writeln!(
+      out,
+      "ai_defender_incidents_total{{severity=\"{label}\"}} {}",
+      load(c)
+    );
+  }
+
+  header(
+    &mut out,
+    "ai_defender_feed_refresh_total",
+    "counter",
+    "Threat feed auto-refresh attempts, by outcome.",
+  );
+  for (label, c) in [
+    ("success", &FEED_REFRESH_SUCCESS),
+    ("failure", &FEED_REFRESH_FAILURE),
+  ] {
+    let _ = writeln!(
+      out,
+      "ai_defender_feed_refresh_total{{result=\"{label}\"}} {}",
+      load(c)
+    );
+  }
+
+  header(
+    &mut out,
+    "ai_defender_killswitch_activations_total",
+    "counter",
+    "Network kill switch activations (or requests to the service core), by source.",
+  );
+  for (label, c) in [("auto", &KILLSWITCH_AUTO), ("user", &KILLSWITCH_USER)] {
+    let _ = writeln!(
+      out,
+      "ai_defender_killswitch_activations_total{{source=\"{label}\"}} {}",
+      load(c)
+    );
+  }
+
+  let scans = SCANS.lock().map(|s| s.clone()).unwrap_or_default();
+  header(
+    &mut out,
+    "ai_defender_scan_duration_seconds",
+    "summary",
+    "Duration of scans started by the agent, by mode.",
+  );
+  for (mode, s) in &scans {
+    let _ = writeln!(
+      out,
+      "ai_defender_scan_duration_seconds_sum{{mode=\"{mode}\"}} {}",
+      s.seconds_sum
+    );
+    let _ = writeln!(
+      out,
+      "ai_defender_scan_duration_seconds_count{{mode=\"{mode}\"}} {}",
+      s.count
+    );
+  }
+  header(
+    &mut out,
+    "ai_defender_scan_last_duration_seconds",
+    "gauge",
+    "Duration of the most recent scan started by the agent, by mode.",
+  );
+  for (mode, s) in &scans {
+    let _ = writeln!(
+      out,
+      "ai_defender_scan_last_duration_seconds{{mode=\"{mode}\"}} {}",
+      s.last_seconds
+    );
+  }
+  out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+  let _ = writeln!(out, "# HELP {name} {help}");
+  let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Writes `render()` to the metrics file every `interval_seconds` when `write_file` is set.
+pub struct MetricsFile {
+  path: PathBuf,
+  last_write_unix_ms: u64,
+}
+
+impl MetricsFile {
+  pub fn new(cfg: &MetricsConfig, base: &Path) -> Self {
+    Self {
+      path: cfg
+        .file
+        .as_deref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| paths::metrics_path(base)),
+      last_write_unix_ms: 0,
+    }
+  }
+
+  pub fn tick(&mut self, cfg: &MetricsConfig) {
+    if !cfg.write_file || runtime::is_dry_run() {
+      return;
+    }
+    let now = now_unix_ms();
+    if now.saturating_sub(self.last_write_unix_ms) < cfg.interval_seconds.saturating_mul(1000) {
+      return;
+    }
+    self.last_write_unix_ms = now;
+    if let Err(e) = write_atomic(&self.path, render()) {
+      tracing::warn!(path = %self.path.display(), error = ?e, "failed to write metrics file (continuing)");
+    }
+  }
+}
+
+// A textfile collector must never see a half-written file, so write next to it and rename.
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn renders_counters_in_prometheus_text_format() {
+    events_processed(3);
+    incident(Severity::Red);
+    feed_refresh(false);
+    scan_finished("quick", 1_500);
+
+    let text = render();
+    for line in text.lines() {
+      assert!(
+        line.starts_with("# HELP ")
+          || line.starts_with("# TYPE ")
+          || line.starts_with("ai_defender_"),
+        "{line}"
+      );
+    }
+    // Tests share the counters, so only check they moved.
+    let value = |prefix: &str| -> f64 {
+      text
+        .lines()
+        .find_map(|l| l.strip_prefix(prefix))
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap()
+    };
+    assert!(value("ai_defender_events_processed_total ") >= 3.0);
+    assert!(value("ai_defender_incidents_total{severity=\"red\"} ") >= 1.0);
+    assert!(value("ai_defender_feed_refresh_total{result=\"failure\"} ") >= 1.0);
+    assert!(value("ai_defender_scan_duration_seconds_count{mode=\"quick\"} ") >= 1.0);
+    assert!(text.contains("# TYPE ai_defender_incidents_total counter"));
+  }
+}
@@ -0,0 +1,119 @@
+use crate::types::DownloadOrigin;
+use std::path::Path;
+
+// Mark of the Web: browsers, mail clients and archive tools that honor it attach a
+// `Zone.Identifier` alternate data stream to files they save from elsewhere:
+//
+//   [ZoneTransfer]
+//   ZoneId=3
+//   ReferrerUrl=https://example.com/download
+//   HostUrl=https://cdn.example.com/setup.exe
+//
+// The URLs are optional and only recorded by some writers. The stream is deleted when a user
+// clicks "Unblock", so a missing mark says little; a present one says where the file came from.
+
+/// Streams are a few hundred bytes; anything much larger isn't a zone identifier.
+#[cfg_attr(not(windows), allow(dead_code))]
+const MAX_STREAM_BYTES: u64 = 64 * 1024;
+/// URLs longer than this are cut, so a crafted mark can't bloat incidents.
+const MAX_URL_CHARS: usize = 2048;
+
+/// The download origin recorded for `path`, if it has a Mark of the Web.
+#[cfg(windows)]
+pub fn read(path: &Path) -> Option<DownloadOrigin> {
+  use std::io::Read;
+  let stream = format!("{}:Zone.Identifier", path.display());
+  let mut raw = Vec::new();
+  std::fs::File::open(stream)
+    .ok()?
+    .take(MAX_STREAM_BYTES)
+    .read_to_end(&mut raw)
+    .ok()?;
+  parse(&decode(&raw))
+}
+
+/// Alternate data streams are NTFS-only.
+#[cfg(not(windows))]
+pub fn read(_path: &Path) -> Option<DownloadOrigin> {
+  None
+}
+
+/// Most writers use ANSI/UTF-8; a few write UTF-16LE with a byte order mark.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn decode(raw: &[u8]) -> String {
+  match raw {
+    [0xFF, 0xFE, rest @ ..] => {
+      let units: Vec<u16> = rest
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+      String::from_utf16_lossy(&units)
+    }
+    [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+    _ => String::from_utf8_lossy(raw).into_owned(),
+  }
+}
+
+/// Parses a `Zone.Identifier` stream; `None` without a `ZoneId` in `[ZoneTransfer]`.
+pub fn parse(raw: &str) -> Option<DownloadOrigin> {
+  let mut in_section = false;
+  let (mut zone_id, mut host_url, mut referrer_url) = (None, None, None);
+  for line in raw.lines().map(str::trim) {
+    if line.starts_with('[') {
+      in_section = line.eq_ignore_ascii_case("[ZoneTransfer]");
+      continue;
+    }
+    let Some((key, value)) = line.split_once('=').filter(|_| in_section) else {
+      continue;
+    };
+    let value = value.trim();
+    match key.trim().to_ascii_lowercase().as_str() {
+      "zoneid" => zone_id = value.parse::<u32>().ok(),
+      "hosturl" => host_url = url(value),
+      "referrerurl" => referrer_url = url(value),
+      _ => {}
+    }
+  }
+  Some(DownloadOrigin {
+    zone_id: zone_id?,
+    host_url,
+    referrer_url,
+  })
+}
+
+fn url(value: &str) -> Option<String> {
+  (!value.is_empty()).then(|| value.chars().take(MAX_URL_CHARS).collect())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_zone_and_urls_from_the_zone_transfer_section() {
+    let origin = parse(
+      "[ZoneTransfer]\r\nZoneId=3\r\nReferrerUrl=https://example.com/get\r\nHostUrl=https://cdn.example.com/setup.exe\r\n",
+    )
+    .unwrap();
+    assert!(origin.is_internet());
+    assert_eq!(
+      origin.label(),
+      "zone=3 (Internet) host_url=https://cdn.example.com/setup.exe referrer_url=https://example.com/get"
+    );
+
+    let local = parse("[ZoneTransfer]\nZoneId=1\nHostUrl=\n").unwrap();
+    assert!(!local.is_internet() && local.host_url.is_none());
+    assert!(parse("[Other]\nZoneId=3\n").is_none());
+    assert!(parse("[ZoneTransfer]\nZoneId=x\n").is_none());
+
+    let utf16: Vec<u8> = [0xFF, 0xFE]
+      .into_iter()
+      .chain(
+        "[ZoneTransfer]\r\nZoneId=4"
+          .encode_utf16()
+          .flat_map(u16::to_le_bytes),
+      )
+      .collect();
+    assert_eq!(parse(&decode(&utf16)).unwrap().zone_id, 4);
+  }
+}
@@ -0,0 +1,176 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Parses an IP address as reported by event sources.
+///
+/// Accepts bracketed IPv6 (`[::1]`), strips zone ids (`fe80::1%12`), and canonicalizes
+/// IPv4-mapped IPv6 (`::ffff:1.2.3.4`) to plain IPv4 so the same host always compares equal.
+pub fn parse_ip(raw: &str) -> Option<IpAddr> {
+  let t = raw.trim().trim_start_matches('[').trim_end_matches(']');
+  let t = t.split('%').next().unwrap_or(t);
+  t.parse::<IpAddr>().ok().map(|ip| ip.to_canonical())
+}
+
+/// An address range in CIDR notation. A bare address is treated as a single-host range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+  network: IpAddr,
+  prefix: u8,
+}
+
+impl Cidr {
+  pub fn contains(&self, ip: &IpAddr) -> bool {
+    match (self.network, ip.to_canonical()) {
+      (IpAddr::V4(net), IpAddr::V4(ip)) => {
+        let mask = prefix_mask_u32(self.prefix);
+        u32::from(net) & mask == u32::from(ip) & mask
+      }
+      (IpAddr::V6(net), IpAddr::V6(ip)) => {
+        let mask = prefix_mask_u128(self.prefix);
+        u128::from(net) & mask == u128::from(ip) & mask
+      }
+      _ => false,
+    }
+  }
+
+  /// First and last address in the range.
+  pub fn bounds(&self) -> (IpAddr, IpAddr) {
+    match self.network {
+      IpAddr::V4(net) => {
+        let mask = prefix_mask_u32(self.prefix);
+        let first = u32::from(net) & mask;
+        (IpAddr::V4(first.into()), IpAddr::V4((first | !mask).into()))
+      }
+      IpAddr::V6(net) => {
+        let mask = prefix_mask_u128(self.prefix);
+        let first = u128::from(net) & mask;
+        (IpAddr::V6(first.into()), IpAddr::V6((first | !mask).into()))
+      }
+    }
+  }
+}
+
+impl FromStr for Cidr {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (addr, prefix) = match s.trim().split_once('/') {
+      Some((a, p)) => (a, Some(p)),
+      None => (s.trim(), None),
+    };
+    let network =
+      parse_ip(addr).ok_or_else(|| anyhow::anyhow!("invalid IP address in CIDR: {s}"))?;
+    let max = if network.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+      Some(p) => p
+        .trim()
+        .parse::<u8>()
+        .ok()
+        .filter(|p| *p <= max)
+        .ok_or_else(|| anyhow::anyhow!("invalid CIDR prefix: {s}"))?,
+      None => max,
+    };
+    Ok(Self { network, prefix })
+  }
+}
+
+impl fmt::Display for Cidr {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}/{}", self.network, self.prefix)
+  }
+}
+
+/// Loopback (`127.0.0.0/8`, `::1`) and unspecified (`0.0.0.0`, `::`) addresses.
+pub fn is_loopback(ip: &IpAddr) -> bool {
+  let ip = ip.to_canonical();
+  ip.is_loopback() || ip.is_unspecified()
+}
+
+/// RFC1918, link-local, and IPv6 unique-local / link-local ranges.
+pub fn is_private(ip: &IpAddr) -> bool {
+  match ip.to_canonical() {
+    IpAddr::V4(v4) => v4.is_private() || v4.is_link_local(),
+    IpAddr::V6(v6) => {
+      let first = v6.segments()[0];
+      (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80
+    }
+  }
+}
+
+fn prefix_mask_u32(prefix: u8) -> u32 {
+  if prefix == 0 {
+    0
+  } else {
+    u32::MAX << (32 - u32::from(prefix))
+  }
+}
+
+fn prefix_mask_u128(prefix: u8) -> u128 {
+  if prefix == 0 {
+    0
+  } else {
+    u128::MAX << (128 - u32::from(prefix))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_ip_normalizes_ipv6_forms() {
+    assert_eq!(parse_ip("::ffff:1.2.3.4"), parse_ip("1.2.3.4"));
+    assert_eq!(parse_ip("[2001:db8::1]"), parse_ip("2001:0db8:0:0:0:0:0:1"));
+    assert_eq!(parse_ip("fe80::1%12"), parse_ip("fe80::1"));
+    assert_eq!(parse_ip("not-an-ip"), None);
+  }
+
+  #[test]
+  fn cidr_matches_ipv4_and_ipv6_ranges() {
+    let v4: Cidr = "10.0.0.0/8".parse().unwrap();
+    assert!(v4.contains(&parse_ip("10.20.30.40").unwrap()));
+    assert!(v4.contains(&parse_ip("::ffff:10.1.1.1").unwrap()));
+    assert!(!v4.contains(&parse_ip("11.0.0.1").unwrap()));
+
+    let v6: Cidr = "2001:db8::/32".parse().unwrap();
+    assert!(v6.contains(&parse_ip("2001:0db8:ffff::1").unwrap()));
+    assert!(!v6.contains(&parse_ip("2001:db9::1").unwrap()));
+    assert!(!v6.contains(&parse_ip("10.0.0.1").unwrap()));
+
+    let host: Cidr = "1.2.3.4".parse().unwrap();
+    assert!(host.contains(&parse_ip("1.2.3.4").unwrap()));
+    assert!(!host.contains(&parse_ip("1.2.3.5").unwrap()));
+
+    let all: Cidr = "0.0.0.0/0".parse().unwrap();
+    assert!(all.contains(&parse_ip("8.8.8.8").unwrap()));
+  }
+
+  #[test]
+  fn classifies_loopback_and_private_ranges() {
+    for ip in ["127.0.0.1", "::1", "::ffff:127.0.0.1", "0.0.0.0"] {
+      assert!(is_loopback(&parse_ip(ip).unwrap()), "{ip}");
+    }
+    for ip in [
+      "10.1.2.3",
+      "172.16.0.1",
+      "192.168.1.1",
+      "169.254.1.1",
+      "fd00::1",
+      "fe80::1",
+    ] {
+      assert!(is_private(&parse_ip(ip).unwrap()), "{ip}");
+    }
+    for ip in ["1.2.3.4", "172.32.0.1", "2001:db8::1"] {
+      let ip = parse_ip(ip).unwrap();
+      assert!(!is_private(&ip) && !is_loopback(&ip));
+    }
+  }
+
+  #[test]
+  fn cidr_rejects_invalid_prefixes() {
+    assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    assert!("::/129".parse::<Cidr>().is_err());
+    assert!("10.0.0.0/x".parse::<Cidr>().is_err());
+  }
+}
@@ -0,0 +1,166 @@
+use crate::paths;
+use crate::runtime;
+use defender_common::fs::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// Monthly accounting of the agent's own network downloads (threat feed refresh is currently the
+// only outbound call; license activation is offline). Counts response body bytes, so TLS and
+// header overhead is not included.
+
+const MONTHS_KEPT: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Purpose {
+  ThreatFeed,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MonthUsage {
+  #[serde(default)]
+  pub threat_feed_bytes: u64,
+  #[serde(default)]
+  pub threat_feed_requests: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Ledger {
+  /// Keyed by `YYYY-MM` (UTC).
+  #[serde(default)]
+  months: BTreeMap<String, MonthUsage>,
+}
+
+pub fn record(base: &Path, purpose: Purpose, bytes: u64, now_unix_ms: u64) {
+  if runtime::is_dry_run() {
+    return;
+  }
+
+  let path = paths::network_usage_path(base);
+  let mut ledger = load(&path);
+  let month = ledger.months.entry(month_key(now_unix_ms)).or_default();
+  match purpose {
+    Purpose::ThreatFeed => {
+      month.threat_feed_bytes = month.threat_feed_bytes.saturating_add(bytes);
+      month.threat_feed_requests = month.threat_feed_requests.saturating_add(1);
+    }
+  }
+  while ledger.months.len() > MONTHS_KEPT {
+    ledger.months.pop_first();
+  }
+
+  if let Err(e) = toml::to_string_pretty(&ledger)
+    .map_err(anyhow::Error::from)
+    .and_then(|raw| write_atomic(&path, &raw))
+  {
+    tracing::warn!(error = ?e, "failed to write network usage ledger (continuing)");
+  }
+}
+
+/// Most recent months first.
+pub fn monthly(base: &Path) -> Vec<(String, MonthUsage)> {
+  load(&paths::network_usage_path(base))
+    .months
+    .into_iter()
+    .rev()
+    .collect()
+}
+
+pub fn current_month(base: &Path, now_unix_ms: u64) -> MonthUsage {
+  load(&paths::network_usage_path(base))
+    .months
+    .remove(&month_key(now_unix_ms))
+    .unwrap_or_default()
+}
+
+/// `Some(true)` when the active internet connection is metered (Windows "fixed" or "variable"
+/// cost, e.g. cellular or a Wi-Fi network marked as metered). `None` if it can't be determined.
+#[cfg(windows)]
+pub fn is_metered() -> Option<bool> {
+  use windows::Networking::Connectivity::{NetworkCostType, NetworkInformation};
+
+  let profile = NetworkInformation::GetInternetConnectionProfile().ok()?;
+  let cost = profile.GetConnectionCost().ok()?;
+  let ty = cost.NetworkCostType().ok()?;
+  Some(
+    ty == NetworkCostType::Fixed
+      || ty == NetworkCostType::Variable
+      || cost.Roaming().unwrap_or(false)
+      || cost.OverDataLimit().unwrap_or(false),
+  )
+}
+
+#[cfg(not(windows))]
+pub fn is_metered() -> Option<bool> {
+  None
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+  const KB: u64 = 1024;
+  const MB: u64 = KB * 1024;
+  if bytes >= MB {
+    format!("{:.1} MB", bytes as f64 / MB as f64)
+  } else if bytes >= KB {
+    format!("{:.1} KB", bytes as f64 / KB as f64)
+  } else {
+    format!("{bytes} B")
+  }
+}
+
+fn load(path: &Path) -> Ledger {
+  fs::read_to_string(path)
+    .ok()
+    .and_then(|raw| toml::from_str(&raw).ok())
+    .unwrap_or_default()
+}
+
+fn month_key(unix_ms: u64) -> String {
+  let (y, m) = year_month((unix_ms / 86_400_000) as i64);
+  format!("{y:04}-{m:02}")
+}
+
+// Civil-from-days (Howard Hinnant), days since 1970-01-01 -> (year, month).
+fn year_month(days: i64) -> (i64, u32) {
+  let z = days + 719_468;
+  let era = z.div_euclid(146_097);
+  let doe = z.rem_euclid(146_097);
+  let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+  let y = yoe + era * 400 + i64::from(m <= 2);
+  (y, m)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn accumulates_per_month_and_keeps_a_year() {
+    let base = std::env::temp_dir().join(format!("aid-netusage-{}", uuid::Uuid::new_v4()));
+    // 2023-11-14 and 2023-12-01 (UTC).
+    let nov = 1_700_000_000_000;
+    let dec = 1_701_388_800_000;
+    assert_eq!(month_key(nov), "2023-11");
+    assert_eq!(month_key(dec), "2023-12");
+    assert_eq!(month_key(0), "1970-01");
+
+    record(&base, Purpose::ThreatFeed, 1_000, nov);
+    record(&base, Purpose::ThreatFeed, 500, nov + 1);
+    record(&base, Purpose::ThreatFeed, 42, dec);
+
+    let usage = current_month(&base, nov);
+    assert_eq!(usage.threat_feed_bytes, 1_500);
+    assert_eq!(usage.threat_feed_requests, 2);
+    assert_eq!(monthly(&base)[0].0, "2023-12");
+
+    for i in 0..14u64 {
+      record(&base, Purpose::ThreatFeed, 1, dec + i * 31 * 86_400_000);
+    }
+    assert_eq!(monthly(&base).len(), MONTHS_KEPT);
+
+    let _ = fs::remove_dir_all(&base);
+  }
+}
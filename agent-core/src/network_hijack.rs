@@ -0,0 +1,283 @@
+// Name-resolution and proxy hijack indicators: writes to the hosts file, the WinINet proxy settings
+// (`AutoConfigURL`, `ProxyServer`, `ProxyEnable`), and static DNS servers on network interfaces.
+// Stealers and wallet drainers use these to silently route the user to look-alike infrastructure.
+//
+// Sysmon only reports the new value, so the previous one comes from a snapshot taken at startup
+// (and updated on every change the engine sees).
+
+use std::collections::HashMap;
+#[cfg(windows)]
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HijackSetting {
+  HostsFile,
+  Proxy,
+  DnsServer,
+}
+
+impl HijackSetting {
+  pub fn label(self) -> &'static str {
+    match self {
+      HijackSetting::HostsFile => "hosts_file",
+      HijackSetting::Proxy => "proxy",
+      HijackSetting::DnsServer => "dns_server",
+    }
+  }
+}
+
+const PROXY_VALUES: &[&str] = &["autoconfigurl", "proxyserver", "proxyenable"];
+
+/// Known values of the watched settings, keyed by normalized registry path (`hklm\...\nameserver`).
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+  hosts_entries: Option<Vec<String>>,
+  registry: HashMap<String, String>,
+}
+
+impl Snapshot {
+  /// Reads the current hosts file and watched registry values. Empty on non-Windows.
+  pub fn capture() -> Self {
+    Self {
+      hosts_entries: read_hosts_entries(&hosts_path()),
+      registry: read_registry_values(),
+    }
+  }
+
+  /// Records the hosts file's new content, returning the previous active entries (if known).
+  pub fn replace_hosts(&mut self, entries: Option<Vec<String>>) -> Option<Vec<String>> {
+    std::mem::replace(&mut self.hosts_entries, entries)
+  }
+
+  /// Records a new registry value, returning the previous one (if known).
+  pub fn replace_registry(&mut self, key_path: &str, value: Option<&str>) -> Option<String> {
+    let key = normalize_key(key_path);
+    match value {
+      Some(v) => self.registry.insert(key, normalize_value(v)),
+      None => self.registry.remove(&key),
+    }
+  }
+}
+
+pub fn hosts_path() -> String {
+  let root = std::env::var("SystemRoot").unwrap_or_else(|_| "C:\\Windows".to_string());
+  format!("{root}\\System32\\drivers\\etc\\hosts")
+}
+
+pub fn is_hosts_file(path: &str) -> bool {
+  path
+    .to_ascii_lowercase()
+    .replace('/', "\\")
+    .ends_with("\\system32\\drivers\\etc\\hosts")
+}
+
+/// Classifies a Sysmon `TargetObject` (key path ending in the value name).
+pub fn classify_registry_key(key_path: &str) -> Option<HijackSetting> {
+  let k = normalize_key(key_path);
+  let (parent, value_name) = k.rsplit_once('\\')?;
+
+  if parent.ends_with("\\software\\microsoft\\windows\\currentversion\\internet settings")
+    && PROXY_VALUES.contains(&value_name)
+  {
+    return Some(HijackSetting::Proxy);
+  }
+
+  let in_interfaces = parent.contains("\\services\\tcpip\\parameters\\interfaces\\")
+    || parent.contains("\\services\\tcpip6\\parameters\\interfaces\\");
+  if in_interfaces && value_name == "nameserver" {
+    return Some(HijackSetting::DnsServer);
+  }
+  None
+}
+
+/// Active (non-comment, non-empty) hosts entries with whitespace collapsed.
+pub fn parse_hosts(contents: &str) -> Vec<String> {
+  contents
+    .lines()
+    .map(|l| l.split('#').next().unwrap_or(""))
+    .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+    .filter(|l| !l.is_empty())
+    .collect()
+}
+
+pub fn read_hosts_entries(path: &str) -> Option<Vec<String>> {
+  let bytes = std::fs::read(path).ok()?;
+  Some(parse_hosts(&String::from_utf8_lossy(&bytes)))
+}
+
+// Sysmon paths use `HKLM\`/`HKU\`; `reg query` prints `HKEY_LOCAL_MACHINE\`/`HKEY_USERS\`.
+fn normalize_key(key_path: &str) -> String {
+  let k = key_path.trim().to_ascii_lowercase();
+  for (long, short) in [
+    ("hkey_local_machine\\", "hklm\\"),
+    ("hkey_users\\", "hku\\"),
+    ("hkey_current_user\\", "hkcu\\"),
+  ] {
+    if let Some(rest) = k.strip_prefix(long) {
+      return format!("{short}{rest}");
+    }
+  }
+  k
+}
+
+// Sysmon reports DWORDs as `DWORD (0x00000001)`, `reg query` as `0x1`; compare them as decimal.
+pub fn normalize_value(value: &str) -> String {
+  let v = value.trim();
+  let hex = v
+    .strip_prefix("DWORD (")
+    .and_then(|s| s.strip_suffix(')'))
+    .unwrap_or(v);
+  match hex
+    .strip_prefix("0x")
+    .and_then(|h| u64::from_str_radix(h, 16).ok())
+  {
+    Some(n) => n.to_string(),
+    None => v.to_string(),
+  }
+}
+
+#[cfg(windows)]
+fn read_registry_values() -> HashMap<String, String> {
+  let mut out = HashMap::new();
+
+  for iface_root in [
+    "HKLM\\SYSTEM\\CurrentControlSet\\Services\\Tcpip\\Parameters\\Interfaces",
+    "HKLM\\SYSTEM\\CurrentControlSet\\Services\\Tcpip6\\Parameters\\Interfaces",
+  ] {
+    if let Some(stdout) = reg_query(&[iface_root, "/s", "/v", "NameServer"]) {
+      out.extend(parse_reg_query_values(&stdout));
+    }
+  }
+
+  // Internet Settings of every loaded user hive (the service runs as SYSTEM, so HKCU is not the
+  // interactive user's).
+  let sids = reg_query(&["HKU"])
+    .map(|s| {
+      s.lines()
+        .map(str::trim)
+        .filter(|l| l.starts_with("HKEY_USERS\\") && !l.ends_with("_Classes"))
+        .map(str::to_string)
+        .collect::<Vec<_>>()
+    })
+    .unwrap_or_default();
+  for hive in sids {
+    let key = format!("{hive}\\Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings");
+    if let Some(stdout) = reg_query(&[&key]) {
+      out.extend(
+        parse_reg_query_values(&stdout)
+          .into_iter()
+          .filter(|(k, _)| classify_registry_key(k) == Some(HijackSetting::Proxy)),
+      );
+    }
+  }
+  out
+}
+
+#[cfg(not(windows))]
+fn read_registry_values() -> HashMap<String, String> {
+  HashMap::new()
+}
+
+#[cfg(windows)]
+fn reg_query(args: &[&str]) -> Option<String> {
+  let output = Command::new("reg").arg("query").args(args).output().ok()?;
+  output
+    .status
+    .success()
+    .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// `reg query` prints the key on its own line followed by `    <name>    <REG_TYPE>    <data>`.
+#[cfg_attr(not(any(windows, test)), allow(dead_code))]
+fn parse_reg_query_values(stdout: &str) -> Vec<(String, String)> {
+  let mut out = Vec::new();
+  let mut key = String::new();
+  for line in stdout.lines() {
+    if line.starts_with("HKEY_") {
+      key = line.trim().to_string();
+      continue;
+    }
+    let mut parts = line.trim().splitn(3, "    ");
+    let (Some(name), Some(ty), data) = (parts.next(), parts.next(), parts.next()) else {
+      continue;
+    };
+    if key.is_empty() || !ty.trim().starts_with("REG_") {
+      continue;
+    }
+    out.push((
+      normalize_key(&format!("{key}\\{}", name.trim())),
+      normalize_value(data.unwrap_or("")),
+    ));
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn classifies_watched_registry_values() {
+    assert_eq!(
+      classify_registry_key(
+        "HKU\\S-1-5-21-1\\Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings\\AutoConfigURL"
+      ),
+      Some(HijackSetting::Proxy)
+    );
+    assert_eq!(
+      classify_registry_key(
+        "HKLM\\System\\CurrentControlSet\\Services\\Tcpip\\Parameters\\Interfaces\\{8d1c}\\NameServer"
+      ),
+      Some(HijackSetting::DnsServer)
+    );
+    assert_eq!(
+      classify_registry_key(
+        "HKLM\\System\\CurrentControlSet\\Services\\Tcpip\\Parameters\\Interfaces\\{8d1c}\\DhcpNameServer"
+      ),
+      None
+    );
+    assert_eq!(
+      classify_registry_key(
+        "HKU\\S-1-5-21-1\\Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings\\ZoneMap\\ProxyBypass"
+      ),
+      None
+    );
+    assert!(is_hosts_file("C:\\Windows\\System32\\drivers\\etc\\HOSTS"));
+    assert!(!is_hosts_file(
+      "C:\\Windows\\System32\\drivers\\etc\\hosts.bak"
+    ));
+  }
+
+  #[test]
+  fn parses_hosts_and_reg_query_output() {
+    let hosts = "# comment\r\n127.0.0.1\tlocalhost\r\n\r\n10.0.0.5   wallet.example  # pinned\r\n";
+    assert_eq!(
+      parse_hosts(hosts),
+      vec!["127.0.0.1 localhost", "10.0.0.5 wallet.example"]
+    );
+
+    let out = "\r\nHKEY_USERS\\S-1-5-21-1\\Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings\r\n    ProxyEnable    REG_DWORD    0x1\r\n    AutoConfigURL    REG_SZ    http://pac.example/p.pac\r\n";
+    let values = parse_reg_query_values(out);
+    assert_eq!(values.len(), 2);
+    assert_eq!(
+      values[0].0,
+      "hku\\s-1-5-21-1\\software\\microsoft\\windows\\currentversion\\internet settings\\proxyenable"
+    );
+    assert_eq!(values[0].1, "1");
+    assert_eq!(normalize_value("DWORD (0x00000001)"), "1");
+  }
+
+  #[test]
+  fn snapshot_returns_previous_values() {
+    let key =
+      "HKU\\S-1\\Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings\\ProxyServer";
+    let mut s = Snapshot::default();
+    assert_eq!(s.replace_registry(key, Some("1.2.3.4:8080")), None);
+    assert_eq!(
+      s.replace_registry(&key.to_ascii_uppercase(), Some("5.6.7.8:3128")),
+      Some("1.2.3.4:8080".to_string())
+    );
+    assert_eq!(s.replace_hosts(Some(vec!["a".to_string()])), None);
+    assert_eq!(s.replace_hosts(None), Some(vec!["a".to_string()]));
+  }
+}
@@ -1,14 +1,78 @@
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
+/// Environment variable naming another data directory, for isolated instances and tests.
+pub const BASE_DIR_ENV: &str = "AI_DEFENDER_BASE_DIR";
+
+/// Set by `--base-dir`; takes precedence over `BASE_DIR_ENV`.
+static BASE_DIR_ARG: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Applies `--base-dir <dir>` from the command line, if present.
+pub fn configure_from_args(args: &[String]) -> anyhow::Result<()> {
+  match base_dir_arg(args)? {
+    Some(dir) => set_base_dir(Path::new(dir)),
+    None => Ok(()),
+  }
+}
+
+fn base_dir_arg(args: &[String]) -> anyhow::Result<Option<&str>> {
+  let Some(i) = args.iter().position(|a| a == "--base-dir") else {
+    return Ok(None);
+  };
+  let dir = args
+    .get(i + 1)
+    .filter(|d| !d.trim().is_empty() && !d.starts_with("--"))
+    .ok_or_else(|| anyhow::anyhow!("--base-dir expects a directory"))?;
+  Ok(Some(dir.trim()))
+}
+
+/// Overrides the data directory for this process (and, through `BASE_DIR_ENV`, the processes it
+/// starts; see `base_dir_override`).
+pub fn set_base_dir(dir: &Path) -> anyhow::Result<()> {
+  let dir = std::path::absolute(dir)?;
+  *BASE_DIR_ARG.lock().unwrap_or_else(|e| e.into_inner()) = Some(dir);
+  Ok(())
+}
+
+/// The data directory set by `--base-dir` or `AI_DEFENDER_BASE_DIR`, if either is. Processes the
+/// agent starts or registers (worker, scanner, failsafe task, service) are passed this one.
+pub fn base_dir_override() -> Option<PathBuf> {
+  let arg = BASE_DIR_ARG
+    .lock()
+    .unwrap_or_else(|e| e.into_inner())
+    .clone();
+  override_from(arg, std::env::var_os(BASE_DIR_ENV))
+}
+
+fn override_from(arg: Option<PathBuf>, env: Option<OsString>) -> Option<PathBuf> {
+  arg.or_else(|| env.filter(|v| !v.is_empty()).map(PathBuf::from))
+}
+
+/// Where the agent keeps its config and state: `--base-dir`, then `AI_DEFENDER_BASE_DIR`, then
+/// `%ProgramData%\AI Defender`.
 pub fn base_dir() -> anyhow::Result<PathBuf> {
-  let program_data = std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".into());
-  Ok(PathBuf::from(program_data).join("AI Defender"))
+  Ok(base_dir_from(
+    base_dir_override(),
+    std::env::var("ProgramData").ok(),
+  ))
+}
+
+fn base_dir_from(override_dir: Option<PathBuf>, program_data: Option<String>) -> PathBuf {
+  override_dir.unwrap_or_else(|| {
+    PathBuf::from(program_data.unwrap_or_else(|| "C:\\ProgramData".into())).join("AI Defender")
+  })
 }
 
 pub fn config_path(base: &Path) -> PathBuf {
   base.join("config.toml")
 }
 
+/// SHA-256 of `config.toml` as AI Defender last wrote or accepted it (self-protection).
+pub fn config_seal_path(base: &Path) -> PathBuf {
+  base.join("config.seal")
+}
+
 pub fn logs_dir(base: &Path) -> PathBuf {
   base.join("logs")
 }
@@ -17,14 +81,169 @@ pub fn killswitch_state_path(base: &Path) -> PathBuf {
   base.join("killswitch-state.toml")
 }
 
+/// When the agent last started and whether it stopped cleanly.
+pub fn run_marker_path(base: &Path) -> PathBuf {
+  base.join("agent-run.toml")
+}
+
+/// Written by the agent loop every few seconds; read by `--status`.
+pub fn heartbeat_path(base: &Path) -> PathBuf {
+  base.join("heartbeat.toml")
+}
+
+/// Prometheus text file written when `[metrics] write_file = true` and no `file` is set.
+pub fn metrics_path(base: &Path) -> PathBuf {
+  base.join("metrics.prom")
+}
+
 pub fn incidents_dir(base: &Path) -> PathBuf {
   base.join("incidents")
 }
 
+/// Outside `incidents`, which users can write to (see `self_protection::harden`).
+pub fn incident_chain_path(base: &Path) -> PathBuf {
+  base.join("incident-chain.ndjson")
+}
+
+pub fn incident_chain_key_path(base: &Path) -> PathBuf {
+  base.join("incident-chain.key")
+}
+
+pub fn scans_dir(base: &Path) -> PathBuf {
+  base.join("scans")
+}
+
+pub fn scan_history_dir(base: &Path) -> PathBuf {
+  scans_dir(base).join("history")
+}
+
+pub fn scan_running_path(base: &Path, scan_id: &str) -> PathBuf {
+  scans_dir(base).join(format!("{scan_id}.running"))
+}
+
+pub fn scan_cancel_path(base: &Path, scan_id: &str) -> PathBuf {
+  scans_dir(base).join(format!("{scan_id}.cancel"))
+}
+
+/// Files hashed by the last completed scan of `mode` (`quick`/`full`), for `scanner --diff`.
+pub fn scan_catalog_path(base: &Path, mode: &str) -> PathBuf {
+  scans_dir(base).join(format!("catalog-{mode}.tsv"))
+}
+
+/// Hashes and signature results of earlier scans, keyed by path, size and modification time.
+pub fn scan_hash_cache_path(base: &Path) -> PathBuf {
+  scans_dir(base).join("hash-cache.tsv")
+}
+
+/// Correlation state the rules engine takes back after a restart (see `rules_engine::state`).
+pub fn engine_state_path(base: &Path) -> PathBuf {
+  base.join("engine-state.json")
+}
+
+pub fn network_usage_path(base: &Path) -> PathBuf {
+  base.join("network-usage.toml")
+}
+
+pub fn health_warnings_path(base: &Path) -> PathBuf {
+  base.join("health-warnings.toml")
+}
+
+pub fn managed_dir(base: &Path) -> PathBuf {
+  base.join("managed")
+}
+
+pub fn managed_profile_json_path(base: &Path) -> PathBuf {
+  managed_dir(base).join("profile.json")
+}
+
+pub fn managed_profile_sig_path(base: &Path) -> PathBuf {
+  managed_dir(base).join("profile.sig")
+}
+
+pub fn ipc_dir(base: &Path) -> PathBuf {
+  base.join("ipc")
+}
+
+pub fn ipc_status_path(base: &Path) -> PathBuf {
+  ipc_dir(base).join("status.toml")
+}
+
+pub fn ipc_notifications_dir(base: &Path) -> PathBuf {
+  ipc_dir(base).join("notifications")
+}
+
+pub fn ipc_replies_dir(base: &Path) -> PathBuf {
+  ipc_dir(base).join("replies")
+}
+
+pub fn ipc_core_requests_dir(base: &Path) -> PathBuf {
+  ipc_dir(base).join("core")
+}
+
+pub fn ipc_scans_dir(base: &Path) -> PathBuf {
+  ipc_dir(base).join("scans")
+}
+
+pub fn recordings_dir(base: &Path) -> PathBuf {
+  base.join("recordings")
+}
+
+pub fn recording_path(base: &Path) -> PathBuf {
+  recordings_dir(base).join("events.ndjson")
+}
+
+pub fn watch_dir(base: &Path) -> PathBuf {
+  base.join("watch")
+}
+
+pub fn watchlist_path(base: &Path) -> PathBuf {
+  watch_dir(base).join("watchlist.toml")
+}
+
+pub fn watch_log_path(base: &Path) -> PathBuf {
+  watch_dir(base).join("events.ndjson")
+}
+
+pub fn debug_dir(base: &Path) -> PathBuf {
+  base.join("debug")
+}
+
+pub fn trace_pids_path(base: &Path) -> PathBuf {
+  debug_dir(base).join("trace-pids.toml")
+}
+
+pub fn trace_log_path(base: &Path) -> PathBuf {
+  debug_dir(base).join("trace.ndjson")
+}
+
+pub fn status_api_token_path(base: &Path) -> PathBuf {
+  base.join("status-api.token")
+}
+
+pub fn baseline_dir(base: &Path) -> PathBuf {
+  base.join("baseline")
+}
+
+pub fn baseline_state_path(base: &Path) -> PathBuf {
+  baseline_dir(base).join("observations.toml")
+}
+
+pub fn baseline_suggestions_path(base: &Path) -> PathBuf {
+  baseline_dir(base).join("suggested-allowlist.toml")
+}
+
+pub fn secrets_dir(base: &Path) -> PathBuf {
+  base.join("secrets")
+}
+
 pub fn sysmon_bookmark_path(base: &Path) -> PathBuf {
   base.join("sysmon-bookmark.toml")
 }
 
+pub fn powershell_bookmark_path(base: &Path) -> PathBuf {
+  base.join("powershell-bookmark.toml")
+}
+
 pub fn device_id_path(base: &Path) -> PathBuf {
   base.join("device_id.txt")
 }
@@ -49,6 +268,24 @@ pub fn license_status_path(base: &Path) -> PathBuf {
   license_dir(base).join("status.toml")
 }
 
+/// Signed key manifest for vendor signing keys (see `trust`).
+pub fn trust_dir(base: &Path) -> PathBuf {
+  base.join("trust")
+}
+
+pub fn trust_manifest_path(base: &Path) -> PathBuf {
+  trust_dir(base).join("keys.json")
+}
+
+pub fn trust_manifest_sig_path(base: &Path) -> PathBuf {
+  trust_dir(base).join("keys.sig")
+}
+
+/// Highest manifest sequence seen and every revocation ever installed.
+pub fn trust_state_path(base: &Path) -> PathBuf {
+  trust_dir(base).join("state.toml")
+}
+
 pub fn threat_feed_dir(base: &Path) -> PathBuf {
   base.join("threat-feed")
 }
@@ -68,3 +305,53 @@ pub fn threat_feed_meta_path(base: &Path) -> PathBuf {
 pub fn threat_feed_state_path(base: &Path) -> PathBuf {
   threat_feed_dir(base).join("state.toml")
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|a| a.to_string()).collect()
+  }
+
+  #[test]
+  fn base_dir_arg_takes_the_next_argument() {
+    let parsed = args(&["--console", "--base-dir", " D:\\Isolated ", "--status"]);
+    assert_eq!(base_dir_arg(&parsed).unwrap(), Some("D:\\Isolated"));
+    assert_eq!(base_dir_arg(&args(&["--console"])).unwrap(), None);
+    assert!(base_dir_arg(&args(&["--base-dir"])).is_err());
+    assert!(base_dir_arg(&args(&["--base-dir", "--status"])).is_err());
+    assert!(base_dir_arg(&args(&["--base-dir", "  "])).is_err());
+  }
+
+  #[test]
+  fn base_dir_prefers_argument_then_environment_then_program_data() {
+    let arg = Some(PathBuf::from("D:\\Arg"));
+    let env = Some(OsString::from("D:\\Env"));
+    let program_data = Some("E:\\ProgramData".to_string());
+
+    let both = override_from(arg.clone(), env.clone());
+    assert_eq!(both, arg);
+    assert_eq!(
+      base_dir_from(both, program_data.clone()),
+      PathBuf::from("D:\\Arg")
+    );
+
+    let env_only = override_from(None, env);
+    assert_eq!(
+      base_dir_from(env_only, program_data.clone()),
+      PathBuf::from("D:\\Env")
+    );
+
+    // An empty variable counts as unset.
+    assert_eq!(override_from(None, Some(OsString::new())), None);
+    assert_eq!(
+      base_dir_from(None, program_data),
+      PathBuf::from("E:\\ProgramData").join("AI Defender")
+    );
+    assert_eq!(
+      base_dir_from(None, None),
+      PathBuf::from("C:\\ProgramData").join("AI Defender")
+    );
+  }
+}
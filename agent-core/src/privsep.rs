@@ -0,0 +1,587 @@
+use crate::agent::Agent;
+use crate::browsers;
+use crate::config::{self, Config, Mode, ResponseAction};
+use crate::incident_chain;
+use crate::incident_store;
+use crate::kill_switch;
+use crate::logging;
+use crate::metrics;
+use crate::paths;
+use crate::response_engine::{self, notifications};
+use crate::runtime;
+use crate::scoring;
+use crate::self_protection;
+use crate::threat_feed;
+use crate::types::{now_unix_ms, Incident};
+use crate::watchdog::{self, Liveness};
+use defender_common::fs::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+// Privilege separation for the Windows service (`[service] privilege_split`). The service process
+// is the privileged core: it owns the firewall (kill switch, failsafe), applies containment, and
+// supervises the worker. Everything that handles untrusted input runs in the worker,
+// `agent-core.exe --worker`: Sysmon event XML parsing, the rules engine, threat feed downloads,
+// scans, the status API and the tray mailbox. The worker removes every privilege from its token
+// before it reads anything, and asks the core for containment through the mailbox:
+//
+//   ipc\core\<id>.toml    worker -> core (containment requests)
+//
+// The core checks each request against the stored incident and its own config, so a compromised
+// worker can at most enable containment for a real, recent RED incident; it can never restore the
+// network or change firewall rules directly.
+
+/// Requests older than this are discarded unapplied.
+const REQUEST_TTL_MS: u64 = 15 * 60_000;
+/// The worker posts the request before it stores the incident; wait this long for the record.
+const INCIDENT_WAIT_MS: u64 = 30_000;
+/// Containment is only applied for incidents raised this recently.
+const INCIDENT_MAX_AGE_MS: u64 = 30 * 60_000;
+const WORKER_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+const WORKER_RESTART_MAX_BACKOFF_MS: u64 = 60_000;
+/// A worker that stayed up this long resets the restart backoff.
+const WORKER_STABLE_MS: u64 = 5 * 60_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContainmentSource {
+  /// Strict mode with `auto_trigger`.
+  Auto,
+  /// The user answered a containment prompt.
+  UserConfirmed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainmentRequest {
+  pub id: String,
+  pub incident_id: String,
+  pub source: ContainmentSource,
+  #[serde(default)]
+  pub user: Option<String>,
+  pub requested_at_unix_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Containment {
+  /// The kill switch was enabled in this process.
+  Enabled,
+  /// This is the worker; the core was asked to enable it.
+  Requested,
+}
+
+/// Enables the network kill switch for `incident_id`, or asks the core to when running as the
+/// worker.
+pub fn enable_containment(
+  cfg: &Config,
+  incident_id: &str,
+  source: ContainmentSource,
+  user: Option<&str>,
+) -> anyhow::Result<Containment> {
+  if !runtime::is_worker() {
+    kill_switch::enable_auto(incident_id, cfg.killswitch.failsafe_minutes)?;
+    metrics::killswitch_activation(source);
+    return Ok(Containment::Enabled);
+  }
+
+  let req = ContainmentRequest {
+    id: uuid::Uuid::new_v4().to_string(),
+    incident_id: incident_id.to_string(),
+    source,
+    user: user.map(str::to_string),
+    requested_at_unix_ms: now_unix_ms(),
+  };
+  if runtime::is_dry_run() {
+    tracing::warn!(incident_id = %incident_id, source = ?source, "DRY-RUN: would request containment from the service");
+    return Ok(Containment::Requested);
+  }
+  post_request_at(&paths::base_dir()?, &req)?;
+  metrics::killswitch_activation(source);
+  Ok(Containment::Requested)
+}
+
+fn post_request_at(base: &Path, req: &ContainmentRequest) -> anyhow::Result<()> {
+  write_atomic(&request_path(base, &req.id), &toml::to_string(req)?)?;
+  tracing::warn!(
+    request_id = %req.id,
+    incident_id = %req.incident_id,
+    source = ?req.source,
+    "containment requested from the service"
+  );
+  Ok(())
+}
+
+fn request_path(base: &Path, id: &str) -> PathBuf {
+  paths::ipc_core_requests_dir(base).join(format!("{id}.toml"))
+}
+
+fn valid_id(id: &str) -> bool {
+  !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Why the core refuses `req`, if it does.
+fn check_request(
+  cfg: &Config,
+  req: &ContainmentRequest,
+  incident: &Incident,
+  now: u64,
+) -> Result<(), String> {
+  if now.saturating_sub(req.requested_at_unix_ms) > REQUEST_TTL_MS {
+    return Err("request expired".to_string());
+  }
+  if !cfg
+    .response
+    .actions_for(incident)
+    .contains(&ResponseAction::Killswitch)
+  {
+    return Err(format!(
+      "response policy for {:?} incident does not include killswitch",
+      incident.severity
+    ));
+  }
+  if now.saturating_sub(incident.created_at_unix_ms) > INCIDENT_MAX_AGE_MS {
+    return Err("incident is too old to contain".to_string());
+  }
+  if incident
+    .actions_taken
+    .iter()
+    .any(|a| a == "killswitch_enable_auto" || a == "killswitch_enable_user_confirmed")
+  {
+    return Err("incident already contained".to_string());
+  }
+  if !cfg.killswitch.enabled {
+    return Err("kill switch is disabled by config".to_string());
+  }
+  if cfg.mode == Mode::Learning {
+    return Err("learning mode".to_string());
+  }
+  if req.source == ContainmentSource::Auto && !cfg.killswitch.auto_trigger {
+    return Err("auto-trigger disabled by config".to_string());
+  }
+  Ok(())
+}
+
+/// Valid containment requests paired with their incident. Requests for incidents not stored yet
+/// are left for a later tick; everything else is consumed.
+fn take_requests(base: &Path, now: u64) -> Vec<(ContainmentRequest, Incident)> {
+  let Ok(entries) = fs::read_dir(paths::ipc_core_requests_dir(base)) else {
+    return Vec::new();
+  };
+
+  let mut out = Vec::new();
+  for path in entries.flatten().map(|e| e.path()) {
+    if path.extension().and_then(|s| s.to_str()) != Some("toml") {
+      continue;
+    }
+    let req = fs::read_to_string(&path)
+      .ok()
+      .and_then(|raw| toml::from_str::<ContainmentRequest>(&raw).ok())
+      .filter(|r| valid_id(&r.incident_id));
+    let Some(req) = req else {
+      let _ = fs::remove_file(&path);
+      tracing::warn!(path = %path.display(), "discarding malformed containment request");
+      continue;
+    };
+    let incident = match incident_store::load(&req.incident_id) {
+      Ok(incident) => incident,
+      Err(_) if now.saturating_sub(req.requested_at_unix_ms) < INCIDENT_WAIT_MS => continue,
+      Err(_) => {
+        let _ = fs::remove_file(&path);
+        tracing::warn!(
+          request_id = %req.id,
+          incident_id = %req.incident_id,
+          "discarding containment request for unknown incident"
+        );
+        continue;
+      }
+    };
+    let _ = fs::remove_file(&path);
+    out.push((req, incident));
+  }
+  out
+}
+
+fn apply_request(cfg: &Config, req: &ContainmentRequest, incident: &Incident, now: u64) {
+  if let Err(reason) = check_request(cfg, req, incident, now) {
+    tracing::warn!(
+      request_id = %req.id,
+      incident_id = %req.incident_id,
+      reason = %reason,
+      "containment request refused"
+    );
+    return;
+  }
+
+  tracing::warn!(
+    incident_id = %req.incident_id,
+    source = ?req.source,
+    user = %req.user.as_deref().unwrap_or("unknown"),
+    "enabling network kill switch on worker request"
+  );
+  let result = kill_switch::enable_auto(&req.incident_id, cfg.killswitch.failsafe_minutes);
+  if let Err(e) = &result {
+    tracing::error!(incident_id = %req.incident_id, error = ?e, "requested kill switch failed");
+  }
+  let actions: &[&str] = match (req.source, result.is_ok()) {
+    (ContainmentSource::Auto, true) => {
+      &["killswitch_enable_auto", "killswitch_failsafe_deadline_set"]
+    }
+    (ContainmentSource::Auto, false) => &["killswitch_enable_auto_failed"],
+    (ContainmentSource::UserConfirmed, true) => &["killswitch_enable_user_confirmed"],
+    (ContainmentSource::UserConfirmed, false) => &["killswitch_enable_user_confirmed_failed"],
+  };
+  for action in actions {
+    if let Err(e) = incident_store::record_action(&req.incident_id, action) {
+      tracing::warn!(
+        incident_id = %req.incident_id,
+        error = ?e,
+        "failed to update incident record (continuing)"
+      );
+    }
+  }
+  if result.is_ok() && req.source == ContainmentSource::UserConfirmed {
+    notifications::notify_killswitch_enabled(cfg, incident, "user confirmed");
+  }
+}
+
+/// The privileged half of the split service.
+pub struct Core {
+  cfg: Config,
+  liveness: Liveness,
+}
+
+impl Core {
+  pub fn new(cfg: Config) -> Self {
+    Self {
+      cfg,
+      liveness: Liveness::default(),
+    }
+  }
+
+  /// Stamped once per loop tick, for the `watchdog`.
+  pub fn with_liveness(mut self, liveness: Liveness) -> Self {
+    self.liveness = liveness;
+    self
+  }
+
+  pub fn run(&self, stop_rx: mpsc::Receiver<()>, tick: Duration) -> anyhow::Result<()> {
+    let base = paths::base_dir()?;
+    let mut worker = WorkerProcess::new();
+    let mut self_protection = self_protection::Monitor::new(&base);
+    tracing::info!("service core started; detection runs in the worker process");
+
+    loop {
+      self.liveness.beat();
+      if stop_rx.recv_timeout(tick).is_ok() {
+        break;
+      }
+
+      let _ = kill_switch::poll_failsafe();
+      for mut incident in self_protection.tick(&self.cfg) {
+        response_engine::handle_incident(&self.cfg, &mut incident)?;
+      }
+      worker.tick();
+      let now = now_unix_ms();
+      for (req, incident) in take_requests(&base, now) {
+        apply_request(&self.cfg, &req, &incident, now);
+      }
+    }
+
+    // The worker flushes its state on the way out and may post a last request doing so.
+    worker.stop();
+    let now = now_unix_ms();
+    for (req, incident) in take_requests(&base, now) {
+      apply_request(&self.cfg, &req, &incident, now);
+    }
+    tracing::info!("service core exiting");
+    Ok(())
+  }
+}
+
+/// Keeps one worker running, restarting it with backoff when it exits. The worker's stdin is a
+/// pipe from the core; closing it (or the core dying) tells the worker to stop.
+struct WorkerProcess {
+  child: Option<(Child, Instant)>,
+  restarts: u32,
+  next_start_unix_ms: u64,
+}
+
+impl WorkerProcess {
+  fn new() -> Self {
+    Self {
+      child: None,
+      restarts: 0,
+      next_start_unix_ms: 0,
+    }
+  }
+
+  fn tick(&mut self) {
+    if let Some((child, started)) = &mut self.child {
+      match child.try_wait() {
+        Ok(None) => return,
+        Ok(Some(status)) => {
+          tracing::error!(status = %status, "worker process exited; restarting");
+        }
+        Err(e) => tracing::error!(error = ?e, "failed to poll worker process; restarting"),
+      }
+      if started.elapsed().as_millis() as u64 >= WORKER_STABLE_MS {
+        self.restarts = 0;
+      }
+      self.child = None;
+      let backoff = 1_000u64
+        .saturating_mul(1 << self.restarts.min(6))
+        .min(WORKER_RESTART_MAX_BACKOFF_MS);
+      self.restarts += 1;
+      self.next_start_unix_ms = now_unix_ms() + backoff;
+    }
+
+    if now_unix_ms() < self.next_start_unix_ms {
+      return;
+    }
+    match spawn_worker() {
+      Ok(child) => {
+        tracing::info!(pid = child.id(), "worker process started");
+        self.child = Some((child, Instant::now()));
+      }
+      Err(e) => {
+        tracing::error!(error = ?e, "failed to start worker process");
+        self.next_start_unix_ms = now_unix_ms() + WORKER_RESTART_MAX_BACKOFF_MS;
+      }
+    }
+  }
+
+  fn stop(&mut self) {
+    let Some((mut child, _)) = self.child.take() else {
+      return;
+    };
+    drop(child.stdin.take());
+    let deadline = Instant::now() + WORKER_STOP_TIMEOUT;
+    while Instant::now() < deadline {
+      if let Ok(Some(_)) = child.try_wait() {
+        tracing::info!("worker process stopped");
+        return;
+      }
+      std::thread::sleep(Duration::from_millis(100));
+    }
+    tracing::warn!("worker process did not stop in time; terminating it");
+    let _ = child.kill();
+    let _ = child.wait();
+  }
+}
+
+fn spawn_worker() -> anyhow::Result<Child> {
+  let exe = std::env::current_exe()?;
+  let child = Command::new(exe)
+    .arg("--worker")
+    .envs(paths::base_dir_override().map(|d| (paths::BASE_DIR_ENV, d)))
+    .stdin(Stdio::piped())
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .spawn()?;
+  Ok(child)
+}
+
+/// Entry point for `agent-core.exe --worker`, started by the service core.
+pub fn run_worker() -> anyhow::Result<()> {
+  runtime::set_worker(true);
+  // Before any config, event or network input is read.
+  drop_privileges()?;
+
+  let base = paths::base_dir()?;
+  let cfg = config::load_or_default_readonly(&paths::config_path(&base))?;
+  logging::init_worker_file_only(&paths::logs_dir(&base), &cfg.logging)?;
+  threat_feed::configure(&cfg);
+  browsers::configure(&cfg.browsers);
+  scoring::configure(&cfg.scoring);
+  incident_chain::configure(&cfg.self_protection);
+
+  let (stop_tx, stop_rx) = mpsc::channel::<()>();
+  std::thread::spawn(move || {
+    // Blocks until the core closes the pipe or goes away.
+    let _ = std::io::stdin().read_to_end(&mut Vec::new());
+    let _ = stop_tx.send(());
+  });
+
+  tracing::info!("worker process started with privileges removed");
+  let tick = Duration::from_millis(500);
+  let loop_cfg = cfg.clone();
+  watchdog::supervise(&cfg, &base, stop_rx, tick, move |stop_rx, liveness| {
+    Agent::new(loop_cfg.clone())
+      .with_liveness(liveness)
+      .run(stop_rx, tick)
+  })?;
+  tracing::info!("worker process stopped");
+  Ok(())
+}
+
+/// Permanently removes every privilege from this process's token except SeChangeNotifyPrivilege
+/// (directory traversal). Removed privileges can't be re-enabled, by this process or its children.
+#[cfg(windows)]
+fn drop_privileges() -> anyhow::Result<()> {
+  use windows::core::{PCWSTR, PWSTR};
+  use windows::Win32::Foundation::{CloseHandle, BOOL, HANDLE};
+  use windows::Win32::Security::{
+    AdjustTokenPrivileges, GetTokenInformation, LookupPrivilegeNameW, TokenPrivileges,
+    LUID_AND_ATTRIBUTES, SE_PRIVILEGE_REMOVED, TOKEN_ADJUST_PRIVILEGES, TOKEN_PRIVILEGES,
+    TOKEN_QUERY,
+  };
+  use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+  let mut token = HANDLE::default();
+  // SAFETY: the pseudo process handle needs no closing; `token` is closed below.
+  unsafe {
+    OpenProcessToken(
+      GetCurrentProcess(),
+      TOKEN_QUERY | TOKEN_ADJUST_PRIVILEGES,
+      &mut token,
+    )
+  }?;
+
+  let result = (|| -> anyhow::Result<()> {
+    let mut len = 0u32;
+    // SAFETY: size query; fails with ERROR_INSUFFICIENT_BUFFER and sets `len`.
+    let _ = unsafe { GetTokenInformation(token, TokenPrivileges, None, 0, &mut len) };
+    // u64 backing keeps the buffer aligned for TOKEN_PRIVILEGES.
+    let mut buf = vec![0u64; (len as usize).div_ceil(8)];
+    // SAFETY: `buf` holds at least `len` bytes.
+    unsafe {
+      GetTokenInformation(
+        token,
+        TokenPrivileges,
+        Some(buf.as_mut_ptr().cast()),
+        len,
+        &mut len,
+      )
+    }?;
+    let privileges = buf.as_mut_ptr().cast::<TOKEN_PRIVILEGES>();
+    // SAFETY: the kernel wrote a TOKEN_PRIVILEGES with `PrivilegeCount` entries into `buf`.
+    let entries: &mut [LUID_AND_ATTRIBUTES] = unsafe {
+      std::slice::from_raw_parts_mut(
+        (*privileges).Privileges.as_mut_ptr(),
+        (*privileges).PrivilegeCount as usize,
+      )
+    };
+
+    for entry in entries.iter_mut() {
+      let mut name = [0u16; 64];
+      let mut name_len = name.len() as u32;
+      // SAFETY: `name` has room for `name_len` UTF-16 units.
+      let named = unsafe {
+        LookupPrivilegeNameW(
+          PCWSTR::null(),
+          &entry.Luid,
+          PWSTR(name.as_mut_ptr()),
+          &mut name_len,
+        )
+      };
+      let keep = named.is_ok()
+        && String::from_utf16_lossy(&name[..name_len as usize]) == "SeChangeNotifyPrivilege";
+      if !keep {
+        entry.Attributes = SE_PRIVILEGE_REMOVED;
+      }
+    }
+    // SAFETY: `privileges` is the buffer read above, with kept entries' attributes unchanged.
+    unsafe { AdjustTokenPrivileges(token, BOOL(0), Some(privileges.cast_const()), 0, None, None) }?;
+    Ok(())
+  })();
+
+  // SAFETY: `token` was opened above and is not used afterwards.
+  let _ = unsafe { CloseHandle(token) };
+  result.map_err(|e| anyhow::anyhow!("refusing to run the worker with full privileges: {e:#}"))
+}
+
+#[cfg(not(windows))]
+fn drop_privileges() -> anyhow::Result<()> {
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::KillSwitchConfig;
+  use crate::types::{Finding, Severity};
+
+  #[test]
+  fn core_only_contains_recent_red_incidents_its_config_allows() {
+    let now = 1_700_000_000_000;
+    let mut cfg = Config {
+      mode: Mode::Strict,
+      killswitch: KillSwitchConfig {
+        enabled: true,
+        auto_trigger: true,
+        failsafe_minutes: 10,
+        always_allow: Vec::new(),
+      },
+      ..Config::default()
+    };
+    let mut incident = Incident::new(vec![Finding {
+      rule_id: "R009".to_string(),
+      severity: Severity::Red,
+      description: "test".to_string(),
+      evidence: vec![],
+      timestamp_unix_ms: now,
+    }]);
+    incident.severity = Severity::Red;
+    incident.created_at_unix_ms = now;
+    let req = ContainmentRequest {
+      id: uuid::Uuid::new_v4().to_string(),
+      incident_id: incident.incident_id.clone(),
+      source: ContainmentSource::Auto,
+      user: None,
+      requested_at_unix_ms: now,
+    };
+
+    assert!(check_request(&cfg, &req, &incident, now + 1).is_ok());
+    assert!(check_request(&cfg, &req, &incident, now + REQUEST_TTL_MS + 1).is_err());
+
+    let mut yellow = incident.clone();
+    yellow.severity = Severity::Yellow;
+    assert!(check_request(&cfg, &req, &yellow, now).is_err());
+
+    let mut contained = incident.clone();
+    contained
+      .actions_taken
+      .push("killswitch_enable_auto".to_string());
+    assert!(check_request(&cfg, &req, &contained, now).is_err());
+
+    // A prompt answer still works when only auto-trigger is off; an auto request doesn't.
+    cfg.killswitch.auto_trigger = false;
+    assert!(check_request(&cfg, &req, &incident, now).is_err());
+    let answered = ContainmentRequest {
+      source: ContainmentSource::UserConfirmed,
+      ..req.clone()
+    };
+    assert!(check_request(&cfg, &answered, &incident, now).is_ok());
+
+    cfg.mode = Mode::Learning;
+    assert!(check_request(&cfg, &answered, &incident, now).is_err());
+  }
+
+  #[test]
+  fn malformed_requests_are_consumed() {
+    let base = std::env::temp_dir().join(format!("aid-privsep-{}", uuid::Uuid::new_v4()));
+    let req = ContainmentRequest {
+      id: uuid::Uuid::new_v4().to_string(),
+      incident_id: "..\\..\\config".to_string(),
+      source: ContainmentSource::Auto,
+      user: None,
+      requested_at_unix_ms: now_unix_ms(),
+    };
+    post_request_at(&base, &req).unwrap();
+    write_atomic(&request_path(&base, "garbage"), "not toml").unwrap();
+
+    assert!(take_requests(&base, now_unix_ms()).is_empty());
+    assert_eq!(
+      fs::read_dir(paths::ipc_core_requests_dir(&base))
+        .unwrap()
+        .count(),
+      0
+    );
+
+    let _ = fs::remove_dir_all(&base);
+  }
+}
@@ -0,0 +1,199 @@
+use crate::config::RecordingConfig;
+use crate::paths;
+use crate::runtime;
+use crate::types::Event;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Optional capture of collected events for support and `--replay`. Events are written as the same
+// JSON the replay reader expects, one per line, after redacting user names and account SIDs from
+// every string field. The file rotates once (`events.ndjson` -> `events.1.ndjson`), so the two
+// together stay within `max_mb`.
+
+const USER_PLACEHOLDER: &str = "<user>";
+const SID_PLACEHOLDER: &str = "<sid>";
+/// Profile folders that aren't a person's name.
+const SHARED_PROFILES: &[&str] = &["public", "default", "default user", "all users"];
+
+pub struct Recorder {
+  base: PathBuf,
+}
+
+impl Recorder {
+  pub fn new(base: &Path) -> Self {
+    Self {
+      base: base.to_path_buf(),
+    }
+  }
+
+  pub fn record(&mut self, cfg: &RecordingConfig, events: &[Event]) {
+    if !cfg.enabled || events.is_empty() || runtime::is_dry_run() {
+      return;
+    }
+    if let Err(e) = self.append(cfg, events) {
+      tracing::warn!(error = ?e, "failed to write event recording (continuing)");
+    }
+  }
+
+  fn append(&self, cfg: &RecordingConfig, events: &[Event]) -> anyhow::Result<()> {
+    let mut lines = String::new();
+    for ev in events {
+      let mut value = serde_json::to_value(ev)?;
+      redact_value(&mut value);
+      lines.push_str(&serde_json::to_string(&value)?);
+      lines.push('\n');
+    }
+
+    let path = paths::recording_path(&self.base);
+    fs::create_dir_all(paths::recordings_dir(&self.base))?;
+
+    let file_budget = cfg.max_mb.saturating_mul(1024 * 1024) / 2;
+    let current = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    if current > 0 && current.saturating_add(lines.len() as u64) > file_budget {
+      fs::rename(&path, rotated_path(&path))?;
+    }
+
+    let mut f = OpenOptions::new().create(true).append(true).open(&path)?;
+    f.write_all(lines.as_bytes())?;
+    Ok(())
+  }
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+  path.with_extension("1.ndjson")
+}
+
+fn redact_value(value: &mut serde_json::Value) {
+  match value {
+    serde_json::Value::String(s) => *s = redact(s),
+    serde_json::Value::Array(items) => items.iter_mut().for_each(redact_value),
+    serde_json::Value::Object(map) => map.values_mut().for_each(redact_value),
+    _ => {}
+  }
+}
+
+/// Replaces the profile folder name in `...\Users\<name>\...` and account SIDs (`S-1-5-21-...`,
+/// as found under `HKEY_USERS`) with placeholders. Matching is case-insensitive.
+pub fn redact(s: &str) -> String {
+  redact_sids(&redact_user_dirs(s))
+}
+
+fn redact_user_dirs(s: &str) -> String {
+  const MARKER: &str = "\\users\\";
+  let lower = s.to_ascii_lowercase();
+  let mut out = String::with_capacity(s.len());
+  let mut pos = 0;
+
+  while let Some(found) = lower[pos..].find(MARKER) {
+    let name_start = pos + found + MARKER.len();
+    // Profile names can contain spaces, so an unquoted name runs to the next separator; redacting
+    // too much of a command line beats leaking a surname.
+    let name_len = lower[name_start..]
+      .find(['\\', '/', '"', '\'', ';'])
+      .unwrap_or(lower.len() - name_start);
+    let name = &lower[name_start..name_start + name_len];
+
+    out.push_str(&s[pos..name_start]);
+    if name.is_empty() || SHARED_PROFILES.contains(&name) || name == USER_PLACEHOLDER {
+      out.push_str(&s[name_start..name_start + name_len]);
+    } else {
+      out.push_str(USER_PLACEHOLDER);
+    }
+    pos = name_start + name_len;
+  }
+  out.push_str(&s[pos..]);
+  out
+}
+
+fn redact_sids(s: &str) -> String {
+  const PREFIX: &str = "s-1-5-21-";
+  let lower = s.to_ascii_lowercase();
+  let mut out = String::with_capacity(s.len());
+  let mut pos = 0;
+
+  while let Some(found) = lower[pos..].find(PREFIX) {
+    let start = pos + found;
+    let rest_start = start + PREFIX.len();
+    let rest_len = lower[rest_start..]
+      .find(|c: char| !(c.is_ascii_digit() || c == '-'))
+      .unwrap_or(lower.len() - rest_start);
+
+    out.push_str(&s[pos..rest_start]);
+    if rest_len > 0 {
+      out.push_str(SID_PLACEHOLDER);
+    }
+    pos = rest_start + rest_len;
+  }
+  out.push_str(&s[pos..]);
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::FileAccessType;
+
+  #[test]
+  fn redacts_user_names_and_sids() {
+    assert_eq!(
+      redact("C:\\Users\\alice.smith\\AppData\\Local\\Temp\\x.exe"),
+      "C:\\Users\\<user>\\AppData\\Local\\Temp\\x.exe"
+    );
+    assert_eq!(
+      redact("\"c:\\users\\Bob\\a.exe\" --out C:\\USERS\\bob"),
+      "\"c:\\users\\<user>\\a.exe\" --out C:\\USERS\\<user>"
+    );
+    assert_eq!(
+      redact("C:\\Users\\Public\\Desktop\\x.lnk"),
+      "C:\\Users\\Public\\Desktop\\x.lnk"
+    );
+    assert_eq!(
+      redact("C:\\Users\\John Smith\\x.exe"),
+      "C:\\Users\\<user>\\x.exe"
+    );
+    assert_eq!(
+      redact("HKU\\S-1-5-21-1004336348-1177238915-682003330-1001\\Software\\Run"),
+      "HKU\\S-1-5-21-<sid>\\Software\\Run"
+    );
+    assert_eq!(
+      redact("C:\\Windows\\notepad.exe"),
+      "C:\\Windows\\notepad.exe"
+    );
+  }
+
+  #[test]
+  fn records_replayable_lines_and_rotates() {
+    let base = std::env::temp_dir().join(format!("aid-recording-{}", uuid::Uuid::new_v4()));
+    let mut recorder = Recorder::new(&base);
+    let cfg = RecordingConfig {
+      enabled: true,
+      max_mb: 1,
+    };
+    let event = Event::FileAccess {
+      pid: 7,
+      image_path: Some("C:\\Users\\alice\\Downloads\\tool.exe".to_string()),
+      file_path: "C:\\Users\\alice\\AppData\\Local\\Google\\Chrome\\User Data\\Default\\Cookies"
+        .to_string(),
+      access: FileAccessType::Read,
+      process_guid: None,
+      timestamp_unix_ms: 1,
+    };
+
+    recorder.record(&cfg, std::slice::from_ref(&event));
+    let path = paths::recording_path(&base);
+    let raw = fs::read_to_string(&path).unwrap();
+    assert!(!raw.contains("alice"));
+    let back: Event = serde_json::from_str(raw.lines().next().unwrap()).unwrap();
+    assert!(matches!(back, Event::FileAccess { pid: 7, .. }));
+
+    // Half of the 1 MB budget per file; keep writing until the first file rotates.
+    let batch = vec![event; 1_000];
+    while !rotated_path(&path).exists() {
+      recorder.record(&cfg, &batch);
+    }
+    assert!(fs::metadata(&path).unwrap().len() <= 512 * 1024);
+
+    let _ = fs::remove_dir_all(&base);
+  }
+}
@@ -0,0 +1,357 @@
+use crate::config::DedupConfig;
+use crate::incident_store;
+use crate::types::{now_unix_ms, Evidence, Finding, FloodCounters, Incident, Severity};
+use std::collections::{HashMap, VecDeque};
+
+const PID_WINDOW_MS: u64 = 60_000;
+
+/// (pid, rule ID, target) of a finding; the target is what the rule is about (file, destination,
+/// registry key, ...).
+type FindingKey = (u32, String, String);
+
+/// Keeps a noisy process from raising one incident per event. Findings that repeat one seen within
+/// the window are dropped and counted on the incident that first reported them; past the per-PID
+/// rate limit, a PID's new incidents are merged into its latest one unless they raise its severity.
+/// State is in memory only, so a restart starts counting afresh.
+pub struct Deduplicator {
+  seen: HashMap<FindingKey, Seen>,
+  pids: HashMap<u32, PidIncidents>,
+}
+
+struct Seen {
+  incident_id: String,
+  last_unix_ms: u64,
+}
+
+struct PidIncidents {
+  created: VecDeque<u64>,
+  latest_id: String,
+  severity: Severity,
+}
+
+/// What gets folded into an incident that is already stored.
+#[derive(Default)]
+struct Merge {
+  counters: FloodCounters,
+  findings: Vec<Finding>,
+}
+
+impl Default for Deduplicator {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Deduplicator {
+  pub fn new() -> Self {
+    Self {
+      seen: HashMap::new(),
+      pids: HashMap::new(),
+    }
+  }
+
+  /// Incidents that should still go through the response engine; everything else has been
+  /// recorded on an earlier incident.
+  pub fn filter(&mut self, cfg: &DedupConfig, incidents: Vec<Incident>) -> Vec<Incident> {
+    if !cfg.enabled {
+      return incidents;
+    }
+    let mut stored = HashMap::new();
+    let out = self.filter_at(cfg, incidents, now_unix_ms(), &mut stored);
+    for (incident_id, merge) in stored {
+      let res = incident_store::update_incident(&incident_id, |incident| {
+        incident.findings.extend(merge.findings);
+        add_counters(incident, &merge.counters);
+      });
+      if let Err(e) = res {
+        tracing::warn!(
+          incident_id = %incident_id,
+          error = ?e,
+          "failed to record merged findings on incident (continuing)"
+        );
+      }
+    }
+    out
+  }
+
+  /// Like `filter`; merges into incidents from earlier batches are collected in `stored`.
+  fn filter_at(
+    &mut self,
+    cfg: &DedupConfig,
+    incidents: Vec<Incident>,
+    now: u64,
+    stored: &mut HashMap<String, Merge>,
+  ) -> Vec<Incident> {
+    let window_ms = cfg.window_seconds.saturating_mul(1000);
+    self
+      .seen
+      .retain(|_, s| now.saturating_sub(s.last_unix_ms) <= window_ms);
+    self.pids.retain(|_, p| {
+      while p
+        .created
+        .front()
+        .is_some_and(|t| now.saturating_sub(*t) > PID_WINDOW_MS)
+      {
+        p.created.pop_front();
+      }
+      !p.created.is_empty()
+    });
+
+    let mut out: Vec<Incident> = Vec::with_capacity(incidents.len());
+    for mut incident in incidents {
+      let mut duplicates: Vec<String> = Vec::new();
+      incident
+        .findings
+        .retain(|f| match self.seen.get_mut(&finding_key(f)) {
+          Some(seen) => {
+            seen.last_unix_ms = now;
+            duplicates.push(seen.incident_id.clone());
+            false
+          }
+          None => true,
+        });
+      for id in duplicates {
+        merge_into(&mut out, stored, &id, now, |m| {
+          m.counters.duplicate_findings += 1
+        });
+      }
+      if incident.findings.is_empty() {
+        continue;
+      }
+
+      let severity = incident.max_severity();
+      let pid = incident.findings.iter().find_map(finding_pid).unwrap_or(0);
+      let into = self.pids.get(&pid).and_then(|p| {
+        let limited = p.created.len() >= cfg.max_incidents_per_pid;
+        (pid != 0 && limited && severity_rank(severity) <= severity_rank(p.severity))
+          .then(|| p.latest_id.clone())
+      });
+
+      if let Some(id) = into {
+        tracing::info!(
+          incident_id = %incident.incident_id,
+          merged_into = %id,
+          pid,
+          "flood control: incident over the per-PID rate limit merged into an earlier one"
+        );
+        for f in &incident.findings {
+          self.seen.insert(
+            finding_key(f),
+            Seen {
+              incident_id: id.clone(),
+              last_unix_ms: now,
+            },
+          );
+        }
+        let findings = std::mem::take(&mut incident.findings);
+        merge_into(&mut out, stored, &id, now, |m| {
+          m.counters.rate_limited_incidents += 1;
+          m.findings.extend(findings);
+        });
+        continue;
+      }
+
+      for f in &incident.findings {
+        self.seen.insert(
+          finding_key(f),
+          Seen {
+            incident_id: incident.incident_id.clone(),
+            last_unix_ms: now,
+          },
+        );
+      }
+      if pid != 0 {
+        let p = self.pids.entry(pid).or_insert_with(|| PidIncidents {
+          created: VecDeque::new(),
+          latest_id: String::new(),
+          severity: Severity::Green,
+        });
+        p.created.push_back(now);
+        p.latest_id = incident.incident_id.clone();
+        p.severity = severity;
+      }
+      out.push(incident);
+    }
+    out
+  }
+}
+
+/// Applies `f` to the incident `id`: directly if it is in this batch, otherwise through `stored`.
+fn merge_into(
+  out: &mut [Incident],
+  stored: &mut HashMap<String, Merge>,
+  id: &str,
+  now: u64,
+  f: impl FnOnce(&mut Merge),
+) {
+  let mut merge = Merge::default();
+  merge.counters.last_merged_at_unix_ms = now;
+  f(&mut merge);
+  match out.iter_mut().find(|i| i.incident_id == id) {
+    Some(incident) => {
+      incident.findings.extend(merge.findings);
+      add_counters(incident, &merge.counters);
+    }
+    None => {
+      let pending = stored.entry(id.to_string()).or_default();
+      pending.findings.extend(merge.findings);
+      add_counters_to(&mut pending.counters, &merge.counters);
+    }
+  }
+}
+
+fn add_counters(incident: &mut Incident, delta: &FloodCounters) {
+  add_counters_to(incident.flood.get_or_insert_with(Default::default), delta);
+}
+
+fn add_counters_to(c: &mut FloodCounters, delta: &FloodCounters) {
+  c.duplicate_findings += delta.duplicate_findings;
+  c.rate_limited_incidents += delta.rate_limited_incidents;
+  c.last_merged_at_unix_ms = c.last_merged_at_unix_ms.max(delta.last_merged_at_unix_ms);
+}
+
+fn severity_rank(s: Severity) -> u8 {
+  match s {
+    Severity::Green => 0,
+    Severity::Yellow => 1,
+    Severity::Red => 2,
+  }
+}
+
+fn finding_pid(f: &Finding) -> Option<u32> {
+  f.evidence.iter().find_map(evidence_pid)
+}
+
+fn evidence_pid(e: &Evidence) -> Option<u32> {
+  match e {
+    Evidence::Process { pid, .. }
+    | Evidence::File { pid, .. }
+    | Evidence::Network { pid, .. }
+    | Evidence::Dns { pid, .. }
+    | Evidence::CommandLine { pid, .. }
+    | Evidence::Registry { pid, .. }
+    | Evidence::SettingChange { pid, .. }
+    | Evidence::ProcessAccess { pid, .. }
+    | Evidence::Script { pid, .. }
+    | Evidence::Clipboard { pid, .. }
+    | Evidence::Correlation { pid, .. } => Some(*pid),
+    Evidence::Note { .. } => None,
+  }
+}
+
+fn finding_key(f: &Finding) -> FindingKey {
+  let target = f
+    .evidence
+    .iter()
+    .find_map(|e| match e {
+      Evidence::File { file_path, .. } => Some(file_path.clone()),
+      Evidence::Network {
+        dest_ip, dest_port, ..
+      } => Some(format!("{dest_ip}:{dest_port}")),
+      Evidence::Dns { query_name, .. } => Some(query_name.clone()),
+      Evidence::CommandLine { command_line, .. } => Some(command_line.clone()),
+      Evidence::Registry { key_path, .. } => Some(key_path.clone()),
+      Evidence::SettingChange { target, .. } => Some(target.clone()),
+      Evidence::ProcessAccess { target_image, .. } => Some(target_image.clone()),
+      Evidence::Script { marker, .. } => Some(marker.clone()),
+      Evidence::Clipboard { current, .. } => Some(current.clone()),
+      Evidence::Correlation {
+        sensitive_file,
+        dest_ip,
+        ..
+      } => Some(format!("{sensitive_file}->{dest_ip}")),
+      Evidence::Process { .. } | Evidence::Note { .. } => None,
+    })
+    .or_else(|| {
+      f.evidence.iter().find_map(|e| match e {
+        Evidence::Process { image_path, .. } => Some(image_path.clone()),
+        _ => None,
+      })
+    })
+    .unwrap_or_default();
+  (
+    finding_pid(f).unwrap_or(0),
+    f.rule_id.clone(),
+    target.to_ascii_lowercase(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::FileAccessType;
+
+  fn incident(pid: u32, rule_id: &str, severity: Severity, file: &str) -> Incident {
+    Incident::new(vec![Finding {
+      rule_id: rule_id.to_string(),
+      severity,
+      description: "test".to_string(),
+      evidence: vec![Evidence::File {
+        pid,
+        image_path: Some("C:\\Temp\\noisy.exe".to_string()),
+        file_path: file.to_string(),
+        access: FileAccessType::Read,
+      }],
+      timestamp_unix_ms: 1_700_000_000_000,
+    }])
+  }
+
+  #[test]
+  fn repeated_findings_are_counted_on_the_first_incident() {
+    let cfg = DedupConfig::default();
+    let mut dedup = Deduplicator::new();
+    let mut stored = HashMap::new();
+    let now = 1_700_000_000_000;
+
+    let batch = (0..50)
+      .map(|_| incident(42, "R001", Severity::Yellow, "C:\\wallet.dat"))
+      .collect();
+    let out = dedup.filter_at(&cfg, batch, now, &mut stored);
+    assert_eq!(out.len(), 1);
+    assert_eq!(out[0].flood.as_ref().unwrap().duplicate_findings, 49);
+
+    // A later batch folds into the stored incident; another PID is a separate incident.
+    let batch = vec![
+      incident(42, "R001", Severity::Yellow, "C:\\WALLET.DAT"),
+      incident(43, "R001", Severity::Yellow, "C:\\wallet.dat"),
+    ];
+    let out2 = dedup.filter_at(&cfg, batch, now + 1_000, &mut stored);
+    assert_eq!(out2.len(), 1);
+    assert_eq!(stored[&out[0].incident_id].counters.duplicate_findings, 1);
+
+    // Past the window the same finding raises a new incident.
+    let later = now + cfg.window_seconds * 1000 + 2_000;
+    let batch = vec![incident(42, "R001", Severity::Yellow, "C:\\wallet.dat")];
+    assert_eq!(dedup.filter_at(&cfg, batch, later, &mut stored).len(), 1);
+  }
+
+  #[test]
+  fn per_pid_rate_limit_merges_but_lets_escalations_through() {
+    let cfg = DedupConfig {
+      max_incidents_per_pid: 2,
+      ..DedupConfig::default()
+    };
+    let mut dedup = Deduplicator::new();
+    let mut stored = HashMap::new();
+    let now = 1_700_000_000_000;
+
+    let batch = (0..5)
+      .map(|n| incident(7, "R001", Severity::Yellow, &format!("C:\\f{n}")))
+      .collect();
+    let out = dedup.filter_at(&cfg, batch, now, &mut stored);
+    assert_eq!(out.len(), 2);
+    assert_eq!(out[1].findings.len(), 4);
+    assert_eq!(out[1].flood.as_ref().unwrap().rate_limited_incidents, 3);
+
+    let batch = vec![incident(7, "R009", Severity::Red, "C:\\f9")];
+    let out = dedup.filter_at(&cfg, batch, now + 1_000, &mut stored);
+    assert_eq!(out.len(), 1);
+    assert_eq!(out[0].severity, Severity::Red);
+
+    // A minute later the PID may raise new incidents again.
+    let batch = vec![incident(7, "R001", Severity::Yellow, "C:\\g")];
+    let out = dedup.filter_at(&cfg, batch, now + PID_WINDOW_MS + 2_000, &mut stored);
+    assert_eq!(out.len(), 1);
+    assert!(stored.is_empty());
+  }
+}
@@ -1,16 +1,28 @@
-use crate::config::Config;
+pub mod dedup;
+pub mod notifications;
+pub mod targeted_scan;
+
+use crate::config::{Config, ResponseAction};
+use crate::guidance;
 use crate::incident_store;
-use crate::kill_switch;
+use crate::ipc;
+use crate::metrics;
+use crate::privsep::{self, Containment, ContainmentSource};
+use crate::scoring;
 use crate::types::{Incident, Severity};
+use std::collections::BTreeSet;
 
 pub fn handle_incident(cfg: &Config, incident: &mut Incident) -> anyhow::Result<()> {
   let incident_id = incident.incident_id.clone();
-  let sev = incident.max_severity();
-  incident.severity = sev;
+  scoring::apply(&cfg.scoring, incident);
+  guidance::attach(incident);
+  let sev = incident.severity;
+  metrics::incident(sev);
 
   tracing::info!(
     incident_id = %incident_id,
     severity = ?sev,
+    score = incident.score.as_ref().map(|s| s.total).unwrap_or(0),
     findings = incident.findings.len(),
     "incident created"
   );
@@ -25,24 +37,41 @@ pub fn handle_incident(cfg: &Config, incident: &mut Incident) -> anyhow::Result<
     );
   }
 
-  if sev == Severity::Red {
-    if let Err(e) = on_red_incident(cfg, incident) {
+  let actions = cfg.response.actions_for(incident);
+  let enforce = [
+    ResponseAction::ContainProcess,
+    ResponseAction::Killswitch,
+    ResponseAction::Quarantine,
+  ]
+  .iter()
+  .any(|a| actions.contains(a));
+  if enforce {
+    if let Err(e) = contain(cfg, incident, &actions) {
       tracing::error!(
         incident_id = %incident_id,
         error = ?e,
-        "RED incident response failed (continuing)"
+        "incident response failed (continuing)"
       );
-      incident
-        .actions_taken
-        .push("red_response_failed".to_string());
+      incident.actions_taken.push("response_failed".to_string());
     }
   } else {
     tracing::info!(
       incident_id = %incident_id,
       severity = ?sev,
-      "no automatic response for non-RED incident"
+      "no automatic response for incident (response policy)"
     );
   }
+  if sev == Severity::Red && !actions.contains(&ResponseAction::Killswitch) {
+    incident
+      .actions_taken
+      .push("killswitch_skipped_response_policy".to_string());
+  }
+  if actions.contains(&ResponseAction::Notify) {
+    notify_user(incident);
+    notifications::notify_incident(cfg, incident);
+  } else if prompt_pending(incident) {
+    notify_user(incident);
+  }
 
   match incident_store::store_incident(incident) {
     Ok(path) => tracing::info!(
@@ -60,25 +89,63 @@ pub fn handle_incident(cfg: &Config, incident: &mut Incident) -> anyhow::Result<
   Ok(())
 }
 
-pub fn on_red_incident(cfg: &Config, incident: &mut Incident) -> anyhow::Result<()> {
-  let incident_id = incident.incident_id.clone();
+// The service has no UI (session 0); the per-user tray helper picks this up via `ipc`. In strict
+// mode with `auto_trigger = false` the user is asked whether to contain instead.
+fn notify_user(incident: &mut Incident) {
+  let prompt = prompt_pending(incident);
 
-  // Placeholder: in v1 MVP we may not have a PID yet.
-  tracing::warn!(
-    incident_id = %incident_id,
-    "RED incident response: process termination not implemented (no PID)"
-  );
-  incident
-    .actions_taken
-    .push("process_kill_skipped_no_pid".to_string());
+  match ipc::notify_incident(incident, prompt) {
+    Ok(()) if prompt => incident
+      .actions_taken
+      .push("containment_prompt_posted".to_string()),
+    Ok(()) => {}
+    Err(e) => tracing::warn!(
+      incident_id = %incident.incident_id,
+      error = ?e,
+      "failed to post user notification (continuing)"
+    ),
+  }
+}
 
-  tracing::warn!(
-    incident_id = %incident_id,
-    "RED incident response: quarantine not implemented (no executable path)"
-  );
+fn prompt_pending(incident: &Incident) -> bool {
   incident
     .actions_taken
-    .push("quarantine_skipped_no_path".to_string());
+    .iter()
+    .any(|a| a == "killswitch_skipped_auto_trigger_disabled")
+}
+
+/// Runs the enforcing actions in `actions` (`[response]` policy) for `incident`.
+pub fn contain(
+  cfg: &Config,
+  incident: &mut Incident,
+  actions: &BTreeSet<ResponseAction>,
+) -> anyhow::Result<()> {
+  let incident_id = incident.incident_id.clone();
+
+  if actions.contains(&ResponseAction::ContainProcess) {
+    // Placeholder: in v1 MVP we may not have a PID yet.
+    tracing::warn!(
+      incident_id = %incident_id,
+      "incident response: process termination not implemented (no PID)"
+    );
+    incident
+      .actions_taken
+      .push("process_kill_skipped_no_pid".to_string());
+  }
+
+  if actions.contains(&ResponseAction::Quarantine) {
+    tracing::warn!(
+      incident_id = %incident_id,
+      "incident response: quarantine not implemented (no executable path)"
+    );
+    incident
+      .actions_taken
+      .push("quarantine_skipped_no_path".to_string());
+  }
+
+  if !actions.contains(&ResponseAction::Killswitch) {
+    return Ok(());
+  }
 
   if !cfg.killswitch.enabled {
     tracing::info!(
@@ -102,11 +169,11 @@ pub fn on_red_incident(cfg: &Config, incident: &mut Incident) -> anyhow::Result<
   if cfg.killswitch.auto_trigger {
     tracing::warn!(
       incident_id = %incident_id,
-      "auto-triggering network kill switch (RED only)"
+      "auto-triggering network kill switch (response policy)"
     );
 
-    match kill_switch::enable_auto(&incident_id, cfg.killswitch.failsafe_minutes) {
-      Ok(()) => {
+    match privsep::enable_containment(cfg, &incident_id, ContainmentSource::Auto, None) {
+      Ok(Containment::Enabled) => {
         incident
           .actions_taken
           .push("killswitch_enable_auto".to_string());
@@ -114,6 +181,10 @@ pub fn on_red_incident(cfg: &Config, incident: &mut Incident) -> anyhow::Result<
           .actions_taken
           .push("killswitch_failsafe_deadline_set".to_string());
       }
+      // The service core records the outcome on the stored incident.
+      Ok(Containment::Requested) => incident
+        .actions_taken
+        .push("killswitch_enable_requested".to_string()),
       Err(e) => {
         tracing::error!(
           incident_id = %incident_id,
@@ -152,19 +223,15 @@ mod tests {
         enabled: true,
         auto_trigger: true,
         failsafe_minutes: 10,
+        always_allow: Vec::new(),
       },
       ..Config::default()
     };
 
-    let mut inc = Incident::new(vec![Finding {
-      rule_id: "R009".to_string(),
-      severity: Severity::Red,
-      description: "test".to_string(),
-      evidence: vec![],
-      timestamp_unix_ms: 1_700_000_000_000,
-    }]);
+    let mut inc = Incident::new(vec![finding("R009", Severity::Red)]);
 
-    on_red_incident(&cfg, &mut inc).unwrap();
+    let actions = cfg.response.actions_for(&inc);
+    contain(&cfg, &mut inc, &actions).unwrap();
 
     assert!(inc
       .actions_taken
@@ -175,4 +242,44 @@ mod tests {
       .iter()
       .any(|a| a == "killswitch_enable_auto"));
   }
+
+  fn finding(rule_id: &str, severity: Severity) -> Finding {
+    Finding {
+      rule_id: rule_id.to_string(),
+      severity,
+      description: "test".to_string(),
+      evidence: vec![],
+      timestamp_unix_ms: 1_700_000_000_000,
+    }
+  }
+
+  #[test]
+  fn response_policy_uses_rule_actions_then_severity() {
+    let mut cfg = Config::default();
+    cfg
+      .response
+      .rules
+      .insert("r009".to_string(), vec![ResponseAction::Log]);
+    cfg.response.severity.yellow = vec![ResponseAction::Log, ResponseAction::ContainProcess];
+
+    let only_r009 = Incident::new(vec![finding("R009", Severity::Red)]);
+    assert_eq!(
+      cfg.response.actions_for(&only_r009),
+      BTreeSet::from([ResponseAction::Log])
+    );
+
+    let with_r010 = Incident::new(vec![
+      finding("R009", Severity::Red),
+      finding("R010", Severity::Red),
+    ]);
+    assert!(cfg
+      .response
+      .actions_for(&with_r010)
+      .contains(&ResponseAction::Killswitch));
+
+    let mut yellow = Incident::new(vec![finding("R008", Severity::Yellow)]);
+    let actions = cfg.response.actions_for(&yellow);
+    contain(&cfg, &mut yellow, &actions).unwrap();
+    assert_eq!(yellow.actions_taken, vec!["process_kill_skipped_no_pid"]);
+  }
 }
@@ -0,0 +1,364 @@
+use crate::config::{Config, NotificationsConfig, WebhookConfig, WebhookFormat};
+use crate::license;
+use crate::paths;
+use crate::runtime;
+use crate::secrets;
+use crate::types::{now_unix_ms, Incident, Severity};
+use anyhow::Context;
+use defender_common::hash::{hex, hmac_sha256};
+use reqwest::blocking::Client;
+use reqwest::header::{CONTENT_TYPE, USER_AGENT};
+use reqwest::redirect::Policy;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+mod smtp;
+
+// Admin alerts (webhooks, e-mail) for incidents whose response policy includes `notify` (RED by
+// default) and kill switch activation. Delivery runs on a
+// background thread so a slow or unreachable endpoint never stalls detection; each channel is
+// retried independently with backoff, and a process-wide hourly cap keeps incident storms from
+// flooding anyone.
+
+const HOUR_MS: u64 = 60 * 60_000;
+const RETRY_BACKOFF_SECONDS: &[u64] = &[2, 10, 30];
+
+static SENT_UNIX_MS: Mutex<VecDeque<u64>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+  RedIncident,
+  /// A GREEN or YELLOW incident whose response policy includes `notify`.
+  Incident,
+  KillSwitchEnabled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+  pub kind: AlertKind,
+  pub incident_id: String,
+  pub severity: Severity,
+  pub rule_ids: Vec<String>,
+  pub actions_taken: Vec<String>,
+  pub hostname: String,
+  pub created_at_unix_ms: u64,
+  pub title: String,
+  pub message: String,
+}
+
+/// Alerts admins about an incident (for RED, including whether the kill switch was enabled for it).
+pub fn notify_incident(cfg: &Config, incident: &Incident) {
+  let contained = incident
+    .actions_taken
+    .iter()
+    .any(|a| a == "killswitch_enable_auto");
+  let rules = rule_ids(incident).join(", ");
+  let (kind, message) = match incident.severity {
+    Severity::Red if contained => (
+      AlertKind::RedIncident,
+      format!("RED incident ({rules}). Network kill switch was enabled automatically."),
+    ),
+    Severity::Red => (
+      AlertKind::RedIncident,
+      format!("RED incident ({rules}). Network was not contained."),
+    ),
+    sev => (
+      AlertKind::Incident,
+      format!("{} incident ({rules}).", severity_label(sev)),
+    ),
+  };
+  send(&cfg.notifications, alert(kind, incident, message));
+}
+
+/// Alerts admins that the kill switch was enabled for an incident outside the automatic RED path
+/// (e.g. a user confirmed a containment prompt).
+pub fn notify_killswitch_enabled(cfg: &Config, incident: &Incident, reason: &str) {
+  let message = format!(
+    "Network kill switch enabled ({reason}) for incident {}.",
+    incident.incident_id
+  );
+  send(
+    &cfg.notifications,
+    alert(AlertKind::KillSwitchEnabled, incident, message),
+  );
+}
+
+fn alert(kind: AlertKind, incident: &Incident, message: String) -> Alert {
+  let hostname = std::env::var("COMPUTERNAME").unwrap_or_default();
+  let title = match kind {
+    AlertKind::RedIncident => format!("AI Defender: RED incident on {hostname}"),
+    AlertKind::Incident => format!(
+      "AI Defender: {} incident on {hostname}",
+      severity_label(incident.severity)
+    ),
+    AlertKind::KillSwitchEnabled => format!("AI Defender: network locked on {hostname}"),
+  };
+  Alert {
+    kind,
+    incident_id: incident.incident_id.clone(),
+    severity: incident.severity,
+    rule_ids: rule_ids(incident),
+    actions_taken: incident.actions_taken.clone(),
+    hostname,
+    created_at_unix_ms: now_unix_ms(),
+    title,
+    message,
+  }
+}
+
+fn severity_label(severity: Severity) -> &'static str {
+  match severity {
+    Severity::Green => "GREEN",
+    Severity::Yellow => "YELLOW",
+    Severity::Red => "RED",
+  }
+}
+
+fn rule_ids(incident: &Incident) -> Vec<String> {
+  let mut ids: Vec<String> = incident
+    .findings
+    .iter()
+    .map(|f| f.rule_id.clone())
+    .collect();
+  ids.sort();
+  ids.dedup();
+  ids
+}
+
+fn send(cfg: &NotificationsConfig, alert: Alert) {
+  if !cfg.enabled || (cfg.webhooks.is_empty() && cfg.smtp.is_none()) {
+    return;
+  }
+  let licensed =
+    paths::base_dir().is_ok_and(|base| license::has_feature(&base, license::FEATURE_ADMIN_ALERTS));
+  if !licensed {
+    tracing::info!(
+      incident_id = %alert.incident_id,
+      "admin notification skipped; admin alerts need a license with this feature"
+    );
+    return;
+  }
+
+  if runtime::is_dry_run() {
+    tracing::warn!(
+      incident_id = %alert.incident_id,
+      kind = ?alert.kind,
+      webhooks = cfg.webhooks.len(),
+      smtp = cfg.smtp.is_some(),
+      "DRY-RUN: would send admin notification"
+    );
+    return;
+  }
+
+  let allowed = {
+    let mut sent = SENT_UNIX_MS.lock().unwrap_or_else(|e| e.into_inner());
+    rate_limit(&mut sent, alert.created_at_unix_ms, cfg.max_per_hour)
+  };
+  if !allowed {
+    tracing::warn!(
+      incident_id = %alert.incident_id,
+      max_per_hour = cfg.max_per_hour,
+      "admin notification rate limit reached; alert dropped"
+    );
+    return;
+  }
+
+  let cfg = cfg.clone();
+  let spawned = std::thread::Builder::new()
+    .name("notifications".to_string())
+    .spawn(move || deliver(&cfg, &alert));
+  if let Err(e) = spawned {
+    tracing::warn!(error = ?e, "failed to start notification delivery (continuing)");
+  }
+}
+
+fn rate_limit(sent: &mut VecDeque<u64>, now: u64, max_per_hour: u32) -> bool {
+  while sent
+    .front()
+    .is_some_and(|t| now.saturating_sub(*t) >= HOUR_MS)
+  {
+    sent.pop_front();
+  }
+  if sent.len() >= max_per_hour as usize {
+    return false;
+  }
+  sent.push_back(now);
+  true
+}
+
+fn deliver(cfg: &NotificationsConfig, alert: &Alert) {
+  let base = match paths::base_dir() {
+    Ok(b) => b,
+    Err(e) => {
+      tracing::warn!(error = ?e, "notifications: base dir unavailable");
+      return;
+    }
+  };
+
+  for hook in &cfg.webhooks {
+    with_retries(cfg, alert, "webhook", || {
+      post_webhook(cfg, &base, hook, alert)
+    });
+  }
+  if let Some(smtp) = &cfg.smtp {
+    with_retries(cfg, alert, "smtp", || {
+      let password = secrets::resolve(&base, smtp.password_secret.as_deref())?;
+      smtp::send(
+        smtp,
+        password.as_deref(),
+        Duration::from_secs(cfg.timeout_seconds),
+        &alert.title,
+        &plain_text(alert),
+      )
+    });
+  }
+}
+
+fn with_retries(
+  cfg: &NotificationsConfig,
+  alert: &Alert,
+  channel: &str,
+  mut attempt: impl FnMut() -> anyhow::Result<()>,
+) {
+  for n in 0..=cfg.retries {
+    match attempt() {
+      Ok(()) => {
+        tracing::info!(
+          incident_id = %alert.incident_id,
+          channel,
+          "admin notification sent"
+        );
+        return;
+      }
+      Err(e) if n < cfg.retries => {
+        let wait = RETRY_BACKOFF_SECONDS[(n as usize).min(RETRY_BACKOFF_SECONDS.len() - 1)];
+        tracing::warn!(
+          incident_id = %alert.incident_id,
+          channel,
+          error = %format!("{e:#}"),
+          retry_in_seconds = wait,
+          "admin notification failed; retrying"
+        );
+        std::thread::sleep(Duration::from_secs(wait));
+      }
+      Err(e) => tracing::error!(
+        incident_id = %alert.incident_id,
+        channel,
+        error = %format!("{e:#}"),
+        "admin notification failed; giving up"
+      ),
+    }
+  }
+}
+
+fn post_webhook(
+  cfg: &NotificationsConfig,
+  base: &std::path::Path,
+  hook: &WebhookConfig,
+  alert: &Alert,
+) -> anyhow::Result<()> {
+  let body = webhook_body(hook.format, alert)?;
+  let client = Client::builder()
+    .timeout(Duration::from_secs(cfg.timeout_seconds))
+    .redirect(Policy::none())
+    .build()
+    .context("build HTTP client")?;
+
+  let mut req = client
+    .post(&hook.url)
+    .header(
+      USER_AGENT,
+      format!("AI-Defender/{}", env!("CARGO_PKG_VERSION")),
+    )
+    .header(CONTENT_TYPE, "application/json");
+  if let Some(key) = secrets::resolve(base, hook.hmac_secret.as_deref())? {
+    req = req.header(
+      "X-AI-Defender-Signature",
+      format!("sha256={}", hex(&hmac_sha256(key.as_bytes(), &body))),
+    );
+  }
+
+  let resp = req.body(body).send().context("send webhook")?;
+  if !resp.status().is_success() {
+    anyhow::bail!("webhook returned HTTP {}", resp.status());
+  }
+  Ok(())
+}
+
+fn webhook_body(format: WebhookFormat, alert: &Alert) -> anyhow::Result<Vec<u8>> {
+  let body = match format {
+    WebhookFormat::Json => serde_json::to_vec(alert)?,
+    WebhookFormat::Slack | WebhookFormat::Teams => serde_json::to_vec(&serde_json::json!({
+      "text": format!("*{}*\n{}\nIncident: {}", alert.title, alert.message, alert.incident_id),
+    }))?,
+  };
+  Ok(body)
+}
+
+fn plain_text(alert: &Alert) -> String {
+  format!(
+    "{}\n\nHost: {}\nIncident: {}\nSeverity: {:?}\nRules: {}\nActions: {}\n",
+    alert.message,
+    alert.hostname,
+    alert.incident_id,
+    alert.severity,
+    alert.rule_ids.join(", "),
+    alert.actions_taken.join(", ")
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::Finding;
+
+  #[test]
+  fn rate_limit_is_a_sliding_hour() {
+    let mut sent = VecDeque::new();
+    let t0 = 1_700_000_000_000;
+    assert!(rate_limit(&mut sent, t0, 2));
+    assert!(rate_limit(&mut sent, t0 + 1, 2));
+    assert!(!rate_limit(&mut sent, t0 + 2, 2));
+    assert!(rate_limit(&mut sent, t0 + HOUR_MS, 2));
+    assert!(!rate_limit(&mut sent, t0 + HOUR_MS, 2));
+  }
+
+  #[test]
+  fn hmac_matches_rfc4231() {
+    assert_eq!(
+      hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+      "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+  }
+
+  #[test]
+  fn chat_webhooks_get_a_text_payload() {
+    let mut incident = Incident::new(vec![Finding {
+      rule_id: "R009".to_string(),
+      severity: Severity::Red,
+      description: "test".to_string(),
+      evidence: vec![],
+      timestamp_unix_ms: 0,
+    }]);
+    incident.severity = Severity::Red;
+    incident
+      .actions_taken
+      .push("killswitch_enable_auto".to_string());
+    let a = alert(AlertKind::RedIncident, &incident, "msg".to_string());
+
+    let slack: serde_json::Value =
+      serde_json::from_slice(&webhook_body(WebhookFormat::Slack, &a).unwrap()).unwrap();
+    assert!(slack["text"]
+      .as_str()
+      .unwrap()
+      .contains(&incident.incident_id));
+
+    let json: serde_json::Value =
+      serde_json::from_slice(&webhook_body(WebhookFormat::Json, &a).unwrap()).unwrap();
+    assert_eq!(json["kind"], "red_incident");
+    assert_eq!(json["rule_ids"][0], "R009");
+  }
+}
@@ -0,0 +1,165 @@
+use crate::config::{SmtpConfig, SmtpSecurity};
+use anyhow::Context;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Just enough SMTP to hand one alert to a relay: TLS is mandatory (STARTTLS or implicit), AUTH
+// PLAIN when a username is configured, plain-text body.
+
+trait Stream: Read + Write {}
+impl<T: Read + Write> Stream for T {}
+
+pub fn send(
+  cfg: &SmtpConfig,
+  password: Option<&str>,
+  timeout: Duration,
+  subject: &str,
+  body: &str,
+) -> anyhow::Result<()> {
+  if cfg.to.is_empty() {
+    anyhow::bail!("smtp.to is empty");
+  }
+
+  let addr = (cfg.host.as_str(), cfg.port)
+    .to_socket_addrs()
+    .with_context(|| format!("resolve {}", cfg.host))?
+    .next()
+    .ok_or_else(|| anyhow::anyhow!("no address for {}", cfg.host))?;
+  let tcp = TcpStream::connect_timeout(&addr, timeout).context("connect")?;
+  tcp.set_read_timeout(Some(timeout))?;
+  tcp.set_write_timeout(Some(timeout))?;
+
+  let mut conn = match cfg.security {
+    SmtpSecurity::Tls => Conn::new(Box::new(tls(&cfg.host, tcp)?)),
+    SmtpSecurity::Starttls => {
+      let mut plain = Conn::new(Box::new(tcp.try_clone()?));
+      plain.expect(220)?;
+      plain.command("EHLO ai-defender", 250)?;
+      plain.command("STARTTLS", 220)?;
+      Conn::new(Box::new(tls(&cfg.host, tcp)?))
+    }
+  };
+
+  if cfg.security == SmtpSecurity::Tls {
+    conn.expect(220)?;
+  }
+  conn.command("EHLO ai-defender", 250)?;
+
+  if let Some(user) = cfg.username.as_deref().filter(|u| !u.is_empty()) {
+    let password = password.ok_or_else(|| anyhow::anyhow!("smtp.password_secret not set"))?;
+    let token = STANDARD.encode(format!("\0{user}\0{password}"));
+    conn.command(&format!("AUTH PLAIN {token}"), 235)?;
+  }
+
+  conn.command(&format!("MAIL FROM:<{}>", cfg.from), 250)?;
+  for to in &cfg.to {
+    conn.command(&format!("RCPT TO:<{to}>"), 250)?;
+  }
+  conn.command("DATA", 354)?;
+  conn.command(&message(cfg, subject, body), 250)?;
+  let _ = conn.command("QUIT", 221);
+  Ok(())
+}
+
+fn tls(host: &str, tcp: TcpStream) -> anyhow::Result<impl Read + Write> {
+  let roots = rustls::RootCertStore {
+    roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+  };
+  let config =
+    rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+      .with_safe_default_protocol_versions()?
+      .with_root_certificates(roots)
+      .with_no_client_auth();
+  let name = rustls::pki_types::ServerName::try_from(host.to_string())
+    .with_context(|| format!("invalid SMTP host name {host}"))?;
+  let conn = rustls::ClientConnection::new(Arc::new(config), name)?;
+  Ok(rustls::StreamOwned::new(conn, tcp))
+}
+
+/// Headers plus dot-stuffed body, terminated by the end-of-data line.
+fn message(cfg: &SmtpConfig, subject: &str, body: &str) -> String {
+  let mut out = format!(
+    "From: <{}>\r\nTo: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+    cfg.from,
+    cfg
+      .to
+      .iter()
+      .map(|t| format!("<{t}>"))
+      .collect::<Vec<_>>()
+      .join(", "),
+    subject.replace(['\r', '\n'], " ")
+  );
+  for line in body.lines() {
+    if line.starts_with('.') {
+      out.push('.');
+    }
+    out.push_str(line);
+    out.push_str("\r\n");
+  }
+  out.push('.');
+  out
+}
+
+struct Conn {
+  stream: BufReader<Box<dyn Stream>>,
+}
+
+impl Conn {
+  fn new(stream: Box<dyn Stream>) -> Self {
+    Self {
+      stream: BufReader::new(stream),
+    }
+  }
+
+  fn command(&mut self, line: &str, expect: u16) -> anyhow::Result<()> {
+    let w = self.stream.get_mut();
+    w.write_all(line.as_bytes())?;
+    w.write_all(b"\r\n")?;
+    w.flush()?;
+    let verb = line.split_whitespace().next().unwrap_or("DATA");
+    self.expect(expect).with_context(|| format!("SMTP {verb}"))
+  }
+
+  fn expect(&mut self, code: u16) -> anyhow::Result<()> {
+    loop {
+      let mut line = String::new();
+      if self.stream.read_line(&mut line)? == 0 {
+        anyhow::bail!("connection closed");
+      }
+      let got: u16 = line.get(..3).and_then(|c| c.parse().ok()).unwrap_or(0);
+      if got != code {
+        anyhow::bail!("unexpected reply: {}", line.trim_end());
+      }
+      // Multi-line replies use `250-` until the last `250 `.
+      if line.as_bytes().get(3) != Some(&b'-') {
+        return Ok(());
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn message_is_dot_stuffed_and_terminated() {
+    let cfg = SmtpConfig {
+      host: "smtp.example.com".to_string(),
+      port: 587,
+      security: SmtpSecurity::Starttls,
+      username: None,
+      password_secret: None,
+      from: "agent@example.com".to_string(),
+      to: vec!["a@example.com".to_string(), "b@example.com".to_string()],
+    };
+    let msg = message(&cfg, "RED\r\nBcc: x", "line\n.hidden\nend");
+    assert!(msg.contains("To: <a@example.com>, <b@example.com>\r\n"));
+    assert!(msg.contains("Subject: RED  Bcc: x\r\n"));
+    assert!(msg.ends_with("line\r\n..hidden\r\nend\r\n."));
+  }
+}
@@ -0,0 +1,301 @@
+use crate::config::Config;
+use crate::incident_store;
+use crate::paths;
+use crate::runtime;
+use crate::types::{now_unix_ms, Evidence, FileAccessType, Incident, Severity};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+// Follow-up for YELLOW incidents: run the scanner over the folders of the binaries involved (and
+// folders that just received an executable) and merge its findings into the same incident, so the
+// user doesn't have to start a manual scan to see what else was dropped next to it.
+
+const MAX_DIRS: usize = 4;
+const EXECUTABLE_EXTENSIONS: &[&str] = &["exe", "dll", "sys", "ps1", "js", "vbs", "bat", "cmd"];
+
+pub struct TargetedScans {
+  last_scanned_unix_ms: HashMap<String, u64>,
+  running: Option<Child>,
+}
+
+impl TargetedScans {
+  pub fn new() -> Self {
+    Self {
+      last_scanned_unix_ms: HashMap::new(),
+      running: None,
+    }
+  }
+
+  pub fn on_incident(&mut self, cfg: &Config, incident: &Incident) {
+    if !cfg.response.targeted_scan || incident.severity != Severity::Yellow {
+      return;
+    }
+
+    let now = now_unix_ms();
+    let cooldown_ms = cfg
+      .response
+      .targeted_scan_cooldown_minutes
+      .saturating_mul(60_000);
+    let dirs: Vec<PathBuf> = implicated_dirs(incident)
+      .into_iter()
+      .filter(|d| {
+        self
+          .last_scanned_unix_ms
+          .get(&key(d))
+          .is_none_or(|t| now.saturating_sub(*t) >= cooldown_ms)
+      })
+      .collect();
+    if dirs.is_empty() {
+      return;
+    }
+
+    if let Some(child) = self.running.as_mut() {
+      if matches!(child.try_wait(), Ok(None)) {
+        tracing::info!(
+          incident_id = %incident.incident_id,
+          "targeted scan already running; skipping"
+        );
+        return;
+      }
+      self.running = None;
+    }
+
+    if runtime::is_dry_run() {
+      tracing::warn!(
+        incident_id = %incident.incident_id,
+        dirs = ?dirs,
+        "DRY-RUN: would start targeted scan"
+      );
+      return;
+    }
+
+    let Some(scanner) = scanner_path() else {
+      tracing::warn!("scanner executable not found next to agent; targeted scan skipped");
+      return;
+    };
+
+    let mut cmd = Command::new(&scanner);
+    cmd
+      .args(["--targeted", "--incident", &incident.incident_id])
+      .envs(paths::base_dir_override().map(|d| (paths::BASE_DIR_ENV, d)))
+      .stdin(Stdio::null())
+      .stdout(Stdio::null())
+      .stderr(Stdio::null());
+    for d in &dirs {
+      cmd.arg("--path").arg(d);
+    }
+
+    match cmd.spawn() {
+      Ok(child) => {
+        tracing::info!(
+          incident_id = %incident.incident_id,
+          dirs = ?dirs,
+          "targeted scan started"
+        );
+        for d in &dirs {
+          self.last_scanned_unix_ms.insert(key(d), now);
+        }
+        self.running = Some(child);
+        record_action(&incident.incident_id, "targeted_scan_started");
+      }
+      Err(e) => {
+        tracing::warn!(
+          incident_id = %incident.incident_id,
+          error = ?e,
+          "failed to start targeted scan"
+        );
+        record_action(&incident.incident_id, "targeted_scan_failed");
+      }
+    }
+  }
+}
+
+impl Default for TargetedScans {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Folders worth scanning for an incident: where the implicated binaries live, and where an
+/// executable was written. System and Program Files folders are left to full scans.
+pub fn implicated_dirs(incident: &Incident) -> Vec<PathBuf> {
+  let mut out: Vec<PathBuf> = Vec::new();
+  let mut push = |file: &str| {
+    // Event paths are Windows paths regardless of the host this is compiled for.
+    let Some((dir, _)) = file.rsplit_once(['\\', '/']) else {
+      return;
+    };
+    let dir = Path::new(dir);
+    if out.len() >= MAX_DIRS || !is_scannable_dir(dir) || out.iter().any(|d| key(d) == key(dir)) {
+      return;
+    }
+    out.push(dir.to_path_buf());
+  };
+
+  for f in &incident.findings {
+    for e in &f.evidence {
+      match e {
+        Evidence::Process { image_path, .. } | Evidence::CommandLine { image_path, .. } => {
+          push(image_path)
+        }
+        Evidence::File {
+          image_path,
+          file_path,
+          access,
+          ..
+        } => {
+          if let Some(img) = image_path {
+            push(img);
+          }
+          if *access == FileAccessType::Write && is_executable(file_path) {
+            push(file_path);
+          }
+        }
+        Evidence::Network { image_path, .. }
+        | Evidence::Dns { image_path, .. }
+        | Evidence::Registry { image_path, .. }
+        | Evidence::SettingChange { image_path, .. }
+        | Evidence::ProcessAccess { image_path, .. }
+        | Evidence::Clipboard { image_path, .. } => {
+          if let Some(img) = image_path {
+            push(img);
+          }
+        }
+        Evidence::Script {
+          image_path, path, ..
+        } => {
+          for p in [image_path, path].into_iter().flatten() {
+            push(p);
+          }
+        }
+        Evidence::Correlation { .. } | Evidence::Note { .. } => {}
+      }
+    }
+  }
+  out
+}
+
+fn is_scannable_dir(dir: &Path) -> bool {
+  let d = dir.to_string_lossy().to_ascii_lowercase();
+  let d = d.trim_end_matches('\\');
+  // Drive roots and bare `C:\Users` would turn into a full scan.
+  if d.matches('\\').count() < 2 && !d.contains("\\temp") {
+    return false;
+  }
+
+  let mut system = vec![std::env::var("SystemRoot").unwrap_or_else(|_| "C:\\Windows".to_string())];
+  for var in ["ProgramFiles", "ProgramFiles(x86)"] {
+    if let Ok(v) = std::env::var(var) {
+      system.push(v);
+    }
+  }
+  !system
+    .iter()
+    .map(|s| s.to_ascii_lowercase())
+    .any(|s| !s.is_empty() && (d == s || d.starts_with(&format!("{s}\\"))))
+}
+
+fn is_executable(path: &str) -> bool {
+  path
+    .rsplit_once('.')
+    .map(|(_, ext)| ext)
+    .is_some_and(|ext| {
+      EXECUTABLE_EXTENSIONS
+        .iter()
+        .any(|e| e.eq_ignore_ascii_case(ext))
+    })
+}
+
+fn key(dir: &Path) -> String {
+  dir
+    .to_string_lossy()
+    .trim_end_matches('\\')
+    .to_ascii_lowercase()
+}
+
+pub(crate) fn scanner_path() -> Option<PathBuf> {
+  let exe = std::env::current_exe().ok()?;
+  let name = if cfg!(windows) {
+    "scanner.exe"
+  } else {
+    "scanner"
+  };
+  let p = exe.parent()?.join(name);
+  p.exists().then_some(p)
+}
+
+fn record_action(incident_id: &str, action: &str) {
+  if let Err(e) = incident_store::record_action(incident_id, action) {
+    tracing::warn!(
+      incident_id = %incident_id,
+      error = ?e,
+      "failed to update incident record (continuing)"
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::Finding;
+
+  fn finding(evidence: Vec<Evidence>) -> Finding {
+    Finding {
+      rule_id: "R008".to_string(),
+      severity: Severity::Yellow,
+      description: "test".to_string(),
+      evidence,
+      timestamp_unix_ms: 0,
+    }
+  }
+
+  #[test]
+  fn collects_binary_and_dropped_executable_folders() {
+    let inc = Incident::new(vec![
+      finding(vec![
+        Evidence::File {
+          pid: 1,
+          image_path: Some("C:\\Users\\alice\\AppData\\Local\\Temp\\x\\dropper.exe".to_string()),
+          file_path: "C:\\Users\\alice\\AppData\\Roaming\\Updater\\svc.exe".to_string(),
+          access: FileAccessType::Write,
+        },
+        Evidence::File {
+          pid: 1,
+          image_path: Some("c:\\users\\alice\\appdata\\local\\temp\\x\\DROPPER.exe".to_string()),
+          file_path: "C:\\Users\\alice\\Documents\\notes.txt".to_string(),
+          access: FileAccessType::Write,
+        },
+      ]),
+      finding(vec![
+        Evidence::Process {
+          pid: 2,
+          ppid: 1,
+          image_path: "C:\\Windows\\System32\\cmd.exe".to_string(),
+          signer_publisher: None,
+          download_origin: None,
+        },
+        Evidence::Network {
+          pid: 3,
+          image_path: Some("C:\\tool.exe".to_string()),
+          dest_ip: "203.0.113.1".parse().unwrap(),
+          dest_port: 443,
+          dest_host: None,
+          protocol: "tcp".to_string(),
+        },
+      ]),
+    ]);
+
+    let dirs: Vec<String> = implicated_dirs(&inc)
+      .iter()
+      .map(|d| d.to_string_lossy().to_string())
+      .collect();
+    assert_eq!(
+      dirs,
+      vec![
+        "C:\\Users\\alice\\AppData\\Local\\Temp\\x".to_string(),
+        "C:\\Users\\alice\\AppData\\Roaming\\Updater".to_string(),
+      ]
+    );
+  }
+}
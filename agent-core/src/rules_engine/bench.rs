@@ -0,0 +1,251 @@
+use super::protected_paths;
+use super::self_exclusion::SelfExclusion;
+use super::Engine;
+use crate::config::Config;
+use crate::types::{Event, FileAccessType};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+// Synthetic workloads for measuring `Engine::process`, shared by `--bench-engine` and the criterion
+// benches. Events are generated per batch from a fixed seed, so runs are repeatable and the
+// workload itself doesn't count toward measured memory. The mix is mostly benign file, network, DNS
+// and registry traffic with a 0.2% share of protected-file reads to exercise correlation state.
+
+const BASE_TS_UNIX_MS: u64 = 1_700_000_000_000;
+/// Roughly 100 events per second, a busy desktop.
+const EVENT_SPACING_MS: u64 = 10;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Workload {
+  pub events: usize,
+  /// Distinct PIDs the events are spread over; drives the size of per-process engine state.
+  pub pids: u32,
+  pub seed: u64,
+}
+
+impl Workload {
+  pub fn new(events: usize, pids: u32) -> Self {
+    Self {
+      events,
+      pids: pids.max(1),
+      seed: 0x5eed,
+    }
+  }
+
+  /// The workload split into batches of at most `batch_size` events, in order.
+  pub fn batches(&self, cfg: &Config, batch_size: usize) -> Batches {
+    let protected_file = protected_paths::protected_roots(cfg)
+      .into_iter()
+      .find_map(|r| {
+        let target = r.targets.first()?;
+        Some(format!("{}\\Default\\{target}", r.root.display()))
+      })
+      .unwrap_or_default();
+    Batches {
+      workload: *self,
+      batch_size: batch_size.max(1),
+      produced: 0,
+      rng: self.seed | 1,
+      protected_file,
+    }
+  }
+}
+
+pub struct Batches {
+  workload: Workload,
+  batch_size: usize,
+  produced: usize,
+  rng: u64,
+  protected_file: String,
+}
+
+impl Batches {
+  fn next_u64(&mut self) -> u64 {
+    // xorshift64
+    self.rng ^= self.rng << 13;
+    self.rng ^= self.rng >> 7;
+    self.rng ^= self.rng << 17;
+    self.rng
+  }
+
+  fn event(&mut self, n: usize) -> Event {
+    let ts = BASE_TS_UNIX_MS + n as u64 * EVENT_SPACING_MS;
+    let pids = self.workload.pids as usize;
+    // Every PID starts once before its other events.
+    if n < pids {
+      let pid = 1_000 + n as u32;
+      return Event::ProcessStart {
+        pid,
+        ppid: 4,
+        image_path: image_for(pid),
+        signer_publisher: pid.is_multiple_of(3).then(|| "Contoso Ltd".to_string()),
+        command_line: None,
+        download_origin: None,
+        process_guid: None,
+        parent_process_guid: None,
+        timestamp_unix_ms: ts,
+      };
+    }
+
+    let r = self.next_u64();
+    let pid = 1_000 + (r % pids as u64) as u32;
+    let image_path = Some(image_for(pid));
+    match (r >> 32) % 1_000 {
+      0..=1 => Event::FileAccess {
+        pid,
+        image_path,
+        file_path: self.protected_file.clone(),
+        access: FileAccessType::Read,
+        process_guid: None,
+        timestamp_unix_ms: ts,
+      },
+      2..=399 => Event::FileAccess {
+        pid,
+        image_path,
+        file_path: format!("C:\\Users\\bench\\Documents\\file{}.txt", r % 500),
+        access: FileAccessType::Read,
+        process_guid: None,
+        timestamp_unix_ms: ts,
+      },
+      400..=699 => Event::NetConnect {
+        pid,
+        image_path,
+        dest_ip: IpAddr::from([93, 184, (r >> 8) as u8, (r >> 16) as u8]),
+        dest_port: 443,
+        dest_host: None,
+        protocol: "tcp".to_string(),
+        process_guid: None,
+        timestamp_unix_ms: ts,
+      },
+      700..=899 => Event::DnsQuery {
+        pid,
+        image_path,
+        query_name: format!("host{}.example.com", r % 1_000),
+        query_results: None,
+        timestamp_unix_ms: ts,
+      },
+      _ => Event::RegistrySet {
+        pid,
+        image_path,
+        key_path: format!("HKCU\\Software\\Bench\\Value{}", r % 100),
+        value: Some("1".to_string()),
+        timestamp_unix_ms: ts,
+      },
+    }
+  }
+}
+
+impl Iterator for Batches {
+  type Item = Vec<Event>;
+
+  fn next(&mut self) -> Option<Vec<Event>> {
+    let remaining = self.workload.events - self.produced;
+    if remaining == 0 {
+      return None;
+    }
+    let len = remaining.min(self.batch_size);
+    let start = self.produced;
+    self.produced += len;
+    Some((start..start + len).map(|n| self.event(n)).collect())
+  }
+}
+
+fn image_for(pid: u32) -> String {
+  format!("C:\\Program Files\\Bench\\app{}.exe", pid % 64)
+}
+
+/// An engine without the self-exclusion lookup or live network settings, so results don't depend
+/// on where the binary runs.
+pub fn engine() -> Engine {
+  Engine::with_self_exclusion(SelfExclusion::default())
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+  pub events: usize,
+  pub pids: u32,
+  /// Time spent inside `Engine::process` only.
+  pub elapsed: Duration,
+  pub incidents: usize,
+  pub working_set_before: Option<u64>,
+  pub working_set_after: Option<u64>,
+}
+
+impl BenchResult {
+  pub fn events_per_second(&self) -> f64 {
+    self.events as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+  }
+}
+
+/// Runs `workload` through a fresh engine in batches, like the agent loop does.
+pub fn run(cfg: &Config, workload: &Workload, batch_size: usize) -> anyhow::Result<BenchResult> {
+  let mut engine = engine();
+  let working_set_before = working_set_bytes();
+  let mut elapsed = Duration::ZERO;
+  let mut incidents = 0;
+
+  for batch in workload.batches(cfg, batch_size) {
+    let started = Instant::now();
+    incidents += engine.process(cfg, &batch)?.len();
+    elapsed += started.elapsed();
+    // The agent hands these to the baseline every tick; don't let them pile up here.
+    engine.drain_observations();
+  }
+
+  Ok(BenchResult {
+    events: workload.events,
+    pids: workload.pids,
+    elapsed,
+    incidents,
+    working_set_before,
+    working_set_after: working_set_bytes(),
+  })
+}
+
+#[cfg(windows)]
+fn working_set_bytes() -> Option<u64> {
+  use windows::Win32::System::ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+  use windows::Win32::System::Threading::GetCurrentProcess;
+
+  let mut counters = PROCESS_MEMORY_COUNTERS {
+    cb: std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
+    ..Default::default()
+  };
+  // SAFETY: the pseudo handle needs no closing; `counters` is sized via `cb`.
+  unsafe { GetProcessMemoryInfo(GetCurrentProcess(), &mut counters, counters.cb) }.ok()?;
+  Some(counters.WorkingSetSize as u64)
+}
+
+#[cfg(not(windows))]
+fn working_set_bytes() -> Option<u64> {
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn workload_is_repeatable_and_batched() {
+    let cfg = Config::default();
+    let workload = Workload::new(1_050, 100);
+    let batches: Vec<Vec<Event>> = workload.batches(&cfg, 500).collect();
+    assert_eq!(
+      batches.iter().map(Vec::len).collect::<Vec<_>>(),
+      [500, 500, 50]
+    );
+    assert!(matches!(
+      batches[0][99],
+      Event::ProcessStart { pid: 1_099, .. }
+    ));
+
+    let again: Vec<Vec<Event>> = workload.batches(&cfg, 1_050).collect();
+    assert_eq!(
+      serde_json::to_string(&batches.concat()).unwrap(),
+      serde_json::to_string(&again[0]).unwrap()
+    );
+
+    let result = run(&cfg, &workload, 500).unwrap();
+    assert_eq!(result.events, 1_050);
+  }
+}
@@ -0,0 +1,280 @@
+use crate::config::CircuitBreakerConfig;
+use crate::paths;
+use crate::runtime;
+use crate::types::{now_unix_ms, Incident};
+use defender_common::fs::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Suppresses a rule (log-only) once it produces more than `max_incidents` incidents within
+/// `window_minutes`, so a bad feed rule or environmental quirk can't flood the incident store or
+/// keep re-triggering the kill switch. A tripped rule stays suppressed until the agent restarts.
+pub struct CircuitBreaker {
+  hits: HashMap<String, VecDeque<u64>>,
+  tripped: HashSet<String>,
+  health_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthWarning {
+  pub kind: String,
+  pub rule_id: String,
+  pub tripped_at_unix_ms: u64,
+  pub incidents_in_window: usize,
+  pub window_minutes: u64,
+  pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct HealthFile {
+  #[serde(default)]
+  warnings: Vec<HealthWarning>,
+}
+
+const KIND: &str = "rule_circuit_breaker";
+
+impl CircuitBreaker {
+  /// Breaker that records trips in `health-warnings.toml` under `base`. Warnings from a previous
+  /// run are cleared since tripped state does not survive a restart.
+  pub fn new(base: &Path) -> Self {
+    let health_path = paths::health_warnings_path(base);
+    if !runtime::is_dry_run() {
+      let _ = update_health_file(&health_path, |f| f.warnings.retain(|w| w.kind != KIND));
+    }
+    Self {
+      hits: HashMap::new(),
+      tripped: HashSet::new(),
+      health_path: Some(health_path),
+    }
+  }
+
+  /// Breaker without a health file (simulation and tests).
+  pub fn in_memory() -> Self {
+    Self {
+      hits: HashMap::new(),
+      tripped: HashSet::new(),
+      health_path: None,
+    }
+  }
+
+  pub fn tripped_rules(&self) -> Vec<String> {
+    let mut ids: Vec<String> = self.tripped.iter().cloned().collect();
+    ids.sort();
+    ids
+  }
+
+  pub fn filter(&mut self, cfg: &CircuitBreakerConfig, incidents: Vec<Incident>) -> Vec<Incident> {
+    self.filter_at(cfg, incidents, now_unix_ms())
+  }
+
+  fn filter_at(
+    &mut self,
+    cfg: &CircuitBreakerConfig,
+    incidents: Vec<Incident>,
+    now_unix_ms: u64,
+  ) -> Vec<Incident> {
+    if !cfg.enabled {
+      return incidents;
+    }
+
+    let mut out = Vec::with_capacity(incidents.len());
+    for mut incident in incidents {
+      let rule_ids: BTreeSet<String> = incident
+        .findings
+        .iter()
+        .map(|f| f.rule_id.clone())
+        .collect();
+
+      let suppressed: HashSet<String> = rule_ids
+        .into_iter()
+        .filter(|id| !self.admit(cfg, id, now_unix_ms))
+        .collect();
+      if suppressed.is_empty() {
+        out.push(incident);
+        continue;
+      }
+
+      for f in incident
+        .findings
+        .iter()
+        .filter(|f| suppressed.contains(&f.rule_id))
+      {
+        tracing::info!(
+          incident_id = %incident.incident_id,
+          rule_id = %f.rule_id,
+          severity = ?f.severity,
+          "circuit breaker: finding suppressed (log-only): {}",
+          f.description
+        );
+      }
+
+      incident
+        .findings
+        .retain(|f| !suppressed.contains(&f.rule_id));
+      if !incident.findings.is_empty() {
+        out.push(incident);
+      }
+    }
+    out
+  }
+
+  fn admit(&mut self, cfg: &CircuitBreakerConfig, rule_id: &str, now_unix_ms: u64) -> bool {
+    if self.tripped.contains(rule_id) {
+      return false;
+    }
+
+    let window_ms = cfg.window_minutes.saturating_mul(60_000);
+    let q = self.hits.entry(rule_id.to_string()).or_default();
+    q.push_back(now_unix_ms);
+    while let Some(front) = q.front() {
+      if now_unix_ms.saturating_sub(*front) <= window_ms {
+        break;
+      }
+      q.pop_front();
+    }
+
+    if q.len() <= cfg.max_incidents {
+      return true;
+    }
+
+    let count = q.len();
+    self.hits.remove(rule_id);
+    self.tripped.insert(rule_id.to_string());
+    self.record_trip(cfg, rule_id, count, now_unix_ms);
+    false
+  }
+
+  fn record_trip(&self, cfg: &CircuitBreakerConfig, rule_id: &str, count: usize, now: u64) {
+    let message = format!(
+      "rule {rule_id} produced {count} incidents within {} minutes and was suppressed (log-only) \
+       until the agent restarts; review the rule/feed and allowlist before restarting",
+      cfg.window_minutes
+    );
+    tracing::error!(
+      rule_id = %rule_id,
+      incidents = count,
+      window_minutes = cfg.window_minutes,
+      "HEALTH WARNING: rule circuit breaker tripped: {message}"
+    );
+
+    let Some(path) = &self.health_path else {
+      return;
+    };
+    if runtime::is_dry_run() {
+      tracing::warn!(rule_id = %rule_id, "DRY-RUN: would write circuit breaker health warning");
+      return;
+    }
+
+    let warning = HealthWarning {
+      kind: KIND.to_string(),
+      rule_id: rule_id.to_string(),
+      tripped_at_unix_ms: now,
+      incidents_in_window: count,
+      window_minutes: cfg.window_minutes,
+      message,
+    };
+    if let Err(e) = update_health_file(path, |f| f.warnings.push(warning)) {
+      tracing::error!(error = ?e, "failed to write health warning (continuing)");
+    }
+  }
+}
+
+pub fn load_health_warnings(base: &Path) -> Vec<HealthWarning> {
+  fs::read_to_string(paths::health_warnings_path(base))
+    .ok()
+    .and_then(|raw| toml::from_str::<HealthFile>(&raw).ok())
+    .map(|f| f.warnings)
+    .unwrap_or_default()
+}
+
+fn update_health_file(path: &Path, f: impl FnOnce(&mut HealthFile)) -> anyhow::Result<()> {
+  let mut file = fs::read_to_string(path)
+    .ok()
+    .and_then(|raw| toml::from_str::<HealthFile>(&raw).ok())
+    .unwrap_or_default();
+  let before = file.warnings.len();
+  f(&mut file);
+  if file.warnings.is_empty() && before == 0 {
+    return Ok(());
+  }
+  write_atomic(path, &toml::to_string_pretty(&file)?)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::{Finding, Severity};
+
+  fn incident(rule_ids: &[&str]) -> Incident {
+    Incident::new(
+      rule_ids
+        .iter()
+        .map(|id| Finding {
+          rule_id: id.to_string(),
+          severity: Severity::Red,
+          description: "test".to_string(),
+          evidence: vec![],
+          timestamp_unix_ms: 0,
+        })
+        .collect(),
+    )
+  }
+
+  #[test]
+  fn trips_after_threshold_and_stays_suppressed() {
+    let cfg = CircuitBreakerConfig {
+      enabled: true,
+      max_incidents: 3,
+      window_minutes: 10,
+    };
+    let mut cb = CircuitBreaker::in_memory();
+    let t0 = 1_700_000_000_000;
+
+    for i in 0..3 {
+      let out = cb.filter_at(&cfg, vec![incident(&["R009", "R010"])], t0 + i);
+      assert_eq!(out.len(), 1);
+    }
+
+    // Fourth R009 incident within the window trips both rules in it.
+    let out = cb.filter_at(&cfg, vec![incident(&["R009", "R010"])], t0 + 10);
+    assert!(out.is_empty());
+    assert_eq!(cb.tripped_rules(), vec!["R009", "R010"]);
+
+    // Other rules are unaffected; tripped ones are stripped from mixed incidents.
+    let out = cb.filter_at(&cfg, vec![incident(&["R001", "R009"])], t0 + 20);
+    assert_eq!(out.len(), 1);
+    assert_eq!(out[0].findings.len(), 1);
+    assert_eq!(out[0].findings[0].rule_id, "R001");
+  }
+
+  #[test]
+  fn incidents_outside_window_do_not_count() {
+    let cfg = CircuitBreakerConfig {
+      enabled: true,
+      max_incidents: 2,
+      window_minutes: 1,
+    };
+    let mut cb = CircuitBreaker::in_memory();
+    let t0 = 1_700_000_000_000;
+
+    for i in 0..10 {
+      let out = cb.filter_at(&cfg, vec![incident(&["R001"])], t0 + i * 40_000);
+      assert_eq!(out.len(), 1, "incident {i} should pass");
+    }
+    assert!(cb.tripped_rules().is_empty());
+  }
+
+  #[test]
+  fn disabled_breaker_passes_everything() {
+    let cfg = CircuitBreakerConfig {
+      enabled: false,
+      max_incidents: 0,
+      window_minutes: 10,
+    };
+    let mut cb = CircuitBreaker::in_memory();
+    let out = cb.filter_at(&cfg, vec![incident(&["R001"]), incident(&["R009"])], 0);
+    assert_eq!(out.len(), 2);
+  }
+}
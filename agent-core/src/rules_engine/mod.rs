@@ -1,7 +1,16 @@
+pub mod bench;
+pub mod circuit_breaker;
 mod engine;
+pub mod feed_rules;
+pub mod policy;
 pub mod protected_paths;
+mod ransomware;
 pub mod rules;
+pub mod self_exclusion;
+pub mod state;
+pub mod trace;
 
+pub use circuit_breaker::CircuitBreaker;
 pub use engine::Engine;
 
 pub fn active_rule_ids() -> &'static [&'static str] {
@@ -0,0 +1,303 @@
+use super::rules::{self, RuleMeta};
+use crate::config::{Config, Mode};
+use crate::threat_feed::schema::{BundleSeverity, DetectionRule, RuleOverride};
+use crate::types::{Incident, Severity};
+
+// Operator and threat-feed control over rule output. Applied to every finding after the engine
+// produced it, so rule logic itself never has to know about overrides.
+//
+// Order: threat-feed override (enabled, floor, learning cap, strict severity), then local
+// `[rules]` config (disabled, severity), which always wins. Feed overrides carrying `tags` /
+// `exclude_tags` are skipped on machines they don't match; scoped `[rules]` entries are merged by
+// `Config::for_machine` before this runs.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicySource {
+  Default,
+  ThreatFeed,
+  Config,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EffectiveRule {
+  pub meta: RuleMeta,
+  pub enabled: bool,
+  /// Severity for findings that come out at the rule's default severity.
+  pub severity: Severity,
+  pub source: PolicySource,
+}
+
+/// Effective state of every known rule in the current mode (for `--rules list`).
+pub fn effective_rules(cfg: &Config, overrides: &[RuleOverride]) -> Vec<EffectiveRule> {
+  let cfg = cfg.for_machine();
+  let cfg = cfg.as_ref();
+  rules::ALL_RULES
+    .iter()
+    .map(|meta| {
+      let (severity, enabled, source) = resolve(cfg, overrides, meta.id, meta.default_severity);
+      EffectiveRule {
+        meta: *meta,
+        enabled,
+        severity: severity.unwrap_or(meta.default_severity),
+        source,
+      }
+    })
+    .collect()
+}
+
+/// Effective state of the threat feed's detections (for `--rules list`). A detection scoped to
+/// other machines by its `tags` is listed as disabled.
+pub fn effective_detections<'a>(
+  cfg: &Config,
+  overrides: &[RuleOverride],
+  detections: &'a [DetectionRule],
+) -> Vec<(&'a DetectionRule, bool, Severity, PolicySource)> {
+  let cfg = cfg.for_machine();
+  let cfg = cfg.as_ref();
+  detections
+    .iter()
+    .map(|d| {
+      let default = Severity::from(d.severity);
+      let (severity, enabled, source) = resolve(cfg, overrides, d.rule_id.trim(), default);
+      let enabled = enabled && d.applies_to(&cfg.machine.tags);
+      (d, enabled, severity.unwrap_or(default), source)
+    })
+    .collect()
+}
+
+/// Drops findings of disabled rules, re-grades the rest, and drops incidents left empty.
+pub fn apply(cfg: &Config, overrides: &[RuleOverride], incidents: Vec<Incident>) -> Vec<Incident> {
+  if overrides.is_empty() && cfg.rules.disabled.is_empty() && cfg.rules.severity.is_empty() {
+    return incidents;
+  }
+
+  incidents
+    .into_iter()
+    .filter_map(|mut incident| {
+      incident.findings.retain_mut(|f| {
+        let (severity, enabled, _) = resolve(cfg, overrides, &f.rule_id, f.severity);
+        if let Some(s) = severity {
+          f.severity = s;
+        }
+        enabled
+      });
+      if incident.findings.is_empty() {
+        return None;
+      }
+      incident.severity = incident.max_severity();
+      Some(incident)
+    })
+    .collect()
+}
+
+fn resolve(
+  cfg: &Config,
+  overrides: &[RuleOverride],
+  rule_id: &str,
+  severity: Severity,
+) -> (Option<Severity>, bool, PolicySource) {
+  let mut out = severity;
+  let mut enabled = true;
+  let mut source = PolicySource::Default;
+
+  if let Some(o) = overrides
+    .iter()
+    .filter(|o| o.applies_to(&cfg.machine.tags))
+    .find(|o| o.rule_id.trim().eq_ignore_ascii_case(rule_id))
+  {
+    source = PolicySource::ThreatFeed;
+    enabled = o.enabled;
+    if let Some(floor) = o.severity_floor {
+      out = max_severity(out, floor.into());
+    }
+    match cfg.mode {
+      Mode::Learning => {
+        if let Some(cap) = o.severity_cap_learning {
+          out = min_severity(out, cap.into());
+        }
+      }
+      Mode::Strict => {
+        if let Some(s) = o.severity_strict {
+          out = s.into();
+        }
+      }
+    }
+  }
+
+  if cfg.rules.is_disabled(rule_id) {
+    enabled = false;
+    source = PolicySource::Config;
+  }
+  if let Some(s) = cfg.rules.severity_for(rule_id) {
+    out = s;
+    source = PolicySource::Config;
+  }
+
+  let changed = (out != severity).then_some(out);
+  (changed, enabled, source)
+}
+
+impl From<BundleSeverity> for Severity {
+  fn from(s: BundleSeverity) -> Self {
+    match s {
+      BundleSeverity::Green => Severity::Green,
+      BundleSeverity::Yellow => Severity::Yellow,
+      BundleSeverity::Red => Severity::Red,
+    }
+  }
+}
+
+fn rank(s: Severity) -> u8 {
+  match s {
+    Severity::Green => 0,
+    Severity::Yellow => 1,
+    Severity::Red => 2,
+  }
+}
+
+fn max_severity(a: Severity, b: Severity) -> Severity {
+  if rank(b) > rank(a) {
+    b
+  } else {
+    a
+  }
+}
+
+fn min_severity(a: Severity, b: Severity) -> Severity {
+  if rank(b) < rank(a) {
+    b
+  } else {
+    a
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::{Evidence, Finding};
+
+  fn incident(rules: &[(&str, Severity)]) -> Incident {
+    Incident::new(
+      rules
+        .iter()
+        .map(|(id, sev)| Finding {
+          rule_id: id.to_string(),
+          severity: *sev,
+          description: String::new(),
+          evidence: vec![Evidence::Note {
+            message: String::new(),
+          }],
+          timestamp_unix_ms: 0,
+        })
+        .collect(),
+    )
+  }
+
+  fn feed_override(rule_id: &str, enabled: bool) -> RuleOverride {
+    RuleOverride {
+      rule_id: rule_id.to_string(),
+      enabled,
+      severity_floor: None,
+      severity_cap_learning: None,
+      severity_strict: None,
+      notes: None,
+      guidance: None,
+      tags: Vec::new(),
+      exclude_tags: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn config_disables_and_regrades_rules() {
+    let mut cfg = Config::default();
+    cfg.rules.disabled = vec!["R008".to_string()];
+    cfg
+      .rules
+      .severity
+      .insert("R009".to_string(), Severity::Yellow);
+
+    let out = apply(
+      &cfg,
+      &[],
+      vec![
+        incident(&[("R008", Severity::Yellow)]),
+        incident(&[("R009", Severity::Red), ("R008", Severity::Yellow)]),
+      ],
+    );
+    assert_eq!(out.len(), 1);
+    assert_eq!(out[0].findings.len(), 1);
+    assert_eq!(out[0].findings[0].severity, Severity::Yellow);
+    assert_eq!(out[0].severity, Severity::Yellow);
+  }
+
+  #[test]
+  fn feed_overrides_apply_per_mode_and_config_wins() {
+    let mut strict = Config {
+      mode: Mode::Strict,
+      ..Config::default()
+    };
+    let overrides = vec![
+      RuleOverride {
+        severity_strict: Some(BundleSeverity::Red),
+        ..feed_override("R007", true)
+      },
+      feed_override("R011", false),
+    ];
+
+    let out = apply(
+      &strict,
+      &overrides,
+      vec![incident(&[
+        ("R007", Severity::Yellow),
+        ("R011", Severity::Yellow),
+      ])],
+    );
+    assert_eq!(out[0].findings.len(), 1);
+    assert_eq!(out[0].severity, Severity::Red);
+
+    // Local config re-enables nothing but regrades R007 back down.
+    strict
+      .rules
+      .severity
+      .insert("R007".to_string(), Severity::Yellow);
+    let listed = effective_rules(&strict, &overrides);
+    let r007 = listed.iter().find(|r| r.meta.id == "R007").unwrap();
+    assert_eq!(r007.severity, Severity::Yellow);
+    assert_eq!(r007.source, PolicySource::Config);
+    let r011 = listed.iter().find(|r| r.meta.id == "R011").unwrap();
+    assert!(!r011.enabled);
+    assert_eq!(r011.source, PolicySource::ThreatFeed);
+  }
+
+  #[test]
+  fn tag_scoped_rules_and_feed_overrides_follow_machine_tags() {
+    let mut cfg = Config::default();
+    cfg.rules.scoped.push(crate::config::ScopedRules {
+      tags: vec!["build-server".to_string()],
+      disabled: vec!["R008".to_string()],
+      ..Default::default()
+    });
+    let overrides = vec![RuleOverride {
+      exclude_tags: vec!["kiosk".to_string()],
+      ..feed_override("R011", false)
+    }];
+    let run = |cfg: &Config| -> Vec<String> {
+      apply(
+        &cfg.for_machine(),
+        &overrides,
+        vec![incident(&[
+          ("R008", Severity::Yellow),
+          ("R011", Severity::Yellow),
+        ])],
+      )
+      .first()
+      .map(|i| i.findings.iter().map(|f| f.rule_id.clone()).collect())
+      .unwrap_or_default()
+    };
+
+    assert_eq!(run(&cfg), vec!["R008"]);
+
+    cfg.machine.tags = vec!["build-server".to_string(), "kiosk".to_string()];
+    assert_eq!(run(&cfg), vec!["R011"]);
+  }
+}
@@ -1,23 +1,291 @@
+use crate::browser_profiles;
 use crate::config::Config;
+use crate::network_hijack;
+use crate::user_profiles::{self, UserProfile};
+use std::path::{Path, PathBuf};
 
 use super::engine::ProtectedTarget;
 
+/// A browser profile root of one user and the protected file names expected somewhere beneath it.
+#[derive(Debug, Clone)]
+pub struct ProtectedRoot {
+  pub browser: String,
+  /// The user profile folder name (see `user_profiles`).
+  pub user: String,
+  pub root: PathBuf,
+  pub targets: Vec<String>,
+  /// Profile directories found for this browser (see `browser_profiles`); Firefox profiles may
+  /// live outside `root`.
+  pub profiles: Vec<PathBuf>,
+}
+
+pub fn protected_roots(cfg: &Config) -> Vec<ProtectedRoot> {
+  let profiles = browser_profiles::current();
+  let browsers = browser_profiles::chromium_browsers();
+  let mut out = Vec::new();
+  for user in user_profiles::all().iter() {
+    let roam = user.appdata.to_string_lossy();
+    let profiles_of = |browser: &str| {
+      profiles
+        .iter()
+        .filter(|p| p.browser == browser && p.user == user.name)
+        .map(|p| p.dir.clone())
+        .collect()
+    };
+    for browser in &browsers {
+      for root in browser.user_data_dirs(user) {
+        out.push(ProtectedRoot {
+          browser: browser.name.clone(),
+          user: user.name.clone(),
+          root,
+          targets: cfg.protected.chrome_targets.clone(),
+          profiles: profiles_of(&browser.name),
+        });
+      }
+    }
+    out.push(ProtectedRoot {
+      browser: "Firefox".to_string(),
+      user: user.name.clone(),
+      root: PathBuf::from(firefox_profiles_root(&roam)),
+      targets: cfg.protected.firefox_targets.clone(),
+      profiles: profiles_of("Firefox"),
+    });
+  }
+  out
+}
+
+/// `(%LOCALAPPDATA%, %APPDATA%)` of every user, lowercased.
+fn user_appdata_lower(users: &[UserProfile]) -> Vec<(String, String)> {
+  users
+    .iter()
+    .map(|u| {
+      (
+        u.local_appdata.to_string_lossy().to_ascii_lowercase(),
+        u.appdata.to_string_lossy().to_ascii_lowercase(),
+      )
+    })
+    .collect()
+}
+
+/// Where a group path is anchored.
+#[derive(Debug, Clone, Copy)]
+enum Root {
+  UserProfile,
+  AppData,
+  LocalAppData,
+  ProgramData,
+  ProgramFiles,
+}
+
+impl Root {
+  /// The root's directory for each user (once for machine-wide roots), lowercased.
+  fn dirs_lower(self, users: &[UserProfile]) -> Vec<String> {
+    let per_user = |dir: fn(&UserProfile) -> &PathBuf| {
+      users
+        .iter()
+        .map(|u| dir(u).to_string_lossy().to_ascii_lowercase())
+        .collect()
+    };
+    let machine = |var: &str, fallback: &str| {
+      vec![std::env::var(var)
+        .unwrap_or_else(|_| fallback.to_string())
+        .to_ascii_lowercase()]
+    };
+    match self {
+      Root::UserProfile => per_user(|u| &u.home),
+      Root::AppData => per_user(|u| &u.appdata),
+      Root::LocalAppData => per_user(|u| &u.local_appdata),
+      Root::ProgramData => machine("ProgramData", "C:\\ProgramData"),
+      Root::ProgramFiles => machine("ProgramFiles", "C:\\Program Files"),
+    }
+  }
+}
+
+/// Non-browser credential stores, enabled by name in `[protected] groups`. Paths are lowercase.
+#[derive(Debug)]
+pub(crate) struct TargetGroup {
+  pub name: &'static str,
+  pub target: ProtectedTarget,
+  /// Every file beneath these directories is protected.
+  dirs: &'static [(Root, &'static str)],
+  /// File names protected wherever they are.
+  file_names: &'static [&'static str],
+  /// Path fragments that mark a protected file anywhere (e.g. a browser extension's storage).
+  fragments: &'static [&'static str],
+  /// File names (or `.suffix`es) inside `dirs` that aren't secret.
+  ignore: &'static [&'static str],
+  /// Images that own the store: file names (`ssh.exe`) or install-path fragments.
+  owners: &'static [&'static str],
+}
+
+impl TargetGroup {
+  fn matches(&self, p: &str, users: &[UserProfile]) -> bool {
+    let name = p.rsplit(['\\', '/']).next().unwrap_or(p);
+    if self
+      .ignore
+      .iter()
+      .any(|i| name == *i || (i.starts_with('.') && name.ends_with(i)))
+    {
+      return false;
+    }
+    self.file_names.contains(&name)
+      || self.fragments.iter().any(|f| p.contains(f))
+      || self.dirs.iter().any(|(root, rel)| {
+        root
+          .dirs_lower(users)
+          .iter()
+          .any(|dir| p.starts_with(&format!("{dir}\\{rel}\\")))
+      })
+  }
+
+  pub(crate) fn is_owner(&self, image_path: &str) -> bool {
+    let image = image_path.to_ascii_lowercase();
+    let name = image.rsplit(['\\', '/']).next().unwrap_or(&image);
+    self.owners.iter().any(|o| {
+      if o.ends_with(".exe") {
+        name == *o
+      } else {
+        image.contains(o)
+      }
+    })
+  }
+}
+
+pub(crate) const TARGET_GROUPS: &[TargetGroup] = &[
+  TargetGroup {
+    name: "ssh",
+    target: ProtectedTarget::SshKey,
+    dirs: &[(Root::UserProfile, ".ssh")],
+    file_names: &[],
+    fragments: &[],
+    ignore: &[
+      ".pub",
+      "known_hosts",
+      "known_hosts.old",
+      "authorized_keys",
+      "config",
+    ],
+    owners: &[
+      "ssh.exe",
+      "ssh-agent.exe",
+      "ssh-add.exe",
+      "scp.exe",
+      "sftp.exe",
+      "\\git\\usr\\bin\\",
+    ],
+  },
+  TargetGroup {
+    name: "crypto_wallets",
+    target: ProtectedTarget::CryptoWallet,
+    dirs: &[
+      (Root::AppData, "exodus\\exodus.wallet"),
+      (Root::AppData, "electrum\\wallets"),
+      (Root::AppData, "ethereum\\keystore"),
+      (Root::AppData, "atomic\\local storage"),
+    ],
+    file_names: &["wallet.dat"],
+    // MetaMask extension storage (Chrome Web Store and Edge Add-ons IDs) in any profile.
+    fragments: &[
+      "\\local extension settings\\nkbihfbeogaeaoehlefnkodbefgpgknn\\",
+      "\\local extension settings\\ejbalbakoplchlghecdalmeeeajnimhm\\",
+    ],
+    ignore: &[],
+    owners: &[
+      "exodus.exe",
+      "electrum.exe",
+      "bitcoin-qt.exe",
+      "atomic wallet.exe",
+      "chrome.exe",
+      "msedge.exe",
+      "brave.exe",
+      "firefox.exe",
+    ],
+  },
+  TargetGroup {
+    name: "credential_manager",
+    target: ProtectedTarget::CredentialVault,
+    dirs: &[
+      (Root::AppData, "microsoft\\credentials"),
+      (Root::LocalAppData, "microsoft\\credentials"),
+      (Root::LocalAppData, "microsoft\\vault"),
+      (Root::ProgramData, "microsoft\\vault"),
+      (Root::AppData, "microsoft\\protect"),
+    ],
+    file_names: &[],
+    fragments: &[],
+    ignore: &[],
+    owners: &["lsass.exe", "svchost.exe"],
+  },
+  TargetGroup {
+    name: "cloud_cli",
+    target: ProtectedTarget::CloudCliToken,
+    dirs: &[
+      (Root::UserProfile, ".aws"),
+      (Root::UserProfile, ".azure"),
+      (Root::UserProfile, ".kube"),
+      (Root::AppData, "gcloud"),
+    ],
+    file_names: &[],
+    fragments: &["\\.docker\\config.json"],
+    ignore: &[],
+    owners: &[
+      "aws.exe",
+      "kubectl.exe",
+      "docker.exe",
+      "\\microsoft sdks\\azure\\cli2\\",
+      "\\google\\cloud sdk\\",
+      "\\amazon\\awscliv2\\",
+    ],
+  },
+  TargetGroup {
+    name: "vpn",
+    target: ProtectedTarget::VpnConfig,
+    dirs: &[
+      (Root::UserProfile, "openvpn\\config"),
+      (Root::ProgramFiles, "openvpn\\config"),
+    ],
+    file_names: &[],
+    fragments: &[],
+    ignore: &[],
+    owners: &["openvpn.exe", "openvpn-gui.exe", "openvpnserv.exe"],
+  },
+];
+
+pub(crate) fn group_for(target: ProtectedTarget) -> Option<&'static TargetGroup> {
+  TARGET_GROUPS.iter().find(|g| g.target == target)
+}
+
+pub fn known_group(name: &str) -> bool {
+  TARGET_GROUPS.iter().any(|g| g.name == name)
+}
+
 pub(crate) fn classify_protected_target(cfg: &Config, file_path: &str) -> Option<ProtectedTarget> {
   let p = file_path.to_ascii_lowercase();
+  let users = user_profiles::all();
+  classify_browser_target(cfg, &p, &users).or_else(|| {
+    TARGET_GROUPS
+      .iter()
+      .filter(|g| cfg.protected.groups.iter().any(|n| n == g.name))
+      .find(|g| g.matches(&p, &users))
+      .map(|g| g.target)
+  })
+}
 
-  let local = std::env::var("LOCALAPPDATA")
-    .unwrap_or_else(|_| "C:\\Users\\User\\AppData\\Local".to_string())
-    .to_ascii_lowercase();
-  let roam = std::env::var("APPDATA")
-    .unwrap_or_else(|_| "C:\\Users\\User\\AppData\\Roaming".to_string())
-    .to_ascii_lowercase();
-
-  let chrome_base = chrome_user_data_root(&local);
-  let edge_base = edge_user_data_root(&local);
-  let brave_base = brave_user_data_root(&local);
-  let firefox_base = firefox_profiles_root(&roam);
+fn classify_browser_target(
+  cfg: &Config,
+  p: &str,
+  users: &[UserProfile],
+) -> Option<ProtectedTarget> {
+  let in_chromium_root = browser_profiles::user_data_browser(p).is_some();
+  let in_firefox_root = user_appdata_lower(users)
+    .iter()
+    .any(|(_, roam)| p.starts_with(&firefox_profiles_root(roam)));
+  // Profiles outside the default roots (Firefox profiles created with an absolute path).
+  let profile_browser = browser_profiles::profile_browser(p);
+  let in_firefox_profile = profile_browser.as_deref() == Some("Firefox");
+  let in_chromium_profile = profile_browser.is_some() && !in_firefox_profile;
 
-  if p.starts_with(&chrome_base) || p.starts_with(&edge_base) || p.starts_with(&brave_base) {
+  if in_chromium_root || in_chromium_profile {
     for name in &cfg.protected.chrome_targets {
       let n = name.to_ascii_lowercase();
       if p.ends_with(&format!("\\{n}")) || p.ends_with(&format!("/{n}")) {
@@ -31,7 +299,7 @@ pub(crate) fn classify_protected_target(cfg: &Config, file_path: &str) -> Option
     }
   }
 
-  if p.starts_with(&firefox_base) {
+  if in_firefox_root || in_firefox_profile {
     for name in &cfg.protected.firefox_targets {
       let n = name.to_ascii_lowercase();
       if p.ends_with(&format!("\\{n}")) || p.ends_with(&format!("/{n}")) {
@@ -50,34 +318,47 @@ pub(crate) fn classify_protected_target(cfg: &Config, file_path: &str) -> Option
 
 pub fn is_under_protected_root(file_path: &str) -> bool {
   let p = file_path.to_ascii_lowercase();
-  let local = std::env::var("LOCALAPPDATA")
-    .unwrap_or_else(|_| "C:\\Users\\User\\AppData\\Local".to_string())
-    .to_ascii_lowercase();
-  let roam = std::env::var("APPDATA")
-    .unwrap_or_else(|_| "C:\\Users\\User\\AppData\\Roaming".to_string())
-    .to_ascii_lowercase();
-
-  let chrome_base = chrome_user_data_root(&local);
-  let edge_base = edge_user_data_root(&local);
-  let brave_base = brave_user_data_root(&local);
-  let firefox_base = firefox_profiles_root(&roam);
-
-  p.starts_with(&chrome_base)
-    || p.starts_with(&edge_base)
-    || p.starts_with(&brave_base)
-    || p.starts_with(&firefox_base)
-}
-
-fn chrome_user_data_root(localappdata_lower: &str) -> String {
-  format!("{localappdata_lower}\\google\\chrome\\user data\\")
+  browser_profiles::user_data_browser(&p).is_some()
+    || user_appdata_lower(&user_profiles::all())
+      .iter()
+      .any(|(_, roam)| p.starts_with(&firefox_profiles_root(roam)))
+    || browser_profiles::profile_browser(&p).is_some()
 }
 
-fn edge_user_data_root(localappdata_lower: &str) -> String {
-  format!("{localappdata_lower}\\microsoft\\edge\\user data\\")
+/// Whether a change of `file_path` can matter to a file rule: a browser profile, a credential
+/// store of any group, a user's document folders or the hosts file. Used to filter sources that
+/// see every change on a volume (the USN journal).
+pub fn is_watched_location(file_path: &str) -> bool {
+  if is_under_protected_root(file_path)
+    || network_hijack::is_hosts_file(file_path)
+    || super::ransomware::is_document_path(file_path)
+  {
+    return true;
+  }
+  let p = file_path.to_ascii_lowercase();
+  let users = user_profiles::all();
+  TARGET_GROUPS.iter().any(|g| g.matches(&p, &users))
 }
 
-fn brave_user_data_root(localappdata_lower: &str) -> String {
-  format!("{localappdata_lower}\\bravesoftware\\brave-browser\\user data\\")
+/// Directories a directory watcher (`[collector] directory_watch`) listens on: every browser
+/// profile root and profile, users' document folders and the hosts file's folder. Entries may
+/// not exist and may be nested.
+pub fn watched_dirs() -> Vec<PathBuf> {
+  let users = user_profiles::all();
+  let browsers = browser_profiles::chromium_browsers();
+  let mut dirs = Vec::new();
+  for user in users.iter() {
+    for browser in &browsers {
+      dirs.extend(browser.user_data_dirs(user));
+    }
+    dirs.push(user.appdata.join("Mozilla\\Firefox\\Profiles"));
+  }
+  dirs.extend(browser_profiles::current().into_iter().map(|p| p.dir));
+  dirs.extend(super::ransomware::document_dirs(&users));
+  if let Some(etc) = Path::new(&network_hijack::hosts_path()).parent() {
+    dirs.push(etc.to_path_buf());
+  }
+  dirs
 }
 
 fn firefox_profiles_root(appdata_lower: &str) -> String {
@@ -138,4 +419,49 @@ mod tests {
     let t = classify_protected_target(&cfg, p);
     assert!(matches!(t, Some(ProtectedTarget::FirefoxCookiesSqlite)));
   }
+
+  #[test]
+  fn classify_credential_store_groups() {
+    let cfg = crate::config::Config::default();
+    let cases = [
+      ("C:\\Users\\User\\.ssh\\id_ed25519", Some(ProtectedTarget::SshKey)),
+      ("C:\\Users\\User\\.ssh\\id_ed25519.pub", None),
+      ("C:\\Users\\User\\.ssh\\known_hosts", None),
+      ("D:\\backup\\wallet.dat", Some(ProtectedTarget::CryptoWallet)),
+      (
+        "C:\\Users\\User\\AppData\\Local\\Google\\Chrome\\User Data\\Profile 2\\Local Extension Settings\\nkbihfbeogaeaoehlefnkodbefgpgknn\\000003.log",
+        Some(ProtectedTarget::CryptoWallet),
+      ),
+      (
+        "C:\\Users\\User\\AppData\\Roaming\\Microsoft\\Credentials\\DFBE70A7E5CC19A398EBF1B96859CE5D",
+        Some(ProtectedTarget::CredentialVault),
+      ),
+      ("C:\\Users\\User\\.aws\\credentials", Some(ProtectedTarget::CloudCliToken)),
+      ("C:\\Users\\User\\.docker\\config.json", Some(ProtectedTarget::CloudCliToken)),
+      (
+        "C:\\Program Files\\OpenVPN\\config\\office.ovpn",
+        Some(ProtectedTarget::VpnConfig),
+      ),
+    ];
+    for (path, expected) in cases {
+      assert_eq!(classify_protected_target(&cfg, path), expected, "{path}");
+    }
+
+    let mut cfg = cfg;
+    cfg.protected.groups.retain(|g| g != "ssh");
+    assert_eq!(
+      classify_protected_target(&cfg, "C:\\Users\\User\\.ssh\\id_rsa"),
+      None
+    );
+  }
+
+  #[test]
+  fn group_owners_match_by_name_or_install_path() {
+    let ssh = group_for(ProtectedTarget::SshKey).unwrap();
+    assert!(ssh.is_owner("C:\\Windows\\System32\\OpenSSH\\ssh.exe"));
+    assert!(!ssh.is_owner("C:\\Users\\User\\Downloads\\ssh.exe.scr"));
+    let cloud = group_for(ProtectedTarget::CloudCliToken).unwrap();
+    assert!(cloud.is_owner("C:\\Program Files (x86)\\Microsoft SDKs\\Azure\\CLI2\\python.exe"));
+    assert!(group_for(ProtectedTarget::ChromeCookies).is_none());
+  }
 }
@@ -0,0 +1,421 @@
+use crate::config::RansomwareConfig;
+use crate::types::{FileAccessType, Severity};
+use crate::user_profiles::{self, UserProfile};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// Ransomware behavior (R025): one process writing or deleting many files under users' document
+// folders within a short window. Encryption shows up as new files carrying an extension that is not
+// a document type: either the same appended extension everywhere (`report.docx.locked`, the
+// extension distribution has almost no entropy) or a random one per file (nearly every extension
+// distinct). A burst without that pattern (a sync client, an unzip) stays YELLOW; with it the
+// finding is RED, which triggers the kill switch like any other RED incident.
+//
+// Each process reports at most once per severity while its burst lasts, so a running encryption
+// produces one YELLOW and one RED incident, not one per file.
+
+/// Folders under each user profile that ransomware goes after first.
+const DOCUMENT_DIRS: &[&str] = &[
+  "Documents",
+  "Desktop",
+  "Pictures",
+  "Videos",
+  "Music",
+  "Downloads",
+  "OneDrive",
+];
+
+/// Extensions of ordinary user files (and the temp files editors write next to them).
+const KNOWN_EXTENSIONS: &[&str] = &[
+  "doc",
+  "docx",
+  "docm",
+  "dot",
+  "dotx",
+  "xls",
+  "xlsx",
+  "xlsm",
+  "xlsb",
+  "ppt",
+  "pptx",
+  "pptm",
+  "pdf",
+  "txt",
+  "rtf",
+  "odt",
+  "ods",
+  "odp",
+  "csv",
+  "md",
+  "xml",
+  "json",
+  "html",
+  "htm",
+  "jpg",
+  "jpeg",
+  "png",
+  "gif",
+  "bmp",
+  "tif",
+  "tiff",
+  "heic",
+  "webp",
+  "svg",
+  "raw",
+  "cr2",
+  "nef",
+  "psd",
+  "ai",
+  "mp3",
+  "wav",
+  "flac",
+  "m4a",
+  "mp4",
+  "mov",
+  "avi",
+  "mkv",
+  "wmv",
+  "zip",
+  "7z",
+  "rar",
+  "gz",
+  "tar",
+  "iso",
+  "exe",
+  "msi",
+  "lnk",
+  "url",
+  "ini",
+  "log",
+  "tmp",
+  "bak",
+  "db",
+  "sqlite",
+  "accdb",
+  "pst",
+  "ost",
+  "eml",
+  "msg",
+  "vsdx",
+  "one",
+  "epub",
+  "crdownload",
+  "part",
+];
+
+/// Tracked processes before idle ones are dropped.
+const MAX_TRACKED_PIDS: usize = 256;
+
+#[derive(Debug, Clone)]
+enum Change {
+  Delete,
+  /// A written file and its extension if it is not a known user file type.
+  Write {
+    new_extension: Option<String>,
+  },
+}
+
+#[derive(Debug, Default)]
+struct PidState {
+  changes: VecDeque<(u64, Change)>,
+  reported: Option<Severity>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Burst {
+  pub files: usize,
+  pub writes: usize,
+  pub deletes: usize,
+  /// Written files with a non-document extension.
+  pub new_extension_files: usize,
+  /// Most common non-document extensions with their counts, most frequent first.
+  pub top_extensions: Vec<(String, usize)>,
+  /// Shannon entropy of the non-document extension distribution.
+  pub entropy_bits: f64,
+  pub severity: Severity,
+}
+
+#[derive(Default)]
+pub(crate) struct Tracker {
+  pids: HashMap<u32, PidState>,
+  dirs: Option<(Arc<Vec<UserProfile>>, Vec<String>)>,
+}
+
+impl Tracker {
+  /// Drops the changes counted for `pid`, e.g. after it exited.
+  pub(crate) fn forget(&mut self, pid: u32) {
+    self.pids.remove(&pid);
+  }
+
+  /// Records a file change; returns a burst when `pid` just crossed a reporting threshold.
+  pub(crate) fn observe(
+    &mut self,
+    cfg: &RansomwareConfig,
+    pid: u32,
+    file_path: &str,
+    access: FileAccessType,
+    ts: u64,
+  ) -> Option<Burst> {
+    if !cfg.enabled || access == FileAccessType::Read || !self.in_document_dir(file_path) {
+      return None;
+    }
+    let window_ms = cfg.window_seconds.saturating_mul(1000);
+    if self.pids.len() >= MAX_TRACKED_PIDS {
+      self.pids.retain(|_, s| {
+        s.changes
+          .back()
+          .is_some_and(|(t, _)| ts.saturating_sub(*t) <= window_ms)
+      });
+    }
+
+    let state = self.pids.entry(pid).or_default();
+    while let Some((t, _)) = state.changes.front() {
+      if ts.saturating_sub(*t) <= window_ms {
+        break;
+      }
+      state.changes.pop_front();
+    }
+    if state.changes.is_empty() {
+      state.reported = None;
+    }
+    let change = match access {
+      FileAccessType::Delete => Change::Delete,
+      _ => Change::Write {
+        new_extension: new_extension(file_path),
+      },
+    };
+    state.changes.push_back((ts, change));
+    if state.changes.len() < cfg.min_files {
+      return None;
+    }
+
+    let burst = summarize(cfg, &state.changes);
+    let already = match state.reported {
+      Some(Severity::Red) => true,
+      Some(_) => burst.severity != Severity::Red,
+      None => false,
+    };
+    if already {
+      return None;
+    }
+    state.reported = Some(burst.severity);
+    Some(burst)
+  }
+
+  fn in_document_dir(&mut self, file_path: &str) -> bool {
+    let users = user_profiles::all();
+    let stale = self
+      .dirs
+      .as_ref()
+      .is_none_or(|(cached, _)| !Arc::ptr_eq(cached, &users));
+    if stale {
+      let dirs = document_dirs_lower(&users);
+      self.dirs = Some((users, dirs));
+    }
+    let path = normalize(file_path);
+    self
+      .dirs
+      .as_ref()
+      .is_some_and(|(_, dirs)| dirs.iter().any(|d| path.starts_with(d.as_str())))
+  }
+}
+
+/// Whether `file_path` is under a user's document folders.
+pub(crate) fn is_document_path(file_path: &str) -> bool {
+  let path = normalize(file_path);
+  document_dirs_lower(&user_profiles::all())
+    .iter()
+    .any(|d| path.starts_with(d.as_str()))
+}
+
+/// Document folders of `users`.
+pub(crate) fn document_dirs(users: &[UserProfile]) -> Vec<PathBuf> {
+  users
+    .iter()
+    .flat_map(|u| DOCUMENT_DIRS.iter().map(move |d| u.home.join(d)))
+    .collect()
+}
+
+/// Lowercased document folders of every user, with a trailing separator.
+fn document_dirs_lower(users: &[UserProfile]) -> Vec<String> {
+  document_dirs(users)
+    .iter()
+    .map(|d| {
+      let mut dir = normalize(&d.to_string_lossy());
+      dir.push('\\');
+      dir
+    })
+    .collect()
+}
+
+fn normalize(path: &str) -> String {
+  path.replace('/', "\\").to_ascii_lowercase()
+}
+
+/// The file's extension when it isn't a known user file type (`None` for files without one).
+fn new_extension(file_path: &str) -> Option<String> {
+  let name = file_path.rsplit(['\\', '/']).next().unwrap_or(file_path);
+  let (stem, ext) = name.rsplit_once('.')?;
+  let ext = ext.to_ascii_lowercase();
+  if stem.is_empty() || ext.is_empty() || KNOWN_EXTENSIONS.contains(&ext.as_str()) {
+    return None;
+  }
+  Some(ext)
+}
+
+fn summarize(cfg: &RansomwareConfig, changes: &VecDeque<(u64, Change)>) -> Burst {
+  let mut counts: HashMap<&str, usize> = HashMap::new();
+  let (mut writes, mut deletes) = (0, 0);
+  for (_, change) in changes {
+    match change {
+      Change::Delete => deletes += 1,
+      Change::Write { new_extension } => {
+        writes += 1;
+        if let Some(ext) = new_extension {
+          *counts.entry(ext.as_str()).or_default() += 1;
+        }
+      }
+    }
+  }
+
+  let new_extension_files: usize = counts.values().sum();
+  let entropy_bits = entropy(counts.values().copied(), new_extension_files);
+  let ratio = if writes == 0 {
+    0.0
+  } else {
+    new_extension_files as f64 / writes as f64
+  };
+  // One or two appended extensions, or (almost) a different random one per file.
+  let ransom_pattern =
+    entropy_bits <= 1.0 || counts.len() as f64 >= 0.9 * new_extension_files as f64;
+  let severity =
+    if new_extension_files > 0 && ratio >= cfg.min_new_extension_ratio && ransom_pattern {
+      Severity::Red
+    } else {
+      Severity::Yellow
+    };
+
+  let mut top_extensions: Vec<(String, usize)> = counts
+    .into_iter()
+    .map(|(e, n)| (e.to_string(), n))
+    .collect();
+  top_extensions.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+  top_extensions.truncate(3);
+
+  Burst {
+    files: changes.len(),
+    writes,
+    deletes,
+    new_extension_files,
+    top_extensions,
+    entropy_bits,
+    severity,
+  }
+}
+
+fn entropy(counts: impl Iterator<Item = usize>, total: usize) -> f64 {
+  if total == 0 {
+    return 0.0;
+  }
+  counts
+    .filter(|n| *n > 0)
+    .map(|n| {
+      let p = n as f64 / total as f64;
+      -p * p.log2()
+    })
+    .sum()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn cfg() -> RansomwareConfig {
+    RansomwareConfig {
+      min_files: 10,
+      ..RansomwareConfig::default()
+    }
+  }
+
+  fn docs(name: &str) -> String {
+    let home = UserProfile::current_process().home;
+    format!("{}\\Documents\\{name}", home.to_string_lossy())
+  }
+
+  #[test]
+  fn burst_is_yellow_until_files_get_a_ransom_extension() {
+    let cfg = cfg();
+    let mut t = Tracker::default();
+    let base = 1_700_000_000_000u64;
+
+    // Outside document folders and reads never count.
+    for i in 0..20 {
+      assert!(t
+        .observe(
+          &cfg,
+          7,
+          &format!("C:\\Temp\\{i}.locked"),
+          FileAccessType::Write,
+          base
+        )
+        .is_none());
+      assert!(t
+        .observe(
+          &cfg,
+          7,
+          &docs(&format!("{i}.docx")),
+          FileAccessType::Read,
+          base
+        )
+        .is_none());
+    }
+
+    // A sync client writing ordinary documents: one YELLOW, then quiet.
+    let mut yellow = Vec::new();
+    for i in 0..20 {
+      yellow.extend(t.observe(
+        &cfg,
+        8,
+        &docs(&format!("{i}.jpg")),
+        FileAccessType::Write,
+        base + i,
+      ));
+    }
+    assert_eq!(yellow.len(), 1);
+    assert_eq!(yellow[0].severity, Severity::Yellow);
+
+    // Encryption: originals deleted, copies with one appended extension written.
+    let mut found = Vec::new();
+    for i in 0..20 {
+      let name = format!("report{i}.docx");
+      found.extend(t.observe(&cfg, 9, &docs(&name), FileAccessType::Delete, base + i));
+      found.extend(t.observe(
+        &cfg,
+        9,
+        &docs(&format!("{name}.locked")),
+        FileAccessType::Write,
+        base + i,
+      ));
+    }
+    assert_eq!(found.len(), 1);
+    let burst = &found[0];
+    assert_eq!(burst.severity, Severity::Red);
+    assert_eq!(burst.top_extensions, vec![("locked".to_string(), 5)]);
+    assert_eq!(burst.entropy_bits, 0.0);
+
+    // Random per-file extensions are just as telling.
+    let mut random = Vec::new();
+    for i in 0..10 {
+      random.extend(t.observe(
+        &cfg,
+        10,
+        &docs(&format!("photo{i}.png.x{i}q7z")),
+        FileAccessType::Write,
+        base + i,
+      ));
+    }
+    assert_eq!(random.len(), 1);
+    assert_eq!(random[0].severity, Severity::Red);
+  }
+}
@@ -1,11 +1,20 @@
 use crate::config::{Config, Mode};
+use crate::crypto_address::{self, AddressKind};
+use crate::event_collector::EventSource;
+use crate::network_hijack::HijackSetting;
+use crate::powershell::{Indicator, ScriptMatch};
 use crate::rules_engine::engine::ProtectedTarget;
+use crate::rules_engine::ransomware::Burst;
+use crate::threat_feed::schema::WalletDrainPattern;
 use crate::types::{Evidence, FileAccessType, Finding, Severity};
+use crate::wallet_drain;
 
 // These rules are currently used for learning, tuning, and logging only.
 // In learning mode, their severity is capped at YELLOW and they never trigger containment.
 
-pub const ACTIVE_RULE_IDS: &[&str] = &["R001", "R002", "R003", "R004", "R005"];
+pub const ACTIVE_RULE_IDS: &[&str] = &[
+  "R001", "R002", "R003", "R004", "R005", "R018", "R019", "R020", "R021", "R022",
+];
 
 #[derive(Debug, Clone, Copy)]
 pub struct RuleMeta {
@@ -40,6 +49,335 @@ pub const R005: RuleMeta = RuleMeta {
   default_severity: Severity::Yellow,
 };
 
+pub const R007: RuleMeta = RuleMeta {
+  id: "R007",
+  title: "High-rate enumeration under browser profile directories",
+  default_severity: Severity::Yellow,
+};
+pub const R008: RuleMeta = RuleMeta {
+  id: "R008",
+  title: "Unknown/unsigned publisher touched protected browser target",
+  default_severity: Severity::Yellow,
+};
+pub const R009: RuleMeta = RuleMeta {
+  id: "R009",
+  title: "Sensitive access followed by outbound network connection",
+  default_severity: Severity::Red,
+};
+pub const R010: RuleMeta = RuleMeta {
+  id: "R010",
+  title: "Outbound connection after sensitive access to direct IP / unknown host",
+  default_severity: Severity::Red,
+};
+
+pub const R011: RuleMeta = RuleMeta {
+  id: "R011",
+  title: "Chromium browser started with --load-extension (unpacked extension sideload)",
+  default_severity: Severity::Yellow,
+};
+pub const R012: RuleMeta = RuleMeta {
+  id: "R012",
+  title: "Browser extension force-install policy (ExtensionInstallForcelist) modified",
+  default_severity: Severity::Yellow,
+};
+
+pub const R013: RuleMeta = RuleMeta {
+  id: "R013",
+  title: "Hosts file modified by unsigned process",
+  default_severity: Severity::Yellow,
+};
+pub const R014: RuleMeta = RuleMeta {
+  id: "R014",
+  title: "System proxy / AutoConfigURL changed by unsigned process",
+  default_severity: Severity::Yellow,
+};
+pub const R015: RuleMeta = RuleMeta {
+  id: "R015",
+  title: "DNS server setting changed by unsigned process",
+  default_severity: Severity::Yellow,
+};
+
+pub const R016: RuleMeta = RuleMeta {
+  id: "R016",
+  title: "DNS lookup of threat-feed blocklisted domain",
+  default_severity: Severity::Red,
+};
+pub const R017: RuleMeta = RuleMeta {
+  id: "R017",
+  title: "DGA-like or newly registered domain looked up after sensitive access",
+  default_severity: Severity::Red,
+};
+
+pub const R018: RuleMeta = RuleMeta {
+  id: "R018",
+  title: "Non-SSH process reads SSH private key",
+  default_severity: Severity::Yellow,
+};
+pub const R019: RuleMeta = RuleMeta {
+  id: "R019",
+  title: "Non-wallet process reads crypto wallet storage",
+  default_severity: Severity::Yellow,
+};
+pub const R020: RuleMeta = RuleMeta {
+  id: "R020",
+  title: "Process reads Windows Credential Manager vault files",
+  default_severity: Severity::Yellow,
+};
+pub const R021: RuleMeta = RuleMeta {
+  id: "R021",
+  title: "Non-CLI process reads cloud CLI credentials",
+  default_severity: Severity::Yellow,
+};
+pub const R022: RuleMeta = RuleMeta {
+  id: "R022",
+  title: "Non-OpenVPN process reads OpenVPN config",
+  default_severity: Severity::Yellow,
+};
+
+pub const R023: RuleMeta = RuleMeta {
+  id: "R023",
+  title: "Crypto wallet-drainer pattern in extension script or command line",
+  default_severity: Severity::Red,
+};
+
+pub const R024: RuleMeta = RuleMeta {
+  id: "R024",
+  title: "Crypto address on the clipboard replaced by another process",
+  default_severity: Severity::Red,
+};
+
+pub const R025: RuleMeta = RuleMeta {
+  id: "R025",
+  title: "Mass file modification with extension change in user documents",
+  default_severity: Severity::Red,
+};
+
+pub const R026: RuleMeta = RuleMeta {
+  id: "R026",
+  title: "Process opens LSASS with memory access (credential dumping)",
+  default_severity: Severity::Red,
+};
+
+pub const R027: RuleMeta = RuleMeta {
+  id: "R027",
+  title: "PowerShell encoded command",
+  default_severity: Severity::Yellow,
+};
+pub const R028: RuleMeta = RuleMeta {
+  id: "R028",
+  title: "PowerShell AMSI bypass attempt",
+  default_severity: Severity::Red,
+};
+pub const R029: RuleMeta = RuleMeta {
+  id: "R029",
+  title: "PowerShell download cradle",
+  default_severity: Severity::Yellow,
+};
+pub const R030: RuleMeta = RuleMeta {
+  id: "R030",
+  title: "Suspicious PowerShell followed by outbound network connection",
+  default_severity: Severity::Red,
+};
+
+pub const ALL_RULES: &[RuleMeta] = &[
+  R001, R002, R003, R004, R005, R007, R008, R009, R010, R011, R012, R013, R014, R015, R016, R017,
+  R018, R019, R020, R021, R022, R023, R024, R025, R026, R027, R028, R029, R030,
+];
+
+/// What a rule needs from the environment before it can produce a finding.
+#[derive(Debug, Clone, Copy)]
+pub struct RuleRequirements {
+  pub id: &'static str,
+  pub sources: &'static [EventSource],
+  pub needs_threat_feed: bool,
+}
+
+const FILE_ONLY: &[EventSource] = &[EventSource::FileAccess];
+const FILE_AND_PROCESS: &[EventSource] = &[EventSource::FileAccess, EventSource::ProcessStart];
+const FILE_AND_NET: &[EventSource] = &[EventSource::FileAccess, EventSource::NetConnect];
+const PROCESS_ONLY: &[EventSource] = &[EventSource::ProcessStart];
+const REGISTRY_ONLY: &[EventSource] = &[EventSource::RegistrySet];
+const REGISTRY_AND_PROCESS: &[EventSource] = &[EventSource::RegistrySet, EventSource::ProcessStart];
+const DNS_ONLY: &[EventSource] = &[EventSource::DnsQuery];
+const FILE_AND_DNS: &[EventSource] = &[EventSource::FileAccess, EventSource::DnsQuery];
+const CLIPBOARD_ONLY: &[EventSource] = &[EventSource::ClipboardChange];
+const PROCESS_ACCESS_AND_START: &[EventSource] =
+  &[EventSource::ProcessAccess, EventSource::ProcessStart];
+const SCRIPT_ONLY: &[EventSource] = &[EventSource::ScriptBlock];
+const SCRIPT_AND_NET: &[EventSource] = &[EventSource::ScriptBlock, EventSource::NetConnect];
+
+pub const RULE_REQUIREMENTS: &[RuleRequirements] = &[
+  RuleRequirements {
+    id: "R001",
+    sources: FILE_ONLY,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R002",
+    sources: FILE_ONLY,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R003",
+    sources: FILE_ONLY,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R004",
+    sources: FILE_ONLY,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R005",
+    sources: FILE_ONLY,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R007",
+    sources: FILE_ONLY,
+    needs_threat_feed: false,
+  },
+  // R008 relies on the signer publisher captured at process start.
+  RuleRequirements {
+    id: "R008",
+    sources: FILE_AND_PROCESS,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R009",
+    sources: FILE_AND_NET,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R010",
+    sources: FILE_AND_NET,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R011",
+    sources: PROCESS_ONLY,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R012",
+    sources: REGISTRY_ONLY,
+    needs_threat_feed: false,
+  },
+  // R013-R015 only fire for unsigned writers, which needs the publisher from process start.
+  RuleRequirements {
+    id: "R013",
+    sources: FILE_AND_PROCESS,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R014",
+    sources: REGISTRY_AND_PROCESS,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R015",
+    sources: REGISTRY_AND_PROCESS,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R016",
+    sources: DNS_ONLY,
+    needs_threat_feed: true,
+  },
+  // R017's DGA heuristic works without a feed; the newly-registered list needs one.
+  RuleRequirements {
+    id: "R017",
+    sources: FILE_AND_DNS,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R018",
+    sources: FILE_ONLY,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R019",
+    sources: FILE_ONLY,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R020",
+    sources: FILE_ONLY,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R021",
+    sources: FILE_ONLY,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R022",
+    sources: FILE_ONLY,
+    needs_threat_feed: false,
+  },
+  // R023 matches extension scripts as they are written; command-line patterns also need process
+  // start events.
+  RuleRequirements {
+    id: "R023",
+    sources: FILE_ONLY,
+    needs_threat_feed: true,
+  },
+  RuleRequirements {
+    id: "R024",
+    sources: CLIPBOARD_ONLY,
+    needs_threat_feed: false,
+  },
+  // R025 counts deletes too, but file creation (Sysmon EID 11) alone is enough to fire.
+  RuleRequirements {
+    id: "R025",
+    sources: FILE_ONLY,
+    needs_threat_feed: false,
+  },
+  // R026's lineage evidence and publisher allowlist come from process start events.
+  RuleRequirements {
+    id: "R026",
+    sources: PROCESS_ACCESS_AND_START,
+    needs_threat_feed: false,
+  },
+  // R027 reads `-EncodedCommand` from process command lines; script blocks add base64 decoded
+  // in-line.
+  RuleRequirements {
+    id: "R027",
+    sources: PROCESS_ONLY,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R028",
+    sources: SCRIPT_ONLY,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R029",
+    sources: SCRIPT_ONLY,
+    needs_threat_feed: false,
+  },
+  RuleRequirements {
+    id: "R030",
+    sources: SCRIPT_AND_NET,
+    needs_threat_feed: false,
+  },
+];
+
+pub fn requirements_for(rule_id: &str) -> Option<&'static RuleRequirements> {
+  RULE_REQUIREMENTS.iter().find(|r| r.id == rule_id)
+}
+
+/// Threat feed detections have their own namespace: `TF` followed by letters, digits, `-` or `_`.
+/// Config and scenarios accept these IDs whether or not the current bundle defines them.
+pub fn is_feed_rule_id(id: &str) -> bool {
+  let id = id.trim();
+  id.len() > 2
+    && id[..2].eq_ignore_ascii_case("TF")
+    && id[2..]
+      .bytes()
+      .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
 pub(crate) fn file_access_rule_findings(
   cfg: &Config,
   pid: u32,
@@ -72,6 +410,26 @@ pub(crate) fn file_access_rule_findings(
       R005,
       "Non-browser process accessed Firefox key4.db (learning only)",
     ),
+    ProtectedTarget::SshKey => (
+      R018,
+      "Non-SSH process accessed an SSH private key (learning only)",
+    ),
+    ProtectedTarget::CryptoWallet => (
+      R019,
+      "Non-wallet process accessed crypto wallet storage (learning only)",
+    ),
+    ProtectedTarget::CredentialVault => (
+      R020,
+      "Process accessed Windows Credential Manager vault files (learning only)",
+    ),
+    ProtectedTarget::CloudCliToken => (
+      R021,
+      "Non-CLI process accessed cloud CLI credentials (learning only)",
+    ),
+    ProtectedTarget::VpnConfig => (
+      R022,
+      "Non-OpenVPN process accessed an OpenVPN config (learning only)",
+    ),
     _ => return out,
   };
 
@@ -92,6 +450,319 @@ pub(crate) fn file_access_rule_findings(
   out
 }
 
+pub(crate) fn wallet_drain_script_finding(
+  pid: u32,
+  image_path: &str,
+  file_path: &str,
+  pattern: &WalletDrainPattern,
+  ts: u64,
+) -> Finding {
+  Finding {
+    rule_id: R023.id.to_string(),
+    severity: R023.default_severity,
+    description: format!(
+      "Browser extension script matches wallet-drainer pattern {}",
+      wallet_drain::label(pattern)
+    ),
+    evidence: vec![Evidence::File {
+      pid,
+      image_path: Some(image_path.to_string()),
+      file_path: file_path.to_string(),
+      access: FileAccessType::Write,
+    }],
+    timestamp_unix_ms: ts,
+  }
+}
+
+pub(crate) fn wallet_drain_command_finding(
+  pid: u32,
+  image_path: &str,
+  command_line: &str,
+  pattern: &WalletDrainPattern,
+  ts: u64,
+) -> Finding {
+  Finding {
+    rule_id: R023.id.to_string(),
+    severity: R023.default_severity,
+    description: format!(
+      "Process command line matches wallet-drainer pattern {}",
+      wallet_drain::label(pattern)
+    ),
+    evidence: vec![Evidence::CommandLine {
+      pid,
+      image_path: image_path.to_string(),
+      command_line: command_line.to_string(),
+    }],
+    timestamp_unix_ms: ts,
+  }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn clipboard_hijack_finding(
+  pid: u32,
+  image_path: Option<&str>,
+  kind: AddressKind,
+  previous_pid: u32,
+  previous: &str,
+  current: &str,
+  delta_ms: u64,
+  ts: u64,
+) -> Finding {
+  Finding {
+    rule_id: R024.id.to_string(),
+    severity: R024.default_severity,
+    description: format!(
+      "{} address on the clipboard was replaced with a different address {delta_ms}ms after it was copied",
+      kind.label()
+    ),
+    evidence: vec![Evidence::Clipboard {
+      pid,
+      image_path: image_path.map(str::to_string),
+      previous_pid,
+      previous: crypto_address::redact(previous),
+      current: crypto_address::redact(current),
+    }],
+    timestamp_unix_ms: ts,
+  }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn lsass_access_finding(
+  pid: u32,
+  image_path: &str,
+  target_pid: u32,
+  target_image: &str,
+  granted_access: u32,
+  rights: &[&str],
+  lineage: Vec<Evidence>,
+  ts: u64,
+) -> Finding {
+  let mut evidence = vec![Evidence::ProcessAccess {
+    pid,
+    image_path: Some(image_path.to_string()),
+    target_pid,
+    target_image: target_image.to_string(),
+    granted_access: format!("0x{granted_access:x} ({})", rights.join(", ")),
+  }];
+  evidence.extend(lineage);
+  Finding {
+    rule_id: R026.id.to_string(),
+    severity: R026.default_severity,
+    description: format!(
+      "Process opened LSASS with {} (possible credential dumping)",
+      rights.join(", ")
+    ),
+    evidence,
+    timestamp_unix_ms: ts,
+  }
+}
+
+/// R027-R029 for one indicator in a script block or a PowerShell command line.
+pub(crate) fn powershell_finding(
+  m: &ScriptMatch,
+  evidence: Evidence,
+  lineage: Vec<Evidence>,
+  ts: u64,
+) -> Finding {
+  let meta = match m.indicator {
+    Indicator::EncodedCommand => R027,
+    Indicator::AmsiBypass => R028,
+    Indicator::DownloadCradle => R029,
+  };
+  let mut evidence = vec![evidence];
+  evidence.extend(lineage);
+  Finding {
+    rule_id: meta.id.to_string(),
+    severity: meta.default_severity,
+    description: format!("PowerShell {} ({})", m.indicator.label(), m.marker),
+    evidence,
+    timestamp_unix_ms: ts,
+  }
+}
+
+/// R030: the process (tree) that ran a flagged script connected out within the correlation
+/// window.
+pub(crate) fn powershell_connect_finding(
+  rule_ids: &[String],
+  script: Evidence,
+  network: Evidence,
+  delta_ms: u64,
+  ts: u64,
+) -> Finding {
+  Finding {
+    rule_id: R030.id.to_string(),
+    severity: R030.default_severity,
+    description: format!(
+      "Outbound connection {}s after PowerShell flagged by {}",
+      delta_ms / 1000,
+      rule_ids.join(", ")
+    ),
+    evidence: vec![script, network],
+    timestamp_unix_ms: ts,
+  }
+}
+
+/// R025 at the burst's severity: YELLOW for a burst of changes, RED (the rule's default) when new
+/// files carry a ransom-style extension.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn ransomware_finding(
+  pid: u32,
+  image_path: &str,
+  file_path: &str,
+  access: FileAccessType,
+  burst: &Burst,
+  window_seconds: u64,
+  ts: u64,
+) -> Finding {
+  let description = if burst.severity == Severity::Red {
+    format!(
+      "Process modified {} files in user documents within {window_seconds}s and {} of them got a non-document extension",
+      burst.files, burst.new_extension_files
+    )
+  } else {
+    format!(
+      "Process modified {} files in user documents within {window_seconds}s",
+      burst.files
+    )
+  };
+  let extensions = burst
+    .top_extensions
+    .iter()
+    .map(|(e, n)| format!(".{e} x{n}"))
+    .collect::<Vec<_>>()
+    .join(", ");
+  Finding {
+    rule_id: R025.id.to_string(),
+    severity: burst.severity,
+    description,
+    evidence: vec![
+      Evidence::File {
+        pid,
+        image_path: Some(image_path.to_string()),
+        file_path: file_path.to_string(),
+        access,
+      },
+      Evidence::Note {
+        message: format!(
+          "{} writes, {} deletes; new extensions: {}; extension entropy {:.2} bits",
+          burst.writes,
+          burst.deletes,
+          if extensions.is_empty() {
+            "none"
+          } else {
+            &extensions
+          },
+          burst.entropy_bits
+        ),
+      },
+    ],
+    timestamp_unix_ms: ts,
+  }
+}
+
+pub(crate) fn load_extension_finding(
+  pid: u32,
+  image_path: &str,
+  command_line: &str,
+  extension_paths: &[String],
+  ts: u64,
+) -> Finding {
+  Finding {
+    rule_id: R011.id.to_string(),
+    severity: R011.default_severity,
+    description: format!(
+      "Chromium browser started with unpacked extension(s): {}",
+      extension_paths.join(", ")
+    ),
+    evidence: vec![Evidence::CommandLine {
+      pid,
+      image_path: image_path.to_string(),
+      command_line: command_line.to_string(),
+    }],
+    timestamp_unix_ms: ts,
+  }
+}
+
+pub(crate) fn forcelist_finding(
+  pid: u32,
+  image_path: &str,
+  key_path: &str,
+  value: Option<&str>,
+  ts: u64,
+) -> Finding {
+  Finding {
+    rule_id: R012.id.to_string(),
+    severity: R012.default_severity,
+    description: "Browser extension force-install policy (ExtensionInstallForcelist) modified"
+      .to_string(),
+    evidence: vec![Evidence::Registry {
+      pid,
+      image_path: Some(image_path.to_string()),
+      key_path: key_path.to_string(),
+      value: value.map(str::to_string),
+    }],
+    timestamp_unix_ms: ts,
+  }
+}
+
+pub(crate) fn hijack_finding(
+  setting: HijackSetting,
+  pid: u32,
+  image_path: &str,
+  target: &str,
+  before: Option<String>,
+  after: Option<String>,
+  ts: u64,
+) -> Finding {
+  let (meta, description) = match setting {
+    HijackSetting::HostsFile => (R013, "Hosts file modified by unsigned process"),
+    HijackSetting::Proxy => (R014, "System proxy setting changed by unsigned process"),
+    HijackSetting::DnsServer => (R015, "DNS server setting changed by unsigned process"),
+  };
+  Finding {
+    rule_id: meta.id.to_string(),
+    severity: meta.default_severity,
+    description: format!(
+      "{description}: {} -> {}",
+      before.as_deref().unwrap_or("<unknown>"),
+      after.as_deref().unwrap_or("<deleted>")
+    ),
+    evidence: vec![Evidence::SettingChange {
+      pid,
+      image_path: Some(image_path.to_string()),
+      setting: setting.label().to_string(),
+      target: target.to_string(),
+      before,
+      after,
+    }],
+    timestamp_unix_ms: ts,
+  }
+}
+
+pub(crate) fn blocked_domain_finding(
+  pid: u32,
+  image_path: &str,
+  query_name: &str,
+  query_results: Option<&str>,
+  blocklist_entry: &str,
+  ts: u64,
+) -> Finding {
+  Finding {
+    rule_id: R016.id.to_string(),
+    severity: R016.default_severity,
+    description: format!(
+      "DNS lookup of blocklisted domain {query_name} (feed entry {blocklist_entry})"
+    ),
+    evidence: vec![Evidence::Dns {
+      pid,
+      image_path: Some(image_path.to_string()),
+      query_name: query_name.to_string(),
+      query_results: query_results.map(str::to_string),
+    }],
+    timestamp_unix_ms: ts,
+  }
+}
+
 fn cap_for_learning(cfg: &Config, sev: Severity) -> Severity {
   if cfg.mode == Mode::Learning {
     Severity::Yellow
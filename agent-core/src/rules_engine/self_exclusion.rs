@@ -0,0 +1,156 @@
+use defender_common::hash::sha256_hex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Binaries shipped by the installer next to each other in INSTALLFOLDER.
+const OWN_BINARY_NAMES: &[&str] = &["agent-core.exe", "scanner.exe", "AI.Defender.Tray.exe"];
+
+/// Images whose hash is kept; the cache starts over when it is full.
+pub(super) const MAX_CACHED_IMAGES: usize = 4096;
+
+/// Identifies events produced by AI Defender's own binaries so the engine never reacts to them
+/// (e.g. the scanner reading browser directories, or the agent fetching the threat feed).
+///
+/// Matching is by normalized image path first; if the path differs (copied or renamed binary),
+/// the image is hashed and compared against the hashes of our own binaries. The hash is cached per
+/// path and recomputed when the file's size or modification time changes, so a copy replaced by
+/// something else stops matching. Our own hashes are computed lazily, the first time an unmatched
+/// image actually exists on disk.
+#[derive(Debug, Default)]
+pub struct SelfExclusion {
+  binaries: Vec<PathBuf>,
+  paths: HashSet<String>,
+  hashes: Option<HashSet<String>>,
+  hash_cache: HashMap<String, CachedImage>,
+}
+
+#[derive(Debug)]
+struct CachedImage {
+  modified: Option<SystemTime>,
+  len: u64,
+  sha256: Option<String>,
+}
+
+impl SelfExclusion {
+  /// Discover our binaries next to the running executable.
+  pub fn discover() -> Self {
+    let Ok(exe) = std::env::current_exe() else {
+      return Self::default();
+    };
+    let mut candidates = vec![exe.clone()];
+    if let Some(dir) = exe.parent() {
+      candidates.extend(OWN_BINARY_NAMES.iter().map(|n| dir.join(n)));
+    }
+    Self::from_paths(&candidates)
+  }
+
+  pub fn from_paths(paths: &[PathBuf]) -> Self {
+    let binaries: Vec<PathBuf> = paths.iter().filter(|p| p.exists()).cloned().collect();
+    let paths = binaries
+      .iter()
+      .map(|p| normalize(&p.to_string_lossy()))
+      .collect();
+    Self {
+      binaries,
+      paths,
+      hashes: None,
+      hash_cache: HashMap::new(),
+    }
+  }
+
+  pub fn is_self(&mut self, image_path: &str) -> bool {
+    let norm = normalize(image_path);
+    if norm.is_empty() {
+      return false;
+    }
+    if self.paths.contains(&norm) {
+      return true;
+    }
+    if self.binaries.is_empty() {
+      return false;
+    }
+
+    let Ok(meta) = std::fs::metadata(image_path) else {
+      return false;
+    };
+    let modified = meta.modified().ok();
+    let len = meta.len();
+    let stale = self
+      .hash_cache
+      .get(&norm)
+      .map(|c| c.modified != modified || c.len != len)
+      .unwrap_or(true);
+    if stale {
+      if self.hash_cache.len() >= MAX_CACHED_IMAGES {
+        self.hash_cache.clear();
+      }
+      let sha256 = sha256_hex(Path::new(image_path)).ok();
+      self.hash_cache.insert(
+        norm.clone(),
+        CachedImage {
+          modified,
+          len,
+          sha256,
+        },
+      );
+    }
+    let Some(hash) = self.hash_cache.get(&norm).and_then(|c| c.sha256.as_ref()) else {
+      return false;
+    };
+
+    let binaries = &self.binaries;
+    self
+      .hashes
+      .get_or_insert_with(|| binaries.iter().filter_map(|p| sha256_hex(p).ok()).collect())
+      .contains(hash)
+  }
+}
+
+pub(super) fn normalize(path: &str) -> String {
+  path.trim().replace('/', "\\").to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  #[test]
+  fn own_binary_matches_by_path_and_hash() {
+    let exe = std::env::current_exe().unwrap();
+    let mut ex = SelfExclusion::from_paths(std::slice::from_ref(&exe));
+
+    assert!(ex.is_self(&exe.to_string_lossy()));
+    assert!(ex.is_self(&exe.to_string_lossy().to_ascii_uppercase()));
+    assert!(!ex.is_self("C:\\Temp\\evil.exe"));
+    assert!(!ex.is_self(""));
+  }
+
+  #[test]
+  fn copied_binary_matches_by_hash() {
+    let exe = std::env::current_exe().unwrap();
+    let mut ex = SelfExclusion::from_paths(std::slice::from_ref(&exe));
+
+    let copy = std::env::temp_dir().join(format!("aid-self-{}.bin", uuid::Uuid::new_v4()));
+    fs::copy(&exe, &copy).unwrap();
+    let matched = ex.is_self(&copy.to_string_lossy());
+    let _ = fs::remove_file(&copy);
+    assert!(matched);
+  }
+
+  #[test]
+  fn replaced_copy_stops_matching() {
+    let exe = std::env::current_exe().unwrap();
+    let mut ex = SelfExclusion::from_paths(std::slice::from_ref(&exe));
+
+    let copy = std::env::temp_dir().join(format!("aid-self-{}.exe", uuid::Uuid::new_v4()));
+    fs::copy(&exe, &copy).unwrap();
+    let before = ex.is_self(&copy.to_string_lossy());
+    fs::write(&copy, b"MZ not the agent").unwrap();
+    let after = ex.is_self(&copy.to_string_lossy());
+    let _ = fs::remove_file(&copy);
+    assert!(before);
+    assert!(!after);
+  }
+}
@@ -0,0 +1,205 @@
+use super::engine::{ProcessInfo, SensitiveAccess};
+use super::Engine;
+use crate::config::Config;
+use crate::lifecycle;
+use crate::paths;
+use crate::runtime;
+use crate::types::now_unix_ms;
+use defender_common::fs::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Correlation state across agent restarts. The engine keeps process lineage, sensitive accesses
+// and enumeration hits in memory, so a service restart (or malware killing the agent between
+// reading `Login Data` and sending it) would forget the read and miss R009. The agent writes a
+// snapshot every `engine_state.save_interval_seconds` while events come in and when it stops, and
+// takes it back at startup if it is at most `engine_state.max_age_seconds` old and from the same
+// boot (PIDs are reused after a reboot).
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(super) struct EngineState {
+  #[serde(default)]
+  pub(super) procs: HashMap<u32, ProcessInfo>,
+  #[serde(default)]
+  pub(super) sensitive: Vec<SensitiveAccess>,
+  #[serde(default)]
+  pub(super) enum_hits: HashMap<u32, Vec<u64>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StateFile {
+  saved_at_unix_ms: u64,
+  #[serde(default)]
+  boot_unix_ms: Option<u64>,
+  #[serde(default)]
+  state: EngineState,
+}
+
+pub struct StateStore {
+  base: PathBuf,
+  next_save_unix_ms: u64,
+}
+
+impl StateStore {
+  pub fn new(base: &Path) -> Self {
+    Self {
+      base: base.to_path_buf(),
+      next_save_unix_ms: 0,
+    }
+  }
+
+  /// Loads the previous run's snapshot into `engine` unless it is too old or from an earlier
+  /// boot. Returns whether it did.
+  pub fn restore(&self, cfg: &Config, engine: &mut Engine) -> bool {
+    self.restore_at(cfg, engine, now_unix_ms(), lifecycle::boot_time_unix_ms())
+  }
+
+  fn restore_at(
+    &self,
+    cfg: &Config,
+    engine: &mut Engine,
+    now_unix_ms: u64,
+    boot_unix_ms: Option<u64>,
+  ) -> bool {
+    if !cfg.engine_state.enabled {
+      return false;
+    }
+    let Some(file) = fs::read_to_string(paths::engine_state_path(&self.base))
+      .ok()
+      .and_then(|raw| serde_json::from_str::<StateFile>(&raw).ok())
+    else {
+      return false;
+    };
+
+    let age_seconds = now_unix_ms.saturating_sub(file.saved_at_unix_ms) / 1000;
+    let same_boot = match (file.boot_unix_ms, boot_unix_ms) {
+      (Some(then), Some(now)) => then.abs_diff(now) <= lifecycle::BOOT_TIME_TOLERANCE_MS,
+      _ => true,
+    };
+    if age_seconds > cfg.engine_state.max_age_seconds || !same_boot {
+      tracing::info!(
+        age_seconds,
+        same_boot,
+        "engine state from the previous run is stale; starting without it"
+      );
+      return false;
+    }
+
+    tracing::info!(
+      age_seconds,
+      processes = file.state.procs.len(),
+      sensitive_accesses = file.state.sensitive.len(),
+      "restored correlation state from the previous run"
+    );
+    engine.restore(cfg, file.state, now_unix_ms);
+    true
+  }
+
+  /// Writes a snapshot of `engine`, at most once per `save_interval_seconds` unless `force`.
+  pub fn save(&mut self, cfg: &Config, engine: &Engine, force: bool) {
+    self.save_at(cfg, engine, now_unix_ms(), force);
+  }
+
+  fn save_at(&mut self, cfg: &Config, engine: &Engine, now_unix_ms: u64, force: bool) {
+    if !cfg.engine_state.enabled || runtime::is_dry_run() {
+      return;
+    }
+    if !force && now_unix_ms < self.next_save_unix_ms {
+      return;
+    }
+    self.next_save_unix_ms =
+      now_unix_ms.saturating_add(cfg.engine_state.save_interval_seconds.saturating_mul(1000));
+
+    let max_age_ms = cfg.engine_state.max_age_seconds.saturating_mul(1000);
+    let file = StateFile {
+      saved_at_unix_ms: now_unix_ms,
+      boot_unix_ms: lifecycle::boot_time_unix_ms(),
+      state: engine.snapshot(cfg, max_age_ms, now_unix_ms),
+    };
+    if let Err(e) = serde_json::to_string(&file)
+      .map_err(anyhow::Error::from)
+      .and_then(|raw| write_atomic(&paths::engine_state_path(&self.base), &raw))
+    {
+      tracing::warn!(error = ?e, "failed to write engine state (continuing)");
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::{Event, FileAccessType};
+  use std::net::IpAddr;
+
+  #[test]
+  fn sensitive_access_survives_a_restart_within_the_staleness_bound() {
+    let base = std::env::temp_dir().join(format!("aid-engine-state-{}", uuid::Uuid::new_v4()));
+    let cfg = Config::default();
+    let t = 1_700_000_000_000u64;
+    let image = "C:\\Temp\\evil.exe".to_string();
+
+    let mut before = Engine::new();
+    before
+      .process(
+        &cfg,
+        &[
+          Event::ProcessStart {
+            pid: 1234,
+            ppid: 0,
+            image_path: image.clone(),
+            signer_publisher: None,
+            command_line: None,
+            download_origin: None,
+            process_guid: None,
+            parent_process_guid: None,
+            timestamp_unix_ms: t,
+          },
+          Event::FileAccess {
+            pid: 1234,
+            image_path: Some(image.clone()),
+            file_path:
+              "C:\\Users\\User\\AppData\\Local\\Google\\Chrome\\User Data\\Default\\Login Data"
+                .to_string(),
+            access: FileAccessType::Read,
+            process_guid: None,
+            timestamp_unix_ms: t + 1_000,
+          },
+        ],
+      )
+      .unwrap();
+    let mut store = StateStore::new(&base);
+    store.save_at(&cfg, &before, t + 2_000, true);
+
+    // A snapshot from an earlier boot, or older than the bound, is not restored.
+    let mut after = Engine::new();
+    let boot = lifecycle::boot_time_unix_ms().map(|b| b + lifecycle::BOOT_TIME_TOLERANCE_MS + 1);
+    if boot.is_some() {
+      assert!(!store.restore_at(&cfg, &mut after, t + 3_000, boot));
+    }
+    let too_late = t + 2_000 + (cfg.engine_state.max_age_seconds + 1) * 1000;
+    assert!(!store.restore_at(&cfg, &mut after, too_late, None));
+
+    assert!(store.restore_at(&cfg, &mut after, t + 3_000, None));
+    let incidents = after
+      .process(
+        &cfg,
+        &[Event::NetConnect {
+          pid: 1234,
+          image_path: Some(image),
+          dest_ip: IpAddr::from([1, 2, 3, 4]),
+          dest_port: 443,
+          dest_host: None,
+          protocol: "tcp".to_string(),
+          process_guid: None,
+          timestamp_unix_ms: t + 4_000,
+        }],
+      )
+      .unwrap();
+    assert!(incidents
+      .iter()
+      .any(|i| i.findings.iter().any(|f| f.rule_id == "R009")));
+    let _ = fs::remove_dir_all(&base);
+  }
+}
@@ -0,0 +1,606 @@
+use super::Engine;
+use crate::paths;
+use crate::runtime;
+use crate::types::{now_unix_ms, Event, Evidence, Incident, Severity};
+use defender_common::fs::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Correlation tracing for debugging. `--debug trace add <pid>` marks a PID; while marked, the
+// engine records every decision it makes for that PID's events (sensitive queue pushes and prunes,
+// allowlist checks, window comparisons, incidents raised and what rule policy did to them). The
+// agent appends these to `debug\trace.ndjson`, and `--debug explain-incident <id>` turns the steps
+// around an incident into a narrative. Marks expire after a day since PIDs are reused.
+
+const TRACE_TTL_MS: u64 = 24 * 60 * 60 * 1000;
+/// Budget for the current trace log plus the one rotated copy.
+const TRACE_LOG_MAX_BYTES: u64 = 20 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum TraceStep {
+  ProcessStarted {
+    image_path: String,
+    ppid: u32,
+    signer_publisher: Option<String>,
+  },
+  Ignored {
+    reason: String,
+  },
+  SensitiveQueued {
+    file_path: String,
+    target: String,
+    queued: usize,
+  },
+  SensitivePruned {
+    dropped: usize,
+    remaining: usize,
+    window_seconds: u64,
+  },
+  AllowlistChecked {
+    check: String,
+    value: Option<String>,
+    matched: bool,
+  },
+  NoSensitiveAccess {
+    tree_root: u32,
+  },
+  WindowCompared {
+    access_pid: u32,
+    file_path: String,
+    delta_ms: u64,
+    window_ms: u64,
+  },
+  Candidate {
+    incident_id: String,
+    rule_ids: Vec<String>,
+    severity: Severity,
+  },
+  PolicyApplied {
+    incident_id: String,
+    kept: bool,
+    severity: Option<Severity>,
+    rule_ids: Vec<String>,
+  },
+  CircuitBreakerSuppressed {
+    incident_id: String,
+  },
+}
+
+impl TraceStep {
+  fn incident_id(&self) -> Option<&str> {
+    match self {
+      Self::Candidate { incident_id, .. }
+      | Self::PolicyApplied { incident_id, .. }
+      | Self::CircuitBreakerSuppressed { incident_id } => Some(incident_id),
+      _ => None,
+    }
+  }
+
+  /// Why correlation stopped at this step, if it did.
+  fn stop_reason(&self) -> Option<String> {
+    match self {
+      Self::Ignored { reason } => Some(format!("event ignored: {reason}")),
+      Self::AllowlistChecked {
+        check,
+        matched: true,
+        ..
+      } => Some(format!("{check} allowlisted")),
+      Self::NoSensitiveAccess { .. } => Some("no sensitive access held to correlate".to_string()),
+      Self::WindowCompared {
+        delta_ms,
+        window_ms,
+        ..
+      } if delta_ms > window_ms => Some(format!(
+        "sensitive access was {}s earlier, outside the {}s window",
+        delta_ms / 1000,
+        window_ms / 1000
+      )),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for TraceStep {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::ProcessStarted {
+        image_path,
+        ppid,
+        signer_publisher,
+      } => write!(
+        f,
+        "process started: {image_path} (parent pid {ppid}, signer {})",
+        signer_publisher.as_deref().unwrap_or("none")
+      ),
+      Self::Ignored { reason } => write!(f, "ignored: {reason}"),
+      Self::SensitiveQueued {
+        file_path,
+        target,
+        queued,
+      } => write!(
+        f,
+        "protected {target} accessed ({file_path}); {queued} access(es) now held for correlation"
+      ),
+      Self::SensitivePruned {
+        dropped,
+        remaining,
+        window_seconds,
+      } => write!(
+        f,
+        "{dropped} access(es) older than the {window_seconds}s window dropped; {remaining} still held"
+      ),
+      Self::AllowlistChecked {
+        check,
+        value,
+        matched,
+      } => write!(
+        f,
+        "{check} allowlist check for {}: {}",
+        value.as_deref().unwrap_or("<none>"),
+        if *matched { "matched" } else { "no match" }
+      ),
+      Self::NoSensitiveAccess { tree_root } => write!(
+        f,
+        "no sensitive access held for this process tree (root pid {tree_root}); nothing to correlate"
+      ),
+      Self::WindowCompared {
+        access_pid,
+        file_path,
+        delta_ms,
+        window_ms,
+      } => write!(
+        f,
+        "{:.1}s since pid {access_pid} accessed {file_path}; window is {}s, so {}",
+        *delta_ms as f64 / 1000.0,
+        window_ms / 1000,
+        if delta_ms <= window_ms {
+          "correlated"
+        } else {
+          "too late to correlate"
+        }
+      ),
+      Self::Candidate {
+        incident_id,
+        rule_ids,
+        severity,
+      } => write!(
+        f,
+        "incident {incident_id} raised by {} ({severity:?})",
+        rule_ids.join(", ")
+      ),
+      Self::PolicyApplied {
+        kept: false,
+        incident_id,
+        ..
+      } => write!(
+        f,
+        "rule policy disabled every finding of {incident_id}; incident dropped"
+      ),
+      Self::PolicyApplied {
+        incident_id,
+        severity,
+        rule_ids,
+        ..
+      } => write!(
+        f,
+        "after rule policy {incident_id} is {:?} ({})",
+        severity.unwrap_or(Severity::Green),
+        rule_ids.join(", ")
+      ),
+      Self::CircuitBreakerSuppressed { incident_id } => write!(
+        f,
+        "circuit breaker suppressed {incident_id} (incident storm)"
+      ),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceRecord {
+  pub pid: u32,
+  pub timestamp_unix_ms: u64,
+  /// Kind of event the step was taken for; none for steps after the whole batch.
+  #[serde(default)]
+  pub event: Option<String>,
+  #[serde(flatten)]
+  pub step: TraceStep,
+}
+
+pub fn event_kind(ev: &Event) -> &'static str {
+  match ev {
+    Event::ProcessStart { .. } => "process_start",
+    Event::ProcessExit { .. } => "process_exit",
+    Event::FileAccess { .. } => "file_access",
+    Event::NetConnect { .. } => "net_connect",
+    Event::RegistrySet { .. } => "registry_set",
+    Event::DnsQuery { .. } => "dns_query",
+    Event::ProcessAccess { .. } => "process_access",
+    Event::ScriptBlock { .. } => "script_block",
+    Event::ClipboardChange { .. } => "clipboard_change",
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TracedPid {
+  pub pid: u32,
+  pub added_at_unix_ms: u64,
+}
+
+impl TracedPid {
+  pub fn expires_at_unix_ms(&self) -> u64 {
+    self.added_at_unix_ms.saturating_add(TRACE_TTL_MS)
+  }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TracedPidsFile {
+  #[serde(default)]
+  pids: Vec<TracedPid>,
+}
+
+pub fn add_pid(base: &Path, pid: u32) -> anyhow::Result<()> {
+  if runtime::is_dry_run() {
+    tracing::warn!(pid, "DRY-RUN: would start tracing pid");
+    return Ok(());
+  }
+  let now = now_unix_ms();
+  let mut file = load_pids(base);
+  file
+    .pids
+    .retain(|p| p.pid != pid && p.expires_at_unix_ms() > now);
+  file.pids.push(TracedPid {
+    pid,
+    added_at_unix_ms: now,
+  });
+  save_pids(base, &file)?;
+  tracing::info!(pid, "correlation tracing enabled for pid");
+  Ok(())
+}
+
+pub fn remove_pid(base: &Path, pid: u32) -> anyhow::Result<bool> {
+  let mut file = load_pids(base);
+  let before = file.pids.len();
+  file.pids.retain(|p| p.pid != pid);
+  if file.pids.len() == before {
+    return Ok(false);
+  }
+  if runtime::is_dry_run() {
+    tracing::warn!(pid, "DRY-RUN: would stop tracing pid");
+    return Ok(true);
+  }
+  save_pids(base, &file)?;
+  tracing::info!(pid, "correlation tracing disabled for pid");
+  Ok(true)
+}
+
+/// Unexpired traced PIDs.
+pub fn list_pids(base: &Path) -> Vec<TracedPid> {
+  let now = now_unix_ms();
+  load_pids(base)
+    .pids
+    .into_iter()
+    .filter(|p| p.expires_at_unix_ms() > now)
+    .collect()
+}
+
+fn load_pids(base: &Path) -> TracedPidsFile {
+  fs::read_to_string(paths::trace_pids_path(base))
+    .ok()
+    .and_then(|raw| toml::from_str(&raw).ok())
+    .unwrap_or_default()
+}
+
+fn save_pids(base: &Path, file: &TracedPidsFile) -> anyhow::Result<()> {
+  write_atomic(
+    &paths::trace_pids_path(base),
+    &toml::to_string_pretty(file)?,
+  )
+}
+
+/// Agent-side half: keeps the engine's traced PIDs in sync with `--debug trace` and writes the
+/// recorded steps out after each batch.
+pub struct Tracer {
+  base: PathBuf,
+  loaded_modified: Option<SystemTime>,
+  next_expiry_check_unix_ms: u64,
+}
+
+impl Tracer {
+  pub fn new(base: &Path) -> Self {
+    Self {
+      base: base.to_path_buf(),
+      loaded_modified: None,
+      next_expiry_check_unix_ms: 0,
+    }
+  }
+
+  pub fn sync(&mut self, engine: &mut Engine) {
+    let modified = fs::metadata(paths::trace_pids_path(&self.base))
+      .and_then(|m| m.modified())
+      .ok();
+    let now = now_unix_ms();
+    if modified == self.loaded_modified && now < self.next_expiry_check_unix_ms {
+      return;
+    }
+    self.loaded_modified = modified;
+    self.next_expiry_check_unix_ms = now + 60_000;
+
+    let pids: HashSet<u32> = list_pids(&self.base).iter().map(|p| p.pid).collect();
+    if !pids.is_empty() {
+      tracing::info!(pids = ?pids, "correlation tracing active");
+    }
+    engine.set_trace_pids(pids);
+  }
+
+  /// Writes the engine's steps for this batch. `passed` are the incidents left after the circuit
+  /// breaker, so suppressed ones can be noted.
+  pub fn flush(&mut self, engine: &mut Engine, passed: &[Incident]) {
+    let mut records = engine.drain_trace();
+    if records.is_empty() || runtime::is_dry_run() {
+      return;
+    }
+
+    let suppressed: Vec<TraceRecord> = records
+      .iter()
+      .filter_map(|r| match &r.step {
+        TraceStep::PolicyApplied {
+          incident_id,
+          kept: true,
+          ..
+        } if !passed.iter().any(|i| &i.incident_id == incident_id) => Some(TraceRecord {
+          step: TraceStep::CircuitBreakerSuppressed {
+            incident_id: incident_id.clone(),
+          },
+          ..r.clone()
+        }),
+        _ => None,
+      })
+      .collect();
+    records.extend(suppressed);
+
+    if let Err(e) = append(&self.base, &records) {
+      tracing::warn!(error = ?e, "failed to write correlation trace (continuing)");
+    }
+  }
+}
+
+fn append(base: &Path, records: &[TraceRecord]) -> anyhow::Result<()> {
+  let mut lines = String::new();
+  for r in records {
+    lines.push_str(&serde_json::to_string(r)?);
+    lines.push('\n');
+  }
+
+  let path = paths::trace_log_path(base);
+  fs::create_dir_all(paths::debug_dir(base))?;
+  let current = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+  if current > 0 && current.saturating_add(lines.len() as u64) > TRACE_LOG_MAX_BYTES / 2 {
+    fs::rename(&path, rotated_path(&path))?;
+  }
+
+  let mut f = OpenOptions::new().create(true).append(true).open(&path)?;
+  f.write_all(lines.as_bytes())?;
+  Ok(())
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+  path.with_extension("1.ndjson")
+}
+
+/// All recorded steps, oldest first.
+fn load_records(base: &Path) -> Vec<TraceRecord> {
+  let path = paths::trace_log_path(base);
+  [rotated_path(&path), path]
+    .iter()
+    .filter_map(|p| fs::read_to_string(p).ok())
+    .flat_map(|raw| {
+      raw
+        .lines()
+        .filter_map(|l| serde_json::from_str::<TraceRecord>(l).ok())
+        .collect::<Vec<_>>()
+    })
+    .collect()
+}
+
+/// Step-by-step account of how `incident` came about, or `None` if its PID wasn't traced at the
+/// time. Covers the traced PIDs involved from one correlation window before the incident to one
+/// after, so later checks that did or didn't escalate are included.
+pub fn explain(base: &Path, incident: &Incident, window_seconds: u64) -> Option<Vec<String>> {
+  let records = load_records(base);
+  let raised = records.iter().find(|r| {
+    matches!(&r.step, TraceStep::Candidate { incident_id, .. } if *incident_id == incident.incident_id)
+  })?;
+  let at = raised.timestamp_unix_ms;
+  let window_ms = window_seconds.saturating_mul(1000);
+
+  let mut pids: HashSet<u32> = incident_pids(incident);
+  pids.insert(raised.pid);
+
+  let mut steps: Vec<&TraceRecord> = records
+    .iter()
+    .filter(|r| pids.contains(&r.pid))
+    .filter(|r| {
+      // Other incidents being raised is part of the story; what happened to them afterwards isn't.
+      matches!(r.step, TraceStep::Candidate { .. })
+        || r
+          .step
+          .incident_id()
+          .is_none_or(|id| id == incident.incident_id)
+    })
+    .filter(|r| {
+      let in_range = r.timestamp_unix_ms + window_ms >= at && r.timestamp_unix_ms <= at + window_ms;
+      in_range || matches!(r.step, TraceStep::ProcessStarted { .. })
+    })
+    .collect();
+  // Policy outcomes are written after the whole batch; put them next to their incident.
+  steps.sort_by_key(|r| r.timestamp_unix_ms);
+
+  let mut out = vec![format!(
+    "Incident {} ({:?}), traced pid(s) {}",
+    incident.incident_id,
+    incident.severity,
+    sorted(&pids)
+  )];
+  for (n, r) in steps.iter().enumerate() {
+    let offset = r.timestamp_unix_ms as f64 / 1000.0 - at as f64 / 1000.0;
+    out.push(format!(
+      "{:>3}. {offset:+.1}s pid {} {}: {}",
+      n + 1,
+      r.pid,
+      r.event.as_deref().unwrap_or("policy"),
+      r.step
+    ));
+  }
+
+  out.push(String::new());
+  if incident.severity == Severity::Red {
+    out.push("Outcome: escalated to RED.".to_string());
+  } else {
+    let escalation = steps.iter().enumerate().find_map(|(n, r)| match &r.step {
+      TraceStep::Candidate {
+        incident_id,
+        severity: Severity::Red,
+        ..
+      } if r.timestamp_unix_ms >= at => Some((n, incident_id)),
+      _ => None,
+    });
+    if let Some((n, red_id)) = escalation {
+      out.push(format!(
+        "Outcome: stayed {:?}; the activity was escalated separately: RED incident {red_id} followed at step {}.",
+        incident.severity,
+        n + 1
+      ));
+      return Some(out);
+    }
+    out.push(format!(
+      "Outcome: stayed {:?}; no check in this window escalated it to RED.",
+      incident.severity
+    ));
+    let stops: Vec<String> = steps
+      .iter()
+      .enumerate()
+      .filter(|(_, r)| r.timestamp_unix_ms >= at)
+      .filter(|(_, r)| matches!(r.event.as_deref(), Some("net_connect" | "dns_query")))
+      .filter_map(|(n, r)| Some(format!("step {}: {}", n + 1, r.step.stop_reason()?)))
+      .collect();
+    if stops.is_empty() {
+      out.push("No outbound connection or lookup was traced after it.".to_string());
+    } else {
+      out.push("Correlation stopped at:".to_string());
+      out.extend(stops.into_iter().map(|s| format!("- {s}")));
+    }
+  }
+  Some(out)
+}
+
+fn incident_pids(incident: &Incident) -> HashSet<u32> {
+  incident
+    .findings
+    .iter()
+    .flat_map(|f| &f.evidence)
+    .filter_map(|e| match e {
+      Evidence::Process { pid, .. }
+      | Evidence::File { pid, .. }
+      | Evidence::Network { pid, .. }
+      | Evidence::Dns { pid, .. } => Some(*pid),
+      _ => None,
+    })
+    .collect()
+}
+
+fn sorted(pids: &HashSet<u32>) -> String {
+  let mut v: Vec<u32> = pids.iter().copied().collect();
+  v.sort_unstable();
+  v.iter().map(u32::to_string).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::Config;
+  use crate::rules_engine::self_exclusion::SelfExclusion;
+  use crate::types::FileAccessType;
+  use std::net::IpAddr;
+
+  #[test]
+  fn explains_why_an_incident_did_not_escalate() {
+    let base = std::env::temp_dir().join(format!("aid-trace-{}", uuid::Uuid::new_v4()));
+    let cfg = Config {
+      correlation_window_seconds: 1,
+      ..Config::default()
+    };
+    let mut engine = Engine::with_self_exclusion(SelfExclusion::default());
+    engine.set_trace_pids(HashSet::from([7]));
+
+    let ts = 1_700_000_000_000;
+    let image = "C:\\Temp\\tool.exe".to_string();
+    let events = [
+      Event::ProcessStart {
+        pid: 7,
+        ppid: 1,
+        image_path: image.clone(),
+        signer_publisher: None,
+        command_line: None,
+        download_origin: None,
+        process_guid: None,
+        parent_process_guid: None,
+        timestamp_unix_ms: ts,
+      },
+      Event::FileAccess {
+        pid: 7,
+        image_path: Some(image.clone()),
+        file_path:
+          "C:\\Users\\User\\AppData\\Local\\Google\\Chrome\\User Data\\Default\\Login Data"
+            .to_string(),
+        access: FileAccessType::Read,
+        process_guid: None,
+        timestamp_unix_ms: ts + 100,
+      },
+      Event::NetConnect {
+        pid: 7,
+        image_path: Some(image),
+        dest_ip: IpAddr::from([1, 2, 3, 4]),
+        dest_port: 443,
+        dest_host: None,
+        protocol: "tcp".to_string(),
+        process_guid: None,
+        timestamp_unix_ms: ts + 900,
+      },
+    ];
+    let incidents = engine.process(&cfg, &events).unwrap();
+    let mut tracer = Tracer::new(&base);
+    tracer.flush(&mut engine, &incidents);
+
+    // The read raised a YELLOW incident; the connection 0.8s later correlated into a RED one.
+    let yellow = incidents
+      .iter()
+      .find(|i| i.severity != Severity::Red)
+      .unwrap();
+    let red = incidents
+      .iter()
+      .find(|i| i.severity == Severity::Red)
+      .unwrap();
+
+    let text = explain(&base, red, 1).unwrap().join("\n");
+    assert!(text.contains("process started: C:\\Temp\\tool.exe"));
+    assert!(text.contains("correlated"));
+    assert!(text.contains("Outcome: escalated to RED."));
+
+    let text = explain(&base, yellow, 1).unwrap().join("\n");
+    assert!(text.contains("protected chromium_login_data accessed"));
+    assert!(text.contains("Outcome: stayed Yellow"));
+    assert!(text.contains(&format!("RED incident {} followed", red.incident_id)));
+    // Untraced incidents have nothing to explain.
+    let mut other = yellow.clone();
+    other.incident_id = uuid::Uuid::new_v4().to_string();
+    assert!(explain(&base, &other, 1).is_none());
+
+    let _ = fs::remove_dir_all(&base);
+  }
+}
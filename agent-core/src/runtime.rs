@@ -1,6 +1,7 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 
 static DRY_RUN: AtomicBool = AtomicBool::new(false);
+static WORKER: AtomicBool = AtomicBool::new(false);
 
 pub fn configure_from_args(args: &[String]) -> bool {
   let enabled = args.iter().any(|arg| arg == "--dry-run");
@@ -15,3 +16,13 @@ pub fn set_dry_run(enabled: bool) {
 pub fn is_dry_run() -> bool {
   DRY_RUN.load(Ordering::SeqCst)
 }
+
+/// Set in the `--worker` process of the split service; privileged actions are requested from the
+/// service core instead of performed (see `privsep`).
+pub fn set_worker(enabled: bool) {
+  WORKER.store(enabled, Ordering::SeqCst);
+}
+
+pub fn is_worker() -> bool {
+  WORKER.load(Ordering::SeqCst)
+}
@@ -0,0 +1,79 @@
+use crate::paths;
+use crate::runtime;
+use defender_common::fs::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanOutcome {
+  Completed,
+  Canceled,
+  Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanRecord {
+  pub scan_id: String,
+  pub mode: String,
+  pub started_at_unix_ms: u64,
+  pub ended_at_unix_ms: u64,
+  #[serde(default)]
+  pub roots: Vec<String>,
+  pub files_scanned: u64,
+  pub findings_count: u64,
+  #[serde(default)]
+  pub incident_id: Option<String>,
+  pub outcome: ScanOutcome,
+}
+
+pub fn record(rec: &ScanRecord) -> anyhow::Result<PathBuf> {
+  let base = paths::base_dir()?;
+  let file_path = paths::scan_history_dir(&base).join(format!("{}.toml", rec.scan_id));
+
+  if runtime::is_dry_run() {
+    tracing::warn!(
+      scan_id = %rec.scan_id,
+      outcome = ?rec.outcome,
+      "DRY-RUN: would store scan history record"
+    );
+    return Ok(file_path);
+  }
+
+  let raw = toml::to_string_pretty(rec)?;
+  write_atomic(&file_path, &raw)?;
+  Ok(file_path)
+}
+
+/// Most recent scans first (by start time).
+pub fn list_recent(limit: usize) -> anyhow::Result<Vec<ScanRecord>> {
+  let base = paths::base_dir()?;
+  let mut all = load_all(&paths::scan_history_dir(&base));
+  all.sort_by_key(|r| std::cmp::Reverse(r.started_at_unix_ms));
+  all.truncate(limit);
+  Ok(all)
+}
+
+pub fn last_completed(mode: &str) -> anyhow::Result<Option<ScanRecord>> {
+  let base = paths::base_dir()?;
+  Ok(
+    load_all(&paths::scan_history_dir(&base))
+      .into_iter()
+      .filter(|r| r.outcome == ScanOutcome::Completed && r.mode == mode)
+      .max_by_key(|r| r.ended_at_unix_ms),
+  )
+}
+
+fn load_all(dir: &Path) -> Vec<ScanRecord> {
+  let Ok(entries) = fs::read_dir(dir) else {
+    return Vec::new();
+  };
+
+  entries
+    .flatten()
+    .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("toml"))
+    .filter_map(|e| fs::read_to_string(e.path()).ok())
+    .filter_map(|raw| toml::from_str::<ScanRecord>(&raw).ok())
+    .collect()
+}
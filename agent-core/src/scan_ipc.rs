@@ -0,0 +1,205 @@
+use crate::paths;
+use crate::types::now_unix_ms;
+use defender_common::fs::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Running scans and the agent, through the IPC mailbox:
+//
+//   ipc\scans\<scan-id>.toml      scanner -> agent (registration, then progress every few seconds)
+//   ipc\scans\<scan-id>.cancel    -> scanner (`--scanner cancel <scan-id>` or `scanner --cancel`)
+//
+// The scanner removes both when it ends; the agent lists the registrations (`--scanner running`,
+// the status API) and clears those of scanners that died. A scanner started while the agent isn't
+// running falls back to `scans\<scan-id>.running` / `.cancel`, which `request_cancel` also serves.
+
+/// The agent refreshes `ipc\status.toml` every few seconds; older means it isn't running.
+const AGENT_ALIVE_MS: u64 = 30_000;
+/// A registration not updated for this long belongs to a scanner that died.
+const STALE_MS: u64 = 10 * 60_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanProgress {
+  pub scan_id: String,
+  pub mode: String,
+  pub pid: u32,
+  pub started_at_unix_ms: u64,
+  pub updated_at_unix_ms: u64,
+  pub scanned: u64,
+  /// Files found by the pre-scan enumeration, once it has finished.
+  #[serde(default)]
+  pub total_files: Option<u64>,
+  pub findings: u64,
+}
+
+fn registration_path(base: &Path, scan_id: &str) -> PathBuf {
+  paths::ipc_scans_dir(base).join(format!("{scan_id}.toml"))
+}
+
+fn cancel_path(base: &Path, scan_id: &str) -> PathBuf {
+  paths::ipc_scans_dir(base).join(format!("{scan_id}.cancel"))
+}
+
+/// Whether the agent is servicing the mailbox.
+pub fn agent_running(base: &Path) -> bool {
+  fs::metadata(paths::ipc_status_path(base))
+    .and_then(|m| m.modified())
+    .ok()
+    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+    .is_some_and(|d| now_unix_ms().saturating_sub(d.as_millis() as u64) <= AGENT_ALIVE_MS)
+}
+
+/// Registers a scan, or updates its progress.
+pub fn report(base: &Path, progress: &ScanProgress) -> anyhow::Result<()> {
+  let path = registration_path(base, &progress.scan_id);
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir)?;
+  }
+  write_atomic(&path, &toml::to_string(progress)?)
+}
+
+/// Removes a scan's registration and any cancel command for it.
+pub fn unregister(base: &Path, scan_id: &str) {
+  let _ = fs::remove_file(registration_path(base, scan_id));
+  let _ = fs::remove_file(cancel_path(base, scan_id));
+}
+
+pub fn cancel_requested(base: &Path, scan_id: &str) -> bool {
+  cancel_path(base, scan_id).exists()
+}
+
+/// Scans registered with the agent, oldest first.
+pub fn running(base: &Path) -> Vec<ScanProgress> {
+  let now = now_unix_ms();
+  let mut scans: Vec<ScanProgress> = registrations(base)
+    .into_iter()
+    .filter(|s| !is_stale(s, now))
+    .collect();
+  scans.sort_by_key(|s| s.started_at_unix_ms);
+  scans
+}
+
+fn registrations(base: &Path) -> Vec<ScanProgress> {
+  let Ok(entries) = fs::read_dir(paths::ipc_scans_dir(base)) else {
+    return Vec::new();
+  };
+  entries
+    .flatten()
+    .map(|e| e.path())
+    .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("toml"))
+    .filter_map(|p| toml::from_str(&fs::read_to_string(p).ok()?).ok())
+    .collect()
+}
+
+fn is_stale(scan: &ScanProgress, now: u64) -> bool {
+  now.saturating_sub(scan.updated_at_unix_ms) > STALE_MS
+}
+
+/// Asks a running scan to stop: through the mailbox when it registered there, else through its
+/// stand-alone control file.
+pub fn request_cancel(base: &Path, scan_id: &str) -> anyhow::Result<()> {
+  let id = scan_id.trim();
+  if uuid::Uuid::parse_str(id).is_err() {
+    anyhow::bail!("scan id must be a UUID");
+  }
+  let path = if registration_path(base, id).exists() {
+    cancel_path(base, id)
+  } else if paths::scan_running_path(base, id).exists() {
+    paths::scan_cancel_path(base, id)
+  } else {
+    anyhow::bail!("no running scan with id {id}");
+  };
+  fs::write(path, b"cancel")?;
+  Ok(())
+}
+
+/// Follows registrations from the agent loop: logs scans as they start and end, and clears the
+/// registrations of scanners that stopped reporting.
+pub struct ScanMonitor {
+  base: PathBuf,
+  known: BTreeMap<String, ScanProgress>,
+}
+
+impl ScanMonitor {
+  pub fn new(base: &Path) -> Self {
+    Self {
+      base: base.to_path_buf(),
+      known: BTreeMap::new(),
+    }
+  }
+
+  pub fn tick(&mut self) {
+    let now = now_unix_ms();
+    let mut current = BTreeMap::new();
+    for scan in registrations(&self.base) {
+      if is_stale(&scan, now) {
+        tracing::warn!(
+          scan_id = %scan.scan_id,
+          pid = scan.pid,
+          "scan stopped reporting progress; registration removed"
+        );
+        unregister(&self.base, &scan.scan_id);
+        continue;
+      }
+      if !self.known.contains_key(&scan.scan_id) {
+        tracing::info!(scan_id = %scan.scan_id, mode = %scan.mode, pid = scan.pid, "scan registered");
+      }
+      current.insert(scan.scan_id.clone(), scan);
+    }
+    for (id, scan) in &self.known {
+      if !current.contains_key(id) {
+        tracing::info!(scan_id = %id, scanned = scan.scanned, "scan ended");
+      }
+    }
+    self.known = current;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn cancel_goes_through_the_mailbox_or_the_standalone_file() {
+    let base = std::env::temp_dir().join(format!("aid-scan-ipc-{}", uuid::Uuid::new_v4()));
+    let id = uuid::Uuid::new_v4().to_string();
+    let now = now_unix_ms();
+    let progress = ScanProgress {
+      scan_id: id.clone(),
+      mode: "quick".to_string(),
+      pid: 1,
+      started_at_unix_ms: now,
+      updated_at_unix_ms: now,
+      scanned: 250,
+      total_files: Some(1000),
+      findings: 2,
+    };
+    assert!(request_cancel(&base, &id).is_err());
+
+    report(&base, &progress).unwrap();
+    assert_eq!(running(&base), std::slice::from_ref(&progress));
+    request_cancel(&base, &id).unwrap();
+    assert!(cancel_requested(&base, &id));
+    unregister(&base, &id);
+    assert!(running(&base).is_empty() && !cancel_requested(&base, &id));
+
+    // A dead scanner's registration is cleared by the agent.
+    let stale = ScanProgress {
+      updated_at_unix_ms: now - STALE_MS - 1,
+      ..progress
+    };
+    report(&base, &stale).unwrap();
+    assert!(running(&base).is_empty());
+    ScanMonitor::new(&base).tick();
+    assert!(!registration_path(&base, &id).exists());
+
+    // Stand-alone scans only have their control file.
+    fs::create_dir_all(paths::scans_dir(&base)).unwrap();
+    fs::write(paths::scan_running_path(&base, &id), "1").unwrap();
+    request_cancel(&base, &id).unwrap();
+    assert!(paths::scan_cancel_path(&base, &id).exists());
+    let _ = fs::remove_dir_all(&base);
+  }
+}
@@ -0,0 +1,398 @@
+use crate::config::{Config, ScheduledScanMode};
+use crate::incident_store;
+use crate::license;
+use crate::metrics;
+use crate::paths;
+use crate::response_engine;
+use crate::runtime;
+use crate::scan_history;
+use crate::scan_ipc;
+use crate::types::now_unix_ms;
+use std::fs;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+// Periodic scans from `[scanner] schedule`. The agent loop ticks this; when a run is due the
+// scanner binary is started in the background and its incident (if any) goes through the normal
+// response pipeline once it exits. A run that falls while another scan is active is skipped, and a
+// run missed while the machine was off starts shortly after the agent does.
+
+const MINUTE_MS: u64 = 60_000;
+const DAY_MS: u64 = 24 * 60 * MINUTE_MS;
+const WEEK_MS: u64 = 7 * DAY_MS;
+const MIN_INTERVAL_MINUTES: u64 = 15;
+/// Catch-up runs wait this long after agent start so they don't compete with logon.
+const STARTUP_DELAY_MS: u64 = 10 * MINUTE_MS;
+/// `.running` markers older than this are treated as left over from a crashed scan.
+const STALE_RUNNING_MS: u64 = DAY_MS;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+  Every {
+    interval_ms: u64,
+  },
+  Daily {
+    minute_of_day: u64,
+  },
+  /// `weekday` 0 = Monday.
+  Weekly {
+    weekday: u64,
+    minute_of_day: u64,
+  },
+}
+
+impl Schedule {
+  pub fn parse(text: &str) -> anyhow::Result<Self> {
+    let parts: Vec<String> = text
+      .split_whitespace()
+      .map(|p| p.to_ascii_lowercase())
+      .collect();
+    match parts.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+      ["every", n] => {
+        let (digits, unit) = n.split_at(n.len().saturating_sub(1));
+        let count: u64 = digits
+          .parse()
+          .map_err(|_| anyhow::anyhow!("expected `every <N>m|h|d`"))?;
+        let minutes = match unit {
+          "m" => count,
+          "h" => count.saturating_mul(60),
+          "d" => count.saturating_mul(24 * 60),
+          _ => anyhow::bail!("expected `every <N>m|h|d`"),
+        };
+        if minutes < MIN_INTERVAL_MINUTES {
+          anyhow::bail!("interval must be at least {MIN_INTERVAL_MINUTES} minutes");
+        }
+        Ok(Self::Every {
+          interval_ms: minutes.saturating_mul(MINUTE_MS),
+        })
+      }
+      ["daily", time] => Ok(Self::Daily {
+        minute_of_day: parse_time(time)?,
+      }),
+      ["weekly", day, time] => Ok(Self::Weekly {
+        weekday: parse_weekday(day)?,
+        minute_of_day: parse_time(time)?,
+      }),
+      _ => anyhow::bail!("expected `every <N>m|h|d`, `daily HH:MM` or `weekly <day> HH:MM`"),
+    }
+  }
+
+  /// When the next run is due, given the start of the last scan of this kind. Times are UTC unix
+  /// ms; `offset_ms` is the local time zone's offset from UTC. A calendar slot that passed after
+  /// `last_run` (or with no run at all) is due immediately.
+  fn due_at(self, now: u64, last_run: Option<u64>, offset_ms: i64) -> u64 {
+    let (period, slot) = match self {
+      Self::Every { interval_ms } => {
+        return last_run.map_or(now, |l| l.saturating_add(interval_ms));
+      }
+      Self::Daily { minute_of_day } => (DAY_MS, minute_of_day * MINUTE_MS),
+      Self::Weekly {
+        weekday,
+        minute_of_day,
+      } => (WEEK_MS, weekday * DAY_MS + minute_of_day * MINUTE_MS),
+    };
+
+    // Work in local time; weeks are aligned to Monday 00:00 (1970-01-01 was a Thursday).
+    let local_now = now.saturating_add_signed(offset_ms);
+    let epoch_shift = if period == WEEK_MS { 3 * DAY_MS } else { 0 };
+    let shifted = local_now + epoch_shift;
+    let mut prev = shifted - shifted % period + slot;
+    if prev > shifted {
+      prev -= period;
+    }
+    let prev = (prev - epoch_shift).saturating_add_signed(-offset_ms);
+
+    if last_run.is_none_or(|l| l < prev) {
+      now
+    } else {
+      prev + period
+    }
+  }
+}
+
+fn parse_time(text: &str) -> anyhow::Result<u64> {
+  let (h, m) = text
+    .split_once(':')
+    .ok_or_else(|| anyhow::anyhow!("expected HH:MM, got `{text}`"))?;
+  match (h.parse::<u64>(), m.parse::<u64>()) {
+    (Ok(h), Ok(m)) if h < 24 && m < 60 => Ok(h * 60 + m),
+    _ => anyhow::bail!("expected HH:MM, got `{text}`"),
+  }
+}
+
+fn parse_weekday(text: &str) -> anyhow::Result<u64> {
+  const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+  DAYS
+    .iter()
+    .position(|d| text.starts_with(d))
+    .map(|i| i as u64)
+    .ok_or_else(|| anyhow::anyhow!("unknown weekday `{text}`"))
+}
+
+struct RunningScan {
+  child: Child,
+  mode: ScheduledScanMode,
+  started_at_unix_ms: u64,
+}
+
+pub struct ScanScheduler {
+  not_before_unix_ms: u64,
+  last_run_unix_ms: Option<u64>,
+  running: Option<RunningScan>,
+}
+
+impl ScanScheduler {
+  pub fn new(cfg: &Config) -> Self {
+    Self {
+      not_before_unix_ms: now_unix_ms() + STARTUP_DELAY_MS,
+      last_run_unix_ms: last_scan_started(cfg.scanner.mode),
+      running: None,
+    }
+  }
+
+  pub fn tick(&mut self, cfg: &Config) {
+    self.reap(cfg);
+
+    let Some(schedule) = cfg
+      .scanner
+      .schedule
+      .as_deref()
+      .and_then(|s| Schedule::parse(s).ok())
+    else {
+      return;
+    };
+    let now = now_unix_ms();
+    let due = schedule
+      .due_at(now, self.last_run_unix_ms, local_offset_ms())
+      .max(self.not_before_unix_ms);
+    if now < due {
+      return;
+    }
+    self.last_run_unix_ms = Some(now);
+
+    let mode = cfg.scanner.mode;
+    let licensed = paths::base_dir()
+      .is_ok_and(|base| license::has_feature(&base, license::FEATURE_SCHEDULED_SCANS));
+    if !licensed {
+      tracing::info!(mode = ?mode, "scheduled scan skipped; scheduled scans need a license with this feature");
+      return;
+    }
+    if self.running.is_some() || other_scan_running(now) {
+      tracing::info!(mode = ?mode, "scheduled scan skipped; another scan is running");
+      return;
+    }
+    if runtime::is_dry_run() {
+      tracing::warn!(mode = ?mode, "DRY-RUN: would start scheduled scan");
+      return;
+    }
+    let Some(scanner) = response_engine::targeted_scan::scanner_path() else {
+      tracing::warn!("scanner executable not found next to agent; scheduled scan skipped");
+      return;
+    };
+
+    let flag = match mode {
+      ScheduledScanMode::Quick => "--quick",
+      ScheduledScanMode::Full => "--full",
+    };
+    let spawned = Command::new(&scanner)
+      .arg(flag)
+      .envs(paths::base_dir_override().map(|d| (paths::BASE_DIR_ENV, d)))
+      .stdin(Stdio::null())
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .spawn();
+    match spawned {
+      Ok(child) => {
+        tracing::info!(mode = ?mode, "scheduled scan started");
+        self.running = Some(RunningScan {
+          child,
+          mode,
+          started_at_unix_ms: now,
+        });
+      }
+      Err(e) => tracing::warn!(error = ?e, "failed to start scheduled scan"),
+    }
+  }
+
+  /// On agent stop: handles a scan that already finished. One still running carries on by itself
+  /// and lands in scan history, but its incident won't go through the agent.
+  pub fn shutdown(&mut self, cfg: &Config) {
+    self.reap(cfg);
+    if let Some(run) = &self.running {
+      tracing::info!(
+        mode = ?run.mode,
+        "scheduled scan still running at shutdown; it finishes on its own"
+      );
+    }
+  }
+
+  /// Picks up a finished scan and hands its incident to the response engine.
+  fn reap(&mut self, cfg: &Config) {
+    let Some(run) = self.running.as_mut() else {
+      return;
+    };
+    let status = match run.child.try_wait() {
+      Ok(None) => return,
+      Ok(Some(status)) => Some(status),
+      Err(_) => None,
+    };
+    let run = self.running.take().expect("checked above");
+    tracing::info!(mode = ?run.mode, status = ?status, "scheduled scan finished");
+
+    // The scanner records its own history entry; find the one this run produced.
+    let record = scan_history::list_recent(20).ok().and_then(|recs| {
+      recs.into_iter().find(|r| {
+        r.mode == mode_label(run.mode)
+          && r.started_at_unix_ms + Duration::from_secs(5).as_millis() as u64
+            >= run.started_at_unix_ms
+      })
+    });
+    if let Some(r) = &record {
+      metrics::scan_finished(
+        &r.mode,
+        r.ended_at_unix_ms.saturating_sub(r.started_at_unix_ms),
+      );
+    }
+    let Some(incident_id) = record.and_then(|r| r.incident_id) else {
+      return;
+    };
+    match incident_store::load(&incident_id) {
+      Ok(mut incident) => {
+        incident.actions_taken.push("scheduled_scan".to_string());
+        if let Err(e) = response_engine::handle_incident(cfg, &mut incident) {
+          tracing::warn!(incident_id = %incident_id, error = ?e, "scheduled scan incident handling failed");
+        }
+      }
+      Err(e) => {
+        tracing::warn!(incident_id = %incident_id, error = ?e, "scheduled scan incident not readable")
+      }
+    }
+  }
+}
+
+fn mode_label(mode: ScheduledScanMode) -> &'static str {
+  match mode {
+    ScheduledScanMode::Quick => "quick",
+    ScheduledScanMode::Full => "full",
+  }
+}
+
+fn last_scan_started(mode: ScheduledScanMode) -> Option<u64> {
+  scan_history::list_recent(200)
+    .ok()?
+    .into_iter()
+    .filter(|r| r.mode == mode_label(mode))
+    .map(|r| r.started_at_unix_ms)
+    .max()
+}
+
+/// Any scan (manual, targeted or scheduled) registered with the agent or under `scans\*.running`.
+fn other_scan_running(now: u64) -> bool {
+  let Ok(base) = paths::base_dir() else {
+    return false;
+  };
+  if !scan_ipc::running(&base).is_empty() {
+    return true;
+  }
+  let Ok(entries) = fs::read_dir(paths::scans_dir(&base)) else {
+    return false;
+  };
+  entries.flatten().any(|e| {
+    let path = e.path();
+    path.extension().and_then(|s| s.to_str()) == Some("running") && !is_stale(&path, now)
+  })
+}
+
+fn is_stale(path: &Path, now: u64) -> bool {
+  fs::metadata(path)
+    .and_then(|m| m.modified())
+    .ok()
+    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+    .is_none_or(|d| now.saturating_sub(d.as_millis() as u64) > STALE_RUNNING_MS)
+}
+
+#[cfg(windows)]
+fn local_offset_ms() -> i64 {
+  use windows::Win32::System::Time::{GetTimeZoneInformation, TIME_ZONE_INFORMATION};
+
+  // TIME_ZONE_ID_DAYLIGHT (SystemServices, not enabled for this crate).
+  const TIME_ZONE_ID_DAYLIGHT: u32 = 2;
+
+  let mut tzi = TIME_ZONE_INFORMATION::default();
+  // SAFETY: fills the provided struct; no other pointers are involved.
+  let id = unsafe { GetTimeZoneInformation(&mut tzi) };
+  let bias_minutes = tzi.Bias
+    + if id == TIME_ZONE_ID_DAYLIGHT {
+      tzi.DaylightBias
+    } else {
+      tzi.StandardBias
+    };
+  // Bias is UTC minus local time.
+  -(bias_minutes as i64) * MINUTE_MS as i64
+}
+
+#[cfg(not(windows))]
+fn local_offset_ms() -> i64 {
+  0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_schedules() {
+    assert_eq!(
+      Schedule::parse("every 6h").unwrap(),
+      Schedule::Every {
+        interval_ms: 6 * 60 * MINUTE_MS
+      }
+    );
+    assert_eq!(
+      Schedule::parse("Weekly Sunday 03:15").unwrap(),
+      Schedule::Weekly {
+        weekday: 6,
+        minute_of_day: 195
+      }
+    );
+    assert!(Schedule::parse("every 5m").is_err());
+    assert!(Schedule::parse("daily 24:00").is_err());
+    assert!(Schedule::parse("hourly").is_err());
+  }
+
+  #[test]
+  fn calendar_slots_catch_up_once() {
+    // 2023-11-14 (a Tuesday) 12:00 UTC.
+    let now = 1_699_963_200_000;
+    let daily = Schedule::Daily {
+      minute_of_day: 2 * 60,
+    };
+    let today_0200 = now - 10 * 60 * MINUTE_MS;
+
+    // Never scanned, or last scan before today's slot: due now.
+    assert_eq!(daily.due_at(now, None, 0), now);
+    assert_eq!(daily.due_at(now, Some(today_0200 - 1), 0), now);
+    // Already ran after today's slot: tomorrow 02:00.
+    assert_eq!(
+      daily.due_at(now, Some(today_0200 + 1), 0),
+      today_0200 + DAY_MS
+    );
+    // UTC+3: 02:00 local is 23:00 UTC the day before.
+    assert_eq!(
+      daily.due_at(now, Some(now - MINUTE_MS), 3 * 60 * MINUTE_MS as i64),
+      today_0200 - 3 * 60 * MINUTE_MS + DAY_MS
+    );
+
+    // Monday 02:00 was yesterday at 02:00.
+    let weekly = Schedule::Weekly {
+      weekday: 0,
+      minute_of_day: 2 * 60,
+    };
+    let monday_0200 = today_0200 - DAY_MS;
+    assert_eq!(
+      weekly.due_at(now, Some(monday_0200 + 1), 0),
+      monday_0200 + WEEK_MS
+    );
+  }
+}
@@ -0,0 +1,175 @@
+use crate::rules_engine::rules::{is_feed_rule_id, ALL_RULES};
+use crate::types::{Event, Incident, Severity};
+use anyhow::Context;
+use serde::Deserialize;
+
+// Scenario files for `--simulate from-file`: named lists of events with the rules they are expected
+// to trigger, so new rules and local tuning can be checked without Sysmon. A file holds one
+// scenario or a JSON array of them:
+//
+//   {"name": "stealer reads Chrome logins, then connects out",
+//    "events": [{"FileAccess": {...}}, {"NetConnect": {...}}],
+//    "expect": {"rules": ["R001"], "absent": ["R010"], "severity": "red"}}
+//
+// Events use the agent's `Event` shape (as in `--rules test`). Their timestamps are shifted so the
+// first event happens when the scenario runs; the gaps between events are kept.
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+  pub name: String,
+  pub events: Vec<Event>,
+  #[serde(default)]
+  pub expect: Expect,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expect {
+  /// Rules that must appear among the findings.
+  #[serde(default)]
+  pub rules: Vec<String>,
+  /// Rules that must not.
+  #[serde(default)]
+  pub absent: Vec<String>,
+  /// The highest incident severity; `green` also passes when there is no incident.
+  #[serde(default)]
+  pub severity: Option<Severity>,
+}
+
+/// Parses a scenario file; expected rule IDs must exist.
+pub fn parse(raw: &str) -> anyhow::Result<Vec<Scenario>> {
+  let scenarios: Vec<Scenario> = if raw.trim_start().starts_with('[') {
+    serde_json::from_str(raw)?
+  } else {
+    vec![serde_json::from_str(raw)?]
+  };
+  for s in &scenarios {
+    if s.events.is_empty() {
+      anyhow::bail!("scenario {:?} has no events", s.name);
+    }
+    for id in s.expect.rules.iter().chain(&s.expect.absent) {
+      if !ALL_RULES
+        .iter()
+        .any(|r| r.id.eq_ignore_ascii_case(id.trim()))
+        && !is_feed_rule_id(id)
+      {
+        anyhow::bail!("scenario {:?} expects unknown rule ID {id}", s.name);
+      }
+    }
+  }
+  Ok(scenarios)
+}
+
+/// The scenario's events, moved in time so the first one happens at `start_unix_ms`.
+pub fn events_at(scenario: &Scenario, start_unix_ms: u64) -> anyhow::Result<Vec<Event>> {
+  let first = scenario
+    .events
+    .iter()
+    .map(Event::timestamp_unix_ms)
+    .min()
+    .context("scenario has no events")?;
+  let mut events = scenario.events.clone();
+  for ev in &mut events {
+    let ts = ev.timestamp_unix_ms_mut();
+    *ts = start_unix_ms + (*ts - first);
+  }
+  Ok(events)
+}
+
+/// Why the incidents don't meet `expect`; empty when they do.
+pub fn check(expect: &Expect, incidents: &[Incident]) -> Vec<String> {
+  let fired = |id: &str| {
+    incidents
+      .iter()
+      .flat_map(|i| &i.findings)
+      .any(|f| f.rule_id.eq_ignore_ascii_case(id.trim()))
+  };
+  let mut failures: Vec<String> = expect
+    .rules
+    .iter()
+    .filter(|id| !fired(id))
+    .map(|id| format!("expected {id}, which did not fire"))
+    .chain(
+      expect
+        .absent
+        .iter()
+        .filter(|id| fired(id))
+        .map(|id| format!("{id} fired but was expected to be absent")),
+    )
+    .collect();
+
+  if let Some(want) = expect.severity {
+    let got = incidents
+      .iter()
+      .map(|i| i.severity)
+      .max_by_key(|s| severity_rank(*s));
+    if got.unwrap_or(Severity::Green) != want {
+      failures.push(match got {
+        Some(got) => format!("expected severity {want:?}, got {got:?}"),
+        None => format!("expected severity {want:?}, got no incident"),
+      });
+    }
+  }
+  failures
+}
+
+fn severity_rank(s: Severity) -> u8 {
+  match s {
+    Severity::Green => 0,
+    Severity::Yellow => 1,
+    Severity::Red => 2,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::Finding;
+
+  #[test]
+  fn parses_scenarios_and_checks_expected_rules() {
+    let raw = r#"[{"name": "stealer",
+      "events": [
+        {"FileAccess": {"pid": 1, "image_path": null, "file_path": "x", "access": "read", "timestamp_unix_ms": 5000}},
+        {"DnsQuery": {"pid": 1, "image_path": null, "query_name": "a.example", "query_results": null, "timestamp_unix_ms": 7000}}
+      ],
+      "expect": {"rules": ["R001"], "absent": ["r010"], "severity": "red"}}]"#;
+    let scenarios = parse(raw).unwrap();
+    let events = events_at(&scenarios[0], 1_000_000).unwrap();
+    assert_eq!(events[0].timestamp_unix_ms(), 1_000_000);
+    assert_eq!(events[1].timestamp_unix_ms(), 1_002_000);
+
+    let incident = |rule: &str, severity| Incident {
+      incident_id: "i".to_string(),
+      severity,
+      findings: vec![Finding {
+        rule_id: rule.to_string(),
+        severity,
+        description: String::new(),
+        evidence: Vec::new(),
+        timestamp_unix_ms: 0,
+      }],
+      actions_taken: Vec::new(),
+      created_at_unix_ms: 0,
+      resolution: None,
+      flood: None,
+      score: None,
+      guidance: Default::default(),
+    };
+    let expect = &scenarios[0].expect;
+    assert!(check(expect, &[incident("R001", Severity::Red)]).is_empty());
+    assert_eq!(
+      check(expect, &[incident("R010", Severity::Yellow)]),
+      [
+        "expected R001, which did not fire",
+        "r010 fired but was expected to be absent",
+        "expected severity Red, got Yellow",
+      ]
+    );
+
+    assert!(parse(r#"{"name": "typo", "events": [], "expect": {}}"#).is_err());
+    let unknown = r#"{"name": "typo", "events": [{"DnsQuery": {"pid": 1, "image_path": null, "query_name": "a", "query_results": null, "timestamp_unix_ms": 1}}], "expect": {"absent": ["R999"]}}"#;
+    assert!(parse(unknown).is_err());
+  }
+}
@@ -0,0 +1,242 @@
+use crate::config::ScoringConfig;
+use crate::types::{Evidence, Finding, FindingScore, Incident, RiskScore, Severity};
+use std::collections::BTreeSet;
+use std::sync::RwLock;
+
+// Risk scores (`[scoring]`). Each finding gets a 0-100 score: a base from its rule (or its
+// severity), adjusted for what the evidence says about the process (no signer, running from a
+// user-writable folder), threat-feed reputation hits and correlation with network activity. The
+// incident score is the highest finding score plus a bonus for every other rule involved. Scores
+// are recorded on every incident; with `enabled`, thresholds turn the incident score into its
+// severity, so a tuned score can raise or lower the response instead of the three fixed buckets.
+
+/// Rules whose findings come from threat-feed reputation lists.
+const REPUTATION_RULES: &[&str] = &["R016", "R017", "R023"];
+
+/// Lowercase path fragments of folders any user can write to.
+const USER_WRITABLE_DIRS: &[&str] = &[
+  "\\appdata\\",
+  "\\temp\\",
+  "\\downloads\\",
+  "\\users\\public\\",
+];
+
+static CONFIG: RwLock<Option<ScoringConfig>> = RwLock::new(None);
+
+/// Applies `[scoring]` for incidents scored outside the response engine (stored incidents that
+/// are updated, scan reports); until called, the defaults apply.
+pub fn configure(cfg: &ScoringConfig) {
+  *CONFIG.write().unwrap_or_else(|e| e.into_inner()) = Some(cfg.clone());
+}
+
+/// Scores `incident` and sets its severity: from the thresholds when scoring is enabled,
+/// otherwise the highest finding severity.
+pub fn apply(cfg: &ScoringConfig, incident: &mut Incident) {
+  let score = score_incident(cfg, incident);
+  incident.severity = if cfg.enabled {
+    severity_for(cfg, score.total)
+  } else {
+    incident.max_severity()
+  };
+  incident.score = Some(score);
+}
+
+pub fn configured() -> ScoringConfig {
+  CONFIG
+    .read()
+    .unwrap_or_else(|e| e.into_inner())
+    .clone()
+    .unwrap_or_default()
+}
+
+pub fn score_incident(cfg: &ScoringConfig, incident: &Incident) -> RiskScore {
+  let findings: Vec<FindingScore> = incident
+    .findings
+    .iter()
+    .map(|f| score_finding(cfg, f))
+    .collect();
+  let rules: BTreeSet<String> = findings
+    .iter()
+    .map(|f| f.rule_id.to_ascii_uppercase())
+    .collect();
+  let highest = findings.iter().map(|f| f.score).max().unwrap_or(0);
+  let others = rules.len().saturating_sub(1) as i32;
+  RiskScore {
+    total: clamp(highest as i32 + others * cfg.weights.additional_rule),
+    findings,
+  }
+}
+
+pub fn score_finding(cfg: &ScoringConfig, f: &Finding) -> FindingScore {
+  let w = &cfg.weights;
+  let mut factors = Vec::new();
+  let base = match cfg
+    .rules
+    .iter()
+    .find(|(r, _)| r.trim().eq_ignore_ascii_case(&f.rule_id))
+  {
+    Some((_, score)) => {
+      factors.push(format!("base {score} (rule)"));
+      *score
+    }
+    None => {
+      let (score, label) = match f.severity {
+        Severity::Green => (w.green, "green"),
+        Severity::Yellow => (w.yellow, "yellow"),
+        Severity::Red => (w.red, "red"),
+      };
+      factors.push(format!("base {score} ({label})"));
+      score
+    }
+  };
+
+  let mut score = base as i32;
+  let mut add = |name: &str, points: i32| {
+    if points != 0 {
+      factors.push(format!("{name} {points:+}"));
+      score += points;
+    }
+  };
+  let unsigned = f.evidence.iter().any(|e| {
+    matches!(
+      e,
+      Evidence::Process {
+        signer_publisher: None,
+        ..
+      }
+    )
+  });
+  if unsigned {
+    add("unsigned", w.unsigned);
+  }
+  if f
+    .evidence
+    .iter()
+    .filter_map(evidence_image)
+    .any(is_user_writable)
+  {
+    add("user_writable", w.user_writable);
+  }
+  if REPUTATION_RULES
+    .iter()
+    .any(|r| r.eq_ignore_ascii_case(&f.rule_id))
+  {
+    add("reputation", w.reputation);
+  }
+  if f
+    .evidence
+    .iter()
+    .any(|e| matches!(e, Evidence::Correlation { .. }))
+  {
+    add("correlation", w.correlation);
+  }
+
+  FindingScore {
+    rule_id: f.rule_id.clone(),
+    score: clamp(score),
+    factors,
+  }
+}
+
+pub fn severity_for(cfg: &ScoringConfig, score: u32) -> Severity {
+  if score >= cfg.red {
+    Severity::Red
+  } else if score >= cfg.yellow {
+    Severity::Yellow
+  } else {
+    Severity::Green
+  }
+}
+
+fn clamp(score: i32) -> u32 {
+  score.clamp(0, 100) as u32
+}
+
+fn evidence_image(e: &Evidence) -> Option<&str> {
+  match e {
+    Evidence::Process { image_path, .. } | Evidence::CommandLine { image_path, .. } => {
+      Some(image_path)
+    }
+    Evidence::File { image_path, .. }
+    | Evidence::Network { image_path, .. }
+    | Evidence::Dns { image_path, .. }
+    | Evidence::Registry { image_path, .. }
+    | Evidence::SettingChange { image_path, .. }
+    | Evidence::ProcessAccess { image_path, .. }
+    | Evidence::Script { image_path, .. }
+    | Evidence::Clipboard { image_path, .. } => image_path.as_deref(),
+    Evidence::Correlation { .. } | Evidence::Note { .. } => None,
+  }
+}
+
+fn is_user_writable(image_path: &str) -> bool {
+  let p = image_path.to_ascii_lowercase().replace('/', "\\");
+  USER_WRITABLE_DIRS.iter().any(|d| p.contains(d))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::net::{IpAddr, Ipv4Addr};
+
+  fn finding(rule_id: &str, severity: Severity, evidence: Vec<Evidence>) -> Finding {
+    Finding {
+      rule_id: rule_id.to_string(),
+      severity,
+      description: "test".to_string(),
+      evidence,
+      timestamp_unix_ms: 1_700_000_000_000,
+    }
+  }
+
+  #[test]
+  fn scores_findings_and_maps_incidents_to_severity() {
+    let unsigned_in_temp = Evidence::Process {
+      pid: 42,
+      ppid: 1,
+      image_path: "C:\\Users\\alice\\AppData\\Local\\Temp\\x.exe".to_string(),
+      signer_publisher: None,
+      download_origin: None,
+    };
+    let correlation = Evidence::Correlation {
+      pid: 42,
+      window_seconds: 60,
+      sensitive_file: "Login Data".to_string(),
+      dest_ip: IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)),
+      dest_host: None,
+      delta_seconds: 3,
+    };
+    let mut cfg = ScoringConfig::default();
+
+    let yellow = finding("R001", Severity::Yellow, vec![unsigned_in_temp.clone()]);
+    let s = score_finding(&cfg, &yellow);
+    assert_eq!(s.score, 60);
+    assert_eq!(
+      s.factors,
+      ["base 40 (yellow)", "unsigned +10", "user_writable +10"]
+    );
+
+    let red = finding("R009", Severity::Red, vec![unsigned_in_temp, correlation]);
+    let mut incident = Incident::new(vec![yellow.clone(), red]);
+    apply(&cfg, &mut incident);
+    // 100 (capped) for R009, plus 5 for R001.
+    assert_eq!(incident.score.as_ref().unwrap().total, 100);
+    assert_eq!(incident.severity, Severity::Red);
+
+    // Disabled: the score is recorded but the severity stays the highest finding severity.
+    let mut incident = Incident::new(vec![yellow.clone()]);
+    cfg.rules.insert("r001".to_string(), 55);
+    apply(&cfg, &mut incident);
+    assert_eq!(incident.score.as_ref().unwrap().total, 75);
+    assert_eq!(incident.severity, Severity::Yellow);
+
+    cfg.enabled = true;
+    apply(&cfg, &mut incident);
+    assert_eq!(incident.severity, Severity::Red);
+    cfg.weights.unsigned = -30;
+    cfg.weights.user_writable = 0;
+    apply(&cfg, &mut incident);
+    assert_eq!(incident.score.as_ref().unwrap().total, 25);
+    assert_eq!(incident.severity, Severity::Green);
+  }
+}
@@ -0,0 +1,197 @@
+use crate::paths;
+use crate::runtime;
+use anyhow::Context;
+use defender_common::fs::write_atomic;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Secrets are stored as DPAPI blobs (machine scope) so the service account and elevated admin
+// consoles can both read them, while the files are useless when copied to another machine.
+// Machine scope protects nothing between local users, so `secrets\` is SYSTEM/Administrators-only.
+// Config refers to secrets by name only; plaintext never lands in config.toml or logs.
+
+const SECRET_EXT: &str = "dpapi";
+const MAX_NAME_LEN: usize = 64;
+
+pub fn set(base: &Path, name: &str, value: &str) -> anyhow::Result<()> {
+  validate_name(name)?;
+  if value.is_empty() {
+    anyhow::bail!("secret value must not be empty");
+  }
+
+  if runtime::is_dry_run() {
+    tracing::warn!(secret = %name, "DRY-RUN: would store secret");
+    return Ok(());
+  }
+
+  let blob = dpapi::protect(value.as_bytes()).context("protect secret with DPAPI")?;
+  let dir = paths::secrets_dir(base);
+  fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
+  // Machine-scope blobs can be decrypted by any local user who can read them, so the folder must
+  // not rely on the data directory ACL (Users may read that).
+  crate::self_protection::restrict_to_admins(&dir)
+    .with_context(|| format!("restrict {} to SYSTEM and Administrators", dir.display()))?;
+  write_atomic(&secret_path(base, name), &blob)?;
+  tracing::info!(secret = %name, "secret stored");
+  Ok(())
+}
+
+pub fn get(base: &Path, name: &str) -> anyhow::Result<String> {
+  validate_name(name)?;
+  let path = secret_path(base, name);
+  let blob = fs::read(&path).with_context(|| format!("secret `{name}` not found"))?;
+  let plain = dpapi::unprotect(&blob).with_context(|| format!("unprotect secret `{name}`"))?;
+  String::from_utf8(plain).context("secret is not valid UTF-8")
+}
+
+/// Resolves an optional config reference (a secret name) to its value.
+pub fn resolve(base: &Path, name: Option<&str>) -> anyhow::Result<Option<String>> {
+  match name.map(str::trim).filter(|n| !n.is_empty()) {
+    Some(n) => get(base, n).map(Some),
+    None => Ok(None),
+  }
+}
+
+pub fn list(base: &Path) -> anyhow::Result<Vec<String>> {
+  let dir = paths::secrets_dir(base);
+  let Ok(entries) = fs::read_dir(&dir) else {
+    return Ok(Vec::new());
+  };
+
+  let mut names: Vec<String> = entries
+    .flatten()
+    .map(|e| e.path())
+    .filter(|p| p.extension().and_then(|s| s.to_str()) == Some(SECRET_EXT))
+    .filter_map(|p| p.file_stem().and_then(|s| s.to_str()).map(str::to_string))
+    .collect();
+  names.sort();
+  Ok(names)
+}
+
+pub fn remove(base: &Path, name: &str) -> anyhow::Result<bool> {
+  validate_name(name)?;
+  let path = secret_path(base, name);
+  if !path.exists() {
+    return Ok(false);
+  }
+
+  if runtime::is_dry_run() {
+    tracing::warn!(secret = %name, "DRY-RUN: would remove secret");
+    return Ok(true);
+  }
+
+  fs::remove_file(&path).with_context(|| format!("delete {}", path.display()))?;
+  tracing::info!(secret = %name, "secret removed");
+  Ok(true)
+}
+
+fn validate_name(name: &str) -> anyhow::Result<()> {
+  let ok = !name.is_empty()
+    && name.len() <= MAX_NAME_LEN
+    && name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+    && !name.starts_with('.');
+  if !ok {
+    anyhow::bail!(
+      "secret name must be 1-{MAX_NAME_LEN} chars of [A-Za-z0-9_.-] and not start with '.'"
+    );
+  }
+  Ok(())
+}
+
+fn secret_path(base: &Path, name: &str) -> PathBuf {
+  paths::secrets_dir(base).join(format!("{name}.{SECRET_EXT}"))
+}
+
+#[cfg(windows)]
+mod dpapi {
+  use windows::core::PCWSTR;
+  use windows::Win32::Foundation::{LocalFree, HLOCAL};
+  use windows::Win32::Security::Cryptography::{
+    CryptProtectData, CryptUnprotectData, CRYPTPROTECT_LOCAL_MACHINE, CRYPTPROTECT_UI_FORBIDDEN,
+    CRYPT_INTEGER_BLOB,
+  };
+
+  pub fn protect(plain: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let input = CRYPT_INTEGER_BLOB {
+      cbData: plain.len() as u32,
+      pbData: plain.as_ptr() as *mut u8,
+    };
+    let mut output = CRYPT_INTEGER_BLOB::default();
+    // SAFETY: `input` points to `plain` for the duration of the call; DPAPI allocates `output`
+    // with LocalAlloc, which we copy and release below.
+    unsafe {
+      CryptProtectData(
+        &input,
+        PCWSTR::null(),
+        None,
+        None,
+        None,
+        CRYPTPROTECT_LOCAL_MACHINE | CRYPTPROTECT_UI_FORBIDDEN,
+        &mut output,
+      )?;
+    }
+    Ok(take_blob(output))
+  }
+
+  pub fn unprotect(blob: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let input = CRYPT_INTEGER_BLOB {
+      cbData: blob.len() as u32,
+      pbData: blob.as_ptr() as *mut u8,
+    };
+    let mut output = CRYPT_INTEGER_BLOB::default();
+    // SAFETY: as in `protect`.
+    unsafe {
+      CryptUnprotectData(
+        &input,
+        None,
+        None,
+        None,
+        None,
+        CRYPTPROTECT_UI_FORBIDDEN,
+        &mut output,
+      )?;
+    }
+    Ok(take_blob(output))
+  }
+
+  fn take_blob(blob: CRYPT_INTEGER_BLOB) -> Vec<u8> {
+    if blob.pbData.is_null() {
+      return Vec::new();
+    }
+    // SAFETY: DPAPI returned a valid buffer of `cbData` bytes that we own and must LocalFree.
+    unsafe {
+      let out = std::slice::from_raw_parts(blob.pbData, blob.cbData as usize).to_vec();
+      let _ = LocalFree(HLOCAL(blob.pbData as *mut core::ffi::c_void));
+      out
+    }
+  }
+}
+
+#[cfg(not(windows))]
+mod dpapi {
+  pub fn protect(_plain: &[u8]) -> anyhow::Result<Vec<u8>> {
+    anyhow::bail!("secrets store requires Windows DPAPI")
+  }
+
+  pub fn unprotect(_blob: &[u8]) -> anyhow::Result<Vec<u8>> {
+    anyhow::bail!("secrets store requires Windows DPAPI")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn secret_names_are_restricted() {
+    assert!(validate_name("smtp_password").is_ok());
+    assert!(validate_name("fleet-token.v2").is_ok());
+    assert!(validate_name("").is_err());
+    assert!(validate_name("..\\evil").is_err());
+    assert!(validate_name(".hidden").is_err());
+    assert!(validate_name("a b").is_err());
+    assert!(validate_name(&"x".repeat(MAX_NAME_LEN + 1)).is_err());
+  }
+}
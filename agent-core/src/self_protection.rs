@@ -0,0 +1,339 @@
+use crate::config::Config;
+use crate::incident_chain;
+use crate::lifecycle::{self, RunMarker};
+use crate::paths;
+use crate::runtime;
+use crate::types::{now_unix_ms, Evidence, FileAccessType, Finding, Incident, Severity};
+use defender_common::fs::write_atomic;
+use defender_common::hash::sha256_bytes_hex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Self-protection (`[self_protection]`). Malware that wants to act unseen can stop the agent or
+// edit its config; this makes both harder and, failing that, loud. It runs in the privileged
+// process only (the service core when the service is split):
+//
+//   - ACLs: the data directory is restricted to SYSTEM and Administrators; Users may read it and
+//     write only the tray's prompt answers. Secrets and the incident chain key are not readable
+//     by Users at all. A change to the directory ACL while the agent runs is reported (T003) and,
+//     with `repair_acls`, undone.
+//   - Config: `config.seal` holds the SHA-256 of `config.toml` as AI Defender last wrote it (agent
+//     start, console commands). A different `config.toml` while the agent runs is reported (T002).
+//   - Termination: a previous run that neither stopped cleanly nor was followed by a reboot means
+//     the agent process was killed (or crashed) (T001).
+//
+// Tamper findings are RED. They never come from Sysmon events, so they bypass the rules engine
+// and go straight to the response engine.
+
+pub const T001_AGENT_TERMINATED: &str = "T001";
+pub const T002_CONFIG_MODIFIED: &str = "T002";
+pub const T003_ACL_CHANGED: &str = "T003";
+/// Raised by the `watchdog`, not by the monitor here.
+pub const T004_AGENT_LOOP_FAILED: &str = "T004";
+
+const CHECK_INTERVAL_MS: u64 = 30_000;
+
+const SID_SYSTEM: &str = "*S-1-5-18";
+const SID_ADMINISTRATORS: &str = "*S-1-5-32-544";
+const SID_USERS: &str = "*S-1-5-32-545";
+
+/// Restricts the data directory ACL. Children inherit it, except the folder users write to and the
+/// ones users may not read.
+pub fn harden(base: &Path) -> anyhow::Result<()> {
+  if runtime::is_dry_run() {
+    tracing::warn!(path = %base.display(), "DRY-RUN: would restrict AI Defender data directory ACLs");
+    return Ok(());
+  }
+  let base_arg = base.to_string_lossy();
+  icacls(&[
+    &base_arg,
+    "/inheritance:r",
+    "/grant:r",
+    &format!("{SID_SYSTEM}:(OI)(CI)F"),
+    &format!("{SID_ADMINISTRATORS}:(OI)(CI)F"),
+    &format!("{SID_USERS}:(OI)(CI)RX"),
+  ])?;
+
+  // Tray answers to prompts; a reply can only ever enable containment (see `ipc`).
+  let replies = paths::ipc_replies_dir(base);
+  fs::create_dir_all(&replies)?;
+  icacls(&[
+    &replies.to_string_lossy(),
+    "/grant",
+    &format!("{SID_USERS}:(OI)(CI)M"),
+  ])?;
+
+  // Only the agent signs incident records (see `incident_chain`), and secrets are machine-scope
+  // DPAPI blobs any local user could decrypt (see `secrets`).
+  let chain_key = paths::incident_chain_key_path(base);
+  if chain_key.exists() {
+    restrict_to_admins(&chain_key)?;
+  }
+  let secrets = paths::secrets_dir(base);
+  if secrets.exists() {
+    restrict_to_admins(&secrets)?;
+  }
+  tracing::info!(path = %base.display(), "data directory ACLs restricted");
+  Ok(())
+}
+
+/// Limits `path` (a file, or a directory and everything in it) to SYSTEM and Administrators.
+pub(crate) fn restrict_to_admins(path: &Path) -> anyhow::Result<()> {
+  let inherit = if path.is_dir() { "(OI)(CI)" } else { "" };
+  icacls(&[
+    &path.to_string_lossy(),
+    "/inheritance:r",
+    "/grant:r",
+    &format!("{SID_SYSTEM}:{inherit}F"),
+    &format!("{SID_ADMINISTRATORS}:{inherit}F"),
+  ])?;
+  Ok(())
+}
+
+/// Records the current `config.toml` as AI Defender's own. Call after every write to it.
+pub fn seal_config(base: &Path) {
+  if runtime::is_dry_run() {
+    return;
+  }
+  let Some(hash) = config_hash(base) else {
+    return;
+  };
+  if let Err(e) = write_atomic(&paths::config_seal_path(base), &hash) {
+    tracing::warn!(error = ?e, "failed to seal config (continuing)");
+  }
+}
+
+/// Sets up self-protection when the agent starts: reports a killed previous run, restricts ACLs,
+/// and seals the config the agent just loaded.
+pub fn on_start(cfg: &Config, base: &Path, previous_run: Option<&RunMarker>) {
+  if !cfg.self_protection.enabled {
+    return;
+  }
+  if let Some(previous) = previous_run {
+    if was_terminated(previous, lifecycle::boot_time_unix_ms()) {
+      report(cfg, terminated_finding(previous));
+    }
+  }
+  incident_chain::prepare(base);
+  if let Err(e) = harden(base) {
+    tracing::warn!(error = ?e, "failed to restrict data directory ACLs (continuing)");
+  }
+  seal_config(base);
+}
+
+/// A run that didn't stop cleanly, on a machine that hasn't rebooted since, was killed. Without
+/// boot times either way, a crash or power loss can't be ruled out and nothing is reported.
+fn was_terminated(previous: &RunMarker, boot_now: Option<u64>) -> bool {
+  if previous.clean_shutdown_at_unix_ms.is_some() {
+    return false;
+  }
+  match (previous.boot_unix_ms, boot_now) {
+    (Some(then), Some(now)) => then.abs_diff(now) <= lifecycle::BOOT_TIME_TOLERANCE_MS,
+    _ => false,
+  }
+}
+
+/// Watches the config seal and data directory ACL while the agent runs.
+pub struct Monitor {
+  base: PathBuf,
+  next_check_unix_ms: u64,
+  config_hash: Option<String>,
+  acl: Option<String>,
+}
+
+impl Monitor {
+  pub fn new(base: &Path) -> Self {
+    Self {
+      base: base.to_path_buf(),
+      next_check_unix_ms: now_unix_ms() + CHECK_INTERVAL_MS,
+      config_hash: config_hash(base),
+      acl: acl_fingerprint(base),
+    }
+  }
+
+  /// Tamper incidents found since the last check (not yet handled).
+  pub fn tick(&mut self, cfg: &Config) -> Vec<Incident> {
+    if !cfg.self_protection.enabled || now_unix_ms() < self.next_check_unix_ms {
+      return Vec::new();
+    }
+    self.next_check_unix_ms = now_unix_ms() + CHECK_INTERVAL_MS;
+
+    let mut incidents = Vec::new();
+    let current = config_hash(&self.base);
+    if current != self.config_hash {
+      let sealed = fs::read_to_string(paths::config_seal_path(&self.base)).ok();
+      if current.is_none() || current != sealed {
+        incidents.push(Incident::new(vec![config_finding(
+          &self.base,
+          current.is_none(),
+        )]));
+      }
+      self.config_hash = current;
+    }
+
+    let acl = acl_fingerprint(&self.base);
+    if acl.is_some() && self.acl.is_some() && acl != self.acl {
+      let mut incident = Incident::new(vec![acl_finding(&self.base)]);
+      self.acl = acl;
+      if cfg.self_protection.repair_acls {
+        match harden(&self.base) {
+          Ok(()) => {
+            tracing::warn!("data directory ACLs were changed; restored");
+            incident.actions_taken.push("acl_restored".to_string());
+            self.acl = acl_fingerprint(&self.base);
+          }
+          Err(e) => {
+            tracing::error!(error = ?e, "failed to restore data directory ACLs");
+            incident
+              .actions_taken
+              .push("acl_restore_failed".to_string());
+          }
+        }
+      }
+      incidents.push(incident);
+    }
+    incidents
+  }
+}
+
+fn report(cfg: &Config, finding: Finding) {
+  let mut incident = Incident::new(vec![finding]);
+  if let Err(e) = crate::response_engine::handle_incident(cfg, &mut incident) {
+    tracing::error!(error = ?e, "failed to handle tamper incident");
+  }
+}
+
+fn terminated_finding(previous: &RunMarker) -> Finding {
+  Finding {
+    rule_id: T001_AGENT_TERMINATED.to_string(),
+    severity: Severity::Red,
+    description:
+      "AI Defender agent was terminated without a clean stop (process killed or crashed)"
+        .to_string(),
+    evidence: vec![Evidence::Note {
+      message: format!(
+        "previous run pid {} started at {} (unix ms); no clean shutdown and no reboot since",
+        previous.pid, previous.started_at_unix_ms
+      ),
+    }],
+    timestamp_unix_ms: now_unix_ms(),
+  }
+}
+
+fn config_finding(base: &Path, deleted: bool) -> Finding {
+  Finding {
+    rule_id: T002_CONFIG_MODIFIED.to_string(),
+    severity: Severity::Red,
+    description: if deleted {
+      "AI Defender config.toml was deleted while the agent was running"
+    } else {
+      "AI Defender config.toml was modified outside AI Defender while the agent was running"
+    }
+    .to_string(),
+    evidence: vec![Evidence::File {
+      pid: 0,
+      image_path: None,
+      file_path: paths::config_path(base).to_string_lossy().to_string(),
+      access: if deleted {
+        FileAccessType::Delete
+      } else {
+        FileAccessType::Write
+      },
+    }],
+    timestamp_unix_ms: now_unix_ms(),
+  }
+}
+
+fn acl_finding(base: &Path) -> Finding {
+  Finding {
+    rule_id: T003_ACL_CHANGED.to_string(),
+    severity: Severity::Red,
+    description: "AI Defender data directory permissions were changed".to_string(),
+    evidence: vec![Evidence::Note {
+      message: format!(
+        "ACL of {} differs from the one the agent set",
+        base.display()
+      ),
+    }],
+    timestamp_unix_ms: now_unix_ms(),
+  }
+}
+
+fn config_hash(base: &Path) -> Option<String> {
+  let bytes = fs::read(paths::config_path(base)).ok()?;
+  Some(sha256_bytes_hex(&bytes))
+}
+
+/// `icacls` output for the data directory. Locale-dependent, but only ever compared with itself.
+fn acl_fingerprint(base: &Path) -> Option<String> {
+  icacls(&[&base.to_string_lossy()]).ok()
+}
+
+#[cfg(windows)]
+fn icacls(args: &[&str]) -> anyhow::Result<String> {
+  let output = std::process::Command::new("icacls").args(args).output()?;
+  if !output.status.success() {
+    return Err(anyhow::anyhow!(
+      "icacls failed ({}): {}",
+      output.status,
+      String::from_utf8_lossy(&output.stdout).trim()
+    ));
+  }
+  Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(not(windows))]
+fn icacls(_args: &[&str]) -> anyhow::Result<String> {
+  Err(anyhow::anyhow!("ACLs are only managed on Windows"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn unclean_runs_count_as_killed_only_without_a_reboot() {
+    let boot = 1_700_000_000_000;
+    let mut previous = RunMarker {
+      pid: 4242,
+      started_at_unix_ms: boot + 3_600_000,
+      clean_shutdown_at_unix_ms: None,
+      boot_unix_ms: Some(boot),
+    };
+
+    assert!(was_terminated(&previous, Some(boot + 900)));
+    assert!(!was_terminated(&previous, Some(boot + 86_400_000)));
+    assert!(!was_terminated(&previous, None));
+
+    previous.clean_shutdown_at_unix_ms = Some(boot + 7_200_000);
+    assert!(!was_terminated(&previous, Some(boot)));
+  }
+
+  #[test]
+  fn config_changes_outside_the_seal_are_reported() {
+    let base = std::env::temp_dir().join(format!("aid-selfprot-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&base).unwrap();
+    let cfg = Config::default();
+    fs::write(paths::config_path(&base), "mode = \"learning\"\n").unwrap();
+    seal_config(&base);
+    let mut monitor = Monitor::new(&base);
+
+    // Written through AI Defender (sealed): accepted.
+    fs::write(paths::config_path(&base), "mode = \"strict\"\n").unwrap();
+    seal_config(&base);
+    monitor.next_check_unix_ms = 0;
+    assert!(monitor.tick(&cfg).is_empty());
+
+    fs::write(paths::config_path(&base), "mode = \"learning\"\n").unwrap();
+    monitor.next_check_unix_ms = 0;
+    let incidents = monitor.tick(&cfg);
+    assert_eq!(incidents.len(), 1);
+    assert_eq!(incidents[0].findings[0].rule_id, T002_CONFIG_MODIFIED);
+
+    // Reported once, not on every check.
+    monitor.next_check_unix_ms = 0;
+    assert!(monitor.tick(&cfg).is_empty());
+
+    let _ = fs::remove_dir_all(&base);
+  }
+}
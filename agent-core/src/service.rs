@@ -1,5 +1,9 @@
 use crate::agent::Agent;
-use crate::{config, kill_switch, license, logging, paths, runtime, threat_feed};
+use crate::privsep::Core;
+use crate::{
+  browsers, config, incident_chain, kill_switch, license, lifecycle, logging, paths, runtime,
+  scoring, self_protection, threat_feed, watchdog,
+};
 use std::sync::mpsc;
 use std::time::Duration;
 use windows_service::define_windows_service;
@@ -9,7 +13,7 @@ use windows_service::service::{
 use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
 use windows_service::service_dispatcher;
 
-pub const SERVICE_NAME: &str = "AI_DEFENDER_AGENT";
+pub use crate::service_install::SERVICE_NAME;
 
 define_windows_service!(ffi_service_main, service_main);
 
@@ -35,13 +39,14 @@ fn run_service_inner() -> anyhow::Result<()> {
   let config_path = paths::config_path(&base);
   let cfg = config::load_or_create_default(&config_path)?;
 
-  logging::init_file_only(
-    &paths::logs_dir(&base),
-    &cfg.logging.level,
-    cfg.logging.retention_days,
-  )?;
+  logging::init_file_only(&paths::logs_dir(&base), &cfg.logging)?;
 
+  kill_switch::configure(&cfg);
   kill_switch::reconcile_on_startup(&cfg)?;
+  threat_feed::configure(&cfg);
+  browsers::configure(&cfg.browsers);
+  scoring::configure(&cfg.scoring);
+  incident_chain::configure(&cfg.self_protection);
 
   // Best-effort: refresh local status files for UI/CLI consumers.
   // This must not affect enforcement behavior.
@@ -73,12 +78,30 @@ fn run_service_inner() -> anyhow::Result<()> {
     Duration::default(),
   )?;
 
-  let agent = Agent::new(cfg);
-  let res = agent.run(stop_rx, Duration::from_millis(500));
+  let previous_run = lifecycle::on_start(&base);
+  self_protection::on_start(&cfg, &base, previous_run.as_ref());
+  let tick = Duration::from_millis(500);
+  let loop_cfg = cfg.clone();
+  let res = watchdog::supervise(&cfg, &base, stop_rx, tick, move |stop_rx, liveness| {
+    let cfg = loop_cfg.clone();
+    if cfg.service.privilege_split {
+      Core::new(cfg).with_liveness(liveness).run(stop_rx, tick)
+    } else {
+      Agent::new(cfg).with_liveness(liveness).run(stop_rx, tick)
+    }
+  });
   if let Err(e) = res {
     tracing::error!(error = ?e, "agent loop exited with error");
   }
 
+  set_service_status(
+    &status_handle,
+    ServiceState::StopPending,
+    1,
+    Duration::from_secs(30),
+  )?;
+  lifecycle::on_stop(&base);
+
   set_service_status(
     &status_handle,
     ServiceState::Stopped,
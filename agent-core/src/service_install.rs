@@ -0,0 +1,238 @@
+use crate::runtime;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Registers and controls the agent service through `sc.exe`, for machines without the MSI
+// (development, scripted deployments). Mirrors the installer's `ServiceInstall` authoring and
+// adds recovery actions. Every change is a plain `sc` command line, so `--dry-run` can print
+// exactly what would run.
+
+pub const SERVICE_NAME: &str = "AI_DEFENDER_AGENT";
+pub const DISPLAY_NAME: &str = "AI Defender Agent";
+pub const DESCRIPTION: &str = "AI Defender behavior-based security agent";
+
+/// Restart after 1 minute twice, then after 5 minutes; the failure count resets after a day.
+const FAILURE_RESET_SECONDS: u32 = 86_400;
+const FAILURE_ACTIONS: &str = "restart/60000/restart/60000/restart/300000";
+
+/// How long `stop`/`restart` wait for the service to reach STOPPED (the service asks for 30s).
+const STOP_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// `sc query` state codes (`SERVICE_STOPPED`, `SERVICE_RUNNING`, ...).
+const STATE_STOPPED: u32 = 1;
+
+/// `sc` exits with this when the service does not exist.
+const ERROR_SERVICE_DOES_NOT_EXIST: i32 = 1060;
+
+/// `base_dir` is passed to the service as `--base-dir`, for instances with their own data
+/// directory.
+pub fn install_commands(exe: &Path, base_dir: Option<&Path>) -> Vec<Vec<String>> {
+  // The image path must be quoted, or Windows may try `C:\Program.exe` first.
+  let mut bin_path = format!("\"{}\"", exe.display());
+  if let Some(dir) = base_dir {
+    bin_path.push_str(&format!(" --base-dir \"{}\"", dir.display()));
+  }
+  vec![
+    args(&[
+      "create",
+      SERVICE_NAME,
+      "binPath=",
+      &bin_path,
+      "type=",
+      "own",
+      "start=",
+      "auto",
+      "error=",
+      "normal",
+      "DisplayName=",
+      DISPLAY_NAME,
+    ]),
+    args(&["description", SERVICE_NAME, DESCRIPTION]),
+    args(&[
+      "failure",
+      SERVICE_NAME,
+      "reset=",
+      &FAILURE_RESET_SECONDS.to_string(),
+      "actions=",
+      FAILURE_ACTIONS,
+    ]),
+    // Also recover when the service stops with an error instead of crashing.
+    args(&["failureflag", SERVICE_NAME, "1"]),
+  ]
+}
+
+pub fn uninstall_commands() -> Vec<Vec<String>> {
+  vec![args(&["delete", SERVICE_NAME])]
+}
+
+fn args(a: &[&str]) -> Vec<String> {
+  a.iter().map(|s| s.to_string()).collect()
+}
+
+/// `sc.exe ...` as it would be typed, for dry-run output.
+pub fn display_command(args: &[String]) -> String {
+  let mut out = String::from("sc.exe");
+  for a in args {
+    out.push(' ');
+    if a.contains(' ') && !a.starts_with('"') {
+      out.push_str(&format!("\"{a}\""));
+    } else {
+      out.push_str(a);
+    }
+  }
+  out
+}
+
+/// Whether the service is registered.
+pub fn is_installed() -> anyhow::Result<bool> {
+  Ok(query()?.is_some())
+}
+
+/// `sc query` state code, or `None` when the service isn't installed.
+pub fn state() -> anyhow::Result<Option<u32>> {
+  Ok(query()?.and_then(|out| parse_state(&out)))
+}
+
+fn query() -> anyhow::Result<Option<String>> {
+  let (code, out) = sc(&args(&["query", SERVICE_NAME]))?;
+  match code {
+    0 => Ok(Some(out)),
+    ERROR_SERVICE_DOES_NOT_EXIST => Ok(None),
+    _ => Err(sc_failed(code, &out)),
+  }
+}
+
+pub fn install(exe: &Path) -> anyhow::Result<()> {
+  if is_installed()? {
+    return Err(anyhow::anyhow!(
+      "service {SERVICE_NAME} is already installed; run `--service uninstall` first"
+    ));
+  }
+  run_all(&install_commands(
+    exe,
+    crate::paths::base_dir_override().as_deref(),
+  ))
+}
+
+/// Stops the service if it is running, then deletes it.
+pub fn uninstall() -> anyhow::Result<()> {
+  if !is_installed()? {
+    return Ok(());
+  }
+  stop()?;
+  run_all(&uninstall_commands())
+}
+
+pub fn start() -> anyhow::Result<()> {
+  run_all(&[args(&["start", SERVICE_NAME])])
+}
+
+/// Stops the service and waits until it has stopped. Stopping a stopped service is a no-op.
+pub fn stop() -> anyhow::Result<()> {
+  if matches!(state()?, None | Some(STATE_STOPPED)) {
+    return Ok(());
+  }
+  run_all(&[args(&["stop", SERVICE_NAME])])?;
+  if runtime::is_dry_run() {
+    return Ok(());
+  }
+  let started = Instant::now();
+  while started.elapsed() < STOP_TIMEOUT {
+    if matches!(state()?, None | Some(STATE_STOPPED)) {
+      return Ok(());
+    }
+    std::thread::sleep(Duration::from_millis(500));
+  }
+  Err(anyhow::anyhow!(
+    "service {SERVICE_NAME} did not stop within {}s",
+    STOP_TIMEOUT.as_secs()
+  ))
+}
+
+pub fn restart() -> anyhow::Result<()> {
+  stop()?;
+  start()
+}
+
+fn run_all(commands: &[Vec<String>]) -> anyhow::Result<()> {
+  for c in commands {
+    if runtime::is_dry_run() {
+      println!("DRY-RUN: would run: {}", display_command(c));
+      continue;
+    }
+    tracing::info!(command = %display_command(c), "service control");
+    let (code, out) = sc(c)?;
+    if code != 0 {
+      return Err(sc_failed(code, &out));
+    }
+  }
+  Ok(())
+}
+
+/// The `STATE : 4  RUNNING` line of `sc query` output.
+fn parse_state(output: &str) -> Option<u32> {
+  output.lines().find_map(|l| {
+    let (key, value) = l.split_once(':')?;
+    if key.trim() != "STATE" {
+      return None;
+    }
+    value.split_whitespace().next()?.parse().ok()
+  })
+}
+
+fn sc_failed(code: i32, output: &str) -> anyhow::Error {
+  anyhow::anyhow!("sc.exe failed (exit code {code}): {}", output.trim())
+}
+
+/// Runs `sc.exe`, returning its exit code and stdout (where sc also reports errors, e.g.
+/// "[SC] OpenService FAILED 1060: ...").
+#[cfg(windows)]
+fn sc(args: &[String]) -> anyhow::Result<(i32, String)> {
+  let output = std::process::Command::new("sc.exe").args(args).output()?;
+  Ok((
+    output.status.code().unwrap_or(-1),
+    String::from_utf8_lossy(&output.stdout).to_string(),
+  ))
+}
+
+#[cfg(not(windows))]
+fn sc(_args: &[String]) -> anyhow::Result<(i32, String)> {
+  Err(anyhow::anyhow!(
+    "service control is only supported on Windows"
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn install_quotes_the_image_path_and_sets_recovery() {
+    let cmds = install_commands(
+      Path::new("C:\\Program Files\\AI Defender\\agent-core.exe"),
+      None,
+    );
+    assert_eq!(
+      display_command(&cmds[0]),
+      "sc.exe create AI_DEFENDER_AGENT binPath= \"C:\\Program Files\\AI Defender\\agent-core.exe\" \
+       type= own start= auto error= normal DisplayName= \"AI Defender Agent\""
+    );
+    assert_eq!(cmds[1][0], "description");
+    let cmds = install_commands(
+      Path::new("C:\\AI Defender\\agent-core.exe"),
+      Some(Path::new("D:\\Tenants\\A")),
+    );
+    assert_eq!(
+      cmds[0][3],
+      "\"C:\\AI Defender\\agent-core.exe\" --base-dir \"D:\\Tenants\\A\""
+    );
+    assert_eq!(
+      display_command(&cmds[2]),
+      "sc.exe failure AI_DEFENDER_AGENT reset= 86400 actions= restart/60000/restart/60000/restart/300000"
+    );
+
+    let query = "SERVICE_NAME: AI_DEFENDER_AGENT\r\n        TYPE               : 10  WIN32_OWN_PROCESS\r\n        STATE              : 4  RUNNING\r\n";
+    assert_eq!(parse_state(query), Some(4));
+    assert_eq!(parse_state("[SC] OpenService FAILED 1060"), None);
+  }
+}
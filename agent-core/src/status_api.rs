@@ -0,0 +1,380 @@
+use crate::config::Config;
+use crate::event_collector;
+use crate::incident_store;
+use crate::kill_switch;
+use crate::license;
+use crate::metrics;
+use crate::paths;
+use crate::runtime;
+use crate::scan_ipc;
+use crate::threat_feed;
+use crate::types::{now_unix_ms, Severity};
+use anyhow::Context;
+use defender_common::fs::write_atomic;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+// Opt-in, read-only HTTP endpoint on 127.0.0.1 so a tray UI or monitoring agent can poll
+// protection state without parsing ProgramData files. Every request needs
+// `Authorization: Bearer <token>` with the token from `status-api.token`; the Host header must
+// name the loopback listener so a web page can't reach it through DNS rebinding. One request per
+// connection, handled inline on a single thread; nothing here changes agent state.
+
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+const DEFAULT_INCIDENT_LIMIT: usize = 50;
+const MAX_INCIDENT_LIMIT: usize = 200;
+const DAY_MS: u64 = 24 * 60 * 60_000;
+
+/// Starts the listener thread when `[status_api]` is enabled. Failures are logged; the agent keeps
+/// running without the endpoint.
+pub fn start(cfg: &Config, base: &Path) {
+  // The listener outlives an agent loop the watchdog restarts.
+  static STARTED: AtomicBool = AtomicBool::new(false);
+  if !cfg.status_api.enabled || STARTED.swap(true, Ordering::SeqCst) {
+    return;
+  }
+
+  let port = cfg.status_api.port;
+  let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+    Ok(l) => l,
+    Err(e) => {
+      tracing::warn!(port, error = ?e, "status API: bind failed; endpoint disabled");
+      return;
+    }
+  };
+  let token = match load_or_create_token(base) {
+    Ok(t) => t,
+    Err(e) => {
+      tracing::warn!(error = ?e, "status API: token unavailable; endpoint disabled");
+      return;
+    }
+  };
+
+  let cfg = cfg.clone();
+  let spawned = std::thread::Builder::new()
+    .name("status-api".to_string())
+    .spawn(move || serve(listener, &cfg, &token));
+  match spawned {
+    Ok(_) => tracing::info!(port, "status API listening on 127.0.0.1"),
+    Err(e) => tracing::warn!(error = ?e, "status API: failed to start thread"),
+  }
+}
+
+fn serve(listener: TcpListener, cfg: &Config, token: &str) {
+  for stream in listener.incoming() {
+    let Ok(mut stream) = stream else {
+      continue;
+    };
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    let _ = stream.set_write_timeout(Some(Duration::from_secs(5)));
+    let (status, body) = match read_request(&stream) {
+      Ok(req) => handle(cfg, token, &req),
+      Err(e) => (400, Body::Json(json!({ "error": format!("{e:#}") }))),
+    };
+    let _ = write_response(&mut stream, status, &body);
+  }
+}
+
+#[derive(Debug, Default)]
+struct Request {
+  method: String,
+  path: String,
+  query: String,
+  host: Option<String>,
+  authorization: Option<String>,
+}
+
+fn read_request(stream: &TcpStream) -> anyhow::Result<Request> {
+  let mut reader = BufReader::new(stream).take(MAX_REQUEST_BYTES as u64);
+  let mut line = String::new();
+  reader.read_line(&mut line).context("read request line")?;
+  let mut parts = line.split_whitespace();
+  let method = parts.next().unwrap_or_default().to_string();
+  let target = parts.next().unwrap_or_default();
+  let (path, query) = target.split_once('?').unwrap_or((target, ""));
+  let mut req = Request {
+    method,
+    path: path.to_string(),
+    query: query.to_string(),
+    ..Request::default()
+  };
+
+  loop {
+    line.clear();
+    if reader.read_line(&mut line).context("read header")? == 0 {
+      anyhow::bail!("request headers incomplete");
+    }
+    let header = line.trim_end();
+    if header.is_empty() {
+      return Ok(req);
+    }
+    if let Some((name, value)) = header.split_once(':') {
+      let value = value.trim().to_string();
+      if name.eq_ignore_ascii_case("host") {
+        req.host = Some(value);
+      } else if name.eq_ignore_ascii_case("authorization") {
+        req.authorization = Some(value);
+      }
+    }
+  }
+}
+
+/// Everything is JSON except `/metrics`, which is Prometheus text.
+enum Body {
+  Json(Value),
+  Text(String),
+}
+
+fn handle(cfg: &Config, token: &str, req: &Request) -> (u16, Body) {
+  if req.path == "/metrics" && check_access(cfg, token, req).is_none() {
+    return (200, Body::Text(metrics::render()));
+  }
+  let (status, body) = handle_json(cfg, token, req);
+  (status, Body::Json(body))
+}
+
+/// The error response for a request that may not be served, if any.
+fn check_access(cfg: &Config, token: &str, req: &Request) -> Option<(u16, Value)> {
+  if !host_allowed(req.host.as_deref(), cfg.status_api.port) {
+    return Some((403, json!({ "error": "host not allowed" })));
+  }
+  if !authorized(req.authorization.as_deref(), token) {
+    return Some((401, json!({ "error": "missing or invalid bearer token" })));
+  }
+  if req.method != "GET" {
+    return Some((405, json!({ "error": "only GET is supported" })));
+  }
+  None
+}
+
+fn handle_json(cfg: &Config, token: &str, req: &Request) -> (u16, Value) {
+  if let Some(denied) = check_access(cfg, token, req) {
+    return denied;
+  }
+
+  let base = match paths::base_dir() {
+    Ok(b) => b,
+    Err(e) => return (500, json!({ "error": format!("{e:#}") })),
+  };
+  match req.path.as_str() {
+    "/status" => (200, status_json(cfg, &base)),
+    "/incidents" => incidents_json(query_limit(&req.query)),
+    "/killswitch" => killswitch_json(),
+    "/feed" => (200, feed_json(&base)),
+    "/scans" => (200, json!({ "scans": scan_ipc::running(&base) })),
+    _ => (404, json!({ "error": "not found" })),
+  }
+}
+
+fn status_json(cfg: &Config, base: &Path) -> Value {
+  let now = now_unix_ms();
+  let recent = incident_store::list_recent(MAX_INCIDENT_LIMIT).unwrap_or_default();
+  let last_day = recent
+    .iter()
+    .filter(|i| now.saturating_sub(i.created_at_unix_ms) < DAY_MS);
+  let killswitch_enabled = kill_switch::status().ok().map(|s| s.enabled);
+  json!({
+    "agent_version": env!("CARGO_PKG_VERSION"),
+    "mode": cfg.mode,
+    "dry_run": runtime::is_dry_run(),
+    "machine_tags": cfg.machine.tags,
+    "managed_profile": cfg.managed.as_ref().map(|m| m.profile_id.clone()),
+    "license": license::status(base).state.as_str(),
+    "killswitch_enabled": killswitch_enabled,
+    "feed_installed": threat_feed::bundle_status_at(base).present,
+    "collector": event_collector::lag(),
+    "incidents_last_24h": last_day.clone().count(),
+    "red_incidents_last_24h": last_day.filter(|i| i.severity == Severity::Red).count(),
+    "checked_at_unix_ms": now,
+  })
+}
+
+fn incidents_json(limit: usize) -> (u16, Value) {
+  match incident_store::list_recent(limit) {
+    Ok(list) => {
+      let items: Vec<Value> = list
+        .into_iter()
+        .map(|i| {
+          json!({
+            "incident_id": i.incident_id,
+            "created_at_unix_ms": i.created_at_unix_ms,
+            "severity": i.severity,
+            "score": i.score,
+            "rule_ids": i.rule_ids,
+          })
+        })
+        .collect();
+      (200, json!({ "incidents": items }))
+    }
+    Err(e) => (500, json!({ "error": format!("{e:#}") })),
+  }
+}
+
+fn killswitch_json() -> (u16, Value) {
+  match kill_switch::status() {
+    Ok(s) => (
+      200,
+      json!({
+        "enabled": s.enabled,
+        "rules_present": s.rules_present,
+        "firewall_backend": s.firewall_backend.map(|b| format!("{b:?}")),
+        "keep_locked": s.keep_locked,
+        "enabled_mode": s.enabled_mode,
+        "enabled_at_unix_ms": s.enabled_at_unix_ms,
+        "failsafe_deadline_unix_ms": s.failsafe_deadline_unix_ms,
+        "last_incident_id": s.last_incident_id,
+      }),
+    ),
+    Err(e) => (503, json!({ "error": format!("{e:#}") })),
+  }
+}
+
+fn feed_json(base: &Path) -> Value {
+  let st = threat_feed::bundle_status_at(base);
+  json!({
+    "installed": st.present,
+    "rules_version": st.rules_version,
+    "created_at_unix_seconds": st.created_at,
+    "verified_at_unix_seconds": st.verified_at,
+    "last_refresh_attempt_at_unix_seconds": st.last_refresh_attempt_at,
+    "last_refresh_result": st.last_refresh_result,
+    "signer": st.signer,
+  })
+}
+
+fn query_limit(query: &str) -> usize {
+  query
+    .split('&')
+    .find_map(|kv| kv.strip_prefix("limit="))
+    .and_then(|v| v.parse::<usize>().ok())
+    .unwrap_or(DEFAULT_INCIDENT_LIMIT)
+    .clamp(1, MAX_INCIDENT_LIMIT)
+}
+
+fn host_allowed(host: Option<&str>, port: u16) -> bool {
+  let Some(host) = host else {
+    return false;
+  };
+  [format!("127.0.0.1:{port}"), format!("localhost:{port}")]
+    .iter()
+    .any(|h| h.eq_ignore_ascii_case(host))
+}
+
+fn authorized(header: Option<&str>, token: &str) -> bool {
+  let Some(given) = header.and_then(|h| h.strip_prefix("Bearer ")) else {
+    return false;
+  };
+  let (a, b) = (given.trim().as_bytes(), token.as_bytes());
+  // Constant-time compare so the token can't be guessed byte by byte from response timing.
+  a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn write_response(stream: &mut TcpStream, status: u16, body: &Body) -> anyhow::Result<()> {
+  let (body, content_type) = match body {
+    Body::Json(v) => (serde_json::to_vec(v)?, "application/json"),
+    Body::Text(t) => (t.clone().into_bytes(), "text/plain; version=0.0.4"),
+  };
+  let reason = match status {
+    200 => "OK",
+    400 => "Bad Request",
+    401 => "Unauthorized",
+    403 => "Forbidden",
+    404 => "Not Found",
+    405 => "Method Not Allowed",
+    503 => "Service Unavailable",
+    _ => "Internal Server Error",
+  };
+  write!(
+    stream,
+    "HTTP/1.1 {status} {reason}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+    body.len()
+  )?;
+  stream.write_all(&body)?;
+  stream.flush()?;
+  Ok(())
+}
+
+/// Reuses the token across restarts so clients don't have to re-read it; delete the file to rotate.
+fn load_or_create_token(base: &Path) -> anyhow::Result<String> {
+  let path = paths::status_api_token_path(base);
+  if let Ok(existing) = fs::read_to_string(&path) {
+    let existing = existing.trim();
+    if existing.len() >= 32 {
+      return Ok(existing.to_string());
+    }
+  }
+
+  let token = format!(
+    "{}{}",
+    uuid::Uuid::new_v4().simple(),
+    uuid::Uuid::new_v4().simple()
+  );
+  if runtime::is_dry_run() {
+    tracing::warn!(path = %path.display(), "DRY-RUN: would write status API token; using it in memory only");
+    return Ok(token);
+  }
+  write_atomic(&path, &token)?;
+  Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn requests_need_loopback_host_and_token() {
+    let cfg = Config::default();
+    let port = cfg.status_api.port;
+    let req = |host: &str, auth: Option<&str>, path: &str| Request {
+      method: "GET".to_string(),
+      path: path.to_string(),
+      host: Some(host.to_string()),
+      authorization: auth.map(str::to_string),
+      ..Request::default()
+    };
+    let ok_host = format!("127.0.0.1:{port}");
+
+    let (status, _) = handle(
+      &cfg,
+      "secret-token",
+      &req("evil.example", Some("Bearer secret-token"), "/feed"),
+    );
+    assert_eq!(status, 403);
+    let (status, _) = handle(&cfg, "secret-token", &req(&ok_host, None, "/feed"));
+    assert_eq!(status, 401);
+    let (status, _) = handle(
+      &cfg,
+      "secret-token",
+      &req(&ok_host, Some("Bearer secret-tokeX"), "/feed"),
+    );
+    assert_eq!(status, 401);
+    let (status, _) = handle(
+      &cfg,
+      "secret-token",
+      &req(&ok_host, Some("Bearer secret-token"), "/nope"),
+    );
+    assert_eq!(status, 404);
+    let (status, _) = handle(&cfg, "secret-token", &req(&ok_host, None, "/metrics"));
+    assert_eq!(status, 401);
+    let (status, body) = handle(
+      &cfg,
+      "secret-token",
+      &req(&ok_host, Some("Bearer secret-token"), "/metrics"),
+    );
+    assert_eq!(status, 200);
+    assert!(matches!(body, Body::Text(t) if t.contains("ai_defender_events_processed_total")));
+  }
+
+  #[test]
+  fn incident_limit_is_clamped() {
+    assert_eq!(query_limit(""), DEFAULT_INCIDENT_LIMIT);
+    assert_eq!(query_limit("limit=5"), 5);
+    assert_eq!(query_limit("x=1&limit=100000"), MAX_INCIDENT_LIMIT);
+    assert_eq!(query_limit("limit=0"), 1);
+  }
+}
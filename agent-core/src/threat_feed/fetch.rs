@@ -1,7 +1,10 @@
+use super::proxy;
 use crate::config::ThreatFeedConfig;
 use anyhow::Context;
-use reqwest::blocking::{Client, Response};
-use reqwest::header::USER_AGENT;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{
+  HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, USER_AGENT,
+};
 use reqwest::redirect::Policy;
 use reqwest::Url;
 use std::io::Read;
@@ -9,11 +12,42 @@ use std::time::Duration;
 
 const MAX_BUNDLE_JSON_BYTES: usize = 2 * 1024 * 1024;
 const MAX_BUNDLE_SIG_BYTES: usize = 8 * 1024;
+const MAX_KEY_MANIFEST_BYTES: usize = 256 * 1024;
 
 pub struct FetchedBundle {
+  /// `None` when the server answered the conditional request with 304 Not Modified.
+  pub bundle: Option<BundleFiles>,
+  /// `keys.json` and `keys.sig`, when the endpoint publishes a key manifest (see `trust`).
+  pub key_manifest: Option<(Vec<u8>, Vec<u8>)>,
+  pub host: String,
+}
+
+pub struct BundleFiles {
   pub bundle_json: Vec<u8>,
   pub bundle_sig: Vec<u8>,
-  pub host: String,
+  pub validators: Validators,
+}
+
+/// HTTP cache validators of `bundle.json`, sent back as `If-None-Match` / `If-Modified-Since`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+  pub etag: Option<String>,
+  pub last_modified: Option<String>,
+}
+
+impl Validators {
+  fn from_headers(headers: &HeaderMap) -> Self {
+    let text = |name| {
+      headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+    };
+    Self {
+      etag: text(ETAG),
+      last_modified: text(LAST_MODIFIED),
+    }
+  }
 }
 
 pub fn validate_refresh_config(cfg: &ThreatFeedConfig) -> anyhow::Result<()> {
@@ -38,7 +72,13 @@ pub fn validate_refresh_config(cfg: &ThreatFeedConfig) -> anyhow::Result<()> {
   Ok(())
 }
 
-pub fn fetch_bundle(cfg: &ThreatFeedConfig) -> anyhow::Result<FetchedBundle> {
+/// `downloaded` accumulates response body bytes, including those of a failed attempt. With
+/// `validators` of the installed bundle, an unchanged bundle isn't downloaded again.
+pub fn fetch_bundle(
+  cfg: &ThreatFeedConfig,
+  validators: &Validators,
+  downloaded: &mut u64,
+) -> anyhow::Result<FetchedBundle> {
   validate_refresh_config(cfg)?;
 
   let endpoint = choose_endpoint(cfg)?;
@@ -47,7 +87,7 @@ pub fn fetch_bundle(cfg: &ThreatFeedConfig) -> anyhow::Result<FetchedBundle> {
     .ok_or_else(|| anyhow::anyhow!("endpoint host missing"))?
     .to_string();
 
-  let client = Client::builder()
+  let client = proxy::apply(Client::builder(), cfg)?
     .timeout(Duration::from_secs(cfg.timeout_seconds))
     .redirect(Policy::none())
     .build()
@@ -63,16 +103,51 @@ pub fn fetch_bundle(cfg: &ThreatFeedConfig) -> anyhow::Result<FetchedBundle> {
     .with_context(|| format!("build signature URL from endpoint {endpoint}"))?;
   validate_endpoint(&sig_url, &cfg.allowlist_domains)?;
 
-  let bundle_json = http_get_bytes(&client, &bundle_url, MAX_BUNDLE_JSON_BYTES)?;
-  let bundle_sig = http_get_bytes(&client, &sig_url, MAX_BUNDLE_SIG_BYTES)?;
+  let bundle = match http_get_conditional(
+    &client,
+    &bundle_url,
+    MAX_BUNDLE_JSON_BYTES,
+    validators,
+    downloaded,
+  )? {
+    Some((bundle_json, validators)) => Some(BundleFiles {
+      bundle_json,
+      bundle_sig: http_get_bytes(&client, &sig_url, MAX_BUNDLE_SIG_BYTES, downloaded)?,
+      validators,
+    }),
+    None => None,
+  };
+  let key_manifest = fetch_key_manifest(&client, &endpoint, cfg, downloaded);
 
   Ok(FetchedBundle {
-    bundle_json,
-    bundle_sig,
+    bundle,
+    key_manifest,
     host,
   })
 }
 
+/// Optional: most feeds don't publish one, so any failure just means "no manifest".
+fn fetch_key_manifest(
+  client: &Client,
+  endpoint: &Url,
+  cfg: &ThreatFeedConfig,
+  downloaded: &mut u64,
+) -> Option<(Vec<u8>, Vec<u8>)> {
+  let json_url = endpoint.join("keys.json").ok()?;
+  let sig_url = endpoint.join("keys.sig").ok()?;
+  validate_endpoint(&json_url, &cfg.allowlist_domains).ok()?;
+  validate_endpoint(&sig_url, &cfg.allowlist_domains).ok()?;
+
+  let json = http_get_bytes(client, &json_url, MAX_KEY_MANIFEST_BYTES, downloaded).ok()?;
+  match http_get_bytes(client, &sig_url, MAX_BUNDLE_SIG_BYTES, downloaded) {
+    Ok(sig) => Some((json, sig)),
+    Err(e) => {
+      tracing::warn!(reason = %e, "key manifest published without a usable signature; ignored");
+      None
+    }
+  }
+}
+
 fn choose_endpoint(cfg: &ThreatFeedConfig) -> anyhow::Result<Url> {
   for raw in &cfg.endpoints {
     let Ok(url) = Url::parse(raw) else {
@@ -101,16 +176,54 @@ fn validate_endpoint(url: &Url, allowlist_domains: &[String]) -> anyhow::Result<
   Ok(())
 }
 
-fn http_get_bytes(client: &Client, url: &Url, max_bytes: usize) -> anyhow::Result<Vec<u8>> {
-  let response = client
-    .get(url.clone())
-    .header(
-      USER_AGENT,
-      format!("AI-Defender/{}", env!("CARGO_PKG_VERSION")),
-    )
+fn http_get_bytes(
+  client: &Client,
+  url: &Url,
+  max_bytes: usize,
+  downloaded: &mut u64,
+) -> anyhow::Result<Vec<u8>> {
+  let response = request(client, url)
     .send()
     .with_context(|| format!("GET {}", safe_url_label(url)))?;
+  read_response_with_limit(check_status(response, url)?, max_bytes, downloaded)
+}
 
+/// `None` on 304 Not Modified.
+fn http_get_conditional(
+  client: &Client,
+  url: &Url,
+  max_bytes: usize,
+  validators: &Validators,
+  downloaded: &mut u64,
+) -> anyhow::Result<Option<(Vec<u8>, Validators)>> {
+  let mut req = request(client, url);
+  if let Some(etag) = validators.etag.as_deref() {
+    req = req.header(IF_NONE_MATCH, etag);
+  }
+  if let Some(last_modified) = validators.last_modified.as_deref() {
+    req = req.header(IF_MODIFIED_SINCE, last_modified);
+  }
+  let has_validators = validators != &Validators::default();
+  let response = req
+    .send()
+    .with_context(|| format!("GET {}", safe_url_label(url)))?;
+  if has_validators && response.status().as_u16() == 304 {
+    return Ok(None);
+  }
+  let response = check_status(response, url)?;
+  let next = Validators::from_headers(response.headers());
+  let bytes = read_response_with_limit(response, max_bytes, downloaded)?;
+  Ok(Some((bytes, next)))
+}
+
+fn request(client: &Client, url: &Url) -> RequestBuilder {
+  client.get(url.clone()).header(
+    USER_AGENT,
+    format!("AI-Defender/{}", env!("CARGO_PKG_VERSION")),
+  )
+}
+
+fn check_status(response: Response, url: &Url) -> anyhow::Result<Response> {
   if response.status().as_u16() != 200 {
     anyhow::bail!(
       "unexpected HTTP status {} for {}",
@@ -118,16 +231,19 @@ fn http_get_bytes(client: &Client, url: &Url, max_bytes: usize) -> anyhow::Resul
       safe_url_label(url)
     );
   }
-
-  read_response_with_limit(response, max_bytes)
+  Ok(response)
 }
 
-fn read_response_with_limit(response: Response, max_bytes: usize) -> anyhow::Result<Vec<u8>> {
+fn read_response_with_limit(
+  response: Response,
+  max_bytes: usize,
+  downloaded: &mut u64,
+) -> anyhow::Result<Vec<u8>> {
   let mut out = Vec::new();
   let mut limited = response.take((max_bytes.saturating_add(1)) as u64);
-  limited
-    .read_to_end(&mut out)
-    .context("read response body")?;
+  let read = limited.read_to_end(&mut out);
+  *downloaded = downloaded.saturating_add(out.len() as u64);
+  read.context("read response body")?;
 
   if out.len() > max_bytes {
     anyhow::bail!("response exceeds max size {} bytes", max_bytes);
@@ -144,3 +260,97 @@ fn safe_url_label(url: &Url) -> String {
   }
   format!("{host}{path}")
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::{BufRead, BufReader, Write};
+  use std::net::{Ipv4Addr, TcpListener};
+  use std::sync::mpsc;
+
+  const LAST_MODIFIED_AT: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+  /// Serves `requests` GETs: 304 when the request carries the validators it handed out, else 200
+  /// with `ETag` and `Last-Modified`. Sends each request's header lines back.
+  fn serve(requests: usize) -> (Url, mpsc::Receiver<Vec<String>>) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let url = Url::parse(&format!(
+      "http://{}/bundle.json",
+      listener.local_addr().unwrap()
+    ))
+    .unwrap();
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+      for stream in listener.incoming().take(requests) {
+        let mut stream = stream.unwrap();
+        let headers: Vec<String> = BufReader::new(&stream)
+          .lines()
+          .map_while(Result::ok)
+          .take_while(|l| !l.is_empty())
+          .map(|l| l.to_ascii_lowercase())
+          .collect();
+        let cached = headers.iter().any(|h| h == "if-none-match: \"v1\"")
+          && headers.iter().any(|h| {
+            *h == format!(
+              "if-modified-since: {}",
+              LAST_MODIFIED_AT.to_ascii_lowercase()
+            )
+          });
+        let response = if cached {
+          "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n".to_string()
+        } else {
+          format!(
+            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nLast-Modified: {LAST_MODIFIED_AT}\r\n\
+             Content-Length: 2\r\nConnection: close\r\n\r\n{{}}"
+          )
+        };
+        stream.write_all(response.as_bytes()).unwrap();
+        tx.send(headers).unwrap();
+      }
+    });
+    (url, rx)
+  }
+
+  #[test]
+  fn validators_round_trip_to_not_modified() {
+    let (url, requests) = serve(3);
+    let client = Client::builder().no_proxy().build().unwrap();
+    let mut downloaded = 0;
+
+    let (body, validators) =
+      http_get_conditional(&client, &url, 16, &Validators::default(), &mut downloaded)
+        .unwrap()
+        .unwrap();
+    assert_eq!(body, b"{}");
+    assert_eq!(
+      validators,
+      Validators {
+        etag: Some("\"v1\"".to_string()),
+        last_modified: Some(LAST_MODIFIED_AT.to_string()),
+      }
+    );
+    let sent = requests.recv().unwrap();
+    assert!(!sent.iter().any(|h| h.starts_with("if-")));
+
+    // Sent back, they turn into a 304 and nothing is downloaded.
+    assert!(
+      http_get_conditional(&client, &url, 16, &validators, &mut downloaded)
+        .unwrap()
+        .is_none()
+    );
+    assert_eq!(downloaded, 2);
+    let sent = requests.recv().unwrap();
+    assert!(sent.contains(&"if-none-match: \"v1\"".to_string()));
+
+    // A changed ETag gets the bundle again.
+    let stale = Validators {
+      etag: Some("\"v0\"".to_string()),
+      ..validators
+    };
+    assert!(
+      http_get_conditional(&client, &url, 16, &stale, &mut downloaded)
+        .unwrap()
+        .is_some()
+    );
+  }
+}
@@ -1,18 +1,26 @@
 use crate::config::Config;
 use crate::license::{self, LicenseState};
+use crate::metrics;
+use crate::net_usage;
 use crate::paths;
 use crate::runtime;
 use crate::types::now_unix_ms;
 use anyhow::Context;
+use defender_common::fs::write_atomic;
+use defender_common::toml_fmt::{toml_string_or_null, toml_u64_or_null};
 use std::fs;
 use std::path::{Path, PathBuf};
 
 pub mod fetch;
+pub mod proxy;
 pub mod schema;
 pub mod verify;
 
 use schema::{ReputationLists, ThreatFeedBundle};
 
+/// Per field of a rule's `guidance`.
+const MAX_GUIDANCE_CHARS: usize = 2000;
+
 pub struct DownloadedBundle {
   pub bundle_json: Vec<u8>,
   pub signature: Vec<u8>,
@@ -36,6 +44,13 @@ pub struct BundleMeta {
   pub last_verified_at: Option<u64>,
   pub last_refresh_attempt_at: Option<u64>,
   pub last_refresh_result: Option<String>,
+  #[serde(default)]
+  pub last_refresh_bytes: Option<u64>,
+  /// Cache validators of the refreshed `bundle.json` that is installed, for conditional fetches.
+  #[serde(default)]
+  pub etag: Option<String>,
+  #[serde(default)]
+  pub last_modified: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -46,6 +61,8 @@ pub struct BundleStatus {
   pub verified_at: Option<u64>,
   pub last_refresh_attempt_at: Option<u64>,
   pub last_refresh_result: Option<String>,
+  /// `vendor` or `organization:<fingerprint>` for the installed bundle.
+  pub signer: Option<String>,
 }
 
 impl BundleStatus {
@@ -57,6 +74,7 @@ impl BundleStatus {
       verified_at: None,
       last_refresh_attempt_at: None,
       last_refresh_result: None,
+      signer: None,
     }
   }
 }
@@ -69,6 +87,7 @@ pub struct FeedStatus {
   pub installed_at_unix_ms: Option<u64>,
   pub checked_at_unix_ms: u64,
   pub reason: Option<String>,
+  pub signer: Option<String>,
 }
 
 impl FeedStatus {
@@ -80,6 +99,7 @@ impl FeedStatus {
       installed_at_unix_ms: None,
       checked_at_unix_ms: now_unix_ms(),
       reason,
+      signer: None,
     }
   }
 }
@@ -99,6 +119,10 @@ pub struct AutoRefreshStatus {
   pub reason: String,
   pub last_attempt_at: Option<u64>,
   pub last_result: Option<String>,
+  pub last_bytes: Option<u64>,
+  pub defer_on_metered: bool,
+  /// `None` when the connection cost can't be determined.
+  pub metered: Option<bool>,
 }
 
 #[derive(Debug, Clone)]
@@ -120,11 +144,12 @@ impl AutoRefreshScheduler {
     out
   }
 
-  pub fn tick(&mut self, cfg: &Config, base: &Path) {
+  /// Returns true when a refresh installed a new bundle.
+  pub fn tick(&mut self, cfg: &Config, base: &Path) -> bool {
     let eligibility = auto_refresh_eligibility(cfg, base);
     if !eligibility.eligible {
       self.next_due_unix_ms = None;
-      return;
+      return false;
     }
 
     let now = now_unix_ms();
@@ -132,17 +157,26 @@ impl AutoRefreshScheduler {
 
     if self.next_due_unix_ms.is_none() {
       self.next_due_unix_ms = Some(now.saturating_add(interval_ms));
-      return;
+      return false;
     }
 
     let Some(next_due) = self.next_due_unix_ms else {
-      return;
+      return false;
     };
     if now < next_due {
-      return;
+      return false;
+    }
+
+    if cfg.threat_feed.defer_on_metered && net_usage::is_metered() == Some(true) {
+      tracing::info!("threat feed auto-refresh deferred (metered connection)");
+      self.next_due_unix_ms = Some(now.saturating_add(interval_ms));
+      return false;
     }
 
     let result = refresh_now(cfg, base);
+    if result.attempted {
+      metrics::feed_refresh(result.success);
+    }
     if result.attempted && result.success {
       tracing::info!("threat feed auto-refresh succeeded");
     } else if result.attempted {
@@ -150,6 +184,7 @@ impl AutoRefreshScheduler {
     }
 
     self.next_due_unix_ms = Some(now.saturating_add(interval_ms));
+    result.attempted && result.success
   }
 
   fn recompute_due(&mut self, cfg: &Config, base: &Path) {
@@ -164,11 +199,48 @@ impl AutoRefreshScheduler {
   }
 }
 
+/// Installs the organization feed key from config (inline `org_public_key` wins over
+/// `org_public_key_file`). A key that can't be loaded is logged and ignored, so only vendor-signed
+/// bundles verify.
+pub fn configure(cfg: &Config) {
+  let tf = &cfg.threat_feed;
+  let text: anyhow::Result<String> = match (&tf.org_public_key, &tf.org_public_key_file) {
+    (Some(key), _) => Ok(key.clone()),
+    (None, Some(file)) => fs::read_to_string(file).with_context(|| format!("read {file}")),
+    (None, None) => {
+      verify::set_org_key(None);
+      return;
+    }
+  };
+
+  match text.and_then(|t| verify::parse_public_key(&t)) {
+    Ok(key) => {
+      tracing::info!(
+        fingerprint = %verify::fingerprint(&key),
+        "threat feed organization key configured"
+      );
+      verify::set_org_key(Some(key));
+    }
+    Err(e) => {
+      tracing::warn!(error = %format!("{e:#}"), "threat feed organization key ignored");
+      verify::set_org_key(None);
+    }
+  }
+}
+
 pub fn verify_bundle_signature(bundle_json: &[u8], sig_bytes: &[u8]) -> bool {
   verify::verify_bundle_signature(bundle_json, sig_bytes).is_ok()
 }
 
 pub fn verify_files(bundle_path: &Path, sig_path: &Path) -> anyhow::Result<ThreatFeedBundle> {
+  verify_files_signed(bundle_path, sig_path).map(|(bundle, _)| bundle)
+}
+
+/// Like [`verify_files`], also reporting which trusted key signed the bundle.
+pub fn verify_files_signed(
+  bundle_path: &Path,
+  sig_path: &Path,
+) -> anyhow::Result<(ThreatFeedBundle, verify::Signer)> {
   let bundle_json =
     fs::read(bundle_path).with_context(|| format!("read {}", bundle_path.display()))?;
   let sig_raw = fs::read(sig_path).with_context(|| format!("read {}", sig_path.display()))?;
@@ -181,14 +253,18 @@ pub fn load_current() -> Option<ThreatFeedBundle> {
 }
 
 pub fn load_current_at(base: &Path) -> Option<ThreatFeedBundle> {
+  load_current_signed(base).map(|(bundle, _)| bundle)
+}
+
+fn load_current_signed(base: &Path) -> Option<(ThreatFeedBundle, verify::Signer)> {
   let bundle_path = paths::threat_feed_bundle_path(base);
   let sig_path = paths::threat_feed_sig_path(base);
 
-  if let Ok(bundle) = verify_files(&bundle_path, &sig_path) {
+  if let Ok(verified) = verify_files_signed(&bundle_path, &sig_path) {
     if !runtime::is_dry_run() {
       let _ = mark_verified(base);
     }
-    return Some(bundle);
+    return Some(verified);
   }
 
   verify_last_good(base).ok()
@@ -216,7 +292,7 @@ pub fn bundle_status() -> BundleStatus {
 pub fn bundle_status_at(base: &Path) -> BundleStatus {
   let meta = read_meta(base);
 
-  if let Some(bundle) = load_current_at(base) {
+  if let Some((bundle, signer)) = load_current_signed(base) {
     return BundleStatus {
       present: true,
       rules_version: Some(bundle.rules_version),
@@ -224,6 +300,7 @@ pub fn bundle_status_at(base: &Path) -> BundleStatus {
       verified_at: meta.last_verified_at,
       last_refresh_attempt_at: meta.last_refresh_attempt_at,
       last_refresh_result: meta.last_refresh_result,
+      signer: Some(signer.label()),
     };
   }
 
@@ -234,6 +311,7 @@ pub fn bundle_status_at(base: &Path) -> BundleStatus {
     verified_at: meta.last_verified_at,
     last_refresh_attempt_at: meta.last_refresh_attempt_at,
     last_refresh_result: meta.last_refresh_result,
+    signer: None,
   }
 }
 
@@ -242,16 +320,22 @@ pub fn import(base: &Path, src_bundle: &Path, src_sig: &Path) -> anyhow::Result<
     fs::read(src_bundle).with_context(|| format!("read {}", src_bundle.display()))?;
   let sig_raw = fs::read(src_sig).with_context(|| format!("read {}", src_sig.display()))?;
 
-  let bundle = verify_bundle_bytes(&bundle_json, &sig_raw)?;
+  let (bundle, signer) = verify_bundle_bytes(&bundle_json, &sig_raw)?;
   if runtime::is_dry_run() {
     tracing::warn!(
       bundle_id = %bundle.bundle_id,
       rules_version = bundle.rules_version,
+      signer = %signer.label(),
       "DRY-RUN: would install threat feed bundle"
     );
     return Ok(bundle_status_at(base));
   }
   install_verified_bundle(base, &bundle_json, &sig_raw)?;
+  tracing::info!(
+    bundle_id = %bundle.bundle_id,
+    signer = %signer.label(),
+    "threat feed bundle imported"
+  );
   Ok(bundle_status_at(base))
 }
 
@@ -301,31 +385,97 @@ pub fn refresh_now(cfg: &Config, base: &Path) -> RefreshNowResult {
   let mut meta = read_meta(base);
   meta.last_refresh_attempt_at = Some(attempt_at);
 
-  let fetched = match fetch::fetch_bundle(&cfg.threat_feed) {
-    Ok(v) => v,
+  // Only ask for "changes since" while the installed bundle still verifies; otherwise a 304 would
+  // leave the agent without a usable bundle.
+  let validators = if load_current_signed(base).is_some() {
+    fetch::Validators {
+      etag: meta.etag.clone(),
+      last_modified: meta.last_modified.clone(),
+    }
+  } else {
+    fetch::Validators::default()
+  };
+
+  let mut downloaded = 0u64;
+  let fetched = fetch::fetch_bundle(&cfg.threat_feed, &validators, &mut downloaded);
+  net_usage::record(
+    base,
+    net_usage::Purpose::ThreatFeed,
+    downloaded,
+    now_unix_ms(),
+  );
+  meta.last_refresh_bytes = Some(downloaded);
+  tracing::info!(bytes = downloaded, "threat feed refresh download");
+
+  match fetched {
+    Ok(fetched) => finish_refresh(base, meta, fetched, attempt_at, downloaded),
     Err(e) => {
       meta.last_refresh_result = Some(format!("failed: {}", short_error(&e)));
       let _ = write_meta(base, &meta);
-      return RefreshNowResult {
+      RefreshNowResult {
         attempted: true,
         success: false,
         reason: format!("refresh failed: {}", short_error(&e)),
-      };
+      }
     }
-  };
+  }
+}
 
-  if let Err(e) = verify_bundle_bytes(&fetched.bundle_json, &fetched.bundle_sig) {
-    meta.last_refresh_result = Some(format!("failed: verification {}", short_error(&e)));
+/// Installs what a refresh downloaded: the key manifest, then the bundle unless it was not
+/// modified.
+fn finish_refresh(
+  base: &Path,
+  mut meta: BundleMeta,
+  fetched: fetch::FetchedBundle,
+  attempt_at: u64,
+  downloaded: u64,
+) -> RefreshNowResult {
+  // A newer key manifest may rotate in the key this bundle is signed with, so install it first.
+  if let Some((json, sig)) = &fetched.key_manifest {
+    if let Err(e) = crate::trust::install_manifest(base, json, sig) {
+      tracing::warn!(host = %fetched.host, reason = %short_error(&e), "key manifest rejected");
+    }
+  }
+
+  let Some(files) = fetched.bundle else {
+    // The installed bundle verified when the request was built, but the manifest just installed
+    // may have revoked its key. Without validators, the next refresh downloads it again.
+    if load_current_signed(base).is_none() {
+      meta.last_refresh_result = Some("failed: installed bundle no longer verifies".to_string());
+      let _ = write_meta(base, &meta);
+      tracing::warn!(host = %fetched.host, "threat feed not modified, but the installed bundle no longer verifies");
+      return RefreshNowResult {
+        attempted: true,
+        success: false,
+        reason: "not modified, but the installed bundle no longer verifies".to_string(),
+      };
+    }
+    meta.last_verified_at = Some(attempt_at);
+    meta.last_refresh_result = Some("success (not modified)".to_string());
     let _ = write_meta(base, &meta);
-    tracing::warn!(host = %fetched.host, reason = %short_error(&e), "threat feed verification failed");
+    tracing::info!(host = %fetched.host, "threat feed refresh: bundle not modified");
     return RefreshNowResult {
       attempted: true,
-      success: false,
-      reason: format!("verification failed: {}", short_error(&e)),
+      success: true,
+      reason: "success (not modified)".to_string(),
     };
-  }
+  };
+
+  let signer = match verify_bundle_bytes(&files.bundle_json, &files.bundle_sig) {
+    Ok((_, signer)) => signer,
+    Err(e) => {
+      meta.last_refresh_result = Some(format!("failed: verification {}", short_error(&e)));
+      let _ = write_meta(base, &meta);
+      tracing::warn!(host = %fetched.host, reason = %short_error(&e), "threat feed verification failed");
+      return RefreshNowResult {
+        attempted: true,
+        success: false,
+        reason: format!("verification failed: {}", short_error(&e)),
+      };
+    }
+  };
 
-  if let Err(e) = install_verified_bundle(base, &fetched.bundle_json, &fetched.bundle_sig) {
+  if let Err(e) = install_verified_bundle(base, &files.bundle_json, &files.bundle_sig) {
     meta.last_refresh_result = Some(format!("failed: install {}", short_error(&e)));
     let _ = write_meta(base, &meta);
     return RefreshNowResult {
@@ -338,9 +488,12 @@ pub fn refresh_now(cfg: &Config, base: &Path) -> RefreshNowResult {
   let mut meta2 = read_meta(base);
   meta2.last_refresh_attempt_at = Some(attempt_at);
   meta2.last_refresh_result = Some("success".to_string());
+  meta2.last_refresh_bytes = Some(downloaded);
+  meta2.etag = files.validators.etag;
+  meta2.last_modified = files.validators.last_modified;
   let _ = write_meta(base, &meta2);
 
-  tracing::info!(host = %fetched.host, "threat feed refresh succeeded");
+  tracing::info!(host = %fetched.host, signer = %signer.label(), "threat feed refresh succeeded");
   RefreshNowResult {
     attempted: true,
     success: true,
@@ -359,6 +512,13 @@ pub fn auto_refresh_eligibility(cfg: &Config, base: &Path) -> AutoRefreshEligibi
 
   let lic = license::status(base);
   match lic.state {
+    LicenseState::ProActive if !lic.has_feature(license::FEATURE_AUTO_REFRESH) => {
+      return AutoRefreshEligibility {
+        eligible: false,
+        interval_minutes: cfg.threat_feed.refresh_interval_minutes,
+        reason: "Auto refresh disabled (not included in license)".to_string(),
+      };
+    }
     LicenseState::ProActive => {}
     LicenseState::Community => {
       return AutoRefreshEligibility {
@@ -404,6 +564,9 @@ pub fn auto_refresh_status(cfg: &Config, base: &Path) -> AutoRefreshStatus {
     reason: eligibility.reason,
     last_attempt_at: meta.last_refresh_attempt_at,
     last_result: meta.last_refresh_result,
+    last_bytes: meta.last_refresh_bytes,
+    defer_on_metered: cfg.threat_feed.defer_on_metered,
+    metered: net_usage::is_metered(),
   }
 }
 
@@ -426,6 +589,7 @@ pub fn status(base: &Path) -> FeedStatus {
     installed_at_unix_ms: st.created_at.map(|seconds| seconds.saturating_mul(1000)),
     checked_at_unix_ms: checked,
     reason: st.last_refresh_result.clone(),
+    signer: st.signer.clone(),
   };
   if !runtime::is_dry_run() {
     let _ = write_state(base, &out);
@@ -445,28 +609,34 @@ fn install_verified_bundle(base: &Path, bundle_json: &[u8], sig_raw: &[u8]) -> a
   let dst_bundle = paths::threat_feed_bundle_path(base);
   let dst_sig = paths::threat_feed_sig_path(base);
 
-  atomic_write_file(&dst_bundle, bundle_json)?;
-  atomic_write_file(&dst_sig, sig_raw)?;
+  write_atomic(&dst_bundle, bundle_json)?;
+  write_atomic(&dst_sig, sig_raw)?;
   write_last_good(base, bundle_json, sig_raw)?;
 
   let now = now_unix_s();
   let mut meta = read_meta(base);
   meta.last_imported_at = Some(now);
   meta.last_verified_at = Some(now);
+  // Validators belong to the previous bundle; a refresh sets them again for its own.
+  meta.etag = None;
+  meta.last_modified = None;
   write_meta(base, &meta)?;
   Ok(())
 }
 
-fn verify_bundle_bytes(bundle_json: &[u8], sig_raw: &[u8]) -> anyhow::Result<ThreatFeedBundle> {
-  let sig = decode_sig_file(sig_raw)?;
-  verify::verify_bundle_signature(bundle_json, &sig)?;
+fn verify_bundle_bytes(
+  bundle_json: &[u8],
+  sig_raw: &[u8],
+) -> anyhow::Result<(ThreatFeedBundle, verify::Signer)> {
+  let signer = verify::verify_bundle_signature(bundle_json, sig_raw)?;
   let bundle: ThreatFeedBundle =
     serde_json::from_slice(bundle_json).context("parse bundle JSON")?;
   validate_bundle_schema(&bundle)?;
-  Ok(bundle)
+  Ok((bundle, signer))
 }
 
-fn validate_bundle_schema(bundle: &ThreatFeedBundle) -> anyhow::Result<()> {
+/// Checks required fields and the version; unknown rule IDs are only warned about.
+pub fn validate_bundle_schema(bundle: &ThreatFeedBundle) -> anyhow::Result<()> {
   if bundle.version != 1 {
     anyhow::bail!("unsupported bundle version {}; expected 1", bundle.version);
   }
@@ -480,21 +650,65 @@ fn validate_bundle_schema(bundle: &ThreatFeedBundle) -> anyhow::Result<()> {
     anyhow::bail!("rules_version must be > 0");
   }
 
+  let mut detection_ids = std::collections::HashSet::new();
+  for detection in &bundle.detections {
+    crate::rules_engine::feed_rules::validate(detection)?;
+    if !detection_ids.insert(detection.rule_id.trim().to_ascii_uppercase()) {
+      anyhow::bail!("detections: duplicate rule_id {}", detection.rule_id.trim());
+    }
+  }
+
   for rule in &bundle.rules {
     if rule.rule_id.trim().is_empty() {
       anyhow::bail!("rule_id must not be empty");
     }
+    if !crate::rules_engine::rules::ALL_RULES
+      .iter()
+      .any(|r| r.id.eq_ignore_ascii_case(rule.rule_id.trim()))
+      && !crate::guidance::is_scanner_rule(&rule.rule_id)
+      && !detection_ids.contains(&rule.rule_id.trim().to_ascii_uppercase())
+    {
+      tracing::warn!(rule_id = %rule.rule_id, "threat feed override for unknown rule ID; ignored");
+    }
+    if let Some(g) = &rule.guidance {
+      if [&g.meaning, &g.false_positives, &g.action]
+        .iter()
+        .any(|t| t.chars().count() > MAX_GUIDANCE_CHARS)
+      {
+        anyhow::bail!(
+          "rules: guidance for {} exceeds {MAX_GUIDANCE_CHARS} characters",
+          rule.rule_id
+        );
+      }
+    }
   }
 
-  Ok(())
-}
+  let malformed = bundle
+    .reputation
+    .hashes_block
+    .iter()
+    .filter(|h| schema::normalize_hash(h).is_none())
+    .count();
+  if malformed > 0 {
+    tracing::warn!(
+      count = malformed,
+      "threat feed hashes_block entries that aren't SHA-256 digests; ignored"
+    );
+  }
 
-fn decode_sig_file(sig_raw: &[u8]) -> anyhow::Result<Vec<u8>> {
-  if sig_raw.len() == 64 {
-    return Ok(sig_raw.to_vec());
+  for pattern in &bundle.reputation.wallet_drain_patterns {
+    if pattern.id.trim().is_empty() {
+      anyhow::bail!("wallet_drain_patterns: id must not be empty");
+    }
+    if pattern.all_of.iter().all(|s| s.trim().is_empty()) {
+      anyhow::bail!(
+        "wallet_drain_patterns: {} has no strings to match",
+        pattern.id
+      );
+    }
   }
-  let text = std::str::from_utf8(sig_raw).context("signature file must be raw bytes or UTF-8")?;
-  verify::decode_sig_base64url(text)
+
+  Ok(())
 }
 
 fn read_meta(base: &Path) -> BundleMeta {
@@ -509,7 +723,7 @@ fn write_meta(base: &Path, meta: &BundleMeta) -> anyhow::Result<()> {
   let dir = paths::threat_feed_dir(base);
   fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
   let bytes = serde_json::to_vec_pretty(meta)?;
-  atomic_write_file(&paths::threat_feed_meta_path(base), &bytes)
+  write_atomic(&paths::threat_feed_meta_path(base), &bytes)
 }
 
 fn mark_verified(base: &Path) -> anyhow::Result<()> {
@@ -530,33 +744,15 @@ fn last_good_sig_path(base: &Path) -> PathBuf {
 fn write_last_good(base: &Path, bundle_json: &[u8], sig_raw: &[u8]) -> anyhow::Result<()> {
   let dir = paths::threat_feed_dir(base);
   fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
-  atomic_write_file(&last_good_bundle_path(base), bundle_json)?;
-  atomic_write_file(&last_good_sig_path(base), sig_raw)?;
+  write_atomic(&last_good_bundle_path(base), bundle_json)?;
+  write_atomic(&last_good_sig_path(base), sig_raw)?;
   Ok(())
 }
 
-fn verify_last_good(base: &Path) -> anyhow::Result<ThreatFeedBundle> {
+fn verify_last_good(base: &Path) -> anyhow::Result<(ThreatFeedBundle, verify::Signer)> {
   let b = last_good_bundle_path(base);
   let s = last_good_sig_path(base);
-  verify_files(&b, &s)
-}
-
-fn atomic_write_file(dst: &Path, bytes: &[u8]) -> anyhow::Result<()> {
-  let dir = dst
-    .parent()
-    .ok_or_else(|| anyhow::anyhow!("destination has no parent directory"))?;
-  fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
-
-  let tmp = tmp_path(dst);
-  fs::write(&tmp, bytes).with_context(|| format!("write {}", tmp.display()))?;
-  fs::rename(&tmp, dst)
-    .with_context(|| format!("rename {} -> {}", tmp.display(), dst.display()))?;
-  Ok(())
-}
-
-fn tmp_path(dst: &Path) -> PathBuf {
-  let name = dst.file_name().and_then(|s| s.to_str()).unwrap_or("tmp");
-  dst.with_file_name(format!(".{name}.tmp"))
+  verify_files_signed(&b, &s)
 }
 
 fn write_state(base: &Path, st: &FeedStatus) -> anyhow::Result<()> {
@@ -564,9 +760,10 @@ fn write_state(base: &Path, st: &FeedStatus) -> anyhow::Result<()> {
   fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
   let path = paths::threat_feed_state_path(base);
   let bundle = bundle_status_at(base);
+  let usage = net_usage::current_month(base, st.checked_at_unix_ms);
 
   let content = format!(
-    "installed = {}\nverified = {}\nversion = {}\ninstalled_at_unix_ms = {}\nchecked_at_unix_ms = {}\nreason = {}\ncreated_at_unix_seconds = {}\nlast_verified_at_unix_seconds = {}\nlast_refresh_attempt_at_unix_seconds = {}\nlast_refresh_result = {}\n",
+    "installed = {}\nverified = {}\nversion = {}\ninstalled_at_unix_ms = {}\nchecked_at_unix_ms = {}\nreason = {}\ncreated_at_unix_seconds = {}\nlast_verified_at_unix_seconds = {}\nlast_refresh_attempt_at_unix_seconds = {}\nlast_refresh_result = {}\nmonth_download_bytes = {}\nsigner = {}\n",
     st.installed,
     st.verified,
     toml_u64_or_null(st.version),
@@ -577,23 +774,11 @@ fn write_state(base: &Path, st: &FeedStatus) -> anyhow::Result<()> {
     toml_u64_or_null(bundle.verified_at),
     toml_u64_or_null(bundle.last_refresh_attempt_at),
     toml_string_or_null(bundle.last_refresh_result.as_deref()),
+    usage.threat_feed_bytes,
+    toml_string_or_null(st.signer.as_deref()),
   );
 
-  atomic_write_file(&path, content.as_bytes())
-}
-
-fn toml_string_or_null(v: Option<&str>) -> String {
-  match v {
-    Some(s) => format!("\"{}\"", s.replace('"', "\\\"")),
-    None => "null".to_string(),
-  }
-}
-
-fn toml_u64_or_null(v: Option<u64>) -> String {
-  match v {
-    Some(x) => x.to_string(),
-    None => "null".to_string(),
-  }
+  write_atomic(&path, content.as_bytes())
 }
 
 fn short_error(e: &anyhow::Error) -> String {
@@ -609,3 +794,50 @@ fn short_error(e: &anyhow::Error) -> String {
 fn now_unix_s() -> u64 {
   now_unix_ms() / 1000
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ed25519_dalek::{Signer as _, SigningKey};
+
+  #[test]
+  fn not_modified_refresh_succeeds_only_while_the_installed_bundle_verifies() {
+    let base = std::env::temp_dir().join(format!("aid-feed-{}", uuid::Uuid::new_v4()));
+    let org = SigningKey::from_bytes(&[9u8; 32]);
+    let bundle = serde_json::json!({
+      "version": 1,
+      "bundle_id": uuid::Uuid::new_v4().to_string(),
+      "created_at": 1_700_000_000u64,
+      "rules_version": 1,
+      "reputation": {},
+    })
+    .to_string();
+    let sig = org.sign(bundle.as_bytes()).to_bytes();
+    verify::set_org_key(Some(org.verifying_key()));
+    install_verified_bundle(&base, bundle.as_bytes(), &sig).unwrap();
+    let mut meta = read_meta(&base);
+    meta.etag = Some("\"v1\"".to_string());
+    write_meta(&base, &meta).unwrap();
+    let not_modified = || fetch::FetchedBundle {
+      bundle: None,
+      key_manifest: None,
+      host: "feed.example".to_string(),
+    };
+
+    let result = finish_refresh(&base, read_meta(&base), not_modified(), 100, 0);
+    assert!(result.success, "{}", result.reason);
+    assert_eq!(result.reason, "success (not modified)");
+    let meta = read_meta(&base);
+    assert_eq!(meta.last_verified_at, Some(100));
+    assert_eq!(meta.etag.as_deref(), Some("\"v1\""));
+
+    // The signing key is no longer trusted (as after a manifest revoking it): not a success.
+    verify::set_org_key(None);
+    let result = finish_refresh(&base, read_meta(&base), not_modified(), 200, 0);
+    assert!(result.attempted && !result.success);
+    let meta = read_meta(&base);
+    assert_eq!(meta.last_verified_at, Some(100));
+    assert!(meta.last_refresh_result.unwrap().starts_with("failed:"));
+    let _ = fs::remove_dir_all(&base);
+  }
+}
@@ -1,4 +1,6 @@
+use crate::types::Guidance;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThreatFeedBundle {
@@ -7,28 +9,210 @@ pub struct ThreatFeedBundle {
   pub created_at: u64,
   pub rules_version: u64,
   pub reputation: ReputationLists,
+  /// Optional per-rule overrides; local `[rules]` config takes precedence.
+  #[serde(default)]
   pub rules: Vec<RuleOverride>,
+  /// Declarative rules evaluated by the engine next to the built-in ones (`TF` rule IDs, see
+  /// `rules_engine::feed_rules`).
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub detections: Vec<DetectionRule>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ReputationLists {
   #[serde(default)]
   pub domains_block: Vec<String>,
+  /// SHA-256 digests of known-bad files, optionally prefixed with `sha256:` (scanner S011).
   #[serde(default)]
   pub hashes_block: Vec<String>,
   #[serde(default)]
   pub wallet_spenders_block: Vec<String>,
+  /// IPv4/IPv6 addresses or CIDR ranges.
+  #[serde(default)]
+  pub ips_block: Vec<String>,
+  /// Domains registered recently (e.g. within 30 days); only used together with a preceding
+  /// sensitive file access (R017).
+  #[serde(default)]
+  pub domains_newly_registered: Vec<String>,
+  /// Crypto wallet-drainer signatures matched against browser extension scripts and process
+  /// command lines (R023, scanner S010).
+  #[serde(default)]
+  pub wallet_drain_patterns: Vec<WalletDrainPattern>,
+}
+
+impl ReputationLists {
+  /// `hashes_block` as lowercase hex digests; malformed entries are left out.
+  pub fn blocked_hashes(&self) -> HashSet<String> {
+    self
+      .hashes_block
+      .iter()
+      .filter_map(|h| normalize_hash(h))
+      .collect()
+  }
+}
+
+/// A `hashes_block` entry as a lowercase hex digest, `None` when it isn't a SHA-256.
+pub fn normalize_hash(entry: &str) -> Option<String> {
+  let entry = entry.trim();
+  let hex = match entry.get(..7) {
+    Some(prefix) if prefix.eq_ignore_ascii_case("sha256:") => &entry[7..],
+    _ => entry,
+  };
+  (hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit())).then(|| hex.to_ascii_lowercase())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletDrainPattern {
+  pub id: String,
+  #[serde(default)]
+  pub target: DrainPatternTarget,
+  /// Case-insensitive substrings that must all appear.
+  pub all_of: Vec<String>,
+  #[serde(default)]
+  pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DrainPatternTarget {
+  /// JavaScript/HTML content (browser extensions, injected scripts).
+  #[default]
+  Script,
+  /// A process command line (clipboard hijackers, drainer loaders).
+  CommandLine,
+}
+
+impl ReputationLists {
+  pub fn is_ip_blocked(&self, ip: &std::net::IpAddr) -> bool {
+    self
+      .ips_block
+      .iter()
+      .filter_map(|e| e.parse::<crate::net::Cidr>().ok())
+      .any(|c| c.contains(ip))
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DetectionRule {
+  /// `TF` followed by letters, digits, `-` or `_`, e.g. `TF001`.
+  pub rule_id: String,
+  pub title: String,
+  #[serde(default = "default_yellow")]
+  pub severity: BundleSeverity,
+  /// Steps the same process must match in order; a single step is a plain event match.
+  pub sequence: Vec<DetectionStep>,
+  /// How long a sequence may take from its first step to its last (default 300 seconds).
+  #[serde(default)]
+  pub window_s: Option<u64>,
+  /// Only evaluated on machines with at least one of these `machine.tags` (empty: every machine).
+  #[serde(default)]
+  pub tags: Vec<String>,
+  /// Never evaluated on machines with any of these tags.
+  #[serde(default)]
+  pub exclude_tags: Vec<String>,
+}
+
+impl DetectionRule {
+  pub fn applies_to(&self, machine_tags: &[String]) -> bool {
+    crate::config::tags_match(&self.tags, &self.exclude_tags, machine_tags)
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DetectionStep {
+  pub event: DetectionEvent,
+  /// All must hold.
+  #[serde(default)]
+  pub conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionEvent {
+  ProcessStart,
+  FileAccess,
+  NetConnect,
+  RegistrySet,
+  DnsQuery,
+  ProcessAccess,
+  ScriptBlock,
+}
+
+/// `field op value(s)`. String comparisons ignore case; a condition on a field the event doesn't
+/// have is false (`exists` tests for that).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+  pub field: String,
+  pub op: ConditionOp,
+  /// Compared values; the condition holds when any of them matches.
+  #[serde(default)]
+  pub values: Vec<String>,
+  /// For `in_list`: a `reputation` list (`domains_block`, `domains_newly_registered`,
+  /// `ips_block`, `wallet_spenders_block`).
+  #[serde(default)]
+  pub list: Option<String>,
+  /// Inverts the result.
+  #[serde(default)]
+  pub not: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionOp {
+  Equals,
+  Contains,
+  StartsWith,
+  EndsWith,
+  /// Windows path globs (see `defender_common::glob`).
+  Glob,
+  /// Any of the bits of a hex (`0x1410`) or decimal mask is set, for `granted_access`.
+  MaskAny,
+  InList,
+  Exists,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RuleOverride {
   pub rule_id: String,
+  #[serde(default = "default_true")]
   pub enabled: bool,
-  pub severity_floor: BundleSeverity,
-  pub severity_cap_learning: BundleSeverity,
-  pub severity_strict: BundleSeverity,
+  /// Findings are raised to at least this severity.
+  #[serde(default)]
+  pub severity_floor: Option<BundleSeverity>,
+  /// In learning mode, findings are lowered to at most this severity.
+  #[serde(default)]
+  pub severity_cap_learning: Option<BundleSeverity>,
+  /// In strict mode, findings get exactly this severity.
+  #[serde(default)]
+  pub severity_strict: Option<BundleSeverity>,
   #[serde(default)]
   pub notes: Option<String>,
+  /// Remediation guidance recorded with this rule's incidents (see `guidance`).
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub guidance: Option<Guidance>,
+  /// Only applies on machines with at least one of these `machine.tags` (empty: every machine).
+  #[serde(default)]
+  pub tags: Vec<String>,
+  /// Never applies on machines with any of these tags.
+  #[serde(default)]
+  pub exclude_tags: Vec<String>,
+}
+
+impl RuleOverride {
+  pub fn applies_to(&self, machine_tags: &[String]) -> bool {
+    crate::config::tags_match(&self.tags, &self.exclude_tags, machine_tags)
+  }
+}
+
+fn default_true() -> bool {
+  true
+}
+
+fn default_yellow() -> BundleSeverity {
+  BundleSeverity::Yellow
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -38,3 +222,25 @@ pub enum BundleSeverity {
   Yellow,
   Red,
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn blocked_hashes_accept_prefixed_digests_and_skip_malformed_ones() {
+    let digest = "AB".repeat(32);
+    let lists = ReputationLists {
+      hashes_block: vec![
+        format!("sha256:{digest}"),
+        format!(" {} ", "cd".repeat(32)),
+        "sha256:...".to_string(),
+        "md5:00".to_string(),
+      ],
+      ..ReputationLists::default()
+    };
+    let hashes = lists.blocked_hashes();
+    assert_eq!(hashes.len(), 2);
+    assert!(hashes.contains(&"ab".repeat(32)) && hashes.contains(&"cd".repeat(32)));
+  }
+}
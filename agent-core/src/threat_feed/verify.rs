@@ -1,40 +1,110 @@
-use anyhow::Context;
-use base64::engine::general_purpose::URL_SAFE_NO_PAD;
-use base64::Engine;
+use crate::trust::{Purpose, TrustSet};
 use ed25519_dalek::{Signature, VerifyingKey};
+use std::sync::RwLock;
 
-// Public-key only. Replace with the production public key for threat feed verification.
-const FEED_PUBKEY_B64URL: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+pub use crate::trust::{decode_signature, fingerprint, parse_public_key};
 
-pub fn verify_bundle_signature(bundle_json: &[u8], sig_bytes: &[u8]) -> anyhow::Result<()> {
-  if sig_bytes.len() != 64 {
-    anyhow::bail!("invalid signature length (expected 64 bytes)");
+// Vendor keys (embedded, or rotated in through the signed key manifest) come from `trust`.
+
+// Optional organization key for private feeds (`threat_feed.org_public_key[_file]`). Bundles signed
+// by it are accepted alongside vendor-signed ones; set once at startup from config.
+static ORG_KEY: RwLock<Option<VerifyingKey>> = RwLock::new(None);
+
+/// Which trusted key produced a bundle signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Signer {
+  Vendor,
+  /// Configured organization key, identified by its fingerprint.
+  Organization(String),
+}
+
+impl Signer {
+  pub fn label(&self) -> String {
+    match self {
+      Signer::Vendor => "vendor".to_string(),
+      Signer::Organization(fp) => format!("organization:{fp}"),
+    }
   }
-  let mut sig_arr = [0u8; 64];
-  sig_arr.copy_from_slice(sig_bytes);
-  let sig = Signature::from_bytes(&sig_arr);
-
-  let key = verifying_key().context("load embedded public key")?;
-  key
-    .verify_strict(bundle_json, &sig)
-    .context("signature verification failed")?;
-  Ok(())
 }
 
-pub fn decode_sig_base64url(text: &str) -> anyhow::Result<Vec<u8>> {
-  URL_SAFE_NO_PAD
-    .decode(text.trim().as_bytes())
-    .context("decode signature base64url")
+pub fn set_org_key(key: Option<VerifyingKey>) {
+  *ORG_KEY.write().unwrap_or_else(|e| e.into_inner()) = key;
 }
 
-fn verifying_key() -> anyhow::Result<VerifyingKey> {
-  let pk = URL_SAFE_NO_PAD
-    .decode(FEED_PUBKEY_B64URL.as_bytes())
-    .context("decode embedded public key base64url")?;
-  if pk.len() != 32 {
-    anyhow::bail!("embedded public key must be 32 bytes (ed25519)");
+pub fn org_key() -> Option<VerifyingKey> {
+  *ORG_KEY.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Verifies a bundle signature given as raw 64 bytes or base64url text.
+pub fn verify_bundle_signature(bundle_json: &[u8], sig_raw: &[u8]) -> anyhow::Result<Signer> {
+  verify_with(
+    bundle_json,
+    &decode_signature(sig_raw)?,
+    &TrustSet::load(Purpose::ThreatFeed),
+    org_key().as_ref(),
+  )
+}
+
+fn verify_with(
+  bundle_json: &[u8],
+  sig: &Signature,
+  vendor: &TrustSet,
+  org: Option<&VerifyingKey>,
+) -> anyhow::Result<Signer> {
+  let vendor_err = match vendor.verify(bundle_json, sig) {
+    Ok(key) => {
+      tracing::debug!(key_id = %key.key_id, "threat feed bundle signed by vendor key");
+      return Ok(Signer::Vendor);
+    }
+    Err(e) => e,
+  };
+  if let Some(org) = org {
+    if org.verify_strict(bundle_json, sig).is_ok() {
+      if vendor.is_revoked(org) {
+        anyhow::bail!(
+          "signed by organization key {}, which is revoked",
+          fingerprint(org)
+        );
+      }
+      return Ok(Signer::Organization(fingerprint(org)));
+    }
+    anyhow::bail!("signature verification failed (vendor and organization keys)");
+  }
+  Err(vendor_err)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+  use base64::Engine;
+  use ed25519_dalek::{Signer as _, SigningKey};
+
+  #[test]
+  fn org_key_is_accepted_only_when_configured() {
+    let org = SigningKey::from_bytes(&[7u8; 32]);
+    let bundle = br#"{"version":1}"#;
+    let sig = org.sign(bundle);
+
+    let vendor = TrustSet::load(Purpose::ThreatFeed);
+    assert!(verify_with(bundle, &sig, &vendor, None).is_err());
+
+    let signer = verify_with(bundle, &sig, &vendor, Some(&org.verifying_key())).unwrap();
+    assert_eq!(
+      signer,
+      Signer::Organization(fingerprint(&org.verifying_key()))
+    );
+    assert!(signer.label().starts_with("organization:"));
+
+    let other = SigningKey::from_bytes(&[8u8; 32]);
+    assert!(verify_with(bundle, &sig, &vendor, Some(&other.verifying_key())).is_err());
+  }
+
+  #[test]
+  fn parses_base64url_keys() {
+    let key = SigningKey::from_bytes(&[7u8; 32]).verifying_key();
+    let text = URL_SAFE_NO_PAD.encode(key.as_bytes());
+    assert_eq!(parse_public_key(&format!(" {text}\n")).unwrap(), key);
+    assert!(parse_public_key("AAAA").is_err());
   }
-  let mut arr = [0u8; 32];
-  arr.copy_from_slice(&pk);
-  Ok(VerifyingKey::from_bytes(&arr)?)
 }
@@ -0,0 +1,347 @@
+use crate::paths;
+use crate::types::{Event, Evidence, Incident};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+// Chronological view of what one process did, for post-incident analysis (`--incidents
+// timeline`): its recorded events (`[recording]`), the findings of incidents naming it, and the
+// response to them. Recorded events are matched by PID only, so a PID reused by a later process
+// shows both; the process start lines make that visible.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+  pub timestamp_unix_ms: u64,
+  /// `process`, `file`, `network`, `dns`, `registry`, `access`, `script`, `clipboard`, `finding`,
+  /// `response` or `review`.
+  pub kind: &'static str,
+  pub text: String,
+}
+
+/// PIDs named in an incident's evidence, in order of appearance.
+pub fn incident_pids(incident: &Incident) -> Vec<u32> {
+  let mut seen = HashSet::new();
+  incident
+    .findings
+    .iter()
+    .flat_map(|f| &f.evidence)
+    .filter_map(evidence_pid)
+    .filter(|pid| *pid != 0 && seen.insert(*pid))
+    .collect()
+}
+
+/// Events from the recording (older rotated file first); unreadable lines are skipped.
+pub fn recorded_events(base: &Path) -> Vec<Event> {
+  let current = paths::recording_path(base);
+  let rotated = current.with_extension("1.ndjson");
+  [rotated, current]
+    .iter()
+    .filter_map(|p| fs::read_to_string(p).ok())
+    .flat_map(|raw| {
+      raw
+        .lines()
+        .filter_map(|l| serde_json::from_str::<Event>(l.trim()).ok())
+        .collect::<Vec<_>>()
+    })
+    .collect()
+}
+
+/// Merges the events of `pids` (and the processes they started) with the findings, actions and
+/// reviews of the incidents that name them, ordered by time.
+pub fn build(pids: &[u32], incidents: &[Incident], events: &[Event]) -> Vec<Entry> {
+  let mut out: Vec<Entry> = events.iter().filter_map(|e| event_entry(pids, e)).collect();
+
+  for inc in incidents {
+    if !incident_pids(inc).iter().any(|p| pids.contains(p)) {
+      continue;
+    }
+    for f in &inc.findings {
+      out.push(Entry {
+        timestamp_unix_ms: f.timestamp_unix_ms,
+        kind: "finding",
+        text: format!(
+          "{} {:?}: {} (incident {})",
+          f.rule_id, f.severity, f.description, inc.incident_id
+        ),
+      });
+    }
+    // Actions carry no time of their own; they were taken when the incident was handled.
+    if !inc.actions_taken.is_empty() {
+      out.push(Entry {
+        timestamp_unix_ms: inc.created_at_unix_ms,
+        kind: "response",
+        text: format!(
+          "{} (incident {})",
+          inc.actions_taken.join(", "),
+          inc.incident_id
+        ),
+      });
+    }
+    if let Some(r) = &inc.resolution {
+      out.push(Entry {
+        timestamp_unix_ms: r.resolved_at_unix_ms,
+        kind: "review",
+        text: format!(
+          "marked {:?}{} (incident {})",
+          r.verdict,
+          r.note
+            .as_deref()
+            .map(|n| format!(": {n}"))
+            .unwrap_or_default(),
+          inc.incident_id
+        ),
+      });
+    }
+  }
+
+  // Stable: findings and responses stay after the events recorded at the same moment.
+  out.sort_by_key(|e| e.timestamp_unix_ms);
+  out
+}
+
+fn event_entry(pids: &[u32], ev: &Event) -> Option<Entry> {
+  let (timestamp_unix_ms, kind, text) = match ev {
+    Event::ProcessStart {
+      pid,
+      ppid,
+      image_path,
+      signer_publisher,
+      command_line,
+      download_origin,
+      timestamp_unix_ms,
+      ..
+    } => {
+      let mut signer = signer_publisher
+        .as_deref()
+        .map(|s| format!(", signed by {s}"))
+        .unwrap_or_else(|| ", unsigned".to_string());
+      if let Some(origin) = download_origin.as_ref().filter(|o| o.is_internet()) {
+        signer.push_str(", downloaded");
+        if let Some(url) = &origin.host_url {
+          signer.push_str(&format!(" from {url}"));
+        }
+      }
+      let cmd = command_line
+        .as_deref()
+        .map(|c| format!(": {c}"))
+        .unwrap_or_default();
+      let text = if pids.contains(pid) {
+        format!("started {image_path} (pid {pid}, parent {ppid}{signer}){cmd}")
+      } else if pids.contains(ppid) {
+        format!("started child {image_path} (pid {pid}{signer}){cmd}")
+      } else {
+        return None;
+      };
+      (*timestamp_unix_ms, "process", text)
+    }
+    Event::FileAccess {
+      pid,
+      file_path,
+      access,
+      timestamp_unix_ms,
+      ..
+    } if pids.contains(pid) => (
+      *timestamp_unix_ms,
+      "file",
+      format!("{} {file_path}", format!("{access:?}").to_lowercase()),
+    ),
+    Event::NetConnect {
+      pid,
+      dest_ip,
+      dest_port,
+      dest_host,
+      protocol,
+      timestamp_unix_ms,
+      ..
+    } if pids.contains(pid) => {
+      let host = dest_host
+        .as_deref()
+        .map(|h| format!(" ({h})"))
+        .unwrap_or_default();
+      (
+        *timestamp_unix_ms,
+        "network",
+        format!("connect {protocol} {dest_ip}:{dest_port}{host}"),
+      )
+    }
+    Event::DnsQuery {
+      pid,
+      query_name,
+      timestamp_unix_ms,
+      ..
+    } if pids.contains(pid) => (*timestamp_unix_ms, "dns", format!("query {query_name}")),
+    Event::RegistrySet {
+      pid,
+      key_path,
+      value,
+      timestamp_unix_ms,
+      ..
+    } if pids.contains(pid) => {
+      let value = value
+        .as_deref()
+        .map(|v| format!(" = {v}"))
+        .unwrap_or_default();
+      (
+        *timestamp_unix_ms,
+        "registry",
+        format!("set {key_path}{value}"),
+      )
+    }
+    Event::ProcessAccess {
+      pid,
+      target_pid,
+      target_image,
+      granted_access,
+      timestamp_unix_ms,
+      ..
+    } if pids.contains(pid) => (
+      *timestamp_unix_ms,
+      "access",
+      format!("opened {target_image} (pid {target_pid}) with access 0x{granted_access:x}"),
+    ),
+    Event::ScriptBlock {
+      pid,
+      script_block_id,
+      part,
+      parts,
+      path,
+      timestamp_unix_ms,
+      ..
+    } if pids.contains(pid) => {
+      let path = path
+        .as_deref()
+        .map(|p| format!(" from {p}"))
+        .unwrap_or_default();
+      (
+        *timestamp_unix_ms,
+        "script",
+        format!("script block {script_block_id} part {part}/{parts}{path}"),
+      )
+    }
+    Event::ClipboardChange {
+      pid,
+      address,
+      timestamp_unix_ms,
+      ..
+    } if pids.contains(pid) => {
+      let what = if address.is_some() {
+        "a crypto address"
+      } else {
+        "other content"
+      };
+      (
+        *timestamp_unix_ms,
+        "clipboard",
+        format!("set clipboard to {what}"),
+      )
+    }
+    _ => return None,
+  };
+  Some(Entry {
+    timestamp_unix_ms,
+    kind,
+    text,
+  })
+}
+
+fn evidence_pid(e: &Evidence) -> Option<u32> {
+  match e {
+    Evidence::Process { pid, .. }
+    | Evidence::File { pid, .. }
+    | Evidence::Network { pid, .. }
+    | Evidence::Dns { pid, .. }
+    | Evidence::CommandLine { pid, .. }
+    | Evidence::Registry { pid, .. }
+    | Evidence::SettingChange { pid, .. }
+    | Evidence::ProcessAccess { pid, .. }
+    | Evidence::Script { pid, .. }
+    | Evidence::Clipboard { pid, .. }
+    | Evidence::Correlation { pid, .. } => Some(*pid),
+    Evidence::Note { .. } => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::{FileAccessType, Finding, Severity};
+
+  #[test]
+  fn merges_events_and_incidents_of_the_process_in_order() {
+    let events = vec![
+      Event::ProcessStart {
+        pid: 42,
+        ppid: 1,
+        image_path: r"C:\Temp\stealer.exe".to_string(),
+        signer_publisher: None,
+        command_line: None,
+        download_origin: None,
+        process_guid: None,
+        parent_process_guid: None,
+        timestamp_unix_ms: 1_000,
+      },
+      Event::FileAccess {
+        pid: 42,
+        image_path: None,
+        file_path: r"C:\Users\<user>\wallet.dat".to_string(),
+        access: FileAccessType::Read,
+        process_guid: None,
+        timestamp_unix_ms: 2_000,
+      },
+      Event::FileAccess {
+        pid: 7,
+        image_path: None,
+        file_path: r"C:\other.txt".to_string(),
+        access: FileAccessType::Read,
+        process_guid: None,
+        timestamp_unix_ms: 2_500,
+      },
+      Event::NetConnect {
+        pid: 42,
+        image_path: None,
+        dest_ip: "203.0.113.9".parse().unwrap(),
+        dest_port: 443,
+        dest_host: None,
+        protocol: "tcp".to_string(),
+        process_guid: None,
+        timestamp_unix_ms: 3_000,
+      },
+    ];
+    let incident = |id: &str, pid: u32| Incident {
+      incident_id: id.to_string(),
+      severity: Severity::Red,
+      findings: vec![Finding {
+        rule_id: "R001".to_string(),
+        severity: Severity::Red,
+        description: "sensitive file then network".to_string(),
+        evidence: vec![
+          Evidence::Note {
+            message: "n".to_string(),
+          },
+          Evidence::Process {
+            pid,
+            ppid: 1,
+            image_path: "x".to_string(),
+            signer_publisher: None,
+            download_origin: None,
+          },
+        ],
+        timestamp_unix_ms: 3_000,
+      }],
+      actions_taken: vec!["kill_process".to_string()],
+      created_at_unix_ms: 3_100,
+      resolution: None,
+      flood: None,
+      score: None,
+      guidance: Default::default(),
+    };
+    let incidents = [incident("a", 42), incident("b", 7)];
+    assert_eq!(incident_pids(&incidents[0]), [42]);
+
+    let entries = build(&[42], &incidents, &events);
+    let kinds: Vec<&str> = entries.iter().map(|e| e.kind).collect();
+    assert_eq!(kinds, ["process", "file", "network", "finding", "response"]);
+    assert_eq!(entries[1].text, r"read C:\Users\<user>\wallet.dat");
+    assert_eq!(entries[4].text, "kill_process (incident a)");
+  }
+}
@@ -0,0 +1,392 @@
+use crate::paths;
+use crate::runtime;
+use anyhow::Context;
+use defender_common::fs::write_atomic;
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+// Trust roots for vendor signatures (threat feed bundles and licenses). The binary embeds one
+// signing key per purpose and the key-manifest root keys. A signed key manifest
+// (`trust\keys.json` + `trust\keys.sig`) adds signing keys with validity windows and revokes keys,
+// embedded ones included, so signing keys can rotate without shipping a new binary:
+//
+//   {
+//     "version": 1,
+//     "sequence": 3,
+//     "issued_at": 1760000000,
+//     "keys": [
+//       { "key_id": "feed-2026b", "purpose": "threat_feed", "public_key": "<base64url>",
+//         "not_before": 1760000000, "not_after": 1790000000 }
+//     ],
+//     "revoked": ["<fingerprint>"]
+//   }
+//
+// Only an embedded root key can sign a manifest. `sequence` must increase with every manifest and
+// revocations are remembered in `trust\state.toml`, so replaying an older manifest (or deleting
+// the current one) doesn't bring a revoked key back.
+
+// Public keys only. Replace with the production keys. Base64url (no padding), 32-byte Ed25519.
+const EMBEDDED_KEYS: &[(&str, Purpose, &str)] = &[
+  (
+    "embedded-feed",
+    Purpose::ThreatFeed,
+    "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+  ),
+  (
+    "embedded-license",
+    Purpose::License,
+    "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+  ),
+];
+const MANIFEST_ROOT_KEYS: &[&str] = &["AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"];
+
+pub use defender_common::signature::{decode_signature, fingerprint, parse_public_key};
+
+const MAX_MANIFEST_BYTES: usize = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Purpose {
+  ThreatFeed,
+  License,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestKey {
+  pub key_id: String,
+  pub purpose: Purpose,
+  pub public_key: String,
+  /// Unix seconds; the key is not trusted before this.
+  #[serde(default)]
+  pub not_before: Option<u64>,
+  /// Unix seconds; the key is not trusted after this.
+  #[serde(default)]
+  pub not_after: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyManifest {
+  pub version: u32,
+  pub sequence: u64,
+  pub issued_at: u64,
+  #[serde(default)]
+  pub keys: Vec<ManifestKey>,
+  /// Fingerprints (see `fingerprint`) of keys that must no longer verify anything.
+  #[serde(default)]
+  pub revoked: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TrustState {
+  #[serde(default)]
+  sequence: u64,
+  #[serde(default)]
+  revoked: Vec<String>,
+}
+
+/// A key that verified a signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedKey {
+  pub key_id: String,
+  pub key: VerifyingKey,
+}
+
+/// Keys trusted for one purpose at one point in time, plus the ones that are known but rejected
+/// (revoked or outside their validity window) so failures can say why.
+#[derive(Debug, Clone)]
+pub struct TrustSet {
+  trusted: Vec<TrustedKey>,
+  rejected: Vec<(TrustedKey, String)>,
+  revoked: Vec<String>,
+}
+
+impl TrustSet {
+  /// Embedded and installed-manifest keys for `purpose`, evaluated now.
+  pub fn load(purpose: Purpose) -> Self {
+    let base = paths::base_dir().ok();
+    let manifest = base.as_deref().and_then(load_manifest);
+    let state = base.as_deref().map(load_state).unwrap_or_default();
+    Self::build(purpose, manifest.as_ref(), &state.revoked, now_unix_s())
+  }
+
+  fn build(
+    purpose: Purpose,
+    manifest: Option<&KeyManifest>,
+    remembered_revoked: &[String],
+    now: u64,
+  ) -> Self {
+    let mut revoked: Vec<String> = remembered_revoked.to_vec();
+    if let Some(m) = manifest {
+      revoked.extend(m.revoked.iter().map(|f| f.trim().to_ascii_lowercase()));
+    }
+
+    let mut set = Self {
+      trusted: Vec::new(),
+      rejected: Vec::new(),
+      revoked,
+    };
+    for (key_id, p, text) in EMBEDDED_KEYS {
+      if *p != purpose {
+        continue;
+      }
+      if let Ok(key) = parse_public_key(text) {
+        set.add(key_id, key, None, None, now);
+      }
+    }
+    for k in manifest.map(|m| m.keys.as_slice()).unwrap_or_default() {
+      if k.purpose != purpose {
+        continue;
+      }
+      match parse_public_key(&k.public_key) {
+        Ok(key) => set.add(&k.key_id, key, k.not_before, k.not_after, now),
+        Err(e) => tracing::warn!(key_id = %k.key_id, error = %e, "key manifest entry ignored"),
+      }
+    }
+    set
+  }
+
+  fn add(
+    &mut self,
+    key_id: &str,
+    key: VerifyingKey,
+    not_before: Option<u64>,
+    not_after: Option<u64>,
+    now: u64,
+  ) {
+    let entry = TrustedKey {
+      key_id: key_id.to_string(),
+      key,
+    };
+    if self.is_revoked(&key) {
+      self.rejected.push((entry, "revoked".to_string()));
+    } else if not_before.is_some_and(|t| now < t) {
+      self.rejected.push((entry, "not valid yet".to_string()));
+    } else if not_after.is_some_and(|t| now > t) {
+      self.rejected.push((entry, "expired".to_string()));
+    } else {
+      self.trusted.push(entry);
+    }
+  }
+
+  /// Every known key with "trusted" or the reason it is rejected.
+  pub fn entries(&self) -> impl Iterator<Item = (&TrustedKey, &str)> {
+    self
+      .trusted
+      .iter()
+      .map(|k| (k, "trusted"))
+      .chain(self.rejected.iter().map(|(k, r)| (k, r.as_str())))
+  }
+
+  pub fn is_revoked(&self, key: &VerifyingKey) -> bool {
+    let fp = fingerprint(key);
+    self.revoked.contains(&fp)
+  }
+
+  /// The trusted key that produced `sig` over `msg`. A signature by a revoked or out-of-window
+  /// key is rejected with that reason.
+  pub fn verify(&self, msg: &[u8], sig: &Signature) -> anyhow::Result<&TrustedKey> {
+    if let Some(k) = self
+      .trusted
+      .iter()
+      .find(|k| k.key.verify_strict(msg, sig).is_ok())
+    {
+      return Ok(k);
+    }
+    if let Some((k, reason)) = self
+      .rejected
+      .iter()
+      .find(|(k, _)| k.key.verify_strict(msg, sig).is_ok())
+    {
+      anyhow::bail!(
+        "signed by key {} ({}), which is {reason}",
+        k.key_id,
+        fingerprint(&k.key)
+      );
+    }
+    anyhow::bail!("signature verification failed")
+  }
+}
+
+/// Verifies and installs a key manifest. It must be signed by an embedded root key. `None` if it
+/// is not newer (higher `sequence`) than the installed one, which is left as is.
+pub fn install_manifest(
+  base: &Path,
+  json: &[u8],
+  sig_raw: &[u8],
+) -> anyhow::Result<Option<KeyManifest>> {
+  let manifest = verify_manifest(json, sig_raw)?;
+  let mut state = load_state(base);
+  if manifest.sequence <= state.sequence {
+    return Ok(None);
+  }
+  if runtime::is_dry_run() {
+    tracing::warn!(
+      sequence = manifest.sequence,
+      "DRY-RUN: would install key manifest"
+    );
+    return Ok(Some(manifest));
+  }
+
+  let dir = paths::trust_dir(base);
+  fs::create_dir_all(&dir)?;
+  write_atomic(&paths::trust_manifest_path(base), json)?;
+  write_atomic(&paths::trust_manifest_sig_path(base), sig_raw)?;
+  state.sequence = manifest.sequence;
+  for fp in &manifest.revoked {
+    let fp = fp.trim().to_ascii_lowercase();
+    if !state.revoked.contains(&fp) {
+      state.revoked.push(fp);
+    }
+  }
+  write_atomic(
+    &paths::trust_state_path(base),
+    toml::to_string(&state)?.as_bytes(),
+  )?;
+
+  tracing::info!(
+    sequence = manifest.sequence,
+    keys = manifest.keys.len(),
+    revoked = manifest.revoked.len(),
+    "key manifest installed"
+  );
+  Ok(Some(manifest))
+}
+
+/// The installed manifest, re-verified. `None` (with a warning) if it is missing or doesn't verify.
+pub fn load_manifest(base: &Path) -> Option<KeyManifest> {
+  let json = fs::read(paths::trust_manifest_path(base)).ok()?;
+  let sig = fs::read(paths::trust_manifest_sig_path(base)).ok()?;
+  match verify_manifest(&json, &sig) {
+    Ok(m) if m.sequence >= load_state(base).sequence => Some(m),
+    Ok(m) => {
+      tracing::warn!(
+        sequence = m.sequence,
+        "installed key manifest is older than one seen before; ignored"
+      );
+      None
+    }
+    Err(e) => {
+      tracing::warn!(error = %format!("{e:#}"), "installed key manifest ignored");
+      None
+    }
+  }
+}
+
+/// Sequence of the newest manifest installed so far (0 if none).
+pub fn installed_sequence(base: &Path) -> u64 {
+  load_state(base).sequence
+}
+
+fn verify_manifest(json: &[u8], sig_raw: &[u8]) -> anyhow::Result<KeyManifest> {
+  if json.len() > MAX_MANIFEST_BYTES {
+    anyhow::bail!("key manifest too large");
+  }
+  let sig = decode_signature(sig_raw)?;
+  let verified = MANIFEST_ROOT_KEYS
+    .iter()
+    .filter_map(|t| parse_public_key(t).ok())
+    .any(|k| k.verify_strict(json, &sig).is_ok());
+  if !verified {
+    anyhow::bail!("key manifest signature verification failed");
+  }
+
+  let manifest: KeyManifest = serde_json::from_slice(json).context("parse key manifest")?;
+  if manifest.version != 1 {
+    anyhow::bail!(
+      "unsupported key manifest version {}; expected 1",
+      manifest.version
+    );
+  }
+  for k in &manifest.keys {
+    if k.key_id.trim().is_empty() {
+      anyhow::bail!("key manifest entry without key_id");
+    }
+    parse_public_key(&k.public_key).with_context(|| format!("key manifest entry {}", k.key_id))?;
+  }
+  Ok(manifest)
+}
+
+fn load_state(base: &Path) -> TrustState {
+  fs::read_to_string(paths::trust_state_path(base))
+    .ok()
+    .and_then(|raw| toml::from_str(&raw).ok())
+    .unwrap_or_default()
+}
+
+fn now_unix_s() -> u64 {
+  std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+  use base64::Engine;
+  use ed25519_dalek::{Signer as _, SigningKey};
+
+  fn manifest_key(id: &str, key: &SigningKey, window: (Option<u64>, Option<u64>)) -> ManifestKey {
+    ManifestKey {
+      key_id: id.to_string(),
+      purpose: Purpose::ThreatFeed,
+      public_key: URL_SAFE_NO_PAD.encode(key.verifying_key().as_bytes()),
+      not_before: window.0,
+      not_after: window.1,
+    }
+  }
+
+  #[test]
+  fn manifest_keys_verify_within_their_window_until_revoked() {
+    let now = 1_800_000_000;
+    let current = SigningKey::from_bytes(&[1u8; 32]);
+    let retired = SigningKey::from_bytes(&[2u8; 32]);
+    let future = SigningKey::from_bytes(&[3u8; 32]);
+    let mut manifest = KeyManifest {
+      version: 1,
+      sequence: 2,
+      issued_at: now,
+      keys: vec![
+        manifest_key("current", &current, (Some(now - 10), Some(now + 10))),
+        manifest_key("retired", &retired, (None, Some(now - 1))),
+        manifest_key("future", &future, (Some(now + 1), None)),
+      ],
+      revoked: Vec::new(),
+    };
+    let msg = br#"{"version":1}"#;
+
+    let set = TrustSet::build(Purpose::ThreatFeed, Some(&manifest), &[], now);
+    assert_eq!(
+      set.verify(msg, &current.sign(msg)).unwrap().key_id,
+      "current"
+    );
+    let err = set.verify(msg, &retired.sign(msg)).unwrap_err();
+    assert!(err.to_string().contains("expired"), "{err}");
+    assert!(set.verify(msg, &future.sign(msg)).is_err());
+
+    // Keys are scoped to their purpose.
+    let license = TrustSet::build(Purpose::License, Some(&manifest), &[], now);
+    assert!(license.verify(msg, &current.sign(msg)).is_err());
+
+    // Revoked in the manifest, or remembered from an earlier one.
+    let fp = fingerprint(&current.verifying_key());
+    manifest.revoked.push(fp.clone());
+    let set = TrustSet::build(Purpose::ThreatFeed, Some(&manifest), &[], now);
+    let err = set.verify(msg, &current.sign(msg)).unwrap_err();
+    assert!(err.to_string().contains("revoked"), "{err}");
+    manifest.revoked.clear();
+    let set = TrustSet::build(Purpose::ThreatFeed, Some(&manifest), &[fp], now);
+    assert!(set.verify(msg, &current.sign(msg)).is_err());
+  }
+
+  #[test]
+  fn manifests_need_a_root_signature() {
+    let json = br#"{"version":1,"sequence":1,"issued_at":1}"#;
+    let not_root = SigningKey::from_bytes(&[9u8; 32]);
+    assert!(verify_manifest(json, &not_root.sign(json).to_bytes()).is_err());
+    assert!(verify_manifest(json, b"short").is_err());
+  }
+}
@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -10,6 +12,10 @@ pub enum Severity {
 
 pub type RuleId = String;
 
+/// `pid` of file events from a source that can't tell which process made the change (the USN
+/// journal).
+pub const UNKNOWN_PID: u32 = 0;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Event {
   ProcessStart {
@@ -17,6 +23,25 @@ pub enum Event {
     ppid: u32,
     image_path: String,
     signer_publisher: Option<String>,
+    #[serde(default)]
+    command_line: Option<String>,
+    /// Set by the collector for unsigned images that carry a Mark of the Web.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    download_origin: Option<DownloadOrigin>,
+    /// Sysmon `ProcessGuid`: unlike the PID, never reused. Not set by other sources.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    process_guid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_process_guid: Option<String>,
+    timestamp_unix_ms: u64,
+  },
+  /// Sysmon ProcessTerminate. `image_path` tells an exit of the recorded process from one of an
+  /// earlier process with the same PID.
+  ProcessExit {
+    pid: u32,
+    image_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    process_guid: Option<String>,
     timestamp_unix_ms: u64,
   },
   FileAccess {
@@ -24,17 +49,129 @@ pub enum Event {
     image_path: Option<String>,
     file_path: String,
     access: FileAccessType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    process_guid: Option<String>,
     timestamp_unix_ms: u64,
   },
   NetConnect {
     pid: u32,
     image_path: Option<String>,
-    dest_ip: String,
+    dest_ip: IpAddr,
     dest_port: u16,
     dest_host: Option<String>,
     protocol: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    process_guid: Option<String>,
+    timestamp_unix_ms: u64,
+  },
+  RegistrySet {
+    pid: u32,
+    image_path: Option<String>,
+    key_path: String,
+    value: Option<String>,
+    timestamp_unix_ms: u64,
+  },
+  DnsQuery {
+    pid: u32,
+    image_path: Option<String>,
+    query_name: String,
+    query_results: Option<String>,
+    timestamp_unix_ms: u64,
+  },
+  /// `pid` opened a handle to `target_pid` with `granted_access` (Sysmon ProcessAccess).
+  ProcessAccess {
+    pid: u32,
+    image_path: Option<String>,
+    target_pid: u32,
+    target_image: String,
+    granted_access: u32,
+    timestamp_unix_ms: u64,
+  },
+  /// PowerShell script block logging (4104): `text` is one part of the block `script_block_id`.
+  ScriptBlock {
+    pid: u32,
+    script_block_id: String,
+    part: u32,
+    parts: u32,
+    text: String,
+    /// Script file the block came from, if any.
+    path: Option<String>,
     timestamp_unix_ms: u64,
   },
+  /// The clipboard content changed. `pid` owns the new content (0 if no window owns it);
+  /// `address` is only set when the content is a crypto address, other content is never kept.
+  ClipboardChange {
+    pid: u32,
+    image_path: Option<String>,
+    address: Option<String>,
+    timestamp_unix_ms: u64,
+  },
+}
+
+impl Event {
+  pub fn timestamp_unix_ms(&self) -> u64 {
+    match self {
+      Event::ProcessStart {
+        timestamp_unix_ms, ..
+      }
+      | Event::ProcessExit {
+        timestamp_unix_ms, ..
+      }
+      | Event::FileAccess {
+        timestamp_unix_ms, ..
+      }
+      | Event::NetConnect {
+        timestamp_unix_ms, ..
+      }
+      | Event::RegistrySet {
+        timestamp_unix_ms, ..
+      }
+      | Event::DnsQuery {
+        timestamp_unix_ms, ..
+      }
+      | Event::ProcessAccess {
+        timestamp_unix_ms, ..
+      }
+      | Event::ScriptBlock {
+        timestamp_unix_ms, ..
+      }
+      | Event::ClipboardChange {
+        timestamp_unix_ms, ..
+      } => *timestamp_unix_ms,
+    }
+  }
+
+  pub fn timestamp_unix_ms_mut(&mut self) -> &mut u64 {
+    match self {
+      Event::ProcessStart {
+        timestamp_unix_ms, ..
+      }
+      | Event::ProcessExit {
+        timestamp_unix_ms, ..
+      }
+      | Event::FileAccess {
+        timestamp_unix_ms, ..
+      }
+      | Event::NetConnect {
+        timestamp_unix_ms, ..
+      }
+      | Event::RegistrySet {
+        timestamp_unix_ms, ..
+      }
+      | Event::DnsQuery {
+        timestamp_unix_ms, ..
+      }
+      | Event::ProcessAccess {
+        timestamp_unix_ms, ..
+      }
+      | Event::ScriptBlock {
+        timestamp_unix_ms, ..
+      }
+      | Event::ClipboardChange {
+        timestamp_unix_ms, ..
+      } => timestamp_unix_ms,
+    }
+  }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -53,6 +190,8 @@ pub enum Evidence {
     ppid: u32,
     image_path: String,
     signer_publisher: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    download_origin: Option<DownloadOrigin>,
   },
   File {
     pid: u32,
@@ -63,16 +202,69 @@ pub enum Evidence {
   Network {
     pid: u32,
     image_path: Option<String>,
-    dest_ip: String,
+    dest_ip: IpAddr,
     dest_port: u16,
     dest_host: Option<String>,
     protocol: String,
   },
+  Dns {
+    pid: u32,
+    image_path: Option<String>,
+    query_name: String,
+    query_results: Option<String>,
+  },
+  CommandLine {
+    pid: u32,
+    image_path: String,
+    command_line: String,
+  },
+  Registry {
+    pid: u32,
+    image_path: Option<String>,
+    key_path: String,
+    value: Option<String>,
+  },
+  /// A watched system setting changed; `before` is the last known value (if any).
+  SettingChange {
+    pid: u32,
+    image_path: Option<String>,
+    setting: String,
+    target: String,
+    before: Option<String>,
+    after: Option<String>,
+  },
+  ProcessAccess {
+    pid: u32,
+    image_path: Option<String>,
+    target_pid: u32,
+    target_image: String,
+    /// Hex access mask followed by the suspicious rights it grants.
+    granted_access: String,
+  },
+  /// A PowerShell script block (or command line) matched `marker`; only `excerpt`, the text around
+  /// the match, is kept.
+  Script {
+    pid: u32,
+    image_path: Option<String>,
+    script_block_id: Option<String>,
+    /// Script file the block came from, if any.
+    path: Option<String>,
+    marker: String,
+    excerpt: String,
+  },
+  /// A crypto address on the clipboard was replaced; both addresses are redacted to prefixes.
+  Clipboard {
+    pid: u32,
+    image_path: Option<String>,
+    previous_pid: u32,
+    previous: String,
+    current: String,
+  },
   Correlation {
     pid: u32,
     window_seconds: u64,
     sensitive_file: String,
-    dest_ip: String,
+    dest_ip: IpAddr,
     dest_host: Option<String>,
     delta_seconds: u64,
   },
@@ -97,6 +289,106 @@ pub struct Incident {
   pub findings: Vec<Finding>,
   pub actions_taken: Vec<String>,
   pub created_at_unix_ms: u64,
+  /// Set once someone reviewed the incident (`--incidents resolve`).
+  #[serde(default)]
+  pub resolution: Option<Resolution>,
+  /// Set once flood control folded later findings into this incident (`[response.dedup]`).
+  #[serde(default)]
+  pub flood: Option<FloodCounters>,
+  /// Risk score, set when the incident is handled (see `scoring`).
+  #[serde(default)]
+  pub score: Option<RiskScore>,
+  /// Remediation guidance per rule in the findings, set when the incident is stored (see
+  /// `guidance`).
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub guidance: BTreeMap<RuleId, Guidance>,
+}
+
+/// Where a file came from, per its Mark of the Web (see `motw`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadOrigin {
+  /// URL security zone: 0 local machine, 1 intranet, 2 trusted sites, 3 Internet, 4 restricted.
+  pub zone_id: u32,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub host_url: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub referrer_url: Option<String>,
+}
+
+impl DownloadOrigin {
+  /// Downloaded from the Internet (or a restricted site).
+  pub fn is_internet(&self) -> bool {
+    self.zone_id >= 3
+  }
+
+  /// E.g. `zone=3 (Internet) host_url=https://... referrer_url=https://...`.
+  pub fn label(&self) -> String {
+    let zone = match self.zone_id {
+      0 => "local machine",
+      1 => "intranet",
+      2 => "trusted sites",
+      3 => "Internet",
+      4 => "restricted sites",
+      _ => "unknown",
+    };
+    let mut label = format!("zone={} ({zone})", self.zone_id);
+    if let Some(url) = &self.host_url {
+      label.push_str(&format!(" host_url={url}"));
+    }
+    if let Some(url) = &self.referrer_url {
+      label.push_str(&format!(" referrer_url={url}"));
+    }
+    label
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Guidance {
+  /// What the finding means.
+  pub meaning: String,
+  /// What commonly causes it on a clean machine.
+  pub false_positives: String,
+  /// What to do about it.
+  pub action: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RiskScore {
+  /// 0-100: the highest finding score plus a bonus per additional rule.
+  pub total: u32,
+  pub findings: Vec<FindingScore>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FindingScore {
+  pub rule_id: RuleId,
+  pub score: u32,
+  /// What made up the score, e.g. `base 70 (red)`, `unsigned +10`.
+  pub factors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FloodCounters {
+  /// Findings repeating one already in this incident (same PID, rule and target).
+  pub duplicate_findings: u64,
+  /// Incidents from the same PID that were over the rate limit and merged into this one.
+  pub rate_limited_incidents: u64,
+  pub last_merged_at_unix_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+  FalsePositive,
+  Confirmed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Resolution {
+  pub verdict: Verdict,
+  pub resolved_at_unix_ms: u64,
+  #[serde(default)]
+  pub note: Option<String>,
 }
 
 impl Incident {
@@ -108,6 +400,10 @@ impl Incident {
       findings,
       actions_taken: Vec::new(),
       created_at_unix_ms: now_unix_ms(),
+      resolution: None,
+      flood: None,
+      score: None,
+      guidance: BTreeMap::new(),
     }
   }
 
@@ -143,3 +439,27 @@ pub fn now_unix_ms() -> u64 {
     .unwrap_or_default()
     .as_millis() as u64
 }
+
+/// `YYYY-MM-DDTHH:MM:SSZ`, the form the task scheduler and the log files use.
+pub fn utc_timestamp(unix_ms: u64) -> String {
+  let secs = unix_ms / 1000;
+  let (days, rem) = (secs / 86_400, secs % 86_400);
+
+  // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+  let z = days as i64 + 719_468;
+  let era = z.div_euclid(146_097);
+  let doe = z.rem_euclid(146_097);
+  let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + i64::from(month <= 2);
+
+  format!(
+    "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+    rem / 3_600,
+    rem % 3_600 / 60,
+    rem % 60
+  )
+}
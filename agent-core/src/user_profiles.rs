@@ -0,0 +1,158 @@
+use crate::types::now_unix_ms;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+// User profiles whose browser data and credential stores are protected. The agent runs as a
+// service under LocalSystem, so its own `%LOCALAPPDATA%`/`%APPDATA%` point into
+// `systemprofile`, not at anyone's browser. Instead, every profile directory under the Users
+// folder (`%SystemDrive%\Users`) is treated as a user with the default `AppData\Local` and
+// `AppData\Roaming` layout. The process's own profile (from its environment) is included too, so
+// console runs as a user and folder redirection for that user still resolve.
+//
+// The list is cached and re-read at most once a minute, so a user signing in for the first time
+// is picked up while the agent runs.
+
+const RECHECK_INTERVAL_MS: u64 = 60_000;
+
+/// Folders under `Users` that aren't real user profiles.
+const NOT_USERS: &[&str] = &[
+  "public",
+  "default",
+  "default user",
+  "all users",
+  "defaultuser0",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserProfile {
+  /// The profile folder name (usually the account name).
+  pub name: String,
+  pub home: PathBuf,
+  pub local_appdata: PathBuf,
+  pub appdata: PathBuf,
+}
+
+impl UserProfile {
+  fn from_home(home: PathBuf) -> Self {
+    Self {
+      name: folder_name(&home),
+      local_appdata: home.join("AppData\\Local"),
+      appdata: home.join("AppData\\Roaming"),
+      home,
+    }
+  }
+
+  /// The profile of this process, from its environment (with the usual fallbacks).
+  pub fn current_process() -> Self {
+    let var = |name: &str, fallback: &str| {
+      PathBuf::from(std::env::var(name).unwrap_or_else(|_| fallback.to_string()))
+    };
+    let home = var("USERPROFILE", "C:\\Users\\User");
+    Self {
+      name: folder_name(&home),
+      local_appdata: var("LOCALAPPDATA", "C:\\Users\\User\\AppData\\Local"),
+      appdata: var("APPDATA", "C:\\Users\\User\\AppData\\Roaming"),
+      home,
+    }
+  }
+}
+
+struct Cache {
+  profiles: Arc<Vec<UserProfile>>,
+  checked_at_unix_ms: u64,
+}
+
+static CACHE: RwLock<Option<Cache>> = RwLock::new(None);
+
+/// Every user profile on the machine (cached).
+pub fn all() -> Arc<Vec<UserProfile>> {
+  let now = now_unix_ms();
+  {
+    let cache = CACHE.read().unwrap_or_else(|e| e.into_inner());
+    if let Some(c) = cache.as_ref() {
+      if now.saturating_sub(c.checked_at_unix_ms) < RECHECK_INTERVAL_MS {
+        return c.profiles.clone();
+      }
+    }
+  }
+
+  let profiles = Arc::new(enumerate());
+  let mut cache = CACHE.write().unwrap_or_else(|e| e.into_inner());
+  if let Some(c) = cache.as_ref() {
+    if c.profiles != profiles {
+      tracing::info!(count = profiles.len(), "user profiles changed");
+    }
+  }
+  *cache = Some(Cache {
+    profiles: profiles.clone(),
+    checked_at_unix_ms: now,
+  });
+  profiles
+}
+
+/// User profiles, freshly enumerated.
+pub fn enumerate() -> Vec<UserProfile> {
+  let mut out = vec![UserProfile::current_process()];
+  for profile in enumerate_in(&users_dir()) {
+    let key = profile.home.to_string_lossy().to_ascii_lowercase();
+    if !out
+      .iter()
+      .any(|p| p.home.to_string_lossy().to_ascii_lowercase() == key)
+    {
+      out.push(profile);
+    }
+  }
+  out
+}
+
+/// Profile directories under `users_dir` that look like real users (they have `AppData`).
+pub fn enumerate_in(users_dir: &Path) -> Vec<UserProfile> {
+  let Ok(entries) = fs::read_dir(users_dir) else {
+    return Vec::new();
+  };
+  let mut homes: Vec<PathBuf> = entries
+    .flatten()
+    .map(|e| e.path())
+    .filter(|p| {
+      let name = folder_name(p).to_ascii_lowercase();
+      !NOT_USERS.contains(&name.as_str()) && p.join("AppData").is_dir()
+    })
+    .collect();
+  homes.sort();
+  homes.into_iter().map(UserProfile::from_home).collect()
+}
+
+fn users_dir() -> PathBuf {
+  let drive = std::env::var("SystemDrive").unwrap_or_else(|_| "C:".to_string());
+  PathBuf::from(format!("{drive}\\Users"))
+}
+
+fn folder_name(path: &Path) -> String {
+  let s = path.to_string_lossy();
+  s.trim_end_matches(['\\', '/'])
+    .rsplit(['\\', '/'])
+    .next()
+    .unwrap_or_default()
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn enumerates_real_user_folders_only() {
+    let users = std::env::temp_dir().join(format!("aid-users-{}", uuid::Uuid::new_v4()));
+    for name in ["alice", "bob", "Public", "Default"] {
+      fs::create_dir_all(users.join(name).join("AppData")).unwrap();
+    }
+    // Not a profile: no AppData.
+    fs::create_dir_all(users.join("stray")).unwrap();
+
+    let names: Vec<String> = enumerate_in(&users).into_iter().map(|p| p.name).collect();
+    assert_eq!(names, vec!["alice".to_string(), "bob".to_string()]);
+
+    let _ = fs::remove_dir_all(&users);
+  }
+}
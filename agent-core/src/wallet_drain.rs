@@ -0,0 +1,127 @@
+use crate::threat_feed::schema::{DrainPatternTarget, WalletDrainPattern};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+// Matching of the threat feed's `wallet_drain_patterns`, shared by the rules engine (R023) and the
+// scanner (S010). A pattern matches when every one of its `all_of` strings appears
+// (case-insensitive) in a script's content or in a command line, depending on its `target`.
+// Patterns with no strings never match.
+
+/// Scripts larger than this are only matched on their first bytes; drainer kits are small.
+pub const MAX_SCRIPT_BYTES: u64 = 2 * 1024 * 1024;
+
+const SCRIPT_EXTENSIONS: &[&str] = &["js", "mjs", "cjs", "html", "htm"];
+
+pub fn is_script_path(path: &str) -> bool {
+  Path::new(path)
+    .extension()
+    .and_then(|e| e.to_str())
+    .map(|e| SCRIPT_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+    .unwrap_or(false)
+}
+
+/// The first `Script` pattern found in the file at `path`.
+pub fn match_script<'a>(
+  path: &Path,
+  patterns: &'a [WalletDrainPattern],
+) -> Option<&'a WalletDrainPattern> {
+  if !patterns
+    .iter()
+    .any(|p| p.target == DrainPatternTarget::Script)
+  {
+    return None;
+  }
+  let mut bytes = Vec::new();
+  fs::File::open(path)
+    .ok()?
+    .take(MAX_SCRIPT_BYTES)
+    .read_to_end(&mut bytes)
+    .ok()?;
+  let content = String::from_utf8_lossy(&bytes).to_lowercase();
+  find(&content, DrainPatternTarget::Script, patterns)
+}
+
+/// The first `CommandLine` pattern found in `command_line`.
+pub fn match_command_line<'a>(
+  command_line: &str,
+  patterns: &'a [WalletDrainPattern],
+) -> Option<&'a WalletDrainPattern> {
+  find(
+    &command_line.to_lowercase(),
+    DrainPatternTarget::CommandLine,
+    patterns,
+  )
+}
+
+fn find<'a>(
+  haystack_lower: &str,
+  target: DrainPatternTarget,
+  patterns: &'a [WalletDrainPattern],
+) -> Option<&'a WalletDrainPattern> {
+  patterns.iter().find(|p| {
+    p.target == target
+      && !p.all_of.is_empty()
+      && p
+        .all_of
+        .iter()
+        .all(|needle| haystack_lower.contains(&needle.to_lowercase()))
+  })
+}
+
+/// `<id>` or `<id> (<description>)`, for finding descriptions.
+pub fn label(pattern: &WalletDrainPattern) -> String {
+  match pattern.description.as_deref().filter(|d| !d.is_empty()) {
+    Some(d) => format!("{} ({d})", pattern.id),
+    None => pattern.id.clone(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn pattern(id: &str, target: DrainPatternTarget, all_of: &[&str]) -> WalletDrainPattern {
+    WalletDrainPattern {
+      id: id.to_string(),
+      target,
+      all_of: all_of.iter().map(|s| s.to_string()).collect(),
+      description: None,
+    }
+  }
+
+  #[test]
+  fn every_string_of_a_pattern_must_appear() {
+    let patterns = vec![
+      pattern(
+        "approval-drain",
+        DrainPatternTarget::Script,
+        &["setApprovalForAll", "ethereum.request"],
+      ),
+      pattern(
+        "clipper",
+        DrainPatternTarget::CommandLine,
+        &["set-clipboard", "0x"],
+      ),
+      pattern("empty", DrainPatternTarget::CommandLine, &[]),
+    ];
+
+    let dir = std::env::temp_dir().join(format!("aid-drain-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let js = dir.join("content.js");
+    fs::write(
+      &js,
+      "await window.ETHEREUM.request({}); c.SetApprovalForAll(x, true);",
+    )
+    .unwrap();
+    assert_eq!(match_script(&js, &patterns).unwrap().id, "approval-drain");
+    fs::write(&js, "c.setApprovalForAll(x, true);").unwrap();
+    assert!(match_script(&js, &patterns).is_none());
+
+    let cmd = "powershell -c Set-Clipboard -Value 0xdeadbeef";
+    assert_eq!(match_command_line(cmd, &patterns).unwrap().id, "clipper");
+    assert!(match_command_line("notepad.exe", &patterns).is_none());
+
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
@@ -0,0 +1,419 @@
+use crate::config::WatchConfig;
+use crate::paths;
+use crate::runtime;
+use crate::types::{now_unix_ms, Event};
+use defender_common::fs::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Watchlist for investigating a suspicious-but-not-convicted application. `--watch add` writes
+// `watch\watchlist.toml`; the agent picks up changes on its next batch and appends every event
+// touching a watched target to `watch\events.ndjson`, unredacted and independent of rule
+// outcomes. Entries expire on their own; the log rotates once like the event recording.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchKind {
+  /// A file or folder; matches process images, file accesses and command lines under it.
+  Path,
+  /// A process image name such as `tool.exe`; matches its events and the processes it starts.
+  Process,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchEntry {
+  pub target: String,
+  pub kind: WatchKind,
+  pub added_at_unix_ms: u64,
+  pub expires_at_unix_ms: u64,
+}
+
+impl WatchEntry {
+  fn is_active(&self, now: u64) -> bool {
+    now < self.expires_at_unix_ms
+  }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct WatchlistFile {
+  #[serde(default)]
+  entries: Vec<WatchEntry>,
+}
+
+/// Targets with a path separator or drive letter are paths; anything else is a process name.
+pub fn kind_of(target: &str) -> WatchKind {
+  if target.contains(['\\', '/', ':']) {
+    WatchKind::Path
+  } else {
+    WatchKind::Process
+  }
+}
+
+/// Adds `target` for `minutes`, or extends it if it is already watched.
+pub fn add(base: &Path, target: &str, minutes: u64) -> anyhow::Result<WatchEntry> {
+  let target = target.trim();
+  if target.is_empty() {
+    anyhow::bail!("watch target must not be empty");
+  }
+  if minutes == 0 {
+    anyhow::bail!("watch duration must be at least one minute");
+  }
+
+  let now = now_unix_ms();
+  let entry = WatchEntry {
+    target: target.to_string(),
+    kind: kind_of(target),
+    added_at_unix_ms: now,
+    expires_at_unix_ms: now.saturating_add(minutes.saturating_mul(60_000)),
+  };
+
+  if runtime::is_dry_run() {
+    tracing::warn!(target = %target, minutes, "DRY-RUN: would add watch target");
+    return Ok(entry);
+  }
+
+  let mut file = load(base);
+  file
+    .entries
+    .retain(|e| e.is_active(now) && !e.target.eq_ignore_ascii_case(target));
+  file.entries.push(entry.clone());
+  save(base, &file)?;
+  tracing::info!(target = %target, kind = ?entry.kind, minutes, "watch target added");
+  Ok(entry)
+}
+
+pub fn remove(base: &Path, target: &str) -> anyhow::Result<bool> {
+  let mut file = load(base);
+  let before = file.entries.len();
+  file
+    .entries
+    .retain(|e| !e.target.eq_ignore_ascii_case(target.trim()));
+  if file.entries.len() == before {
+    return Ok(false);
+  }
+
+  if runtime::is_dry_run() {
+    tracing::warn!(target = %target, "DRY-RUN: would remove watch target");
+    return Ok(true);
+  }
+
+  save(base, &file)?;
+  tracing::info!(target = %target, "watch target removed");
+  Ok(true)
+}
+
+/// Unexpired entries, soonest expiry first.
+pub fn list(base: &Path) -> Vec<WatchEntry> {
+  let now = now_unix_ms();
+  let mut entries: Vec<WatchEntry> = load(base)
+    .entries
+    .into_iter()
+    .filter(|e| e.is_active(now))
+    .collect();
+  entries.sort_by_key(|e| e.expires_at_unix_ms);
+  entries
+}
+
+fn load(base: &Path) -> WatchlistFile {
+  fs::read_to_string(paths::watchlist_path(base))
+    .ok()
+    .and_then(|raw| toml::from_str(&raw).ok())
+    .unwrap_or_default()
+}
+
+fn save(base: &Path, file: &WatchlistFile) -> anyhow::Result<()> {
+  write_atomic(&paths::watchlist_path(base), &toml::to_string_pretty(file)?)
+}
+
+#[derive(Serialize)]
+struct WatchRecord<'a> {
+  watched: &'a str,
+  logged_at_unix_ms: u64,
+  event: &'a Event,
+}
+
+pub struct Watcher {
+  base: PathBuf,
+  entries: Vec<WatchEntry>,
+  loaded_modified: Option<SystemTime>,
+  /// PIDs of running processes matched by a process target, so their events still match when the
+  /// collector has no image path for them.
+  pids: HashMap<u32, usize>,
+}
+
+impl Watcher {
+  pub fn new(base: &Path) -> Self {
+    Self {
+      base: base.to_path_buf(),
+      entries: Vec::new(),
+      loaded_modified: None,
+      pids: HashMap::new(),
+    }
+  }
+
+  pub fn observe(&mut self, cfg: &WatchConfig, events: &[Event]) {
+    self.reload_if_changed();
+    let now = now_unix_ms();
+    if !self.entries.iter().any(|e| e.is_active(now)) || runtime::is_dry_run() {
+      return;
+    }
+
+    let mut lines = String::new();
+    for ev in events {
+      let Some(i) = self.matching_entry(ev, now) else {
+        continue;
+      };
+      let record = WatchRecord {
+        watched: &self.entries[i].target,
+        logged_at_unix_ms: now,
+        event: ev,
+      };
+      match serde_json::to_string(&record) {
+        Ok(line) => {
+          lines.push_str(&line);
+          lines.push('\n');
+        }
+        Err(e) => tracing::warn!(error = ?e, "failed to serialize watched event"),
+      }
+    }
+    if lines.is_empty() {
+      return;
+    }
+    if let Err(e) = append(&self.base, cfg, &lines) {
+      tracing::warn!(error = ?e, "failed to write watch log (continuing)");
+    }
+  }
+
+  fn reload_if_changed(&mut self) {
+    let modified = fs::metadata(paths::watchlist_path(&self.base))
+      .and_then(|m| m.modified())
+      .ok();
+    if modified == self.loaded_modified {
+      return;
+    }
+    self.loaded_modified = modified;
+    self.entries = load(&self.base).entries;
+    self.pids.clear();
+    tracing::info!(targets = self.entries.len(), "watchlist loaded");
+  }
+
+  fn matching_entry(&mut self, ev: &Event, now: u64) -> Option<usize> {
+    let (pid, image) = pid_and_image(ev);
+
+    if let Event::ProcessStart { ppid, .. } = ev {
+      let own = self.position(now, |e| image.is_some_and(|img| e.matches_image(img)));
+      match own {
+        Some(i) => {
+          self.pids.insert(pid, i);
+          return Some(i);
+        }
+        // PID reuse: whatever we tracked under this pid is gone.
+        None => {
+          self.pids.remove(&pid);
+        }
+      }
+      if let Some(&i) = self.pids.get(ppid) {
+        return Some(i);
+      }
+    }
+
+    if let Some(i) = self.position(now, |e| e.matches_event(ev)) {
+      return Some(i);
+    }
+    self
+      .pids
+      .get(&pid)
+      .copied()
+      .filter(|&i| self.entries.get(i).is_some_and(|e| e.is_active(now)))
+  }
+
+  fn position(&self, now: u64, pred: impl Fn(&WatchEntry) -> bool) -> Option<usize> {
+    self
+      .entries
+      .iter()
+      .position(|e| e.is_active(now) && pred(e))
+  }
+}
+
+impl WatchEntry {
+  fn matches_image(&self, image: &str) -> bool {
+    match self.kind {
+      WatchKind::Process => {
+        let name = image
+          .rsplit_once(['\\', '/'])
+          .map_or(image, |(_, n)| n)
+          .to_ascii_lowercase();
+        let target = self.target.to_ascii_lowercase();
+        name == target || name.strip_suffix(".exe") == Some(target.as_str())
+      }
+      WatchKind::Path => under_path(image, &self.target),
+    }
+  }
+
+  fn matches_event(&self, ev: &Event) -> bool {
+    let (_, image) = pid_and_image(ev);
+    if image.is_some_and(|img| self.matches_image(img)) {
+      return true;
+    }
+    if self.kind != WatchKind::Path {
+      return false;
+    }
+    match ev {
+      Event::FileAccess { file_path, .. } => under_path(file_path, &self.target),
+      Event::ProcessStart {
+        command_line: Some(cmd),
+        ..
+      } => normalize(cmd).contains(&normalize(&self.target)),
+      _ => false,
+    }
+  }
+}
+
+fn pid_and_image(ev: &Event) -> (u32, Option<&str>) {
+  match ev {
+    Event::ProcessStart {
+      pid, image_path, ..
+    } => (*pid, Some(image_path.as_str())),
+    Event::ProcessExit {
+      pid, image_path, ..
+    }
+    | Event::FileAccess {
+      pid, image_path, ..
+    }
+    | Event::NetConnect {
+      pid, image_path, ..
+    }
+    | Event::RegistrySet {
+      pid, image_path, ..
+    }
+    | Event::DnsQuery {
+      pid, image_path, ..
+    }
+    | Event::ProcessAccess {
+      pid, image_path, ..
+    }
+    | Event::ClipboardChange {
+      pid, image_path, ..
+    } => (*pid, image_path.as_deref()),
+    Event::ScriptBlock { pid, .. } => (*pid, None),
+  }
+}
+
+fn normalize(path: &str) -> String {
+  path.replace('/', "\\").to_ascii_lowercase()
+}
+
+/// `path` is `prefix` itself or something inside it.
+fn under_path(path: &str, prefix: &str) -> bool {
+  let path = normalize(path);
+  let prefix = normalize(prefix);
+  let prefix = prefix.trim_end_matches('\\');
+  path
+    .strip_prefix(prefix)
+    .is_some_and(|rest| rest.is_empty() || rest.starts_with('\\'))
+}
+
+fn append(base: &Path, cfg: &WatchConfig, lines: &str) -> anyhow::Result<()> {
+  let path = paths::watch_log_path(base);
+  fs::create_dir_all(paths::watch_dir(base))?;
+
+  let file_budget = cfg.max_mb.saturating_mul(1024 * 1024) / 2;
+  let current = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+  if current > 0 && current.saturating_add(lines.len() as u64) > file_budget {
+    fs::rename(&path, path.with_extension("1.ndjson"))?;
+  }
+
+  let mut f = OpenOptions::new().create(true).append(true).open(&path)?;
+  f.write_all(lines.as_bytes())?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::types::FileAccessType;
+
+  fn start(pid: u32, ppid: u32, image: &str) -> Event {
+    Event::ProcessStart {
+      pid,
+      ppid,
+      image_path: image.to_string(),
+      signer_publisher: None,
+      command_line: None,
+      download_origin: None,
+      process_guid: None,
+      parent_process_guid: None,
+      timestamp_unix_ms: 1,
+    }
+  }
+
+  fn file(pid: u32, image: Option<&str>, path: &str) -> Event {
+    Event::FileAccess {
+      pid,
+      image_path: image.map(str::to_string),
+      file_path: path.to_string(),
+      access: FileAccessType::Write,
+      process_guid: None,
+      timestamp_unix_ms: 2,
+    }
+  }
+
+  #[test]
+  fn logs_events_touching_watched_targets() {
+    let base = std::env::temp_dir().join(format!("aid-watch-{}", uuid::Uuid::new_v4()));
+    add(&base, "Tool", 60).unwrap();
+    add(&base, "C:\\Users\\alice\\Downloads\\", 60).unwrap();
+    assert_eq!(list(&base).len(), 2);
+
+    let mut watcher = Watcher::new(&base);
+    watcher.observe(
+      &WatchConfig::default(),
+      &[
+        // Watched process, then an event from its pid without an image path.
+        start(10, 1, "C:\\Program Files\\Tool\\tool.exe"),
+        file(10, None, "C:\\ProgramData\\x.dat"),
+        // Child of the watched process.
+        start(11, 10, "C:\\Windows\\System32\\cmd.exe"),
+        // Under the watched folder, but not a sibling with the same prefix.
+        file(
+          20,
+          Some("C:\\Windows\\explorer.exe"),
+          "C:\\Users\\alice\\Downloads\\a.zip",
+        ),
+        file(
+          20,
+          Some("C:\\Windows\\explorer.exe"),
+          "C:\\Users\\alice\\Downloads2\\b.zip",
+        ),
+        // Pid 10 reused by something unrelated.
+        start(10, 1, "C:\\Windows\\notepad.exe"),
+        file(10, None, "C:\\ProgramData\\y.dat"),
+      ],
+    );
+
+    let raw = fs::read_to_string(paths::watch_log_path(&base)).unwrap();
+    let watched: Vec<String> = raw
+      .lines()
+      .map(|l| {
+        let v: serde_json::Value = serde_json::from_str(l).unwrap();
+        v["watched"].as_str().unwrap().to_string()
+      })
+      .collect();
+    assert_eq!(
+      watched,
+      ["Tool", "Tool", "Tool", "C:\\Users\\alice\\Downloads\\"]
+    );
+    // Logged in full, user names included.
+    assert!(raw.contains("alice"));
+
+    assert!(remove(&base, "tool").unwrap());
+    assert!(!remove(&base, "tool").unwrap());
+    assert_eq!(list(&base).len(), 1);
+
+    let _ = fs::remove_dir_all(&base);
+  }
+}
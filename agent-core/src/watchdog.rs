@@ -0,0 +1,229 @@
+use crate::config::Config;
+use crate::lifecycle;
+use crate::response_engine;
+use crate::runtime;
+use crate::self_protection::T004_AGENT_LOOP_FAILED;
+use crate::types::{now_unix_ms, Evidence, Finding, Incident, Severity};
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::{Arc, Once};
+use std::time::{Duration, Instant};
+
+// Supervises the agent loop (`[service] watchdog`). The loop runs on its own thread and stamps a
+// `Liveness` every tick. When it returns an error or panics, the watchdog records a T004 incident
+// and starts it again in-process with backoff. A thread can't be killed, so a loop that stops
+// ticking (deadlock, stuck collector) or keeps failing is escalated instead: the process exits
+// with an error and the service recovery actions (`--service install`) restart it. In the split
+// service the core restarts an exited worker itself.
+
+/// Exit code when the watchdog gives up on the process.
+const EXIT_CODE: i32 = 3;
+
+const RESTART_MAX_BACKOFF_MS: u64 = 60_000;
+/// A loop that ran this long before failing resets the restart count.
+const STABLE_MS: u64 = 10 * 60_000;
+/// Failures in a row, without a stable run in between, before the process exits.
+const MAX_RESTARTS: u32 = 5;
+/// How long a stop request waits for the loop to flush and return.
+const STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Last completed tick of a supervised loop. Unsupervised loops get a default one nobody reads.
+#[derive(Debug, Clone, Default)]
+pub struct Liveness(Arc<AtomicU64>);
+
+impl Liveness {
+  pub fn beat(&self) {
+    self.0.store(now_unix_ms(), Ordering::Relaxed);
+  }
+
+  fn last_unix_ms(&self) -> u64 {
+    self.0.load(Ordering::Relaxed)
+  }
+}
+
+enum Failure {
+  /// Returned an error or panicked; can be restarted in-process.
+  Exited(String),
+  /// No tick for `hang_ms`; the thread can't be reclaimed.
+  Hung(String),
+}
+
+/// Runs `run` until `stop_rx` fires, restarting it when it fails. `run` gets its own stop channel
+/// and must call `Liveness::beat` once per tick.
+pub fn supervise<F>(
+  cfg: &Config,
+  base: &Path,
+  stop_rx: mpsc::Receiver<()>,
+  tick: Duration,
+  run: F,
+) -> anyhow::Result<()>
+where
+  F: Fn(mpsc::Receiver<()>, Liveness) -> anyhow::Result<()> + Send + Sync + 'static,
+{
+  if !cfg.service.watchdog {
+    return run(stop_rx, Liveness::default());
+  }
+  install_panic_hook();
+
+  let run = Arc::new(run);
+  let hang_ms = cfg.service.watchdog_hang_seconds.saturating_mul(1000);
+  let mut restarts = 0u32;
+  loop {
+    let (loop_stop_tx, loop_stop_rx) = mpsc::channel::<()>();
+    let (done_tx, done_rx) = mpsc::channel();
+    let liveness = Liveness::default();
+    liveness.beat();
+    let started = Instant::now();
+    {
+      let run = Arc::clone(&run);
+      let liveness = liveness.clone();
+      std::thread::Builder::new()
+        .name("agent-loop".to_string())
+        .spawn(move || {
+          let res = panic::catch_unwind(AssertUnwindSafe(|| run(loop_stop_rx, liveness)));
+          let _ = done_tx.send(res);
+        })?;
+    }
+
+    let failure = loop {
+      if stop_rx.recv_timeout(tick).is_ok() {
+        let _ = loop_stop_tx.send(());
+        if done_rx.recv_timeout(STOP_TIMEOUT).is_err() {
+          tracing::warn!("watchdog: agent loop did not stop in time; exiting without it");
+        }
+        return Ok(());
+      }
+      match done_rx.try_recv() {
+        Ok(Ok(Ok(()))) => return Ok(()),
+        Ok(Ok(Err(e))) => break Failure::Exited(format!("agent loop exited with error: {e:#}")),
+        Ok(Err(payload)) => {
+          break Failure::Exited(format!("agent loop panicked: {}", panic_message(&*payload)))
+        }
+        Err(TryRecvError::Disconnected) => {
+          break Failure::Exited("agent loop thread ended without a result".to_string())
+        }
+        Err(TryRecvError::Empty) => {}
+      }
+      if let Some(reason) = hung(&liveness, now_unix_ms(), hang_ms) {
+        break Failure::Hung(reason);
+      }
+    };
+
+    let reason = match failure {
+      Failure::Hung(reason) => {
+        exit_for_restart(cfg, base, &reason);
+      }
+      Failure::Exited(reason) => reason,
+    };
+    if started.elapsed().as_millis() as u64 >= STABLE_MS {
+      restarts = 0;
+    }
+    restarts += 1;
+    if restarts > MAX_RESTARTS {
+      exit_for_restart(
+        cfg,
+        base,
+        &format!("{reason} ({MAX_RESTARTS} in-process restarts did not help)"),
+      );
+    }
+
+    let backoff = 1_000u64
+      .saturating_mul(1 << (restarts - 1).min(6))
+      .min(RESTART_MAX_BACKOFF_MS);
+    tracing::error!(
+      reason = %reason,
+      restarts,
+      backoff_ms = backoff,
+      "watchdog: agent loop failed; restarting it"
+    );
+    report(cfg, &reason, "agent_loop_restarted");
+    if stop_rx.recv_timeout(Duration::from_millis(backoff)).is_ok() {
+      return Ok(());
+    }
+  }
+}
+
+/// Why the loop counts as hung, if it does.
+fn hung(liveness: &Liveness, now: u64, hang_ms: u64) -> Option<String> {
+  let silent_ms = now.saturating_sub(liveness.last_unix_ms());
+  (silent_ms > hang_ms).then(|| {
+    format!(
+      "agent loop has not completed a tick for {}s (hung or deadlocked)",
+      silent_ms / 1000
+    )
+  })
+}
+
+/// Records the failure and exits, leaving the restart to the service manager (or the core, for
+/// the worker). The failsafe is handed off first, since nothing else will run it.
+fn exit_for_restart(cfg: &Config, base: &Path, reason: &str) -> ! {
+  tracing::error!(
+    reason = %reason,
+    exit_code = EXIT_CODE,
+    "watchdog: exiting so the agent is restarted"
+  );
+  report(cfg, reason, "agent_process_exit_for_restart");
+  if !runtime::is_worker() {
+    // Also records the stop, so the next start doesn't report T001 for it as well.
+    lifecycle::on_stop(base);
+  }
+  std::process::exit(EXIT_CODE);
+}
+
+fn report(cfg: &Config, reason: &str, action: &str) {
+  let mut incident = Incident::new(vec![Finding {
+    rule_id: T004_AGENT_LOOP_FAILED.to_string(),
+    severity: Severity::Yellow,
+    description: "AI Defender agent loop failed; protection was interrupted".to_string(),
+    evidence: vec![Evidence::Note {
+      message: reason.to_string(),
+    }],
+    timestamp_unix_ms: now_unix_ms(),
+  }]);
+  incident.actions_taken.push(action.to_string());
+  if let Err(e) = response_engine::handle_incident(cfg, &mut incident) {
+    tracing::error!(error = ?e, "failed to handle watchdog incident");
+  }
+}
+
+/// Panics go to the log; the service has no console for the default hook's output.
+fn install_panic_hook() {
+  static HOOK: Once = Once::new();
+  HOOK.call_once(|| {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+      tracing::error!(panic = %info, "panic");
+      previous(info);
+    }));
+  });
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+  payload
+    .downcast_ref::<&str>()
+    .map(|s| s.to_string())
+    .or_else(|| payload.downcast_ref::<String>().cloned())
+    .unwrap_or_else(|| "unknown panic payload".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn detects_hangs_and_describes_panics() {
+    let liveness = Liveness::default();
+    liveness.beat();
+    let last = liveness.last_unix_ms();
+    assert!(hung(&liveness, last + 180_000, 180_000).is_none());
+    assert!(hung(&liveness, last + 180_001, 180_000)
+      .unwrap()
+      .contains("180s"));
+
+    let payload = panic::catch_unwind(|| panic!("collector {} failed", 7)).unwrap_err();
+    assert_eq!(panic_message(&*payload), "collector 7 failed");
+  }
+}
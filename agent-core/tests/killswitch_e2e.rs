@@ -0,0 +1,84 @@
+//! Drives the `--simulate red` chain through the real rules and response pipeline against the
+//! in-memory firewall, in a throwaway data directory: no admin rights, no firewall changes.
+
+use agent_core::config::{Config, KillSwitchConfig, Mode};
+use agent_core::kill_switch::{self, FirewallBackend, MockFirewall};
+use agent_core::types::Severity;
+use agent_core::{console, incident_store, paths};
+use std::path::Path;
+use std::sync::Arc;
+
+fn state(base: &Path) -> toml::Table {
+  let raw = std::fs::read_to_string(paths::killswitch_state_path(base)).unwrap();
+  toml::from_str(&raw).unwrap()
+}
+
+#[test]
+fn red_chain_locks_the_network_and_the_failsafe_restores_it() {
+  let base = std::env::temp_dir().join(format!("aid-e2e-{}", uuid::Uuid::new_v4()));
+  paths::set_base_dir(&base).unwrap();
+  let firewall = Arc::new(MockFirewall::default());
+  kill_switch::set_firewall(Some(firewall.clone()));
+
+  let cfg = Config {
+    mode: Mode::Strict,
+    killswitch: KillSwitchConfig {
+      enabled: true,
+      auto_trigger: true,
+      // Expires at once, so the failsafe can be exercised without waiting.
+      failsafe_minutes: 0,
+      always_allow: vec!["10.0.0.0/8".to_string()],
+    },
+    ..Config::default()
+  };
+  kill_switch::configure(&cfg);
+
+  let ids = console::run_events_through_pipeline(&cfg, console::red_chain_events(1_000)).unwrap();
+  let incident = ids
+    .iter()
+    .map(|id| incident_store::load(id).unwrap())
+    .find(|i| i.severity == Severity::Red)
+    .expect("RED chain raises a RED incident");
+  let incident_id = &incident.incident_id;
+
+  // Incident record with the response, state file and firewall rules agree: locked.
+  assert!(
+    incident
+      .actions_taken
+      .contains(&"killswitch_enable_auto".to_string()),
+    "{:?}",
+    incident.actions_taken
+  );
+  let st = state(&base);
+  assert_eq!(st["enabled"].as_bool(), Some(true));
+  assert_eq!(st["enabled_mode"].as_str(), Some("autoredonly"));
+  assert_eq!(st["last_incident_id"].as_str(), Some(incident_id.as_str()));
+
+  let rules = firewall.rules();
+  assert_eq!(rules.len(), 2);
+  assert!(rules
+    .iter()
+    .all(|r| r.action == "block" && r.remote_addresses.starts_with("0.0.0.0-9.255.255.255,")));
+  let status = kill_switch::status().unwrap();
+  assert!(status.enabled && status.rules_present);
+  assert_eq!(status.firewall_backend, Some(FirewallBackend::Mock));
+
+  // keep_locked holds the lock past the failsafe deadline.
+  kill_switch::set_keep_locked(true).unwrap();
+  kill_switch::poll_failsafe().unwrap();
+  assert_eq!(firewall.rules().len(), 2);
+  assert_eq!(state(&base)["enabled"].as_bool(), Some(true));
+
+  // Without it, the expired failsafe restores the network.
+  kill_switch::set_keep_locked(false).unwrap();
+  assert!(kill_switch::run_failsafe_restore().unwrap());
+  assert!(firewall.rules().is_empty());
+  let st = state(&base);
+  assert_eq!(st["enabled"].as_bool(), Some(false));
+  assert!(st.get("failsafe_deadline_unix_ms").is_none());
+  let status = kill_switch::status().unwrap();
+  assert!(!status.enabled && !status.rules_present);
+
+  kill_switch::set_firewall(None);
+  let _ = std::fs::remove_dir_all(&base);
+}
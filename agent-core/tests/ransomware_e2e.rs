@@ -0,0 +1,46 @@
+//! Drives the `--simulate ransomware` burst through the real rules and response pipeline in
+//! dry-run: R025 escalates to RED and the response would enable the kill switch, but the firewall
+//! is left alone.
+
+use agent_core::config::{Config, Mode};
+use agent_core::kill_switch::{self, MockFirewall};
+use agent_core::types::Severity;
+use agent_core::user_profiles::UserProfile;
+use agent_core::{console, paths, runtime};
+use std::sync::Arc;
+
+#[test]
+fn ransomware_burst_would_lock_the_network() {
+  let base = std::env::temp_dir().join(format!("aid-e2e-{}", uuid::Uuid::new_v4()));
+  paths::set_base_dir(&base).unwrap();
+  let firewall = Arc::new(MockFirewall::default());
+  kill_switch::set_firewall(Some(firewall.clone()));
+  runtime::set_dry_run(true);
+
+  let cfg = Config {
+    mode: Mode::Strict,
+    ..Config::default()
+  };
+  kill_switch::configure(&cfg);
+
+  let home = UserProfile::current_process().home;
+  let events = console::ransomware_events(1_000, &home, cfg.ransomware.min_files);
+  let incidents = console::run_pipeline(&cfg, events).unwrap();
+
+  let red = incidents
+    .iter()
+    .find(|i| i.severity == Severity::Red)
+    .expect("the burst with .locked copies raises a RED incident");
+  assert!(red.findings.iter().any(|f| f.rule_id == "R025"));
+  assert!(
+    red
+      .actions_taken
+      .contains(&"killswitch_enable_auto".to_string()),
+    "{:?}",
+    red.actions_taken
+  );
+  assert!(firewall.rules().is_empty());
+
+  kill_switch::set_firewall(None);
+  let _ = std::fs::remove_dir_all(&base);
+}
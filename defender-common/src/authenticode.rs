@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// Authenticode verification, shared by the scanner (is this file signed by someone Windows
+// trusts?) and the event collector (who actually published the image of a new process?). Sysmon's
+// `Company` field comes from the PE version resource, which any binary can set to "Google LLC";
+// the publisher here is the subject of the signing certificate of a signature `WinVerifyTrust`
+// accepted, embedded in the file or in a system catalog.
+//
+// Verification is offline: revocation is not checked and no URLs are retrieved.
+
+const MAX_CACHED: usize = 4096;
+
+/// The file a cached verdict belongs to. A changed modification time or size means a different
+/// file at the same path, which is verified again.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FileKey {
+  path: String,
+  modified: Option<SystemTime>,
+  len: u64,
+}
+
+#[derive(Debug, Default)]
+struct PublisherCache {
+  entries: HashMap<FileKey, Option<String>>,
+}
+
+static CACHE: Mutex<Option<PublisherCache>> = Mutex::new(None);
+
+impl PublisherCache {
+  /// The cached publisher for `key`, or the result of `verify`. Failed verifications (e.g. the
+  /// file is locked) are not cached.
+  fn get_or_verify(
+    &mut self,
+    key: FileKey,
+    verify: impl FnOnce() -> anyhow::Result<Option<String>>,
+  ) -> Option<String> {
+    if let Some(publisher) = self.entries.get(&key) {
+      return publisher.clone();
+    }
+    let publisher = verify().ok()?;
+    if self.entries.len() >= MAX_CACHED {
+      self.entries.clear();
+    }
+    self.entries.insert(key, publisher.clone());
+    publisher
+  }
+}
+
+/// Whether `path` carries a trusted Authenticode signature, either embedded in the file or in a
+/// system catalog (most Windows binaries are catalog-signed and have no embedded signature).
+pub fn is_trusted_signed(path: &Path) -> anyhow::Result<bool> {
+  Ok(verify(path)?.is_some())
+}
+
+/// The publisher of `path`'s trusted signature, or `None` if the file is unsigned, its signature
+/// doesn't verify, or it can't be read. Results are cached by path, modification time and size.
+pub fn verified_publisher(path: &Path) -> Option<String> {
+  let meta = std::fs::metadata(path).ok()?;
+  let key = FileKey {
+    path: path.to_string_lossy().to_ascii_lowercase(),
+    modified: meta.modified().ok(),
+    len: meta.len(),
+  };
+  let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+  cache
+    .get_or_insert_with(PublisherCache::default)
+    .get_or_verify(key, || verify(path))
+    .filter(|p| !p.is_empty())
+}
+
+/// `Some(publisher)` for a trusted signature (empty if the certificate has no usable name),
+/// `None` if there is none.
+fn verify(path: &Path) -> anyhow::Result<Option<String>> {
+  match embedded_signature(path)? {
+    Some(publisher) => Ok(Some(publisher)),
+    None => catalog_signature(path),
+  }
+}
+
+#[cfg(windows)]
+mod win {
+  use windows::core::GUID;
+  use windows::Win32::Foundation::{ERROR_SUCCESS, HANDLE, HWND};
+  use windows::Win32::Security::Cryptography::{CertGetNameStringW, CERT_NAME_SIMPLE_DISPLAY_TYPE};
+  use windows::Win32::Security::WinTrust::{
+    WTHelperGetProvSignerFromChain, WTHelperProvDataFromStateData, WinVerifyTrust,
+    WINTRUST_ACTION_GENERIC_VERIFY_V2, WINTRUST_DATA, WTD_STATEACTION_CLOSE,
+    WTD_STATEACTION_VERIFY,
+  };
+
+  /// Runs `WinVerifyTrust` on `data` and returns the signer's name if the signature is trusted.
+  /// The verification state is always closed.
+  ///
+  /// # Safety
+  /// The pointers in `data` must be valid for the duration of the call.
+  pub(super) unsafe fn verify_trust(data: &mut WINTRUST_DATA) -> Option<String> {
+    let mut action: GUID = WINTRUST_ACTION_GENERIC_VERIFY_V2;
+    data.dwStateAction = WTD_STATEACTION_VERIFY;
+    data.hWVTStateData = HANDLE::default();
+    let status = WinVerifyTrust(HWND(0), &mut action, data as *mut _ as *mut _);
+    let publisher = (status == ERROR_SUCCESS.0 as i32).then(|| signer_name(data.hWVTStateData));
+
+    data.dwStateAction = WTD_STATEACTION_CLOSE;
+    let _ = WinVerifyTrust(HWND(0), &mut action, data as *mut _ as *mut _);
+    publisher
+  }
+
+  /// Subject name of the leaf certificate of the primary signer.
+  unsafe fn signer_name(state: HANDLE) -> String {
+    let prov = WTHelperProvDataFromStateData(state);
+    if prov.is_null() {
+      return String::new();
+    }
+    let signer = WTHelperGetProvSignerFromChain(prov, 0, false, 0);
+    if signer.is_null() || (*signer).csCertChain == 0 || (*signer).pasCertChain.is_null() {
+      return String::new();
+    }
+    let cert = (*(*signer).pasCertChain).pCert;
+    if cert.is_null() {
+      return String::new();
+    }
+    let mut name = vec![0u16; 256];
+    let len = CertGetNameStringW(
+      cert,
+      CERT_NAME_SIMPLE_DISPLAY_TYPE,
+      0,
+      None,
+      Some(&mut name),
+    );
+    // The length includes the terminating NUL.
+    let len = (len as usize).saturating_sub(1).min(name.len());
+    String::from_utf16_lossy(&name[..len]).trim().to_string()
+  }
+}
+
+#[cfg(windows)]
+fn embedded_signature(path: &Path) -> anyhow::Result<Option<String>> {
+  use std::ffi::OsStr;
+  use std::os::windows::ffi::OsStrExt;
+  use windows::core::PWSTR;
+  use windows::Win32::Foundation::HANDLE;
+  use windows::Win32::Security::WinTrust::{
+    WINTRUST_DATA, WINTRUST_DATA_0, WINTRUST_DATA_UICONTEXT, WINTRUST_FILE_INFO,
+    WTD_CACHE_ONLY_URL_RETRIEVAL, WTD_CHOICE_FILE, WTD_REVOKE_NONE, WTD_STATEACTION_VERIFY,
+    WTD_UI_NONE,
+  };
+
+  let wide: Vec<u16> = OsStr::new(path)
+    .encode_wide()
+    .chain(std::iter::once(0))
+    .collect();
+
+  let mut file_info = WINTRUST_FILE_INFO {
+    cbStruct: std::mem::size_of::<WINTRUST_FILE_INFO>() as u32,
+    pcwszFilePath: windows::core::PCWSTR(wide.as_ptr()),
+    hFile: HANDLE::default(),
+    pgKnownSubject: std::ptr::null_mut(),
+  };
+
+  let mut data = WINTRUST_DATA {
+    cbStruct: std::mem::size_of::<WINTRUST_DATA>() as u32,
+    pPolicyCallbackData: std::ptr::null_mut(),
+    pSIPClientData: std::ptr::null_mut(),
+    dwUIChoice: WTD_UI_NONE,
+    fdwRevocationChecks: WTD_REVOKE_NONE,
+    dwUnionChoice: WTD_CHOICE_FILE,
+    Anonymous: WINTRUST_DATA_0 {
+      pFile: &mut file_info,
+    },
+    dwStateAction: WTD_STATEACTION_VERIFY,
+    hWVTStateData: HANDLE::default(),
+    pwszURLReference: PWSTR::null(),
+    // Offline-first: do not allow URL retrieval during signature verification.
+    dwProvFlags: WTD_CACHE_ONLY_URL_RETRIEVAL,
+    dwUIContext: WINTRUST_DATA_UICONTEXT(0),
+    pSignatureSettings: std::ptr::null_mut(),
+  };
+
+  // SAFETY: `file_info` and `wide` outlive the call.
+  Ok(unsafe { win::verify_trust(&mut data) })
+}
+
+#[cfg(not(windows))]
+fn embedded_signature(_path: &Path) -> anyhow::Result<Option<String>> {
+  Ok(None)
+}
+
+/// Looks the file's hash up in the installed catalogs (`CatRoot`) and verifies the catalog that
+/// lists it. Catalogs are indexed by SHA-256 on current Windows and by SHA-1 on older ones.
+#[cfg(windows)]
+fn catalog_signature(path: &Path) -> anyhow::Result<Option<String>> {
+  use std::ffi::OsStr;
+  use std::os::windows::ffi::OsStrExt;
+  use std::os::windows::io::AsRawHandle;
+  use windows::core::{GUID, PCWSTR, PWSTR};
+  use windows::Win32::Foundation::HANDLE;
+  use windows::Win32::Security::Cryptography::Catalog::{
+    CryptCATAdminAcquireContext2, CryptCATAdminCalcHashFromFileHandle2,
+    CryptCATAdminEnumCatalogFromHash, CryptCATAdminReleaseCatalogContext,
+    CryptCATAdminReleaseContext, CryptCATCatalogInfoFromContext, CATALOG_INFO,
+  };
+  use windows::Win32::Security::Cryptography::BCRYPT_SHA256_ALGORITHM;
+  use windows::Win32::Security::WinTrust::{
+    DRIVER_ACTION_VERIFY, WINTRUST_CATALOG_INFO, WINTRUST_DATA, WINTRUST_DATA_0,
+    WINTRUST_DATA_UICONTEXT, WTD_CACHE_ONLY_URL_RETRIEVAL, WTD_CHOICE_CATALOG, WTD_REVOKE_NONE,
+    WTD_STATEACTION_VERIFY, WTD_UI_NONE,
+  };
+
+  let file = std::fs::File::open(path)?;
+  let handle = HANDLE(file.as_raw_handle() as isize);
+  let wide_path: Vec<u16> = OsStr::new(path)
+    .encode_wide()
+    .chain(std::iter::once(0))
+    .collect();
+
+  for algorithm in [BCRYPT_SHA256_ALGORITHM, PCWSTR::null()] {
+    // SAFETY: Every handle acquired below is released before the next iteration; all pointers
+    // passed to Win32 outlive the calls that use them.
+    unsafe {
+      let mut admin: isize = 0;
+      let subsystem: GUID = DRIVER_ACTION_VERIFY;
+      if CryptCATAdminAcquireContext2(&mut admin, Some(&subsystem), algorithm, None, 0).is_err() {
+        continue;
+      }
+
+      let mut hash_len = 0u32;
+      let _ = CryptCATAdminCalcHashFromFileHandle2(admin, handle, &mut hash_len, None, 0);
+      let mut hash = vec![0u8; hash_len as usize];
+      if hash.is_empty()
+        || CryptCATAdminCalcHashFromFileHandle2(
+          admin,
+          handle,
+          &mut hash_len,
+          Some(hash.as_mut_ptr()),
+          0,
+        )
+        .is_err()
+      {
+        let _ = CryptCATAdminReleaseContext(admin, 0);
+        continue;
+      }
+      hash.truncate(hash_len as usize);
+
+      let cat_info = CryptCATAdminEnumCatalogFromHash(admin, &hash, 0, None);
+      if cat_info == 0 {
+        let _ = CryptCATAdminReleaseContext(admin, 0);
+        continue;
+      }
+
+      let mut info = CATALOG_INFO {
+        cbStruct: std::mem::size_of::<CATALOG_INFO>() as u32,
+        wszCatalogFile: [0; 260],
+      };
+      let mut publisher = None;
+      if CryptCATCatalogInfoFromContext(cat_info, &mut info, 0).is_ok() {
+        let tag: Vec<u16> = member_tag(&hash)
+          .encode_utf16()
+          .chain(std::iter::once(0))
+          .collect();
+        let mut catalog = WINTRUST_CATALOG_INFO {
+          cbStruct: std::mem::size_of::<WINTRUST_CATALOG_INFO>() as u32,
+          dwCatalogVersion: 0,
+          pcwszCatalogFilePath: PCWSTR(info.wszCatalogFile.as_ptr()),
+          pcwszMemberTag: PCWSTR(tag.as_ptr()),
+          pcwszMemberFilePath: PCWSTR(wide_path.as_ptr()),
+          hMemberFile: handle,
+          pbCalculatedFileHash: hash.as_mut_ptr(),
+          cbCalculatedFileHash: hash.len() as u32,
+          pcCatalogContext: std::ptr::null_mut(),
+          hCatAdmin: admin,
+        };
+        let mut data = WINTRUST_DATA {
+          cbStruct: std::mem::size_of::<WINTRUST_DATA>() as u32,
+          pPolicyCallbackData: std::ptr::null_mut(),
+          pSIPClientData: std::ptr::null_mut(),
+          dwUIChoice: WTD_UI_NONE,
+          fdwRevocationChecks: WTD_REVOKE_NONE,
+          dwUnionChoice: WTD_CHOICE_CATALOG,
+          Anonymous: WINTRUST_DATA_0 {
+            pCatalog: &mut catalog,
+          },
+          dwStateAction: WTD_STATEACTION_VERIFY,
+          hWVTStateData: HANDLE::default(),
+          pwszURLReference: PWSTR::null(),
+          dwProvFlags: WTD_CACHE_ONLY_URL_RETRIEVAL,
+          dwUIContext: WINTRUST_DATA_UICONTEXT(0),
+          pSignatureSettings: std::ptr::null_mut(),
+        };
+        // The signer of a catalog-signed file is the catalog's signer.
+        publisher = win::verify_trust(&mut data);
+      }
+
+      let _ = CryptCATAdminReleaseCatalogContext(admin, cat_info, 0);
+      let _ = CryptCATAdminReleaseContext(admin, 0);
+      if publisher.is_some() {
+        return Ok(publisher);
+      }
+    }
+  }
+  Ok(None)
+}
+
+#[cfg(not(windows))]
+fn catalog_signature(_path: &Path) -> anyhow::Result<Option<String>> {
+  Ok(None)
+}
+
+/// Catalog members are tagged with the file hash in uppercase hex.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn member_tag(hash: &[u8]) -> String {
+  hash.iter().map(|b| format!("{b:02X}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::cell::Cell;
+
+  #[test]
+  fn caches_verdicts_per_file_version() {
+    let mut cache = PublisherCache::default();
+    let key = |len| FileKey {
+      path: "c:\\tools\\app.exe".to_string(),
+      modified: Some(SystemTime::UNIX_EPOCH),
+      len,
+    };
+    let calls = Cell::new(0);
+    let verify = |publisher: Option<&'static str>| {
+      let calls = &calls;
+      move || {
+        calls.set(calls.get() + 1);
+        Ok(publisher.map(str::to_string))
+      }
+    };
+    let contoso = Some("Contoso Ltd".to_string());
+
+    assert_eq!(
+      cache.get_or_verify(key(10), verify(Some("Contoso Ltd"))),
+      contoso
+    );
+    assert_eq!(cache.get_or_verify(key(10), verify(None)), contoso);
+    assert_eq!(calls.get(), 1);
+
+    // Replaced by an unsigned file of another size.
+    assert_eq!(cache.get_or_verify(key(11), verify(None)), None);
+    assert_eq!(cache.get_or_verify(key(11), verify(Some("x"))), None);
+    assert_eq!(calls.get(), 2);
+
+    // Errors are retried.
+    assert_eq!(
+      cache.get_or_verify(key(12), || anyhow::bail!("sharing violation")),
+      None
+    );
+    assert_eq!(
+      cache.get_or_verify(key(12), verify(Some("Contoso Ltd"))),
+      contoso
+    );
+    assert_eq!(calls.get(), 3);
+  }
+}
@@ -0,0 +1,77 @@
+use anyhow::Context;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Writes `contents` to a temporary file next to `path` and moves it over `path`, creating the
+/// parent directory if needed. Readers see the old or the new file, never a partial one.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> anyhow::Result<()> {
+  let parent = path
+    .parent()
+    .ok_or_else(|| anyhow::anyhow!("file path has no parent: {}", path.display()))?;
+  fs::create_dir_all(parent).with_context(|| format!("create {}", parent.display()))?;
+
+  let tmp = sibling(path, "tmp");
+  fs::write(&tmp, contents).with_context(|| format!("write {}", tmp.display()))?;
+  if let Err(e) = replace(&tmp, path) {
+    let _ = fs::remove_file(&tmp);
+    return Err(e);
+  }
+  Ok(())
+}
+
+/// `.<name>.<ext>` in the same directory as `path`.
+fn sibling(path: &Path, ext: &str) -> PathBuf {
+  let name = path.file_name().unwrap_or_default().to_string_lossy();
+  path.with_file_name(format!(".{name}.{ext}"))
+}
+
+/// Renames `staging` over `dst`. A plain rename replaces an existing file, but fails on Windows
+/// while another process holds `dst` open without delete sharing (antivirus scanners, indexers);
+/// moving `dst` aside first usually still works then, and it is moved back if the rename fails.
+fn replace(staging: &Path, dst: &Path) -> anyhow::Result<()> {
+  let first = match fs::rename(staging, dst) {
+    Ok(()) => return Ok(()),
+    Err(e) => e,
+  };
+  if !dst.exists() {
+    return Err(first)
+      .with_context(|| format!("rename {} -> {}", staging.display(), dst.display()));
+  }
+
+  let bak = sibling(dst, "bak");
+  let _ = fs::remove_file(&bak);
+  fs::rename(dst, &bak).with_context(|| format!("replace {} ({first})", dst.display()))?;
+  match fs::rename(staging, dst) {
+    Ok(()) => {
+      let _ = fs::remove_file(&bak);
+      Ok(())
+    }
+    Err(e) => {
+      let _ = fs::rename(&bak, dst);
+      Err(e).with_context(|| format!("replace {}", dst.display()))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn writes_and_replaces_without_leftovers() {
+    let dir = std::env::temp_dir().join(format!("defender-common-fs-{}", std::process::id()));
+    let path = dir.join("state").join("status.toml");
+
+    write_atomic(&path, "a = 1\n").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a = 1\n");
+    write_atomic(&path, b"a = 2\n").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a = 2\n");
+
+    let names: Vec<_> = fs::read_dir(path.parent().unwrap())
+      .unwrap()
+      .map(|e| e.unwrap().file_name())
+      .collect();
+    assert_eq!(names, ["status.toml"]);
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
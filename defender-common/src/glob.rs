@@ -0,0 +1,91 @@
+/// Whether Windows path `path` matches glob `pattern`, case-insensitively. `\` and `/` both
+/// separate components; `*` matches within a component, `?` one character, and a `**` component
+/// any number of components (none included). E.g. `C:\Dev\**\target\**`.
+pub fn matches(pattern: &str, path: &str) -> bool {
+  let pattern = components(pattern);
+  let path = components(path);
+  match_components(&pattern, &path)
+}
+
+/// Whether `s` uses glob syntax, as opposed to a plain path prefix.
+pub fn is_glob(s: &str) -> bool {
+  s.contains(['*', '?'])
+}
+
+fn components(s: &str) -> Vec<Vec<char>> {
+  s.to_lowercase()
+    .split(['\\', '/'])
+    .filter(|c| !c.is_empty())
+    .map(|c| c.chars().collect())
+    .collect()
+}
+
+fn match_components(pattern: &[Vec<char>], path: &[Vec<char>]) -> bool {
+  match pattern.split_first() {
+    None => path.is_empty(),
+    Some((p, rest)) if p[..] == ['*', '*'] => {
+      match_components(rest, path) || (!path.is_empty() && match_components(pattern, &path[1..]))
+    }
+    Some((p, rest)) => path
+      .split_first()
+      .is_some_and(|(c, path)| match_component(p, c) && match_components(rest, path)),
+  }
+}
+
+/// `*` and `?` within one component.
+fn match_component(pattern: &[char], s: &[char]) -> bool {
+  let (mut p, mut i) = (0, 0);
+  // Where the last `*` was and how much of `s` it has taken so far.
+  let mut star: Option<(usize, usize)> = None;
+  while i < s.len() {
+    match pattern.get(p) {
+      Some('*') => {
+        star = Some((p, i));
+        p += 1;
+      }
+      Some(&c) if c == '?' || c == s[i] => {
+        p += 1;
+        i += 1;
+      }
+      _ => match star {
+        Some((sp, si)) => {
+          p = sp + 1;
+          i = si + 1;
+          star = Some((sp, si + 1));
+        }
+        None => return false,
+      },
+    }
+  }
+  pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches_components_wildcards_and_double_stars() {
+    let target = r"C:\Dev\**\target\**";
+    assert!(matches(target, r"c:\dev\app\target\debug\app.exe"));
+    assert!(matches(target, r"C:/Dev/a/b/target/x.dll"));
+    assert!(!matches(target, r"C:\Dev\app\src\main.exe"));
+    assert!(!matches(target, r"D:\Dev\app\target\x.exe"));
+
+    assert!(matches(
+      r"C:\Users\*\AppData\Local\Temp\*.exe",
+      r"C:\Users\alice\AppData\Local\Temp\setup.exe"
+    ));
+    assert!(!matches(
+      r"C:\Users\*\AppData\Local\Temp\*.exe",
+      r"C:\Users\alice\AppData\Local\Temp\sub\setup.exe"
+    ));
+    assert!(matches(
+      r"**\node_modules\**",
+      r"C:\src\web\node_modules\.bin\esbuild.exe"
+    ));
+    assert!(matches(r"C:\Tools\tool-??.exe", r"C:\Tools\TOOL-12.exe"));
+    assert!(!matches(r"C:\Tools\tool-??.exe", r"C:\Tools\tool-123.exe"));
+    assert!(is_glob(target) && !is_glob(r"C:\Dev"));
+  }
+}
@@ -0,0 +1,66 @@
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// Lowercase hex of `bytes`.
+pub fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// SHA-256 of `bytes` as lowercase hex.
+pub fn sha256_bytes_hex(bytes: &[u8]) -> String {
+  hex(&Sha256::digest(bytes))
+}
+
+/// HMAC-SHA256 (RFC 2104) of `msg` under `key`.
+pub fn hmac_sha256(key: &[u8], msg: &[u8]) -> [u8; 32] {
+  const BLOCK: usize = 64;
+  let mut k = [0u8; BLOCK];
+  if key.len() > BLOCK {
+    k[..32].copy_from_slice(&Sha256::digest(key));
+  } else {
+    k[..key.len()].copy_from_slice(key);
+  }
+
+  let mut inner = Sha256::new();
+  inner.update(k.map(|b| b ^ 0x36));
+  inner.update(msg);
+  let mut outer = Sha256::new();
+  outer.update(k.map(|b| b ^ 0x5c));
+  outer.update(inner.finalize());
+  outer.finalize().into()
+}
+
+/// SHA-256 of the file at `path` as lowercase hex, read in chunks.
+pub fn sha256_hex(path: &Path) -> anyhow::Result<String> {
+  let mut file = fs::File::open(path)?;
+  let mut hasher = Sha256::new();
+  let mut buf = [0u8; 64 * 1024];
+  loop {
+    let n = file.read(&mut buf)?;
+    if n == 0 {
+      break;
+    }
+    hasher.update(&buf[..n]);
+  }
+  Ok(hex(&hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn hashes_files_and_bytes_alike() {
+    let empty = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+    assert_eq!(sha256_bytes_hex(b""), empty);
+    assert_eq!(hex(&[0x00, 0xab, 0x0f]), "00ab0f");
+
+    let path = std::env::temp_dir().join(format!("defender-common-hash-{}", std::process::id()));
+    let data = vec![7u8; 200_000];
+    fs::write(&path, &data).unwrap();
+    assert_eq!(sha256_hex(&path).unwrap(), sha256_bytes_hex(&data));
+    let _ = fs::remove_file(&path);
+  }
+}
@@ -0,0 +1,11 @@
+// Utilities shared by the agent and the scanner: Authenticode and Ed25519 signature checks,
+// SHA-256 helpers, path globs, shortcut (`.lnk`) targets, atomic file writes, and the
+// hand-written TOML of the status files.
+
+pub mod authenticode;
+pub mod fs;
+pub mod glob;
+pub mod hash;
+pub mod lnk;
+pub mod signature;
+pub mod toml_fmt;
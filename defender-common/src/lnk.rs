@@ -0,0 +1,228 @@
+// Shell link (`.lnk`) target resolution, after [MS-SHLLINK]. Only what a shortcut launches is
+// read: the environment-variable target (`%LOCALAPPDATA%\...`), else the local path from the link
+// info, else the relative path, plus the command-line arguments. Shortcuts that only have a shell
+// item ID list (e.g. to Control Panel items) don't resolve.
+
+const HEADER_SIZE: usize = 0x4C;
+const LINK_CLSID: [u8; 16] = [
+  0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46,
+];
+
+const HAS_TARGET_ID_LIST: u32 = 0x1;
+const HAS_LINK_INFO: u32 = 0x2;
+const HAS_NAME: u32 = 0x4;
+const HAS_RELATIVE_PATH: u32 = 0x8;
+const HAS_WORKING_DIR: u32 = 0x10;
+const HAS_ARGUMENTS: u32 = 0x20;
+const HAS_ICON_LOCATION: u32 = 0x40;
+const IS_UNICODE: u32 = 0x80;
+const HAS_EXP_STRING: u32 = 0x200;
+
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x1;
+const ENVIRONMENT_BLOCK_SIGNATURE: u32 = 0xA000_0001;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkTarget {
+  /// As stored: may hold `%VAR%` references (environment target) or be relative to the link's
+  /// folder (relative path only).
+  pub path: String,
+  pub arguments: Option<String>,
+}
+
+/// The target of a shell link, or `None` when `raw` isn't one or has no file target.
+pub fn parse(raw: &[u8]) -> Option<LinkTarget> {
+  if u32_at(raw, 0)? as usize != HEADER_SIZE || raw.get(4..20)? != LINK_CLSID {
+    return None;
+  }
+  let flags = u32_at(raw, 0x14)?;
+  let mut pos = HEADER_SIZE;
+  if flags & HAS_TARGET_ID_LIST != 0 {
+    pos += 2 + u16_at(raw, pos)? as usize;
+  }
+
+  let mut local_path = None;
+  if flags & HAS_LINK_INFO != 0 {
+    let info = raw.get(pos..)?;
+    let size = u32_at(info, 0)? as usize;
+    local_path = link_info_path(info.get(..size)?);
+    pos += size;
+  }
+
+  let unicode = flags & IS_UNICODE != 0;
+  let mut relative_path = None;
+  let mut arguments = None;
+  for (flag, slot) in [
+    (HAS_NAME, None),
+    (HAS_RELATIVE_PATH, Some(&mut relative_path)),
+    (HAS_WORKING_DIR, None),
+    (HAS_ARGUMENTS, Some(&mut arguments)),
+    (HAS_ICON_LOCATION, None),
+  ] {
+    if flags & flag == 0 {
+      continue;
+    }
+    let chars = u16_at(raw, pos)? as usize;
+    let len = if unicode { chars * 2 } else { chars };
+    let bytes = raw.get(pos + 2..pos + 2 + len)?;
+    if let Some(slot) = slot {
+      *slot = Some(if unicode { utf16(bytes) } else { ansi(bytes) });
+    }
+    pos += 2 + len;
+  }
+
+  let env_path = if flags & HAS_EXP_STRING != 0 {
+    environment_target(raw.get(pos..)?)
+  } else {
+    None
+  };
+  let path = env_path
+    .or(local_path)
+    .or(relative_path)
+    .filter(|p| !p.trim().is_empty())?;
+  Some(LinkTarget {
+    path,
+    arguments: arguments.filter(|a| !a.trim().is_empty()),
+  })
+}
+
+/// `LocalBasePath` + `CommonPathSuffix`, preferring the Unicode copies when present.
+fn link_info_path(info: &[u8]) -> Option<String> {
+  let header_size = u32_at(info, 4)? as usize;
+  if u32_at(info, 8)? & VOLUME_ID_AND_LOCAL_BASE_PATH == 0 {
+    return None;
+  }
+  if header_size >= 0x24 {
+    let base = u32_at(info, 0x1C)? as usize;
+    let suffix = u32_at(info, 0x20)? as usize;
+    if base != 0 {
+      let mut path = utf16_z(info.get(base..)?);
+      if suffix != 0 {
+        path.push_str(&utf16_z(info.get(suffix..)?));
+      }
+      return Some(path);
+    }
+  }
+  let base = u32_at(info, 0x10)? as usize;
+  let suffix = u32_at(info, 0x18)? as usize;
+  let mut path = ansi_z(info.get(base..)?);
+  path.push_str(&ansi_z(info.get(suffix..)?));
+  Some(path)
+}
+
+/// The target from the `EnvironmentVariableDataBlock` among the extra data blocks.
+fn environment_target(mut extra: &[u8]) -> Option<String> {
+  loop {
+    let size = u32_at(extra, 0)? as usize;
+    if size < 8 {
+      return None;
+    }
+    if u32_at(extra, 4)? == ENVIRONMENT_BLOCK_SIGNATURE {
+      let unicode = utf16_z(extra.get(8 + 260..8 + 260 + 520)?);
+      return Some(if unicode.is_empty() {
+        ansi_z(extra.get(8..8 + 260)?)
+      } else {
+        unicode
+      });
+    }
+    extra = extra.get(size..)?;
+  }
+}
+
+fn u16_at(b: &[u8], at: usize) -> Option<u16> {
+  Some(u16::from_le_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(b: &[u8], at: usize) -> Option<u32> {
+  Some(u32::from_le_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn utf16(b: &[u8]) -> String {
+  let units: Vec<u16> = b
+    .chunks_exact(2)
+    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+    .collect();
+  String::from_utf16_lossy(&units)
+}
+
+fn utf16_z(b: &[u8]) -> String {
+  let units: Vec<u16> = b
+    .chunks_exact(2)
+    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+    .take_while(|&u| u != 0)
+    .collect();
+  String::from_utf16_lossy(&units)
+}
+
+/// Code page text; non-ASCII characters are rare in paths and come out lossy.
+fn ansi(b: &[u8]) -> String {
+  String::from_utf8_lossy(b).into_owned()
+}
+
+fn ansi_z(b: &[u8]) -> String {
+  ansi(b.split(|&c| c == 0).next().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn header(flags: u32) -> Vec<u8> {
+    let mut b = vec![0u8; HEADER_SIZE];
+    b[0] = HEADER_SIZE as u8;
+    b[4..20].copy_from_slice(&LINK_CLSID);
+    b[0x14..0x18].copy_from_slice(&flags.to_le_bytes());
+    b
+  }
+
+  fn counted_utf16(s: &str) -> Vec<u8> {
+    let units: Vec<u16> = s.encode_utf16().collect();
+    let mut b = (units.len() as u16).to_le_bytes().to_vec();
+    b.extend(units.iter().flat_map(|u| u.to_le_bytes()));
+    b
+  }
+
+  #[test]
+  fn resolves_local_path_arguments_and_environment_targets() {
+    // Link info with an ANSI local base path, then Unicode arguments.
+    let mut raw = header(HAS_LINK_INFO | HAS_ARGUMENTS | IS_UNICODE);
+    let base = b"C:\\Users\\alice\\AppData\\Roaming\\upd\\svc.exe\0";
+    let mut info = vec![0u8; 0x1C];
+    info[4..8].copy_from_slice(&0x1Cu32.to_le_bytes());
+    info[8..12].copy_from_slice(&VOLUME_ID_AND_LOCAL_BASE_PATH.to_le_bytes());
+    info[0x10..0x14].copy_from_slice(&0x1Cu32.to_le_bytes());
+    let suffix_at = 0x1C + base.len() as u32;
+    info[0x18..0x1C].copy_from_slice(&suffix_at.to_le_bytes());
+    info.extend_from_slice(base);
+    info.push(0);
+    let size = info.len() as u32;
+    info[0..4].copy_from_slice(&size.to_le_bytes());
+    raw.extend(info);
+    raw.extend(counted_utf16("--hidden"));
+    assert_eq!(
+      parse(&raw).unwrap(),
+      LinkTarget {
+        path: r"C:\Users\alice\AppData\Roaming\upd\svc.exe".to_string(),
+        arguments: Some("--hidden".to_string()),
+      }
+    );
+
+    // The environment target wins over the relative path.
+    let mut raw = header(HAS_RELATIVE_PATH | IS_UNICODE | HAS_EXP_STRING);
+    raw.extend(counted_utf16(r"..\x.exe"));
+    let mut block = vec![0u8; 8 + 260 + 520];
+    let size = block.len() as u32;
+    block[0..4].copy_from_slice(&size.to_le_bytes());
+    block[4..8].copy_from_slice(&ENVIRONMENT_BLOCK_SIGNATURE.to_le_bytes());
+    let target: Vec<u8> = r"%LOCALAPPDATA%\x.exe"
+      .encode_utf16()
+      .flat_map(u16::to_le_bytes)
+      .collect();
+    block[268..268 + target.len()].copy_from_slice(&target);
+    raw.extend(block);
+    raw.extend(0u32.to_le_bytes());
+    assert_eq!(parse(&raw).unwrap().path, r"%LOCALAPPDATA%\x.exe");
+
+    assert!(parse(&header(HAS_TARGET_ID_LIST)[..HEADER_SIZE]).is_none());
+    assert!(parse(b"MZ not a shortcut").is_none());
+  }
+}
@@ -0,0 +1,83 @@
+use anyhow::Context;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+// Ed25519 encodings shared by the signed files (threat feed bundles, licenses, config profiles,
+// the key manifest): signatures are distributed as raw 64 bytes or as base64url/base64 text, public
+// keys as base64url.
+
+/// Raw 64 bytes, or base64url/base64 text.
+pub fn decode_signature(raw: &[u8]) -> anyhow::Result<Signature> {
+  let bytes = if raw.len() == 64 {
+    raw.to_vec()
+  } else {
+    let text = std::str::from_utf8(raw).context("signature must be raw bytes or UTF-8")?;
+    let t = text.trim();
+    if t.is_empty() {
+      anyhow::bail!("empty signature");
+    }
+    URL_SAFE_NO_PAD
+      .decode(t.as_bytes())
+      .or_else(|_| STANDARD.decode(t.as_bytes()))
+      .context("decode signature (base64 or base64url)")?
+  };
+  let arr: [u8; 64] = bytes
+    .try_into()
+    .map_err(|_| anyhow::anyhow!("invalid signature length (expected 64 bytes)"))?;
+  Ok(Signature::from_bytes(&arr))
+}
+
+/// Parses a base64url (no padding) Ed25519 public key.
+pub fn parse_public_key(text: &str) -> anyhow::Result<VerifyingKey> {
+  let pk = URL_SAFE_NO_PAD
+    .decode(text.trim().as_bytes())
+    .context("decode public key base64url")?;
+  let arr: [u8; 32] = pk
+    .try_into()
+    .map_err(|_| anyhow::anyhow!("public key must be 32 bytes (ed25519)"))?;
+  Ok(VerifyingKey::from_bytes(&arr)?)
+}
+
+/// First 16 hex chars of SHA-256 over the raw key bytes; enough to tell keys apart in status output.
+pub fn fingerprint(key: &VerifyingKey) -> String {
+  crate::hash::hex(&Sha256::digest(key.as_bytes())[..8])
+}
+
+/// Verifies `sig` over `msg` with `key`, rejecting weak keys and malleable signatures.
+pub fn verify_strict(key: &VerifyingKey, msg: &[u8], sig: &Signature) -> anyhow::Result<()> {
+  key
+    .verify_strict(msg, sig)
+    .context("signature verification failed")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ed25519_dalek::{Signer, SigningKey};
+
+  #[test]
+  fn accepts_raw_and_text_signatures() {
+    let key = SigningKey::from_bytes(&[5u8; 32]);
+    let sig = key.sign(b"bundle");
+    let raw = sig.to_bytes();
+
+    for encoded in [
+      raw.to_vec(),
+      URL_SAFE_NO_PAD.encode(raw).into_bytes(),
+      format!("{}\n", STANDARD.encode(raw)).into_bytes(),
+    ] {
+      let decoded = decode_signature(&encoded).unwrap();
+      verify_strict(&key.verifying_key(), b"bundle", &decoded).unwrap();
+    }
+    assert!(verify_strict(&key.verifying_key(), b"other", &sig).is_err());
+    assert!(decode_signature(b"  ").is_err());
+    assert!(decode_signature(URL_SAFE_NO_PAD.encode([1u8; 32]).as_bytes()).is_err());
+
+    let public = URL_SAFE_NO_PAD.encode(key.verifying_key().as_bytes());
+    let parsed = parse_public_key(&public).unwrap();
+    assert_eq!(parsed, key.verifying_key());
+    assert_eq!(fingerprint(&parsed).len(), 16);
+  }
+}
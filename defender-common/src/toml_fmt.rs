@@ -0,0 +1,36 @@
+// Values for the status files written with `format!` (`threat_feed\state.toml`,
+// `license\status.toml`), where a missing value is written as `null`.
+
+pub fn toml_string_or_null(v: Option<&str>) -> String {
+  match v {
+    Some(s) => format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")),
+    None => "null".to_string(),
+  }
+}
+
+pub fn toml_u64_or_null(v: Option<u64>) -> String {
+  match v {
+    Some(x) => x.to_string(),
+    None => "null".to_string(),
+  }
+}
+
+pub fn toml_u32_or_null(v: Option<u32>) -> String {
+  toml_u64_or_null(v.map(u64::from))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn quotes_strings_and_writes_null() {
+    assert_eq!(
+      toml_string_or_null(Some(r#"C:\feed "v2""#)),
+      r#""C:\\feed \"v2\"""#
+    );
+    assert_eq!(toml_string_or_null(None), "null");
+    assert_eq!(toml_u64_or_null(Some(7)), "7");
+    assert_eq!(toml_u32_or_null(None), "null");
+  }
+}
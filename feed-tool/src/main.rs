@@ -0,0 +1,202 @@
+use agent_core::threat_feed::{self, schema::ThreatFeedBundle};
+use anyhow::Context;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use defender_common::fs::write_atomic;
+use defender_common::signature::{decode_signature, fingerprint, parse_public_key, verify_strict};
+use ed25519_dalek::{Signer, SigningKey};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Development tool for threat feed bundles: creates a signing key, a bundle skeleton, and signs
+// and checks bundles the way the agent does, so the import path can be exercised end to end with
+// the key trusted as an organization key (`[threat_feed] org_public_key_file`). Not shipped.
+
+const USAGE: &str = "usage:
+  feed-tool keygen <dir>                                write feed-signing.key and feed-signing.pub
+  feed-tool new <bundle.json>                           write an empty, valid bundle
+  feed-tool validate <bundle.json>                      check the bundle schema
+  feed-tool sign <bundle.json> --key <file> [--out <bundle.sig>]
+  feed-tool verify <bundle.json> <bundle.sig> --pub <file-or-base64url>";
+
+const SECRET_KEY_FILE: &str = "feed-signing.key";
+const PUBLIC_KEY_FILE: &str = "feed-signing.pub";
+
+fn main() -> anyhow::Result<()> {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let arg = |i: usize| {
+    args
+      .get(i)
+      .map(String::as_str)
+      .filter(|a| !a.starts_with("--"))
+      .ok_or_else(|| anyhow::anyhow!("{USAGE}"))
+  };
+  let flag = |name: &str| {
+    args
+      .iter()
+      .position(|a| a == name)
+      .map(|i| {
+        args
+          .get(i + 1)
+          .map(String::as_str)
+          .ok_or_else(|| anyhow::anyhow!("{name} needs a value"))
+      })
+      .transpose()
+  };
+
+  match args.first().map(String::as_str) {
+    Some("keygen") => keygen(Path::new(arg(1)?)),
+    Some("new") => new_bundle(Path::new(arg(1)?)),
+    Some("validate") => {
+      let bundle = read_bundle(Path::new(arg(1)?))?;
+      println!(
+        "OK: bundle {} (rules_version {})",
+        bundle.bundle_id, bundle.rules_version
+      );
+      Ok(())
+    }
+    Some("sign") => {
+      let bundle = Path::new(arg(1)?);
+      let key = flag("--key")?.ok_or_else(|| anyhow::anyhow!("sign needs --key <file>"))?;
+      let out = flag("--out")?
+        .map(PathBuf::from)
+        .unwrap_or_else(|| bundle.with_file_name("bundle.sig"));
+      sign(bundle, Path::new(key), &out)
+    }
+    Some("verify") => {
+      let key = flag("--pub")?.ok_or_else(|| anyhow::anyhow!("verify needs --pub <key>"))?;
+      verify(Path::new(arg(1)?), Path::new(arg(2)?), key)
+    }
+    _ => anyhow::bail!("{USAGE}"),
+  }
+}
+
+fn keygen(dir: &Path) -> anyhow::Result<()> {
+  let secret_path = dir.join(SECRET_KEY_FILE);
+  let public_path = dir.join(PUBLIC_KEY_FILE);
+  if secret_path.exists() || public_path.exists() {
+    anyhow::bail!("{} already has a key; not overwritten", dir.display());
+  }
+  let mut seed = [0u8; 32];
+  getrandom::getrandom(&mut seed).map_err(|e| anyhow::anyhow!("generate key: {e}"))?;
+  let key = SigningKey::from_bytes(&seed);
+  let public = URL_SAFE_NO_PAD.encode(key.verifying_key().as_bytes());
+
+  fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+  write_atomic(&secret_path, URL_SAFE_NO_PAD.encode(seed))?;
+  write_atomic(&public_path, &public)?;
+  println!("Secret key: {} (keep it private)", secret_path.display());
+  println!("Public key: {} = {public}", public_path.display());
+  println!("Fingerprint: {}", fingerprint(&key.verifying_key()));
+  println!(
+    "Trust it with `org_public_key_file = '{}'` in [threat_feed].",
+    public_path.display()
+  );
+  Ok(())
+}
+
+fn new_bundle(path: &Path) -> anyhow::Result<()> {
+  if path.exists() {
+    anyhow::bail!("{} exists; not overwritten", path.display());
+  }
+  let created_at = std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(1);
+  let bundle = serde_json::json!({
+    "version": 1,
+    "bundle_id": uuid::Uuid::new_v4().to_string(),
+    "created_at": created_at,
+    "rules_version": 1,
+    "reputation": {
+      "domains_block": [],
+      "hashes_block": [],
+      "wallet_spenders_block": [],
+      "ips_block": [],
+      "domains_newly_registered": [],
+      "wallet_drain_patterns": []
+    },
+    "rules": [],
+    "detections": []
+  });
+  write_atomic(path, serde_json::to_vec_pretty(&bundle)?)?;
+  println!("Wrote {}", path.display());
+  Ok(())
+}
+
+fn read_bundle(path: &Path) -> anyhow::Result<ThreatFeedBundle> {
+  let json = fs::read(path).with_context(|| format!("read {}", path.display()))?;
+  parse_bundle(&json)
+}
+
+/// Parses and schema-checks a bundle as the agent does on import.
+fn parse_bundle(json: &[u8]) -> anyhow::Result<ThreatFeedBundle> {
+  let bundle: ThreatFeedBundle = serde_json::from_slice(json).context("parse bundle JSON")?;
+  threat_feed::validate_bundle_schema(&bundle)?;
+  Ok(bundle)
+}
+
+fn read_secret_key(path: &Path) -> anyhow::Result<SigningKey> {
+  let text = fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+  let seed: [u8; 32] = URL_SAFE_NO_PAD
+    .decode(text.trim())
+    .context("decode secret key base64url")?
+    .try_into()
+    .map_err(|_| anyhow::anyhow!("secret key must be 32 bytes"))?;
+  Ok(SigningKey::from_bytes(&seed))
+}
+
+/// The base64url signature of `json`, in the form `bundle.sig` is read back.
+fn sign_bytes(key: &SigningKey, json: &[u8]) -> anyhow::Result<String> {
+  parse_bundle(json)?;
+  Ok(URL_SAFE_NO_PAD.encode(key.sign(json).to_bytes()))
+}
+
+fn sign(bundle: &Path, key: &Path, out: &Path) -> anyhow::Result<()> {
+  let key = read_secret_key(key)?;
+  let json = fs::read(bundle).with_context(|| format!("read {}", bundle.display()))?;
+  write_atomic(out, sign_bytes(&key, &json)?)?;
+  println!(
+    "Signed {} with key {}; signature: {}",
+    bundle.display(),
+    fingerprint(&key.verifying_key()),
+    out.display()
+  );
+  Ok(())
+}
+
+fn verify(bundle: &Path, sig: &Path, key: &str) -> anyhow::Result<()> {
+  let key = match fs::read_to_string(key) {
+    Ok(text) => parse_public_key(&text),
+    Err(_) => parse_public_key(key),
+  }
+  .context("public key (file or base64url)")?;
+  let json = fs::read(bundle).with_context(|| format!("read {}", bundle.display()))?;
+  let sig_raw = fs::read(sig).with_context(|| format!("read {}", sig.display()))?;
+  verify_strict(&key, &json, &decode_signature(&sig_raw)?)?;
+  let parsed = parse_bundle(&json)?;
+  println!(
+    "OK: bundle {} signed by {}",
+    parsed.bundle_id,
+    fingerprint(&key)
+  );
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn signs_only_valid_bundles_and_signatures_verify() {
+    let key = SigningKey::from_bytes(&[9u8; 32]);
+    let bundle = br#"{"version":1,"bundle_id":"7a0d3c52-6a1b-4f7e-9a51-7b8c1f0e2d34","created_at":1700000000,"rules_version":3,"reputation":{}}"#;
+    let sig = sign_bytes(&key, bundle).unwrap();
+    let sig = decode_signature(sig.as_bytes()).unwrap();
+    assert!(verify_strict(&key.verifying_key(), bundle, &sig).is_ok());
+
+    let bad_version =
+      br#"{"version":2,"bundle_id":"x","created_at":1,"rules_version":1,"reputation":{}}"#;
+    assert!(sign_bytes(&key, bad_version).is_err());
+  }
+}
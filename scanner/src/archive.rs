@@ -0,0 +1,535 @@
+use agent_core::config::ArchiveScanConfig;
+use agent_core::types::{now_unix_ms, Evidence, Finding, Severity};
+use agent_core::user_profiles;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use walkdir::WalkDir;
+
+// Archive inspection (`[scanner.archives]`). Malware often arrives as a script or executable inside
+// a zip in Downloads, which the executable checks never see. Zip archives are listed by reading
+// their central directory (nothing is decompressed); 7z archives are listed with the system
+// `tar` (bsdtar, shipped with Windows 10 1803 and later). With `deep_extract`, `tar` extracts the
+// archive into a temporary sandbox directory so the executables can be hashed and
+// signature-checked and nested archives opened; the directory is removed afterwards.
+
+const S009: &str = "S009";
+
+/// Entry names quoted per finding; the count is always reported.
+const MAX_LISTED_ENTRIES: usize = 20;
+
+/// Central directories beyond this are not read.
+const MAX_CENTRAL_DIRECTORY_BYTES: u64 = 64 * 1024 * 1024;
+
+/// What runs when double-clicked inside an archive, beyond the scanner's own candidates.
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+  "exe", "dll", "sys", "ps1", "js", "jse", "vbs", "vbe", "wsf", "hta", "bat", "cmd", "com", "scr",
+  "pif", "lnk", "msi", "cpl", "jar",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+  Zip,
+  SevenZip,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ArchiveEntry {
+  name: String,
+  /// Uncompressed size, when the format records it.
+  size: Option<u64>,
+}
+
+fn archive_kind(path: &Path) -> Option<Kind> {
+  let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+  match ext.as_str() {
+    "zip" => Some(Kind::Zip),
+    "7z" => Some(Kind::SevenZip),
+    _ => None,
+  }
+}
+
+/// Archives worth opening: zip/7z files in Downloads, Desktop, Temp or AppData.
+pub fn is_candidate(path: &Path) -> bool {
+  archive_kind(path).is_some() && in_user_writable_location(path)
+}
+
+fn in_user_writable_location(path: &Path) -> bool {
+  if crate::is_user_writable_location(path) {
+    return true;
+  }
+  let p = path.to_string_lossy().to_ascii_lowercase();
+  user_profiles::all().iter().any(|u| {
+    let home = u.home.to_string_lossy().to_ascii_lowercase();
+    ["downloads", "desktop", "appdata"]
+      .iter()
+      .any(|dir| p.starts_with(&format!("{home}\\{dir}\\")))
+  })
+}
+
+/// S009 findings for `path` (an archive) and, with `deep_extract`, for archives nested in it.
+pub fn inspect(path: &Path, cfg: &ArchiveScanConfig) -> Vec<Finding> {
+  let mut findings = Vec::new();
+  inspect_at(path, &path.to_string_lossy(), cfg, 1, &mut findings);
+  findings
+}
+
+/// `label` is how the archive is named in findings (`outer.zip!inner.zip` when nested).
+fn inspect_at(
+  path: &Path,
+  label: &str,
+  cfg: &ArchiveScanConfig,
+  depth: usize,
+  findings: &mut Vec<Finding>,
+) {
+  let Some(kind) = archive_kind(path) else {
+    return;
+  };
+  let too_big = fs::metadata(path)
+    .map(|m| m.len() > cfg.max_archive_mb.saturating_mul(1024 * 1024))
+    .unwrap_or(true);
+  if too_big {
+    tracing::debug!(archive = %label, "archive too large or unreadable; skipped");
+    return;
+  }
+
+  let entries = match list(path, kind, cfg.max_entries) {
+    Ok(entries) => entries,
+    Err(e) => {
+      tracing::debug!(archive = %label, error = %e, "unable to list archive");
+      return;
+    }
+  };
+  let executables: Vec<&ArchiveEntry> = entries
+    .iter()
+    .filter(|e| is_executable_name(&e.name))
+    .collect();
+  let nested = entries
+    .iter()
+    .any(|e| archive_kind(Path::new(&e.name)).is_some());
+  let declared: u64 = entries.iter().filter_map(|e| e.size).sum();
+
+  let extract_limit = cfg.max_extract_mb.saturating_mul(1024 * 1024);
+  let extract = cfg.deep_extract
+    && (!executables.is_empty() || (nested && depth < cfg.max_depth.max(1)))
+    && declared <= extract_limit;
+  let sandbox = if extract {
+    match extract_to_sandbox(path, extract_limit) {
+      Ok(dir) => Some(dir),
+      Err(e) => {
+        tracing::warn!(archive = %label, error = %e, "archive extraction failed; listing only");
+        None
+      }
+    }
+  } else {
+    None
+  };
+
+  if !executables.is_empty() {
+    let mut evidence = vec![Evidence::Note {
+      message: format!(
+        "archive={label} executable_entries={} total_entries={}",
+        executables.len(),
+        entries.len()
+      ),
+    }];
+    for entry in executables.iter().take(MAX_LISTED_ENTRIES) {
+      let hashed = sandbox
+        .as_deref()
+        .map(|dir| dir.join(entry_rel_path(&entry.name)))
+        .filter(|p| p.is_file())
+        .and_then(|p| {
+          let sha256 = defender_common::hash::sha256_hex(&p).ok()?;
+          let signed = crate::signature::is_trusted_signed(&p).unwrap_or(false);
+          Some(format!(" sha256={sha256} signed={signed}"))
+        })
+        .unwrap_or_default();
+      evidence.push(Evidence::Note {
+        message: format!("entry={}{hashed}", entry.name),
+      });
+    }
+    findings.push(Finding {
+      rule_id: S009.to_string(),
+      severity: Severity::Yellow,
+      description: "Archive in a user-writable location contains executable content".to_string(),
+      evidence,
+      timestamp_unix_ms: now_unix_ms(),
+    });
+  }
+
+  if let Some(dir) = sandbox {
+    if depth < cfg.max_depth.max(1) {
+      for inner in WalkDir::new(&dir)
+        .follow_links(false)
+        .into_iter()
+        .flatten()
+        .filter(|e| e.file_type().is_file() && archive_kind(e.path()).is_some())
+      {
+        let rel = inner.path().strip_prefix(&dir).unwrap_or(inner.path());
+        let inner_label = format!("{label}!{}", rel.to_string_lossy());
+        inspect_at(inner.path(), &inner_label, cfg, depth + 1, findings);
+      }
+    }
+    if let Err(e) = fs::remove_dir_all(&dir) {
+      tracing::warn!(dir = %dir.display(), error = %e, "failed to remove archive sandbox");
+    }
+  }
+}
+
+fn is_executable_name(name: &str) -> bool {
+  Path::new(name)
+    .extension()
+    .and_then(|e| e.to_str())
+    .map(|e| EXECUTABLE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+    .unwrap_or(false)
+}
+
+/// An entry name as a relative path below the sandbox (`tar` drops `..` and leading slashes).
+fn entry_rel_path(name: &str) -> PathBuf {
+  name
+    .split(['/', '\\'])
+    .filter(|part| !part.is_empty() && *part != "." && *part != "..")
+    .collect()
+}
+
+fn list(path: &Path, kind: Kind, max_entries: usize) -> anyhow::Result<Vec<ArchiveEntry>> {
+  match kind {
+    Kind::Zip => list_zip(&mut fs::File::open(path)?, max_entries),
+    Kind::SevenZip => list_with_tar(path, max_entries),
+  }
+}
+
+/// Lists with `tar -tvf`, whose lines look like `ls -l`:
+/// `-rw-r--r--  0 0      0    1234 Jan  1  2020 dir/name.exe`.
+fn list_with_tar(path: &Path, max_entries: usize) -> anyhow::Result<Vec<ArchiveEntry>> {
+  let output = tar().arg("-tvf").arg(path).output()?;
+  if !output.status.success() {
+    anyhow::bail!(
+      "tar -tvf failed ({}): {}",
+      output.status,
+      String::from_utf8_lossy(&output.stderr).trim()
+    );
+  }
+  Ok(
+    String::from_utf8_lossy(&output.stdout)
+      .lines()
+      .filter(|l| !l.starts_with('d'))
+      .filter_map(|line| {
+        let (fields, name) = split_fields(line, 8)?;
+        Some(ArchiveEntry {
+          name: name.to_string(),
+          size: fields.get(4).and_then(|s| s.parse().ok()),
+        })
+      })
+      .take(max_entries)
+      .collect(),
+  )
+}
+
+/// The first `n` whitespace-separated fields of `line` and the rest (which may contain spaces).
+fn split_fields(line: &str, n: usize) -> Option<(Vec<&str>, &str)> {
+  let mut fields = Vec::with_capacity(n);
+  let mut rest = line.trim_start();
+  while fields.len() < n {
+    let end = rest.find(char::is_whitespace)?;
+    fields.push(&rest[..end]);
+    rest = rest[end..].trim_start();
+  }
+  (!rest.is_empty()).then_some((fields, rest))
+}
+
+/// Windows' own bsdtar, not whatever `tar` comes first on PATH.
+fn tar() -> Command {
+  #[cfg(windows)]
+  {
+    let root = std::env::var("SystemRoot").unwrap_or_else(|_| "C:\\Windows".to_string());
+    Command::new(PathBuf::from(root).join("System32\\tar.exe"))
+  }
+  #[cfg(not(windows))]
+  {
+    Command::new("tar")
+  }
+}
+
+/// Extracts `archive` into a new directory under `%TEMP%`. Fails (and cleans up) if the result is
+/// larger than `limit` bytes.
+fn extract_to_sandbox(archive: &Path, limit: u64) -> anyhow::Result<PathBuf> {
+  let dir = std::env::temp_dir().join(format!("ai-defender-archive-{}", uuid::Uuid::new_v4()));
+  fs::create_dir_all(&dir)?;
+  let result = tar().arg("-xf").arg(archive).arg("-C").arg(&dir).output();
+  let extracted: u64 = WalkDir::new(&dir)
+    .follow_links(false)
+    .into_iter()
+    .flatten()
+    .filter_map(|e| e.metadata().ok())
+    .filter(|m| m.is_file())
+    .map(|m| m.len())
+    .sum();
+
+  let failure = match result {
+    Err(e) => Some(anyhow::anyhow!("unable to run tar: {e}")),
+    Ok(out) if !out.status.success() => Some(anyhow::anyhow!(
+      "tar -xf failed ({}): {}",
+      out.status,
+      String::from_utf8_lossy(&out.stderr).trim()
+    )),
+    Ok(_) if extracted > limit => Some(anyhow::anyhow!(
+      "extracted {extracted} bytes, more than the {limit} byte limit"
+    )),
+    Ok(_) => None,
+  };
+  match failure {
+    Some(e) => {
+      let _ = fs::remove_dir_all(&dir);
+      Err(e)
+    }
+    None => Ok(dir),
+  }
+}
+
+// ZIP central directory parsing (APPNOTE 6.3): the end-of-central-directory record (and its
+// ZIP64 variant) locates the central directory, whose file headers carry names and sizes.
+
+const EOCD_SIG: u32 = 0x0605_4b50;
+const ZIP64_EOCD_LOCATOR_SIG: u32 = 0x0706_4b50;
+const ZIP64_EOCD_SIG: u32 = 0x0606_4b50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+const EOCD_LEN: u64 = 22;
+const MAX_COMMENT_LEN: u64 = 0xFFFF;
+
+fn list_zip<R: Read + Seek>(r: &mut R, max_entries: usize) -> anyhow::Result<Vec<ArchiveEntry>> {
+  let file_len = r.seek(SeekFrom::End(0))?;
+  if file_len < EOCD_LEN {
+    anyhow::bail!("too small to be a zip archive");
+  }
+
+  // The EOCD record is at the end, followed only by an optional comment.
+  let tail_len = file_len.min(EOCD_LEN + MAX_COMMENT_LEN);
+  let tail_start = file_len - tail_len;
+  let tail = read_at(r, tail_start, tail_len)?;
+  let eocd_pos = (0..=tail.len() - EOCD_LEN as usize)
+    .rev()
+    .find(|&i| le32(&tail, i) == EOCD_SIG)
+    .ok_or_else(|| anyhow::anyhow!("no end of central directory record"))?;
+  let eocd = &tail[eocd_pos..];
+
+  let mut total = u64::from(le16(eocd, 10));
+  let mut cd_size = u64::from(le32(eocd, 12));
+  let mut cd_offset = u64::from(le32(eocd, 16));
+
+  if total == 0xFFFF || cd_size == 0xFFFF_FFFF || cd_offset == 0xFFFF_FFFF {
+    let locator_pos = tail_start + eocd_pos as u64;
+    let locator = locator_pos
+      .checked_sub(20)
+      .map(|at| read_at(r, at, 20))
+      .transpose()?
+      .filter(|l| le32(l, 0) == ZIP64_EOCD_LOCATOR_SIG)
+      .ok_or_else(|| anyhow::anyhow!("ZIP64 end of central directory locator missing"))?;
+    let zip64 = read_at(r, le64(&locator, 8), 56)?;
+    if le32(&zip64, 0) != ZIP64_EOCD_SIG {
+      anyhow::bail!("bad ZIP64 end of central directory record");
+    }
+    total = le64(&zip64, 32);
+    cd_size = le64(&zip64, 40);
+    cd_offset = le64(&zip64, 48);
+  }
+
+  if cd_size > MAX_CENTRAL_DIRECTORY_BYTES || cd_offset.saturating_add(cd_size) > file_len {
+    anyhow::bail!("central directory out of bounds");
+  }
+  let cd = read_at(r, cd_offset, cd_size)?;
+
+  let mut entries = Vec::new();
+  let mut pos = 0usize;
+  while entries.len() < max_entries && (entries.len() as u64) < total && pos + 46 <= cd.len() {
+    if le32(&cd, pos) != CENTRAL_HEADER_SIG {
+      anyhow::bail!("bad central directory header");
+    }
+    let mut size = u64::from(le32(&cd, pos + 24));
+    let name_len = usize::from(le16(&cd, pos + 28));
+    let extra_len = usize::from(le16(&cd, pos + 30));
+    let comment_len = usize::from(le16(&cd, pos + 32));
+    let name_start = pos + 46;
+    let extra_start = name_start + name_len;
+    let next = extra_start + extra_len + comment_len;
+    if next > cd.len() {
+      anyhow::bail!("truncated central directory");
+    }
+
+    if size == 0xFFFF_FFFF {
+      size = zip64_uncompressed_size(&cd[extra_start..extra_start + extra_len]).unwrap_or(size);
+    }
+    let name = String::from_utf8_lossy(&cd[name_start..extra_start]).to_string();
+    if !name.ends_with('/') {
+      entries.push(ArchiveEntry {
+        name,
+        size: Some(size),
+      });
+    }
+    pos = next;
+  }
+  Ok(entries)
+}
+
+/// The uncompressed size from a ZIP64 extended information extra field (always its first value).
+fn zip64_uncompressed_size(extra: &[u8]) -> Option<u64> {
+  let mut pos = 0;
+  while pos + 4 <= extra.len() {
+    let id = le16(extra, pos);
+    let len = usize::from(le16(extra, pos + 2));
+    if id == 0x0001 && len >= 8 && pos + 12 <= extra.len() {
+      return Some(le64(extra, pos + 4));
+    }
+    pos += 4 + len;
+  }
+  None
+}
+
+fn read_at<R: Read + Seek>(r: &mut R, offset: u64, len: u64) -> anyhow::Result<Vec<u8>> {
+  r.seek(SeekFrom::Start(offset))?;
+  let mut buf = vec![0u8; usize::try_from(len)?];
+  r.read_exact(&mut buf)?;
+  Ok(buf)
+}
+
+fn le16(b: &[u8], at: usize) -> u16 {
+  u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+  u32::from_le_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]])
+}
+
+fn le64(b: &[u8], at: usize) -> u64 {
+  u64::from(le32(b, at)) | (u64::from(le32(b, at + 4)) << 32)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  /// A zip with a central directory header per `(name, compressed size, uncompressed size)`; the
+  /// local headers and data aren't read by `list_zip`, so a few filler bytes stand in for them.
+  fn zip(entries: &[(&str, u32, u32)], declared_total: u16) -> Vec<u8> {
+    let mut out = vec![0u8; 30];
+    let cd_offset = out.len() as u32;
+    for (name, compressed, size) in entries {
+      out.extend(CENTRAL_HEADER_SIG.to_le_bytes());
+      out.extend([0u8; 16]);
+      out.extend(compressed.to_le_bytes());
+      out.extend(size.to_le_bytes());
+      out.extend((name.len() as u16).to_le_bytes());
+      out.extend([0u8; 16]);
+      out.extend(name.as_bytes());
+    }
+    let cd_size = out.len() as u32 - cd_offset;
+    out.extend(EOCD_SIG.to_le_bytes());
+    out.extend([0u8; 4]);
+    out.extend(declared_total.to_le_bytes());
+    out.extend(declared_total.to_le_bytes());
+    out.extend(cd_size.to_le_bytes());
+    out.extend(cd_offset.to_le_bytes());
+    out.extend([0u8; 2]);
+    out
+  }
+
+  fn list(bytes: Vec<u8>, max_entries: usize) -> anyhow::Result<Vec<ArchiveEntry>> {
+    list_zip(&mut Cursor::new(bytes), max_entries)
+  }
+
+  fn names(entries: &[ArchiveEntry]) -> Vec<&str> {
+    entries.iter().map(|e| e.name.as_str()).collect()
+  }
+
+  #[test]
+  fn lists_central_directory_entries() {
+    let entries = list(
+      zip(
+        &[
+          ("docs/", 0, 0),
+          ("docs/invoice.js", 10, 20),
+          ("readme.txt", 5, 5),
+        ],
+        3,
+      ),
+      100,
+    )
+    .unwrap();
+    assert_eq!(names(&entries), ["docs/invoice.js", "readme.txt"]);
+    assert_eq!(entries[0].size, Some(20));
+    assert!(is_executable_name(&entries[0].name) && !is_executable_name(&entries[1].name));
+  }
+
+  #[test]
+  fn rejects_truncated_and_malformed_archives() {
+    let good = zip(&[("a.exe", 1, 1)], 1);
+    assert!(list(Vec::new(), 100).is_err());
+    assert!(list(vec![0x50; 64], 100).is_err());
+    // Cut off before the end record, and inside it.
+    assert!(list(good[..40].to_vec(), 100).is_err());
+    assert!(list(good[..good.len() - 4].to_vec(), 100).is_err());
+
+    // A header whose name runs past the central directory.
+    let mut bad = good.clone();
+    bad[30 + 28] = 0xFF;
+    assert!(list(bad, 100)
+      .unwrap_err()
+      .to_string()
+      .contains("truncated"));
+    // A corrupt header signature.
+    let mut bad = good.clone();
+    bad[30] = 0;
+    assert!(list(bad, 100).is_err());
+    // A central directory offset past the end of the file.
+    let mut bad = good;
+    let at = bad.len() - 6;
+    bad[at..at + 4].copy_from_slice(&0x7FFF_FFFFu32.to_le_bytes());
+    assert!(list(bad, 100).is_err());
+  }
+
+  #[test]
+  fn entry_counts_are_bounded() {
+    let entries = [("a.exe", 1, 1), ("b.exe", 1, 1), ("c.exe", 1, 1)];
+    // More entries declared than present: only the real headers are read.
+    assert_eq!(list(zip(&entries, 0xFFFE), 100).unwrap().len(), 3);
+    // Fewer declared than present: the declared count wins.
+    assert_eq!(names(&list(zip(&entries, 1), 100).unwrap()), ["a.exe"]);
+    // `max_entries` caps the listing.
+    assert_eq!(list(zip(&entries, 3), 2).unwrap().len(), 2);
+    // 0xFFFF means ZIP64, which needs a locator this archive doesn't have.
+    assert!(list(zip(&entries, 0xFFFF), 100).is_err());
+  }
+
+  #[test]
+  fn oversized_sizes_are_reported_not_trusted() {
+    // Declared sizes far beyond the file are listed as declared, so the extraction limit sees
+    // them; they are never used to read anything.
+    let entries = list(zip(&[("big.exe", 0xFFFF_FFFE, 0xFFFF_FFFE)], 1), 100).unwrap();
+    assert_eq!(entries[0].size, Some(0xFFFF_FFFE));
+    // 0xFFFFFFFF without a ZIP64 extra field keeps the sentinel rather than failing.
+    let entries = list(zip(&[("big.exe", 0xFFFF_FFFF, 0xFFFF_FFFF)], 1), 100).unwrap();
+    assert_eq!(entries[0].size, Some(0xFFFF_FFFF));
+
+    // A central directory larger than `MAX_CENTRAL_DIRECTORY_BYTES` is not read.
+    let mut bad = zip(&[("a.exe", 1, 1)], 1);
+    let at = bad.len() - 10;
+    let cd_size = MAX_CENTRAL_DIRECTORY_BYTES as u32 + 1;
+    bad[at..at + 4].copy_from_slice(&cd_size.to_le_bytes());
+    assert!(list(bad, 100)
+      .unwrap_err()
+      .to_string()
+      .contains("out of bounds"));
+  }
+
+  #[test]
+  fn zip64_extra_field_carries_the_real_size() {
+    let mut extra = 0x0001u16.to_le_bytes().to_vec();
+    extra.extend(8u16.to_le_bytes());
+    extra.extend((5u64 << 32).to_le_bytes());
+    assert_eq!(zip64_uncompressed_size(&extra), Some(5u64 << 32));
+    // A field claiming more bytes than it has is ignored.
+    assert_eq!(zip64_uncompressed_size(&extra[..10]), None);
+  }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// Catalog of every executable candidate a completed quick or full scan hashed, kept per mode so
+// `--diff` can report only what is new, changed or removed since the previous run. A full scan catalogs a
+// few hundred thousand files, so it is one tab-separated line per file rather than TOML:
+//
+//   <sha256>\t<signed 0|1>\t<path>
+//
+// Paths are compared case-insensitively, like Windows does.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+  pub sha256: String,
+  pub signed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+  New,
+  Changed { previous: Entry },
+}
+
+#[derive(Debug, Default)]
+pub struct Catalog {
+  files: HashMap<String, (String, Entry)>,
+}
+
+impl Catalog {
+  /// The catalog saved at `path`, or `None` if there isn't one yet.
+  pub fn load(path: &Path) -> Option<Self> {
+    let raw = fs::read_to_string(path).ok()?;
+    let mut catalog = Self::default();
+    for line in raw.lines() {
+      let mut parts = line.splitn(3, '\t');
+      let (Some(sha256), Some(signed), Some(file)) = (parts.next(), parts.next(), parts.next())
+      else {
+        continue;
+      };
+      catalog.insert(
+        file,
+        Entry {
+          sha256: sha256.to_string(),
+          signed: signed == "1",
+        },
+      );
+    }
+    Some(catalog)
+  }
+
+  pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+    let parent = path
+      .parent()
+      .ok_or_else(|| anyhow::anyhow!("file path has no parent: {}", path.display()))?;
+    fs::create_dir_all(parent)?;
+    let tmp = parent.join(format!(
+      ".{}.tmp",
+      path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    {
+      let mut out = BufWriter::new(fs::File::create(&tmp)?);
+      for (file, entry) in self.files.values() {
+        writeln!(
+          out,
+          "{}\t{}\t{file}",
+          entry.sha256,
+          if entry.signed { 1 } else { 0 }
+        )?;
+      }
+      out.flush()?;
+    }
+    fs::rename(&tmp, path)?;
+    Ok(())
+  }
+
+  pub fn insert(&mut self, file: &str, entry: Entry) {
+    // Tabs and newlines would corrupt the line format; such names aren't worth cataloging.
+    if file.contains(['\t', '\n', '\r']) {
+      return;
+    }
+    self
+      .files
+      .insert(file.to_lowercase(), (file.to_string(), entry));
+  }
+
+  /// How `file` differs from this (previous) catalog, if it does.
+  pub fn compare(&self, file: &str, entry: &Entry) -> Option<Change> {
+    match self.files.get(&file.to_lowercase()) {
+      None => Some(Change::New),
+      Some((_, previous)) if previous != entry => Some(Change::Changed {
+        previous: previous.clone(),
+      }),
+      Some(_) => None,
+    }
+  }
+
+  /// Files in this (previous) catalog that `current` doesn't have and that no longer exist. Files
+  /// still on disk but left out of `current` (excluded, or unreadable this time) aren't removed.
+  pub fn removed(&self, current: &Catalog) -> Vec<String> {
+    let mut removed: Vec<String> = self
+      .files
+      .iter()
+      .filter(|(key, _)| !current.files.contains_key(*key))
+      .map(|(_, (file, _))| file)
+      .filter(|file| !Path::new(file).exists())
+      .cloned()
+      .collect();
+    removed.sort();
+    removed
+  }
+
+  pub fn len(&self) -> usize {
+    self.files.len()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(sha256: &str, signed: bool) -> Entry {
+    Entry {
+      sha256: sha256.to_string(),
+      signed,
+    }
+  }
+
+  #[test]
+  fn compare_reports_new_and_changed_entries() {
+    let mut previous = Catalog::default();
+    previous.insert("C:\\Tools\\Tool.exe", entry("aa", true));
+    previous.insert("C:\\Tools\\Other.exe", entry("bb", false));
+
+    assert_eq!(
+      previous.compare("c:\\tools\\tool.exe", &entry("aa", true)),
+      None
+    );
+    assert_eq!(
+      previous.compare("C:\\Tools\\New.exe", &entry("aa", true)),
+      Some(Change::New)
+    );
+    assert_eq!(
+      previous.compare("C:\\Tools\\Tool.exe", &entry("cc", true)),
+      Some(Change::Changed {
+        previous: entry("aa", true)
+      })
+    );
+    // A signature that is no longer valid is a change too.
+    assert_eq!(
+      previous.compare("C:\\Tools\\Other.exe", &entry("bb", true)),
+      Some(Change::Changed {
+        previous: entry("bb", false)
+      })
+    );
+  }
+
+  #[test]
+  fn removed_lists_files_gone_from_disk() {
+    let base = std::env::temp_dir().join(format!("aid-catalog-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&base).unwrap();
+    let kept = base.join("kept.exe");
+    let excluded = base.join("excluded.exe");
+    fs::write(&kept, b"MZ").unwrap();
+    fs::write(&excluded, b"MZ").unwrap();
+    let gone = base.join("gone.exe").to_string_lossy().to_string();
+
+    let mut previous = Catalog::default();
+    previous.insert(&kept.to_string_lossy(), entry("aa", true));
+    previous.insert(&excluded.to_string_lossy(), entry("bb", true));
+    previous.insert(&gone, entry("cc", false));
+    let mut current = Catalog::default();
+    current.insert(&kept.to_string_lossy().to_uppercase(), entry("aa", true));
+
+    assert_eq!(previous.removed(&current), std::slice::from_ref(&gone));
+
+    // Saved and loaded, the catalog keeps the original spelling of paths.
+    let path = base.join("catalog-quick.tsv");
+    previous.save(&path).unwrap();
+    let loaded = Catalog::load(&path).unwrap();
+    assert_eq!(loaded.len(), 3);
+    assert_eq!(loaded.removed(&current), [gone]);
+    assert_eq!(
+      loaded.compare(&excluded.to_string_lossy(), &entry("bb", true)),
+      None
+    );
+    let _ = fs::remove_dir_all(&base);
+  }
+}
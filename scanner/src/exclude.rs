@@ -0,0 +1,149 @@
+use agent_core::config::ScannerConfig;
+use anyhow::Context;
+use defender_common::glob;
+use std::collections::HashSet;
+use std::path::Path;
+
+// What a scan skips, from `--exclude` / `--exclude-hash` / `--exclude-hashes-file` and the
+// `[scanner]` exclude settings. Paths are case-insensitive prefixes, or globs when they contain
+// `*` or `?` (see `defender_common::glob`). Hash files hold one SHA-256 per line; `#` starts a
+// comment and anything after the digest is ignored, so `sha256sum` output works as is.
+
+#[derive(Debug, Default)]
+pub struct Excludes {
+  prefixes: Vec<String>,
+  globs: Vec<String>,
+  hashes: HashSet<String>,
+}
+
+impl Excludes {
+  pub fn load(
+    paths: &[String],
+    hashes: &[String],
+    hashes_file: Option<&str>,
+    cfg: &ScannerConfig,
+  ) -> anyhow::Result<Self> {
+    let mut ex = Excludes::default();
+    for p in paths.iter().chain(&cfg.exclude) {
+      let p = p.trim();
+      if p.is_empty() {
+        continue;
+      }
+      if glob::is_glob(p) {
+        ex.globs.push(p.to_string());
+      } else {
+        ex.prefixes.push(p.to_ascii_lowercase());
+      }
+    }
+
+    for h in hashes {
+      if !is_sha256(h.trim()) {
+        anyhow::bail!("--exclude-hash expects a SHA-256 hex digest, got `{h}`");
+      }
+      ex.hashes.insert(h.trim().to_ascii_lowercase());
+    }
+    // Invalid config entries are reported by config validation.
+    ex.hashes.extend(
+      cfg
+        .exclude_hashes
+        .iter()
+        .map(|h| h.trim().to_ascii_lowercase())
+        .filter(|h| is_sha256(h)),
+    );
+    for file in hashes_file
+      .into_iter()
+      .chain(cfg.exclude_hashes_file.as_deref())
+    {
+      let raw = std::fs::read_to_string(file)
+        .with_context(|| format!("unable to read hash exclusion file {file}"))?;
+      ex.hashes.extend(parse_hash_list(&raw, file)?);
+    }
+    Ok(ex)
+  }
+
+  pub fn path(&self, path: &Path) -> bool {
+    let p = path.to_string_lossy();
+    let lower = p.to_ascii_lowercase();
+    self.prefixes.iter().any(|ex| lower.starts_with(ex))
+      || self.globs.iter().any(|g| glob::matches(g, &p))
+  }
+
+  pub fn hash(&self, sha256: &str) -> bool {
+    self.hashes.contains(&sha256.to_ascii_lowercase())
+  }
+}
+
+fn parse_hash_list(raw: &str, file: &str) -> anyhow::Result<Vec<String>> {
+  let mut hashes = Vec::new();
+  for (n, line) in raw.lines().enumerate() {
+    let line = line.split('#').next().unwrap_or_default();
+    let Some(digest) = line.split_whitespace().next() else {
+      continue;
+    };
+    if !is_sha256(digest) {
+      anyhow::bail!("{file}:{}: not a SHA-256 hex digest: `{digest}`", n + 1);
+    }
+    hashes.push(digest.to_ascii_lowercase());
+  }
+  Ok(hashes)
+}
+
+fn is_sha256(s: &str) -> bool {
+  s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn paths_match_prefixes_and_globs_case_insensitively() {
+    let cfg = ScannerConfig {
+      exclude: vec!["C:\\Dev\\**\\target\\**".to_string(), "  ".to_string()],
+      ..ScannerConfig::default()
+    };
+    let ex = Excludes::load(
+      &["d:\\games\\".to_string(), "C:\\Tools\\*.exe".to_string()],
+      &[],
+      None,
+      &cfg,
+    )
+    .unwrap();
+    assert!(ex.path(Path::new("D:\\Games\\Steam\\game.exe")));
+    assert!(ex.path(Path::new("c:\\tools\\Build.EXE")));
+    assert!(!ex.path(Path::new("C:\\Tools\\sub\\build.exe")));
+    assert!(ex.path(Path::new("C:\\Dev\\app\\crates\\x\\target\\debug\\x.exe")));
+    assert!(!ex.path(Path::new("C:\\Dev\\app\\src\\main.exe")));
+    assert!(!ex.path(Path::new("C:\\Users\\User\\Downloads\\setup.exe")));
+  }
+
+  #[test]
+  fn hashes_come_from_flags_config_and_hash_files() {
+    let digest = |c: char| c.to_string().repeat(64);
+    let file = std::env::temp_dir().join(format!("aid-exclude-{}.txt", uuid::Uuid::new_v4()));
+    std::fs::write(
+      &file,
+      format!(
+        "# known good\n{}  tool.exe\n\n{} # comment\n",
+        digest('c'),
+        digest('D')
+      ),
+    )
+    .unwrap();
+    let cfg = ScannerConfig {
+      exclude_hashes: vec![digest('b'), "not-a-hash".to_string()],
+      ..ScannerConfig::default()
+    };
+    let ex = Excludes::load(&[], &[digest('A')], file.to_str(), &cfg).unwrap();
+    for c in ['a', 'B', 'c', 'd'] {
+      assert!(ex.hash(&digest(c)), "{c}");
+    }
+    assert!(!ex.hash(&digest('e')));
+
+    assert!(Excludes::load(&[], &["abc".to_string()], None, &cfg).is_err());
+    std::fs::write(&file, format!("{}\nabc\n", digest('c'))).unwrap();
+    let err = Excludes::load(&[], &[], file.to_str(), &cfg).unwrap_err();
+    assert!(err.to_string().contains(":2:"), "{err}");
+    let _ = std::fs::remove_file(&file);
+  }
+}
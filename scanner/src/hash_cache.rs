@@ -0,0 +1,227 @@
+use crate::catalog::Entry;
+use agent_core::types::now_unix_ms;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+// Hash and signature results of earlier scans, so repeated scans skip files that haven't changed.
+// An entry is only reused while the file's size and modification time match what was recorded.
+// The whole cache is dropped after `MAX_AGE_MS`, so signatures are re-verified now and then (a
+// certificate may have been revoked since). Same line format idea as the catalog:
+//
+//   # hash-cache v2 created=<unix ms>
+//   <size>\t<mtime ns>\t<sha256>\t<signed 0|1>\t<path>
+//
+// Paths are compared case-insensitively, like Windows does.
+
+// v2: `signed` also covers catalog signatures, so v1 results are recomputed.
+const HEADER_PREFIX: &str = "# hash-cache v2 created=";
+const MAX_AGE_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+
+/// What identifies an unchanged file: its size and modification time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+  size: u64,
+  mtime_ns: u128,
+}
+
+impl Stamp {
+  pub fn of(meta: &fs::Metadata) -> Option<Self> {
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(Self {
+      size: meta.len(),
+      mtime_ns: mtime.as_nanos(),
+    })
+  }
+}
+
+#[derive(Debug)]
+pub struct HashCache {
+  created_at_unix_ms: u64,
+  files: HashMap<String, (String, Stamp, Entry)>,
+  pub hits: u64,
+  pub misses: u64,
+}
+
+impl Default for HashCache {
+  fn default() -> Self {
+    Self {
+      created_at_unix_ms: now_unix_ms(),
+      files: HashMap::new(),
+      hits: 0,
+      misses: 0,
+    }
+  }
+}
+
+impl HashCache {
+  /// The cache saved at `path`; empty if there is none, it can't be read, or it has expired.
+  pub fn load(path: &Path) -> Self {
+    let Ok(raw) = fs::read_to_string(path) else {
+      return Self::default();
+    };
+    let mut lines = raw.lines();
+    let created = lines
+      .next()
+      .and_then(|l| l.strip_prefix(HEADER_PREFIX))
+      .and_then(|v| v.trim().parse::<u64>().ok());
+    let Some(created) = created.filter(|c| now_unix_ms().saturating_sub(*c) < MAX_AGE_MS) else {
+      tracing::info!("scan hash cache missing a header or expired; starting over");
+      return Self::default();
+    };
+
+    let mut cache = Self {
+      created_at_unix_ms: created,
+      ..Self::default()
+    };
+    for line in lines {
+      let mut parts = line.splitn(5, '\t');
+      let (Some(size), Some(mtime), Some(sha256), Some(signed), Some(file)) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+      ) else {
+        continue;
+      };
+      let (Ok(size), Ok(mtime_ns)) = (size.parse(), mtime.parse()) else {
+        continue;
+      };
+      cache.insert(
+        file,
+        Stamp { size, mtime_ns },
+        Entry {
+          sha256: sha256.to_string(),
+          signed: signed == "1",
+        },
+      );
+    }
+    cache
+  }
+
+  pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+    let parent = path
+      .parent()
+      .ok_or_else(|| anyhow::anyhow!("file path has no parent: {}", path.display()))?;
+    fs::create_dir_all(parent)?;
+    let tmp = parent.join(format!(
+      ".{}.tmp",
+      path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    {
+      let mut out = BufWriter::new(fs::File::create(&tmp)?);
+      writeln!(out, "{HEADER_PREFIX}{}", self.created_at_unix_ms)?;
+      for (file, stamp, entry) in self.files.values() {
+        writeln!(
+          out,
+          "{}\t{}\t{}\t{}\t{file}",
+          stamp.size,
+          stamp.mtime_ns,
+          entry.sha256,
+          if entry.signed { 1 } else { 0 }
+        )?;
+      }
+      out.flush()?;
+    }
+    fs::rename(&tmp, path)?;
+    Ok(())
+  }
+
+  /// The recorded result for `file` if it still has the same size and modification time.
+  pub fn get(&mut self, file: &str, stamp: Stamp) -> Option<Entry> {
+    let found = match self.files.get(&file.to_lowercase()) {
+      Some((_, recorded, entry)) if *recorded == stamp => Some(entry.clone()),
+      _ => None,
+    };
+    if found.is_some() {
+      self.hits += 1;
+    } else {
+      self.misses += 1;
+    }
+    found
+  }
+
+  pub fn insert(&mut self, file: &str, stamp: Stamp, entry: Entry) {
+    // Tabs and newlines would corrupt the line format; such names aren't worth caching.
+    if file.contains(['\t', '\n', '\r']) {
+      return;
+    }
+    self
+      .files
+      .insert(file.to_lowercase(), (file.to_string(), stamp, entry));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entry(sha256: &str) -> Entry {
+    Entry {
+      sha256: sha256.to_string(),
+      signed: true,
+    }
+  }
+
+  #[test]
+  fn entries_are_reused_only_while_size_and_mtime_match() {
+    let stamp = Stamp {
+      size: 10,
+      mtime_ns: 1_000,
+    };
+    let mut cache = HashCache::default();
+    cache.insert("C:\\Tools\\Tool.exe", stamp, entry("aa"));
+    assert_eq!(cache.get("c:\\tools\\tool.exe", stamp), Some(entry("aa")));
+
+    let resized = Stamp { size: 11, ..stamp };
+    let touched = Stamp {
+      mtime_ns: 2_000,
+      ..stamp
+    };
+    assert_eq!(cache.get("C:\\Tools\\Tool.exe", resized), None);
+    assert_eq!(cache.get("C:\\Tools\\Tool.exe", touched), None);
+    assert_eq!((cache.hits, cache.misses), (1, 2));
+
+    // A rescan records the new result, which then replaces the old one.
+    cache.insert("C:\\Tools\\Tool.exe", touched, entry("bb"));
+    assert_eq!(cache.get("C:\\Tools\\Tool.exe", touched), Some(entry("bb")));
+    assert_eq!(cache.get("C:\\Tools\\Tool.exe", stamp), None);
+  }
+
+  #[test]
+  fn saved_cache_is_invalidated_when_the_file_changes() {
+    let base = std::env::temp_dir().join(format!("aid-hash-cache-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&base).unwrap();
+    let file = base.join("tool.exe");
+    fs::write(&file, b"MZ").unwrap();
+    let name = file.to_string_lossy().to_string();
+    let stamp = Stamp::of(&fs::metadata(&file).unwrap()).unwrap();
+
+    let mut cache = HashCache::default();
+    cache.insert(&name, stamp, entry("aa"));
+    cache.insert("C:\\bad\tname.exe", stamp, entry("bb"));
+    let cache_path = base.join("cache").join("hash-cache.txt");
+    cache.save(&cache_path).unwrap();
+
+    let mut cache = HashCache::load(&cache_path);
+    assert_eq!(cache.files.len(), 1);
+    assert_eq!(cache.get(&name, stamp), Some(entry("aa")));
+
+    fs::write(&file, b"MZ and more").unwrap();
+    let changed = Stamp::of(&fs::metadata(&file).unwrap()).unwrap();
+    assert_ne!(changed, stamp);
+    assert_eq!(cache.get(&name, changed), None);
+
+    // Expired and headerless caches start over.
+    let expired = now_unix_ms() - MAX_AGE_MS - 1;
+    let line = format!("{}\t{}\taa\t1\t{name}", stamp.size, stamp.mtime_ns);
+    fs::write(&cache_path, format!("{HEADER_PREFIX}{expired}\n{line}\n")).unwrap();
+    assert!(HashCache::load(&cache_path).files.is_empty());
+    fs::write(&cache_path, format!("{line}\n")).unwrap();
+    assert!(HashCache::load(&cache_path).files.is_empty());
+    let _ = fs::remove_dir_all(&base);
+  }
+}
@@ -1,17 +1,36 @@
+mod archive;
+mod catalog;
+mod exclude;
+mod hash_cache;
+mod persistence;
+mod progress;
 mod signature;
+mod startup;
 
+use agent_core::config::{ScannerConfig, MAX_SCANNER_THREADS};
+use agent_core::scan_history::{self, ScanOutcome, ScanRecord};
+use agent_core::scan_ipc::{self, ScanProgress};
+use agent_core::threat_feed::schema::{ThreatFeedBundle, WalletDrainPattern};
 use agent_core::types::{now_unix_ms, Evidence, Finding, Incident, Severity};
-use sha2::{Digest, Sha256};
+use agent_core::{browser_extensions, browser_profiles, motw, wallet_drain};
+use catalog::{Catalog, Change};
+use defender_common::hash::sha256_hex;
+use exclude::Excludes;
+use hash_cache::HashCache;
+use serde_json::json;
+use signature::DriveKind;
 use std::collections::HashSet;
 use std::fs;
-use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use walkdir::WalkDir;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ScanMode {
   Quick,
   Full,
+  /// Folders named with `--path`, usually started by the agent for a YELLOW incident.
+  Targeted,
 }
 
 impl ScanMode {
@@ -22,121 +41,911 @@ impl ScanMode {
     if args.iter().any(|a| a == "--full") {
       return Ok(Self::Full);
     }
-    Err(anyhow::anyhow!("expected `--quick` or `--full`"))
+    if args.iter().any(|a| a == "--targeted") {
+      return Ok(Self::Targeted);
+    }
+    Err(anyhow::anyhow!(
+      "expected `--quick`, `--full`, or `--targeted --path <dir>`"
+    ))
+  }
+
+  pub fn label(self) -> &'static str {
+    match self {
+      Self::Quick => "quick",
+      Self::Full => "full",
+      Self::Targeted => "targeted",
+    }
+  }
+
+  /// Targeted scans stay close to the implicated folder instead of walking a whole profile.
+  fn max_depth(self) -> usize {
+    match self {
+      Self::Quick | Self::Full => usize::MAX,
+      Self::Targeted => TARGETED_MAX_DEPTH,
+    }
+  }
+}
+
+const TARGETED_MAX_DEPTH: usize = 3;
+
+/// How roots are walked, from `[scanner]`, `[scanner.full]` for full scans, and the command line.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WalkLimits {
+  /// 0 means no limit.
+  pub max_files_per_root: u64,
+  /// 0 means no limit.
+  pub max_hash_bytes: u64,
+  pub skip_reparse_points: bool,
+}
+
+impl WalkLimits {
+  fn new(mode: ScanMode, scanner: &ScannerConfig, cfg: &ScanConfig) -> Self {
+    let full = (mode == ScanMode::Full).then_some(&scanner.full);
+    let max_hash_mb = cfg.max_hash_mb.unwrap_or_else(|| {
+      full
+        .map(|f| f.max_hash_mb)
+        .filter(|mb| *mb > 0)
+        .unwrap_or(scanner.max_file_mb)
+    });
+    Self {
+      // Only full scans walk whole drives.
+      max_files_per_root: full.map_or(0, |f| {
+        cfg.max_files_per_root.unwrap_or(f.max_files_per_root)
+      }),
+      max_hash_bytes: max_hash_mb.saturating_mul(1024 * 1024),
+      skip_reparse_points: !scanner.follow_reparse_points
+        && full.is_none_or(|f| f.skip_reparse_points),
+    }
+  }
+}
+
+/// Executables hashed per batch for each thread.
+const HASH_BATCH_PER_THREAD: usize = 8;
+
+/// `[scanner] threads` or `--threads`; 0 picks half the logical processors, 1 to 4.
+fn hash_threads(configured: usize) -> usize {
+  match configured {
+    0 => std::thread::available_parallelism().map_or(1, |n| (n.get() / 2).clamp(1, 4)),
+    n => n.min(MAX_SCANNER_THREADS),
+  }
+}
+
+/// An executable found by the walk, with its hash and signature status once known.
+struct Pending {
+  path: PathBuf,
+  file: String,
+  stamp: Option<hash_cache::Stamp>,
+  /// `result` came from the hash cache.
+  cached: bool,
+  result: Option<(Option<String>, bool)>,
+}
+
+/// Hashes and signature-checks the files in `batch` that the cache didn't have, on up to
+/// `threads` threads.
+fn hash_pending(batch: &mut [Pending], threads: usize) {
+  fn hash(p: &mut Pending) {
+    let sha256 = sha256_hex(&p.path).ok();
+    let signed = signature::is_trusted_signed(&p.path).unwrap_or(false);
+    p.result = Some((sha256, signed));
+  }
+  let mut todo: Vec<&mut Pending> = batch.iter_mut().filter(|p| p.result.is_none()).collect();
+  if threads <= 1 || todo.len() <= 1 {
+    todo.into_iter().for_each(hash);
+    return;
+  }
+  let per_thread = todo.len().div_ceil(threads);
+  std::thread::scope(|s| {
+    for chunk in todo.chunks_mut(per_thread) {
+      s.spawn(move || chunk.iter_mut().for_each(|p| hash(p)));
+    }
+  });
+}
+
+/// Entries under `root` without following links. With `skip_reparse`, reparse points below the
+/// root are left out together with anything beneath them.
+pub(crate) fn walk(
+  root: &Path,
+  max_depth: usize,
+  skip_reparse: bool,
+) -> impl Iterator<Item = walkdir::DirEntry> {
+  WalkDir::new(root)
+    .follow_links(false)
+    .max_depth(max_depth)
+    .into_iter()
+    .filter_entry(move |e| !(skip_reparse && e.depth() > 0 && signature::is_reparse_point(e)))
+    .flatten()
+}
+
+/// The files of one root in walk order, ending early once `max` files (0 = no limit) came out.
+/// Each root gets its own, so a limited root leaves the others their full count.
+struct RootFiles<I> {
+  entries: I,
+  max: u64,
+  files: u64,
+  limited: bool,
+}
+
+impl<I: Iterator<Item = walkdir::DirEntry>> RootFiles<I> {
+  fn new(entries: I, max: u64) -> Self {
+    Self {
+      entries,
+      max,
+      files: 0,
+      limited: false,
+    }
+  }
+}
+
+impl<I: Iterator<Item = walkdir::DirEntry>> Iterator for RootFiles<I> {
+  type Item = walkdir::DirEntry;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.limited {
+      return None;
+    }
+    let entry = self.entries.find(|e| !e.file_type().is_dir())?;
+    self.files += 1;
+    if self.max > 0 && self.files > self.max {
+      self.limited = true;
+      return None;
+    }
+    Some(entry)
   }
 }
 
 #[derive(Debug, Clone)]
 struct ScanConfig {
   excludes: Vec<String>,
+  exclude_hashes: Vec<String>,
+  exclude_hashes_file: Option<String>,
   cancel_file: Option<String>,
+  paths: Vec<String>,
+  incident_id: Option<String>,
+  diff: bool,
+  progress_json: bool,
+  progress_pipe: Option<String>,
+  no_cache: bool,
+  include_removable: bool,
+  include_network: bool,
+  max_files_per_root: Option<u64>,
+  max_hash_mb: Option<u64>,
+  threads: Option<usize>,
+}
+
+static CTRL_C: AtomicBool = AtomicBool::new(false);
+
+/// Signals a running scan (started on this machine) to stop, through the agent's mailbox or the
+/// scan's control file.
+pub fn request_cancel(scan_id: &str) -> anyhow::Result<()> {
+  agent_core::scan_ipc::request_cancel(&agent_core::paths::base_dir()?, scan_id)
 }
 
 pub fn run(mode: ScanMode) -> anyhow::Result<()> {
   set_low_priority();
   let args: Vec<String> = std::env::args().collect();
-  let cfg = parse_scan_config(&args);
+  let cfg = parse_scan_config(&args)?;
+  let dry_run = agent_core::runtime::configure_from_args(&args);
+  if mode == ScanMode::Targeted && cfg.paths.is_empty() {
+    anyhow::bail!("`--targeted` needs at least one `--path <dir>`");
+  }
+  if mode == ScanMode::Targeted && cfg.diff {
+    anyhow::bail!("`--diff` works with `--quick` or `--full`");
+  }
+  if let Some(id) = &cfg.incident_id {
+    if uuid::Uuid::parse_str(id).is_err() {
+      anyhow::bail!("incident id must be a UUID");
+    }
+  }
 
-  tracing_subscriber::fmt()
+  // In JSON mode stdout carries only records, so logs go to stderr.
+  let log = tracing_subscriber::fmt()
     .with_ansi(false)
-    .with_target(false)
-    .init();
+    .with_target(false);
+  if cfg.progress_json {
+    log.with_writer(std::io::stderr).init();
+  } else {
+    log.init();
+  }
 
-  println!("AI Defender Scanner starting: mode={mode:?}");
+  let _ = ctrlc::set_handler(|| CTRL_C.store(true, Ordering::SeqCst));
+  let mut control = ScanControl::register(&cfg, mode)?;
+  let mut out = progress::Reporter::new(cfg.progress_json, cfg.progress_pipe.as_deref());
+  let agent_cfg = agent_core::paths::base_dir()
+    .ok()
+    .and_then(|base| {
+      agent_core::config::load_or_default_readonly(&agent_core::paths::config_path(&base)).ok()
+    })
+    .unwrap_or_default();
+  agent_core::browsers::configure(&agent_cfg.browsers);
+  agent_core::scoring::configure(&agent_cfg.scoring);
+  let excludes = Excludes::load(
+    &cfg.excludes,
+    &cfg.exclude_hashes,
+    cfg.exclude_hashes_file.as_deref(),
+    &agent_cfg.scanner,
+  )?;
+  let limits = WalkLimits::new(mode, &agent_cfg.scanner, &cfg);
+  let threads = hash_threads(cfg.threads.unwrap_or(agent_cfg.scanner.threads));
+  let batch_size = threads * HASH_BATCH_PER_THREAD;
+  let archives = agent_cfg.scanner.archives;
+  let full = &agent_cfg.scanner.full;
+  let feed = agent_core::threat_feed::load_current();
+  let drain_patterns = feed
+    .as_ref()
+    .map(|b| b.reputation.wallet_drain_patterns.clone())
+    .unwrap_or_default();
+  let blocked_hashes = feed
+    .as_ref()
+    .map(|b| b.reputation.blocked_hashes())
+    .unwrap_or_default();
 
+  out.text(&format!(
+    "AI Defender Scanner starting: mode={mode:?} scan_id={}",
+    control.scan_id
+  ));
+  if dry_run {
+    out.text("DRY-RUN: no incident, scan history, catalog or hash cache is written");
+  }
+
+  let started_at_unix_ms = now_unix_ms();
   let roots = match mode {
     ScanMode::Quick => quick_roots(),
-    ScanMode::Full => full_roots(),
+    ScanMode::Full => {
+      let (roots, skipped) = full_roots(
+        full.removable || cfg.include_removable,
+        full.network || cfg.include_network,
+      );
+      for (drive, kind) in skipped {
+        out.text(&format!(
+          "SKIP drive={} type={}",
+          drive.display(),
+          kind.label()
+        ));
+      }
+      roots
+    }
+    ScanMode::Targeted => cfg.paths.iter().map(PathBuf::from).collect(),
   };
-
-  let excludes: Vec<String> = cfg
-    .excludes
+  let root_labels: Vec<String> = roots
     .iter()
-    .map(|s| s.to_ascii_lowercase())
+    .map(|r| r.to_string_lossy().to_string())
     .collect();
+  out.record(json!({
+    "type": "start",
+    "scan_id": control.scan_id,
+    "mode": mode.label(),
+    "roots": root_labels,
+  }));
+
+  // Pre-enumerate so progress lines can carry percent/ETA; a canceled enumeration just means
+  // there's nothing left to scan either.
+  let enumerate_started = std::time::Instant::now();
+  let total_files = progress::enumerate_total(&roots, mode.max_depth(), &limits, || {
+    control.should_cancel()
+  });
+  if let Some(total) = total_files {
+    let enumerate_ms = enumerate_started.elapsed().as_millis();
+    out.text(&format!(
+      "ESTIMATE total_files={total} enumerate_ms={enumerate_ms}"
+    ));
+    out.record(json!({
+      "type": "estimate",
+      "total_files": total,
+      "enumerate_ms": enumerate_ms as u64,
+    }));
+  }
+  let progress = progress::Progress::new(total_files);
+
+  // Targeted scans only see a few folders, so they neither use nor replace the catalog.
+  let catalog_path = (mode != ScanMode::Targeted)
+    .then(|| {
+      agent_core::paths::base_dir()
+        .ok()
+        .map(|base| agent_core::paths::scan_catalog_path(&base, mode.label()))
+    })
+    .flatten();
+  let previous = catalog_path
+    .as_deref()
+    .filter(|_| cfg.diff)
+    .and_then(Catalog::load);
+  if cfg.diff {
+    match &previous {
+      Some(p) => out.text(&format!("DIFF previous_files={}", p.len())),
+      None => out.text(&format!(
+        "DIFF no previous {} scan catalog; reporting everything",
+        mode.label()
+      )),
+    }
+  }
+  let mut catalog = Catalog::default();
+  let mut diff = DiffCounts::default();
+
+  // `--no-cache` re-hashes everything; the results still refresh the cache for the next scan.
+  let hash_cache_path = agent_core::paths::base_dir()
+    .ok()
+    .map(|base| agent_core::paths::scan_hash_cache_path(&base));
+  let mut hash_cache = match &hash_cache_path {
+    Some(path) if !cfg.no_cache => HashCache::load(path),
+    _ => HashCache::default(),
+  };
 
   let mut scanned: u64 = 0;
+  // Machine-wide checks belong to quick/full scans; a targeted pass only looks at its folders.
   let mut findings: Vec<Finding> = Vec::new();
   let mut seen_hashes: HashSet<String> = HashSet::new();
+  if mode != ScanMode::Targeted {
+    for f in scan_extension_sideloading()
+      .into_iter()
+      .chain(persistence::scan_persistence())
+      .chain(scan_startup_targets(&excludes, &limits))
+    {
+      // The walk doesn't report a startup target's S001 again.
+      if let Some(hash) = extract_sha256(&f) {
+        seen_hashes.insert(hash);
+      }
+      report_finding(&mut out, &f);
+      findings.push(f);
+    }
+  }
+  let mut canceled = false;
+  let mut limited_roots: u64 = 0;
+  let mut skipped_large: u64 = 0;
+  let mut excluded_by_hash: u64 = 0;
 
-  for root in roots {
-    if should_cancel(&cfg) {
-      println!("Scan canceled by user.");
-      return Ok(());
+  'roots: for root in roots {
+    if control.should_cancel() {
+      canceled = true;
+      break;
     }
 
     if root.as_os_str().is_empty() || !root.exists() {
       continue;
     }
 
-    for entry in WalkDir::new(&root)
-      .follow_links(false)
-      .into_iter()
-      .flatten()
-    {
-      if should_cancel(&cfg) {
-        println!("Scan canceled by user.");
-        return Ok(());
-      }
+    let mut root_limited = false;
+    let mut entries = RootFiles::new(
+      walk(&root, mode.max_depth(), limits.skip_reparse_points),
+      limits.max_files_per_root,
+    );
+    loop {
+      // The cheap checks run as the walk goes; executables are collected and hashed a batch at a
+      // time, so the hashing can be spread over threads.
+      let mut batch: Vec<Pending> = Vec::new();
+      let mut walked_all = true;
+      for entry in entries.by_ref() {
+        if control.should_cancel() {
+          canceled = true;
+          break;
+        }
+
+        let p = entry.path();
+        scanned += 1;
+        if scanned.is_multiple_of(progress::PROGRESS_EVERY_FILES) {
+          control.report(scanned, total_files, findings.len());
+          let current = safe_filename(p);
+          if out.is_json() {
+            out.record(progress.record(scanned, findings.len(), &current));
+          } else {
+            out.text(&progress.line(scanned, findings.len(), &current));
+          }
+        }
+
+        if archives.enabled && archive::is_candidate(p) && !excludes.path(p) {
+          for f in archive::inspect(p, &archives) {
+            report_finding(&mut out, &f);
+            findings.push(f);
+          }
+        }
 
-      let p = entry.path();
-      if entry.file_type().is_dir() {
-        if is_excluded(&excludes, p) {
+        if !drain_patterns.is_empty() && !excludes.path(p) {
+          if let Some(f) = scan_wallet_drain(p, &drain_patterns) {
+            report_finding(&mut out, &f);
+            findings.push(f);
+          }
+        }
+
+        if !is_executable_candidate(p) {
+          continue;
+        }
+        if excludes.path(p) {
           continue;
         }
-        continue;
-      }
 
-      scanned += 1;
-      if scanned.is_multiple_of(250) {
-        println!(
-          "PROGRESS scanned={scanned} findings={} current={}",
-          findings.len(),
-          safe_filename(p)
-        );
-      }
+        let too_large = limits.max_hash_bytes > 0
+          && entry
+            .metadata()
+            .is_ok_and(|m| m.len() > limits.max_hash_bytes);
+        if too_large {
+          // Not hashed or signature-checked, so only the location checks apply.
+          skipped_large += 1;
+          for f in scan_file(p, None, None).unwrap_or_default() {
+            report_finding(&mut out, &f);
+            findings.push(f);
+          }
+          continue;
+        }
 
-      if !is_executable_candidate(p) {
-        continue;
+        let file = p.to_string_lossy().to_string();
+        let stamp = entry
+          .metadata()
+          .ok()
+          .and_then(|m| hash_cache::Stamp::of(&m));
+        let cached = stamp.and_then(|stamp| hash_cache.get(&file, stamp));
+        batch.push(Pending {
+          path: p.to_path_buf(),
+          file,
+          stamp,
+          cached: cached.is_some(),
+          result: cached.map(|hit| (Some(hit.sha256), hit.signed)),
+        });
+        if batch.len() >= batch_size {
+          walked_all = false;
+          break;
+        }
       }
-      if is_excluded(&excludes, p) {
-        continue;
+
+      if entries.limited && !root_limited {
+        out.text(&format!(
+          "LIMIT root={} max_files_per_root={} reached; skipping the rest of it",
+          root.display(),
+          limits.max_files_per_root
+        ));
+        out.record(json!({
+          "type": "root_limit",
+          "root": root.to_string_lossy(),
+          "max_files_per_root": limits.max_files_per_root,
+        }));
+        limited_roots += 1;
+        root_limited = true;
       }
 
-      if let Ok(mut fs) = scan_file(p) {
-        for f in fs.drain(..) {
-          if let Some(hash) = extract_sha256(&f) {
-            if !seen_hashes.insert(hash) {
+      hash_pending(&mut batch, threads);
+      for pending in batch {
+        let (sha256, signed) = pending.result.unwrap_or_default();
+        let file = pending.file;
+        if !pending.cached {
+          if let (Some(stamp), Some(sha256)) = (pending.stamp, &sha256) {
+            hash_cache.insert(
+              &file,
+              stamp,
+              catalog::Entry {
+                sha256: sha256.clone(),
+                signed,
+              },
+            );
+          }
+        }
+        // Reported even when the hash is excluded: the feed says this exact file is malware.
+        if let (Some(sha256), Some(bundle)) = (&sha256, &feed) {
+          if blocked_hashes.contains(sha256) {
+            let f = feed_hash_finding(&file, sha256, bundle);
+            report_finding(&mut out, &f);
+            findings.push(f);
+          }
+        }
+        if sha256.as_deref().is_some_and(|h| excludes.hash(h)) {
+          excluded_by_hash += 1;
+          continue;
+        }
+        if let Some(sha256) = &sha256 {
+          let entry = catalog::Entry {
+            sha256: sha256.clone(),
+            signed,
+          };
+          // Files that can't be hashed can't be compared either, so they're always reported.
+          let changed = previous.as_ref().map(|prev| prev.compare(&file, &entry));
+          catalog.insert(&file, entry.clone());
+          match changed {
+            Some(Some(change)) => {
+              out.text(&diff_line(&change, &entry, &file));
+              out.record(diff_record(&change, &entry, &file));
+              diff.count(&change);
+            }
+            Some(None) => {
+              diff.unchanged += 1;
               continue;
             }
+            None => {}
+          }
+        }
+
+        if let Ok(mut fs) = scan_file(&pending.path, sha256.as_deref(), Some(signed)) {
+          for f in fs.drain(..) {
+            if let Some(hash) = extract_sha256(&f) {
+              if !seen_hashes.insert(hash) {
+                continue;
+              }
+            }
+            report_finding(&mut out, &f);
+            findings.push(f);
           }
-          findings.push(f);
         }
       }
+
+      if canceled {
+        break 'roots;
+      }
+      if root_limited || walked_all {
+        break;
+      }
+    }
+  }
+
+  if canceled {
+    out.text(&format!("Scan canceled by user. scanned={scanned}"));
+  }
+  // Only a complete pass shows that a file is gone.
+  if let Some(previous) = previous
+    .as_ref()
+    .filter(|_| !canceled && limited_roots == 0)
+  {
+    for file in previous.removed(&catalog) {
+      out.text(&format!("DIFF removed path={file}"));
+      out.record(json!({ "type": "diff", "change": "removed", "path": file }));
+      diff.removed += 1;
+    }
+  }
+  if previous.is_some() {
+    out.text(&format!(
+      "DIFF summary new={} changed={} removed={} unchanged={}",
+      diff.new, diff.changed, diff.removed, diff.unchanged
+    ));
+  }
+  if skipped_large > 0 {
+    out.text(&format!(
+      "SKIPPED large_files={skipped_large} (not hashed; over max_hash_mb)"
+    ));
+  }
+  if excluded_by_hash > 0 {
+    out.text(&format!("EXCLUDED by_hash={excluded_by_hash}"));
+  }
+  if hash_cache.hits > 0 {
+    out.text(&format!(
+      "CACHE hits={} misses={}",
+      hash_cache.hits, hash_cache.misses
+    ));
+  }
+  // Cached results stay valid whether or not the pass completed.
+  if let Some(path) = hash_cache_path.as_ref().filter(|_| !dry_run) {
+    if let Err(e) = hash_cache.save(path) {
+      tracing::warn!(error = ?e, "failed to save scan hash cache");
+    }
+  }
+  // Only a complete pass is a fair baseline for the next diff.
+  if let Some(path) = catalog_path.filter(|_| !canceled && limited_roots == 0 && !dry_run) {
+    if let Err(e) = catalog.save(&path) {
+      tracing::warn!(error = ?e, "failed to save scan catalog");
+    }
+  }
+
+  let findings_count = findings.len() as u64;
+  let stored = match &cfg.incident_id {
+    Some(id) => merge_findings(id, findings, canceled),
+    None => store_findings(findings, canceled, previous.is_some()),
+  };
+  let mut record = ScanRecord {
+    scan_id: control.scan_id.clone(),
+    mode: mode.label().to_string(),
+    started_at_unix_ms,
+    ended_at_unix_ms: now_unix_ms(),
+    roots: root_labels,
+    files_scanned: scanned,
+    findings_count,
+    incident_id: None,
+    outcome: if canceled {
+      ScanOutcome::Canceled
+    } else {
+      ScanOutcome::Completed
+    },
+  };
+
+  let mut error = None;
+  let result = match stored {
+    Ok(None) => {
+      if !canceled {
+        out.text(&format!("Scan complete: no findings. scanned={scanned}"));
+      }
+      Ok(())
     }
+    Ok(Some((id, path))) => {
+      out.text(&format!(
+        "Scan {}: incident_id={id} severity=yellow stored={}",
+        if canceled { "partial" } else { "complete" },
+        path.display()
+      ));
+      record.incident_id = Some(id);
+      Ok(())
+    }
+    Err(e) => {
+      record.outcome = ScanOutcome::Failed;
+      error = Some(format!("{e:#}"));
+      Err(e)
+    }
+  };
+
+  let mut summary = json!({
+    "type": "summary",
+    "scan_id": record.scan_id,
+    "outcome": record.outcome,
+    "scanned": scanned,
+    "findings": findings_count,
+    "incident_id": record.incident_id,
+    "cache_hits": hash_cache.hits,
+    "limited_roots": limited_roots,
+    "skipped_large": skipped_large,
+    "excluded_by_hash": excluded_by_hash,
+  });
+  if previous.is_some() {
+    summary["diff"] = json!({
+      "new": diff.new,
+      "changed": diff.changed,
+      "removed": diff.removed,
+      "unchanged": diff.unchanged,
+    });
   }
+  if let Some(error) = error {
+    summary["error"] = json!(error);
+  }
+  out.record(summary);
 
+  if dry_run {
+    tracing::warn!(scan_id = %record.scan_id, "DRY-RUN: would record scan history");
+  } else if let Err(e) = scan_history::record(&record) {
+    tracing::warn!(error = ?e, "failed to record scan history");
+  }
+  result
+}
+
+fn store_findings(
+  findings: Vec<Finding>,
+  canceled: bool,
+  diff: bool,
+) -> anyhow::Result<Option<(String, PathBuf)>> {
   if findings.is_empty() {
-    println!("Scan complete: no findings. scanned={scanned}");
-    return Ok(());
+    return Ok(None);
   }
 
+  // Canceled scans still flush what was found so far so partial work is not lost.
   let mut incident = Incident::new(findings);
+  // Even with a RED finding (S011): scans never trigger containment.
   incident.severity = Severity::Yellow;
+  incident.score = Some(agent_core::scoring::score_incident(
+    &agent_core::scoring::configured(),
+    &incident,
+  ));
+  agent_core::guidance::attach(&mut incident);
   incident.actions_taken.push("scan_report_only".to_string());
-  let id = incident.incident_id.clone();
+  if diff {
+    incident.actions_taken.push("scan_diff_only".to_string());
+  }
+  if canceled {
+    incident
+      .actions_taken
+      .push("scan_canceled_partial".to_string());
+  }
 
   let path = agent_core::incident_store::store_incident(&incident)?;
-  println!(
-    "Scan complete: incident_id={id} severity=yellow stored={}",
-    path.display()
-  );
-  Ok(())
+  Ok(Some((incident.incident_id, path)))
+}
+
+/// Adds a targeted scan's findings to the incident that triggered it, so the follow-up shows up in
+/// the same report.
+fn merge_findings(
+  incident_id: &str,
+  findings: Vec<Finding>,
+  canceled: bool,
+) -> anyhow::Result<Option<(String, PathBuf)>> {
+  let merged = !findings.is_empty();
+  agent_core::incident_store::update_incident(incident_id, |incident| {
+    incident.findings.extend(findings);
+    incident.actions_taken.push(
+      if canceled {
+        "targeted_scan_canceled_partial"
+      } else if merged {
+        "targeted_scan_merged"
+      } else {
+        "targeted_scan_clean"
+      }
+      .to_string(),
+    );
+  })?;
+
+  if !merged {
+    return Ok(None);
+  }
+  let base = agent_core::paths::base_dir()?;
+  let path = agent_core::paths::incidents_dir(&base).join(format!("{incident_id}.toml"));
+  Ok(Some((incident_id.to_string(), path)))
+}
+
+#[derive(Debug, Default)]
+struct DiffCounts {
+  new: u64,
+  changed: u64,
+  removed: u64,
+  unchanged: u64,
+}
+
+impl DiffCounts {
+  fn count(&mut self, change: &Change) {
+    match change {
+      Change::New => self.new += 1,
+      Change::Changed { .. } => self.changed += 1,
+    }
+  }
+}
+
+/// `DIFF` output line for a file that is new or changed since the previous catalog. Like progress
+/// lines, the path comes last since it may contain spaces.
+fn diff_line(change: &Change, entry: &catalog::Entry, file: &str) -> String {
+  match change {
+    Change::New => format!(
+      "DIFF new signed={} sha256={} path={file}",
+      entry.signed, entry.sha256
+    ),
+    Change::Changed { previous } => format!(
+      "DIFF changed signed={} previous_signed={} sha256={} previous_sha256={} path={file}",
+      entry.signed, previous.signed, entry.sha256, previous.sha256
+    ),
+  }
+}
+
+fn diff_record(change: &Change, entry: &catalog::Entry, file: &str) -> serde_json::Value {
+  let mut record = json!({
+    "type": "diff",
+    "change": "new",
+    "signed": entry.signed,
+    "sha256": entry.sha256,
+    "path": file,
+  });
+  if let Change::Changed { previous } = change {
+    record["change"] = json!("changed");
+    record["previous_signed"] = json!(previous.signed);
+    record["previous_sha256"] = json!(previous.sha256);
+  }
+  record
+}
+
+fn report_finding(out: &mut progress::Reporter, finding: &Finding) {
+  out.record(json!({ "type": "finding", "finding": finding }));
+}
+
+/// Reports progress to the agent at most this often.
+const IPC_PROGRESS_INTERVAL_MS: u64 = 2_000;
+
+/// Registration and cancellation for one scan. With the agent running, the scan registers in its
+/// mailbox, reports progress there and takes cancel commands from it (`agent_core::scan_ipc`);
+/// otherwise it falls back to a control file under `scans\`. Ctrl+C and the legacy
+/// `--cancel-file` work either way. The registration is removed when the scan ends.
+struct ScanControl {
+  scan_id: String,
+  cancel_file: Option<PathBuf>,
+  running_path: Option<PathBuf>,
+  control_cancel_path: Option<PathBuf>,
+  ipc: Option<(PathBuf, ScanProgress)>,
+}
+
+impl ScanControl {
+  fn register(cfg: &ScanConfig, mode: ScanMode) -> anyhow::Result<Self> {
+    let scan_id = uuid::Uuid::new_v4().to_string();
+    let base = agent_core::paths::base_dir()?;
+    let cancel_file = cfg.cancel_file.as_ref().map(PathBuf::from);
+
+    if scan_ipc::agent_running(&base) {
+      let now = now_unix_ms();
+      let progress = ScanProgress {
+        scan_id: scan_id.clone(),
+        mode: mode.label().to_string(),
+        pid: std::process::id(),
+        started_at_unix_ms: now,
+        updated_at_unix_ms: now,
+        scanned: 0,
+        total_files: None,
+        findings: 0,
+      };
+      match scan_ipc::report(&base, &progress) {
+        Ok(()) => {
+          return Ok(Self {
+            scan_id,
+            cancel_file,
+            running_path: None,
+            control_cancel_path: None,
+            ipc: Some((base, progress)),
+          })
+        }
+        Err(e) => {
+          tracing::warn!(error = ?e, "unable to register with the agent; using a control file")
+        }
+      }
+    }
+
+    let running = agent_core::paths::scan_running_path(&base, &scan_id);
+
+    let registered = running
+      .parent()
+      .map(|dir| fs::create_dir_all(dir).is_ok())
+      .unwrap_or(false)
+      && fs::write(&running, std::process::id().to_string()).is_ok();
+    if !registered {
+      tracing::warn!("unable to register scan control file; `--cancel` will not reach this scan");
+    }
+
+    Ok(Self {
+      cancel_file,
+      running_path: registered.then_some(running),
+      control_cancel_path: registered.then(|| agent_core::paths::scan_cancel_path(&base, &scan_id)),
+      scan_id,
+      ipc: None,
+    })
+  }
+
+  fn should_cancel(&self) -> bool {
+    CTRL_C.load(Ordering::SeqCst)
+      || self.cancel_file.as_deref().is_some_and(Path::exists)
+      || self
+        .control_cancel_path
+        .as_deref()
+        .is_some_and(Path::exists)
+      || self
+        .ipc
+        .as_ref()
+        .is_some_and(|(base, _)| scan_ipc::cancel_requested(base, &self.scan_id))
+  }
+
+  /// Updates the agent's view of the scan, at most every `IPC_PROGRESS_INTERVAL_MS`.
+  fn report(&mut self, scanned: u64, total_files: Option<u64>, findings: usize) {
+    let Some((base, progress)) = &mut self.ipc else {
+      return;
+    };
+    let now = now_unix_ms();
+    if now.saturating_sub(progress.updated_at_unix_ms) < IPC_PROGRESS_INTERVAL_MS {
+      return;
+    }
+    progress.updated_at_unix_ms = now;
+    progress.scanned = scanned;
+    progress.total_files = total_files;
+    progress.findings = findings as u64;
+    if let Err(e) = scan_ipc::report(base, progress) {
+      tracing::debug!(error = ?e, "failed to report scan progress");
+    }
+  }
+}
+
+impl Drop for ScanControl {
+  fn drop(&mut self) {
+    if let Some((base, progress)) = &self.ipc {
+      scan_ipc::unregister(base, &progress.scan_id);
+    }
+    if let Some(p) = &self.running_path {
+      let _ = fs::remove_file(p);
+    }
+    if let Some(p) = &self.control_cancel_path {
+      let _ = fs::remove_file(p);
+    }
+  }
 }
 
-fn parse_scan_config(args: &[String]) -> ScanConfig {
+fn parse_scan_config(args: &[String]) -> anyhow::Result<ScanConfig> {
   let mut excludes = Vec::new();
+  let mut exclude_hashes = Vec::new();
+  let mut exclude_hashes_file = None;
   let mut cancel_file = None;
+  let mut paths = Vec::new();
+  let mut incident_id = None;
+  let mut progress_pipe = None;
+  let diff = args.iter().any(|a| a == "--diff");
+  let progress_json = args.iter().any(|a| a == "--progress-json");
+  let no_cache = args.iter().any(|a| a == "--no-cache");
+  let include_removable = args.iter().any(|a| a == "--include-removable");
+  let include_network = args.iter().any(|a| a == "--include-network");
+  let mut max_files_per_root = None;
+  let mut max_hash_mb = None;
+  let mut threads = None;
+  let number = |flag: &str, v: &str| -> anyhow::Result<u64> {
+    v.trim()
+      .parse()
+      .map_err(|_| anyhow::anyhow!("{flag} expects a number, got `{v}`"))
+  };
   let mut i = 0;
   while i < args.len() {
     match args[i].as_str() {
@@ -147,6 +956,20 @@ fn parse_scan_config(args: &[String]) -> ScanConfig {
           continue;
         }
       }
+      "--exclude-hash" => {
+        if let Some(v) = args.get(i + 1) {
+          exclude_hashes.push(v.clone());
+          i += 2;
+          continue;
+        }
+      }
+      "--exclude-hashes-file" => {
+        if let Some(v) = args.get(i + 1) {
+          exclude_hashes_file = Some(v.clone());
+          i += 2;
+          continue;
+        }
+      }
       "--cancel-file" => {
         if let Some(v) = args.get(i + 1) {
           cancel_file = Some(v.clone());
@@ -154,28 +977,69 @@ fn parse_scan_config(args: &[String]) -> ScanConfig {
           continue;
         }
       }
+      "--path" => {
+        if let Some(v) = args.get(i + 1) {
+          paths.push(v.clone());
+          i += 2;
+          continue;
+        }
+      }
+      "--incident" => {
+        if let Some(v) = args.get(i + 1) {
+          incident_id = Some(v.trim().to_string());
+          i += 2;
+          continue;
+        }
+      }
+      "--progress-pipe" => {
+        if let Some(v) = args.get(i + 1) {
+          progress_pipe = Some(v.clone());
+          i += 2;
+          continue;
+        }
+      }
+      "--max-files-per-root" => {
+        if let Some(v) = args.get(i + 1) {
+          max_files_per_root = Some(number("--max-files-per-root", v)?);
+          i += 2;
+          continue;
+        }
+      }
+      "--max-hash-mb" => {
+        if let Some(v) = args.get(i + 1) {
+          max_hash_mb = Some(number("--max-hash-mb", v)?);
+          i += 2;
+          continue;
+        }
+      }
+      "--threads" => {
+        if let Some(v) = args.get(i + 1) {
+          threads = Some(number("--threads", v)? as usize);
+          i += 2;
+          continue;
+        }
+      }
       _ => {}
     }
     i += 1;
   }
-  ScanConfig {
+  Ok(ScanConfig {
     excludes,
+    exclude_hashes,
+    exclude_hashes_file,
     cancel_file,
-  }
-}
-
-fn should_cancel(cfg: &ScanConfig) -> bool {
-  cfg
-    .cancel_file
-    .as_deref()
-    .is_some_and(|p| Path::new(p).exists())
-}
-
-fn is_excluded(excludes: &[String], path: &Path) -> bool {
-  let p = path.to_string_lossy().to_ascii_lowercase();
-  excludes
-    .iter()
-    .any(|ex| !ex.trim().is_empty() && p.starts_with(ex))
+    paths,
+    incident_id,
+    diff,
+    progress_json,
+    progress_pipe,
+    no_cache,
+    include_removable,
+    include_network,
+    max_files_per_root,
+    max_hash_mb,
+    threads,
+  })
 }
 
 fn quick_roots() -> Vec<PathBuf> {
@@ -199,14 +1063,26 @@ fn quick_roots() -> Vec<PathBuf> {
   }
   if let Ok(local) = std::env::var("LOCALAPPDATA") {
     roots.push(PathBuf::from(&local));
-    roots.extend(browser_extension_roots(&local));
   }
+  roots.extend(browser_extension_roots());
+  roots.extend(relocated_firefox_profiles(&roots));
 
   roots
 }
 
-fn full_roots() -> Vec<PathBuf> {
-  fixed_drives()
+/// Drives a full scan walks, and the ones it leaves out with their type. CD-ROMs and RAM disks
+/// are never scanned.
+fn full_roots(removable: bool, network: bool) -> (Vec<PathBuf>, Vec<(PathBuf, DriveKind)>) {
+  let (scanned, skipped): (Vec<_>, Vec<_>) =
+    signature::drives()
+      .into_iter()
+      .partition(|(_, kind)| match kind {
+        DriveKind::Fixed => true,
+        DriveKind::Removable => removable,
+        DriveKind::Network => network,
+        DriveKind::Other => false,
+      });
+  (scanned.into_iter().map(|(d, _)| d).collect(), skipped)
 }
 
 fn startup_folders() -> Vec<PathBuf> {
@@ -220,13 +1096,31 @@ fn startup_folders() -> Vec<PathBuf> {
   out
 }
 
-fn browser_extension_roots(localappdata: &str) -> Vec<PathBuf> {
-  let l = PathBuf::from(localappdata);
-  vec![
-    l.join("Google\\Chrome\\User Data\\Default\\Extensions"),
-    l.join("Microsoft\\Edge\\User Data\\Default\\Extensions"),
-    l.join("BraveSoftware\\Brave-Browser\\User Data\\Default\\Extensions"),
-  ]
+/// Extension directories of every Chromium and Firefox profile, not just `Default`.
+fn browser_extension_roots() -> Vec<PathBuf> {
+  browser_profiles::discover()
+    .iter()
+    .map(|p| p.extensions_dir())
+    .filter(|d| d.is_dir())
+    .collect()
+}
+
+/// Firefox profiles outside `roots`: created with an absolute path in `profiles.ini`, or belonging
+/// to another user.
+fn relocated_firefox_profiles(roots: &[PathBuf]) -> Vec<PathBuf> {
+  let roots_lower: Vec<String> = roots
+    .iter()
+    .map(|r| r.to_string_lossy().to_ascii_lowercase())
+    .collect();
+  browser_profiles::discover()
+    .into_iter()
+    .filter(|p| p.is_firefox())
+    .map(|p| p.dir)
+    .filter(|d| {
+      let d = d.to_string_lossy().to_ascii_lowercase();
+      !roots_lower.iter().any(|r| d.starts_with(r.as_str()))
+    })
+    .collect()
 }
 
 fn is_executable_candidate(p: &Path) -> bool {
@@ -248,25 +1142,82 @@ fn safe_filename(p: &Path) -> String {
     .to_string()
 }
 
-fn scan_file(path: &Path) -> anyhow::Result<Vec<Finding>> {
+/// S010: a script in a browser profile (extension code) or a user-writable folder that matches a
+/// threat feed wallet-drainer pattern.
+fn scan_wallet_drain(path: &Path, patterns: &[WalletDrainPattern]) -> Option<Finding> {
+  let file = path.to_string_lossy();
+  if !wallet_drain::is_script_path(&file) {
+    return None;
+  }
+  let in_scope = browser_profiles::profile_browser(&file.to_ascii_lowercase()).is_some()
+    || is_user_writable_location(path);
+  if !in_scope {
+    return None;
+  }
+  let pattern = wallet_drain::match_script(path, patterns)?;
+  Some(Finding {
+    rule_id: "S010".to_string(),
+    severity: Severity::Yellow,
+    description: format!(
+      "Script matches wallet-drainer pattern {}",
+      wallet_drain::label(pattern)
+    ),
+    evidence: vec![Evidence::Note {
+      message: format!("path={file}"),
+    }],
+    timestamp_unix_ms: now_unix_ms(),
+  })
+}
+
+/// S011: the file's hash is in the feed's `hashes_block`. The evidence names the bundle, so the
+/// detection can be reproduced with the same feed data.
+fn feed_hash_finding(file: &str, sha256: &str, bundle: &ThreatFeedBundle) -> Finding {
+  Finding {
+    rule_id: "S011".to_string(),
+    severity: Severity::Red,
+    description: "File matches a known-bad hash from the threat feed".to_string(),
+    evidence: vec![Evidence::Note {
+      message: format!(
+        "path={file} sha256={sha256} feed_rules_version={} feed_bundle_id={}",
+        bundle.rules_version, bundle.bundle_id
+      ),
+    }],
+    timestamp_unix_ms: now_unix_ms(),
+  }
+}
+
+/// `signed` is `None` when the signature wasn't checked (file over `max_hash_mb`). Findings for
+/// unsigned files carry their Mark of the Web, when they have one.
+fn scan_file(
+  path: &Path,
+  sha256: Option<&str>,
+  signed: Option<bool>,
+) -> anyhow::Result<Vec<Finding>> {
   let mut findings = Vec::new();
   let ts = now_unix_ms();
+  let unsigned = signed == Some(false) && is_executable_candidate(path);
+  let origin = if unsigned { motw::read(path) } else { None };
+  let with_origin = |mut evidence: Vec<Evidence>| {
+    if let Some(o) = &origin {
+      evidence.push(Evidence::Note {
+        message: format!("download_origin {}", o.label()),
+      });
+    }
+    evidence
+  };
 
-  let sha256 = sha256_hex(path).ok();
-  let signed = signature::is_trusted_signed(path).unwrap_or(false);
-
-  if !signed && is_executable_candidate(path) {
+  if unsigned {
     findings.push(Finding {
       rule_id: "S001".to_string(),
       severity: Severity::Yellow,
       description: "Unsigned executable/script found".to_string(),
-      evidence: vec![Evidence::Note {
+      evidence: with_origin(vec![Evidence::Note {
         message: format!(
           "path={} sha256={}",
           path.to_string_lossy(),
-          sha256.clone().unwrap_or_else(|| "<unknown>".to_string())
+          sha256.unwrap_or("<unknown>")
         ),
-      }],
+      }]),
       timestamp_unix_ms: ts,
     });
   }
@@ -276,21 +1227,32 @@ fn scan_file(path: &Path) -> anyhow::Result<Vec<Finding>> {
       rule_id: "S002".to_string(),
       severity: Severity::Yellow,
       description: "Executable in user-writable directory".to_string(),
-      evidence: vec![Evidence::Note {
+      evidence: with_origin(vec![Evidence::Note {
         message: format!("path={}", path.to_string_lossy()),
-      }],
+      }]),
       timestamp_unix_ms: ts,
     });
   }
 
   if is_in_startup_folder(path) && is_executable_candidate(path) {
+    // An unsigned program downloaded from the Internet that runs at every sign-in.
+    let downloaded = origin.as_ref().is_some_and(|o| o.is_internet());
     findings.push(Finding {
       rule_id: "S003".to_string(),
-      severity: Severity::Yellow,
-      description: "Executable in Startup folder (persistence location)".to_string(),
-      evidence: vec![Evidence::Note {
+      severity: if downloaded {
+        Severity::Red
+      } else {
+        Severity::Yellow
+      },
+      description: if downloaded {
+        "Unsigned executable downloaded from the Internet in Startup folder (persistence location)"
+          .to_string()
+      } else {
+        "Executable in Startup folder (persistence location)".to_string()
+      },
+      evidence: with_origin(vec![Evidence::Note {
         message: format!("path={}", path.to_string_lossy()),
-      }],
+      }]),
       timestamp_unix_ms: ts,
     });
   }
@@ -298,6 +1260,88 @@ fn scan_file(path: &Path) -> anyhow::Result<Vec<Finding>> {
   Ok(findings)
 }
 
+/// The file checks for what Startup shortcuts and scheduled tasks start (see `startup`), with the
+/// entry that starts it as evidence. Targets anywhere on the machine are checked, walked or not.
+fn scan_startup_targets(excludes: &Excludes, limits: &WalkLimits) -> Vec<Finding> {
+  let mut checked = HashSet::new();
+  let mut findings = Vec::new();
+  for entry in startup::entries(&startup_folders()) {
+    let target = &entry.target;
+    if !is_executable_candidate(target)
+      || excludes.path(target)
+      || !checked.insert(target.to_string_lossy().to_ascii_lowercase())
+    {
+      continue;
+    }
+    let Ok(meta) = fs::metadata(target) else {
+      continue;
+    };
+    let checks = if limits.max_hash_bytes > 0 && meta.len() > limits.max_hash_bytes {
+      scan_file(target, None, None)
+    } else {
+      let sha256 = sha256_hex(target).ok();
+      if sha256.as_deref().is_some_and(|h| excludes.hash(h)) {
+        continue;
+      }
+      let signed = signature::is_trusted_signed(target).unwrap_or(false);
+      scan_file(target, sha256.as_deref(), Some(signed))
+    };
+    for mut f in checks.unwrap_or_default() {
+      f.evidence.push(Evidence::Note {
+        message: entry.note(),
+      });
+      findings.push(f);
+    }
+  }
+  findings
+}
+
+/// Extension sideloading doesn't leave executables behind, so it is checked directly: forcelist
+/// policy values and running Chromium browsers started with `--load-extension`.
+fn scan_extension_sideloading() -> Vec<Finding> {
+  let ts = now_unix_ms();
+  let mut findings = Vec::new();
+
+  for entry in browser_extensions::read_forcelist_entries() {
+    findings.push(Finding {
+      rule_id: "S004".to_string(),
+      severity: Severity::Yellow,
+      description: "Browser extension force-installed via policy (ExtensionInstallForcelist)"
+        .to_string(),
+      evidence: vec![Evidence::Registry {
+        pid: 0,
+        image_path: None,
+        key_path: format!("{}\\{}", entry.key, entry.value_name),
+        value: Some(entry.data),
+      }],
+      timestamp_unix_ms: ts,
+    });
+  }
+
+  for proc in browser_extensions::running_browser_processes() {
+    let paths = browser_extensions::load_extension_paths(&proc.command_line);
+    if paths.is_empty() {
+      continue;
+    }
+    findings.push(Finding {
+      rule_id: "S005".to_string(),
+      severity: Severity::Yellow,
+      description: format!(
+        "Running browser loaded unpacked extension(s): {}",
+        paths.join(", ")
+      ),
+      evidence: vec![Evidence::CommandLine {
+        pid: proc.pid,
+        image_path: proc.image_path,
+        command_line: proc.command_line,
+      }],
+      timestamp_unix_ms: ts,
+    });
+  }
+
+  findings
+}
+
 fn extract_sha256(f: &Finding) -> Option<String> {
   for e in &f.evidence {
     if let Evidence::Note { message } = e {
@@ -309,20 +1353,6 @@ fn extract_sha256(f: &Finding) -> Option<String> {
   None
 }
 
-fn sha256_hex(path: &Path) -> anyhow::Result<String> {
-  let mut file = fs::File::open(path)?;
-  let mut hasher = Sha256::new();
-  let mut buf = [0u8; 64 * 1024];
-  loop {
-    let n = file.read(&mut buf)?;
-    if n == 0 {
-      break;
-    }
-    hasher.update(&buf[..n]);
-  }
-  Ok(format!("{:x}", hasher.finalize()))
-}
-
 fn is_in_startup_folder(path: &Path) -> bool {
   let p = path.to_string_lossy().to_ascii_lowercase();
   startup_folders()
@@ -346,20 +1376,101 @@ fn is_user_writable_location(path: &Path) -> bool {
     .any(|prefix| !prefix.is_empty() && p.starts_with(&prefix))
 }
 
-fn fixed_drives() -> Vec<PathBuf> {
+fn set_low_priority() {
   #[cfg(windows)]
   {
-    signature::fixed_drives()
-  }
-  #[cfg(not(windows))]
-  {
-    vec![PathBuf::from("/")]
+    let _ = signature::set_low_priority();
   }
 }
 
-fn set_low_priority() {
-  #[cfg(windows)]
-  {
-    let _ = signature::set_low_priority();
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn scan_config(args: &[&str]) -> ScanConfig {
+    let args: Vec<String> = std::iter::once("scanner.exe")
+      .chain(args.iter().copied())
+      .map(str::to_string)
+      .collect();
+    parse_scan_config(&args).unwrap()
+  }
+
+  #[test]
+  fn walk_limits_come_from_scanner_full_and_the_command_line() {
+    let mut scanner = ScannerConfig {
+      max_file_mb: 50,
+      ..ScannerConfig::default()
+    };
+    scanner.full.max_files_per_root = 1000;
+    let none = scan_config(&[]);
+
+    let quick = WalkLimits::new(ScanMode::Quick, &scanner, &none);
+    assert_eq!(quick.max_files_per_root, 0);
+    assert_eq!(quick.max_hash_bytes, 50 * 1024 * 1024);
+    assert!(quick.skip_reparse_points);
+
+    // `[scanner.full] max_hash_mb` wins for full scans when set; 0 falls back to `[scanner]`.
+    let full = WalkLimits::new(ScanMode::Full, &scanner, &none);
+    assert_eq!(full.max_files_per_root, 1000);
+    assert_eq!(full.max_hash_bytes, 50 * 1024 * 1024);
+    scanner.full.max_hash_mb = 10;
+    let full = WalkLimits::new(ScanMode::Full, &scanner, &none);
+    assert_eq!(full.max_hash_bytes, 10 * 1024 * 1024);
+
+    let args = scan_config(&["--max-hash-mb", "0", "--max-files-per-root", "5"]);
+    let full = WalkLimits::new(ScanMode::Full, &scanner, &args);
+    assert_eq!((full.max_files_per_root, full.max_hash_bytes), (5, 0));
+    let targeted = WalkLimits::new(ScanMode::Targeted, &scanner, &args);
+    assert_eq!(targeted.max_files_per_root, 0);
+
+    scanner.follow_reparse_points = true;
+    assert!(!WalkLimits::new(ScanMode::Full, &scanner, &none).skip_reparse_points);
+    scanner.follow_reparse_points = false;
+    scanner.full.skip_reparse_points = false;
+    assert!(!WalkLimits::new(ScanMode::Full, &scanner, &none).skip_reparse_points);
+    assert!(WalkLimits::new(ScanMode::Quick, &scanner, &none).skip_reparse_points);
+  }
+
+  #[test]
+  fn hash_threads_are_clamped() {
+    assert!((1..=4).contains(&hash_threads(0)));
+    assert_eq!(hash_threads(3), 3);
+    assert_eq!(hash_threads(1000), MAX_SCANNER_THREADS);
+    assert!(parse_scan_config(&[
+      "scanner.exe".to_string(),
+      "--threads".to_string(),
+      "many".to_string()
+    ])
+    .is_err());
+  }
+
+  #[test]
+  fn a_limited_root_leaves_the_other_roots_their_files() {
+    let base = std::env::temp_dir().join(format!("aid-root-limit-{}", uuid::Uuid::new_v4()));
+    let big = base.join("big");
+    let small = base.join("small");
+    std::fs::create_dir_all(big.join("sub")).unwrap();
+    std::fs::create_dir_all(&small).unwrap();
+    for name in ["a.exe", "b.exe", "c.exe", "sub/d.exe", "sub/e.exe"] {
+      std::fs::write(big.join(name), b"x").unwrap();
+    }
+    for name in ["f.exe", "g.exe"] {
+      std::fs::write(small.join(name), b"x").unwrap();
+    }
+
+    let mut counts = Vec::new();
+    for root in [&big, &small, &big] {
+      let mut files = RootFiles::new(walk(root, usize::MAX, true), 2);
+      let n = files.by_ref().count();
+      assert!(files.next().is_none());
+      counts.push((n, files.limited));
+    }
+    assert_eq!(counts, [(2, true), (2, false), (2, true)]);
+
+    let mut files = RootFiles::new(walk(&big, usize::MAX, true), 0);
+    assert_eq!(files.by_ref().count(), 5);
+    assert!(!files.limited);
+
+    std::fs::remove_dir_all(&base).unwrap();
   }
 }
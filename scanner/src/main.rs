@@ -2,12 +2,22 @@ use anyhow::Context;
 
 fn main() -> anyhow::Result<()> {
   let args: Vec<String> = std::env::args().collect();
+  agent_core::paths::configure_from_args(&args)?;
 
   if args.iter().any(|a| a == "--version") {
     println!("{}", env!("CARGO_PKG_VERSION"));
     return Ok(());
   }
 
+  if let Some(i) = args.iter().position(|a| a == "--cancel") {
+    let id = args
+      .get(i + 1)
+      .ok_or_else(|| anyhow::anyhow!("expected: --cancel <scan-id>"))?;
+    scanner::request_cancel(id).context("cancel scan")?;
+    println!("Cancel requested for scan_id={id}");
+    return Ok(());
+  }
+
   let mode = scanner::ScanMode::from_args(&args)?;
   scanner::run(mode).context("scanner run")
 }
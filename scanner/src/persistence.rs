@@ -0,0 +1,548 @@
+use agent_core::types::{now_unix_ms, Evidence, Finding, Severity};
+#[cfg(windows)]
+use std::process::Command;
+
+// Registry persistence that survives normal remediation (Safe Mode boot, logon shell, process
+// launch/exit hooks). None of it leaves an executable in a Startup folder, so it is read directly
+// from the registry on every scan type.
+
+const SAFEBOOT_KEY: &str = "HKLM\\SYSTEM\\CurrentControlSet\\Control\\SafeBoot";
+const SERVICES_KEY: &str = "HKLM\\SYSTEM\\CurrentControlSet\\Services";
+const WINLOGON_SUBKEY: &str = "Software\\Microsoft\\Windows NT\\CurrentVersion\\Winlogon";
+const IFEO_KEYS: &[&str] = &[
+  "HKLM\\SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\Image File Execution Options",
+  "HKLM\\SOFTWARE\\WOW6432Node\\Microsoft\\Windows NT\\CurrentVersion\\Image File Execution Options",
+];
+const SILENT_PROCESS_EXIT_KEY: &str =
+  "HKLM\\SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\SilentProcessExit";
+
+// GlobalFlag bit that enables silent-process-exit monitoring (launches `MonitorProcess` whenever
+// the target exits).
+const FLG_MONITOR_SILENT_PROCESS_EXIT: u64 = 0x200;
+
+#[derive(Debug, Clone, Default)]
+struct RegKey {
+  path: String,
+  values: Vec<(String, String)>,
+}
+
+impl RegKey {
+  fn value(&self, name: &str) -> Option<&str> {
+    self
+      .values
+      .iter()
+      .find(|(n, _)| n.eq_ignore_ascii_case(name))
+      .map(|(_, v)| v.as_str())
+  }
+
+  fn leaf(&self) -> &str {
+    self.path.rsplit('\\').next().unwrap_or(&self.path)
+  }
+}
+
+/// Where Windows and programs live, for telling default values from replaced ones.
+#[derive(Debug, Clone)]
+struct Locations {
+  /// Lowercase `%SystemRoot%`.
+  system_root: String,
+  /// Lowercase `%SystemRoot%` and Program Files folders.
+  system_dirs: Vec<String>,
+}
+
+impl Locations {
+  fn current() -> Self {
+    let system_root = std::env::var("SystemRoot")
+      .unwrap_or_else(|_| "C:\\Windows".to_string())
+      .to_ascii_lowercase();
+    let mut system_dirs = vec![system_root.clone()];
+    for var in ["ProgramFiles", "ProgramFiles(x86)"] {
+      if let Ok(v) = std::env::var(var) {
+        system_dirs.push(v.to_ascii_lowercase());
+      }
+    }
+    Self {
+      system_root,
+      system_dirs,
+    }
+  }
+
+  fn is_default_shell(&self, shell: &str) -> bool {
+    let s = shell.trim().to_ascii_lowercase();
+    s == "explorer.exe" || s == format!("{}\\explorer.exe", self.system_root)
+  }
+
+  // Default is `C:\Windows\system32\userinit.exe,` (trailing comma included).
+  fn is_default_userinit(&self, userinit: &str) -> bool {
+    let expected = format!("{}\\system32\\userinit.exe", self.system_root);
+    userinit
+      .split(',')
+      .map(|e| e.trim().to_ascii_lowercase())
+      .filter(|e| !e.is_empty())
+      .all(|e| e == expected || e == "userinit.exe")
+  }
+
+  fn is_system_location(&self, path: &str) -> bool {
+    let p = path.to_ascii_lowercase();
+    self
+      .system_dirs
+      .iter()
+      .any(|r| !r.is_empty() && p.starts_with(&format!("{r}\\")))
+  }
+
+  // Service ImagePath forms: `"C:\x\svc.exe" -arg`, `C:\x\svc.exe -arg`, `\SystemRoot\...`,
+  // `System32\drivers\x.sys`, `%SystemRoot%\...`, `\??\C:\...`.
+  fn resolve_image_path(&self, raw: &str) -> String {
+    let raw = raw.trim();
+    let path = if let Some(rest) = raw.strip_prefix('"') {
+      rest.split('"').next().unwrap_or(rest).to_string()
+    } else {
+      let lower = raw.to_ascii_lowercase();
+      match [".exe", ".sys", ".dll"]
+        .iter()
+        .filter_map(|ext| lower.find(ext).map(|i| i + ext.len()))
+        .min()
+      {
+        Some(end) => raw[..end].to_string(),
+        None => raw.to_string(),
+      }
+    };
+
+    let root = &self.system_root;
+    let lower = path.to_ascii_lowercase();
+    if let Some(rest) = lower.strip_prefix("\\systemroot\\") {
+      return format!("{root}\\{rest}");
+    }
+    if let Some(rest) = lower.strip_prefix("%systemroot%\\") {
+      return format!("{root}\\{rest}");
+    }
+    if lower.starts_with("system32\\") {
+      return format!("{root}\\{lower}");
+    }
+    path.strip_prefix("\\??\\").unwrap_or(&path).to_string()
+  }
+}
+
+/// Reads a key (and with `true`, everything beneath it) as `reg query` lists it.
+type Query<'a> = &'a dyn Fn(&str, bool) -> Vec<RegKey>;
+
+/// S006 (SafeBoot), S007 (Winlogon), S008 (IFEO) findings.
+pub fn scan_persistence() -> Vec<Finding> {
+  let ts = now_unix_ms();
+  let loc = Locations::current();
+  let mut findings = Vec::new();
+  findings.extend(check_safeboot(&query, &loc, ts));
+  findings.extend(check_winlogon(&query, &loc, ts));
+  findings.extend(check_ifeo(&query, ts));
+  findings
+}
+
+fn check_safeboot(query: Query, loc: &Locations, ts: u64) -> Vec<Finding> {
+  let mut out = Vec::new();
+
+  if let Some(shell) = query(SAFEBOOT_KEY, false)
+    .first()
+    .and_then(|k| k.value("AlternateShell"))
+  {
+    if !shell.trim().eq_ignore_ascii_case("cmd.exe") {
+      out.push(finding(
+        "S006",
+        format!("Safe Mode alternate shell replaced: {shell}"),
+        format!("{SAFEBOOT_KEY}\\AlternateShell"),
+        shell,
+        ts,
+      ));
+    }
+  }
+
+  // Subkeys of Minimal/Network name services (or driver groups) allowed to start in Safe Mode.
+  // Malware registers its own service there so it survives a Safe Mode cleanup.
+  for mode in ["Minimal", "Network"] {
+    let key = format!("{SAFEBOOT_KEY}\\{mode}");
+    for entry in query(&key, false).iter().skip(1) {
+      let service = entry.leaf();
+      let Some(image) = query(&format!("{SERVICES_KEY}\\{service}"), false)
+        .first()
+        .and_then(|k| k.value("ImagePath"))
+        .map(|raw| loc.resolve_image_path(raw))
+      else {
+        continue;
+      };
+      if loc.is_system_location(&image) {
+        continue;
+      }
+      out.push(finding(
+        "S006",
+        format!("Service `{service}` registered to start in Safe Mode ({mode}) from {image}"),
+        entry.path.clone(),
+        &image,
+        ts,
+      ));
+    }
+  }
+  out
+}
+
+fn check_winlogon(query: Query, loc: &Locations, ts: u64) -> Vec<Finding> {
+  let mut out = Vec::new();
+  let machine_key = format!("HKLM\\{WINLOGON_SUBKEY}");
+
+  if let Some(k) = query(&machine_key, false).first() {
+    if let Some(shell) = k.value("Shell") {
+      if !loc.is_default_shell(shell) {
+        out.push(finding(
+          "S007",
+          format!("Winlogon shell is not Explorer: {shell}"),
+          format!("{machine_key}\\Shell"),
+          shell,
+          ts,
+        ));
+      }
+    }
+    if let Some(userinit) = k.value("Userinit") {
+      if !loc.is_default_userinit(userinit) {
+        out.push(finding(
+          "S007",
+          format!("Winlogon Userinit runs extra programs: {userinit}"),
+          format!("{machine_key}\\Userinit"),
+          userinit,
+          ts,
+        ));
+      }
+    }
+  }
+
+  // A per-user Shell value overrides the machine one at that user's logon.
+  for hive in user_hives(query) {
+    let key = format!("{hive}\\{WINLOGON_SUBKEY}");
+    if let Some(shell) = query(&key, false).first().and_then(|k| k.value("Shell")) {
+      if !loc.is_default_shell(shell) {
+        out.push(finding(
+          "S007",
+          format!("Per-user Winlogon shell override: {shell}"),
+          format!("{key}\\Shell"),
+          shell,
+          ts,
+        ));
+      }
+    }
+  }
+  out
+}
+
+fn check_ifeo(query: Query, ts: u64) -> Vec<Finding> {
+  let mut out = Vec::new();
+  let silent_exit = query(SILENT_PROCESS_EXIT_KEY, true);
+
+  for root in IFEO_KEYS {
+    for k in query(root, true).iter().skip(1) {
+      let image = k.leaf();
+
+      if let Some(debugger) = k.value("Debugger") {
+        out.push(finding(
+          "S008",
+          format!("Image File Execution Options debugger hijacks {image}: {debugger}"),
+          format!("{}\\Debugger", k.path),
+          debugger,
+          ts,
+        ));
+      }
+
+      let flags = k.value("GlobalFlag").and_then(parse_dword).unwrap_or(0);
+      if flags & FLG_MONITOR_SILENT_PROCESS_EXIT == 0 {
+        continue;
+      }
+      let monitor = silent_exit
+        .iter()
+        .find(|s| s.leaf().eq_ignore_ascii_case(image))
+        .and_then(|s| s.value("MonitorProcess"));
+      out.push(finding(
+        "S008",
+        match monitor {
+          Some(m) => format!("Silent process exit monitor on {image} launches {m}"),
+          None => format!("Silent process exit monitoring enabled on {image} (GlobalFlag)"),
+        },
+        format!("{}\\GlobalFlag", k.path),
+        &format!(
+          "GlobalFlag=0x{flags:x}{}",
+          monitor
+            .map(|m| format!(" MonitorProcess={m}"))
+            .unwrap_or_default()
+        ),
+        ts,
+      ));
+    }
+  }
+  out
+}
+
+fn finding(rule_id: &str, description: String, key_path: String, value: &str, ts: u64) -> Finding {
+  Finding {
+    rule_id: rule_id.to_string(),
+    severity: Severity::Yellow,
+    description,
+    evidence: vec![Evidence::Registry {
+      pid: 0,
+      image_path: None,
+      key_path,
+      value: Some(value.to_string()),
+    }],
+    timestamp_unix_ms: ts,
+  }
+}
+
+fn parse_dword(v: &str) -> Option<u64> {
+  let v = v.trim();
+  match v.strip_prefix("0x") {
+    Some(hex) => u64::from_str_radix(hex, 16).ok(),
+    None => v.parse().ok(),
+  }
+}
+
+fn user_hives(query: Query) -> Vec<String> {
+  query("HKU", false)
+    .iter()
+    .skip(1)
+    .map(|k| k.path.clone())
+    .filter(|p| !p.ends_with("_Classes"))
+    .collect()
+}
+
+// `reg query` prints each key path on its own line, followed by its values as
+// `    <name>    <REG_TYPE>    <data>`. Subkeys of a non-recursive query are listed as bare paths.
+fn parse_reg_query(stdout: &str) -> Vec<RegKey> {
+  let mut keys: Vec<RegKey> = Vec::new();
+  for line in stdout.lines() {
+    if line.starts_with("HKEY_") {
+      keys.push(RegKey {
+        path: line.trim().to_string(),
+        values: Vec::new(),
+      });
+      continue;
+    }
+    let mut parts = line.trim().splitn(3, "    ");
+    let (Some(name), Some(ty)) = (parts.next(), parts.next()) else {
+      continue;
+    };
+    if !ty.trim().starts_with("REG_") {
+      continue;
+    }
+    if let Some(k) = keys.last_mut() {
+      k.values.push((
+        name.trim().to_string(),
+        parts.next().unwrap_or("").trim().to_string(),
+      ));
+    }
+  }
+  keys
+}
+
+fn query(key: &str, recursive: bool) -> Vec<RegKey> {
+  reg_query(key, recursive)
+    .map(|s| parse_reg_query(&s))
+    .unwrap_or_default()
+}
+
+#[cfg(windows)]
+fn reg_query(key: &str, recursive: bool) -> Option<String> {
+  let mut cmd = Command::new("reg");
+  cmd.args(["query", key]);
+  if recursive {
+    cmd.arg("/s");
+  }
+  let output = cmd.output().ok()?;
+  output
+    .status
+    .success()
+    .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(not(windows))]
+fn reg_query(_key: &str, _recursive: bool) -> Option<String> {
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashMap;
+
+  fn locations() -> Locations {
+    Locations {
+      system_root: "c:\\windows".to_string(),
+      system_dirs: vec!["c:\\windows".to_string(), "c:\\program files".to_string()],
+    }
+  }
+
+  /// `reg query` output by queried key.
+  fn registry(outputs: &[(&str, &str)]) -> impl Fn(&str, bool) -> Vec<RegKey> {
+    let outputs: HashMap<String, String> = outputs
+      .iter()
+      .map(|(k, v)| (k.to_string(), v.to_string()))
+      .collect();
+    move |key, _| {
+      outputs
+        .get(key)
+        .map(|s| parse_reg_query(s))
+        .unwrap_or_default()
+    }
+  }
+
+  fn summary(findings: &[Finding]) -> Vec<(String, String)> {
+    findings
+      .iter()
+      .map(|f| (f.rule_id.clone(), f.description.clone()))
+      .collect()
+  }
+
+  #[test]
+  fn default_values_are_recognized() {
+    let loc = locations();
+    assert!(loc.is_default_shell(" Explorer.exe"));
+    assert!(loc.is_default_shell("C:\\Windows\\explorer.exe"));
+    assert!(!loc.is_default_shell("explorer.exe, C:\\Users\\Public\\evil.exe"));
+    assert!(loc.is_default_userinit("C:\\Windows\\system32\\userinit.exe,"));
+    assert!(loc.is_default_userinit("userinit.exe"));
+    assert!(!loc.is_default_userinit("C:\\Windows\\system32\\userinit.exe,C:\\x\\evil.exe"));
+    assert!(loc.is_system_location("C:\\Program Files\\Vendor\\svc.exe"));
+    assert!(!loc.is_system_location("C:\\Program Files Evil\\svc.exe"));
+    assert!(!loc.is_system_location("C:\\ProgramData\\svc.exe"));
+    assert_eq!(parse_dword("0x200"), Some(0x200));
+    assert_eq!(parse_dword("512"), Some(512));
+    assert_eq!(parse_dword("nope"), None);
+  }
+
+  #[test]
+  fn image_paths_resolve_to_files() {
+    let loc = locations();
+    for (raw, expected) in [
+      (
+        "\"C:\\Program Files\\Vendor\\svc.exe\" -k run",
+        "C:\\Program Files\\Vendor\\svc.exe",
+      ),
+      ("C:\\Tools\\svc.exe -service", "C:\\Tools\\svc.exe"),
+      (
+        "\\SystemRoot\\System32\\drivers\\x.sys",
+        "c:\\windows\\system32\\drivers\\x.sys",
+      ),
+      (
+        "System32\\drivers\\y.sys",
+        "c:\\windows\\system32\\drivers\\y.sys",
+      ),
+      (
+        "%SystemRoot%\\system32\\svchost.exe -k netsvcs",
+        "c:\\windows\\system32\\svchost.exe",
+      ),
+      ("\\??\\C:\\Drivers\\z.sys", "C:\\Drivers\\z.sys"),
+    ] {
+      assert_eq!(loc.resolve_image_path(raw), expected, "{raw}");
+    }
+  }
+
+  #[test]
+  fn safeboot_reports_replaced_shell_and_services_outside_system_folders() {
+    let reg = registry(&[
+      (
+        SAFEBOOT_KEY,
+        "\r\nHKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Control\\SafeBoot\r\n    AlternateShell    REG_SZ    C:\\Users\\Public\\shell.exe\r\n",
+      ),
+      (
+        &format!("{SAFEBOOT_KEY}\\Minimal"),
+        "\r\nHKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Control\\SafeBoot\\Minimal\r\nHKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Control\\SafeBoot\\Minimal\\EvilSvc\r\nHKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Control\\SafeBoot\\Minimal\\WinDefend\r\nHKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Control\\SafeBoot\\Minimal\\Base\r\n",
+      ),
+      (
+        &format!("{SERVICES_KEY}\\EvilSvc"),
+        "HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services\\EvilSvc\r\n    ImagePath    REG_EXPAND_SZ    \"C:\\ProgramData\\evil\\svc.exe\" -k\r\n",
+      ),
+      (
+        &format!("{SERVICES_KEY}\\WinDefend"),
+        "HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Services\\WinDefend\r\n    ImagePath    REG_EXPAND_SZ    \"C:\\Program Files\\Windows Defender\\MsMpEng.exe\"\r\n",
+      ),
+    ]);
+    let findings = check_safeboot(&reg, &locations(), 1);
+    assert_eq!(
+      summary(&findings),
+      [
+        (
+          "S006".to_string(),
+          "Safe Mode alternate shell replaced: C:\\Users\\Public\\shell.exe".to_string()
+        ),
+        (
+          "S006".to_string(),
+          "Service `EvilSvc` registered to start in Safe Mode (Minimal) from C:\\ProgramData\\evil\\svc.exe".to_string()
+        ),
+      ]
+    );
+
+    let clean = registry(&[(
+      SAFEBOOT_KEY,
+      "HKEY_LOCAL_MACHINE\\SYSTEM\\CurrentControlSet\\Control\\SafeBoot\r\n    AlternateShell    REG_SZ    cmd.exe\r\n",
+    )]);
+    assert!(check_safeboot(&clean, &locations(), 1).is_empty());
+  }
+
+  #[test]
+  fn winlogon_reports_non_default_machine_and_user_values() {
+    let reg = registry(&[
+      (
+        &format!("HKLM\\{WINLOGON_SUBKEY}"),
+        "HKEY_LOCAL_MACHINE\\Software\\Microsoft\\Windows NT\\CurrentVersion\\Winlogon\r\n    Shell    REG_SZ    explorer.exe\r\n    Userinit    REG_SZ    C:\\Windows\\system32\\userinit.exe,C:\\Temp\\x.exe,\r\n",
+      ),
+      (
+        "HKU",
+        "\r\nHKEY_USERS\\.DEFAULT\r\nHKEY_USERS\\S-1-5-21-1\r\nHKEY_USERS\\S-1-5-21-1_Classes\r\n",
+      ),
+      (
+        &format!("HKEY_USERS\\S-1-5-21-1\\{WINLOGON_SUBKEY}"),
+        "HKEY_USERS\\S-1-5-21-1\\Software\\Microsoft\\Windows NT\\CurrentVersion\\Winlogon\r\n    Shell    REG_SZ    C:\\Users\\a\\shell.exe\r\n",
+      ),
+    ]);
+    assert_eq!(
+      summary(&check_winlogon(&reg, &locations(), 1)),
+      [
+        (
+          "S007".to_string(),
+          "Winlogon Userinit runs extra programs: C:\\Windows\\system32\\userinit.exe,C:\\Temp\\x.exe,".to_string()
+        ),
+        (
+          "S007".to_string(),
+          "Per-user Winlogon shell override: C:\\Users\\a\\shell.exe".to_string()
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn ifeo_reports_debuggers_and_silent_exit_monitors() {
+    let reg = registry(&[
+      (
+        IFEO_KEYS[0],
+        "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\Image File Execution Options\r\n\r\nHKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\Image File Execution Options\\sethc.exe\r\n    Debugger    REG_SZ    cmd.exe\r\n\r\nHKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\Image File Execution Options\\notepad.exe\r\n    GlobalFlag    REG_DWORD    0x200\r\n\r\nHKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\Image File Execution Options\\calc.exe\r\n    GlobalFlag    REG_DWORD    0x2\r\n",
+      ),
+      (
+        SILENT_PROCESS_EXIT_KEY,
+        "HKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\SilentProcessExit\r\n\r\nHKEY_LOCAL_MACHINE\\SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\SilentProcessExit\\Notepad.exe\r\n    MonitorProcess    REG_SZ    C:\\Temp\\m.exe\r\n",
+      ),
+    ]);
+    let findings = check_ifeo(&reg, 1);
+    assert_eq!(
+      summary(&findings),
+      [
+        (
+          "S008".to_string(),
+          "Image File Execution Options debugger hijacks sethc.exe: cmd.exe".to_string()
+        ),
+        (
+          "S008".to_string(),
+          "Silent process exit monitor on notepad.exe launches C:\\Temp\\m.exe".to_string()
+        ),
+      ]
+    );
+    let Evidence::Registry { value, .. } = &findings[1].evidence[0] else {
+      panic!("expected registry evidence");
+    };
+    assert_eq!(
+      value.as_deref(),
+      Some("GlobalFlag=0x200 MonitorProcess=C:\\Temp\\m.exe")
+    );
+  }
+}
@@ -0,0 +1,174 @@
+use crate::{walk, WalkLimits};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+// Progress is reported on stdout as `key=value` lines so the tray UI (and anyone piping the
+// scanner) can parse them. `current` is always last since file names may contain spaces:
+//
+//   ESTIMATE total_files=<n> enumerate_ms=<ms>
+//   PROGRESS scanned=<n> findings=<n> total=<n> percent=<p> eta_seconds=<s> current=<name>
+//
+// `total`, `percent`, and `eta_seconds` are omitted when no estimate is available.
+//
+// With `--progress-json`, stdout carries NDJSON records instead (one object per line, logs go to
+// stderr), optionally copied to a named pipe (`--progress-pipe <name>`) the consumer has created:
+//
+//   {"type":"start","scan_id":..,"mode":..,"roots":[..]}
+//   {"type":"estimate","total_files":..,"enumerate_ms":..}
+//   {"type":"progress","scanned":..,"findings":..,"total":..,"percent":..,"eta_seconds":..,"current":..}
+//   {"type":"finding","finding":{..}}          (as each finding is made)
+//   {"type":"diff","change":"new"|"changed",..} (with `--diff`)
+//   {"type":"summary","outcome":..,"scanned":..,"findings":..,"incident_id":..}
+
+/// Files seen between progress lines.
+pub const PROGRESS_EVERY_FILES: u64 = 250;
+
+// Percent stays below 100 until the scan actually ends; files created during the scan can push
+// `scanned` past the pre-enumerated total.
+const MAX_RUNNING_PERCENT: f64 = 99.9;
+
+// Too little data for a meaningful rate; avoids wild ETAs in the first second.
+const MIN_ETA_ELAPSED: Duration = Duration::from_secs(2);
+
+pub struct Progress {
+  total_files: Option<u64>,
+  started: Instant,
+}
+
+impl Progress {
+  pub fn new(total_files: Option<u64>) -> Self {
+    Self {
+      total_files,
+      started: Instant::now(),
+    }
+  }
+
+  pub fn line(&self, scanned: u64, findings: usize, current: &str) -> String {
+    let mut line = format!("PROGRESS scanned={scanned} findings={findings}");
+    if let Some(total) = self.total_files {
+      let (percent, eta) = estimate(scanned, total, self.started.elapsed());
+      line.push_str(&format!(" total={total} percent={percent:.1}"));
+      if let Some(eta) = eta {
+        line.push_str(&format!(" eta_seconds={}", eta.as_secs()));
+      }
+    }
+    line.push_str(&format!(" current={current}"));
+    line
+  }
+
+  pub fn record(&self, scanned: u64, findings: usize, current: &str) -> Value {
+    let mut record = json!({
+      "type": "progress",
+      "scanned": scanned,
+      "findings": findings,
+      "current": current,
+    });
+    if let Some(total) = self.total_files {
+      let (percent, eta) = estimate(scanned, total, self.started.elapsed());
+      record["total"] = json!(total);
+      record["percent"] = json!((percent * 10.0).round() / 10.0);
+      if let Some(eta) = eta {
+        record["eta_seconds"] = json!(eta.as_secs());
+      }
+    }
+    record
+  }
+}
+
+/// Scan output: the text lines above, or NDJSON records with `--progress-json`.
+pub struct Reporter {
+  json: bool,
+  pipe: Option<fs::File>,
+}
+
+impl Reporter {
+  pub fn new(json: bool, pipe_name: Option<&str>) -> Self {
+    let pipe = pipe_name.filter(|_| json).and_then(|name| {
+      let path = if name.starts_with("\\\\") {
+        name.to_string()
+      } else {
+        format!("\\\\.\\pipe\\{name}")
+      };
+      match fs::OpenOptions::new().write(true).open(&path) {
+        Ok(f) => Some(f),
+        Err(e) => {
+          tracing::warn!(pipe = %path, error = %e, "unable to open progress pipe; stdout only");
+          None
+        }
+      }
+    });
+    Self { json, pipe }
+  }
+
+  pub fn is_json(&self) -> bool {
+    self.json
+  }
+
+  /// A text-mode line; dropped in JSON mode.
+  pub fn text(&self, line: &str) {
+    if !self.json {
+      println!("{line}");
+    }
+  }
+
+  /// A JSON-mode record; dropped in text mode.
+  pub fn record(&mut self, record: Value) {
+    if !self.json {
+      return;
+    }
+    let line = record.to_string();
+    println!("{line}");
+    if let Some(pipe) = &mut self.pipe {
+      if let Err(e) = writeln!(pipe, "{line}") {
+        tracing::warn!(error = %e, "progress pipe closed; stdout only");
+        self.pipe = None;
+      }
+    }
+  }
+}
+
+/// Counts files under `roots` without opening them. Returns `None` if `cancel` fires first.
+pub fn enumerate_total(
+  roots: &[PathBuf],
+  max_depth: usize,
+  limits: &WalkLimits,
+  cancel: impl Fn() -> bool,
+) -> Option<u64> {
+  let mut total = 0u64;
+  for root in roots {
+    if root.as_os_str().is_empty() || !root.exists() {
+      continue;
+    }
+    let mut root_files = 0u64;
+    for entry in walk(root, max_depth, limits.skip_reparse_points) {
+      if !entry.file_type().is_dir() {
+        root_files += 1;
+        if limits.max_files_per_root > 0 && root_files > limits.max_files_per_root {
+          break;
+        }
+        total += 1;
+        if total.is_multiple_of(10_000) && cancel() {
+          return None;
+        }
+      }
+    }
+  }
+  Some(total)
+}
+
+fn estimate(scanned: u64, total: u64, elapsed: Duration) -> (f64, Option<Duration>) {
+  if total == 0 {
+    return (MAX_RUNNING_PERCENT, None);
+  }
+  let percent = (scanned as f64 * 100.0 / total as f64).min(MAX_RUNNING_PERCENT);
+
+  if scanned == 0 || elapsed < MIN_ETA_ELAPSED {
+    return (percent, None);
+  }
+  let remaining = total.saturating_sub(scanned) as f64;
+  let rate = scanned as f64 / elapsed.as_secs_f64();
+  (percent, Some(Duration::from_secs_f64(remaining / rate)))
+}
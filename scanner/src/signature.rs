@@ -1,5 +1,3 @@
-use std::path::Path;
-
 #[cfg(windows)]
 pub fn set_low_priority() -> anyhow::Result<()> {
   use windows::Win32::System::Threading::{
@@ -21,85 +19,78 @@ pub fn set_low_priority() -> anyhow::Result<()> {
   Ok(())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(windows), allow(dead_code))]
+pub enum DriveKind {
+  Fixed,
+  Removable,
+  Network,
+  /// CD-ROM, RAM disk, or a type Windows can't tell.
+  Other,
+}
+
+impl DriveKind {
+  pub fn label(self) -> &'static str {
+    match self {
+      Self::Fixed => "fixed",
+      Self::Removable => "removable",
+      Self::Network => "network",
+      Self::Other => "other",
+    }
+  }
+}
+
+/// Drive letters in use, with their type. Only the type is queried; nothing on the drive is
+/// touched, so a disconnected network mapping doesn't block.
 #[cfg(windows)]
-pub fn fixed_drives() -> Vec<std::path::PathBuf> {
+pub fn drives() -> Vec<(std::path::PathBuf, DriveKind)> {
   use windows::core::PCWSTR;
   use windows::Win32::Storage::FileSystem::GetDriveTypeW;
+  const DRIVE_NO_ROOT_DIR: u32 = 1;
+  const DRIVE_REMOVABLE: u32 = 2;
+  const DRIVE_FIXED: u32 = 3;
+  const DRIVE_REMOTE: u32 = 4;
   let mut out = Vec::new();
-  const DRIVE_FIXED_U32: u32 = 3;
   for letter in b'C'..=b'Z' {
     let root = format!("{}:\\", letter as char);
     let wide: Vec<u16> = root.encode_utf16().chain([0]).collect();
     // SAFETY: `wide` is null-terminated.
-    let t = unsafe { GetDriveTypeW(PCWSTR(wide.as_ptr())) };
-    if t == DRIVE_FIXED_U32 {
-      out.push(std::path::PathBuf::from(root));
-    }
+    let kind = match unsafe { GetDriveTypeW(PCWSTR(wide.as_ptr())) } {
+      0 | DRIVE_NO_ROOT_DIR => continue,
+      DRIVE_REMOVABLE => DriveKind::Removable,
+      DRIVE_FIXED => DriveKind::Fixed,
+      DRIVE_REMOTE => DriveKind::Network,
+      _ => DriveKind::Other,
+    };
+    out.push((std::path::PathBuf::from(root), kind));
   }
   out
 }
 
 #[cfg(not(windows))]
-pub fn fixed_drives() -> Vec<std::path::PathBuf> {
-  vec![std::path::PathBuf::from("/")]
+pub fn drives() -> Vec<(std::path::PathBuf, DriveKind)> {
+  vec![(std::path::PathBuf::from("/"), DriveKind::Fixed)]
 }
 
+/// Junctions, symlinks and other reparse points, such as cloud-only placeholders (OneDrive) that
+/// would be downloaded when read.
 #[cfg(windows)]
-pub fn is_trusted_signed(path: &Path) -> anyhow::Result<bool> {
-  use std::ffi::OsStr;
-  use std::os::windows::ffi::OsStrExt;
-  use windows::core::GUID;
-  use windows::core::PWSTR;
-  use windows::Win32::Foundation::{ERROR_SUCCESS, HANDLE, HWND};
-  use windows::Win32::Security::WinTrust::{
-    WinVerifyTrust, WINTRUST_ACTION_GENERIC_VERIFY_V2, WINTRUST_DATA, WINTRUST_DATA_0,
-    WINTRUST_DATA_UICONTEXT, WINTRUST_FILE_INFO, WTD_CACHE_ONLY_URL_RETRIEVAL, WTD_CHOICE_FILE,
-    WTD_REVOKE_NONE, WTD_STATEACTION_CLOSE, WTD_STATEACTION_VERIFY, WTD_UI_NONE,
-  };
-
-  // SAFETY: WinVerifyTrust requires Win32 structs and pointers. We keep this isolated.
-  let wide: Vec<u16> = OsStr::new(path)
-    .encode_wide()
-    .chain(std::iter::once(0))
-    .collect();
-
-  let mut file_info = WINTRUST_FILE_INFO {
-    cbStruct: std::mem::size_of::<WINTRUST_FILE_INFO>() as u32,
-    pcwszFilePath: windows::core::PCWSTR(wide.as_ptr()),
-    hFile: HANDLE::default(),
-    pgKnownSubject: std::ptr::null_mut(),
-  };
-
-  let mut data = WINTRUST_DATA {
-    cbStruct: std::mem::size_of::<WINTRUST_DATA>() as u32,
-    pPolicyCallbackData: std::ptr::null_mut(),
-    pSIPClientData: std::ptr::null_mut(),
-    dwUIChoice: WTD_UI_NONE,
-    fdwRevocationChecks: WTD_REVOKE_NONE,
-    dwUnionChoice: WTD_CHOICE_FILE,
-    Anonymous: WINTRUST_DATA_0 {
-      pFile: &mut file_info,
-    },
-    dwStateAction: WTD_STATEACTION_VERIFY,
-    hWVTStateData: HANDLE::default(),
-    pwszURLReference: PWSTR::null(),
-    // Offline-first: do not allow URL retrieval during signature verification.
-    dwProvFlags: WTD_CACHE_ONLY_URL_RETRIEVAL,
-    dwUIContext: WINTRUST_DATA_UICONTEXT(0),
-    pSignatureSettings: std::ptr::null_mut(),
-  };
-
-  let mut action: GUID = WINTRUST_ACTION_GENERIC_VERIFY_V2;
-  let status = unsafe { WinVerifyTrust(HWND(0), &mut action, &mut data as *mut _ as *mut _) };
-
-  // Close state data if created.
-  data.dwStateAction = WTD_STATEACTION_CLOSE;
-  let _ = unsafe { WinVerifyTrust(HWND(0), &mut action, &mut data as *mut _ as *mut _) };
-
-  Ok(status == ERROR_SUCCESS.0 as i32)
+pub fn is_reparse_point(entry: &walkdir::DirEntry) -> bool {
+  use std::os::windows::fs::MetadataExt;
+  const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+  const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x0040_0000;
+  entry.path_is_symlink()
+    || entry.metadata().is_ok_and(|m| {
+      m.file_attributes() & (FILE_ATTRIBUTE_REPARSE_POINT | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS)
+        != 0
+    })
 }
 
 #[cfg(not(windows))]
-pub fn is_trusted_signed(_path: &Path) -> anyhow::Result<bool> {
-  Ok(false)
+pub fn is_reparse_point(entry: &walkdir::DirEntry) -> bool {
+  entry.path_is_symlink()
 }
+
+/// Whether `path` carries a trusted Authenticode signature, either embedded in the file or in a
+/// system catalog. Shared with the agent's publisher verification (`defender-common`).
+pub use defender_common::authenticode::is_trusted_signed;
@@ -0,0 +1,240 @@
+use defender_common::lnk;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// What runs at sign-in or on a schedule without being an executable in a Startup folder itself:
+// shortcuts (`.lnk`) in the Startup folders, and the `Exec` actions of scheduled tasks (the task
+// definitions under `%SystemRoot%\System32\Tasks`, readable when the scanner runs elevated). The
+// scanner applies its file checks to the program each one starts and to absolute paths on its
+// command line, so `powershell.exe -File C:\Users\x\AppData\Roaming\a.ps1` gets the script
+// checked, not just PowerShell.
+
+/// Shortcuts and task definitions are a few KB; anything much larger isn't one.
+const MAX_DEFINITION_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct StartupEntry {
+  /// The shortcut or task definition.
+  pub source: PathBuf,
+  pub kind: &'static str,
+  /// A program or script the entry starts, with environment variables expanded.
+  pub target: PathBuf,
+  pub arguments: Option<String>,
+}
+
+impl StartupEntry {
+  /// Evidence line naming what starts the target.
+  pub fn note(&self) -> String {
+    let mut note = format!(
+      "startup_entry kind={} source={}",
+      self.kind,
+      self.source.display()
+    );
+    if let Some(args) = &self.arguments {
+      note.push_str(&format!(" arguments={args}"));
+    }
+    note
+  }
+}
+
+/// Targets of the shortcuts in `startup_folders` and of scheduled tasks.
+pub fn entries(startup_folders: &[PathBuf]) -> Vec<StartupEntry> {
+  let mut out = Vec::new();
+  for folder in startup_folders {
+    for path in files(folder) {
+      let is_lnk = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("lnk"));
+      let Some(link) = is_lnk
+        .then(|| read(&path))
+        .flatten()
+        .and_then(|raw| lnk::parse(&raw))
+      else {
+        continue;
+      };
+      let dir = path.parent().unwrap_or(folder);
+      let target = expand_env(&link.path);
+      let target = if is_absolute(&target) {
+        PathBuf::from(target)
+      } else {
+        dir.join(target)
+      };
+      push_entry(&mut out, &path, "shortcut", target, link.arguments);
+    }
+  }
+
+  let tasks = PathBuf::from(std::env::var("SystemRoot").unwrap_or_else(|_| "C:\\Windows".into()))
+    .join("System32\\Tasks");
+  for path in files(&tasks) {
+    let Some(raw) = read(&path) else {
+      continue;
+    };
+    for (command, arguments) in task_exec_actions(&decode(&raw)) {
+      let command = expand_env(unquote(&command));
+      if is_absolute(&command) {
+        push_entry(
+          &mut out,
+          &path,
+          "scheduled_task",
+          PathBuf::from(command),
+          arguments,
+        );
+      }
+    }
+  }
+  out
+}
+
+/// The entry for `target`, and one for each absolute path among its arguments.
+fn push_entry(
+  out: &mut Vec<StartupEntry>,
+  source: &Path,
+  kind: &'static str,
+  target: PathBuf,
+  arguments: Option<String>,
+) {
+  let from_args: Vec<PathBuf> = arguments.as_deref().map(argument_paths).unwrap_or_default();
+  for target in std::iter::once(target).chain(from_args) {
+    out.push(StartupEntry {
+      source: source.to_path_buf(),
+      kind,
+      target,
+      arguments: arguments.clone(),
+    });
+  }
+}
+
+fn files(dir: &Path) -> impl Iterator<Item = PathBuf> {
+  WalkDir::new(dir)
+    .max_depth(8)
+    .into_iter()
+    .flatten()
+    .filter(|e| e.file_type().is_file())
+    .map(|e| e.into_path())
+}
+
+fn read(path: &Path) -> Option<Vec<u8>> {
+  use std::io::Read;
+  let mut raw = Vec::new();
+  std::fs::File::open(path)
+    .ok()?
+    .take(MAX_DEFINITION_BYTES)
+    .read_to_end(&mut raw)
+    .ok()?;
+  Some(raw)
+}
+
+/// Task definitions are UTF-16LE with a byte order mark; imported ones may be UTF-8.
+fn decode(raw: &[u8]) -> String {
+  match raw {
+    [0xFF, 0xFE, rest @ ..] => {
+      let units: Vec<u16> = rest
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+      String::from_utf16_lossy(&units)
+    }
+    [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+    _ => String::from_utf8_lossy(raw).into_owned(),
+  }
+}
+
+/// `(Command, Arguments)` of each `<Exec>` action in a task definition. COM handler actions have
+/// no command and are skipped.
+fn task_exec_actions(xml: &str) -> Vec<(String, Option<String>)> {
+  let mut out = Vec::new();
+  let mut rest = xml;
+  while let Some(start) = rest.find("<Exec>") {
+    let body = &rest[start + "<Exec>".len()..];
+    let end = body.find("</Exec>").unwrap_or(body.len());
+    let exec = &body[..end];
+    if let Some(command) = element_text(exec, "Command") {
+      out.push((command, element_text(exec, "Arguments")));
+    }
+    rest = &body[end..];
+  }
+  out
+}
+
+fn element_text(xml: &str, name: &str) -> Option<String> {
+  let open = format!("<{name}>");
+  let start = xml.find(&open)? + open.len();
+  let end = start + xml[start..].find(&format!("</{name}>"))?;
+  let text = xml[start..end]
+    .replace("&quot;", "\"")
+    .replace("&apos;", "'")
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&amp;", "&");
+  let text = text.trim();
+  (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Absolute paths among command-line arguments: quoted or plain tokens like `C:\x\a.ps1`, also as
+/// the value of `/switch:` or `-switch=` forms and before the `,Entry` of `rundll32` arguments.
+fn argument_paths(arguments: &str) -> Vec<PathBuf> {
+  tokens(arguments)
+    .into_iter()
+    .filter_map(|t| {
+      let t = expand_env(&t);
+      let start = t.find(":\\").and_then(|i| i.checked_sub(1))?;
+      let path = t[start..].split(',').next().unwrap_or_default();
+      is_absolute(path).then(|| PathBuf::from(path))
+    })
+    .collect()
+}
+
+/// Whitespace-separated tokens; double quotes group and are removed.
+fn tokens(s: &str) -> Vec<String> {
+  let mut out = Vec::new();
+  let mut cur = String::new();
+  let mut quoted = false;
+  for c in s.chars() {
+    match c {
+      '"' => quoted = !quoted,
+      c if c.is_whitespace() && !quoted => {
+        if !cur.is_empty() {
+          out.push(std::mem::take(&mut cur));
+        }
+      }
+      c => cur.push(c),
+    }
+  }
+  if !cur.is_empty() {
+    out.push(cur);
+  }
+  out
+}
+
+fn unquote(s: &str) -> &str {
+  let s = s.trim();
+  s.strip_prefix('"')
+    .map(|r| r.split('"').next().unwrap_or(r))
+    .unwrap_or(s)
+}
+
+/// `C:\...` (not UNC: a network path may not be reachable, or may hang the scan).
+fn is_absolute(s: &str) -> bool {
+  let b = s.as_bytes();
+  b.len() > 3 && b[0].is_ascii_alphabetic() && b[1] == b':' && b[2] == b'\\'
+}
+
+/// Replaces `%VAR%` with the variable's value; unknown variables are left as they are.
+fn expand_env(s: &str) -> String {
+  let mut out = String::new();
+  let mut rest = s;
+  while let Some(start) = rest.find('%') {
+    let Some(len) = rest[start + 1..].find('%') else {
+      break;
+    };
+    let name = &rest[start + 1..start + 1 + len];
+    out.push_str(&rest[..start]);
+    match std::env::var(name) {
+      Ok(value) if !name.is_empty() => out.push_str(&value),
+      _ => out.push_str(&rest[start..start + len + 2]),
+    }
+    rest = &rest[start + len + 2..];
+  }
+  out.push_str(rest);
+  out
+}
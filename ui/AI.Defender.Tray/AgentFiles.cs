@@ -13,6 +13,10 @@ internal static class AgentFiles
   public static string IncidentsDir => Path.Combine(BaseDir, "incidents");
   public static string ThreatFeedDir => Path.Combine(BaseDir, "threat-feed");
   public static string ThreatFeedStatePath => Path.Combine(ThreatFeedDir, "state.toml");
+  public static string IpcDir => Path.Combine(BaseDir, "ipc");
+  public static string IpcStatusPath => Path.Combine(IpcDir, "status.toml");
+  public static string IpcNotificationsDir => Path.Combine(IpcDir, "notifications");
+  public static string IpcRepliesDir => Path.Combine(IpcDir, "replies");
 
   public static AgentConfig? TryReadConfig()
   {
@@ -101,6 +105,81 @@ internal static class AgentFiles
     }
   }
 
+  public static IpcStatus? TryReadIpcStatus()
+  {
+    try
+    {
+      if (!File.Exists(IpcStatusPath))
+      {
+        return null;
+      }
+      var text = File.ReadAllText(IpcStatusPath);
+      return TomlMini.ParseIpcStatus(text);
+    }
+    catch
+    {
+      return null;
+    }
+  }
+
+  public static IReadOnlyList<AgentNotification> ReadPendingNotifications()
+  {
+    try
+    {
+      if (!Directory.Exists(IpcNotificationsDir))
+      {
+        return Array.Empty<AgentNotification>();
+      }
+      var now = (ulong)DateTimeOffset.UtcNow.ToUnixTimeMilliseconds();
+      return Directory
+        .EnumerateFiles(IpcNotificationsDir, "*.toml", SearchOption.TopDirectoryOnly)
+        .Select(p =>
+        {
+          try
+          {
+            return TomlMini.ParseNotification(File.ReadAllText(p));
+          }
+          catch
+          {
+            return null;
+          }
+        })
+        .OfType<AgentNotification>()
+        .Where(n => n.ExpiresAtUnixMs > now)
+        .OrderBy(n => n.CreatedAtUnixMs)
+        .ToArray();
+    }
+    catch
+    {
+      return Array.Empty<AgentNotification>();
+    }
+  }
+
+  // The agent consumes replies from the service side; the file only needs to land atomically.
+  public static bool TryWriteReply(string notificationId, bool contain, out string? error)
+  {
+    error = null;
+    try
+    {
+      Directory.CreateDirectory(IpcRepliesDir);
+      var path = Path.Combine(IpcRepliesDir, $"{notificationId}.toml");
+      var tmp = path + ".tmp";
+      var text =
+        $"id = \"{TomlMini.Escape(notificationId)}\"\n" +
+        $"decision = \"{(contain ? "contain" : "dismiss")}\"\n" +
+        $"user = \"{TomlMini.Escape(Environment.UserDomainName + "\\" + Environment.UserName)}\"\n" +
+        $"replied_at_unix_ms = {DateTimeOffset.UtcNow.ToUnixTimeMilliseconds()}\n";
+      File.WriteAllText(tmp, text);
+      File.Move(tmp, path, overwrite: true);
+      return true;
+    }
+    catch (Exception ex)
+    {
+      error = ex.Message;
+      return false;
+    }
+  }
+
   public static IncidentSummary? TryReadLastIncidentSummary()
   {
     try
@@ -154,6 +233,21 @@ internal sealed record IncidentSummary(
   IReadOnlyList<string> RuleIds,
   IReadOnlyList<string> ActionsTaken);
 
+internal sealed record IpcStatus(
+  ulong UpdatedAtUnixMs,
+  AgentMode Mode,
+  ulong PendingPrompts);
+
+internal sealed record AgentNotification(
+  string Id,
+  bool IsContainmentPrompt,
+  string IncidentId,
+  string Severity,
+  string Title,
+  string Message,
+  ulong CreatedAtUnixMs,
+  ulong ExpiresAtUnixMs);
+
 internal sealed record LicenseState(
   bool Pro,
   string? LicenseId,
@@ -172,4 +266,6 @@ internal sealed record ThreatFeedState(
   ulong? LastRefreshAttemptAtUnixMs,
   string? LastRefreshResult,
   ulong CheckedAtUnixMs,
-  string? Reason);
+  string? Reason,
+  ulong? MonthDownloadBytes,
+  string? Signer);
@@ -80,7 +80,11 @@ internal sealed class ScanProgressDialog : Form
     _cli.Process.OutputDataReceived += (_, e) =>
     {
       if (string.IsNullOrWhiteSpace(e.Data)) return;
-      BeginInvoke(() => Append(e.Data!));
+      BeginInvoke(() =>
+      {
+        UpdateProgress(e.Data!);
+        Append(e.Data!);
+      });
     };
     _cli.Process.ErrorDataReceived += (_, e) =>
     {
@@ -95,6 +99,56 @@ internal sealed class ScanProgressDialog : Form
     _cli.Process.BeginErrorReadLine();
   }
 
+  private void UpdateProgress(string line)
+  {
+    if (!line.StartsWith("PROGRESS ", StringComparison.Ordinal)) return;
+
+    var fields = ParseFields(line);
+    if (!fields.TryGetValue("percent", out var raw)
+      || !double.TryParse(raw, System.Globalization.NumberStyles.Float, System.Globalization.CultureInfo.InvariantCulture, out var percent))
+    {
+      return;
+    }
+
+    _bar.Style = ProgressBarStyle.Continuous;
+    _bar.Value = Math.Clamp((int)percent, 0, 100);
+
+    var title = _mode == ScanMode.Quick ? "AI Defender — Quick Scan" : "AI Defender — Full Scan";
+    if (fields.TryGetValue("eta_seconds", out var etaRaw) && long.TryParse(etaRaw, out var eta))
+    {
+      var remaining = TimeSpan.FromSeconds(eta);
+      Text = $"{title} — {percent:0}% (about {FormatRemaining(remaining)} left)";
+    }
+    else
+    {
+      Text = $"{title} — {percent:0}%";
+    }
+  }
+
+  private static Dictionary<string, string> ParseFields(string line)
+  {
+    // `current=` is last and may contain spaces; only the fields before it matter here.
+    var fields = new Dictionary<string, string>(StringComparer.Ordinal);
+    var cut = line.IndexOf(" current=", StringComparison.Ordinal);
+    if (cut >= 0) line = line[..cut];
+    foreach (var part in line.Split(' ', StringSplitOptions.RemoveEmptyEntries))
+    {
+      var eq = part.IndexOf('=');
+      if (eq > 0)
+      {
+        fields[part[..eq]] = part[(eq + 1)..];
+      }
+    }
+    return fields;
+  }
+
+  private static string FormatRemaining(TimeSpan t)
+  {
+    if (t.TotalHours >= 1) return $"{(int)t.TotalHours}h {t.Minutes}m";
+    if (t.TotalMinutes >= 1) return $"{t.Minutes}m {t.Seconds}s";
+    return $"{Math.Max(1, t.Seconds)}s";
+  }
+
   private void OnExited()
   {
     _bar.Style = ProgressBarStyle.Continuous;
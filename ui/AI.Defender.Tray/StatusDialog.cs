@@ -132,10 +132,18 @@ internal sealed class StatusDialog : Form
       lines.Add(
         $"Threat feed last refresh attempt: {TryFormatLocalTime(feed.LastRefreshAttemptAtUnixMs.Value) ?? $"unix_ms={feed.LastRefreshAttemptAtUnixMs.Value}"}");
     }
+    if (!string.IsNullOrWhiteSpace(feed?.Signer))
+    {
+      lines.Add($"Threat feed signed by: {feed.Signer}");
+    }
     if (!string.IsNullOrWhiteSpace(feed?.LastRefreshResult))
     {
       lines.Add($"Threat feed last refresh result: {feed.LastRefreshResult}");
     }
+    if (feed?.MonthDownloadBytes is > 0)
+    {
+      lines.Add($"Threat feed downloads this month: {feed.MonthDownloadBytes.Value / 1024.0:0.0} KB");
+    }
 
     if (serviceDetail is not null)
     {
@@ -191,6 +191,8 @@ internal static class TomlMini
     string? lastRefreshResult = null;
     ulong checkedAt = 0;
     string? reason = null;
+    ulong? monthBytes = null;
+    string? signer = null;
 
     foreach (var line in Lines(text))
     {
@@ -201,6 +203,12 @@ internal static class TomlMini
 
       switch (key)
       {
+        case "month_download_bytes":
+          monthBytes = ParseNullableU64(value);
+          break;
+        case "signer":
+          signer = ParseNullableString(value);
+          break;
         case "installed":
           installed = ParseBool(value);
           break;
@@ -253,7 +261,107 @@ internal static class TomlMini
       lastRefreshAttemptAt,
       lastRefreshResult,
       checkedAt,
-      reason);
+      reason,
+      monthBytes,
+      signer);
+  }
+
+  public static IpcStatus ParseIpcStatus(string text)
+  {
+    ulong updatedAt = 0;
+    var mode = AgentMode.Unknown;
+    ulong pendingPrompts = 0;
+
+    foreach (var line in Lines(text))
+    {
+      if (!TryKeyValue(line, out var key, out var value))
+      {
+        continue;
+      }
+
+      switch (key)
+      {
+        case "updated_at_unix_ms":
+          updatedAt = ParseU64(value);
+          break;
+        case "mode":
+          mode = ParseConfig(line).Mode;
+          break;
+        case "pending_prompts":
+          pendingPrompts = ParseU64(value);
+          break;
+      }
+    }
+
+    return new IpcStatus(updatedAt, mode, pendingPrompts);
+  }
+
+  public static AgentNotification? ParseNotification(string text)
+  {
+    string? id = null;
+    string? kind = null;
+    string? incidentId = null;
+    string? severity = null;
+    string? title = null;
+    string? message = null;
+    ulong createdAt = 0;
+    ulong expiresAt = 0;
+
+    foreach (var line in Lines(text))
+    {
+      if (!TryKeyValue(line, out var key, out var value))
+      {
+        continue;
+      }
+
+      switch (key)
+      {
+        case "id":
+          id = ParseNullableString(value);
+          break;
+        case "kind":
+          kind = ParseNullableString(value);
+          break;
+        case "incident_id":
+          incidentId = ParseNullableString(value);
+          break;
+        case "severity":
+          severity = ParseNullableString(value);
+          break;
+        case "title":
+          title = ParseNullableString(value);
+          break;
+        case "message":
+          message = ParseNullableString(value);
+          break;
+        case "created_at_unix_ms":
+          createdAt = ParseU64(value);
+          break;
+        case "expires_at_unix_ms":
+          expiresAt = ParseU64(value);
+          break;
+      }
+    }
+
+    if (string.IsNullOrWhiteSpace(id) || string.IsNullOrWhiteSpace(incidentId))
+    {
+      return null;
+    }
+
+    return new AgentNotification(
+      id,
+      kind == "containment_prompt",
+      incidentId,
+      severity ?? "unknown",
+      title ?? "AI Defender",
+      message ?? "",
+      createdAt,
+      expiresAt);
+  }
+
+  public static string Escape(string value)
+  {
+    return value.Replace("\\", "\\\\").Replace("\"", "\\\"");
   }
 
   public static IncidentSummary? ParseIncidentSummary(string text)
@@ -374,7 +482,12 @@ internal static class TomlMini
     {
       return null;
     }
-    return t.Trim('"');
+    // Literal strings ('...') are emitted for values containing quotes or backslashes.
+    if (t.Length >= 2 && t.StartsWith('\'') && t.EndsWith('\''))
+    {
+      return t[1..^1];
+    }
+    return t.Trim('"').Replace("\\\"", "\"").Replace("\\\\", "\\");
   }
 
   private static ulong? ParseNullableU64(string v)
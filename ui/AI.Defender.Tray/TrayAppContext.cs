@@ -20,6 +20,10 @@ internal sealed class TrayAppContext : ApplicationContext
 
   private AgentStatusSnapshot _snapshot = AgentStatusSnapshot.Empty;
 
+  // Notifications already shown in this session (the agent removes them on expiry or reply).
+  private readonly HashSet<string> _seenNotifications = new(StringComparer.OrdinalIgnoreCase);
+  private bool _promptOpen;
+
   public TrayAppContext()
   {
     _statusItem = new ToolStripMenuItem("Status");
@@ -62,10 +66,15 @@ internal sealed class TrayAppContext : ApplicationContext
     _notifyIcon.DoubleClick += (_, _) => ShowStatus();
 
     _timer = new System.Windows.Forms.Timer { Interval = 4_000 };
-    _timer.Tick += (_, _) => RefreshStatus();
+    _timer.Tick += (_, _) =>
+    {
+      RefreshStatus();
+      ProcessNotifications();
+    };
     _timer.Start();
 
     RefreshStatus();
+    ProcessNotifications();
   }
 
   protected override void Dispose(bool disposing)
@@ -89,14 +98,21 @@ internal sealed class TrayAppContext : ApplicationContext
       var cfg = AgentFiles.TryReadConfig();
       var kill = AgentFiles.TryReadKillSwitchState();
       var last = AgentFiles.TryReadLastIncidentSummary();
+      var ipc = AgentFiles.TryReadIpcStatus();
 
       var agentRunning = service == ServiceControllerStatus.Running;
       var serviceDetail = service is null ? "Service not found or not installed." : null;
 
+      // Prefer the mode the agent actually runs in (it refuses strict without active rules);
+      // fall back to config when the agent hasn't written a recent status.
+      var now = (ulong)DateTimeOffset.UtcNow.ToUnixTimeMilliseconds();
+      var ipcFresh = ipc is not null && ipc.Mode != AgentMode.Unknown && now - ipc.UpdatedAtUnixMs < 30_000;
+      var mode = ipcFresh ? ipc!.Mode : cfg?.Mode ?? AgentMode.Unknown;
+
       _snapshot = new AgentStatusSnapshot(
         AgentRunning: agentRunning,
         ServiceState: service,
-        Mode: cfg?.Mode ?? AgentMode.Unknown,
+        Mode: mode,
         KillSwitchEnabled: kill?.Enabled == true,
         LastIncidentSummary: last,
         ServiceDetail: serviceDetail);
@@ -120,6 +136,70 @@ internal sealed class TrayAppContext : ApplicationContext
     }
   }
 
+  private void ProcessNotifications()
+  {
+    if (_promptOpen)
+    {
+      return;
+    }
+
+    foreach (var n in AgentFiles.ReadPendingNotifications())
+    {
+      if (!_seenNotifications.Add(n.Id))
+      {
+        continue;
+      }
+
+      if (n.IsContainmentPrompt)
+      {
+        ShowContainmentPrompt(n);
+        // One modal prompt at a time; the rest are picked up on the next tick.
+        return;
+      }
+
+      var icon = n.Severity.Equals("red", StringComparison.OrdinalIgnoreCase) ? ToolTipIcon.Error : ToolTipIcon.Warning;
+      _notifyIcon.ShowBalloonTip(10_000, n.Title, n.Message, icon);
+    }
+  }
+
+  private void ShowContainmentPrompt(AgentNotification n)
+  {
+    _promptOpen = true;
+    try
+    {
+      var res = MessageBox.Show(
+        n.Message + "\n\nIncident: " + n.IncidentId,
+        n.Title,
+        MessageBoxButtons.YesNo,
+        MessageBoxIcon.Warning);
+
+      var now = (ulong)DateTimeOffset.UtcNow.ToUnixTimeMilliseconds();
+      if (now >= n.ExpiresAtUnixMs)
+      {
+        MessageBox.Show(
+          "This prompt has expired and no action was taken.",
+          "AI Defender",
+          MessageBoxButtons.OK,
+          MessageBoxIcon.Information);
+        return;
+      }
+
+      if (!AgentFiles.TryWriteReply(n.Id, contain: res == DialogResult.Yes, out var err))
+      {
+        MessageBox.Show(
+          "Unable to send your answer to the AI Defender agent.\n\n" + (err ?? "") +
+          "\n\nUse \"Enable Kill Switch NOW\" from the tray menu if you want to block networking.",
+          "AI Defender - Action Failed",
+          MessageBoxButtons.OK,
+          MessageBoxIcon.Error);
+      }
+    }
+    finally
+    {
+      _promptOpen = false;
+    }
+  }
+
   private static (string Tooltip, IconFactory.Badge Badge) ComputeTrayPresentation(AgentStatusSnapshot s)
   {
     if (IsServiceTransitioning(s.ServiceState))